@@ -1,18 +1,52 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+
+/// Whether a device plays audio or records it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Output,
+    Input,
+}
+
 /// A handle to an audio device.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Device {
     pub name: String,
+    pub direction: Direction,
+    /// Whether this is the device the system uses when none is picked.
+    pub is_default: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DeviceEntry {
     pub id: String,
     pub device: Device,
 }
 
+#[derive(Debug, Clone, Default)]
 pub struct DeviceHandler {
     pub devices: Vec<DeviceEntry>,
 }
 
 impl DeviceHandler {
+    /// Find the output and input devices of the default host, outputs first. Devices whose names can't be read are left out.
+    #[must_use]
+    pub fn audio() -> Self {
+        let host = cpal::default_host();
+        let mut handler = Self::default();
+        for direction in [Direction::Output, Direction::Input] {
+            let (default, devices) = match direction {
+                Direction::Output => (host.default_output_device(), host.output_devices().map(Iterator::collect::<Vec<_>>)),
+                Direction::Input => (host.default_input_device(), host.input_devices().map(Iterator::collect::<Vec<_>>)),
+            };
+            let default = default.and_then(|device| device.name().ok());
+            for name in devices.into_iter().flatten().filter_map(|device| device.name().ok()) {
+                let is_default = default.as_ref() == Some(&name);
+                handler.add_device(format!("{direction:?}/{name}"), Device { name, direction, is_default });
+            }
+        }
+        handler
+    }
+
     pub fn add_device(&mut self, id: String, device: Device) {
         self.devices.push(DeviceEntry { id, device });
     }
@@ -1,5 +1,6 @@
 #![warn(clippy::nursery, clippy::pedantic, clippy::undocumented_unsafe_blocks, clippy::allow_attributes_without_reason)]
 pub mod device;
+pub mod midi;
 pub mod processing;
 pub mod wavefile;
 
@@ -0,0 +1,436 @@
+use std::collections::{BTreeSet, HashMap};
+
+use thiserror::Error;
+
+/// A note of a MIDI file, timed in beats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub key: u8,
+    pub velocity: u8,
+    pub start: f64,
+    pub length: f64,
+    /// The channel the note is played on, counted from 0. MPE controllers play each note on a channel of its own, so that its [`Expression`] only
+    /// changes that note.
+    pub channel: u8,
+}
+
+/// How a note is played while it's held, which MPE (MIDI Polyphonic Expression) controllers send for every note on its own channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    /// How far the pitch is bent, in semitones.
+    PitchBend,
+    /// How hard the key is pressed, from 0 to 1.
+    Pressure,
+    /// Where the finger is along the key, from 0 at the bottom to 1 at the top, sent as controller 74.
+    Slide,
+}
+
+/// A change to the expression of the notes of a channel, timed in beats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Expression {
+    pub channel: u8,
+    pub start: f64,
+    pub dimension: Dimension,
+    pub value: f64,
+}
+
+/// A track of a MIDI file, with the notes of every channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Track {
+    /// The name the track is given in the file, if any.
+    pub name: Option<String>,
+    /// The notes, ordered by when they start.
+    pub notes: Vec<Note>,
+    /// How the notes are played while they're held, ordered by when it changes.
+    pub expression: Vec<Expression>,
+}
+
+/// A change to the tempo of a MIDI file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    /// When the tempo changes, in beats.
+    pub start: f64,
+    pub bpm: f64,
+}
+
+/// The notes of a Standard MIDI File, both merged and by track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiFile {
+    /// The notes of every track and channel, ordered by when they start.
+    pub notes: Vec<Note>,
+    /// The expression of every track and channel, ordered by when it changes.
+    pub expression: Vec<Expression>,
+    /// The tempo in BPM that the file starts at, or 120 if it doesn't say. Later tempo changes are in [`Self::tempos`].
+    pub tempo: f64,
+    /// How long the file is in beats, up to the end of its longest track.
+    pub length: f64,
+    /// The tracks that have notes, in the order of the file.
+    pub tracks: Vec<Track>,
+    /// Every tempo set by the file, ordered by when it's set.
+    pub tempos: Vec<TempoChange>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MidiError {
+    #[error("not a MIDI file")]
+    NotMidi,
+    #[error("SMPTE timing is not supported")]
+    SmpteTiming,
+    #[error("the file ends in the middle of a chunk")]
+    UnexpectedEnd,
+    #[error("an event has no status")]
+    MissingStatus,
+}
+
+/// How many ticks a beat is split into in the files written.
+const TICKS_PER_BEAT: u16 = 480;
+/// How far pitch bends go in semitones, unless a file says otherwise.
+const DEFAULT_BEND_RANGE: f64 = 2.;
+/// How far pitch bends go in semitones on the channels of notes of an MPE zone, unless a file says otherwise, and in the files written.
+pub const MPE_BEND_RANGE: f64 = 48.;
+/// The controller sending [`Dimension::Slide`].
+pub const SLIDE_CONTROLLER: u8 = 74;
+
+/// Reads the bytes of a chunk in order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], MidiError> {
+        let taken = self.bytes.get(self.position..self.position + count).ok_or(MidiError::UnexpectedEnd)?;
+        self.position += count;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, MidiError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Result<u8, MidiError> {
+        self.bytes.get(self.position).copied().ok_or(MidiError::UnexpectedEnd)
+    }
+
+    fn u16(&mut self) -> Result<u16, MidiError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, MidiError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a variable-length quantity, which stores 7 bits per byte with the high bit set on every byte but the last.
+    fn variable(&mut self) -> Result<u32, MidiError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(value)
+    }
+}
+
+impl MidiFile {
+    /// Read a Standard MIDI File of any format. Chunks other than tracks are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` aren't a complete MIDI file, or if the file is timed in SMPTE frames rather than beats.
+    pub fn read(bytes: &[u8]) -> Result<Self, MidiError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4).map_err(|_| MidiError::NotMidi)? != b"MThd" {
+            return Err(MidiError::NotMidi);
+        }
+        let header_length = reader.u32()? as usize;
+        let mut header = Reader::new(reader.take(header_length)?);
+        let _format = header.u16()?;
+        let _tracks = header.u16()?;
+        let division = header.u16()?;
+        if division & 0x8000 != 0 {
+            return Err(MidiError::SmpteTiming);
+        }
+        let ticks_per_beat = f64::from(division.max(1));
+        let mut file = Self {
+            notes: Vec::new(),
+            expression: Vec::new(),
+            tempo: 120.,
+            length: 0.,
+            tracks: Vec::new(),
+            tempos: Vec::new(),
+        };
+        while !reader.is_empty() {
+            let id = reader.take(4)?;
+            let length = reader.u32()? as usize;
+            let chunk = reader.take(length)?;
+            if id == b"MTrk" {
+                let mut track = Track::default();
+                let end = read_track(chunk, ticks_per_beat, &mut track, &mut file.tempos)?;
+                file.length = file.length.max(end);
+                if !track.notes.is_empty() {
+                    track.notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.key.cmp(&b.key)));
+                    track.expression.sort_by(|a, b| a.start.total_cmp(&b.start));
+                    file.notes.extend_from_slice(&track.notes);
+                    file.expression.extend_from_slice(&track.expression);
+                    file.tracks.push(track);
+                }
+            }
+        }
+        file.notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.key.cmp(&b.key)));
+        file.expression.sort_by(|a, b| a.start.total_cmp(&b.start));
+        file.tempos.sort_by(|a, b| a.start.total_cmp(&b.start));
+        file.tempo = file.tempos.first().map_or(file.tempo, |tempo| tempo.bpm);
+        Ok(file)
+    }
+
+    /// Write the file as a Standard MIDI File of format 1, with [`Self::tempos`] in the first track followed by [`Self::tracks`].
+    /// [`Self::notes`] and [`Self::expression`] are left out, as they are those of the tracks. If there are no tempo changes, [`Self::tempo`] is written
+    /// instead. Channels with pitch bends have their range set to [`MPE_BEND_RANGE`].
+    #[must_use]
+    pub fn write(&self) -> Vec<u8> {
+        let tracks = &self.tracks[..self.tracks.len().min(usize::from(u16::MAX) - 1)];
+        let mut bytes = b"MThd\0\0\0\x06\0\x01".to_vec();
+        bytes.extend(u16::try_from(tracks.len() + 1).unwrap_or(u16::MAX).to_be_bytes());
+        bytes.extend(TICKS_PER_BEAT.to_be_bytes());
+        let tempos = if self.tempos.is_empty() { vec![TempoChange { start: 0., bpm: self.tempo }] } else { self.tempos.clone() };
+        let tempo_events = tempos.iter().map(|tempo| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "tempos are positive and well within range")]
+            let microseconds = (60_000_000. / tempo.bpm.max(1.)).round() as u32;
+            let [_, a, b, c] = microseconds.to_be_bytes();
+            (ticks(tempo.start), vec![0xff, 0x51, 0x03, a, b, c])
+        });
+        write_track(&mut bytes, tempo_events.collect());
+        for track in tracks {
+            let name = track.name.iter().map(|name| {
+                let mut event = vec![0xff, 0x03];
+                write_variable(&mut event, u32::try_from(name.len()).unwrap_or(u32::MAX));
+                event.extend(name.as_bytes());
+                (0, event)
+            });
+            let bent = track.expression.iter().filter(|expression| expression.dimension == Dimension::PitchBend).map(|expression| expression.channel & 0x0f);
+            let ranges = bent.collect::<BTreeSet<_>>().into_iter().flat_map(|channel| {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the range is a small positive number of semitones")]
+                let range = MPE_BEND_RANGE as u8;
+                [(101, 0), (100, 0), (6, range), (38, 0)].map(|(controller, value)| (0, vec![0xb0 | channel, controller, value]))
+            });
+            // Notes are released before others start at the same time, so that repeated notes aren't cut short, and the expression of a note is
+            // set before it starts.
+            let releases = track.notes.iter().map(|note| (ticks(note.start + note.length), vec![0x80 | (note.channel & 0x0f), note.key, 0]));
+            let expression = track.expression.iter().map(|expression| (ticks(expression.start), write_expression(expression)));
+            let presses = track.notes.iter().map(|note| (ticks(note.start), vec![0x90 | (note.channel & 0x0f), note.key, note.velocity.max(1)]));
+            write_track(&mut bytes, name.chain(ranges).chain(releases).chain(expression).chain(presses).collect());
+        }
+        bytes
+    }
+
+    /// Return how long the file is in seconds, at its tempo.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        self.length * 60. / self.tempo
+    }
+}
+
+/// Return a position in beats in the ticks of the files written.
+fn ticks(beats: f64) -> u64 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+    let ticks = (beats.max(0.) * f64::from(TICKS_PER_BEAT)).round() as u64;
+    ticks
+}
+
+/// Return the bytes of the event setting `expression`, with pitch bends going up to [`MPE_BEND_RANGE`].
+fn write_expression(expression: &Expression) -> Vec<u8> {
+    let channel = expression.channel & 0x0f;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "values are clamped to their range first")]
+    let scale = |value: f64, maximum: f64| (value.clamp(0., 1.) * maximum).round() as u16;
+    match expression.dimension {
+        Dimension::PitchBend => {
+            let value = scale((expression.value / MPE_BEND_RANGE).mul_add(0.5, 0.5), 16383.);
+            let [high, low] = (value << 1).to_be_bytes();
+            vec![0xe0 | channel, low >> 1, high]
+        }
+        Dimension::Pressure => vec![0xd0 | channel, scale(expression.value, 127.).to_be_bytes()[1]],
+        Dimension::Slide => vec![0xb0 | channel, SLIDE_CONTROLLER, scale(expression.value, 127.).to_be_bytes()[1]],
+    }
+}
+
+/// Write a variable-length quantity, the opposite of [`Reader::variable`].
+fn write_variable(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![value.to_be_bytes()[3] & 0x7f];
+    let mut value = value >> 7;
+    while value > 0 {
+        groups.push(value.to_be_bytes()[3] | 0x80);
+        value >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+/// Write a track chunk of `events`, given as when they happen in ticks and their bytes. Events at the same time are written in the order given.
+fn write_track(bytes: &mut Vec<u8>, mut events: Vec<(u64, Vec<u8>)>) {
+    events.sort_by_key(|(ticks, _)| *ticks);
+    let mut chunk = Vec::new();
+    let mut last = 0;
+    for (ticks, event) in events {
+        write_variable(&mut chunk, u32::try_from(ticks - last).unwrap_or(u32::MAX));
+        chunk.extend(event);
+        last = ticks;
+    }
+    chunk.extend([0x00, 0xff, 0x2f, 0x00]);
+    bytes.extend(b"MTrk");
+    bytes.extend(u32::try_from(chunk.len()).unwrap_or(u32::MAX).to_be_bytes());
+    bytes.extend(chunk);
+}
+
+/// Add the notes, expression and name of the track in `chunk` to `track` and its tempo changes to `tempos`, and return where the track ends in beats.
+fn read_track(chunk: &[u8], ticks_per_beat: f64, track: &mut Track, tempos: &mut Vec<TempoChange>) -> Result<f64, MidiError> {
+    let notes = &mut track.notes;
+    let mut channels = Channels::default();
+    let mut reader = Reader::new(chunk);
+    let mut ticks = 0_u64;
+    let mut status = None;
+    // The notes being held by channel and key, with when they started and how hard they were hit. A key can be hit again before it's released.
+    let mut held = HashMap::<(u8, u8), Vec<(u64, u8)>>::new();
+    #[allow(clippy::cast_precision_loss, reason = "ticks are well within range")]
+    let beats = |ticks: u64| ticks as f64 / ticks_per_beat;
+    while !reader.is_empty() {
+        ticks += u64::from(reader.variable()?);
+        // Running status: the status of the last channel event is reused when the byte isn't a status.
+        let byte = if reader.peek()? & 0x80 == 0 { status.ok_or(MidiError::MissingStatus)? } else { reader.byte()? };
+        match byte {
+            0xff => {
+                let kind = reader.byte()?;
+                let length = reader.variable()? as usize;
+                let data = reader.take(length)?;
+                match (kind, data) {
+                    (0x2f, _) => break,
+                    (0x03, name) if track.name.is_none() => track.name = Some(String::from_utf8_lossy(name).into_owned()),
+                    (0x51, &[a, b, c]) => {
+                        let microseconds = u32::from_be_bytes([0, a, b, c]).max(1);
+                        tempos.push(TempoChange { start: beats(ticks), bpm: 60_000_000. / f64::from(microseconds) });
+                    }
+                    _ => {}
+                }
+            }
+            0xf0 | 0xf7 => {
+                let length = reader.variable()? as usize;
+                reader.take(length)?;
+            }
+            _ => {
+                status = Some(byte);
+                let channel = byte & 0x0f;
+                let expression = |dimension, value| Expression { channel, start: beats(ticks), dimension, value };
+                match byte & 0xf0 {
+                    0x80 | 0x90 => {
+                        let (key, velocity) = (reader.byte()?, reader.byte()?);
+                        if byte & 0xf0 == 0x90 && velocity > 0 {
+                            held.entry((channel, key)).or_default().push((ticks, velocity));
+                        } else if let Some((start, velocity)) = held.get_mut(&(channel, key)).filter(|starts| !starts.is_empty()).map(|starts| starts.remove(0)) {
+                            notes.push(Note { key, velocity, start: beats(start), length: beats(ticks - start), channel });
+                        }
+                    }
+                    0xb0 => {
+                        let (controller, value) = (reader.byte()?, reader.byte()?);
+                        if controller == SLIDE_CONTROLLER {
+                            track.expression.push(expression(Dimension::Slide, f64::from(value) / 127.));
+                        } else {
+                            channels.control_change(channel, controller, value);
+                        }
+                    }
+                    0xd0 => track.expression.push(expression(Dimension::Pressure, f64::from(reader.byte()?) / 127.)),
+                    0xe0 => {
+                        let (low, high) = (reader.byte()?, reader.byte()?);
+                        track.expression.push(expression(Dimension::PitchBend, channels.bend(channel, low, high)));
+                    }
+                    0xc0 => {
+                        reader.byte()?;
+                    }
+                    _ => {
+                        reader.take(2)?;
+                    }
+                }
+            }
+        }
+    }
+    // Notes that are never released last until the end of the track.
+    for ((channel, key), starts) in held {
+        for (start, velocity) in starts {
+            notes.push(Note { key, velocity, start: beats(start), length: beats(ticks - start), channel });
+        }
+    }
+    Ok(beats(ticks))
+}
+
+/// What a track has set up on a channel through controllers.
+#[derive(Clone, Copy)]
+struct Channel {
+    /// The registered parameter that data entry sets, as its most and least significant bytes.
+    parameter: (Option<u8>, Option<u8>),
+    /// How far pitch bends go in semitones.
+    bend_range: f64,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            parameter: (None, None),
+            bend_range: DEFAULT_BEND_RANGE,
+        }
+    }
+}
+
+/// What has been set up on every channel through controllers, which sets how far their pitch bends go. Files set it up as they're read, and
+/// controllers played live as they're played.
+#[derive(Clone, Copy, Default)]
+pub struct Channels([Channel; 16]);
+
+impl Channels {
+    /// Apply the control change of `controller` to `value` on `channel`, which can set the pitch bend range of the channel or, through the MPE
+    /// configuration message, of the channels of an MPE zone.
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) {
+        control_change(&mut self.0, channel & 0x0f, controller, value);
+    }
+
+    /// Return how far the pitch bend of `channel` sent as its `low` and `high` bytes bends, in semitones.
+    #[must_use]
+    pub fn bend(&self, channel: u8, low: u8, high: u8) -> f64 {
+        let bend = f64::from(u16::from(high & 0x7f) << 7 | u16::from(low & 0x7f)) / 8192. - 1.;
+        bend * self.0[usize::from(channel & 0x0f)].bend_range
+    }
+}
+
+/// Apply the control change of `controller` to `value` on `channel` of `channels`, see [`Channels::control_change`].
+fn control_change(channels: &mut [Channel; 16], channel: u8, controller: u8, value: u8) {
+    let index = usize::from(channel);
+    match controller {
+        101 => channels[index].parameter.0 = Some(value),
+        100 => channels[index].parameter.1 = Some(value),
+        // Data entry sets the registered parameter chosen before it.
+        6 => match channels[index].parameter {
+            (Some(0), Some(0)) => channels[index].bend_range = f64::from(value),
+            // The lower zone is set up on the first channel and uses the channels after it for its notes, while the upper zone is set up on the last.
+            (Some(0), Some(6)) => {
+                let count = usize::from(value).min(15);
+                let members = if index == 0 { 1..count + 1 } else if index == 15 { 15 - count..15 } else { 0..0 };
+                for member in &mut channels[members] {
+                    member.bend_range = MPE_BEND_RANGE;
+                }
+            }
+            _ => {}
+        },
+        // Fine data entry adds cents to the pitch bend range.
+        38 if channels[index].parameter == (Some(0), Some(0)) => channels[index].bend_range = channels[index].bend_range.trunc() + f64::from(value) / 100.,
+        _ => {}
+    }
+}
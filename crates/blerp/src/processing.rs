@@ -1,4 +1,18 @@
+pub mod analysis;
+pub mod bridge;
+pub mod drum_rack;
 pub mod effects;
 pub mod export;
 pub mod generation;
+pub mod graph;
+pub mod instrument;
 pub mod live;
+pub mod loudness;
+pub mod overview;
+pub mod pitch;
+pub mod registry;
+pub mod resample;
+pub mod spectrum;
+pub mod stream;
+pub mod stretch;
+pub mod synth;
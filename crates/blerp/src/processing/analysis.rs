@@ -0,0 +1,220 @@
+//! Estimating the tempo and key of audio, to describe samples in a library, and finding where its hits start, to slice it.
+
+use std::{f64::consts::TAU, fmt};
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use super::resample;
+
+/// The sample rate audio is analysed at, which keeps the frequencies that matter for rhythm and harmony while making analysis quick.
+const SAMPLE_RATE: f64 = 11025.;
+/// The length of the frames the spectrum is measured over, in samples at [`SAMPLE_RATE`].
+const FRAME_LENGTH: usize = 2048;
+/// How far apart frames start, in samples at [`SAMPLE_RATE`].
+const HOP: usize = 256;
+/// The length of the frames and how far apart they start when finding onsets, which are shorter than for the tempo to tell apart hits close together.
+const ONSET_FRAME_LENGTH: usize = 512;
+const ONSET_HOP: usize = 64;
+/// The shortest time between two onsets, in seconds, so a hit with a rough attack is found once.
+const ONSET_GAP: f64 = 0.05;
+/// How long before the loudest sample of a hit it's searched for, in seconds.
+const ONSET_SEARCH: f64 = 0.03;
+/// How long before its first loud sample an onset is put, in seconds, to keep the very start of the hit.
+const ONSET_LEAD: f64 = 0.001;
+/// The range of tempos estimated, in BPM. A tempo out of range is taken as its double or half.
+const TEMPO_RANGE: (f64, f64) = (70., 180.);
+/// The lowest and highest MIDI keys whose energy counts toward the pitch classes.
+const PITCH_RANGE: (u8, u8) = (36, 96);
+
+/// The Krumhansl-Kessler profiles of how strongly each pitch class, from the tonic up, belongs to a major and to a minor key.
+const MAJOR_PROFILE: [f64; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f64; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// A musical key, like C major or F# minor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// The pitch class of the tonic, from 0 for C to 11 for B.
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", NOTE_NAMES[usize::from(self.tonic % 12)], if self.minor { "m" } else { "" })
+    }
+}
+
+impl Key {
+    /// Return every key, majors first.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [false, true].into_iter().flat_map(|minor| (0..12).map(move |tonic| Self { tonic, minor }))
+    }
+}
+
+/// What analysis found out about some audio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Analysis {
+    /// The tempo in BPM, or [`None`] if there's no steady rhythm.
+    pub tempo: Option<f64>,
+    /// The key, or [`None`] if nothing is pitched.
+    pub key: Option<Key>,
+}
+
+/// Estimate the tempo and key of interleaved `samples` with `channels` channels.
+#[must_use]
+pub fn analyse(samples: &[f64], channels: usize, sample_rate: f64) -> Analysis {
+    let mono = resample::convert(samples, channels.max(1), sample_rate, 1, SAMPLE_RATE);
+    let spectra = spectra(&mono, FRAME_LENGTH, HOP);
+    Analysis {
+        tempo: estimate_tempo(&onset_strength(&spectra)),
+        key: estimate_key(&spectra),
+    }
+}
+
+/// Return where hits start in interleaved `samples` with `channels` channels, in seconds from the start, like the slicer of a sampler.
+///
+/// Onsets are found where the spectrum gets louder much more than it has around, and `sensitivity`, from 0 to 1, lowers how much more that has to
+/// be, so higher sensitivities also find softer hits. Each onset is then moved to just before the first loud sample of its hit.
+#[must_use]
+pub fn onsets(samples: &[f64], channels: usize, sample_rate: f64, sensitivity: f64) -> Vec<f64> {
+    let channels = channels.max(1);
+    let mono = samples.chunks(channels).map(|frame| frame.iter().sum::<f64>()).collect::<Vec<_>>();
+    // Silence before the start lets a hit right at the start be an onset too.
+    let mut resampled = vec![0.; ONSET_FRAME_LENGTH];
+    resampled.extend(resample::convert(&mono, 1, sample_rate, 1, SAMPLE_RATE));
+    let strengths = onset_strength(&spectra(&resampled, ONSET_FRAME_LENGTH, ONSET_HOP));
+    let loudest = strengths.iter().copied().fold(0., f64::max);
+    if loudest <= f64::EPSILON {
+        return Vec::new();
+    }
+    let threshold = 0.45f64.mul_add(1. - sensitivity.clamp(0., 1.), 0.05) * loudest;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "the gap and hop are short")]
+    let neighbours = (ONSET_GAP * SAMPLE_RATE / ONSET_HOP as f64).ceil() as usize;
+    let mut onsets: Vec<f64> = Vec::new();
+    for (frame, strength) in strengths.iter().enumerate() {
+        let around = &strengths[frame.saturating_sub(neighbours)..(frame + neighbours + 1).min(strengths.len())];
+        #[allow(clippy::cast_precision_loss, reason = "there are few neighbours")]
+        let mean = around.iter().sum::<f64>() / around.len() as f64;
+        if *strength < threshold + mean || around.iter().any(|other| other > strength) {
+            continue;
+        }
+        // The strength compares the frame after it with the frame itself, which was padded, and a hit counts most at the middle of a frame.
+        #[allow(clippy::cast_precision_loss, reason = "frames are well within range")]
+        let rough = ((frame + 1) * ONSET_HOP) as f64 / SAMPLE_RATE - (ONSET_FRAME_LENGTH / 2) as f64 / SAMPLE_RATE;
+        let onset = refine(&mono, sample_rate, rough, onsets.last().copied());
+        if onsets.last().is_none_or(|last| onset - last >= ONSET_GAP) {
+            onsets.push(onset);
+        }
+    }
+    onsets
+}
+
+/// Return where the hit found around `rough` seconds into `mono` starts, which is just before its first sample at least half as loud as its loudest
+/// one, and after the `previous` onset.
+fn refine(mono: &[f64], sample_rate: f64, rough: f64, previous: Option<f64>) -> f64 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "times are positive and well within range")]
+    let index = |seconds: f64| ((seconds.max(0.) * sample_rate) as usize).min(mono.len());
+    let start = index((rough - ONSET_SEARCH).max(previous.map_or(0., |previous| previous + ONSET_GAP)));
+    let end = index(rough + ONSET_SEARCH).max(start);
+    let range = &mono[start..end];
+    let peak = range.iter().fold(0., |peak: f64, sample| peak.max(sample.abs()));
+    let first = range.iter().position(|sample| sample.abs() >= peak / 2.).unwrap_or(0);
+    #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+    ((start + first) as f64 / sample_rate - ONSET_LEAD).max(0.)
+}
+
+/// Return the magnitude spectrum of every frame of `samples` of `frame_length` samples, starting `hop` samples apart, taken at [`SAMPLE_RATE`].
+fn spectra(samples: &[f64], frame_length: usize, hop: usize) -> Vec<Vec<f64>> {
+    if samples.len() < frame_length {
+        return Vec::new();
+    }
+    let fft = FftPlanner::new().plan_fft_forward(frame_length);
+    #[allow(clippy::cast_precision_loss, reason = "the frame is short")]
+    let window = (0..frame_length).map(|index| 0.5f64.mul_add(-(TAU * index as f64 / frame_length as f64).cos(), 0.5)).collect::<Vec<_>>();
+    (0..=(samples.len() - frame_length) / hop)
+        .map(|frame| {
+            let mut buffer = samples[frame * hop..frame * hop + frame_length].iter().zip(&window).map(|(sample, weight)| Complex::new(sample * weight, 0.)).collect::<Vec<_>>();
+            fft.process(&mut buffer);
+            buffer[..frame_length / 2].iter().map(|bin| bin.norm()).collect()
+        })
+        .collect()
+}
+
+/// Return how strongly something starts in each frame, as the increase in loudness across the spectrum since the frame before.
+fn onset_strength(spectra: &[Vec<f64>]) -> Vec<f64> {
+    spectra
+        .windows(2)
+        .map(|pair| pair[1].iter().zip(&pair[0]).map(|(now, before)| (now.ln_1p() - before.ln_1p()).max(0.)).sum())
+        .collect()
+}
+
+/// Estimate the tempo of frames with the `onsets` strengths, from how similar the onsets are to themselves delayed by the length of a beat.
+fn estimate_tempo(onsets: &[f64]) -> Option<f64> {
+    #[allow(clippy::cast_precision_loss, reason = "the hop is short")]
+    let frame_rate = SAMPLE_RATE / HOP as f64;
+    #[allow(clippy::cast_precision_loss, reason = "there are few frames")]
+    let mean = onsets.iter().sum::<f64>() / onsets.len().max(1) as f64;
+    let centered = onsets.iter().map(|onset| onset - mean).collect::<Vec<_>>();
+    let energy = centered.iter().map(|onset| onset * onset).sum::<f64>();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lags are short")]
+    let lags = (60. * frame_rate / TEMPO_RANGE.1).floor() as usize..=(60. * frame_rate / TEMPO_RANGE.0).ceil() as usize;
+    // At least a few beats are needed to find a rhythm.
+    if energy <= f64::EPSILON || centered.len() < lags.end() * 4 {
+        return None;
+    }
+    let correlation = |lag: usize| centered.iter().zip(&centered[lag..]).map(|(a, b)| a * b).sum::<f64>() / energy;
+    // A beat also lines up with two beats later, which helps to pick the beat over the half beat.
+    let score = |lag: usize| if lag * 2 < centered.len() { 0.5f64.mul_add(correlation(lag * 2).max(0.), correlation(lag)) } else { correlation(lag) };
+    let (lag, score) = lags.map(|lag| (lag, score(lag))).max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if score <= 0.1 {
+        return None;
+    }
+    // Interpolating between the lags around the best one gives a tempo between the ones the frame rate can tell apart.
+    let (before, after) = (correlation(lag - 1), correlation(lag + 1));
+    let peak = correlation(lag);
+    let curvature = 2f64.mul_add(-peak, before) + after;
+    let offset = if curvature < 0. { (0.5 * (before - after) / curvature).clamp(-0.5, 0.5) } else { 0. };
+    #[allow(clippy::cast_precision_loss, reason = "lags are short")]
+    Some(60. * frame_rate / (lag as f64 + offset))
+}
+
+/// Estimate the key from the energy of each pitch class in `spectra`, by comparing it with the profile of every key.
+fn estimate_key(spectra: &[Vec<f64>]) -> Option<Key> {
+    let mut chroma = [0.; 12];
+    #[allow(clippy::cast_precision_loss, reason = "the frame is short")]
+    let bin_width = SAMPLE_RATE / FRAME_LENGTH as f64;
+    for spectrum in spectra {
+        for (bin, magnitude) in spectrum.iter().enumerate().skip(1) {
+            #[allow(clippy::cast_precision_loss, reason = "bins are few")]
+            let key = 12f64.mul_add(((bin as f64 * bin_width) / 440.).log2(), 69.).round();
+            if (f64::from(PITCH_RANGE.0)..=f64::from(PITCH_RANGE.1)).contains(&key) {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the key is in range")]
+                let class = key as usize % 12;
+                chroma[class] += magnitude * magnitude;
+            }
+        }
+    }
+    let total = chroma.iter().sum::<f64>();
+    if total <= f64::EPSILON {
+        return None;
+    }
+    Key::all()
+        .map(|key| {
+            let profile = if key.minor { &MINOR_PROFILE } else { &MAJOR_PROFILE };
+            let rotated = (0..12).map(|class| profile[(class + 12 - usize::from(key.tonic)) % 12]).collect::<Vec<_>>();
+            (key, correlation(&chroma, &rotated))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(key, _)| key)
+}
+
+/// Return the Pearson correlation of `a` and `b`.
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    #[allow(clippy::cast_precision_loss, reason = "the slices are short")]
+    let (mean_a, mean_b) = (a.iter().sum::<f64>() / a.len() as f64, b.iter().sum::<f64>() / b.len() as f64);
+    let covariance = a.iter().zip(b).map(|(a, b)| (a - mean_a) * (b - mean_b)).sum::<f64>();
+    let spread = a.iter().map(|a| (a - mean_a).powi(2)).sum::<f64>() * b.iter().map(|b| (b - mean_b).powi(2)).sum::<f64>();
+    covariance / spread.sqrt().max(f64::EPSILON)
+}
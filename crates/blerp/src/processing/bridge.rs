@@ -0,0 +1,217 @@
+//! Passing values between the UI and the audio thread without locks, so that the audio thread never waits on another thread nor allocates.
+//!
+//! Commands go through a [`ring`] of a fixed size, one per thread sending them. What the audio thread is done with, like a schedule it replaced, is sent
+//! back through another ring so that it's dropped elsewhere. The latest state of playback goes the other way through a [`triple`] buffer, which the UI
+//! reads the newest of whenever it draws.
+
+use std::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Values waiting to be received, in slots that are each only touched by one end at a time.
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// How many values were received so far, which only the consumer moves.
+    head: AtomicUsize,
+    /// How many values were sent so far, which only the producer moves.
+    tail: AtomicUsize,
+}
+
+// SAFETY: values are moved from the producer's thread to the consumer's, and each slot is only accessed by the end that owns it as of `head` and `tail`.
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        for index in head..tail {
+            // SAFETY: the slots between `head` and `tail` were written and not read.
+            unsafe { self.slots[index % self.slots.len()].get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// The sending end of a [`ring`], which can be moved to another thread but not shared.
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+    _unsync: PhantomData<Cell<()>>,
+}
+
+/// The receiving end of a [`ring`], which can be moved to another thread but not shared.
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+    _unsync: PhantomData<Cell<()>>,
+}
+
+/// Return both ends of a queue holding up to `capacity` values at once, which neither waits nor allocates once it's made.
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+#[must_use]
+pub fn ring<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "a ring has to hold at least one value");
+    let ring = Arc::new(Ring {
+        slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer { ring: Arc::clone(&ring), _unsync: PhantomData },
+        Consumer { ring, _unsync: PhantomData },
+    )
+}
+
+impl<T> Producer<T> {
+    /// Send `value`, or give it back if the ring is full.
+    ///
+    /// # Errors
+    /// Returns `value` if the consumer didn't make room for it yet.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let ring = &*self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        if tail - ring.head.load(Ordering::Acquire) == ring.slots.len() {
+            return Err(value);
+        }
+        // SAFETY: the slot at `tail` was read already or never written, so the consumer won't touch it until `tail` is moved past it.
+        unsafe { (*ring.slots[tail % ring.slots.len()].get()).write(value) };
+        ring.tail.store(tail + 1, Ordering::Release);
+        Ok(())
+    }
+
+    /// How many values can be sent before the ring is full.
+    #[must_use]
+    pub fn free(&self) -> usize {
+        self.ring.slots.len() - (self.ring.tail.load(Ordering::Relaxed) - self.ring.head.load(Ordering::Acquire))
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Receive the oldest value sent, or [`None`] if there is none waiting.
+    #[must_use]
+    pub fn pop(&self) -> Option<T> {
+        let ring = &*self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head == ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: the slot at `head` was written before `tail` was moved past it, and the producer won't touch it until `head` is moved past it.
+        let value = unsafe { (*ring.slots[head % ring.slots.len()].get()).assume_init_read() };
+        ring.head.store(head + 1, Ordering::Release);
+        Some(value)
+    }
+
+    /// How many values are waiting to be received.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ring.tail.load(Ordering::Acquire) - self.ring.head.load(Ordering::Relaxed)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Receive every value waiting, oldest first.
+    pub fn drain(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.pop())
+    }
+}
+
+/// Three copies of a value: the one being written, the one being read and the one in between, which the writer swaps with its own when it
+/// publishes it and the reader with its own when there's a newer one.
+struct Triple<T> {
+    buffers: [UnsafeCell<T>; 3],
+    /// The index of the copy in between, along with [`NEWER`] if it was published since the reader last took it.
+    middle: AtomicUsize,
+}
+
+/// Set in [`Triple::middle`] when the copy in between is newer than the reader's.
+const NEWER: usize = 4;
+
+// SAFETY: each copy is only accessed by the end whose index it is, and copies are only handed over through `middle`.
+unsafe impl<T: Send> Sync for Triple<T> {}
+
+/// The writing end of a [`triple`] buffer, which can be moved to another thread but not shared.
+pub struct Writer<T> {
+    triple: Arc<Triple<T>>,
+    index: usize,
+    _unsync: PhantomData<Cell<()>>,
+}
+
+/// The reading end of a [`triple`] buffer, which can be moved to another thread but not shared.
+pub struct Reader<T> {
+    triple: Arc<Triple<T>>,
+    index: Cell<usize>,
+}
+
+/// Return both ends of a buffer whose writer publishes values and whose reader reads the latest one published, starting with `initial`.
+///
+/// Neither end ever waits for the other, and values the reader didn't get to are skipped.
+#[must_use]
+pub fn triple<T: Clone>(initial: T) -> (Writer<T>, Reader<T>) {
+    let triple = Arc::new(Triple {
+        buffers: [UnsafeCell::new(initial.clone()), UnsafeCell::new(initial.clone()), UnsafeCell::new(initial)],
+        middle: AtomicUsize::new(1),
+    });
+    (
+        Writer { triple: Arc::clone(&triple), index: 0, _unsync: PhantomData },
+        Reader { triple, index: Cell::new(2) },
+    )
+}
+
+impl<T> Writer<T> {
+    /// The copy being written, which holds whatever was written to it before it was last handed over, rather than what was published last.
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFETY: the copy at `index` belongs to the writer until it's published.
+        unsafe { &mut *self.triple.buffers[self.index].get() }
+    }
+
+    /// Publish the copy being written, and take the copy in between to write the next one.
+    pub fn publish(&mut self) {
+        self.index = self.triple.middle.swap(self.index | NEWER, Ordering::AcqRel) & !NEWER;
+    }
+
+    /// Write `value` and publish it.
+    pub fn write(&mut self, value: T) {
+        *self.get_mut() = value;
+        self.publish();
+    }
+}
+
+impl<T> Reader<T> {
+    /// Take the latest copy published, if there's a newer one than the reader's.
+    fn update(&self) {
+        if self.has_newer() {
+            self.index.set(self.triple.middle.swap(self.index.get(), Ordering::AcqRel) & !NEWER);
+        }
+    }
+
+    /// Return the latest value published.
+    pub fn read(&mut self) -> &T {
+        self.update();
+        // SAFETY: the copy at `index` belongs to the reader until it takes another.
+        unsafe { &*self.triple.buffers[self.index.get()].get() }
+    }
+
+    /// Return a copy of the latest value published, which doesn't need the reader to be mutable.
+    #[must_use]
+    pub fn latest(&self) -> T
+    where
+        T: Copy,
+    {
+        self.update();
+        // SAFETY: the copy at `index` belongs to the reader until it takes another, which it can't while it's copied as the reader isn't shared.
+        unsafe { *self.triple.buffers[self.index.get()].get() }
+    }
+
+    /// Return whether a value was published since the reader last read one.
+    #[must_use]
+    pub fn has_newer(&self) -> bool {
+        self.triple.middle.load(Ordering::Relaxed) & NEWER != 0
+    }
+}
@@ -0,0 +1,179 @@
+//! A drum rack, which plays a sample on each of its pads when the note of the pad is played, like a drum machine.
+//!
+//! The pads are played by the notes from [`FIRST_KEY`] up, which is where General MIDI puts the kick drum. Drums aren't held, so a pad plays its sample
+//! through until its envelope ends, however long its note is. Pads in the same choke group cut each other off, like an open and a closed hi-hat.
+
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use super::synth::{amount, choice, Filter, Setting};
+use crate::midi::Note;
+
+/// How many pads a drum rack has.
+pub const PADS: usize = 16;
+/// The note that plays the first pad, which is C2.
+pub const FIRST_KEY: u8 = 36;
+/// How long a pad cut off by another pad of its choke group takes to fade out, in seconds, which is just long enough not to click.
+const CHOKE_FADE: f64 = 0.005;
+/// The cutoff the filter of a pad is open at, where it's skipped.
+const OPEN_CUTOFF: f64 = 20000.;
+
+const CHOKE_GROUPS: &[&str] = &["None", "1", "2", "3", "4"];
+
+/// The settings of every pad, in the order their values are given to [`Pad::new`].
+pub static SETTINGS: &[Setting] = &[
+    amount("Pitch", -24., 24., 0.),
+    amount("Cutoff", 20., OPEN_CUTOFF, OPEN_CUTOFF),
+    amount("Resonance", 0., 1., 0.),
+    amount("Attack", 0., 1., 0.),
+    amount("Hold", 0., 10., 10.),
+    amount("Decay", 0., 5., 0.05),
+    amount("Volume", 0., 1., 0.8),
+    choice("Choke group", CHOKE_GROUPS, 0),
+];
+
+/// Return the default value of every setting of a pad.
+#[must_use]
+pub fn defaults() -> Vec<f64> {
+    SETTINGS.iter().map(|setting| setting.parameter.default).collect()
+}
+
+/// Return the pad played by `key`, if any.
+#[must_use]
+pub fn pad(key: u8) -> Option<usize> {
+    key.checked_sub(FIRST_KEY).map(usize::from).filter(|pad| *pad < PADS)
+}
+
+/// A pad of a [`DrumRack`], with its sample and settings.
+#[derive(Debug, Clone)]
+pub struct Pad {
+    /// Interleaved samples of every channel, which are played on the channels of the rack, or mixed down to one if there are more or fewer of them.
+    samples: Arc<[f64]>,
+    channels: usize,
+    sample_rate: f64,
+    /// How far the sample is pitched, in semitones, which also changes how fast it's played.
+    pitch: f64,
+    cutoff: f64,
+    resonance: f64,
+    /// How long the pad takes to reach its full volume, stays there and fades out, in seconds.
+    attack: f64,
+    hold: f64,
+    decay: f64,
+    volume: f64,
+    choke_group: Option<usize>,
+}
+
+impl Pad {
+    /// Return a pad playing `samples` of `channels` channels at `sample_rate`, with the given values of its [settings](SETTINGS). Missing values are
+    /// replaced by their defaults, and values are clamped to their range.
+    #[must_use]
+    pub fn new(samples: Arc<[f64]>, channels: usize, sample_rate: f64, values: &[f64]) -> Self {
+        let values = SETTINGS
+            .iter()
+            .enumerate()
+            .map(|(index, setting)| {
+                let parameter = &setting.parameter;
+                values.get(index).copied().unwrap_or(parameter.default).clamp(*parameter.range.start(), *parameter.range.end())
+            })
+            .collect_vec();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "choices are clamped to their range")]
+        let choke_group = Some(values[7].round() as usize).filter(|group| *group > 0);
+        Self {
+            samples,
+            channels: channels.max(1),
+            sample_rate,
+            pitch: values[0],
+            cutoff: values[1],
+            resonance: values[2],
+            attack: values[3],
+            hold: values[4],
+            decay: values[5],
+            volume: values[6],
+            choke_group,
+        }
+    }
+
+    /// Return the sample of `channel` at `frame` played on `channels` channels, or silence past the end. Samples with as many channels as are played
+    /// keep them apart, while others are mixed down to one channel played on all of them.
+    fn frame(&self, frame: usize, channel: usize, channels: usize) -> f64 {
+        let own = self.channels;
+        let Some(samples) = self.samples.get(frame * own..(frame + 1) * own) else {
+            return 0.;
+        };
+        #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+        if own == channels {
+            samples[channel]
+        } else {
+            samples.iter().sum::<f64>() / own as f64
+        }
+    }
+
+    /// Return the interleaved samples of the pad hit at `velocity`, from 0 to 1, on `channels` channels, until its envelope or sample ends, or until it's
+    /// cut off `choke` seconds in.
+    fn hit(&self, velocity: f64, choke: Option<f64>, channels: usize, sample_rate: f64) -> Vec<f64> {
+        // How many frames of the sample are played for each sample returned.
+        let step = self.sample_rate / sample_rate * (self.pitch / 12.).exp2();
+        #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+        let frames = (self.samples.len() / self.channels) as f64 / step;
+        let seconds = (self.attack + self.hold + self.decay).min(choke.map_or(f64::INFINITY, |choke| choke + CHOKE_FADE));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lengths are positive and well within range")]
+        let length = frames.min(seconds * sample_rate).ceil() as usize;
+        let mut filters = (0..channels).map(|_| Filter::default()).collect_vec();
+        let mut samples = Vec::with_capacity(length * channels);
+        for index in 0..length {
+            #[allow(clippy::cast_precision_loss, reason = "indices are well within range")]
+            let (position, time) = (index as f64 * step, index as f64 / sample_rate);
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+            let frame = position as usize;
+            let attack = if self.attack > 0. { (time / self.attack).min(1.) } else { 1. };
+            let decay = (1. - (time - self.attack - self.hold).max(0.) / self.decay.max(f64::EPSILON)).max(0.);
+            let choked = choke.map_or(1., |choke| (1. - (time - choke).max(0.) / CHOKE_FADE).max(0.));
+            for (channel, filter) in filters.iter_mut().enumerate() {
+                let (current, next) = (self.frame(frame, channel, channels), self.frame(frame + 1, channel, channels));
+                let value = (next - current).mul_add(position.fract(), current);
+                let filtered = if self.cutoff < OPEN_CUTOFF { filter.process(value, self.cutoff, self.resonance, sample_rate) } else { value };
+                samples.push(self.volume * velocity * attack * decay * choked * filtered);
+            }
+        }
+        samples
+    }
+}
+
+/// A drum rack, with a pad for each note from [`FIRST_KEY`] up that can hold a sample.
+#[derive(Debug, Clone, Default)]
+pub struct DrumRack {
+    pub pads: [Option<Pad>; PADS],
+}
+
+impl DrumRack {
+    /// Play `notes`, timed in beats at `tempo` BPM, and return the interleaved samples of `channels` channels. Notes of pads without a sample aren't played.
+    #[must_use]
+    pub fn play(&self, notes: &[Note], tempo: f64, channels: usize, sample_rate: f64) -> Vec<f64> {
+        let channels = channels.max(1);
+        let seconds = 60. / tempo;
+        let hits = notes
+            .iter()
+            .filter_map(|note| Some((note, self.pads[pad(note.key)?].as_ref()?)))
+            .sorted_by(|(a, _), (b, _)| a.start.total_cmp(&b.start))
+            .collect_vec();
+        let mut samples = Vec::new();
+        for (index, (note, pad)) in hits.iter().enumerate() {
+            // The next hit of the pad's choke group cuts it off, unless it's hit at the same time.
+            let choke = pad.choke_group.and_then(|group| {
+                let (next, _) = hits[index + 1..].iter().find(|(next, other)| other.choke_group == Some(group) && next.start > note.start)?;
+                Some((next.start - note.start) * seconds)
+            });
+            let hit = pad.hit(f64::from(note.velocity) / 127., choke, channels, sample_rate);
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "times are positive and well within range")]
+            let start = (note.start * seconds * sample_rate) as usize * channels;
+            if samples.len() < start + hit.len() {
+                samples.resize(start + hit.len(), 0.);
+            }
+            for (sample, value) in samples[start..].iter_mut().zip(hit) {
+                *sample += value;
+            }
+        }
+        samples
+    }
+}
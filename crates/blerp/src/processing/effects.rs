@@ -16,6 +16,8 @@ pub struct Stuff<'a> {
     pub time: f64,
     /// Sample rate used in conjunction with [`Self::time`] to find a block.
     pub sample_rate: f64,
+    /// Number of channels interleaved in [`Self::samples`].
+    pub channels: usize,
     /// Sequence of samples.
     pub samples: Cow<'a, [f64]>,
 }
@@ -25,7 +27,6 @@ pub mod clip {
 
     use super::{Effect, EffectError, Stuff};
     use cpal::Sample;
-    use itertools::Itertools;
 
     /// An effect that clips a sample to between `lower` and `upper`.
     pub struct ClipEffect {
@@ -35,7 +36,7 @@ pub mod clip {
 
     impl Effect for ClipEffect {
         fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
-            input.samples = input.samples.iter().map(|sample| sample.clamp(self.lower, self.upper)).collect_vec().into();
+            input.samples.to_mut().iter_mut().for_each(|sample| *sample = sample.clamp(self.lower, self.upper));
             Ok(input)
         }
     }
@@ -75,7 +76,6 @@ pub mod scale {
     use std::fmt::{self, Display, Formatter};
 
     use super::{Effect, EffectError, Stuff};
-    use itertools::Itertools;
 
     /// An effect that scales a sample by a factor.
     pub struct ScaleEffect {
@@ -90,7 +90,7 @@ pub mod scale {
 
     impl Effect for ScaleEffect {
         fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
-            input.samples = input.samples.iter().map(|sample| sample * self.factor).collect_vec().into();
+            input.samples.to_mut().iter_mut().for_each(|sample| *sample *= self.factor);
             Ok(input)
         }
     }
@@ -103,3 +103,197 @@ pub mod scale {
         }
     }
 }
+
+pub mod reverse {
+    use std::fmt::{self, Display, Formatter};
+
+    use super::{Effect, EffectError, Stuff};
+
+    /// An effect that plays a sequence of samples backwards, keeping channels in place.
+    pub struct ReverseEffect;
+
+    impl Display for ReverseEffect {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "Reverse")
+        }
+    }
+
+    impl Effect for ReverseEffect {
+        fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
+            let channels = input.channels.max(1);
+            let samples = input.samples.to_mut();
+            // Reversing every sample reverses the frames along with the channels in them, and a partial frame at the end ends up at the start, so
+            // it's put back in order before the channels of every whole frame are.
+            samples.reverse();
+            let partial = samples.len() % channels;
+            samples[..partial].reverse();
+            samples[partial..].chunks_exact_mut(channels).for_each(<[f64]>::reverse);
+            Ok(input)
+        }
+    }
+}
+
+pub mod normalize {
+    use std::fmt::{self, Display, Formatter};
+
+    use super::{Effect, EffectError, Stuff};
+    use crate::processing::loudness::{decibels_to_gain, gain_to_decibels, integrated_loudness, peak};
+
+    /// The level that a [`NormalizeEffect`] brings its input to.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum NormalizeTarget {
+        /// Scale so that the loudest sample reaches the given level in dBFS.
+        Peak(f64),
+        /// Scale so that the integrated loudness reaches the given level in LUFS.
+        Loudness(f64),
+    }
+
+    /// An effect that scales a sequence of samples so that it reaches a target level.
+    ///
+    /// Silent input is left untouched.
+    pub struct NormalizeEffect {
+        target: NormalizeTarget,
+    }
+
+    impl Display for NormalizeEffect {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "Normalize")
+        }
+    }
+
+    impl Effect for NormalizeEffect {
+        fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
+            let difference = match self.target {
+                NormalizeTarget::Peak(decibels) => decibels - gain_to_decibels(peak(&input.samples)),
+                NormalizeTarget::Loudness(lufs) => lufs - integrated_loudness(&input.samples, input.channels, input.sample_rate),
+            };
+            if difference.is_finite() {
+                let factor = decibels_to_gain(difference);
+                input.samples.to_mut().iter_mut().for_each(|sample| *sample *= factor);
+            }
+            Ok(input)
+        }
+    }
+
+    impl NormalizeEffect {
+        /// Return a new [`NormalizeEffect`] which brings samples to `target`.
+        #[must_use]
+        pub const fn new(target: NormalizeTarget) -> Self {
+            Self { target }
+        }
+    }
+}
+
+pub mod fade {
+    use std::fmt::{self, Display, Formatter};
+
+    use super::{Effect, EffectError, Stuff};
+
+    /// An effect that linearly fades a sequence of samples in from silence at its start, and out to silence at its end.
+    ///
+    /// If the fades are longer than the input, they overlap and both apply.
+    pub struct FadeEffect {
+        fade_in: f64,
+        fade_out: f64,
+    }
+
+    impl Display for FadeEffect {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "Fade")
+        }
+    }
+
+    impl Effect for FadeEffect {
+        fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
+            let channels = input.channels.max(1);
+            #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+            let frames = (input.samples.len() / channels) as f64;
+            let fade_in = self.fade_in * input.sample_rate;
+            let fade_out = self.fade_out * input.sample_rate;
+            for (frame, samples) in input.samples.to_mut().chunks_mut(channels).enumerate() {
+                #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+                let frame = frame as f64;
+                let gain = ((frame + 0.5) / fade_in).min(1.) * ((frames - frame - 0.5) / fade_out).min(1.);
+                for sample in samples {
+                    *sample *= gain;
+                }
+            }
+            Ok(input)
+        }
+    }
+
+    impl FadeEffect {
+        /// Return a new [`FadeEffect`] which fades in over `fade_in` seconds and out over `fade_out` seconds. Fades of zero seconds are not applied.
+        #[must_use]
+        pub const fn new(fade_in: f64, fade_out: f64) -> Self {
+            Self { fade_in, fade_out }
+        }
+    }
+}
+
+pub mod oscillator {
+    use std::fmt::{self, Display, Formatter};
+
+    use super::{Effect, EffectError, Stuff};
+    use crate::processing::generation::{sawtooth_wave, sine_wave, square_wave, triangle_wave};
+
+    /// The shape of the wave an [`OscillatorEffect`] generates.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Waveform {
+        Sine,
+        Square,
+        Triangle,
+        Sawtooth,
+    }
+
+    impl Waveform {
+        pub const ALL: [Self; 4] = [Self::Sine, Self::Square, Self::Triangle, Self::Sawtooth];
+    }
+
+    /// An effect that adds a wave to its input, on every channel, so that it generates the wave from silence.
+    pub struct OscillatorEffect {
+        waveform: Waveform,
+        frequency: f64,
+        amplitude: f64,
+    }
+
+    impl Display for OscillatorEffect {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.waveform)
+        }
+    }
+
+    impl Effect for OscillatorEffect {
+        fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
+            let (frequency, amplitude) = (self.frequency, self.amplitude);
+            match self.waveform {
+                Waveform::Sine => add(sine_wave(frequency, amplitude), &mut input),
+                Waveform::Square => add(square_wave(frequency, amplitude), &mut input),
+                Waveform::Triangle => add(triangle_wave(frequency, amplitude), &mut input),
+                Waveform::Sawtooth => add(sawtooth_wave(frequency, amplitude), &mut input),
+            }
+            Ok(input)
+        }
+    }
+
+    /// Add `wave` to every channel of `input`, starting from its time.
+    fn add(mut wave: impl FnMut(f64) -> f64, input: &mut Stuff) {
+        let channels = input.channels.max(1);
+        let (start, sample_rate) = (input.time, input.sample_rate);
+        for (frame, samples) in input.samples.to_mut().chunks_mut(channels).enumerate() {
+            #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+            let value = wave(start + frame as f64 / sample_rate);
+            for sample in samples {
+                *sample += value;
+            }
+        }
+    }
+
+    impl OscillatorEffect {
+        /// Return a new [`OscillatorEffect`] which adds a `waveform` of `frequency` hertz and `amplitude`.
+        #[must_use]
+        pub const fn new(waveform: Waveform, frequency: f64, amplitude: f64) -> Self {
+            Self { waveform, frequency, amplitude }
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    mem::take,
+    sync::{
+        atomic::{fence, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use thiserror::Error;
+
+use super::{
+    effects::{Effect, Stuff},
+    stream::Stream,
+};
+
+/// A node of a [`Schedule`]. The audio of every node connected to its input is summed before it is processed.
+pub enum Node {
+    /// Passes its input through unchanged.
+    Sum,
+    /// Multiplies its input by an amplitude, like a fader or the level of a send.
+    Gain(f64),
+    /// Adds interleaved samples to its input, starting from the beginning of playback.
+    Samples { samples: Arc<[f64]>, looping: bool },
+    /// Adds the interleaved samples of a track from the beginning of playback, along with the clips of the track streamed while they play.
+    Track { samples: Arc<[f64]>, streams: Vec<Arc<Stream>> },
+    /// Adds audio from outside the schedule to its input, given by index to [`Schedule::process`].
+    Input(usize),
+    /// Applies an effect to its input.
+    Effect(Box<dyn Effect + Send>),
+    /// Passes its input through unchanged, keeping a copy of the latest audio in the [`Tap`].
+    Tap(Arc<Tap>),
+}
+
+/// How loud a channel of the audio that went through a [`Tap`] was, in amplitude.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Level {
+    pub peak: f64,
+    pub rms: f64,
+}
+
+/// The latest audio that went through a [`Node::Tap`], so that it can be looked at while it plays.
+///
+/// Audio is pushed from one thread at a time, like the audio thread, which never waits on the threads reading it: samples are kept as the bits of
+/// `f64`s in atomics, and readers check afterwards whether what they read was written over meanwhile.
+pub struct Tap {
+    channels: usize,
+    sample_rate: f64,
+    /// How many frames are kept.
+    length: usize,
+    /// The samples kept, in a circle where frame `n` is kept at `n % length`.
+    samples: Box<[AtomicU64]>,
+    /// How many frames are written or being written in all, which is moved before their samples are written.
+    writing: AtomicUsize,
+    /// How many frames were written in all, which is moved once their samples are.
+    total: AtomicUsize,
+    /// The peak and sum of squares of every channel since the levels were last taken.
+    levels: Box<[(AtomicU64, AtomicU64)]>,
+    /// How many frames the levels were measured over, which is moved once a block is measured.
+    measured: AtomicUsize,
+}
+
+impl Tap {
+    /// Return a tap keeping the last `length` frames of audio with the given format, which should be the format of the [`Schedule`] it is used in.
+    #[must_use]
+    pub fn new(channels: usize, sample_rate: f64, length: usize) -> Self {
+        let channels = channels.max(1);
+        Self {
+            channels,
+            sample_rate,
+            length,
+            samples: (0..length * channels).map(|_| AtomicU64::new(0_f64.to_bits())).collect(),
+            writing: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            levels: (0..channels).map(|_| (AtomicU64::new(0_f64.to_bits()), AtomicU64::new(0_f64.to_bits()))).collect(),
+            measured: AtomicUsize::new(0),
+        }
+    }
+
+    #[must_use]
+    pub const fn channels(&self) -> usize {
+        self.channels
+    }
+
+    #[must_use]
+    pub const fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Return a copy of the latest interleaved samples, oldest first. Samples written over while they're copied may already be newer.
+    #[must_use]
+    pub fn samples(&self) -> Vec<f64> {
+        self.read(self.total.load(Ordering::Acquire), self.length)
+    }
+
+    /// Return the interleaved samples that went through once `frames` frames had, oldest first and as far as they are still kept, and how many
+    /// frames went through in all, to be passed the next time so that every sample is read once.
+    #[must_use]
+    pub fn samples_since(&self, frames: usize) -> (Vec<f64>, usize) {
+        let total = self.total.load(Ordering::Acquire);
+        let fresh = total.saturating_sub(frames).min(self.length);
+        let mut samples = self.read(total, fresh);
+        // Frames that were written over while they were read are dropped, as they're newer than the ones around them.
+        fence(Ordering::Acquire);
+        let overwritten = self.writing.load(Ordering::Relaxed).saturating_sub(self.length).saturating_sub(total - fresh).min(fresh);
+        samples.drain(..overwritten * self.channels);
+        (samples, total)
+    }
+
+    /// Return the samples of the last `frames` of the `total` frames that went through, which are at most as many as are kept.
+    fn read(&self, total: usize, frames: usize) -> Vec<f64> {
+        let first = (total + self.length - frames) * self.channels;
+        (first..first + frames * self.channels)
+            .map(|index| f64::from_bits(self.samples[index % self.samples.len()].load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Return the level of every channel since the levels were last taken, measured over every sample rather than only the ones kept, or `None` if
+    /// no audio went through since. A block pushed while the levels are taken may be counted in the next ones.
+    #[must_use]
+    pub fn take_levels(&self) -> Option<Vec<Level>> {
+        let frames = self.measured.swap(0, Ordering::Acquire);
+        #[allow(clippy::cast_precision_loss, reason = "frame counts are well within range")]
+        let frames = frames as f64;
+        (frames > 0.).then(|| {
+            self.levels
+                .iter()
+                .map(|(peak, squares)| {
+                    let [peak, squares] = [peak, squares].map(|level| f64::from_bits(level.swap(0_f64.to_bits(), Ordering::Relaxed)));
+                    Level { peak, rms: (squares / frames).sqrt() }
+                })
+                .collect()
+        })
+    }
+
+    /// Keep a copy of `block`, dropping the oldest samples, and measure its levels, without waiting on anything reading them.
+    ///
+    /// A [`Node::Tap`] pushes the audio going through it, and audio from outside a schedule, like from a capture device, can be pushed too, as long as
+    /// only one thread pushes at a time.
+    pub fn push(&self, block: &[f64]) {
+        let frames = block.len() / self.channels;
+        let total = self.total.load(Ordering::Relaxed);
+        if !self.samples.is_empty() {
+            self.writing.store(total + frames, Ordering::Relaxed);
+            fence(Ordering::Release);
+            // Only the end of a block longer than the tap is kept.
+            let kept = frames.min(self.length);
+            let first = (total + frames - kept) * self.channels;
+            for (index, sample) in (first..).zip(&block[(frames - kept) * self.channels..frames * self.channels]) {
+                self.samples[index % self.samples.len()].store(sample.to_bits(), Ordering::Relaxed);
+            }
+        }
+        self.total.store(total + frames, Ordering::Release);
+        for (channel, (peak, squares)) in self.levels.iter().enumerate() {
+            let samples = block[..frames * self.channels].iter().skip(channel).step_by(self.channels);
+            let (block_peak, block_squares) = samples.fold((0_f64, 0_f64), |(peak, squares), sample| (peak.max(sample.abs()), sample.mul_add(*sample, squares)));
+            // The bits of positive numbers are ordered like the numbers.
+            peak.fetch_max(block_peak.to_bits(), Ordering::Relaxed);
+            let _ = squares.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| Some((f64::from_bits(sum) + block_squares).to_bits()));
+        }
+        self.measured.fetch_add(frames, Ordering::Release);
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("the graph contains a cycle")]
+pub struct CycleError;
+
+/// How many frames the buffer of every node holds from the start, so that evaluating blocks up to this long never allocates.
+pub const RESERVED_FRAMES: usize = 4096;
+
+/// A graph of [`Node`]s prepared for being evaluated a block at a time, in an order where every node comes after its inputs.
+pub struct Schedule {
+    nodes: Vec<Node>,
+    /// The indices of the nodes connected to the input of each node.
+    inputs: Vec<Vec<usize>>,
+    /// The nodes that contribute to the output, in the order they are evaluated.
+    order: Vec<usize>,
+    output: usize,
+    /// The latest block produced by each node.
+    buffers: Vec<Vec<f64>>,
+    channels: usize,
+    sample_rate: f64,
+    /// The number of frames that have been processed since the start of playback.
+    position: usize,
+    /// Frames where blocks are split, in order, so that what happens there doesn't wait for the next block.
+    boundaries: Vec<usize>,
+}
+
+impl Schedule {
+    /// Prepare `nodes` connected by `edges`, which are pairs of indices from an output to an input, for evaluation.
+    /// # Errors
+    /// Returns a [`CycleError`] if a node feeds back into itself, since it could never be evaluated.
+    /// # Panics
+    /// Panics if `output` or an edge refers to a node that doesn't exist.
+    pub fn new(nodes: Vec<Node>, edges: &[(usize, usize)], output: usize, channels: usize, sample_rate: f64) -> Result<Self, CycleError> {
+        assert!(output < nodes.len(), "output node doesn't exist");
+        let mut inputs = vec![Vec::new(); nodes.len()];
+        let mut outputs = vec![Vec::new(); nodes.len()];
+        for (from, to) in edges {
+            inputs[*to].push(*from);
+            outputs[*from].push(*to);
+        }
+
+        // Kahn's algorithm: repeatedly evaluate a node with no inputs left to evaluate. Any node that is never reached is part of a cycle.
+        let mut remaining = inputs.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut ready = (0..nodes.len()).filter(|node| remaining[*node] == 0).collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(nodes.len());
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for next in &outputs[node] {
+                remaining[*next] -= 1;
+                if remaining[*next] == 0 {
+                    ready.push_back(*next);
+                }
+            }
+        }
+        if order.len() != nodes.len() {
+            return Err(CycleError);
+        }
+
+        // Nodes that don't lead to the output can't be heard, so they aren't evaluated at all.
+        let mut audible = vec![false; nodes.len()];
+        let mut stack = vec![output];
+        while let Some(node) = stack.pop() {
+            if !audible[node] {
+                audible[node] = true;
+                stack.extend(&inputs[node]);
+            }
+        }
+        order.retain(|node| audible[*node]);
+
+        Ok(Self {
+            // Cloned vectors don't keep their capacity, so every buffer is made on its own.
+            buffers: (0..nodes.len()).map(|_| Vec::with_capacity(RESERVED_FRAMES * channels.max(1))).collect(),
+            nodes,
+            inputs,
+            order,
+            output,
+            channels: channels.max(1),
+            sample_rate,
+            position: 0,
+            boundaries: Vec::new(),
+        })
+    }
+
+    /// The number of frames that have been processed since the start of playback.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Continue playback from `position` frames after its start.
+    pub const fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Split blocks at `frames`, like where clips start and end or where the tempo changes, so that effects are evaluated from exactly there rather
+    /// than from wherever the block that holds them starts. Blocks are also split where looping [`Node::Samples`] start over.
+    pub fn set_boundaries(&mut self, frames: impl IntoIterator<Item = usize>) {
+        self.boundaries = frames.into_iter().collect();
+        self.boundaries.sort_unstable();
+        self.boundaries.dedup();
+    }
+
+    /// Return the first frame after the position where a block is split, if there is one.
+    fn next_boundary(&self) -> Option<usize> {
+        let given = self.boundaries.get(self.boundaries.partition_point(|frame| *frame <= self.position)).copied();
+        let wraps = self.order.iter().filter_map(|node| match &self.nodes[*node] {
+            Node::Samples { samples, looping: true } if samples.len() >= self.channels => {
+                let length = samples.len() / self.channels;
+                Some((self.position / length + 1) * length)
+            }
+            _ => None,
+        });
+        given.into_iter().chain(wraps).min()
+    }
+
+    /// Evaluate the next block of the schedule into `output`, whose length decides the length of the block. The block is evaluated in parts split
+    /// at the boundaries in it, see [`Self::set_boundaries`].
+    ///
+    /// `inputs` are interleaved blocks of audio for [`Node::Input`], which are treated as silence if missing or too short.
+    pub fn process(&mut self, inputs: &[&[f64]], output: &mut [f64]) {
+        let mut start = 0;
+        while start < output.len() {
+            let remaining = output.len() - start;
+            let length = self.next_boundary().map_or(remaining, |boundary| ((boundary - self.position) * self.channels).min(remaining));
+            self.evaluate(inputs, start, &mut output[start..start + length], true);
+            self.position += length / self.channels;
+            start += length;
+        }
+    }
+
+    /// Evaluate the next block like [`Self::process`] while playback is stopped: [`Node::Samples`] and [`Node::Track`] are silent and the position doesn't move, so only
+    /// `inputs` are heard, through the effects they go through. This is how an input is monitored without playing the rest of the schedule.
+    pub fn process_inputs(&mut self, inputs: &[&[f64]], output: &mut [f64]) {
+        self.evaluate(inputs, 0, output, false);
+    }
+
+    /// Evaluate the part of a block in `output`, which starts `from` samples into `inputs`.
+    fn evaluate(&mut self, inputs: &[&[f64]], from: usize, output: &mut [f64], playing: bool) {
+        let length = output.len();
+        let offset = self.position * self.channels;
+        for node in self.order.iter().copied() {
+            let mut buffer = take(&mut self.buffers[node]);
+            buffer.clear();
+            buffer.resize(length, 0.);
+            for input in &self.inputs[node] {
+                for (sample, input) in buffer.iter_mut().zip(&self.buffers[*input]) {
+                    *sample += input;
+                }
+            }
+            match &self.nodes[node] {
+                Node::Sum => {}
+                Node::Gain(gain) => buffer.iter_mut().for_each(|sample| *sample *= gain),
+                Node::Samples { .. } | Node::Track { .. } if !playing => {}
+                Node::Samples { samples, looping } => {
+                    for (index, sample) in buffer.iter_mut().enumerate() {
+                        let position = offset + index;
+                        let position = if *looping && !samples.is_empty() { position % samples.len() } else { position };
+                        *sample += samples.get(position).copied().unwrap_or_default();
+                    }
+                }
+                Node::Track { samples, streams } => {
+                    for (sample, rendered) in buffer.iter_mut().zip(samples.get(offset..).unwrap_or_default()) {
+                        *sample += rendered;
+                    }
+                    for stream in streams {
+                        stream.read(self.position, &mut buffer);
+                    }
+                }
+                Node::Input(index) => {
+                    for (sample, input) in buffer.iter_mut().zip(inputs.get(*index).and_then(|input| input.get(from..)).unwrap_or_default()) {
+                        *sample += input;
+                    }
+                }
+                Node::Tap(tap) => tap.push(&buffer),
+                Node::Effect(effect) => {
+                    #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+                    let stuff = Stuff {
+                        time: self.position as f64 / self.sample_rate,
+                        sample_rate: self.sample_rate,
+                        channels: self.channels,
+                        samples: Cow::Owned(take(&mut buffer)),
+                    };
+                    // Effects that keep the length work in the buffer itself, so it's handed back without allocating.
+                    let Ok(processed) = effect.apply(stuff);
+                    buffer = processed.samples.into_owned();
+                    buffer.resize(length, 0.);
+                }
+            }
+            self.buffers[node] = buffer;
+        }
+        match self.buffers.get(self.output).filter(|buffer| buffer.len() == length) {
+            Some(buffer) => output.copy_from_slice(buffer),
+            None => output.fill(0.),
+        }
+    }
+}
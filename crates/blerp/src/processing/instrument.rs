@@ -0,0 +1,90 @@
+use std::{f64::consts::TAU, iter::Peekable, vec};
+
+use itertools::Itertools;
+
+use crate::midi::{Dimension, Expression, Note};
+
+/// How long a note takes to reach its full amplitude, in seconds.
+const ATTACK: f64 = 0.005;
+/// How long a note takes to fade out once it's released, in seconds.
+const RELEASE: f64 = 0.08;
+/// How quickly a held note fades, as the fraction of its amplitude left after a second.
+const DECAY: f64 = 0.3;
+/// The amplitude of a note hit as hard as possible, low enough that chords don't clip.
+const AMPLITUDE: f64 = 0.2;
+/// The amplitude of the octave above a note slid all the way up, relative to the note.
+const OCTAVE: f64 = 0.6;
+/// Where notes are slid to until they say otherwise, which is the middle of the key.
+pub(crate) const DEFAULT_SLIDE: f64 = 0.5;
+
+/// Return the frequency of a MIDI `key` in hertz, with A4 (key 69) at 440 Hz.
+#[must_use]
+pub fn frequency(key: u8) -> f64 {
+    440. * ((f64::from(key) - 69.) / 12.).exp2()
+}
+
+/// How a note is played while it's held, following the expression of its channel as the note goes on.
+pub(crate) struct NoteExpression<'a> {
+    changes: Peekable<vec::IntoIter<&'a Expression>>,
+    /// How far the note is bent, in semitones.
+    pub bend: f64,
+    /// How hard the key is pressed, or [`None`] until that's sent.
+    pub pressure: Option<f64>,
+    pub slide: f64,
+}
+
+impl<'a> NoteExpression<'a> {
+    /// Return how `note` starts out, given the `expression` of every channel.
+    pub fn new(note: &Note, expression: &'a [Expression]) -> Self {
+        // Changes made after the note is released are for the next note played on its channel.
+        let changes = expression
+            .iter()
+            .filter(|change| change.channel == note.channel && change.start <= note.start + note.length)
+            .sorted_by(|a, b| a.start.total_cmp(&b.start))
+            .peekable();
+        Self { changes, bend: 0., pressure: None, slide: DEFAULT_SLIDE }
+    }
+
+    /// Apply the changes made up to `beat`, timed like the notes. Expression sent before the note starts is where it starts from.
+    pub fn advance(&mut self, beat: f64) {
+        while let Some(change) = self.changes.next_if(|change| change.start <= beat) {
+            match change.dimension {
+                Dimension::PitchBend => self.bend = change.value,
+                Dimension::Pressure => self.pressure = Some(change.value),
+                Dimension::Slide => self.slide = change.value,
+            }
+        }
+    }
+}
+
+/// Play `notes`, timed in beats at `tempo` BPM, with a simple built-in instrument and return the mono samples.
+///
+/// Each note is a sine wave with a softer octave above it, which fades while it's held and fades out quickly once it's released, so that MIDI can be heard
+/// without a synthesizer. Notes follow the `expression` of their channels, so that each note of an MPE controller is bent, pressed and slid on its own:
+/// pitch bends move the note, pressure makes it louder once the key is pressed and slide brings the octave in.
+#[must_use]
+pub fn play(notes: &[Note], expression: &[Expression], tempo: f64, sample_rate: f64) -> Vec<f64> {
+    let seconds = 60. / tempo;
+    let end = notes.iter().map(|note| (note.start + note.length).mul_add(seconds, RELEASE)).fold(0., f64::max);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the length is positive and well within range")]
+    let mut samples = vec![0.; (end * sample_rate).ceil() as usize];
+    for note in notes {
+        let amplitude = AMPLITUDE * f64::from(note.velocity) / 127.;
+        let length = note.length * seconds;
+        let mut expression = NoteExpression::new(note, expression);
+        // Where the waves are in their cycles, which is kept rather than computed from the time, as bends change the frequency.
+        let mut phase = 0.;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "times are positive and well within range")]
+        let (start, count) = ((note.start * seconds * sample_rate) as usize, ((length + RELEASE) * sample_rate) as usize);
+        for (index, sample) in samples.iter_mut().skip(start).take(count).enumerate() {
+            #[allow(clippy::cast_precision_loss, reason = "indices are well within range")]
+            let time = index as f64 / sample_rate;
+            expression.advance(note.start + time / seconds);
+            let envelope = (time / ATTACK).min(1.) * DECAY.powf(time) * if time > length { 1. - (time - length) / RELEASE } else { 1. };
+            let loudness = expression.pressure.map_or(1., |pressure| 0.5 + pressure);
+            *sample += amplitude * loudness * envelope * (expression.slide * OCTAVE).mul_add((TAU * 2. * phase).sin(), (TAU * phase).sin());
+            phase += frequency(note.key) * (expression.bend / 12.).exp2() / sample_rate;
+        }
+    }
+    samples
+}
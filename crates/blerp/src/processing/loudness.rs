@@ -0,0 +1,325 @@
+use std::{collections::VecDeque, f64::consts::PI, iter::once};
+
+use itertools::Itertools;
+
+/// Length of a gating block, in seconds, as defined by ITU-R BS.1770, which is also the window of the momentary loudness.
+const BLOCK_LENGTH: f64 = 0.4;
+/// The window of the short-term loudness, in seconds, as defined by EBU Tech 3341.
+const SHORT_TERM_LENGTH: f64 = 3.;
+/// How far apart consecutive blocks start, in seconds.
+const STEP: f64 = 0.1;
+/// Blocks quieter than this (in LUFS) are never taken into account.
+const ABSOLUTE_GATE: f64 = -70.;
+/// Blocks more than this many LU below the ungated loudness are not taken into account.
+const RELATIVE_GATE: f64 = -10.;
+/// Short-term blocks more than this many LU below their ungated loudness are not taken into account for the loudness range, as defined by EBU
+/// Tech 3342.
+const RANGE_GATE: f64 = -20.;
+/// The quieter and louder percentiles of short-term loudness the loudness range spans.
+const RANGE_PERCENTILES: (f64, f64) = (0.1, 0.95);
+/// How many times the sample rate true peaks are measured at, as recommended by ITU-R BS.1770.
+const OVERSAMPLING: usize = 4;
+/// How many samples on each side of a point between samples are interpolated from to find its value.
+const TAPS: usize = 8;
+
+/// Convert a level in decibels to a linear gain factor.
+#[must_use]
+pub fn decibels_to_gain(decibels: f64) -> f64 {
+    10_f64.powf(decibels / 20.)
+}
+
+/// Convert a linear gain factor to a level in decibels.
+#[must_use]
+pub fn gain_to_decibels(gain: f64) -> f64 {
+    20. * gain.log10()
+}
+
+/// Return the largest absolute sample value, or `0.` if there are no samples.
+#[must_use]
+pub fn peak(samples: &[f64]) -> f64 {
+    samples.iter().fold(0., |peak, sample| sample.abs().max(peak))
+}
+
+/// A second order IIR filter in direct form I.
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, sample: f64) -> f64 {
+        let output = self.b[0].mul_add(sample, self.b[1].mul_add(self.x[0], self.b[2] * self.x[1])) - self.a[0].mul_add(self.y[0], self.a[1] * self.y[1]);
+        self.x = [sample, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// The two-stage "K" frequency weighting filter from ITU-R BS.1770, for a single channel.
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    /// Compute the filter coefficients for the given sample rate, so that sample rates other than 48 kHz are weighted correctly.
+    fn new(sample_rate: f64) -> Self {
+        let shelf = {
+            const FREQUENCY: f64 = 1_681.974_450_955_533;
+            const GAIN: f64 = 3.999_843_853_973_347;
+            const Q: f64 = 0.707_175_236_955_419_6;
+            let k = (PI * FREQUENCY / sample_rate).tan();
+            let k_squared = k.powi(2);
+            let high_gain = 10_f64.powf(GAIN / 20.);
+            let band_gain = high_gain.powf(0.499_666_774_154_541_6);
+            let a0 = 1. + k / Q + k_squared;
+            Biquad {
+                b: [(high_gain + band_gain * k / Q + k_squared) / a0, 2. * (k_squared - high_gain) / a0, (high_gain - band_gain * k / Q + k_squared) / a0],
+                a: [2. * (k_squared - 1.) / a0, (1. - k / Q + k_squared) / a0],
+                ..Biquad::default()
+            }
+        };
+        let high_pass = {
+            const FREQUENCY: f64 = 38.135_470_876_024_44;
+            const Q: f64 = 0.500_327_037_323_877_3;
+            let k = (PI * FREQUENCY / sample_rate).tan();
+            let k_squared = k.powi(2);
+            let a0 = 1. + k / Q + k_squared;
+            Biquad {
+                b: [1., -2., 1.],
+                a: [2. * (k_squared - 1.) / a0, (1. - k / Q + k_squared) / a0],
+                ..Biquad::default()
+            }
+        };
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+/// Convert a mean square power (summed over channels) to a loudness in LUFS.
+fn power_to_loudness(power: f64) -> f64 {
+    10_f64.mul_add(power.log10(), -0.691)
+}
+
+/// Return the K-weighted mean square power of every block of `samples` lasting `length` seconds, summed over channels.
+///
+/// `samples` are interleaved with `channels` channels. If the samples are shorter than a single block, the whole input is treated as one block.
+fn block_powers(samples: &[f64], channels: usize, sample_rate: f64, length: f64) -> Vec<f64> {
+    let channels = channels.max(1);
+    let mut filters = vec![KWeighting::new(sample_rate); channels];
+    // Running sum of the weighted power of every frame, so each block's mean is a single subtraction.
+    let cumulative_power = once(0.)
+        .chain(samples.chunks_exact(channels).scan(0., |total, frame| {
+            *total += frame.iter().zip(&mut filters).map(|(sample, filter)| filter.process(*sample).powi(2)).sum::<f64>();
+            Some(*total)
+        }))
+        .collect_vec();
+    let frames = cumulative_power.len() - 1;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "block sizes are small and positive")]
+    let (block_size, step) = (((length * sample_rate) as usize).max(1), ((STEP * sample_rate) as usize).max(1));
+    #[allow(clippy::cast_precision_loss, reason = "block sizes are small")]
+    let mean_power = |start: usize, end: usize| (cumulative_power[end] - cumulative_power[start]) / (end - start) as f64;
+    if frames == 0 {
+        Vec::new()
+    } else if frames < block_size {
+        vec![mean_power(0, frames)]
+    } else {
+        (0..=frames - block_size).step_by(step).map(|start| mean_power(start, start + block_size)).collect()
+    }
+}
+
+/// Return the gated integrated loudness of `samples` in LUFS, as defined by ITU-R BS.1770.
+///
+/// `samples` are interleaved with `channels` channels, all of which are weighted equally. Returns [`f64::NEG_INFINITY`] for silence.
+#[must_use]
+pub fn integrated_loudness(samples: &[f64], channels: usize, sample_rate: f64) -> f64 {
+    gated_loudness(&block_powers(samples, channels, sample_rate, BLOCK_LENGTH))
+}
+
+/// Return the loudness of gating blocks with the given `powers`, leaving out silent blocks and blocks much quieter than the rest.
+fn gated_loudness(powers: &[f64]) -> f64 {
+    let gated_mean = |threshold: f64| {
+        let gated = powers.iter().copied().filter(|power| power_to_loudness(*power) > threshold).collect_vec();
+        #[allow(clippy::cast_precision_loss, reason = "the number of blocks is small")]
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+    let Some(ungated) = gated_mean(ABSOLUTE_GATE) else {
+        return f64::NEG_INFINITY;
+    };
+    gated_mean((power_to_loudness(ungated) + RELATIVE_GATE).max(ABSOLUTE_GATE)).map_or(f64::NEG_INFINITY, power_to_loudness)
+}
+
+/// Return the loudness range of `samples` in LU, as defined by EBU Tech 3342.
+///
+/// This is how far apart the quiet and the loud parts are, leaving out the quietest tenth and the loudest twentieth of the audio.
+///
+/// `samples` are interleaved with `channels` channels. Returns 0 for silence.
+#[must_use]
+pub fn loudness_range(samples: &[f64], channels: usize, sample_rate: f64) -> f64 {
+    let powers = block_powers(samples, channels, sample_rate, SHORT_TERM_LENGTH).into_iter().filter(|power| power_to_loudness(*power) > ABSOLUTE_GATE).collect_vec();
+    if powers.is_empty() {
+        return 0.;
+    }
+    #[allow(clippy::cast_precision_loss, reason = "the number of blocks is small")]
+    let threshold = power_to_loudness(powers.iter().sum::<f64>() / powers.len() as f64) + RANGE_GATE;
+    let loudnesses = powers.into_iter().map(power_to_loudness).filter(|loudness| *loudness > threshold).sorted_by(f64::total_cmp).collect_vec();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "the number of blocks is small")]
+    let percentile = |fraction: f64| loudnesses[((loudnesses.len() - 1) as f64 * fraction).round() as usize];
+    percentile(RANGE_PERCENTILES.1) - percentile(RANGE_PERCENTILES.0)
+}
+
+/// Return the largest absolute value `samples` reach between samples as well as at them once converted to analog, as a linear gain factor.
+///
+/// `samples` are interleaved with `channels` channels. Peaks between samples are found by interpolating the samples at [`OVERSAMPLING`] times
+/// their rate with a windowed sinc, where they may be up to a few decibels louder than the samples around them.
+#[must_use]
+pub fn true_peak(samples: &[f64], channels: usize) -> f64 {
+    let channels = channels.max(1);
+    // The weights of the samples from TAPS - 1 before to TAPS after a point, for each point between two samples.
+    #[allow(clippy::cast_precision_loss, reason = "tap counts are small")]
+    let kernels = (1..OVERSAMPLING)
+        .map(|phase| {
+            let fraction = phase as f64 / OVERSAMPLING as f64;
+            (0..2 * TAPS)
+                .map(|tap| {
+                    let distance = tap as f64 - (TAPS - 1) as f64 - fraction;
+                    let sinc = (PI * distance).sin() / (PI * distance);
+                    sinc * 0.5 * (1. + (PI * distance / TAPS as f64).cos())
+                })
+                .collect_vec()
+        })
+        .collect_vec();
+    let mut loudest = peak(samples);
+    for channel in 0..channels {
+        let channel = samples.iter().skip(channel).step_by(channels).copied().collect_vec();
+        for index in 0..channel.len().saturating_sub(1) {
+            // Points between samples this much quieter than the loudest can't be louder than it.
+            if channel[index].abs().max(channel[index + 1].abs()) * 2. < loudest {
+                continue;
+            }
+            for kernel in &kernels {
+                let value: f64 = kernel
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(tap, weight)| Some(channel.get((index + tap).checked_sub(TAPS - 1)?)? * weight))
+                    .sum();
+                loudest = loudest.max(value.abs());
+            }
+        }
+    }
+    loudest
+}
+
+/// The loudness of some audio as a whole, for checking that a mix meets the loudness a platform asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    /// The integrated loudness, in LUFS.
+    pub integrated: f64,
+    /// The true peak, in dBTP.
+    pub true_peak: f64,
+    /// The loudness range, in LU.
+    pub range: f64,
+}
+
+/// Measure the loudness of `samples` as a whole, which are interleaved with `channels` channels.
+#[must_use]
+pub fn report(samples: &[f64], channels: usize, sample_rate: f64) -> Report {
+    Report {
+        integrated: integrated_loudness(samples, channels, sample_rate),
+        true_peak: gain_to_decibels(true_peak(samples, channels)),
+        range: loudness_range(samples, channels, sample_rate),
+    }
+}
+
+/// Return how many steps of [`STEP`] seconds a window of `length` seconds spans.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "windows are a few steps long")]
+fn steps(length: f64) -> usize {
+    (length / STEP).round() as usize
+}
+
+/// Measures the loudness of audio as it plays, a block at a time, as defined by ITU-R BS.1770 and EBU Tech 3341.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<KWeighting>,
+    /// How many frames a step of [`STEP`] seconds lasts.
+    step_length: usize,
+    /// The weighted power of the step being measured so far, summed over channels, and how many frames it has.
+    power: f64,
+    frames: usize,
+    /// The mean power of the latest steps, oldest first, as many as the short-term window holds.
+    steps: VecDeque<f64>,
+    /// The power of every gating block so far, for the integrated loudness.
+    blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    #[must_use]
+    pub fn new(channels: usize, sample_rate: f64) -> Self {
+        let channels = channels.max(1);
+        Self {
+            channels,
+            filters: vec![KWeighting::new(sample_rate); channels],
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "steps are short and positive")]
+            step_length: ((STEP * sample_rate) as usize).max(1),
+            power: 0.,
+            frames: 0,
+            steps: VecDeque::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Measure interleaved `samples`, which follow the ones measured before.
+    pub fn push(&mut self, samples: &[f64]) {
+        let (block_steps, short_term_steps) = (steps(BLOCK_LENGTH), steps(SHORT_TERM_LENGTH));
+        for frame in samples.chunks_exact(self.channels) {
+            self.power += frame.iter().zip(&mut self.filters).map(|(sample, filter)| filter.process(*sample).powi(2)).sum::<f64>();
+            self.frames += 1;
+            if self.frames < self.step_length {
+                continue;
+            }
+            #[allow(clippy::cast_precision_loss, reason = "steps are short")]
+            self.steps.push_back(self.power / self.frames as f64);
+            (self.power, self.frames) = (0., 0);
+            if self.steps.len() > short_term_steps {
+                self.steps.pop_front();
+            }
+            if self.steps.len() >= block_steps {
+                self.blocks.push(self.mean(block_steps));
+            }
+        }
+    }
+
+    /// Return the mean power of the latest `steps` steps, or of all of them if there are fewer.
+    fn mean(&self, steps: usize) -> f64 {
+        let latest = self.steps.range(self.steps.len().saturating_sub(steps)..);
+        #[allow(clippy::cast_precision_loss, reason = "windows are a few steps long")]
+        let count = latest.len().max(1) as f64;
+        latest.sum::<f64>() / count
+    }
+
+    /// Return the loudness of the last 400 ms, in LUFS.
+    #[must_use]
+    pub fn momentary(&self) -> f64 {
+        power_to_loudness(self.mean(steps(BLOCK_LENGTH)))
+    }
+
+    /// Return the loudness of the last 3 seconds, in LUFS.
+    #[must_use]
+    pub fn short_term(&self) -> f64 {
+        power_to_loudness(self.mean(steps(SHORT_TERM_LENGTH)))
+    }
+
+    /// Return the gated loudness of everything measured so far, in LUFS, which is [`f64::NEG_INFINITY`] until something loud enough was.
+    #[must_use]
+    pub fn integrated(&self) -> f64 {
+        gated_loudness(&self.blocks)
+    }
+}
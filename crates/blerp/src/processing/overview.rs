@@ -0,0 +1,25 @@
+/// The lowest and highest sample in a stretch of audio.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Peak {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Split interleaved `samples` into `width` stretches of about the same length and return the lowest and highest sample of each, across every channel, for drawing a waveform at that width.
+///
+/// There is a stretch per frame instead if there are fewer frames than `width`.
+#[must_use]
+pub fn overview(samples: &[f64], channels: usize, width: usize) -> Vec<Peak> {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    let width = width.min(frames);
+    (0..width)
+        .map(|index| {
+            let stretch = &samples[index * frames / width * channels..(index + 1) * frames / width * channels];
+            stretch.iter().fold(Peak::default(), |peak, &sample| Peak {
+                min: peak.min.min(sample),
+                max: peak.max.max(sample),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,94 @@
+//! Finding the pitch of a note being played, for tuning an instrument, with the YIN algorithm of de Cheveigné and Kawahara.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// The lowest pitch found, in Hz, which is just under the low B of a five-string bass.
+pub const LOWEST: f64 = 30.;
+/// The highest pitch found, in Hz, which is well above the high E of a guitar.
+pub const HIGHEST: f64 = 2000.;
+/// How far the normalized difference of a period has to dip for it to be taken as the period, where lower values find fewer but surer pitches.
+const THRESHOLD: f64 = 0.15;
+/// The RMS level under which audio is taken as silence rather than a note.
+const SILENCE: f64 = 0.003;
+
+/// Return the pitch of mono `samples` in Hz, or [`None`] if there is no clear pitch, like in silence or noise.
+///
+/// The first half of the samples is compared with itself shifted by each possible period, so the lowest pitch that can be found is
+/// [`LOWEST`], or the sample rate over half the number of samples if that's higher.
+#[must_use]
+pub fn detect(samples: &[f64], sample_rate: f64) -> Option<f64> {
+    let window = samples.len() / 2;
+    #[allow(clippy::cast_precision_loss, reason = "blocks are short")]
+    let rms = (samples.iter().map(|sample| sample * sample).sum::<f64>() / samples.len().max(1) as f64).sqrt();
+    if window < 2 || rms < SILENCE {
+        return None;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "periods are positive and well within range")]
+    let (shortest, longest) = (((sample_rate / HIGHEST) as usize).max(2), ((sample_rate / LOWEST).ceil() as usize).min(window - 1));
+    if shortest >= longest {
+        return None;
+    }
+    let differences = normalized_differences(samples, window, longest + 1);
+    // The first period that dips under the threshold is taken, at the bottom of its dip, so that multiples of the period aren't.
+    let mut period = (shortest..longest).find(|period| differences[*period] < THRESHOLD)?;
+    while period + 1 < longest && differences[period + 1] < differences[period] {
+        period += 1;
+    }
+    // The period is refined between samples with a parabola through the dip.
+    let (before, at, after) = (differences[period - 1], differences[period], differences[period + 1]);
+    let curvature = 2f64.mul_add(-at, before + after);
+    let offset = if curvature > 0. { (before - after) / (2. * curvature) } else { 0. };
+    #[allow(clippy::cast_precision_loss, reason = "periods are short")]
+    let period = period as f64 + offset.clamp(-1., 1.);
+    Some(sample_rate / period)
+}
+
+/// Return the difference of the first `window` samples with the samples shifted by each period below `periods`, divided by the mean of the
+/// differences of the shorter periods, so that dips don't depend on how loud the audio is.
+fn normalized_differences(samples: &[f64], window: usize, periods: usize) -> Vec<f64> {
+    // The difference is the energy of both parts minus twice their correlation, which is found for every period at once through an FFT.
+    let length = (2 * window).next_power_of_two();
+    let transform = |samples: &[f64]| {
+        let mut bins = samples.iter().map(|sample| Complex::new(*sample, 0.)).collect::<Vec<_>>();
+        bins.resize(length, Complex::default());
+        FftPlanner::new().plan_fft_forward(length).process(&mut bins);
+        bins
+    };
+    let (first, whole) = (transform(&samples[..window]), transform(&samples[..2 * window]));
+    let mut correlations = first.iter().zip(&whole).map(|(first, whole)| first.conj() * whole).collect::<Vec<_>>();
+    FftPlanner::new().plan_fft_inverse(length).process(&mut correlations);
+    let energies = samples[..2 * window]
+        .iter()
+        .scan(0., |energy, sample| {
+            *energy += sample * sample;
+            Some(*energy)
+        })
+        .collect::<Vec<_>>();
+    let energy = |start: usize| energies[start + window - 1] - start.checked_sub(1).map_or(0., |before| energies[before]);
+    let mut sum = 0.;
+    (0..periods)
+        .map(|period| {
+            if period == 0 {
+                return 1.;
+            }
+            #[allow(clippy::cast_precision_loss, reason = "FFT lengths are short")]
+            let difference = 2f64.mul_add(-correlations[period].re / length as f64, energy(0) + energy(period)).max(0.);
+            sum += difference;
+            #[allow(clippy::cast_precision_loss, reason = "periods are short")]
+            if sum > 0. {
+                difference * period as f64 / sum
+            } else {
+                1.
+            }
+        })
+        .collect()
+}
+
+/// Return the MIDI key nearest to `frequency` with `reference` as the pitch of A4, and how far off it `frequency` is in cents, from -50 to 50.
+#[must_use]
+pub fn nearest_key(frequency: f64, reference: f64) -> (u8, f64) {
+    let key = 12_f64.mul_add((frequency / reference).log2(), 69.);
+    let nearest = key.round().clamp(0., 127.);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "keys are clamped to the MIDI range")]
+    (nearest as u8, (key - nearest) * 100.)
+}
@@ -0,0 +1,166 @@
+use std::ops::RangeInclusive;
+
+use super::effects::{
+    clip::ClipEffect,
+    oscillator::{OscillatorEffect, Waveform},
+    scale::ScaleEffect,
+    Effect,
+};
+
+/// An effect or generator that can be created at runtime, for example from a list shown to the user.
+pub struct RegisteredEffect {
+    /// What identifies the effect in saved projects, which stays the same if its name changes.
+    pub id: &'static str,
+    /// The name shown to the user.
+    pub name: &'static str,
+    pub category: Category,
+    /// The settings of the effect, which are passed to [`Self::build`] in this order.
+    pub parameters: &'static [Parameter],
+    build: fn(&[f64]) -> Box<dyn Effect + Send>,
+}
+
+/// What kind of effect a [`RegisteredEffect`] is, to list effects in groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// Effects that add sound rather than change it.
+    Generator,
+    /// Effects that change how loud the sound is, or how its loudness changes.
+    Dynamics,
+    /// Effects that do simple things like inverting the sound.
+    Utility,
+}
+
+impl Category {
+    /// Every category, in the order they should be listed.
+    pub const ALL: [Self; 3] = [Self::Generator, Self::Dynamics, Self::Utility];
+
+    /// Return the name shown to the user.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Generator => "Generators",
+            Self::Dynamics => "Dynamics",
+            Self::Utility => "Utilities",
+        }
+    }
+}
+
+/// Describes a setting of a [`RegisteredEffect`].
+pub struct Parameter {
+    pub name: &'static str,
+    /// The values that make sense for the parameter.
+    pub range: RangeInclusive<f64>,
+    pub default: f64,
+}
+
+impl RegisteredEffect {
+    /// Return the default value of every parameter.
+    #[must_use]
+    pub fn defaults(&self) -> Vec<f64> {
+        self.parameters.iter().map(|parameter| parameter.default).collect()
+    }
+
+    /// Return a new instance of the effect with the given parameter values. Missing values are replaced by their defaults, and values are clamped to their range.
+    #[must_use]
+    pub fn build(&self, values: &[f64]) -> Box<dyn Effect + Send> {
+        let values = self
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(index, parameter)| values.get(index).copied().unwrap_or(parameter.default).clamp(*parameter.range.start(), *parameter.range.end()))
+            .collect::<Vec<_>>();
+        (self.build)(&values)
+    }
+}
+
+/// The parameters of every oscillator.
+const OSCILLATOR: &[Parameter] = &[
+    Parameter {
+        name: "Frequency",
+        range: 20.0..=20000.0,
+        default: 440.,
+    },
+    Parameter {
+        name: "Amplitude",
+        range: 0.0..=1.0,
+        default: 0.5,
+    },
+];
+
+/// Every effect that can be inserted into a signal chain, in the order they should be listed.
+pub static EFFECTS: &[RegisteredEffect] = &[
+    RegisteredEffect {
+        id: "clip",
+        name: "Clip",
+        category: Category::Dynamics,
+        parameters: &[Parameter {
+            name: "Threshold",
+            range: 0.0..=1.0,
+            default: 0.5,
+        }],
+        build: |values| Box::new(ClipEffect::new_symmetrical(values[0])),
+    },
+    RegisteredEffect {
+        id: "scale",
+        name: "Scale",
+        category: Category::Dynamics,
+        parameters: &[Parameter {
+            name: "Factor",
+            range: -4.0..=4.0,
+            default: 1.,
+        }],
+        build: |values| Box::new(ScaleEffect::new(values[0])),
+    },
+    RegisteredEffect {
+        id: "invert",
+        name: "Invert",
+        category: Category::Utility,
+        parameters: &[],
+        build: |_| Box::new(ScaleEffect::new(-1.)),
+    },
+    RegisteredEffect {
+        id: "sine",
+        name: "Sine",
+        category: Category::Generator,
+        parameters: OSCILLATOR,
+        build: |values| Box::new(OscillatorEffect::new(Waveform::Sine, values[0], values[1])),
+    },
+    RegisteredEffect {
+        id: "square",
+        name: "Square",
+        category: Category::Generator,
+        parameters: OSCILLATOR,
+        build: |values| Box::new(OscillatorEffect::new(Waveform::Square, values[0], values[1])),
+    },
+    RegisteredEffect {
+        id: "triangle",
+        name: "Triangle",
+        category: Category::Generator,
+        parameters: OSCILLATOR,
+        build: |values| Box::new(OscillatorEffect::new(Waveform::Triangle, values[0], values[1])),
+    },
+    RegisteredEffect {
+        id: "sawtooth",
+        name: "Sawtooth",
+        category: Category::Generator,
+        parameters: OSCILLATOR,
+        build: |values| Box::new(OscillatorEffect::new(Waveform::Sawtooth, values[0], values[1])),
+    },
+];
+
+/// Return the registered effect called `name`, ignoring case.
+#[must_use]
+pub fn find(name: &str) -> Option<&'static RegisteredEffect> {
+    EFFECTS.iter().find(|effect| effect.name.eq_ignore_ascii_case(name))
+}
+
+/// Return the registered effect identified by `id`.
+#[must_use]
+pub fn find_by_id(id: &str) -> Option<&'static RegisteredEffect> {
+    EFFECTS.iter().find(|effect| effect.id == id)
+}
+
+/// Return the registered effects of `category`, in the order they should be listed.
+pub fn in_category(category: Category) -> impl Iterator<Item = &'static RegisteredEffect> {
+    EFFECTS.iter().filter(move |effect| effect.category == category)
+}
@@ -0,0 +1,38 @@
+/// Convert interleaved `samples` from one channel count and sample rate to another, using linear interpolation.
+///
+/// Missing channels repeat the existing ones in order, so mono is duplicated to every channel, and extra channels are dropped.
+#[must_use]
+pub fn convert(samples: &[f64], from_channels: usize, from_rate: f64, to_channels: usize, to_rate: f64) -> Vec<f64> {
+    let mut output = Vec::new();
+    convert_into(samples, from_channels, from_rate, to_channels, to_rate, &mut output);
+    output
+}
+
+/// Replace the contents of `output` with `samples` converted as [`convert`] does, which only allocates if `output` doesn't have the capacity for them.
+pub fn convert_into(samples: &[f64], from_channels: usize, from_rate: f64, to_channels: usize, to_rate: f64, output: &mut Vec<f64>) {
+    output.clear();
+    let from_channels = from_channels.max(1);
+    let to_channels = to_channels.max(1);
+    let frames = samples.len() / from_channels;
+    if frames == 0 || !from_rate.is_normal() || !to_rate.is_normal() {
+        return;
+    }
+    let ratio = from_rate / to_rate;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "lengths are positive and well within range")]
+    let output_frames = (frames as f64 / ratio).round() as usize;
+    output.reserve(output_frames * to_channels);
+    for frame in 0..output_frames {
+        #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+        let position = frame as f64 * ratio;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let index = position as usize;
+        #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+        let fraction = position - index as f64;
+        let current = index.min(frames - 1) * from_channels;
+        let next = (index + 1).min(frames - 1) * from_channels;
+        output.extend((0..to_channels).map(|channel| {
+            let channel = channel % from_channels;
+            (samples[next + channel] - samples[current + channel]).mul_add(fraction, samples[current + channel])
+        }));
+    }
+}
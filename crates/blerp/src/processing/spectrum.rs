@@ -0,0 +1,23 @@
+//! Measuring how loud each frequency is in a block of audio, for showing its spectrum.
+
+use std::f64::consts::TAU;
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Return the amplitude of each frequency in mono `samples`, from 0 Hz up to half the sample rate.
+///
+/// Frequencies are the sample rate divided by how many samples there are apart, and a sine at full scale has an amplitude of about 1 at its frequency. The samples are taken through a Hann window, which keeps the edges of the block from showing up as frequencies that aren't there.
+#[must_use]
+pub fn amplitudes(samples: &[f64]) -> Vec<f64> {
+    #[allow(clippy::cast_precision_loss, reason = "blocks are short")]
+    let length = samples.len() as f64;
+    #[allow(clippy::cast_precision_loss, reason = "blocks are short")]
+    let mut bins = samples
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| Complex::new(sample * 0.5 * (1. - (TAU * index as f64 / length).cos()), 0.))
+        .collect::<Vec<_>>();
+    FftPlanner::new().plan_fft_forward(bins.len()).process(&mut bins);
+    // The window halves the amplitude, and each side of the spectrum holds half of it.
+    bins.iter().take(bins.len() / 2 + 1).map(|bin| bin.norm() * 4. / length).collect()
+}
@@ -0,0 +1,173 @@
+//! Audio read ahead of playback from somewhere too slow to read from while playing, like a disk.
+//!
+//! Playback reads a [`Stream`] without waiting or allocating, and whatever reads the audio ahead asks the stream for the frames it needs next and
+//! pushes them as they're read.
+
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// How many of the low bits of [`Stream::written`] hold a frame, the others holding a generation.
+const FRAME_BITS: u32 = 40;
+const FRAME_MASK: u64 = (1 << FRAME_BITS) - 1;
+/// Generations wrap around within the bits [`Stream::written`] has left for them.
+const GENERATION_MASK: usize = (1 << (u64::BITS - FRAME_BITS)) - 1;
+
+/// Interleaved audio that starts at a frame of playback and is read ahead of it, keeping only the frames that weren't played yet.
+///
+/// Playback and whatever reads ahead only share atomics: frames are written into a circle of samples that playback isn't reading from, and published
+/// along with the generation they were read for, which playback starts anew whenever it moves away from the frames read.
+pub struct Stream {
+    channels: usize,
+    /// The frame of playback the stream starts at.
+    start: usize,
+    /// How many frames long the stream is.
+    length: usize,
+    /// How many frames are read ahead of the frame playback is at.
+    ahead: usize,
+    /// The samples read ahead as the bits of `f64`s, in a circle where frame `n` of the stream is kept at `n % frames`.
+    samples: Box<[AtomicU64]>,
+    /// The frame of the stream playback needs next, which only playback moves.
+    wanted: AtomicUsize,
+    /// How many times playback moved away from the frames read, which only playback counts.
+    generation: AtomicUsize,
+    /// The frame of the stream the frames read so far end at, along with the generation they were read for, so that both are published at once.
+    written: AtomicU64,
+    /// How many blocks of playback were missing frames that weren't read yet.
+    underruns: AtomicUsize,
+}
+
+/// Frames a [`Stream`] needs to be read next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Need {
+    /// The frames of the stream to read.
+    pub frames: Range<usize>,
+    /// How many frames are left to play before the ones read so far run out, so that streams that are about to run out are read first.
+    pub buffered: usize,
+}
+
+#[allow(clippy::cast_possible_truncation, reason = "generations are masked, and frames are far below the bits they're given")]
+const fn pack(generation: usize, frame: usize) -> u64 {
+    ((generation as u64) << FRAME_BITS) | (frame as u64 & FRAME_MASK)
+}
+
+#[allow(clippy::cast_possible_truncation, reason = "both parts fit in the bits they were packed from")]
+const fn unpack(written: u64) -> (usize, usize) {
+    ((written >> FRAME_BITS) as usize, (written & FRAME_MASK) as usize)
+}
+
+impl Stream {
+    /// Return a stream of `length` frames of `channels` channels starting at the frame `start` of playback, which keeps `ahead` frames read ahead.
+    #[must_use]
+    pub fn new(channels: usize, start: usize, length: usize, ahead: usize) -> Self {
+        let channels = channels.max(1);
+        Self {
+            channels,
+            start,
+            length,
+            ahead,
+            // Room for what's read ahead and a block being played, so that reading ahead never writes over what's played.
+            samples: (0..(ahead * 2).max(1) * channels).map(|_| AtomicU64::new(0)).collect(),
+            wanted: AtomicUsize::new(0),
+            generation: AtomicUsize::new(0),
+            written: AtomicU64::new(pack(0, 0)),
+            underruns: AtomicUsize::new(0),
+        }
+    }
+
+    #[must_use]
+    pub const fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// The frame of playback the stream starts at.
+    #[must_use]
+    pub const fn start(&self) -> usize {
+        self.start
+    }
+
+    #[must_use]
+    pub const fn length(&self) -> usize {
+        self.length
+    }
+
+    /// How many blocks of playback were missing frames so far.
+    #[must_use]
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Add the frames of the stream that play in the block `output` starting at the frame `position` of playback, dropping the frames before it.
+    /// Frames that weren't read yet are left silent.
+    ///
+    /// This is called while playing, so it neither waits nor allocates, and only one thread plays a stream at a time.
+    pub fn read(&self, position: usize, output: &mut [f64]) {
+        let frames = output.len() / self.channels;
+        let (from, to) = (position.max(self.start), (position + frames).min(self.start + self.length));
+        // Before the stream starts, its first frames are read ahead.
+        let first = from.saturating_sub(self.start).min(self.length);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let wanted = self.wanted.load(Ordering::Relaxed);
+        let (written_generation, written) = unpack(self.written.load(Ordering::Acquire));
+        let end = if written_generation == generation { written } else { wanted };
+        if first < wanted || first > end {
+            // Playback moved away from the frames read, so they're dropped and reading starts over from here.
+            self.wanted.store(first, Ordering::Relaxed);
+            self.generation.store((generation + 1) & GENERATION_MASK, Ordering::Release);
+            if from < to {
+                self.underruns.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
+        // The frames before this block are played, so they can be written over.
+        self.wanted.store(first, Ordering::Release);
+        if from >= to {
+            return;
+        }
+        let last = to - self.start;
+        let available = end.min(last) - first;
+        let offset = (from - position) * self.channels;
+        let first_sample = first * self.channels;
+        for (index, output) in output[offset..offset + available * self.channels].iter_mut().enumerate() {
+            *output += f64::from_bits(self.samples[(first_sample + index) % self.samples.len()].load(Ordering::Relaxed));
+        }
+        if available < last - first {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Return the frames to read next to stay far enough ahead of playback, or [`None`] if enough are read. Reading starts over from where
+    /// playback is if it moved away from the frames read so far.
+    ///
+    /// Only one thread reads a stream ahead at a time.
+    #[must_use]
+    pub fn need(&self) -> Option<Need> {
+        let generation = self.generation.load(Ordering::Acquire);
+        let wanted = self.wanted.load(Ordering::Acquire);
+        let (written_generation, mut written) = unpack(self.written.load(Ordering::Relaxed));
+        if written_generation != generation {
+            written = wanted;
+            self.written.store(pack(generation, written), Ordering::Release);
+        }
+        let target = (wanted + self.ahead).min(self.length);
+        (written < target).then(|| Need { frames: written..target, buffered: written.saturating_sub(wanted) })
+    }
+
+    /// Add interleaved `samples` read from the frame `first` of the stream after the ones read so far. Samples that don't follow them, because
+    /// playback moved in the meantime, are dropped.
+    pub fn push(&self, first: usize, samples: &[f64]) {
+        let (generation, written) = unpack(self.written.load(Ordering::Relaxed));
+        if first != written {
+            return;
+        }
+        // Frames up to a whole circle past the one playback needs are written where frames it's done with were.
+        let room = (self.wanted.load(Ordering::Acquire) + self.samples.len() / self.channels).saturating_sub(first);
+        let frames = (samples.len() / self.channels).min(self.length.saturating_sub(first)).min(room);
+        let first_sample = first * self.channels;
+        for (index, sample) in samples[..frames * self.channels].iter().enumerate() {
+            self.samples[(first_sample + index) % self.samples.len()].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.written.store(pack(generation, first + frames), Ordering::Release);
+    }
+}
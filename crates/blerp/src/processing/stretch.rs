@@ -0,0 +1,114 @@
+use std::{
+    f64::consts::TAU,
+    fmt::{self, Display, Formatter},
+};
+
+use itertools::Itertools;
+
+use super::effects::{Effect, EffectError, Stuff};
+
+/// Length of a single grain, in seconds.
+const WINDOW_LENGTH: f64 = 0.05;
+/// How far (as a fraction of the window) a grain may be moved to line up with the previous one.
+const TOLERANCE: f64 = 0.125;
+/// Only every n-th frame is compared when searching for the best alignment, which is plenty for finding the waveform's phase.
+const SEARCH_STRIDE: usize = 4;
+
+/// Change the length of `samples` by `ratio` without changing their pitch, using waveform similarity overlap-add (WSOLA).
+///
+/// `samples` are interleaved with `channels` channels. A `ratio` of `2.` makes the output twice as long (half as fast), `0.5` makes it half as long.
+#[must_use]
+pub fn time_stretch(samples: &[f64], channels: usize, sample_rate: f64, ratio: f64) -> Vec<f64> {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    if frames == 0 || !ratio.is_normal() || (ratio - 1.).abs() < f64::EPSILON {
+        return samples.to_vec();
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "window sizes are small and positive")]
+    let window_size = ((WINDOW_LENGTH * sample_rate) as usize).clamp(2, frames.max(2));
+    let output_hop = window_size / 2;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "window sizes are small and positive")]
+    let tolerance = (window_size as f64 * TOLERANCE) as usize;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "lengths are positive and well within range")]
+    let output_frames = (frames as f64 * ratio).round() as usize;
+    // Offset by half a frame so that no weight is exactly zero, which keeps the very first frame from being silenced.
+    #[allow(clippy::cast_precision_loss, reason = "window sizes are small")]
+    let window = (0..window_size).map(|index| 0.5_f64.mul_add(-(TAU * (index as f64 + 0.5) / window_size as f64).cos(), 0.5)).collect_vec();
+    // A mono mixdown is enough to find where grains line up, regardless of the number of channels.
+    #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+    let mono = samples.chunks_exact(channels).map(|frame| frame.iter().sum::<f64>() / channels as f64).collect_vec();
+    let frame_at = |position: usize| mono.get(position).copied().unwrap_or_default();
+    let last_start = frames.saturating_sub(window_size);
+
+    let mut output = vec![0.; (output_frames + window_size) * channels];
+    let mut weights = vec![0.; output_frames + window_size];
+    let mut previous_start = 0;
+    for (grain, output_start) in (0..output_frames).step_by(output_hop).enumerate() {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "positions are positive and well within range")]
+        let nominal_start = ((output_start as f64 / ratio).round() as usize).min(last_start);
+        let start = if grain == 0 {
+            0
+        } else {
+            // Pick the grain that best continues the previous one, so that overlapping grains add up in phase.
+            let natural_start = previous_start + output_hop;
+            let similarity = |start: usize| (0..output_hop).step_by(SEARCH_STRIDE).map(|offset| frame_at(start + offset) * frame_at(natural_start + offset)).sum::<f64>();
+            (nominal_start.saturating_sub(tolerance)..=(nominal_start + tolerance).min(last_start))
+                .map(|start| (start, similarity(start)))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map_or(nominal_start, |(start, _)| start)
+        };
+        for (offset, weight) in window.iter().enumerate() {
+            let Some(input_frame) = samples.get((start + offset) * channels..(start + offset + 1) * channels) else {
+                break;
+            };
+            let output_frame = output_start + offset;
+            for (channel, sample) in input_frame.iter().enumerate() {
+                output[output_frame * channels + channel] += sample * weight;
+            }
+            weights[output_frame] += weight;
+        }
+        previous_start = start;
+    }
+
+    output.truncate(output_frames * channels);
+    for (frame, weight) in output.chunks_exact_mut(channels).zip(weights) {
+        if weight > f64::EPSILON {
+            for sample in frame {
+                *sample /= weight;
+            }
+        }
+    }
+    output
+}
+
+/// An effect that changes the length of a sequence of samples by a ratio without changing its pitch. See [`time_stretch`].
+pub struct StretchEffect {
+    ratio: f64,
+}
+
+impl Display for StretchEffect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Stretch")
+    }
+}
+
+impl Effect for StretchEffect {
+    fn apply<'a>(&self, mut input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
+        input.samples = time_stretch(&input.samples, input.channels, input.sample_rate, self.ratio).into();
+        Ok(input)
+    }
+}
+
+impl StretchEffect {
+    /// Return a new [`StretchEffect`] which makes samples `ratio` times as long.
+    #[must_use]
+    pub const fn new(ratio: f64) -> Self {
+        Self { ratio }
+    }
+
+    /// Return a new [`StretchEffect`] which conforms material recorded at `source_bpm` to `target_bpm`.
+    #[must_use]
+    pub fn from_tempos(source_bpm: f64, target_bpm: f64) -> Self {
+        Self::new(source_bpm / target_bpm)
+    }
+}
@@ -0,0 +1,579 @@
+//! A subtractive synthesizer, which is what MIDI is played with unless it's routed elsewhere.
+//!
+//! Every note is played by a voice of its own, made of two band-limited oscillators and noise going through a resonant low-pass filter, whose cutoff
+//! follows an envelope, and then through an amplifier with an envelope of its own. Two LFOs can wobble the pitch, the cutoff or the volume, and the
+//! oscillators can be stacked into several detuned copies of themselves for a thicker sound, which is called unison.
+//!
+//! The synth is set up with a list of values, like the effects of the [registry](super::registry), so that its settings can be shown and saved
+//! without knowing what they are.
+
+use std::f64::consts::{PI, TAU};
+
+use super::{
+    effects::oscillator::Waveform,
+    instrument::{frequency, NoteExpression, DEFAULT_SLIDE},
+    registry::Parameter,
+};
+use crate::midi::{Dimension, Expression, Note};
+
+/// How far an LFO at full depth bends the pitch, in semitones.
+const LFO_PITCH: f64 = 1.;
+/// How far an LFO at full depth moves the cutoff, in octaves.
+const LFO_CUTOFF: f64 = 2.;
+/// How much an LFO at full depth changes the volume, as a fraction of it.
+const LFO_VOLUME: f64 = 0.5;
+/// How far a slide all the way up or down moves the cutoff, in octaves.
+const SLIDE_CUTOFF: f64 = 4.;
+/// The key the cutoff is set for, which it moves away from with the keys played as much as the key tracking says. This is middle C.
+const TRACKING_KEY: f64 = 60.;
+/// How many steps the pitch of a note is divided into, in cents.
+const CENTS: f64 = 1200.;
+/// How many copies of the oscillators can be played for unison.
+const MAX_VOICES: usize = VOICES.len();
+/// How many notes a [`Live`] synth plays at once.
+const LIVE_NOTES: usize = 32;
+
+/// A setting of the synth.
+pub struct Setting {
+    pub parameter: Parameter,
+    /// What each whole value of the setting stands for, from 0 up, if it picks one of a few things rather than setting an amount.
+    pub choices: &'static [&'static str],
+}
+
+/// Settings of the synth that belong together, like those of an oscillator.
+pub struct Section {
+    pub name: &'static str,
+    pub settings: &'static [Setting],
+}
+
+pub(crate) const fn amount(name: &'static str, start: f64, end: f64, default: f64) -> Setting {
+    Setting { parameter: Parameter { name, range: start..=end, default }, choices: &[] }
+}
+
+pub(crate) const fn choice(name: &'static str, choices: &'static [&'static str], default: usize) -> Setting {
+    #[allow(clippy::cast_precision_loss, reason = "there are only a few choices")]
+    let (end, default) = ((choices.len() - 1) as f64, default as f64);
+    Setting { parameter: Parameter { name, range: 0.0..=end, default }, choices }
+}
+
+/// The names of the waveforms, in the order of [`Waveform::ALL`].
+const WAVEFORMS: &[&str] = &["Sine", "Square", "Triangle", "Sawtooth"];
+const OCTAVES: &[&str] = &["-2", "-1", "0", "+1", "+2"];
+const TARGETS: &[&str] = &["Pitch", "Cutoff", "Volume"];
+const VOICES: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8"];
+
+/// Every setting of the synth, in sections, in the order their values are given to [`Synth::new`].
+pub static SECTIONS: &[Section] = &[
+    Section {
+        name: "Oscillator 1",
+        settings: &[choice("Waveform", WAVEFORMS, 3), choice("Octave", OCTAVES, 2), amount("Detune", -100., 100., 0.), amount("Level", 0., 1., 0.7)],
+    },
+    Section {
+        name: "Oscillator 2",
+        settings: &[choice("Waveform", WAVEFORMS, 1), choice("Octave", OCTAVES, 1), amount("Detune", -100., 100., 7.), amount("Level", 0., 1., 0.4)],
+    },
+    Section {
+        name: "Noise",
+        settings: &[amount("Level", 0., 1., 0.)],
+    },
+    Section {
+        name: "Filter",
+        settings: &[amount("Cutoff", 20., 20000., 1200.), amount("Resonance", 0., 1., 0.3), amount("Envelope", -8., 8., 3.), amount("Key tracking", 0., 1., 0.5)],
+    },
+    Section {
+        name: "Filter envelope",
+        settings: &[amount("Attack", 0., 5., 0.005), amount("Decay", 0., 5., 0.4), amount("Sustain", 0., 1., 0.2), amount("Release", 0., 5., 0.3)],
+    },
+    Section {
+        name: "Amplifier envelope",
+        settings: &[amount("Attack", 0., 5., 0.005), amount("Decay", 0., 5., 0.6), amount("Sustain", 0., 1., 0.6), amount("Release", 0., 5., 0.2)],
+    },
+    Section {
+        name: "LFO 1",
+        settings: &[choice("Waveform", WAVEFORMS, 0), amount("Rate", 0.05, 20., 5.), amount("Depth", 0., 1., 0.), choice("Target", TARGETS, 0)],
+    },
+    Section {
+        name: "LFO 2",
+        settings: &[choice("Waveform", WAVEFORMS, 2), amount("Rate", 0.05, 20., 0.5), amount("Depth", 0., 1., 0.), choice("Target", TARGETS, 1)],
+    },
+    Section {
+        name: "Unison",
+        settings: &[choice("Voices", VOICES, 0), amount("Detune", 0., 100., 20.)],
+    },
+    Section {
+        name: "Output",
+        settings: &[amount("Volume", 0., 1., 0.2)],
+    },
+];
+
+/// Return every setting of the synth, in the order their values are given to [`Synth::new`].
+pub fn settings() -> impl Iterator<Item = &'static Setting> {
+    SECTIONS.iter().flat_map(|section| section.settings)
+}
+
+/// Return the default value of every setting.
+#[must_use]
+pub fn defaults() -> Vec<f64> {
+    settings().map(|setting| setting.parameter.default).collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Oscillator {
+    waveform: Waveform,
+    /// How far the oscillator is from the note, in octaves and cents.
+    octave: f64,
+    detune: f64,
+    level: f64,
+}
+
+/// How a level rises when a note starts, and falls back when it's released. Times are in seconds.
+#[derive(Debug, Clone, Copy)]
+struct Envelope {
+    attack: f64,
+    decay: f64,
+    sustain: f64,
+    release: f64,
+}
+
+impl Envelope {
+    /// Return the level `time` seconds into a note held for `length` seconds.
+    fn level(self, time: f64, length: f64) -> f64 {
+        let held = |time: f64| {
+            if time < self.attack {
+                time / self.attack
+            } else {
+                let decayed = ((time - self.attack) / self.decay.max(f64::EPSILON)).min(1.);
+                decayed.mul_add(self.sustain - 1., 1.)
+            }
+        };
+        if time < length {
+            held(time)
+        } else {
+            held(length) * (1. - (time - length) / self.release.max(f64::EPSILON)).max(0.)
+        }
+    }
+}
+
+/// What an LFO wobbles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Pitch,
+    Cutoff,
+    Volume,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lfo {
+    waveform: Waveform,
+    /// How many times a second the LFO goes around.
+    rate: f64,
+    depth: f64,
+    target: Target,
+}
+
+/// The synth, with its settings. See the [module](self) for what it's made of.
+#[derive(Debug, Clone)]
+pub struct Synth {
+    oscillators: [Oscillator; 2],
+    noise: f64,
+    /// The cutoff of the filter in hertz, and how far the filter envelope moves it in octaves.
+    cutoff: f64,
+    resonance: f64,
+    envelope_amount: f64,
+    key_tracking: f64,
+    filter_envelope: Envelope,
+    amplifier: Envelope,
+    lfos: [Lfo; 2],
+    /// How many copies of the oscillators are played, and how far apart the copies furthest apart are, in cents.
+    voices: usize,
+    unison_detune: f64,
+    volume: f64,
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl Synth {
+    /// Return a synth with the given values of its [settings](settings). Missing values are replaced by their defaults, and values are clamped to
+    /// their range.
+    #[must_use]
+    pub fn new(values: &[f64]) -> Self {
+        let values = settings().enumerate().map(|(index, setting)| {
+            let parameter = &setting.parameter;
+            values.get(index).copied().unwrap_or(parameter.default).clamp(*parameter.range.start(), *parameter.range.end())
+        });
+        let mut values = Values(values);
+        let oscillators = [values.oscillator(), values.oscillator()];
+        let noise = values.amount();
+        let (cutoff, resonance, envelope_amount, key_tracking) = (values.amount(), values.amount(), values.amount(), values.amount());
+        let (filter_envelope, amplifier) = (values.envelope(), values.envelope());
+        let lfos = [values.lfo(), values.lfo()];
+        let voices = values.choice() + 1;
+        let (unison_detune, volume) = (values.amount(), values.amount());
+        Self {
+            oscillators,
+            noise,
+            cutoff,
+            resonance,
+            envelope_amount,
+            key_tracking,
+            filter_envelope,
+            amplifier,
+            lfos,
+            voices,
+            unison_detune,
+            volume,
+        }
+    }
+
+    /// Play `notes`, timed in beats at `tempo` BPM, and return the mono samples.
+    ///
+    /// Notes follow the `expression` of their channels, so that each note of an MPE controller is bent, pressed and slid on its own: pitch bends
+    /// move the note, pressure makes it louder once the key is pressed and slide opens or closes the filter.
+    #[must_use]
+    pub fn play(&self, notes: &[Note], expression: &[Expression], tempo: f64, sample_rate: f64) -> Vec<f64> {
+        let seconds = 60. / tempo;
+        let end = notes.iter().map(|note| (note.start + note.length).mul_add(seconds, self.amplifier.release)).fold(0., f64::max);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the length is positive and well within range")]
+        let mut samples = vec![0.; (end * sample_rate).ceil() as usize];
+        for note in notes {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "times are positive and well within range")]
+            let start = ((note.start * seconds * sample_rate) as usize).min(samples.len());
+            for (sample, value) in samples[start..].iter_mut().zip(Voice::new(self, note, expression, seconds, sample_rate)) {
+                *sample += value;
+            }
+        }
+        samples
+    }
+
+    /// Return how far the LFOs aimed at `target` move it at `time` seconds into a note, from -1 to 1 for each LFO at full depth.
+    fn lfo(&self, target: Target, time: f64) -> f64 {
+        self.lfos.iter().filter(|lfo| lfo.target == target).map(|lfo| lfo.depth * naive(lfo.waveform, (lfo.rate * time).fract())).sum()
+    }
+}
+
+/// Reads the values given to [`Synth::new`] in order.
+struct Values<I>(I);
+
+impl<I: Iterator<Item = f64>> Values<I> {
+    fn amount(&mut self) -> f64 {
+        self.0.next().unwrap_or_default()
+    }
+
+    fn choice(&mut self) -> usize {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "choices are clamped to their range")]
+        let choice = self.amount().round() as usize;
+        choice
+    }
+
+    fn oscillator(&mut self) -> Oscillator {
+        let waveform = Waveform::ALL[self.choice()];
+        #[allow(clippy::cast_precision_loss, reason = "there are only a few octaves")]
+        let octave = self.choice() as f64 - 2.;
+        Oscillator { waveform, octave, detune: self.amount(), level: self.amount() }
+    }
+
+    fn envelope(&mut self) -> Envelope {
+        Envelope { attack: self.amount(), decay: self.amount(), sustain: self.amount(), release: self.amount() }
+    }
+
+    fn lfo(&mut self) -> Lfo {
+        let waveform = Waveform::ALL[self.choice()];
+        let (rate, depth) = (self.amount(), self.amount());
+        let target = [Target::Pitch, Target::Cutoff, Target::Volume][self.choice()];
+        Lfo { waveform, rate, depth, target }
+    }
+}
+
+/// Return the value of `waveform` at `phase`, from 0 to 1 through its cycle, without limiting its band.
+fn naive(waveform: Waveform, phase: f64) -> f64 {
+    match waveform {
+        Waveform::Sine => (TAU * phase).sin(),
+        Waveform::Square => {
+            if phase < 0.5 {
+                1.
+            } else {
+                -1.
+            }
+        }
+        Waveform::Triangle => 4f64.mul_add((phase - 0.5).abs(), -1.),
+        Waveform::Sawtooth => 2f64.mul_add(phase, -1.),
+    }
+}
+
+/// Return the correction that smooths a jump from 1 to -1 at the start of a cycle, for a wave at `phase` that moves by `increment` every
+/// sample, which is called a polynomial band-limited step. It keeps the harmonics the jump makes above the Nyquist frequency from folding back.
+fn poly_blep(phase: f64, increment: f64) -> f64 {
+    if phase < increment {
+        let t = phase / increment;
+        2f64.mul_add(t, -t * t) - 1.
+    } else if phase > 1. - increment {
+        let t = (phase - 1.) / increment;
+        2f64.mul_add(t, t * t) + 1.
+    } else {
+        0.
+    }
+}
+
+/// Return the value of `waveform` at `phase`, for a wave that moves by `increment` every sample, with its band limited.
+fn band_limited(waveform: Waveform, phase: f64, increment: f64) -> f64 {
+    match waveform {
+        // Sines have no harmonics, and those of triangles fade fast enough not to be heard folding back.
+        Waveform::Sine | Waveform::Triangle => naive(waveform, phase),
+        Waveform::Square => naive(waveform, phase) + poly_blep(phase, increment) - poly_blep((phase + 0.5).fract(), increment),
+        Waveform::Sawtooth => naive(waveform, phase) - poly_blep(phase, increment),
+    }
+}
+
+/// A resonant low-pass filter, which is a state variable filter discretized so that its cutoff can change every sample without blowing up.
+#[derive(Default)]
+pub(crate) struct Filter {
+    low: f64,
+    band: f64,
+}
+
+impl Filter {
+    pub fn process(&mut self, input: f64, cutoff: f64, resonance: f64, sample_rate: f64) -> f64 {
+        let g = (PI * cutoff.clamp(1., sample_rate * 0.49) / sample_rate).tan();
+        // The damping is kept just above 0, where the filter would ring forever.
+        let k = 1.95f64.mul_add(-resonance, 2.);
+        let a1 = 1. / g.mul_add(g + k, 1.);
+        let (a2, a3) = (g * a1, g * g * a1);
+        let v3 = input - self.low;
+        let v1 = a1.mul_add(self.band, a2 * v3);
+        let v2 = a2.mul_add(self.band, a3.mul_add(v3, self.low));
+        self.band = 2f64.mul_add(v1, -self.band);
+        self.low = 2f64.mul_add(v2, -self.low);
+        v2
+    }
+}
+
+/// Where a note is and how it's played there, which is all a [`Sound`] needs to know about it.
+struct Moment {
+    key: u8,
+    velocity: u8,
+    /// How far into the note this is, and how long it's held for, in seconds. Notes played live are held for ever until they're released.
+    time: f64,
+    length: f64,
+    expressed: Expressed,
+}
+
+/// How far a note is bent, pressed and slid.
+#[derive(Debug, Clone, Copy)]
+struct Expressed {
+    /// How far the note is bent, in semitones.
+    bend: f64,
+    /// How hard the key is pressed, or [`None`] until that's sent.
+    pressure: Option<f64>,
+    slide: f64,
+}
+
+impl Default for Expressed {
+    fn default() -> Self {
+        Self { bend: 0., pressure: None, slide: DEFAULT_SLIDE }
+    }
+}
+
+/// What a note keeps from one sample to the next, which is where its oscillators are in their cycles, its filter and its noise.
+struct Sound {
+    /// Where the oscillators of each copy for unison are in their cycles. There's room for every copy the synth can have, and it plays as many as it has.
+    phases: [[f64; 2]; MAX_VOICES],
+    filter: Filter,
+    /// The state of the generator of the noise, which is the same for every note so that playing again sounds the same.
+    noise: u64,
+}
+
+impl Sound {
+    fn new() -> Self {
+        #[allow(clippy::cast_precision_loss, reason = "there are only a few voices")]
+        // The copies start at different points in their cycles, so that they don't cancel each other out.
+        let phases = std::array::from_fn(|index| [(index as f64 * 0.37).fract(); 2]);
+        Self { phases, filter: Filter::default(), noise: 0x9e37_79b9_7f4a_7c15 }
+    }
+
+    /// Return the next white noise sample, from -1 to 1.
+    fn noise(&mut self) -> f64 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 7;
+        self.noise ^= self.noise << 17;
+        #[allow(clippy::cast_precision_loss, reason = "the noise doesn't need every bit")]
+        let value = (self.noise >> 11) as f64 / (1u64 << 53) as f64;
+        value.mul_add(2., -1.)
+    }
+
+    /// Return the sum of the oscillators of `synth` at `pitch` hertz, moving them on by a sample.
+    fn oscillators(&mut self, synth: &Synth, pitch: f64, sample_rate: f64) -> f64 {
+        let voices = synth.voices.min(MAX_VOICES);
+        let mut sum = 0.;
+        for (index, phases) in self.phases[..voices].iter_mut().enumerate() {
+            #[allow(clippy::cast_precision_loss, reason = "there are only a few voices")]
+            let spread = if voices == 1 { 0. } else { index as f64 / (voices - 1) as f64 - 0.5 };
+            for (oscillator, phase) in synth.oscillators.iter().zip(phases) {
+                let increment = pitch * (oscillator.octave + spread.mul_add(synth.unison_detune, oscillator.detune) / CENTS).exp2() / sample_rate;
+                sum += oscillator.level * band_limited(oscillator.waveform, *phase, increment);
+                *phase = (*phase + increment).fract();
+            }
+        }
+        #[allow(clippy::cast_precision_loss, reason = "there are only a few voices")]
+        let voices = voices as f64;
+        sum / voices.sqrt()
+    }
+
+    /// Return the sample of `synth` playing the note at `moment`, moving on to the next.
+    fn next(&mut self, synth: &Synth, moment: &Moment, sample_rate: f64) -> f64 {
+        let Moment { key, velocity, time, length, expressed } = *moment;
+        let pitch = frequency(key) * (synth.lfo(Target::Pitch, time).mul_add(LFO_PITCH, expressed.bend) / 12.).exp2();
+        let source = synth.noise.mul_add(self.noise(), self.oscillators(synth, pitch, sample_rate));
+        let octaves = [
+            synth.envelope_amount * synth.filter_envelope.level(time, length),
+            synth.key_tracking * (f64::from(key) - TRACKING_KEY) / 12.,
+            synth.lfo(Target::Cutoff, time) * LFO_CUTOFF,
+            (expressed.slide - DEFAULT_SLIDE) * SLIDE_CUTOFF,
+        ];
+        let filtered = self.filter.process(source, synth.cutoff * octaves.iter().sum::<f64>().exp2(), synth.resonance, sample_rate);
+        let loudness = f64::from(velocity) / 127. * expressed.pressure.map_or(1., |pressure| 0.5 + pressure);
+        let tremolo = synth.lfo(Target::Volume, time).mul_add(LFO_VOLUME, 1.);
+        synth.volume * loudness * tremolo * synth.amplifier.level(time, length) * filtered
+    }
+}
+
+/// A note being played by a [`Synth`], which returns its samples until its release has ended.
+struct Voice<'a> {
+    synth: &'a Synth,
+    note: &'a Note,
+    expression: NoteExpression<'a>,
+    /// How long a beat lasts, in seconds.
+    seconds: f64,
+    sample_rate: f64,
+    index: usize,
+    count: usize,
+    sound: Sound,
+}
+
+impl<'a> Voice<'a> {
+    fn new(synth: &'a Synth, note: &'a Note, expression: &'a [Expression], seconds: f64, sample_rate: f64) -> Self {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "times are positive and well within range")]
+        let count = (note.length.mul_add(seconds, synth.amplifier.release) * sample_rate) as usize;
+        Self {
+            synth,
+            note,
+            expression: NoteExpression::new(note, expression),
+            seconds,
+            sample_rate,
+            index: 0,
+            count,
+            sound: Sound::new(),
+        }
+    }
+}
+
+impl Iterator for Voice<'_> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        if self.index >= self.count {
+            return None;
+        }
+        #[allow(clippy::cast_precision_loss, reason = "indices are well within range")]
+        let time = self.index as f64 / self.sample_rate;
+        self.index += 1;
+        self.expression.advance(self.note.start + time / self.seconds);
+        let expressed = Expressed { bend: self.expression.bend, pressure: self.expression.pressure, slide: self.expression.slide };
+        let moment = Moment { key: self.note.key, velocity: self.note.velocity, time, length: self.note.length * self.seconds, expressed };
+        Some(self.sound.next(self.synth, &moment, self.sample_rate))
+    }
+}
+
+/// A note played on a [`Live`] synth.
+struct Held {
+    channel: u8,
+    key: u8,
+    velocity: u8,
+    /// How many samples were played since the note was pressed, and after how many it was released, if it was.
+    index: usize,
+    released: Option<usize>,
+    sound: Sound,
+}
+
+/// A synth played live, like from a MIDI controller, whose notes last from when they're pressed to when they're released rather than being known
+/// ahead like those given to [`Synth::play`].
+///
+/// Notes follow the expression of their channels as it's sent, so that each note of an MPE controller is bent, pressed and slid on its own, and
+/// sound the same as they would played from a file. Nothing is allocated once the synth is made, so it can be played from an audio callback.
+pub struct Live {
+    synth: Synth,
+    sample_rate: f64,
+    /// The notes held or releasing, oldest first. Once there are [`LIVE_NOTES`], the oldest is cut off for the next.
+    notes: Vec<Held>,
+    /// How the notes of each channel are played, as of the last expression sent on it.
+    channels: [Expressed; 16],
+}
+
+impl Live {
+    #[must_use]
+    pub fn new(synth: Synth, sample_rate: f64) -> Self {
+        Self { synth, sample_rate, notes: Vec::with_capacity(LIVE_NOTES), channels: [Expressed::default(); 16] }
+    }
+
+    /// Play the notes with `synth` from now on, including those being held.
+    pub const fn set_synth(&mut self, synth: Synth) {
+        self.synth = synth;
+    }
+
+    /// Start playing `key` on `channel`, releasing it first if it's already held there.
+    pub fn press(&mut self, channel: u8, key: u8, velocity: u8) {
+        self.release(channel, key);
+        if self.notes.len() == LIVE_NOTES {
+            self.notes.remove(0);
+        }
+        self.notes.push(Held { channel, key, velocity, index: 0, released: None, sound: Sound::new() });
+    }
+
+    /// Release `key` on `channel`, which then fades out over the release of the amplifier.
+    pub fn release(&mut self, channel: u8, key: u8) {
+        for note in &mut self.notes {
+            if note.channel == channel && note.key == key && note.released.is_none() {
+                note.released = Some(note.index);
+            }
+        }
+    }
+
+    /// Set how the notes of `channel` are played in `dimension`, both those held and those played on it later.
+    pub fn express(&mut self, channel: u8, dimension: Dimension, value: f64) {
+        let Some(expressed) = self.channels.get_mut(usize::from(channel)) else {
+            return;
+        };
+        match dimension {
+            Dimension::PitchBend => expressed.bend = value,
+            Dimension::Pressure => expressed.pressure = Some(value),
+            Dimension::Slide => expressed.slide = value,
+        }
+    }
+
+    /// Add the next samples of the notes to `buffer`, which has `channels` interleaved channels, each of which gets the same.
+    pub fn process(&mut self, buffer: &mut [f64], channels: usize) {
+        if self.notes.is_empty() {
+            return;
+        }
+        let (synth, sample_rate) = (&self.synth, self.sample_rate);
+        for frame in buffer.chunks_mut(channels.max(1)) {
+            let mut sum = 0.;
+            for note in &mut self.notes {
+                #[allow(clippy::cast_precision_loss, reason = "indices are well within range")]
+                let (time, length) = (note.index as f64 / sample_rate, note.released.map_or(f64::INFINITY, |released| released as f64 / sample_rate));
+                note.index += 1;
+                let expressed = self.channels.get(usize::from(note.channel)).copied().unwrap_or_default();
+                sum += note.sound.next(synth, &Moment { key: note.key, velocity: note.velocity, time, length, expressed }, sample_rate);
+            }
+            for sample in frame {
+                *sample += sum;
+            }
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "releases are short and positive")]
+        let release = (synth.amplifier.release * sample_rate) as usize;
+        self.notes.retain(|note| note.released.is_none_or(|released| note.index < released + release));
+    }
+}
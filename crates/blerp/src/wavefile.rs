@@ -15,7 +15,7 @@ use nom::{
 };
 use nom_locate::LocatedSpan;
 use num::traits::ToBytes;
-use read::{wave_file, Input};
+use read::{wave_file, wave_format, Input};
 use thiserror::Error;
 
 #[derive(Debug, Clone)]
@@ -27,6 +27,15 @@ pub struct WaveFile {
     pub data: Vec<u8>,
 }
 
+/// The format of a wave file, without its samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaveFormat {
+    pub format: Format,
+    pub channels: NonZeroU16,
+    pub sample_rate: u32,
+    pub bytes_per_sample: u16,
+}
+
 #[repr(u16)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
@@ -229,6 +238,21 @@ impl WaveFile {
     }
 }
 
+impl WaveFormat {
+    /// Read the [`WaveFormat`] from the start of a wave file, which only has to be long enough to hold the chunks up to the format chunk.
+    /// # Errors
+    /// Returns a [`ReadError`] if the start is not that of a valid wave file.
+    pub fn read(bytes: &[u8]) -> Result<Self, ReadError> {
+        complete(wave_format)(LocatedSpan::new(bytes)).map(|(_, format)| format).map_err(|error| match error {
+            Err::Incomplete(_) => {
+                // SAFETY: we called `complete`, so `Err::Incomplete` is impossible.
+                unsafe { unreachable_unchecked() }
+            }
+            Err::Error(error) | Err::Failure(error) => error,
+        })
+    }
+}
+
 mod read {
     use std::num::NonZeroU16;
 
@@ -236,14 +260,14 @@ mod read {
         branch::{alt, permutation},
         bytes::complete::{tag, take},
         combinator::{all_consuming, consumed, map, map_res, opt, verify},
-        multi::{length_data, length_value},
+        multi::{length_data, length_value, many0},
         number::complete::{le_u16, le_u32},
         sequence::{preceded, terminated, tuple},
         IResult,
     };
     use nom_locate::LocatedSpan;
 
-    use super::{Format, ReadError, ReadErrorKind, WaveFile};
+    use super::{Format, ReadError, ReadErrorKind, WaveFile, WaveFormat};
 
     pub type Input<'a> = LocatedSpan<&'a [u8]>;
     type FormatChunk = (Format, NonZeroU16, u32, u16, u16);
@@ -306,6 +330,23 @@ mod read {
         )(input)
     }
 
+    /// Skip a chunk other than the format chunk, like `JUNK` or `bext`.
+    fn other_chunk(input: Input) -> IResult<Input, Input, ReadError> {
+        preceded(verify(take(4_usize), |id: &Input| *id.fragment() != b"fmt "), length_data(le_u32))(input)
+    }
+
+    pub fn wave_format(input: Input) -> IResult<Input, WaveFormat, ReadError> {
+        map(
+            preceded(tuple((tag(b"RIFF"), le_u32, tag(b"WAVE"), many0(other_chunk))), format_chunk),
+            |(format, channels, sample_rate, _, bits_per_sample)| WaveFormat {
+                format,
+                channels,
+                sample_rate,
+                bytes_per_sample: bits_per_sample / 8,
+            },
+        )(input)
+    }
+
     fn data_chunk(input: Input) -> IResult<Input, Input, ReadError> {
         preceded(
             tag(b"data"),
@@ -0,0 +1,81 @@
+use std::f64::consts::TAU;
+
+use blerp::processing::analysis::{analyse, onsets, Key};
+
+const SAMPLE_RATE: f64 = 44100.;
+
+/// Return `seconds` of clicks at `tempo` BPM, each a short burst of decaying noise.
+fn clicks(tempo: f64, seconds: f64) -> Vec<f64> {
+    accented_clicks(tempo, seconds, 1.)
+}
+
+/// Return `seconds` of clicks at `tempo` BPM, where every other click is `quieter` times as loud.
+fn accented_clicks(tempo: f64, seconds: f64, quieter: f64) -> Vec<f64> {
+    let beat = 60. / tempo;
+    let mut noise = 1_u32;
+    (0..(seconds * SAMPLE_RATE) as usize)
+        .map(|index| {
+            noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let time = index as f64 / SAMPLE_RATE;
+            let volume = if ((time / beat) as usize).is_multiple_of(2) { 1. } else { quieter };
+            (f64::from(noise >> 8) / f64::from(1 << 24) - 0.5) * (-(time % beat) * 60.).exp() * volume
+        })
+        .collect()
+}
+
+/// Return `seconds` of the chords made of `keys`, one after the other, each lasting a second.
+fn chords(chords: &[&[u8]], seconds: f64) -> Vec<f64> {
+    (0..(seconds * SAMPLE_RATE) as usize)
+        .map(|index| {
+            let time = index as f64 / SAMPLE_RATE;
+            let chord = chords[time as usize % chords.len()];
+            chord.iter().map(|key| 0.1 * (TAU * 440. * ((f64::from(*key) - 69.) / 12.).exp2() * time).sin()).sum()
+        })
+        .collect()
+}
+
+#[test]
+fn tempo_is_found_from_clicks() {
+    for tempo in [90., 120., 150.] {
+        let found = analyse(&clicks(tempo, 10.), 1, SAMPLE_RATE).tempo.unwrap();
+        assert!((found - tempo).abs() < 1.5, "{found} instead of {tempo}");
+    }
+}
+
+#[test]
+fn key_is_found_from_chords() {
+    // I, IV, V and I in C major.
+    let c_major = chords(&[&[60, 64, 67], &[65, 69, 72], &[67, 71, 74], &[60, 64, 67]], 8.);
+    assert_eq!(analyse(&c_major, 1, SAMPLE_RATE).key, Some(Key { tonic: 0, minor: false }));
+    // i, iv, v and i in A minor.
+    let a_minor = chords(&[&[57, 60, 64], &[62, 65, 69], &[64, 67, 71], &[57, 60, 64]], 8.);
+    assert_eq!(analyse(&a_minor, 1, SAMPLE_RATE).key, Some(Key { tonic: 9, minor: true }));
+    assert_eq!(Key { tonic: 6, minor: true }.to_string(), "F#m");
+}
+
+#[test]
+fn silence_has_no_tempo_or_key() {
+    let analysis = analyse(&vec![0.; 44100 * 4], 1, SAMPLE_RATE);
+    assert_eq!((analysis.tempo, analysis.key), (None, None));
+}
+
+#[test]
+fn onsets_are_found_at_clicks() {
+    let found = onsets(&clicks(120., 4.), 1, SAMPLE_RATE, 0.5);
+    assert_eq!(found.len(), 8, "{found:?}");
+    for (index, onset) in found.iter().enumerate() {
+        assert!((onset - index as f64 * 0.5).abs() < 0.005, "{onset} instead of {}", index as f64 * 0.5);
+    }
+}
+
+#[test]
+fn sensitivity_finds_softer_hits() {
+    let samples = accented_clicks(120., 4., 0.1);
+    assert_eq!(onsets(&samples, 1, SAMPLE_RATE, 0.).len(), 4);
+    assert_eq!(onsets(&samples, 1, SAMPLE_RATE, 1.).len(), 8);
+}
+
+#[test]
+fn silence_has_no_onsets() {
+    assert!(onsets(&vec![0.; 44100], 1, SAMPLE_RATE, 1.).is_empty());
+}
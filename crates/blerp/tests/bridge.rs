@@ -0,0 +1,182 @@
+use std::{sync::Arc, thread};
+
+use blerp::processing::bridge::{ring, triple, Consumer, Producer, Writer};
+
+/// Where a fake audio thread is, as the UI sees it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Status {
+    position: usize,
+    playing: bool,
+}
+
+enum Command {
+    Play,
+    Seek(usize),
+    Samples(Vec<f64>),
+}
+
+/// An audio thread run by hand, whose clock only moves when a block is processed.
+struct FakeAudio {
+    frames: usize,
+    status: Status,
+    samples: Vec<f64>,
+    commands: Consumer<Command>,
+    writer: Writer<Status>,
+    garbage: Producer<Vec<f64>>,
+}
+
+impl FakeAudio {
+    fn block(&mut self, frames: usize) {
+        for command in self.commands.drain() {
+            match command {
+                Command::Play => self.status.playing = true,
+                Command::Seek(position) => self.status.position = position,
+                Command::Samples(samples) => {
+                    let old = std::mem::replace(&mut self.samples, samples);
+                    assert!(self.garbage.push(old).is_ok());
+                }
+            }
+        }
+        self.frames += frames;
+        if self.status.playing {
+            self.status.position += frames;
+        }
+        self.writer.write(self.status);
+    }
+}
+
+#[test]
+fn values_are_received_in_order_until_the_ring_is_full() {
+    let (producer, consumer) = ring(3);
+    assert!(consumer.pop().is_none());
+    for value in 0..3 {
+        assert_eq!(producer.push(value), Ok(()));
+    }
+    assert_eq!(producer.push(3), Err(3));
+    assert_eq!((producer.free(), consumer.len()), (0, 3));
+    assert_eq!(consumer.pop(), Some(0));
+    assert_eq!(producer.push(3), Ok(()));
+    assert_eq!(consumer.drain().collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(consumer.is_empty());
+}
+
+#[test]
+fn values_left_in_a_ring_are_dropped_with_it() {
+    let value = Arc::new(());
+    let (producer, consumer) = ring(4);
+    for _ in 0..3 {
+        assert!(producer.push(Arc::clone(&value)).is_ok());
+    }
+    drop(consumer.pop());
+    assert_eq!(Arc::strong_count(&value), 3);
+    drop((producer, consumer));
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+#[test]
+fn values_cross_threads_in_order() {
+    let (producer, consumer) = ring(16);
+    let sender = thread::spawn(move || {
+        for mut value in 0..10_000 {
+            while let Err(rejected) = producer.push(value) {
+                value = rejected;
+                thread::yield_now();
+            }
+        }
+    });
+    let mut expected = 0;
+    while expected < 10_000 {
+        match consumer.pop() {
+            Some(value) => {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+    sender.join().unwrap();
+}
+
+#[test]
+fn the_reader_gets_the_latest_value_published() {
+    let (mut writer, mut reader) = triple(0);
+    assert_eq!(*reader.read(), 0);
+    assert!(!reader.has_newer());
+    for value in 1..=5 {
+        writer.write(value);
+    }
+    assert!(reader.has_newer());
+    assert_eq!(*reader.read(), 5);
+    assert_eq!(reader.latest(), 5);
+    // Writing in place doesn't show until it's published.
+    *writer.get_mut() = 6;
+    assert_eq!(reader.latest(), 5);
+    writer.publish();
+    assert_eq!(reader.latest(), 6);
+}
+
+#[test]
+fn values_read_are_never_torn() {
+    let (mut writer, reader) = triple([0_usize; 64]);
+    let publisher = thread::spawn(move || {
+        for value in 1..=20_000 {
+            writer.write([value; 64]);
+        }
+    });
+    let mut last = 0;
+    while last < 20_000 {
+        let values = reader.latest();
+        assert!(values.iter().all(|value| *value == values[0]));
+        assert!(values[0] >= last);
+        last = values[0];
+    }
+    publisher.join().unwrap();
+}
+
+#[test]
+fn commands_take_effect_on_the_next_block() {
+    let (commands, audio_commands) = ring(8);
+    let (writer, reader) = triple(Status::default());
+    let (garbage, collected) = ring(8);
+    let mut audio = FakeAudio {
+        frames: 0,
+        status: Status::default(),
+        samples: vec![1.; 4],
+        commands: audio_commands,
+        writer,
+        garbage,
+    };
+    audio.block(256);
+    assert_eq!(reader.latest(), Status::default());
+    assert!(commands.push(Command::Seek(1000)).is_ok());
+    assert!(commands.push(Command::Play).is_ok());
+    // Nothing changes until the audio thread gets to them.
+    assert_eq!(reader.latest(), Status::default());
+    audio.block(256);
+    assert_eq!(reader.latest(), Status { position: 1256, playing: true });
+    // The UI only sees the latest block when it reads slower than blocks are processed.
+    audio.block(256);
+    audio.block(128);
+    assert_eq!(reader.latest(), Status { position: 1640, playing: true });
+    assert_eq!(audio.frames, 896);
+    assert!(collected.is_empty());
+}
+
+#[test]
+fn replaced_values_are_sent_back_to_be_dropped() {
+    let (commands, audio_commands) = ring(8);
+    let (writer, _reader) = triple(Status::default());
+    let (garbage, collected) = ring(8);
+    let mut audio = FakeAudio {
+        frames: 0,
+        status: Status::default(),
+        samples: vec![1.; 4],
+        commands: audio_commands,
+        writer,
+        garbage,
+    };
+    assert!(commands.push(Command::Samples(vec![2.; 8])).is_ok());
+    audio.block(64);
+    assert_eq!(audio.samples, [2.; 8]);
+    assert_eq!(collected.drain().collect::<Vec<_>>(), [vec![1.; 4]]);
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use blerp::{
+    midi::Note,
+    processing::drum_rack::{defaults, DrumRack, Pad, FIRST_KEY, SETTINGS},
+};
+
+const SAMPLE_RATE: f64 = 48000.;
+
+/// Return a pad playing `frames` frames of `value` on two channels, with the setting called `name` set to `value` if given.
+fn pad(frames: usize, value: f64, setting: Option<(&str, f64)>) -> Pad {
+    stereo_pad(frames, (value, value), setting)
+}
+
+/// Like [`pad`], but with a value for each channel.
+fn stereo_pad(frames: usize, (left, right): (f64, f64), setting: Option<(&str, f64)>) -> Pad {
+    let mut values = defaults();
+    if let Some((name, value)) = setting {
+        values[SETTINGS.iter().position(|setting| setting.parameter.name == name).unwrap()] = value;
+    }
+    Pad::new(Arc::from([left, right].repeat(frames)), 2, SAMPLE_RATE, &values)
+}
+
+fn note(key: u8, start: f64) -> Note {
+    Note { key, velocity: 127, start, length: 0.25, channel: 9 }
+}
+
+#[test]
+fn notes_play_the_sample_of_their_pad_through() {
+    let mut rack = DrumRack::default();
+    rack.pads[1] = Some(pad(1000, 0.5, None));
+    let samples = rack.play(&[note(FIRST_KEY + 1, 0.)], 120., 1, SAMPLE_RATE);
+    // The note is shorter than the sample, which plays through anyway.
+    assert_eq!(samples.len(), 1000);
+    assert!((samples[0] - 0.4).abs() < 1e-9);
+}
+
+#[test]
+fn notes_of_pads_without_a_sample_are_skipped() {
+    let mut rack = DrumRack::default();
+    rack.pads[0] = Some(pad(1000, 0.5, None));
+    assert!(rack.play(&[note(FIRST_KEY - 1, 0.), note(FIRST_KEY + 2, 0.), note(FIRST_KEY + 16, 0.)], 120., 1, SAMPLE_RATE).is_empty());
+}
+
+#[test]
+fn pitching_a_pad_up_plays_it_faster() {
+    let mut rack = DrumRack::default();
+    rack.pads[0] = Some(pad(1000, 0.5, Some(("Pitch", 12.))));
+    assert_eq!(rack.play(&[note(FIRST_KEY, 0.)], 120., 1, SAMPLE_RATE).len(), 500);
+}
+
+#[test]
+fn pads_of_a_choke_group_cut_each_other_off() {
+    let play = |group| {
+        let mut rack = DrumRack::default();
+        rack.pads[0] = Some(pad(SAMPLE_RATE as usize, 0.5, Some(("Choke group", group))));
+        rack.pads[1] = Some(pad(SAMPLE_RATE as usize, 0., Some(("Choke group", group))));
+        // The second pad is hit half a second in.
+        rack.play(&[note(FIRST_KEY, 0.), note(FIRST_KEY + 1, 1.)], 120., 1, SAMPLE_RATE)
+    };
+    let choked = play(1.);
+    assert!(choked[..SAMPLE_RATE as usize / 2].iter().all(|sample| *sample > 0.));
+    assert!(choked[SAMPLE_RATE as usize * 51 / 100..].iter().all(|sample| *sample == 0.));
+    assert!(play(0.)[SAMPLE_RATE as usize * 3 / 4] > 0.);
+}
+
+#[test]
+fn stereo_pads_stay_stereo_and_are_mixed_down_to_mono() {
+    let mut rack = DrumRack::default();
+    rack.pads[0] = Some(stereo_pad(1000, (0.5, -0.25), None));
+    let stereo = rack.play(&[note(FIRST_KEY, 0.)], 120., 2, SAMPLE_RATE);
+    assert_eq!(stereo.len(), 2000);
+    assert!((stereo[0] - 0.4).abs() < 1e-9 && (stereo[1] + 0.2).abs() < 1e-9);
+    let mono = rack.play(&[note(FIRST_KEY, 0.)], 120., 1, SAMPLE_RATE);
+    assert_eq!(mono.len(), 1000);
+    assert!((mono[0] - 0.1).abs() < 1e-9);
+}
@@ -0,0 +1,24 @@
+use std::borrow::Cow;
+
+use blerp::processing::effects::{fade::FadeEffect, Effect, Stuff};
+
+fn stuff(samples: Vec<f64>, channels: usize) -> Stuff<'static> {
+    Stuff {
+        time: 0.,
+        sample_rate: 4.,
+        channels,
+        samples: Cow::Owned(samples),
+    }
+}
+
+#[test]
+fn fades_ramp_linearly() {
+    let Ok(faded) = FadeEffect::new(1., 0.5).apply(stuff(vec![1.; 8], 1));
+    assert_eq!(*faded.samples, [0.125, 0.375, 0.625, 0.875, 1., 1., 0.75, 0.25]);
+}
+
+#[test]
+fn fades_apply_to_every_channel() {
+    let Ok(faded) = FadeEffect::new(0.5, 0.).apply(stuff(vec![1., -1., 1., -1., 1., -1.], 2));
+    assert_eq!(*faded.samples, [0.25, -0.25, 0.75, -0.75, 1., -1.]);
+}
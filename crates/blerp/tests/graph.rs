@@ -0,0 +1,225 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use blerp::processing::{
+    effects::{Effect, EffectError, Stuff},
+    graph::{Level, Node, Schedule, Tap},
+    registry,
+};
+
+/// Passes its input through, keeping the time and length in frames of every block it's applied to.
+struct Blocks(Arc<Mutex<Vec<(f64, usize)>>>);
+
+impl Display for Blocks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Blocks")
+    }
+}
+
+impl Effect for Blocks {
+    fn apply<'a>(&self, input: Stuff<'a>) -> Result<Stuff<'a>, EffectError> {
+        self.0.lock().unwrap().push((input.time, input.samples.len() / input.channels));
+        Ok(input)
+    }
+}
+
+#[test]
+fn nodes_are_evaluated_after_their_inputs() {
+    // The effect is listed before its input, and the output is listed first.
+    let nodes = vec![
+        Node::Sum,
+        Node::Effect(registry::find("Scale").unwrap().build(&[2.])),
+        Node::Samples {
+            samples: Arc::from([1., 2., 3., 4.]),
+            looping: false,
+        },
+    ];
+    let mut schedule = Schedule::new(nodes, &[(2, 1), (1, 0)], 0, 1, 4.).unwrap();
+    let mut output = [0.; 3];
+    schedule.process(&[], &mut output);
+    assert_eq!(output, [2., 4., 6.]);
+    schedule.process(&[], &mut output);
+    assert_eq!(output, [8., 0., 0.]);
+    assert_eq!(schedule.position(), 6);
+}
+
+#[test]
+fn gains_scale_what_goes_through_them() {
+    // A track going through a fader to the output, and sent before the fader to a bus that also goes to the output.
+    let nodes = vec![
+        Node::Sum,
+        Node::Samples {
+            samples: Arc::from([1., 2., 4.]),
+            looping: false,
+        },
+        Node::Gain(0.5),
+        Node::Gain(0.25),
+        Node::Sum,
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 2), (1, 3), (3, 4), (2, 0), (4, 0)], 0, 1, 4.).unwrap();
+    let mut output = [0.; 3];
+    schedule.process(&[], &mut output);
+    assert_eq!(output, [0.75, 1.5, 3.]);
+}
+
+#[test]
+fn inputs_are_summed_and_outputs_fan_out() {
+    let nodes = vec![
+        Node::Sum,
+        Node::Samples {
+            samples: Arc::from([1., 2.]),
+            looping: true,
+        },
+        Node::Input(0),
+        Node::Sum,
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 2), (1, 3), (2, 0), (3, 0)], 0, 2, 4.).unwrap();
+    let mut output = [0.; 4];
+    schedule.process(&[&[0.5, 0.5, 0.5, 0.5]], &mut output);
+    assert_eq!(output, [2.5, 4.5, 2.5, 4.5]);
+}
+
+#[test]
+fn cycles_are_rejected() {
+    let nodes = vec![Node::Sum, Node::Sum, Node::Sum];
+    assert!(Schedule::new(nodes, &[(1, 2), (2, 1), (2, 0)], 0, 1, 4.).is_err());
+}
+
+#[test]
+fn taps_keep_the_latest_audio() {
+    let tap = Arc::new(Tap::new(1, 4., 3));
+    let nodes = vec![
+        Node::Tap(Arc::clone(&tap)),
+        Node::Samples {
+            samples: Arc::from([1., 2., 3., 4., 5.]),
+            looping: false,
+        },
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 0)], 0, 1, 4.).unwrap();
+    assert_eq!(tap.samples(), [0., 0., 0.]);
+    let mut output = [0.; 2];
+    schedule.process(&[], &mut output);
+    assert_eq!(output, [1., 2.]);
+    assert_eq!(tap.samples(), [0., 1., 2.]);
+    schedule.process(&[], &mut output);
+    assert_eq!(tap.samples(), [2., 3., 4.]);
+}
+
+#[test]
+fn taps_give_every_sample_once() {
+    let tap = Tap::new(1, 4., 3);
+    tap.push(&[1., 2.]);
+    let (samples, frames) = tap.samples_since(0);
+    assert_eq!((samples.as_slice(), frames), ([1., 2.].as_slice(), 2));
+    tap.push(&[3.]);
+    assert_eq!(tap.samples_since(frames).0, [3.]);
+    // Only the samples still kept are given once too many went through.
+    tap.push(&[4., 5., 6., 7.]);
+    assert_eq!(tap.samples_since(3), ([5., 6., 7.].to_vec(), 7));
+}
+
+#[test]
+fn taps_are_read_while_they_are_pushed() {
+    let tap = Arc::new(Tap::new(1, 4., 64));
+    let pusher = Arc::clone(&tap);
+    let pushing = thread::spawn(move || {
+        for block in 0..20_000_u32 {
+            pusher.push(&[0, 1, 2, 3].map(|sample| f64::from(block * 4 + sample)));
+        }
+    });
+    let mut read = 0;
+    while !pushing.is_finished() || read < 80_000 {
+        let (samples, frames) = tap.samples_since(read);
+        // What's read is always the newest frames in order, even if the oldest of them were written over meanwhile.
+        for (sample, frame) in samples.iter().rev().zip((0..frames).rev()) {
+            assert!((sample - f64::from(u32::try_from(frame).unwrap())).abs() < 0.5);
+        }
+        read = frames;
+    }
+    pushing.join().unwrap();
+}
+
+#[test]
+fn taps_measure_levels_of_every_channel() {
+    let tap = Arc::new(Tap::new(2, 4., 0));
+    let nodes = vec![
+        Node::Tap(Arc::clone(&tap)),
+        Node::Samples {
+            samples: Arc::from([1., 0.5, -1., 0., 1., 0.5, -1., 0.]),
+            looping: false,
+        },
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 0)], 0, 2, 4.).unwrap();
+    assert_eq!(tap.take_levels(), None);
+    let mut output = [0.; 8];
+    schedule.process(&[], &mut output);
+    let levels = tap.take_levels().unwrap();
+    assert_eq!(levels[0], Level { peak: 1., rms: 1. });
+    assert_eq!(levels[1].peak, 0.5);
+    assert!((levels[1].rms - 0.125_f64.sqrt()).abs() < 1e-9);
+    // Levels are reset once taken.
+    assert_eq!(tap.take_levels(), None);
+    assert!(tap.samples().is_empty());
+}
+
+#[test]
+fn inputs_are_processed_alone_while_stopped() {
+    let nodes = vec![
+        Node::Effect(registry::find("Scale").unwrap().build(&[2.])),
+        Node::Samples {
+            samples: Arc::from([1., 2., 3., 4.]),
+            looping: false,
+        },
+        Node::Input(0),
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 0), (2, 0)], 0, 1, 4.).unwrap();
+    let mut output = [0.; 2];
+    schedule.process_inputs(&[&[0.5, 0.25]], &mut output);
+    assert_eq!(output, [1., 0.5]);
+    assert_eq!(schedule.position(), 0);
+    schedule.process(&[&[0.5, 0.25]], &mut output);
+    assert_eq!(output, [3., 4.5]);
+    assert_eq!(schedule.position(), 2);
+}
+
+#[test]
+fn blocks_are_split_at_boundaries() {
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let nodes = vec![
+        Node::Effect(Box::new(Blocks(Arc::clone(&blocks)))),
+        Node::Samples {
+            samples: Arc::from([1., 2., 3., 4., 5., 6., 7., 8.]),
+            looping: false,
+        },
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 0)], 0, 2, 4.).unwrap();
+    schedule.set_boundaries([3, 1, 3]);
+    let mut output = [0.; 8];
+    schedule.process(&[], &mut output);
+    // The samples come out the same as in one block.
+    assert_eq!(output, [1., 2., 3., 4., 5., 6., 7., 8.]);
+    assert_eq!(*blocks.lock().unwrap(), [(0., 1), (0.25, 2), (0.75, 1)]);
+    assert_eq!(schedule.position(), 4);
+}
+
+#[test]
+fn blocks_are_split_where_samples_loop() {
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let nodes = vec![
+        Node::Effect(Box::new(Blocks(Arc::clone(&blocks)))),
+        Node::Samples {
+            samples: Arc::from([1., 2., 3.]),
+            looping: true,
+        },
+        Node::Input(0),
+    ];
+    let mut schedule = Schedule::new(nodes, &[(1, 0), (2, 0)], 0, 1, 1.).unwrap();
+    let mut output = [0.; 5];
+    schedule.process(&[&[0., 0., 0., 10., 0.]], &mut output);
+    // Inputs stay in step with the parts of the block.
+    assert_eq!(output, [1., 2., 3., 11., 2.]);
+    assert_eq!(*blocks.lock().unwrap(), [(0., 3), (3., 2)]);
+}
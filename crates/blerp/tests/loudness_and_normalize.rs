@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+
+use blerp::processing::{
+    effects::{
+        normalize::{NormalizeEffect, NormalizeTarget},
+        reverse::ReverseEffect,
+        Effect, Stuff,
+    },
+    generation::sine_wave,
+    loudness::{gain_to_decibels, integrated_loudness, loudness_range, peak, true_peak, LoudnessMeter},
+};
+use itertools::Itertools;
+
+const SAMPLE_RATE: f64 = 48000.;
+
+fn sine(amplitude: f64) -> Vec<f64> {
+    (0..48000).map(|sample| sine_wave(997., amplitude)(f64::from(sample) / SAMPLE_RATE)).collect_vec()
+}
+
+fn stuff(samples: Vec<f64>, channels: usize) -> Stuff<'static> {
+    Stuff {
+        time: 0.,
+        sample_rate: SAMPLE_RATE,
+        channels,
+        samples: Cow::Owned(samples),
+    }
+}
+
+#[test]
+fn full_scale_sine_is_minus_three_lufs() {
+    let loudness = integrated_loudness(&sine(1.), 1, SAMPLE_RATE);
+    assert!((loudness + 3.01).abs() < 0.1, "got {loudness} LUFS");
+    assert_eq!(integrated_loudness(&vec![0.; 48000], 1, SAMPLE_RATE), f64::NEG_INFINITY);
+}
+
+#[test]
+fn meter_follows_the_loudness_as_it_plays() {
+    let mut meter = LoudnessMeter::new(1, SAMPLE_RATE);
+    assert_eq!(meter.integrated(), f64::NEG_INFINITY);
+    for block in sine(1.).chunks(512) {
+        meter.push(block);
+    }
+    assert!((meter.momentary() + 3.01).abs() < 0.1, "got {} LUFS", meter.momentary());
+    assert!((meter.short_term() + 3.01).abs() < 0.1, "got {} LUFS", meter.short_term());
+    assert!((meter.integrated() - integrated_loudness(&sine(1.), 1, SAMPLE_RATE)).abs() < 0.1);
+    // The momentary loudness falls within half a second of silence, while the integrated loudness leaves the silence out.
+    meter.push(&[0.; 24000]);
+    assert!(meter.momentary() < -70.);
+    let played = sine(1.).into_iter().chain([0.; 24000]).collect_vec();
+    assert!((meter.integrated() - integrated_loudness(&played, 1, SAMPLE_RATE)).abs() < 0.1);
+}
+
+#[test]
+fn range_spans_quiet_and_loud_parts() {
+    let steady = (0..10).flat_map(|_| sine(0.5)).collect_vec();
+    assert!(loudness_range(&steady, 1, SAMPLE_RATE) < 0.1);
+    let contrasted = (0..10).flat_map(|_| sine(1.)).chain((0..10).flat_map(|_| sine(0.1))).collect_vec();
+    let range = loudness_range(&contrasted, 1, SAMPLE_RATE);
+    assert!((range - 20.).abs() < 1., "got {range} LU");
+    assert_eq!(loudness_range(&vec![0.; 48000], 1, SAMPLE_RATE), 0.);
+}
+
+#[test]
+fn true_peak_is_found_between_samples() {
+    // A quarter of the sample rate, shifted by an eighth of a cycle, peaks halfway between samples of ±0.707.
+    let samples = (0..4800).map(|sample| (std::f64::consts::FRAC_PI_2 * f64::from(sample) + std::f64::consts::FRAC_PI_4).sin()).collect_vec();
+    assert!((peak(&samples) - 0.5_f64.sqrt()).abs() < 1e-9);
+    let true_peak = true_peak(&samples, 1);
+    assert!((gain_to_decibels(true_peak)).abs() < 0.2, "got {true_peak}");
+}
+
+#[test]
+fn normalize_reaches_target() {
+    let Ok(peak_normalized) = NormalizeEffect::new(NormalizeTarget::Peak(-6.)).apply(stuff(sine(0.1), 1));
+    assert!((gain_to_decibels(peak(&peak_normalized.samples)) + 6.).abs() < 0.01);
+
+    let Ok(loudness_normalized) = NormalizeEffect::new(NormalizeTarget::Loudness(-14.)).apply(stuff(sine(0.1), 1));
+    assert!((integrated_loudness(&loudness_normalized.samples, 1, SAMPLE_RATE) + 14.).abs() < 0.01);
+}
+
+#[test]
+fn reverse_keeps_channels_in_place() {
+    let Ok(reversed) = ReverseEffect.apply(stuff(vec![1., -1., 2., -2., 3., -3.], 2));
+    assert_eq!(*reversed.samples, [3., -3., 2., -2., 1., -1.]);
+}
@@ -0,0 +1,207 @@
+use blerp::{
+    midi::{Dimension, Expression, MidiError, MidiFile, Note, TempoChange, Track, MPE_BEND_RANGE},
+    processing::instrument::{frequency, play},
+};
+
+/// Return a MIDI file with one track of `events`, at 480 ticks per beat.
+fn file(events: &[u8]) -> Vec<u8> {
+    let mut bytes = b"MThd\0\0\0\x06\0\0\0\x01\x01\xe0MTrk".to_vec();
+    bytes.extend(u32::try_from(events.len()).unwrap().to_be_bytes());
+    bytes.extend(events);
+    bytes
+}
+
+#[test]
+fn notes_are_read_in_beats() {
+    let bytes = file(&[
+        // A tempo of 100 BPM.
+        0x00, 0xff, 0x51, 0x03, 0x09, 0x27, 0xc0, //
+        0x00, 0x90, 60, 100, //
+        // Running status, with a velocity of 0 releasing the note.
+        0x83, 0x60, 64, 80, //
+        0x00, 60, 0, //
+        0x81, 0x70, 0x80, 64, 0, //
+        0x00, 0xff, 0x2f, 0x00,
+    ]);
+    let midi = MidiFile::read(&bytes).unwrap();
+    assert_eq!(
+        midi.notes,
+        [Note { key: 60, velocity: 100, start: 0., length: 1., channel: 0 }, Note { key: 64, velocity: 80, start: 1., length: 0.5, channel: 0 }]
+    );
+    assert!((midi.tempo - 100.).abs() < 1e-9);
+    assert!((midi.length - 1.5).abs() < 1e-9);
+    assert!((midi.duration() - 0.9).abs() < 1e-9);
+}
+
+#[test]
+fn notes_that_are_never_released_last_until_the_end_of_the_track() {
+    let bytes = file(&[0x00, 0x91, 67, 90, 0x87, 0x40, 0xff, 0x2f, 0x00]);
+    let midi = MidiFile::read(&bytes).unwrap();
+    assert_eq!(midi.notes, [Note { key: 67, velocity: 90, start: 0., length: 2., channel: 1 }]);
+    assert!((midi.tempo - 120.).abs() < 1e-9);
+}
+
+#[test]
+fn other_files_are_rejected() {
+    assert_eq!(MidiFile::read(b"RIFF\0\0\0\0WAVE"), Err(MidiError::NotMidi));
+    assert_eq!(MidiFile::read(&file(&[0x00, 0x90, 60])), Err(MidiError::UnexpectedEnd));
+}
+
+#[test]
+fn tracks_keep_their_names_and_tempo_changes_are_kept() {
+    let mut bytes = b"MThd\0\0\0\x06\0\x01\0\x03\x01\xe0".to_vec();
+    let tracks: [&[u8]; 3] = [
+        &[0x00, 0xff, 0x51, 0x03, 0x07, 0x53, 0x00, 0x87, 0x40, 0xff, 0x51, 0x03, 0x09, 0x27, 0xc0, 0x00, 0xff, 0x2f, 0x00],
+        &[0x00, 0xff, 0x03, 0x04, b'B', b'a', b's', b's', 0x00, 0x90, 36, 100, 0x83, 0x60, 0x80, 36, 0, 0x00, 0xff, 0x2f, 0x00],
+        &[0x00, 0xff, 0x03, 0x04, b'L', b'e', b'a', b'd', 0x83, 0x60, 0x90, 72, 90, 0x83, 0x60, 0x80, 72, 0, 0x00, 0xff, 0x2f, 0x00],
+    ];
+    for track in tracks {
+        bytes.extend(b"MTrk");
+        bytes.extend(u32::try_from(track.len()).unwrap().to_be_bytes());
+        bytes.extend(track);
+    }
+    let midi = MidiFile::read(&bytes).unwrap();
+    assert_eq!(
+        midi.tracks,
+        [
+            Track {
+                name: Some("Bass".into()),
+                notes: vec![Note { key: 36, velocity: 100, start: 0., length: 1., channel: 0 }],
+                expression: Vec::new(),
+            },
+            Track {
+                name: Some("Lead".into()),
+                notes: vec![Note { key: 72, velocity: 90, start: 1., length: 1., channel: 0 }],
+                expression: Vec::new(),
+            },
+        ]
+    );
+    assert_eq!(midi.notes.len(), 2);
+    assert_eq!(midi.tempos, [TempoChange { start: 0., bpm: 125. }, TempoChange { start: 2., bpm: 100. }]);
+    assert!((midi.tempo - 125.).abs() < 1e-9);
+}
+
+#[test]
+fn written_files_are_read_back_the_same() {
+    let tracks = vec![
+        Track {
+            name: Some("Chords".into()),
+            notes: vec![
+                Note { key: 60, velocity: 100, start: 0., length: 1., channel: 0 },
+                Note { key: 64, velocity: 100, start: 0., length: 1., channel: 0 },
+                // Played again right as it's released.
+                Note { key: 60, velocity: 80, start: 1., length: 0.5, channel: 0 },
+            ],
+            expression: Vec::new(),
+        },
+        Track {
+            name: None,
+            notes: vec![Note { key: 36, velocity: 127, start: 200., length: 0.25, channel: 0 }],
+            expression: Vec::new(),
+        },
+    ];
+    let tempos = vec![TempoChange { start: 0., bpm: 140. }, TempoChange { start: 8., bpm: 70. }];
+    let file = MidiFile { notes: Vec::new(), expression: Vec::new(), tempo: 140., length: 0., tracks: tracks.clone(), tempos: tempos.clone() };
+    let midi = MidiFile::read(&file.write()).unwrap();
+    assert_eq!(midi.tracks, tracks);
+    assert_eq!(midi.tempos.len(), tempos.len());
+    // Tempos are written in microseconds per beat, so they're only close.
+    assert!(midi.tempos.iter().zip(&tempos).all(|(a, b)| a.start == b.start && (a.bpm - b.bpm).abs() < 1e-3));
+    assert!((midi.length - 200.25).abs() < 1e-9);
+
+    // Without tempo changes, the tempo of the file is written.
+    let file = MidiFile { tempos: Vec::new(), tempo: 90., ..file };
+    assert!((MidiFile::read(&file.write()).unwrap().tempo - 90.).abs() < 1e-3);
+}
+
+#[test]
+fn notes_are_played_for_as_long_as_they_are_held() {
+    assert!((frequency(69) - 440.).abs() < 1e-9);
+    assert!((frequency(81) - 880.).abs() < 1e-9);
+    let notes = [Note { key: 69, velocity: 127, start: 1., length: 1., channel: 0 }];
+    let samples = play(&notes, &[], 120., 1000.);
+    // The note starts after half a second and is released after another, then fades out.
+    assert!(samples.len() > 1000 && samples.len() < 1200);
+    assert!(samples[..500].iter().all(|sample| *sample == 0.));
+    assert!(samples[500..1000].iter().any(|sample| sample.abs() > 0.1));
+    assert!(samples.iter().all(|sample| sample.abs() <= 1.));
+}
+
+#[test]
+fn mpe_expression_is_read_for_each_note() {
+    let bytes = file(&[
+        // The MPE configuration message sets up a lower zone of 15 channels, whose pitch bends go 48 semitones.
+        0x00, 0xb0, 101, 0, 0x00, 0xb0, 100, 6, 0x00, 0xb0, 6, 15, //
+        // A note on the second channel, bent half the way up and pressed.
+        0x00, 0xe1, 0x00, 0x60, 0x00, 0x91, 60, 100, //
+        0x83, 0x60, 0xd1, 64, //
+        // A note on the third channel, slid up, then both are released.
+        0x00, 0x92, 64, 90, 0x00, 0xb2, 74, 127, //
+        0x83, 0x60, 0x81, 60, 0, 0x00, 0x82, 64, 0, //
+        0x00, 0xff, 0x2f, 0x00,
+    ]);
+    let midi = MidiFile::read(&bytes).unwrap();
+    assert_eq!(
+        midi.notes,
+        [Note { key: 60, velocity: 100, start: 0., length: 2., channel: 1 }, Note { key: 64, velocity: 90, start: 1., length: 1., channel: 2 }]
+    );
+    assert_eq!(
+        midi.expression,
+        [
+            Expression { channel: 1, start: 0., dimension: Dimension::PitchBend, value: 24. },
+            Expression { channel: 1, start: 1., dimension: Dimension::Pressure, value: 64. / 127. },
+            Expression { channel: 2, start: 1., dimension: Dimension::Slide, value: 1. },
+        ]
+    );
+    assert_eq!(midi.tracks[0].expression, midi.expression);
+}
+
+#[test]
+fn pitch_bend_ranges_are_two_semitones_unless_set() {
+    // The range of the first channel is set to 12 semitones, while the second keeps the default.
+    let bytes = file(&[
+        0x00, 0xb0, 101, 0, 0x00, 0xb0, 100, 0, 0x00, 0xb0, 6, 12, //
+        0x00, 0xe0, 0x7f, 0x7f, 0x00, 0xe1, 0x00, 0x00, //
+        0x00, 0x90, 60, 100, 0x83, 0x60, 0x80, 60, 0, //
+        0x00, 0xff, 0x2f, 0x00,
+    ]);
+    let midi = MidiFile::read(&bytes).unwrap();
+    let bends = midi.expression.iter().map(|expression| (expression.channel, expression.value)).collect::<Vec<_>>();
+    assert_eq!(bends.len(), 2);
+    assert!(bends[0].0 == 0 && (bends[0].1 - 12.).abs() < 0.01);
+    assert!(bends[1].0 == 1 && (bends[1].1 + 2.).abs() < 1e-9);
+}
+
+#[test]
+fn written_expression_is_read_back() {
+    let expression = vec![
+        Expression { channel: 3, start: 0., dimension: Dimension::PitchBend, value: -12. },
+        Expression { channel: 3, start: 0.5, dimension: Dimension::Pressure, value: 1. },
+        Expression { channel: 3, start: 0.5, dimension: Dimension::Slide, value: 0. },
+    ];
+    let track = Track {
+        name: None,
+        notes: vec![Note { key: 48, velocity: 64, start: 0., length: 1., channel: 3 }],
+        expression: expression.clone(),
+    };
+    let file = MidiFile { notes: Vec::new(), expression: Vec::new(), tempo: 120., length: 0., tracks: vec![track], tempos: Vec::new() };
+    let midi = MidiFile::read(&file.write()).unwrap();
+    assert_eq!(midi.notes, file.tracks[0].notes);
+    assert_eq!(midi.expression.len(), expression.len());
+    // Pitch bends are written in steps of a 16384th of the range.
+    let step = 2. * MPE_BEND_RANGE / 16383.;
+    assert!(midi.expression.iter().zip(&expression).all(|(a, b)| a.channel == b.channel && a.start == b.start && a.dimension == b.dimension && (a.value - b.value).abs() <= step));
+}
+
+#[test]
+fn notes_follow_the_expression_of_their_channel() {
+    let notes = [Note { key: 69, velocity: 127, start: 0., length: 2., channel: 1 }];
+    // How often the wave crosses zero going up over a part of it, which follows its frequency.
+    let crossings = |samples: &[f64]| samples[500..1000].windows(2).filter(|pair| pair[0] < 0. && pair[1] >= 0.).count();
+    let plain = play(&notes, &[], 120., 8000.);
+    let bent_up = play(&notes, &[Expression { channel: 1, start: 0., dimension: Dimension::PitchBend, value: 12. }], 120., 8000.);
+    let other_channel = play(&notes, &[Expression { channel: 2, start: 0., dimension: Dimension::PitchBend, value: 12. }], 120., 8000.);
+    assert_eq!(bent_up.len(), plain.len());
+    assert!(crossings(&bent_up) > crossings(&plain) * 3 / 2);
+    assert_eq!(other_channel, plain);
+}
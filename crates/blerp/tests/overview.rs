@@ -0,0 +1,13 @@
+use blerp::processing::overview::{overview, Peak};
+
+#[test]
+fn stretches_cover_every_channel() {
+    let samples = [0.5, -0.25, 1., 0., -1., 0.75, 0., 0.];
+    assert_eq!(overview(&samples, 2, 2), [Peak { min: -0.25, max: 1. }, Peak { min: -1., max: 0.75 }]);
+}
+
+#[test]
+fn short_audio_has_a_stretch_per_frame() {
+    assert_eq!(overview(&[0.5, -0.5], 1, 100), [Peak { min: 0., max: 0.5 }, Peak { min: -0.5, max: 0. }]);
+    assert!(overview(&[], 1, 100).is_empty());
+}
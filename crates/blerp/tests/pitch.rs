@@ -0,0 +1,60 @@
+use std::f64::consts::TAU;
+
+use blerp::processing::pitch::{detect, nearest_key};
+
+const SAMPLE_RATE: f64 = 48000.;
+
+/// Return 4096 samples of a note at `frequency` with its first `harmonics` harmonics, each quieter than the one before, like a plucked string.
+fn note(frequency: f64, harmonics: u32) -> Vec<f64> {
+    (0..4096)
+        .map(|index| {
+            let time = index as f64 / SAMPLE_RATE;
+            (1..=harmonics).map(|harmonic| (TAU * frequency * f64::from(harmonic) * time).sin() * 0.5 / f64::from(harmonic)).sum()
+        })
+        .collect()
+}
+
+fn cents(frequency: f64, expected: f64) -> f64 {
+    1200. * (frequency / expected).log2().abs()
+}
+
+#[test]
+fn the_pitch_of_a_sine_is_found() {
+    let frequency = detect(&note(440., 1), SAMPLE_RATE).unwrap();
+    assert!(cents(frequency, 440.) < 1., "found {frequency} Hz");
+}
+
+#[test]
+fn the_pitch_of_a_low_string_is_its_fundamental() {
+    // The low E of a guitar, whose harmonics are nearly as loud as its fundamental.
+    for expected in [82.41, 110., 329.63] {
+        let frequency = detect(&note(expected, 6), SAMPLE_RATE).unwrap();
+        assert!(cents(frequency, expected) < 2., "found {frequency} Hz instead of {expected} Hz");
+    }
+}
+
+#[test]
+fn silence_and_noise_have_no_pitch() {
+    assert_eq!(detect(&[0.; 4096], SAMPLE_RATE), None);
+    let mut noise = 1_u32;
+    let samples = (0..4096)
+        .map(|_| {
+            noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            f64::from(noise >> 8) / f64::from(1 << 24) - 0.5
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(detect(&samples, SAMPLE_RATE), None);
+}
+
+#[test]
+fn frequencies_are_named_by_their_nearest_key() {
+    assert_eq!(nearest_key(440., 440.), (69, 0.));
+    let (key, cents) = nearest_key(445., 440.);
+    assert_eq!(key, 69);
+    assert!((cents - 19.56).abs() < 0.01);
+    let (key, cents) = nearest_key(82.41 * 0.99, 440.);
+    assert_eq!(key, 40);
+    assert!((cents + 17.4).abs() < 0.1);
+    // With A4 at 432 Hz, 440 Hz is sharp of A4 rather than in tune.
+    assert!(nearest_key(440., 432.).1 > 30.);
+}
@@ -0,0 +1,58 @@
+use std::borrow::Cow;
+
+use blerp::processing::{
+    effects::Stuff,
+    registry::{find, find_by_id, in_category, Category, EFFECTS},
+};
+
+#[test]
+fn effects_are_found_by_name() {
+    for effect in EFFECTS {
+        assert_eq!(find(&effect.name.to_uppercase()).map(|found| found.name), Some(effect.name));
+    }
+    assert!(find("no such effect").is_none());
+}
+
+#[test]
+fn effects_are_found_by_id() {
+    for effect in EFFECTS {
+        assert_eq!(find_by_id(effect.id).map(|found| found.name), Some(effect.name));
+    }
+    assert!(find_by_id("Clip").is_none());
+}
+
+#[test]
+fn every_effect_is_in_one_category() {
+    let listed: usize = Category::ALL.into_iter().map(|category| in_category(category).count()).sum();
+    assert_eq!(listed, EFFECTS.len());
+    assert!(in_category(Category::Generator).all(|effect| effect.category == Category::Generator));
+}
+
+#[test]
+fn generators_add_to_their_input() {
+    let square = find_by_id("square").unwrap();
+    let stuff = Stuff {
+        time: 0.,
+        sample_rate: 8.,
+        channels: 2,
+        samples: Cow::Owned(vec![0.25; 8]),
+    };
+    // A square wave of 20 Hz flips every 1/40 s, so at 8 Hz every frame is 5 flips after the last one.
+    let Ok(output) = square.build(&[20., 0.5]).apply(stuff);
+    assert_eq!(*output.samples, [0.75, 0.75, -0.25, -0.25, 0.75, 0.75, -0.25, -0.25]);
+}
+
+#[test]
+fn parameters_are_filled_in_and_clamped() {
+    let scale = find("scale").unwrap();
+    let stuff = || Stuff {
+        time: 0.,
+        sample_rate: 48000.,
+        channels: 1,
+        samples: Cow::Owned(vec![0.5]),
+    };
+    let Ok(output) = scale.build(&[]).apply(stuff());
+    assert_eq!(*output.samples, [0.5]);
+    let Ok(output) = scale.build(&[100.]).apply(stuff());
+    assert_eq!(*output.samples, [2.]);
+}
@@ -0,0 +1,21 @@
+use blerp::processing::resample::{convert, convert_into};
+
+#[test]
+fn mono_is_copied_to_every_channel() {
+    assert_eq!(convert(&[1., 2.], 1, 4., 2, 4.), [1., 1., 2., 2.]);
+}
+
+#[test]
+fn upsampling_interpolates_linearly() {
+    assert_eq!(convert(&[0., 1., 2.], 1, 2., 1, 4.), [0., 0.5, 1., 1.5, 2., 2.]);
+}
+
+#[test]
+fn converting_into_a_buffer_reuses_it() {
+    let mut output = Vec::with_capacity(64);
+    output.push(9.);
+    let buffer = output.as_ptr();
+    convert_into(&[0., 1., 2., 3.], 2, 2., 1, 4., &mut output);
+    assert_eq!(output, convert(&[0., 1., 2., 3.], 2, 2., 1, 4.));
+    assert_eq!(output.as_ptr(), buffer);
+}
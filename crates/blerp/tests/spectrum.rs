@@ -0,0 +1,27 @@
+use std::f64::consts::TAU;
+
+use blerp::processing::spectrum::amplitudes;
+
+const LENGTH: usize = 4096;
+const SAMPLE_RATE: f64 = 48000.;
+
+fn sine(frequency: f64, amplitude: f64) -> Vec<f64> {
+    (0..LENGTH).map(|index| amplitude * (TAU * frequency * index as f64 / SAMPLE_RATE).sin()).collect()
+}
+
+#[test]
+fn a_sine_shows_at_its_frequency_and_amplitude() {
+    let bin = 100;
+    let amplitudes = amplitudes(&sine(bin as f64 * SAMPLE_RATE / LENGTH as f64, 0.5));
+    assert_eq!(amplitudes.len(), LENGTH / 2 + 1);
+    let loudest = (0..amplitudes.len()).max_by(|a, b| amplitudes[*a].total_cmp(&amplitudes[*b])).unwrap();
+    assert_eq!(loudest, bin);
+    assert!((amplitudes[bin] - 0.5).abs() < 0.01, "{}", amplitudes[bin]);
+    // The window keeps the sine from spreading far.
+    assert!(amplitudes[bin + 4..].iter().all(|amplitude| *amplitude < 1e-3));
+}
+
+#[test]
+fn silence_has_no_amplitude() {
+    assert!(amplitudes(&[0.; LENGTH]).iter().all(|amplitude| *amplitude == 0.));
+}
@@ -0,0 +1,85 @@
+use std::{sync::Arc, thread};
+
+use blerp::processing::stream::{Need, Stream};
+
+#[test]
+fn frames_are_read_ahead_of_playback() {
+    let stream = Stream::new(1, 4, 10, 4);
+    // Before the stream starts, its first frames are wanted.
+    let mut output = [0.; 4];
+    stream.read(0, &mut output);
+    assert_eq!(stream.need(), Some(Need { frames: 0..4, buffered: 0 }));
+    stream.push(0, &[1., 2., 3., 4.]);
+    assert_eq!(stream.need(), None);
+    stream.read(2, &mut output);
+    assert_eq!(output, [0., 0., 1., 2.]);
+    // Playing frames makes room for more.
+    stream.read(6, &mut [0.; 2]);
+    assert_eq!(stream.underruns(), 0);
+    assert_eq!(stream.need(), Some(Need { frames: 4..6, buffered: 2 }));
+}
+
+#[test]
+fn missing_frames_are_silent_and_counted() {
+    let stream = Stream::new(2, 0, 8, 4);
+    stream.push(0, &[1., 1., 2., 2.]);
+    let mut output = [0.; 8];
+    stream.read(0, &mut output);
+    assert_eq!(output, [1., 1., 2., 2., 0., 0., 0., 0.]);
+    assert_eq!(stream.underruns(), 1);
+}
+
+#[test]
+fn reading_starts_over_where_playback_jumps() {
+    let stream = Stream::new(1, 0, 100, 4);
+    stream.push(0, &[1., 2., 3., 4.]);
+    stream.read(50, &mut [0.; 2]);
+    assert_eq!(stream.need(), Some(Need { frames: 50..54, buffered: 0 }));
+    // Frames read from before the jump are dropped.
+    stream.push(4, &[5., 6.]);
+    stream.push(50, &[1., 2., 3., 4.]);
+    let mut output = [0.; 2];
+    stream.read(50, &mut output);
+    assert_eq!(output, [1., 2.]);
+}
+
+#[test]
+fn frames_past_the_end_are_dropped() {
+    let stream = Stream::new(1, 0, 3, 4);
+    assert_eq!(stream.need(), Some(Need { frames: 0..3, buffered: 0 }));
+    stream.push(0, &[1., 2., 3., 4., 5.]);
+    let mut output = [0.; 5];
+    stream.read(0, &mut output);
+    assert_eq!(output, [1., 2., 3., 0., 0.]);
+    assert_eq!(stream.underruns(), 0);
+}
+
+#[test]
+fn streams_are_read_ahead_while_they_play() {
+    let stream = Arc::new(Stream::new(1, 0, 1 << 14, 64));
+    let reader = Arc::clone(&stream);
+    let reading = thread::spawn(move || {
+        while Arc::strong_count(&reader) > 1 {
+            if let Some(need) = reader.need() {
+                let samples = need.frames.clone().map(|frame| f64::from(u32::try_from(frame).unwrap() + 1)).collect::<Vec<_>>();
+                reader.push(need.frames.start, &samples);
+            }
+        }
+    });
+    let (mut output, mut heard) = ([0.; 16], 0);
+    // Playing through the stream twice also jumps back to its start.
+    for position in (0..1 << 14).step_by(16).chain((0..1 << 14).step_by(16)) {
+        output.fill(0.);
+        stream.read(position, &mut output);
+        // Frames that weren't read in time are silent, and the others are always the right ones.
+        for (frame, sample) in (position..).zip(output) {
+            let expected = f64::from(u32::try_from(frame).unwrap() + 1);
+            assert!(sample.abs() < 0.5 || (sample - expected).abs() < 0.5, "frame {frame} is {sample}");
+            heard += usize::from(sample.abs() >= 0.5);
+        }
+        thread::yield_now();
+    }
+    assert!(heard > 0);
+    drop(stream);
+    reading.join().unwrap();
+}
@@ -0,0 +1,113 @@
+use blerp::{
+    midi::{Dimension, Expression, Note},
+    processing::synth::{defaults, settings, Live, Synth, SECTIONS},
+};
+
+const SAMPLE_RATE: f64 = 48000.;
+
+/// Return the default values of the settings, with the setting called `name` in `section` set to `value`.
+fn with(section: &str, name: &str, value: f64) -> Vec<f64> {
+    let mut values = defaults();
+    let index = SECTIONS
+        .iter()
+        .flat_map(|other| other.settings.iter().map(move |setting| (other.name, setting.parameter.name)))
+        .position(|other| other == (section, name))
+        .unwrap();
+    values[index] = value;
+    values
+}
+
+/// Return how much the sound changes from one sample to the next compared to how loud it is, which is higher for brighter sounds.
+fn brightness(samples: &[f64]) -> f64 {
+    let changes: f64 = samples.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum();
+    changes / samples.iter().map(|sample| sample.powi(2)).sum::<f64>()
+}
+
+fn note(start: f64, length: f64) -> Note {
+    Note { key: 57, velocity: 100, start, length, channel: 0 }
+}
+
+#[test]
+fn every_setting_has_a_default_in_its_range() {
+    for setting in settings() {
+        let parameter = &setting.parameter;
+        assert!(parameter.range.contains(&parameter.default), "{} is out of range", parameter.name);
+        if !setting.choices.is_empty() {
+            assert_eq!(setting.choices.len() - 1, *parameter.range.end() as usize, "{} has a value without a choice", parameter.name);
+        }
+    }
+}
+
+#[test]
+fn notes_ring_until_their_release_has_ended() {
+    let samples = Synth::default().play(&[note(0., 1.), note(1., 1.)], &[], 120., SAMPLE_RATE);
+    // Two beats at 120 BPM, and the release of the amplifier.
+    assert_eq!(samples.len(), (1.2 * SAMPLE_RATE) as usize);
+    assert!(samples.iter().all(|sample| sample.abs() < 1.));
+    assert!(samples[..SAMPLE_RATE as usize].iter().any(|sample| sample.abs() > 0.01));
+    assert!(samples.last().unwrap().abs() < 1e-3);
+}
+
+#[test]
+fn nothing_is_played_without_notes() {
+    assert!(Synth::default().play(&[], &[], 120., SAMPLE_RATE).is_empty());
+}
+
+#[test]
+fn values_out_of_range_are_clamped() {
+    let mut values = vec![f64::MAX; defaults().len()];
+    values[0] = -1.;
+    let samples = Synth::new(&values).play(&[note(0., 1.)], &[], 120., SAMPLE_RATE);
+    assert!(samples.iter().all(|sample| sample.is_finite()));
+}
+
+#[test]
+fn a_lower_cutoff_makes_a_darker_sound() {
+    let play = |cutoff| Synth::new(&with("Filter", "Cutoff", cutoff)).play(&[note(0., 1.)], &[], 120., SAMPLE_RATE);
+    assert!(brightness(&play(300.)) < brightness(&play(8000.)) / 4.);
+}
+
+#[test]
+fn unison_adds_voices_without_getting_much_louder() {
+    let peak = |voices| {
+        let samples = Synth::new(&with("Unison", "Voices", voices)).play(&[note(0., 1.)], &[], 120., SAMPLE_RATE);
+        samples.iter().fold(0., |peak: f64, sample| peak.max(sample.abs()))
+    };
+    let (one, eight) = (peak(0.), peak(7.));
+    assert!(eight > one / 2. && eight < one * 3.);
+}
+
+#[test]
+fn notes_played_live_sound_like_those_played_from_a_file() {
+    let values = with("Unison", "Voices", 2.);
+    let expression = [Expression { channel: 3, start: 0., dimension: Dimension::PitchBend, value: 1.5 }];
+    let note = Note { channel: 3, ..note(0., 0.5) };
+    let played = Synth::new(&values).play(&[note], &expression, 60., SAMPLE_RATE);
+    let mut live = Live::new(Synth::new(&values), SAMPLE_RATE);
+    live.express(3, Dimension::PitchBend, 1.5);
+    live.press(3, note.key, note.velocity);
+    let mut samples = vec![0.; played.len() + 100];
+    live.process(&mut samples[..(0.5 * SAMPLE_RATE) as usize], 1);
+    live.release(3, note.key);
+    live.process(&mut samples[(0.5 * SAMPLE_RATE) as usize..], 1);
+    assert!(played.iter().zip(&samples).all(|(a, b)| (a - b).abs() < 1e-9));
+    // The release can end a sample later, as it's timed from the release rather than from the start.
+    assert!(samples[played.len() + 1..].iter().all(|sample| *sample == 0.));
+}
+
+#[test]
+fn notes_played_live_follow_the_expression_of_their_own_channel() {
+    let mut bent = Live::new(Synth::default(), SAMPLE_RATE);
+    let mut other = Live::new(Synth::default(), SAMPLE_RATE);
+    for live in [&mut bent, &mut other] {
+        live.press(1, 57, 100);
+    }
+    bent.express(1, Dimension::PitchBend, 12.);
+    other.express(2, Dimension::PitchBend, 12.);
+    let (mut high, mut low) = (vec![0.; 4800], vec![0.; 4800]);
+    bent.process(&mut high, 1);
+    other.process(&mut low, 1);
+    let crossings = |samples: &[f64]| samples.windows(2).filter(|pair| pair[0].signum() != pair[1].signum()).count();
+    // An octave up crosses zero twice as often.
+    assert!(crossings(&high) * 2 > crossings(&low) * 3);
+}
@@ -0,0 +1,32 @@
+use blerp::processing::{generation::sine_wave, stretch::time_stretch};
+use itertools::Itertools;
+
+const SAMPLE_RATE: f64 = 44100.;
+
+fn zero_crossings(samples: &[f64]) -> usize {
+    samples.iter().tuple_windows().filter(|(a, b)| a.signum() != b.signum()).count()
+}
+
+#[test]
+fn stretching_keeps_pitch() {
+    let sine = (0..44100).map(|sample| sine_wave(440., 0.5)(f64::from(sample) / SAMPLE_RATE)).collect_vec();
+    for ratio in [0.5, 0.8, 1.25, 2.] {
+        let stretched = time_stretch(&sine, 1, SAMPLE_RATE, ratio);
+        #[allow(clippy::cast_precision_loss, reason = "lengths are small")]
+        let expected_length = (sine.len() as f64 * ratio).round();
+        #[allow(clippy::cast_precision_loss, reason = "lengths are small")]
+        let length = stretched.len() as f64;
+        assert!((length - expected_length).abs() <= 1., "ratio {ratio}: got {length} samples, expected {expected_length}");
+        // The number of zero crossings per second, and therefore the pitch, should stay roughly the same.
+        #[allow(clippy::cast_precision_loss, reason = "counts are small")]
+        let frequency = zero_crossings(&stretched) as f64 / 2. / (length / SAMPLE_RATE);
+        assert!((frequency - 440.).abs() < 440. * 0.03, "ratio {ratio}: got {frequency} Hz");
+    }
+}
+
+#[test]
+fn stretching_keeps_channels_apart() {
+    let stereo = (0..44100).flat_map(|_| [1., -1.]).collect_vec();
+    let stretched = time_stretch(&stereo, 2, SAMPLE_RATE, 1.5);
+    assert!(stretched.chunks_exact(2).all(|frame| frame[0] > 0.99 && frame[1] < -0.99));
+}
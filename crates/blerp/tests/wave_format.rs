@@ -0,0 +1,19 @@
+use blerp::wavefile::{Format, WaveFile, WaveFormat};
+
+#[test]
+fn format_is_read_from_the_start_of_a_file() {
+    let mut bytes = Vec::new();
+    WaveFile::from_samples::<f32, _>([[0.; 1000]], 48000).unwrap().write(&mut bytes).unwrap();
+    let format = WaveFormat::read(&bytes[..64]).unwrap();
+    assert_eq!(format.format, Format::FloatingPoint);
+    assert_eq!((format.channels.get(), format.sample_rate, format.bytes_per_sample), (1, 48000, 4));
+}
+
+#[test]
+fn other_chunks_before_the_format_are_skipped() {
+    let mut bytes = Vec::new();
+    WaveFile::from_samples::<i16, _>([[0.; 10]], 44100).unwrap().write(&mut bytes).unwrap();
+    bytes.splice(12..12, *b"JUNK\x04\0\0\0\0\0\0\0");
+    let format = WaveFormat::read(&bytes).unwrap();
+    assert_eq!((format.format, format.sample_rate, format.bytes_per_sample), (Format::PulseCodeModulation, 44100, 2));
+}
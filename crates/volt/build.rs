@@ -0,0 +1,21 @@
+//! Lists the crates Volt depends on for the About window, from the dependencies of its manifest, so that the list follows them as they change.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    println!("cargo::rerun-if-changed=Cargo.toml");
+    let manifest = fs::read_to_string("Cargo.toml").expect("the manifest can be read");
+    // Dependencies are written one per line, and the ones with a path are part of Volt itself.
+    let mut crates = manifest
+        .lines()
+        .skip_while(|line| line.trim() != "[dependencies]")
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.contains("path ="))
+        .filter_map(|line| line.split_once('=').map(|(name, _)| name.trim()))
+        .collect::<Vec<_>>();
+    crates.sort_unstable();
+    let out = Path::new(&env::var("OUT_DIR").expect("Cargo sets OUT_DIR")).join("crates.rs");
+    fs::write(out, format!("{crates:?}")).expect("the list of crates can be written");
+}
@@ -0,0 +1,343 @@
+//! Reading files out of ZIP archives, like sample packs, without extracting them, and writing archives of projects.
+//!
+//! A file in an archive has a path made of the archive's path followed by its name in it, like `packs/drums.zip/kicks/kick.wav`, so that it can be browsed and
+//! previewed like any other file. Only stored and deflated files are supported, which covers what archivers write by default.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+
+const END_SIGNATURE: u32 = 0x0605_4b50;
+const ENTRY_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+/// The size of the end of central directory record without its comment.
+const END_SIZE: usize = 22;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// The version of the format needed to read the archives written, which is the first with deflated files.
+const VERSION: u16 = 20;
+/// The flag saying that names are UTF-8.
+const UTF8_NAMES: u16 = 0x0800;
+/// The time and date files are written with, which is midnight on the first of January 1980 as that's the earliest date there is.
+const TIME: u16 = 0;
+const DATE: u16 = 0x21;
+/// The table of the CRC-32 that archives check files with.
+#[allow(clippy::cast_possible_truncation, reason = "the index is below 256")]
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+/// A file in an archive, as described by the archive's central directory.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The path of the file in the archive, with forward slashes.
+    pub name: String,
+    method: u16,
+    compressed_size: u64,
+    size: u64,
+    /// Where the local header of the file starts in the archive.
+    offset: u64,
+}
+
+/// Return whether `path` is a ZIP archive, going by its extension.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip")) && path.is_file()
+}
+
+/// Split a path to a file in an archive into the archive's path and the file's name in it, or return [`None`] if `path` isn't in an archive.
+pub fn split(path: &Path) -> Option<(&Path, String)> {
+    let archive = path.ancestors().skip(1).find(|ancestor| is_archive(ancestor))?;
+    let name = path.strip_prefix(archive).ok()?.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/");
+    Some((archive, name))
+}
+
+/// Return the files in the archive at `path`, leaving out folders.
+pub fn list(path: &Path) -> io::Result<Vec<Entry>> {
+    let mut file = File::open(path)?;
+    let length = file.seek(SeekFrom::End(0))?;
+    // The record ends with a comment of up to 64 KiB, so it's searched for backwards.
+    let tail_length = length.min((END_SIZE + usize::from(u16::MAX)) as u64);
+    file.seek(SeekFrom::Start(length - tail_length))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let end = (0..=tail.len().saturating_sub(END_SIZE))
+        .rev()
+        .find(|&start| u32_at(&tail, start) == END_SIGNATURE)
+        .ok_or_else(|| invalid("the end of the central directory is missing"))?;
+    let count = u16_at(&tail, end + 10);
+    let directory_size = u32_at(&tail, end + 12);
+    let directory_offset = u32_at(&tail, end + 16);
+    if count == u16::MAX || directory_offset == u32::MAX {
+        return Err(io::Error::new(ErrorKind::Unsupported, "ZIP64 archives aren't supported"));
+    }
+    // The sizes come from the archive, so they're checked before anything is allocated for them.
+    if u64::from(directory_offset) + u64::from(directory_size) > length {
+        return Err(invalid("the central directory goes past the end of the archive"));
+    }
+    let mut directory = vec![0; directory_size as usize];
+    file.seek(SeekFrom::Start(u64::from(directory_offset)))?;
+    file.read_exact(&mut directory)?;
+    let mut entries = Vec::with_capacity(usize::from(count));
+    let mut start = 0;
+    for _ in 0..count {
+        if start + 46 > directory.len() || u32_at(&directory, start) != ENTRY_SIGNATURE {
+            return Err(invalid("the central directory is corrupt"));
+        }
+        let name_length = usize::from(u16_at(&directory, start + 28));
+        let extra_length = usize::from(u16_at(&directory, start + 30));
+        let comment_length = usize::from(u16_at(&directory, start + 32));
+        let name = directory.get(start + 46..start + 46 + name_length).ok_or_else(|| invalid("the central directory is corrupt"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        // Names that would lead out of the archive, like `../kick.wav`, are left out so that they're never extracted there.
+        let plain = Path::new(&name).components().all(|component| matches!(component, Component::Normal(_)));
+        if plain && !name.ends_with('/') {
+            entries.push(Entry {
+                name,
+                method: u16_at(&directory, start + 10),
+                compressed_size: u64::from(u32_at(&directory, start + 20)),
+                size: u64::from(u32_at(&directory, start + 24)),
+                offset: u64::from(u32_at(&directory, start + 42)),
+            });
+        }
+        start += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+/// Return the contents of the file called `name` in the archive at `archive`.
+pub fn read(archive: &Path, name: &str) -> io::Result<Vec<u8>> {
+    let entry = list(archive)?.into_iter().find(|entry| entry.name == name).ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("there is no {name} in the archive")))?;
+    read_entry(archive, &entry)
+}
+
+/// Return the contents of the file described by `entry`, as listed by [`list`], in the archive at `archive`.
+pub fn read_entry(archive: &Path, entry: &Entry) -> io::Result<Vec<u8>> {
+    let mut file = File::open(archive)?;
+    let mut header = [0; 30];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut header)?;
+    if u32_at(&header, 0) != LOCAL_SIGNATURE {
+        return Err(invalid("the file's header is corrupt"));
+    }
+    let skipped = i64::from(u16_at(&header, 26)) + i64::from(u16_at(&header, 28));
+    file.seek(SeekFrom::Current(skipped))?;
+    let mut data = Vec::new();
+    file.take(entry.compressed_size).read_to_end(&mut data)?;
+    #[allow(clippy::cast_possible_truncation, reason = "files that don't fit in memory couldn't be decoded anyway")]
+    let size = entry.size as usize;
+    match entry.method {
+        STORED => Ok(data),
+        DEFLATED => decompress_to_vec_with_limit(&data, size).map_err(|error| invalid(&error.to_string())),
+        method => Err(io::Error::new(ErrorKind::Unsupported, format!("compression method {method} isn't supported"))),
+    }
+}
+
+/// A file opened with [`open`], which is read from the disk or from memory if it was in an archive.
+pub enum Reader {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Memory(memory) => memory.read(buf),
+        }
+    }
+}
+
+impl Seek for Reader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Memory(memory) => memory.seek(pos),
+        }
+    }
+}
+
+/// Open the file at `path`, which may be in an archive, in which case it's read into memory.
+pub fn open(path: &Path) -> io::Result<Reader> {
+    match split(path) {
+        Some((archive, name)) => read(archive, &name).map(|data| Reader::Memory(Cursor::new(data))),
+        None => File::open(path).map(|file| Reader::File(BufReader::new(file))),
+    }
+}
+
+/// Return a path to the file at `path` outside of any archive: the path itself if it isn't in one, or where it was extracted to in `folder` otherwise. Files extracted
+/// before are reused.
+pub fn extract(path: &Path, folder: &Path) -> io::Result<PathBuf> {
+    let Some((archive, name)) = split(path) else {
+        return Ok(path.to_path_buf());
+    };
+    let stem = archive.file_stem().unwrap_or_default();
+    let destination = folder.join(stem).join(&name);
+    if !destination.exists() {
+        let data = read(archive, &name)?;
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&destination, data)?;
+    }
+    Ok(destination)
+}
+
+/// Writes a ZIP archive file by file, deflating each file unless that doesn't make it smaller.
+pub struct Writer {
+    file: BufWriter<File>,
+    /// The central directory, which is written at the end once every file is in.
+    directory: Vec<u8>,
+    count: u16,
+    /// Where the next file starts.
+    offset: u32,
+}
+
+impl Writer {
+    /// Create an empty archive at `path`, replacing the file there if there is one.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            directory: Vec::new(),
+            count: 0,
+            offset: 0,
+        })
+    }
+
+    /// Add a file called `name`, with forward slashes between folders, containing `data`.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let compressed = compress_to_vec(data, 6);
+        let (method, stored) = if compressed.len() < data.len() { (DEFLATED, &compressed[..]) } else { (STORED, data) };
+        let too_big = || io::Error::new(ErrorKind::Unsupported, "ZIP64 archives aren't supported");
+        let size = u32::try_from(data.len()).map_err(|_| too_big())?;
+        let compressed_size = u32::try_from(stored.len()).map_err(|_| too_big())?;
+        let name_length = u16::try_from(name.len()).map_err(|_| invalid("a name is too long"))?;
+        let count = self.count.checked_add(1).filter(|count| *count < u16::MAX).ok_or_else(too_big)?;
+        let crc = crc32(data);
+        // The fields from the version needed to the length of the name are the same in the local header and the central directory.
+        let mut common = Vec::with_capacity(26);
+        for field in [VERSION, UTF8_NAMES, method, TIME, DATE] {
+            common.extend(field.to_le_bytes());
+        }
+        for field in [crc, compressed_size, size] {
+            common.extend(field.to_le_bytes());
+        }
+        common.extend(name_length.to_le_bytes());
+        let mut header = LOCAL_SIGNATURE.to_le_bytes().to_vec();
+        header.extend(&common);
+        header.extend([0, 0]);
+        header.extend(name.as_bytes());
+        self.file.write_all(&header)?;
+        self.file.write_all(stored)?;
+        self.directory.extend(ENTRY_SIGNATURE.to_le_bytes());
+        self.directory.extend(VERSION.to_le_bytes());
+        self.directory.extend(&common);
+        // No extra field, comment, disk number or attributes.
+        self.directory.extend([0; 12]);
+        self.directory.extend(self.offset.to_le_bytes());
+        self.directory.extend(name.as_bytes());
+        self.count = count;
+        self.offset = u32::try_from(header.len() + stored.len()).ok().and_then(|length| self.offset.checked_add(length)).ok_or_else(too_big)?;
+        Ok(())
+    }
+
+    /// Write the central directory, which finishes the archive.
+    pub fn finish(mut self) -> io::Result<()> {
+        let directory_size = u32::try_from(self.directory.len()).map_err(|_| io::Error::new(ErrorKind::Unsupported, "ZIP64 archives aren't supported"))?;
+        self.file.write_all(&self.directory)?;
+        let mut end = END_SIGNATURE.to_le_bytes().to_vec();
+        // This is the only disk, and the archive has no comment.
+        for field in [0, 0, self.count, self.count] {
+            end.extend(field.to_le_bytes());
+        }
+        end.extend(directory_size.to_le_bytes());
+        end.extend(self.offset.to_le_bytes());
+        end.extend([0, 0]);
+        self.file.write_all(&end)?;
+        self.file.flush()
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| CRC_TABLE[usize::from(crc.to_le_bytes()[0] ^ byte)] ^ (crc >> 8))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("the archive is invalid, {message}"))
+}
+
+fn u16_at(bytes: &[u8], start: usize) -> u16 {
+    u16::from_le_bytes([bytes[start], bytes[start + 1]])
+}
+
+fn u32_at(bytes: &[u8], start: usize) -> u32 {
+    u32::from_le_bytes([bytes[start], bytes[start + 1], bytes[start + 2], bytes[start + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{crc32, list, read, Writer};
+
+    /// Return a path for an archive of the test called `name`, which no other test uses.
+    fn path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("volt-{}-{name}.zip", process::id()))
+    }
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn written_files_are_read_back() {
+        let path = path("round-trip");
+        let repeated = b"kick ".repeat(1000);
+        let mut writer = Writer::create(&path).unwrap();
+        writer.add("drums/kick.wav", &repeated).unwrap();
+        writer.add("notes.txt", b"tiny").unwrap();
+        writer.add("empty", b"").unwrap();
+        writer.finish().unwrap();
+        let names = list(&path).unwrap().into_iter().map(|entry| entry.name).collect::<Vec<_>>();
+        assert_eq!(names, ["drums/kick.wav", "notes.txt", "empty"]);
+        assert_eq!(read(&path, "drums/kick.wav").unwrap(), repeated);
+        assert_eq!(read(&path, "notes.txt").unwrap(), b"tiny");
+        assert!(read(&path, "empty").unwrap().is_empty());
+        assert!(read(&path, "missing").is_err());
+        // The repeated file is deflated, so the archive is smaller than it.
+        assert!(fs::metadata(&path).unwrap().len() < repeated.len() as u64);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_central_directory_past_the_end_is_refused() {
+        let path = path("corrupt");
+        let mut writer = Writer::create(&path).unwrap();
+        writer.add("kick.wav", b"kick").unwrap();
+        writer.finish().unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        // The size of the central directory is 12 bytes into the end record, which is the last 22 bytes.
+        let size = bytes.len() - 22 + 12;
+        bytes[size..size + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(list(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,363 @@
+//! The settings of the app, kept in its folder in the configuration folder of the system along with everything else that's kept between sessions.
+
+use std::{
+    env, fs,
+    io::ErrorKind,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+use egui::{Color32, Context, FontFamily, FontId, Stroke, Style, TextStyle, ThemePreference, ViewportBuilder, Visuals};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::error;
+
+/// The name of the settings file.
+const NAME: &str = "config.toml";
+/// The files that were kept in the working directory before there was a configuration folder, which are moved into it.
+const LEGACY_FILES: &[&str] = &[
+    "keymap.toml",
+    "browser.toml",
+    "browser_index.toml",
+    "favorites.toml",
+    "tags.toml",
+    "recent.toml",
+    "collections.toml",
+    "freesound.toml",
+    "commands.toml",
+];
+
+static FOLDER: OnceLock<PathBuf> = OnceLock::new();
+
+/// Return the folder of the app in the configuration folder of the system, creating it if needed. If there is no configuration folder, it's the working directory.
+fn folder() -> &'static Path {
+    FOLDER.get_or_init(|| {
+        let Some(folder) = system_folder().map(|folder| folder.join("volt")) else {
+            error!("There is no configuration folder, keeping settings in the working directory");
+            return PathBuf::new();
+        };
+        if let Err(error) = fs::create_dir_all(&folder) {
+            error!("Couldn't create {}: {error}", folder.display());
+        }
+        folder
+    })
+}
+
+fn system_folder() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|folder| folder.is_absolute()).or_else(|| home().map(|home| home.join(".config")))
+    }
+}
+
+/// Return where the file called `name` is kept between sessions.
+pub fn path(name: &str) -> PathBuf {
+    folder().join(name)
+}
+
+/// Return what's kept in the file called `name`, or [`None`] if there's nothing kept or it couldn't be read, which is logged. `what` is what the file
+/// holds, like "the tags", to say what couldn't be read.
+pub fn load<T: DeserializeOwned>(name: &str, what: &str) -> Option<T> {
+    match fs::read_to_string(path(name)) {
+        Ok(text) => toml::from_str(&text).inspect_err(|error| error!("Couldn't read {what}, they're invalid: {error}")).ok(),
+        Err(error) if error.kind() == ErrorKind::NotFound => None,
+        Err(error) => {
+            error!("Couldn't read {what}: {error}");
+            None
+        }
+    }
+}
+
+/// Keep `value` in the file called `name`, logging it if it couldn't be. `what` is what the file holds, like "the tags".
+pub fn save<T: Serialize + ?Sized>(name: &str, value: &T, what: &str) {
+    let result = toml::to_string(value).map_err(|error| error.to_string()).and_then(|text| fs::write(path(name), text).map_err(|error| error.to_string()));
+    if let Err(error) = result {
+        error!("Couldn't save {what}: {error}");
+    }
+}
+
+/// Move the files kept in the working directory by earlier versions into the configuration folder, unless they're already there.
+fn migrate() {
+    for name in LEGACY_FILES {
+        let (old, new) = (Path::new(name), path(name));
+        if new.exists() || !old.exists() {
+            continue;
+        }
+        // Renaming doesn't work across file systems, where the file is copied instead.
+        if let Err(error) = fs::rename(old, &new).or_else(|_| fs::copy(old, &new).map(|_| ())) {
+            error!("Couldn't move {name} to {}: {error}", new.display());
+        }
+    }
+}
+
+/// Whether the interface is light or dark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Theme {
+    /// Follow the theme of the system.
+    System,
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Self; 3] = [Self::System, Self::Dark, Self::Light];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::System => "System",
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+        }
+    }
+}
+
+impl From<Theme> for ThemePreference {
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::System => Self::System,
+            Theme::Dark => Self::Dark,
+            Theme::Light => Self::Light,
+        }
+    }
+}
+
+/// The smallest and the largest the interface can be scaled to, where 1 is its normal size.
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.75..=2.;
+
+/// Scale the interface of `ctx` up or down by `steps` tenths of its normal size, within [`UI_SCALE_RANGE`].
+pub fn step_ui_scale(ctx: &Context, steps: i8) {
+    let scale = (ctx.zoom_factor() * 10.).round() / 10. + f32::from(steps) / 10.;
+    ctx.set_zoom_factor(scale.clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end()));
+}
+
+/// The shortest and the longest the pointer can rest on something before its tooltip shows, in seconds.
+pub const TOOLTIP_DELAY_RANGE: RangeInclusive<f32> = 0. ..=2.;
+
+/// The size of body text, which the other text styles are sized after.
+const BODY_TEXT_SIZE: f32 = 12.;
+/// The smallest and the largest the minimum text size can be set to.
+pub const MIN_TEXT_SIZE_RANGE: RangeInclusive<f32> = 8. ..=24.;
+
+static REDUCED_MOTION: AtomicBool = AtomicBool::new(false);
+
+/// Return whether animations that aren't needed should be left out, as of the last time the settings were applied.
+pub fn reduced_motion() -> bool {
+    REDUCED_MOTION.load(Ordering::Relaxed)
+}
+
+/// Options that make the interface easier to see and use.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accessibility {
+    /// Draw text and the outlines of widgets in stronger colors, with thicker outlines around the widget that has the focus.
+    pub high_contrast: bool,
+    /// The smallest text is drawn, in points. Text styles smaller than this are drawn at this size.
+    pub min_text_size: f32,
+    /// Leave out spinning, fading, sliding and blinking that isn't needed.
+    pub reduced_motion: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            min_text_size: *MIN_TEXT_SIZE_RANGE.start(),
+            reduced_motion: false,
+        }
+    }
+}
+
+impl Accessibility {
+    /// Set the text styles, visuals and animations of `ctx` for the light and dark themes.
+    pub fn apply(self, ctx: &Context) {
+        REDUCED_MOTION.store(self.reduced_motion, Ordering::Relaxed);
+        let min_text_size = self.min_text_size.clamp(*MIN_TEXT_SIZE_RANGE.start(), *MIN_TEXT_SIZE_RANGE.end());
+        ctx.all_styles_mut(|style| {
+            let size = |scale: f32| (BODY_TEXT_SIZE * scale).max(min_text_size);
+            style.override_font_id = Some(FontId::new(size(1.), FontFamily::Proportional));
+            style.text_styles = [
+                (TextStyle::Heading, size(1.5)),
+                (TextStyle::Body, size(1.)),
+                (TextStyle::Button, size(1.)),
+                (TextStyle::Small, size(0.8)),
+                (TextStyle::Monospace, size(1.)),
+            ]
+            .map(|(text_style, size)| (text_style, FontId::new(size, FontFamily::Proportional)))
+            .into();
+            style.visuals = if style.visuals.dark_mode { Visuals::dark() } else { Visuals::light() };
+            if self.high_contrast {
+                high_contrast(style);
+            }
+            style.animation_time = if self.reduced_motion { 0. } else { Style::default().animation_time };
+            style.visuals.text_cursor.blink = !self.reduced_motion;
+        });
+    }
+}
+
+/// Make the text and outlines of `style` stand out from the background as much as they can.
+fn high_contrast(style: &mut Style) {
+    let visuals = &mut style.visuals;
+    let (strong, background) = if visuals.dark_mode { (Color32::WHITE, Color32::BLACK) } else { (Color32::BLACK, Color32::WHITE) };
+    let focus = if visuals.dark_mode { Color32::YELLOW } else { Color32::from_rgb(0, 0x40, 0xc0) };
+    visuals.override_text_color = Some(strong);
+    visuals.panel_fill = background;
+    visuals.window_fill = background;
+    visuals.extreme_bg_color = background;
+    visuals.window_stroke = Stroke::new(2., strong);
+    visuals.widgets.noninteractive.bg_stroke = Stroke::new(1., strong);
+    visuals.widgets.inactive.bg_stroke = Stroke::new(1., strong);
+    visuals.widgets.hovered.bg_stroke = Stroke::new(2., strong);
+    // Widgets with the focus are drawn like active ones.
+    visuals.widgets.active.bg_stroke = Stroke::new(3., focus);
+    visuals.selection.stroke = Stroke::new(3., focus);
+    for widget in [&mut visuals.widgets.noninteractive, &mut visuals.widgets.inactive, &mut visuals.widgets.hovered, &mut visuals.widgets.active, &mut visuals.widgets.open] {
+        widget.fg_stroke.color = strong;
+    }
+}
+
+/// The range of how fast level meters can fall back, in dB per second.
+pub const RELEASE_RANGE: RangeInclusive<f32> = 3. ..=60.;
+/// The range of how long level meters can keep their highest peak marked, in seconds.
+pub const PEAK_HOLD_RANGE: RangeInclusive<f32> = 0. ..=5.;
+
+/// How the level meters move. They rise as soon as the audio gets louder.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ballistics {
+    /// How fast meters fall back once the audio gets quieter, in dB per second.
+    pub release: f32,
+    /// How long the highest peak lately stays marked, in seconds.
+    pub peak_hold: f32,
+}
+
+impl Default for Ballistics {
+    fn default() -> Self {
+        Self { release: 20., peak_hold: 1.5 }
+    }
+}
+
+/// Where the window was and how big it was, in points of the system.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    /// Where the top left corner of the window was, if the system tells.
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+    pub maximized: bool,
+}
+
+/// The settings of the app, which are saved whenever they change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The device played through, or [`None`] for the default one.
+    pub output_device: Option<String>,
+    /// The device recorded from, or [`None`] for the default one.
+    pub input_device: Option<String>,
+    pub theme: Theme,
+    /// The name of the theme file the colors are taken from, or [`None`] for the default colors.
+    pub colors: Option<String>,
+    /// The name of the font the interface is written in, see [`crate::visual::font`], or [`None`] for the default one.
+    pub font: Option<String>,
+    /// The name of the icon pack the icons are taken from, see [`crate::visual::icon`], or [`None`] for the bundled icons.
+    pub icon_pack: Option<String>,
+    /// How big the interface is drawn, where 1 is its normal size.
+    pub ui_scale: f32,
+    /// How long the pointer rests on something before its tooltip shows, in seconds.
+    pub tooltip_delay: f32,
+    pub accessibility: Accessibility,
+    pub meters: Ballistics,
+    /// Whether a loudness report is written next to each exported mix.
+    pub loudness_reports: bool,
+    /// Whether GitHub is asked for a newer release of Volt whenever it starts.
+    pub check_for_updates: bool,
+    /// The window as it was left, or [`None`] to open it at the default size.
+    pub window: Option<Window>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            input_device: None,
+            theme: Theme::default(),
+            colors: None,
+            font: None,
+            icon_pack: None,
+            ui_scale: 1.,
+            tooltip_delay: 0.5,
+            accessibility: Accessibility::default(),
+            meters: Ballistics::default(),
+            loudness_reports: false,
+            check_for_updates: false,
+            window: None,
+        }
+    }
+}
+
+impl Config {
+    /// Return the settings saved in the last session, moving the files kept by earlier versions into the configuration folder first.
+    pub fn load() -> Self {
+        migrate();
+        load(NAME, "the settings").unwrap_or_default()
+    }
+
+    /// Return whether settings were saved in an earlier session, which isn't the case on the first launch.
+    pub fn is_saved() -> bool {
+        path(NAME).exists()
+    }
+
+    pub fn save(&self) {
+        save(NAME, self, "the settings");
+    }
+
+    /// Return `viewport` with the window placed where it was left.
+    pub fn viewport(&self, viewport: ViewportBuilder) -> ViewportBuilder {
+        let Some(window) = self.window else {
+            return viewport;
+        };
+        let viewport = viewport.with_inner_size(window.size).with_maximized(window.maximized);
+        match window.position {
+            Some(position) => viewport.with_position(position),
+            None => viewport,
+        }
+    }
+
+    /// Apply the theme, the scale of the interface, the tooltip delay and the accessibility options to `ctx`. The interface is scaled through the keymap rather than by egui, to keep it within
+    /// [`UI_SCALE_RANGE`].
+    pub fn apply(&self, ctx: &Context) {
+        ctx.set_theme(self.theme);
+        self.accessibility.apply(ctx);
+        ctx.options_mut(|options| options.zoom_with_keyboard = false);
+        ctx.set_zoom_factor(self.ui_scale.clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end()));
+        let tooltip_delay = self.tooltip_delay.clamp(*TOOLTIP_DELAY_RANGE.start(), *TOOLTIP_DELAY_RANGE.end());
+        ctx.all_styles_mut(|style| style.interaction.tooltip_delay = tooltip_delay);
+    }
+
+    /// Take the scale of the interface, the tooltip delay and the place of the window from `ctx`, which change without going through the settings.
+    pub fn update(&mut self, ctx: &Context) {
+        self.ui_scale = ctx.zoom_factor();
+        self.tooltip_delay = ctx.style().interaction.tooltip_delay;
+        // The rectangles of the viewport are in points of the interface, which are scaled by its zoom.
+        let window = ctx.input(|input| {
+            let viewport = input.viewport();
+            let maximized = viewport.maximized.unwrap_or(false);
+            // The size of a maximized window is the size of the screen, so the size it had before is kept to restore it to.
+            let (position, size) = match (maximized, self.window) {
+                (true, Some(window)) => (window.position, window.size),
+                _ => (viewport.outer_rect.map(|rect| (rect.min.to_vec2() * self.ui_scale).into()), (viewport.inner_rect?.size() * self.ui_scale).into()),
+            };
+            Some(Window { position, size, maximized })
+        });
+        if window.is_some() {
+            self.window = window;
+        }
+    }
+}
@@ -0,0 +1,525 @@
+//! MIDI controllers, whose controls can be mapped to the parameters of the graph by moving them after picking a parameter, which is called MIDI learn.
+//!
+//! Mappings are kept in the project. How each control was last set up, its range and whether it picks parameters up, is kept between sessions in a
+//! profile, which new mappings of the control start from.
+//!
+//! Controllers can also run the transport, through MIDI Machine Control and the start, continue and stop messages, and notes and controls can be
+//! bound to actions in the keymap, like a footswitch to play.
+//!
+//! Notes that aren't bound to actions are played live on the synth of the armed track, along with the pitch bends, pressure and slides of their
+//! channels, so that the notes of MPE controllers are each bent, pressed and slid on their own.
+//!
+//! Controllers are read through the MIDI API of the system, ALSA on Linux, `CoreMIDI` on macOS and `WinMM` on Windows. Every input port is connected to when the
+//! app starts, and ports plugged in later can be connected to from the devices in the browser.
+
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use egui::{Context, DragValue, Response, RichText};
+use blerp::midi::{Channels, Dimension};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{config, visual::central::NodeId};
+
+/// Where the profile is kept between sessions.
+const PROFILE_PATH: &str = "controllers.toml";
+/// The name the app connects to MIDI inputs under.
+const CLIENT: &str = "Volt";
+/// How close a control has to get to the value of a parameter to pick it up, as a fraction of the parameter's range.
+const PICKUP_DISTANCE: f64 = 0.02;
+/// How long a system exclusive message is kept while it's read, which is plenty for MIDI Machine Control. Longer ones are dropped.
+const MAX_SYSEX: usize = 16;
+
+/// A control of a MIDI controller, like a knob or a fader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Control {
+    /// The channel the control sends on, counted from 0.
+    pub channel: u8,
+    /// The number of the controller, or CC, the control sends.
+    pub controller: u8,
+}
+
+impl std::fmt::Display for Control {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CC {} on channel {}", self.controller, self.channel + 1)
+    }
+}
+
+/// A control that was moved to `value`, between 0 and 127.
+#[derive(Debug, Clone, Copy)]
+pub struct ControlChange {
+    pub control: Control,
+    pub value: u8,
+}
+
+/// What a MIDI Machine Control message or a real time message asks the transport to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Play,
+    Stop,
+    /// Go back to the start, without starting or stopping.
+    Rewind,
+    /// Play from the start.
+    Start,
+}
+
+impl Transport {
+    /// Return what the command of a MIDI Machine Control message asks for. Commands that have nothing to drive, like recording, are skipped.
+    const fn from_mmc(command: u8) -> Option<Self> {
+        match command {
+            0x01 | 0x09 => Some(Self::Stop),
+            0x02 | 0x03 => Some(Self::Play),
+            0x05 => Some(Self::Rewind),
+            _ => None,
+        }
+    }
+}
+
+/// A message from a MIDI controller that the app does something with.
+#[derive(Debug, Clone, Copy)]
+pub enum Message {
+    Control(ControlChange),
+    /// A note was pressed, like a key or a pad.
+    Note { channel: u8, key: u8, velocity: u8 },
+    /// A note was released, which notes pressed with no velocity also are.
+    Release { channel: u8, key: u8 },
+    /// The notes of a channel were bent or pressed. Slides are sent as controls, as they can also be mapped.
+    Expression { channel: u8, dimension: Dimension, value: f64 },
+    Transport(Transport),
+}
+
+/// Reads the bytes of a MIDI stream, keeping the status of the last message so that messages sent without one can be read.
+#[derive(Default)]
+struct Parser {
+    status: Option<u8>,
+    data: Vec<u8>,
+    /// The system exclusive message being read, if any.
+    sysex: Option<Vec<u8>>,
+    /// How far the pitch bends of each channel go, as set up by the controller.
+    channels: Channels,
+}
+
+impl Parser {
+    /// Read the next byte, returning the message it completes, if any. Messages the app does nothing with are skipped.
+    fn push(&mut self, byte: u8) -> Option<Message> {
+        match byte {
+            // Real time messages can come in the middle of other messages, and don't change the status.
+            0xfa => Some(Message::Transport(Transport::Start)),
+            0xfb => Some(Message::Transport(Transport::Play)),
+            0xfc => Some(Message::Transport(Transport::Stop)),
+            0xf8.. => None,
+            0xf0 => {
+                self.status = None;
+                self.sysex = Some(Vec::new());
+                None
+            }
+            0xf7 => {
+                self.status = None;
+                match self.sysex.take()?.as_slice() {
+                    // Universal real time, to any device, MIDI Machine Control command.
+                    [0x7f, _, 0x06, command, ..] => Transport::from_mmc(*command).map(Message::Transport),
+                    _ => None,
+                }
+            }
+            0x80..=0xef | 0xf1..=0xf6 => {
+                // System messages cancel the status, as they can't be sent without one.
+                self.status = (byte < 0xf0).then_some(byte);
+                self.data.clear();
+                self.sysex = None;
+                None
+            }
+            _ => {
+                if let Some(sysex) = &mut self.sysex {
+                    sysex.push(byte);
+                    if sysex.len() > MAX_SYSEX {
+                        self.sysex = None;
+                    }
+                    return None;
+                }
+                let status = self.status?;
+                self.data.push(byte);
+                let length = if matches!(status & 0xf0, 0xc0 | 0xd0) { 1 } else { 2 };
+                if self.data.len() < length {
+                    return None;
+                }
+                let data = std::mem::take(&mut self.data);
+                let channel = status & 0x0f;
+                match status & 0xf0 {
+                    0xb0 => {
+                        self.channels.control_change(channel, data[0], data[1]);
+                        Some(Message::Control(ControlChange { control: Control { channel, controller: data[0] }, value: data[1] }))
+                    }
+                    0x90 if data[1] > 0 => Some(Message::Note { channel, key: data[0], velocity: data[1] }),
+                    0x80 | 0x90 => Some(Message::Release { channel, key: data[0] }),
+                    0xd0 => Some(Message::Expression { channel, dimension: Dimension::Pressure, value: f64::from(data[0]) / 127. }),
+                    0xe0 => Some(Message::Expression { channel, dimension: Dimension::PitchBend, value: self.channels.bend(channel, data[0], data[1]) }),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// The MIDI input ports that are connected, which are read in the background.
+pub struct Inputs {
+    /// The connections by the name of their port, which are closed once dropped.
+    connections: Vec<(String, MidiInputConnection<Parser>)>,
+    tx: Sender<Message>,
+    messages: Receiver<Message>,
+    ctx: Context,
+}
+
+impl Inputs {
+    /// Connect to every MIDI input port there is, repainting `ctx` whenever one sends something.
+    pub fn open(ctx: &Context) -> Self {
+        let (tx, messages) = unbounded();
+        let mut inputs = Self {
+            connections: Vec::new(),
+            tx,
+            messages,
+            ctx: ctx.clone(),
+        };
+        for name in ports() {
+            if let Err(error) = inputs.connect(&name) {
+                error!("Couldn't connect to the MIDI input {name}: {error}");
+            }
+        }
+        info!("Connected to {} MIDI inputs", inputs.connections.len());
+        inputs
+    }
+
+    /// Connect to the MIDI input port called `name`, unless it's connected already.
+    ///
+    /// # Errors
+    ///
+    /// Returns why the port couldn't be connected to, to be shown to the user.
+    pub fn connect(&mut self, name: &str) -> Result<(), String> {
+        if self.connections.iter().any(|(connected, _)| connected == name) {
+            return Ok(());
+        }
+        // Connecting consumes the client, so each port gets one of its own.
+        let mut input = MidiInput::new(CLIENT).map_err(|error| error.to_string())?;
+        // System exclusive and real time messages drive the transport.
+        input.ignore(Ignore::None);
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| input.port_name(port).is_ok_and(|port| port == name))
+            .ok_or("it isn't plugged in")?;
+        let (tx, ctx) = (self.tx.clone(), self.ctx.clone());
+        let connection = input
+            .connect(
+                &port,
+                CLIENT,
+                move |_, bytes, parser: &mut Parser| {
+                    let mut sent = false;
+                    for message in bytes.iter().filter_map(|byte| parser.push(*byte)) {
+                        sent |= tx.send(message).is_ok();
+                    }
+                    if sent {
+                        ctx.request_repaint();
+                    }
+                },
+                Parser::default(),
+            )
+            .map_err(|error| error.to_string())?;
+        self.connections.push((name.to_string(), connection));
+        Ok(())
+    }
+
+    /// Return the names of the ports that are connected.
+    pub fn names(&self) -> Vec<String> {
+        self.connections.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Return the messages sent since this was last called, in the order they were.
+    pub fn take(&self) -> Vec<Message> {
+        self.messages.try_iter().collect()
+    }
+}
+
+/// Return the names of the MIDI input ports there are, like those of the controllers plugged in.
+pub fn ports() -> Vec<String> {
+    let input = match MidiInput::new(CLIENT) {
+        Ok(input) => input,
+        Err(error) => {
+            error!("Couldn't look for MIDI inputs: {error}");
+            return Vec::new();
+        }
+    };
+    input.ports().iter().filter_map(|port| input.port_name(port).ok()).collect()
+}
+
+/// A parameter of a node that a control can be mapped to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    /// The track whose insert chain the node is in, if it isn't in the main graph.
+    pub track: Option<u32>,
+    /// The node, after the groups it's in.
+    pub path: Vec<NodeId>,
+    /// The index of the parameter among the parameters of the node's effect.
+    pub parameter: usize,
+}
+
+/// How a control moves a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// The part of the parameter's range the control goes through from its lowest to its highest value, as fractions of the range. It's reversed if
+    /// the first is higher.
+    pub range: [f64; 2],
+    /// Only move the parameter once the control reaches its value, so that it doesn't jump.
+    pub pickup: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { range: [0., 1.], pickup: false }
+    }
+}
+
+/// A control mapped to a parameter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mapping {
+    pub control: Control,
+    pub target: Target,
+    pub settings: Settings,
+    /// Whether the control reached the value of the parameter since it was last changed some other way, when the parameter is picked up.
+    #[serde(skip)]
+    picked_up: bool,
+    /// Where the control last was, as a fraction of the parameter's range.
+    #[serde(skip)]
+    last: Option<f64>,
+}
+
+impl Mapping {
+    /// Return the value of the parameter, whose values are in `range` and which is at `current`, after the control moved to `value`. Returns [`None`]
+    /// if the parameter stays where it is, because the control hasn't picked it up yet.
+    pub fn value(&mut self, current: f64, value: u8, range: &RangeInclusive<f64>) -> Option<f64> {
+        let (start, end) = (*range.start(), *range.end());
+        let current = if end > start { (current - start) / (end - start) } else { 0. };
+        let [from, to] = self.settings.range;
+        let fraction = (to - from).mul_add(f64::from(value) / 127., from);
+        if self.settings.pickup && !self.picked_up {
+            // A control that moves quickly can skip over the value, so passing it counts too.
+            let passed = self.last.is_some_and(|last| (last - current).signum() != (fraction - current).signum());
+            self.last = Some(fraction);
+            if !passed && (fraction - current).abs() > PICKUP_DISTANCE {
+                return None;
+            }
+            self.picked_up = true;
+        }
+        self.last = Some(fraction);
+        Some((end - start).mul_add(fraction, start))
+    }
+}
+
+/// The settings of every control as they were last set, by control. Saved with keys like `"1:7"` for CC 7 on the first channel, as TOML keys have to be
+/// strings.
+#[derive(Default, Serialize, Deserialize)]
+struct Profile {
+    controls: BTreeMap<String, Settings>,
+}
+
+impl Profile {
+    fn load() -> Self {
+        config::load(PROFILE_PATH, "the controller profile").unwrap_or_default()
+    }
+
+    fn save(&self) {
+        config::save(PROFILE_PATH, self, "the controller profile");
+    }
+
+    fn key(control: Control) -> String {
+        format!("{}:{}", control.channel + 1, control.controller)
+    }
+}
+
+/// The mappings of a project, along with the parameter waiting for a control to be moved, if any.
+pub struct Mappings {
+    list: Vec<Mapping>,
+    learning: Option<Target>,
+    profile: Profile,
+}
+
+impl Default for Mappings {
+    fn default() -> Self {
+        Self { list: Vec::new(), learning: None, profile: Profile::load() }
+    }
+}
+
+impl Mappings {
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.list
+    }
+
+    /// Replace the mappings, for example with those loaded from a project, which stops waiting for a control to be moved.
+    pub fn set(&mut self, mappings: Vec<Mapping>) {
+        self.list = mappings;
+        self.learning = None;
+    }
+
+    /// Take `change` into account, returning the mappings of its control to change the parameters of, or [`None`] if the control was mapped to
+    /// the parameter waiting for it. Mapping a control replaces what it was mapped to.
+    pub fn changed(&mut self, change: ControlChange) -> Option<impl Iterator<Item = &mut Mapping>> {
+        if let Some(target) = self.learning.take() {
+            let settings = self.profile.controls.get(&Profile::key(change.control)).copied().unwrap_or_default();
+            self.list.retain(|mapping| mapping.control != change.control && mapping.target != target);
+            self.list.push(Mapping { control: change.control, target, settings, picked_up: false, last: None });
+            return None;
+        }
+        Some(self.list.iter_mut().filter(move |mapping| mapping.control == change.control))
+    }
+
+    /// Add a menu to map a control to the parameter at `target` to the `response` of its editor, and say which control it's mapped to when hovered.
+    /// Returns the description of the edit if a mapping was removed or changed.
+    pub fn parameter_menu(&mut self, response: &Response, target: &Target) -> Option<String> {
+        let mut edit = None;
+        let index = self.list.iter().position(|mapping| mapping.target == *target);
+        if response.changed() {
+            // The parameter moved away from where the control picked it up.
+            if let Some(index) = index {
+                self.list[index].picked_up = false;
+            }
+        }
+        if self.learning.as_ref() == Some(target) {
+            response.show_tooltip_text("Move a control on a MIDI controller to map it");
+        } else if let Some(index) = index {
+            response.clone().on_hover_text(format!("Mapped to {}", self.list[index].control));
+        }
+        response.context_menu(|ui| {
+            if self.learning.as_ref() == Some(target) {
+                ui.label(RichText::new("Move a control…").italics());
+                if ui.button("Cancel").clicked() {
+                    self.learning = None;
+                    ui.close_menu();
+                }
+            } else if ui.button("MIDI learn").on_hover_text("Map the next control moved on a MIDI controller to this parameter").clicked() {
+                self.learning = Some(target.clone());
+                ui.close_menu();
+            }
+            let Some(index) = index else {
+                return;
+            };
+            ui.separator();
+            let mapping = &mut self.list[index];
+            ui.label(mapping.control.to_string());
+            let mut settings = mapping.settings;
+            ui.horizontal(|ui| {
+                fn percent(value: &mut f64) -> DragValue<'_> {
+                    DragValue::new(value).range(0. ..=1.).speed(0.005).custom_formatter(|value, _| format!("{:.0}%", value * 100.))
+                }
+                ui.label("From");
+                ui.add(percent(&mut settings.range[0]));
+                ui.label("to");
+                ui.add(percent(&mut settings.range[1]));
+            });
+            ui.checkbox(&mut settings.pickup, "Pick up").on_hover_text("Only move the parameter once the control reaches its value, so that it doesn't jump");
+            if settings != mapping.settings {
+                mapping.settings = settings;
+                mapping.picked_up = false;
+                self.profile.controls.insert(Profile::key(mapping.control), settings);
+                self.profile.save();
+                edit = Some("Change MIDI mapping".into());
+            }
+            if ui.button("Remove mapping").clicked() {
+                self.list.remove(index);
+                edit = Some("Remove MIDI mapping".into());
+                ui.close_menu();
+            }
+        });
+        edit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Message> {
+        let mut parser = Parser::default();
+        bytes.iter().filter_map(|byte| parser.push(*byte)).collect()
+    }
+
+    fn mapping(pickup: bool) -> Mapping {
+        let target = Target { track: None, path: Vec::new(), parameter: 0 };
+        Mapping { control: Control { channel: 0, controller: 7 }, target, settings: Settings { range: [0., 1.], pickup }, picked_up: false, last: None }
+    }
+
+    #[test]
+    fn controls_are_read_with_and_without_a_status() {
+        let messages = parse(&[0xb2, 7, 100, 7, 20]);
+        let changes: Vec<_> = messages.iter().map(|message| if let Message::Control(change) = message { (change.control, change.value) } else { panic!("{message:?} isn't a control") }).collect();
+        let control = Control { channel: 2, controller: 7 };
+        assert_eq!(changes, [(control, 100), (control, 20)]);
+    }
+
+    #[test]
+    fn notes_without_velocity_are_releases() {
+        let messages = parse(&[0x91, 36, 90, 36, 0, 0x81, 36, 64]);
+        assert!(matches!(
+            messages.as_slice(),
+            [Message::Note { channel: 1, key: 36, velocity: 90 }, Message::Release { channel: 1, key: 36 }, Message::Release { channel: 1, key: 36 }]
+        ));
+    }
+
+    #[test]
+    fn pitch_bends_follow_the_range_set_up_by_the_controller() {
+        // The MPE configuration message gives the lower zone three channels, which bend 48 semitones, while the others bend 2.
+        let messages = parse(&[0xb0, 101, 0, 100, 6, 6, 3, 0xe1, 0, 0x60, 0xe5, 0, 0x60, 0xd1, 127]);
+        let expression: Vec<_> = messages.iter().filter_map(|message| if let Message::Expression { channel, dimension, value } = message { Some((*channel, *dimension, *value)) } else { None }).collect();
+        assert_eq!(expression, [(1, Dimension::PitchBend, 24.), (5, Dimension::PitchBend, 1.), (1, Dimension::Pressure, 1.)]);
+    }
+
+    #[test]
+    fn real_time_messages_in_the_middle_of_others_keep_them() {
+        let messages = parse(&[0xb0, 1, 0xfa, 64, 0xf8, 0xfc]);
+        assert!(matches!(
+            messages.as_slice(),
+            [
+                Message::Transport(Transport::Start),
+                Message::Control(ControlChange { value: 64, .. }),
+                Message::Transport(Transport::Stop)
+            ]
+        ));
+    }
+
+    #[test]
+    fn machine_control_is_read() {
+        let messages = parse(&[0xf0, 0x7f, 0x7f, 0x06, 0x02, 0xf7, 0xf0, 0x7f, 0x7f, 0x06, 0x01, 0xf7, 0xf0, 0x7f, 0x7f, 0x06, 0x06, 0xf7]);
+        assert!(matches!(messages.as_slice(), [Message::Transport(Transport::Play), Message::Transport(Transport::Stop)]));
+    }
+
+    #[test]
+    fn long_system_exclusive_messages_are_dropped() {
+        let mut bytes = vec![0xf0, 0x7f, 0x7f, 0x06, 0x02];
+        bytes.extend([0; MAX_SYSEX]);
+        bytes.push(0xf7);
+        assert!(parse(&bytes).is_empty());
+    }
+
+    #[test]
+    fn a_control_without_pickup_moves_the_parameter_at_once() {
+        let mut mapping = mapping(false);
+        assert_eq!(mapping.value(50., 127, &(0. ..=100.)), Some(100.));
+    }
+
+    #[test]
+    fn a_control_picks_up_the_parameter_once_it_gets_near() {
+        let mut mapping = mapping(true);
+        let range = 0. ..=127.;
+        assert_eq!(mapping.value(64., 10, &range), None);
+        assert_eq!(mapping.value(64., 30, &range), None);
+        assert_eq!(mapping.value(64., 63, &range), Some(63.));
+        assert_eq!(mapping.value(63., 20, &range), Some(20.));
+    }
+
+    #[test]
+    fn a_control_passing_over_the_parameter_picks_it_up() {
+        let mut mapping = mapping(true);
+        let range = 0. ..=127.;
+        assert_eq!(mapping.value(64., 20, &range), None);
+        assert_eq!(mapping.value(64., 100, &range), Some(100.));
+    }
+}
@@ -0,0 +1,96 @@
+//! Crash reports, written to a file when the app panics and pointed out the next time it starts.
+
+use std::{
+    backtrace::Backtrace,
+    fs,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+use tracing::error;
+
+use crate::{config, info, logs};
+
+/// The folder crash reports are written to, in the configuration folder.
+const FOLDER: &str = "crashes";
+/// The file holding the path of the last report until it was pointed out, in [`FOLDER`].
+const PENDING: &str = "pending";
+/// How many of the latest log lines are added to a report.
+const LOG_LINES: usize = 100;
+
+/// The path of the project open, to say which it was in the report.
+static PROJECT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Write a report whenever the app panics, before doing what was done on panics until now.
+pub fn install() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |panic| {
+        if let Some(path) = write_report(panic) {
+            eprintln!("A crash report was written to {}", path.display());
+        }
+        previous(panic);
+    }));
+}
+
+/// Say that the project at `path` is open, or that none is.
+pub fn set_project(path: Option<&Path>) {
+    let Ok(mut project) = PROJECT.lock() else {
+        return;
+    };
+    if project.as_deref() != path {
+        *project = path.map(Path::to_path_buf);
+    }
+}
+
+/// Return the path of the report written when the app last crashed, if it wasn't returned before.
+pub fn take_pending() -> Option<PathBuf> {
+    let pending = config::path(FOLDER).join(PENDING);
+    let path = fs::read_to_string(&pending).ok()?;
+    if let Err(error) = fs::remove_file(&pending) {
+        error!("Couldn't remove {}: {error}", pending.display());
+    }
+    Some(PathBuf::from(path.trim_end())).filter(|path| path.exists())
+}
+
+/// Return the paths of the reports written, oldest first.
+pub fn reports() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(config::path(FOLDER)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .sorted()
+        .collect()
+}
+
+/// Write a report of `panic` to a file named after the time, and return its path if it could be written.
+fn write_report(panic: &PanicHookInfo<'_>) -> Option<PathBuf> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    // The project and the log are skipped if the panic happened while they were used, rather than waiting forever.
+    let project = PROJECT
+        .try_lock()
+        .ok()
+        .and_then(|project| project.as_ref().map(|path| path.display().to_string()))
+        .unwrap_or_else(|| "None".into());
+    let log = logs::recent(LOG_LINES).map_or_else(|| "Unavailable".into(), |lines| lines.iter().join("\n"));
+    let report = format!(
+        "Volt crashed at {time} (seconds since the Unix epoch).\n\n{panic}\n\nBacktrace:\n{}\n\nSystem:\n{}\n\nProject: {project}\n\nLast log lines:\n{log}\n",
+        Backtrace::force_capture(),
+        info::system(),
+    );
+    let folder = config::path(FOLDER);
+    let path = folder.join(format!("crash-{time}.txt"));
+    let written = fs::create_dir_all(&folder).and_then(|()| fs::write(&path, report)).and_then(|()| fs::write(folder.join(PENDING), path.to_string_lossy().as_bytes()));
+    match written {
+        Ok(()) => Some(path),
+        Err(error) => {
+            eprintln!("Couldn't write a crash report to {}: {error}", path.display());
+            None
+        }
+    }
+}
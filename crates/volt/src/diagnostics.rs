@@ -0,0 +1,62 @@
+//! Bundles of what helps with bug reports: what the app runs on, its log, its timings and its settings, but none of the user's audio.
+
+use std::{fs, io, path::Path};
+
+use itertools::Itertools;
+use tracing::error;
+
+use crate::{archive, config, crash, info, logs, timings};
+
+/// The settings files added to bundles, which say how the app is set up. The other files of the configuration folder are left out, as they list
+/// the user's folders and files or hold the keys to web services.
+const SETTINGS: [&str; 3] = ["config.toml", "keymap.toml", "controllers.toml"];
+/// Parts of the names of settings whose values are replaced in bundles, in case a secret ends up in one of the files added.
+const SECRETS: [&str; 4] = ["api_key", "token", "password", "secret"];
+
+/// Write a ZIP archive at `path` holding the system info, the log, the timings, the settings and the crash reports.
+pub fn export(path: &Path) -> io::Result<()> {
+    let mut writer = archive::Writer::create(path)?;
+    writer.add("system.txt", info::system().as_bytes())?;
+    writer.add("log.txt", logs::lines().iter().join("\n").as_bytes())?;
+    writer.add("timings.txt", timings::report().as_bytes())?;
+    for name in SETTINGS {
+        let text = match fs::read_to_string(config::path(name)) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        // Files that can't be read as settings aren't added, as there's no telling what's in them.
+        match toml::from_str::<toml::Table>(&text) {
+            Ok(mut settings) => {
+                redact(&mut settings);
+                writer.add(&format!("config/{name}"), settings.to_string().as_bytes())?;
+            }
+            Err(error) => error!("Couldn't add {name} to the diagnostics: {error}"),
+        }
+    }
+    for report in crash::reports() {
+        let name = report.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        writer.add(&format!("crashes/{name}"), &fs::read(&report)?)?;
+    }
+    writer.finish()
+}
+
+/// Replace the values of the settings in `table`, and in the tables it holds, whose names look like they hold a secret.
+fn redact(table: &mut toml::Table) {
+    for (name, value) in table.iter_mut() {
+        let name = name.to_lowercase();
+        if SECRETS.iter().any(|secret| name.contains(secret)) {
+            *value = toml::Value::String("(redacted)".into());
+        } else {
+            redact_value(value);
+        }
+    }
+}
+
+fn redact_value(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => redact(table),
+        toml::Value::Array(values) => values.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
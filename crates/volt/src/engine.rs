@@ -0,0 +1,840 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+use blerp::{
+    midi::Dimension,
+    processing::{
+        bridge::{ring, triple, Consumer, Producer, Reader, Writer},
+        graph::{Node, Schedule, Tap, RESERVED_FRAMES},
+        resample,
+        synth::{Live, Synth},
+    },
+};
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    Sample, Stream, StreamConfig,
+};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+use rodio::{Decoder, Source};
+use tracing::error;
+
+use crate::{archive, timings};
+
+pub use preview::{tempo_in_name, PreviewBus, PreviewCommand, PreviewOptions, PreviewState};
+pub use streaming::{probe, StreamedClip, STREAMED_LENGTH};
+
+mod preview;
+mod streaming;
+
+/// Plays a [`Schedule`] through an output device, along with previews of files on their own bus and notes played live on a synth. The schedule can be replaced at any time, which takes effect on the next block.
+///
+/// The output callback never waits on the rest of the app nor allocates: it takes commands from a [`ring`], sends what it's done with back through
+/// another to be dropped on a thread of its own, and publishes where playback is through a [`triple`] buffer.
+pub struct Engine {
+    config: StreamConfig,
+    commands: Producer<Command>,
+    /// Keeps the thread dropping what the output callback replaced or finished playing running, until the engine is dropped.
+    _collector: Sender<()>,
+    _output: Stream,
+    output_name: String,
+    /// Records from the capture device, while the schedule has a live input or something listens to the input.
+    live_input: Option<Stream>,
+    /// The name of the capture device picked, or [`None`] for the default one.
+    input_name: Option<String>,
+    /// The latest audio recorded from the capture device in the output format, whether or not the schedule plays it.
+    input_tap: Arc<Tap>,
+    latency: Arc<Latency>,
+    playback: Reader<Playback>,
+    playing: bool,
+    /// Decoded files converted to the output format, or [`None`] for files that couldn't be decoded.
+    files: HashMap<PathBuf, Option<Arc<[f64]>>>,
+    /// The formats of files played by file players, or [`None`] for files that couldn't be read.
+    formats: HashMap<PathBuf, Option<streaming::Format>>,
+    /// Rendered tracks, along with the revision of the playlist they were rendered from.
+    tracks: HashMap<u32, (u64, Arc<[f64]>)>,
+    /// Reads long clips from the disk while they play, as they aren't rendered into their track.
+    streamer: streaming::Streamer,
+    /// Files to decode for the preview bus, along with their request ids, which is done on another thread.
+    preview_requests: Sender<(u64, PathBuf, PreviewOptions)>,
+    preview: Arc<preview::Shared>,
+    /// The file last asked for on the preview bus and whether it loops, until it's stopped.
+    preview_path: Option<(PathBuf, bool)>,
+    /// What went wrong, like files that couldn't be previewed or devices that failed, until it's taken to be shown.
+    errors: Receiver<String>,
+    error_sender: Sender<String>,
+}
+
+enum Command {
+    Schedule(Schedule),
+    Play,
+    Stop,
+    /// Move playback to a frame, which it also starts from from then on.
+    Seek(usize),
+    StopPreview,
+    /// Move the preview to a frame.
+    SeekPreview(usize),
+    PreviewBus(PreviewBus),
+    /// Keep processing the live inputs of the schedule while playback is stopped, so they can be monitored.
+    Monitor(bool),
+    /// Feed the live inputs of the schedule from a newly opened capture device.
+    Live(Consumer<f64>),
+    LiveNote(LiveNote),
+    LiveSynth(Synth),
+}
+
+/// Something played on the synth notes are played live with, like from a MIDI controller. Channels are those of the controller, so that each note
+/// of an MPE controller follows its own expression.
+#[derive(Debug, Clone, Copy)]
+pub enum LiveNote {
+    Press { channel: u8, key: u8, velocity: u8 },
+    Release { channel: u8, key: u8 },
+    Express { channel: u8, dimension: Dimension, value: f64 },
+}
+
+/// What the output callback is done with, sent back to be dropped outside of it.
+#[allow(dead_code, reason = "the values are only sent back to be dropped")]
+enum Garbage {
+    Schedule(Schedule),
+    Preview(preview::Audio),
+    Live(Consumer<f64>),
+}
+
+/// How long each step from the capture device to the output device took lately, in microseconds, which is how far behind the input is heard.
+#[derive(Default)]
+struct Latency {
+    /// From when the audio was captured to when it reached the app.
+    input: AtomicU64,
+    /// How long it waited for the output to take it.
+    queue: AtomicU64,
+    /// From when the output took it to when the output device plays it.
+    output: AtomicU64,
+}
+
+/// Where playback is and how many frames the output device asks for at a time, as of the latest callback.
+#[derive(Debug, Clone, Copy, Default)]
+struct Playback {
+    /// The frame playback is at, or starts from while it's stopped.
+    position: usize,
+    /// How many frames the latest callback filled, or 0 before the first one.
+    buffer: usize,
+    preview: preview::Playing,
+}
+
+/// How many commands can wait for the output callback, which takes them all on every block.
+const COMMANDS: usize = 256;
+/// How many things the output callback is done with can wait to be dropped, and how many more it holds on to when they can't before it stops taking
+/// commands.
+const GARBAGE: usize = 64;
+/// How often what the output callback is done with is dropped, which is far more often than it can fill [`GARBAGE`].
+const COLLECTION_INTERVAL: Duration = Duration::from_millis(50);
+/// How many decoded previews can wait for the output callback.
+const PREVIEWS: usize = 4;
+/// How many blocks of live input can be waiting before the oldest are dropped, to keep the latency low.
+const LIVE_BLOCKS: usize = 4;
+/// How many samples of live input can be waiting at most, which is far more than [`LIVE_BLOCKS`] of any output device.
+const LIVE_SAMPLES: usize = 1 << 16;
+/// How many frames of the live input are kept in its tap, which is enough to find the pitch of the lowest string of a bass.
+const INPUT_TAP_LENGTH: usize = 8192;
+
+impl Engine {
+    /// Open the output device called `name`, or the default one if no name is given or there is no such device. Return [`None`] if no device could be opened.
+    pub fn open(name: Option<&str>) -> Option<Self> {
+        let host = cpal::default_host();
+        let device = name
+            .and_then(|name| host.output_devices().ok()?.find(|device| device.name().is_ok_and(|other| other == name)))
+            .or_else(|| host.default_output_device())?;
+        let output_name = device.name().unwrap_or_default();
+        let config = device
+            .default_output_config()
+            .inspect_err(|error| error!("Couldn't configure the output device: {error}"))
+            .ok()?
+            .config();
+        let (commands, command_receiver) = ring(COMMANDS);
+        let (garbage_sender, garbage) = ring(GARBAGE);
+        let (preview_sender, preview_receiver) = ring(PREVIEWS);
+        let (error_sender, errors) = unbounded();
+        let preview = Arc::new(preview::Shared::default());
+        let latency = Arc::new(Latency::default());
+        let (playback_writer, playback) = triple(Playback::default());
+        let callback = output_callback(
+            Bridge {
+                commands: command_receiver,
+                previews: preview_receiver,
+                garbage: Recycler::new(garbage_sender),
+                playback: playback_writer,
+            },
+            (Arc::clone(&preview), Arc::clone(&latency)),
+            usize::from(config.channels),
+            config.sample_rate.0,
+        );
+        let output_errors = error_sender.clone();
+        let on_error = move |error| {
+            error!("Audio output failed: {error}");
+            let _ = output_errors.send(format!("Audio output failed, {error}."));
+        };
+        let output = device
+            .build_output_stream(&config, callback, on_error, None)
+            .inspect_err(|error| error!("Couldn't open the output device: {error}"))
+            .ok()?;
+        output.play().inspect_err(|error| error!("Couldn't start audio output: {error}")).ok()?;
+        let (collector, stop) = bounded::<()>(0);
+        spawn(move || {
+            while stop.recv_timeout(COLLECTION_INTERVAL) == Err(RecvTimeoutError::Timeout) {
+                garbage.drain().for_each(drop);
+            }
+        });
+        let (preview_requests, request_receiver) = unbounded::<(u64, PathBuf, PreviewOptions)>();
+        let preview_errors = error_sender.clone();
+        let shared = Arc::clone(&preview);
+        let (channels, sample_rate) = (config.channels, config.sample_rate.0);
+        spawn(move || {
+            while let Ok(request) = request_receiver.recv() {
+                // Only the latest file picked is worth decoding.
+                let (id, path, options) = request_receiver.try_iter().last().unwrap_or(request);
+                if id != shared.requested.load(Ordering::Relaxed) {
+                    continue;
+                }
+                match preview::load(&path, options, channels, sample_rate) {
+                    Ok(samples) => {
+                        // If the output callback didn't take the previews before, it's stalled and this one would be stale by the time it plays.
+                        let _ = preview_sender.push(preview::Audio { id, samples: samples.into(), looping: options.looping });
+                    }
+                    Err(error) => {
+                        error!("Couldn't preview {}: {error}", path.display());
+                        shared.failed.store(id, Ordering::Relaxed);
+                        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                        let _ = preview_errors.send(format!("Couldn't preview {name}, {error}."));
+                    }
+                }
+            }
+        });
+        Some(Self {
+            config,
+            commands,
+            _collector: collector,
+            _output: output,
+            output_name,
+            live_input: None,
+            input_name: None,
+            input_tap: Arc::new(Tap::new(usize::from(channels), f64::from(sample_rate), INPUT_TAP_LENGTH)),
+            latency,
+            playback,
+            playing: false,
+            files: HashMap::new(),
+            formats: HashMap::new(),
+            tracks: HashMap::new(),
+            streamer: streaming::Streamer::new(channels, sample_rate),
+            preview_requests,
+            preview,
+            preview_path: None,
+            errors,
+            error_sender,
+        })
+    }
+
+    pub const fn sample_rate(&self) -> u32 {
+        self.config.sample_rate.0
+    }
+
+    pub const fn channels(&self) -> u16 {
+        self.config.channels
+    }
+
+    /// Replace the schedule being played, keeping the playback position.
+    pub fn update(&self, schedule: Schedule) {
+        self.send(Command::Schedule(schedule));
+    }
+
+    /// Send `command` to the output callback.
+    fn send(&self, command: Command) {
+        if self.commands.push(command).is_err() {
+            error!("The audio output isn't taking commands, one was dropped");
+        }
+    }
+
+    pub const fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Play `note` on the live synth, whether or not playback is going.
+    pub fn play_live(&self, note: LiveNote) {
+        self.send(Command::LiveNote(note));
+    }
+
+    /// Play the notes played live with `synth` from now on.
+    pub fn set_live_synth(&self, synth: Synth) {
+        self.send(Command::LiveSynth(synth));
+    }
+
+    /// Start playback from where it was last moved to with [`Self::seek`], or stop it.
+    pub fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+        self.send(if playing { Command::Play } else { Command::Stop });
+    }
+
+    /// Move playback to `position`, where it starts from from now on.
+    pub fn seek(&self, position: Duration) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let frame = (position.as_secs_f64() * f64::from(self.sample_rate())) as usize;
+        self.send(Command::Seek(frame));
+    }
+
+    /// Return where playback is, or where it starts from while it's stopped.
+    pub fn position(&self) -> Duration {
+        #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+        let frame = self.playback.latest().position as f64;
+        Duration::from_secs_f64(frame / f64::from(self.sample_rate()))
+    }
+
+    /// Return how many frames the output device asks for at a time, or [`None`] if it didn't ask for any yet.
+    pub fn buffer_size(&self) -> Option<usize> {
+        Some(self.playback.latest().buffer).filter(|frames| *frames > 0)
+    }
+
+    /// Return the name of the output device being played through.
+    pub fn output_name(&self) -> &str {
+        &self.output_name
+    }
+
+    /// Return the name of the capture device picked, or [`None`] if the default one is used.
+    pub fn input_name(&self) -> Option<&str> {
+        self.input_name.as_deref()
+    }
+
+    /// Record from the capture device called `name` from now on, or from the default one if `name` is [`None`].
+    pub fn set_input_device(&mut self, name: Option<String>) {
+        self.input_name = name;
+        if self.live_input.take().is_some() {
+            self.set_live_input(true);
+        }
+    }
+
+    /// Keep processing the live inputs of the schedule while playback is stopped, or only process the schedule while playing.
+    pub fn set_monitoring(&self, monitoring: bool) {
+        self.send(Command::Monitor(monitoring));
+    }
+
+    /// Return how far behind the capture device its audio is heard through the output device, or [`None`] if nothing was recorded yet.
+    pub fn monitoring_latency(&self) -> Option<Duration> {
+        self.live_input.as_ref()?;
+        let [input, queue, output] = [&self.latency.input, &self.latency.queue, &self.latency.output].map(|latency| latency.load(Ordering::Relaxed));
+        (input > 0).then(|| Duration::from_micros(input + queue + output))
+    }
+
+    /// Return the tap keeping the latest audio recorded from the capture device, while it's recording.
+    pub fn input_tap(&self) -> Arc<Tap> {
+        Arc::clone(&self.input_tap)
+    }
+
+    /// Start or stop recording from the capture device, which is fed to live inputs of the schedule.
+    pub fn set_live_input(&mut self, enabled: bool) {
+        if !enabled {
+            self.live_input = None;
+            return;
+        }
+        if self.live_input.is_some() {
+            return;
+        }
+        let host = cpal::default_host();
+        let picked = self
+            .input_name
+            .as_ref()
+            .and_then(|name| host.input_devices().ok()?.find(|device| device.name().is_ok_and(|other| other == *name)));
+        let Some(device) = picked.or_else(|| host.default_input_device()) else {
+            error!("There is no capture device for the live input");
+            let _ = self.error_sender.send("There is no capture device for the live input.".into());
+            return;
+        };
+        let config = match device.default_input_config() {
+            Ok(config) => config.config(),
+            Err(error) => {
+                error!("Couldn't configure the capture device: {error}");
+                let _ = self.error_sender.send(format!("Couldn't configure the capture device, {error}."));
+                return;
+            }
+        };
+        let (sender, receiver) = ring(LIVE_SAMPLES);
+        let input_tap = Arc::clone(&self.input_tap);
+        let latency = Arc::clone(&self.latency);
+        let (channels, sample_rate) = (usize::from(config.channels), f64::from(config.sample_rate.0));
+        let (output_channels, output_sample_rate) = (usize::from(self.channels()), f64::from(self.sample_rate()));
+        // Blocks are converted up to this many frames at a time, so that these never grow once the callback runs.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "lengths are positive and well within range")]
+        let resampled_frames = (RESERVED_FRAMES as f64 * output_sample_rate / sample_rate.max(1.)).ceil() as usize + 1;
+        let mut block = Vec::with_capacity(RESERVED_FRAMES * channels.max(1));
+        let mut resampled = Vec::with_capacity(resampled_frames * output_channels.max(1));
+        let callback = move |data: &[f32], info: &cpal::InputCallbackInfo| {
+            let timestamp = info.timestamp();
+            if let Some(input) = timestamp.callback.duration_since(&timestamp.capture) {
+                latency.input.store(u64::try_from(input.as_micros()).unwrap_or(u64::MAX), Ordering::Relaxed);
+            }
+            for part in data.chunks(RESERVED_FRAMES * channels.max(1)) {
+                block.clear();
+                block.extend(part.iter().copied().map(f64::from_sample));
+                resample::convert_into(&block, channels, sample_rate, output_channels, output_sample_rate, &mut resampled);
+                input_tap.push(&resampled);
+                // If the output isn't keeping up, the rest of the block is dropped rather than adding latency.
+                for &sample in &resampled {
+                    if sender.push(sample).is_err() {
+                        break;
+                    }
+                }
+            }
+        };
+        let capture_errors = self.error_sender.clone();
+        let on_error = move |error| {
+            error!("Audio capture failed: {error}");
+            let _ = capture_errors.send(format!("Audio capture failed, {error}."));
+        };
+        let stream = device
+            .build_input_stream(&config, callback, on_error, None)
+            .map_err(|error| format!("Couldn't open the capture device, {error}."))
+            .and_then(|stream| stream.play().map(|()| stream).map_err(|error| format!("Couldn't start audio capture, {error}.")));
+        self.live_input = stream
+            .inspect_err(|error| {
+                error!("{error}");
+                let _ = self.error_sender.send(error.clone());
+            })
+            .ok();
+        if self.live_input.is_some() {
+            self.send(Command::Live(receiver));
+        }
+    }
+
+    /// Control the preview bus. Files are decoded on another thread, and start playing once they're ready.
+    pub fn preview(&mut self, command: PreviewCommand) {
+        match command {
+            PreviewCommand::Play(path, options) => {
+                let id = self.preview.requested.fetch_add(1, Ordering::Relaxed) + 1;
+                self.preview_path = Some((path.clone(), options.looping));
+                let _ = self.preview_requests.send((id, path, options));
+            }
+            PreviewCommand::Seek(position) => {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+                let frame = (position.as_secs_f64() * f64::from(self.sample_rate())) as usize;
+                self.send(Command::SeekPreview(frame));
+            }
+            PreviewCommand::Stop => {
+                self.preview.requested.fetch_add(1, Ordering::Relaxed);
+                self.preview_path = None;
+                self.send(Command::StopPreview);
+            }
+            PreviewCommand::Bus(bus) => self.send(Command::PreviewBus(bus)),
+        }
+    }
+
+    /// Return messages for what went wrong since the last call, like files that couldn't be previewed or decoded and devices that failed.
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.try_iter().collect()
+    }
+
+    /// Return what the preview bus is playing or loading, or [`None`] if it's silent.
+    pub fn preview_state(&self) -> Option<PreviewState> {
+        let (path, looping) = self.preview_path.as_ref()?;
+        self.preview.state(path, *looping, self.sample_rate(), self.playback.latest().preview)
+    }
+
+    /// Return how many decoded files and rendered tracks are kept, and how many bytes their samples take.
+    pub fn cache_usage(&self) -> (usize, usize) {
+        let files = self.files.values().flatten();
+        let tracks = self.tracks.values().map(|(_, samples)| samples);
+        let samples = files.chain(tracks).map(|samples| size_of_val::<[f64]>(samples));
+        (self.files.len() + self.tracks.len(), samples.sum())
+    }
+
+    /// Forget the decoded files and rendered tracks, which are decoded and rendered again the next time they're played. What's playing keeps its samples until
+    /// then.
+    pub fn clear_cache(&mut self) {
+        self.files.clear();
+        self.formats.clear();
+        self.tracks.clear();
+    }
+
+    /// Return the samples of the audio file at `path` in the output format, decoding it the first time, or [`None`] if it can't be decoded.
+    pub fn file(&mut self, path: &Path) -> Option<Arc<[f64]>> {
+        let (channels, sample_rate) = (self.channels(), self.sample_rate());
+        let errors = &self.error_sender;
+        self.files
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                decode(path, channels, sample_rate)
+                    .inspect_err(|error| {
+                        error!("Couldn't decode {}: {error}", path.display());
+                        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                        let _ = errors.send(format!("Couldn't play {name}, {error}."));
+                    })
+                    .ok()
+                    .map(Arc::from)
+            })
+            .clone()
+    }
+
+    /// Return the node playing the audio file at `path` from the start of playback, looping it if it's `looping`, or [`None`] if it can't be decoded.
+    /// Long files that don't loop are streamed from the disk rather than decoded whole.
+    pub fn file_player(&mut self, path: &Path, looping: bool) -> Option<Node> {
+        let format = *self.formats.entry(path.to_path_buf()).or_insert_with(|| probe(path).ok());
+        if let Some(format) = format.filter(|format| !looping && format.length >= STREAMED_LENGTH) {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lengths are positive and well within range")]
+            let length = (format.length.as_secs_f64() * f64::from(self.sample_rate())).round() as usize;
+            let stream = self.stream(StreamedClip {
+                path: path.to_path_buf(),
+                channels: format.channels,
+                sample_rate: format.sample_rate,
+                offset: 0,
+                start: 0,
+                length,
+            });
+            return Some(Node::Track { samples: Arc::default(), streams: vec![stream] });
+        }
+        Some(Node::Samples { samples: self.file(path)?, looping })
+    }
+
+    /// Return the audio of a playlist track in the output format, calling `render` only if the track wasn't rendered at this `revision` of the playlist yet.
+    pub fn track(&mut self, track: u32, revision: u64, render: impl FnOnce() -> Vec<f64>) -> Arc<[f64]> {
+        match self.tracks.get(&track) {
+            Some((rendered, samples)) if *rendered == revision => Arc::clone(samples),
+            _ => {
+                let samples: Arc<[f64]> = render().into();
+                self.tracks.insert(track, (revision, Arc::clone(&samples)));
+                samples
+            }
+        }
+    }
+
+    /// Return the stream of a clip played from the disk, which is read ahead of playback as long as a schedule plays it.
+    pub fn stream(&mut self, clip: StreamedClip) -> Arc<blerp::processing::stream::Stream> {
+        self.streamer.stream(clip)
+    }
+}
+
+/// Return the samples of the audio file at `path` converted to `channels` channels at `sample_rate`.
+///
+/// # Errors
+/// Returns why the file couldn't be opened or decoded.
+pub fn decode(path: &Path, channels: u16, sample_rate: u32) -> Result<Vec<f64>, String> {
+    let decoder = archive::open(path).map_err(|error| error.to_string()).and_then(|file| Decoder::new(file).map_err(|error| error.to_string()))?;
+    let (file_channels, file_sample_rate) = (decoder.channels(), decoder.sample_rate());
+    let samples = decoder.map(f64::from_sample).collect::<Vec<_>>();
+    Ok(resample::convert(&samples, usize::from(file_channels), f64::from(file_sample_rate), usize::from(channels), f64::from(sample_rate)))
+}
+
+/// The ends of the rings and the triple buffer the output callback talks to the rest of the engine through.
+struct Bridge {
+    commands: Consumer<Command>,
+    /// Previews decoded on their own thread.
+    previews: Consumer<preview::Audio>,
+    garbage: Recycler,
+    playback: Writer<Playback>,
+}
+
+/// Sends what the output callback is done with back to be dropped, holding on to what doesn't fit until there's room, so that nothing is dropped on
+/// the audio thread.
+struct Recycler {
+    garbage: Producer<Garbage>,
+    /// What didn't fit in the ring yet, which is kept within the room made for it from the start.
+    pending: Vec<Garbage>,
+}
+
+impl Recycler {
+    fn new(garbage: Producer<Garbage>) -> Self {
+        Self { garbage, pending: Vec::with_capacity(GARBAGE) }
+    }
+
+    /// Send `old` back to be dropped, or hold on to it until there's room.
+    fn recycle(&mut self, old: Option<Garbage>) {
+        if let Some(old) = old {
+            if let Err(old) = self.garbage.push(old) {
+                debug_assert!(self.room() > 0, "the output callback can't hold on to more than it made room for");
+                self.pending.push(old);
+            }
+        }
+    }
+
+    /// Send what was held on to again, now that the thread dropping it may have made room.
+    fn retry(&mut self) {
+        while let Some(old) = self.pending.pop() {
+            if let Err(old) = self.garbage.push(old) {
+                self.pending.push(old);
+                break;
+            }
+        }
+    }
+
+    /// How many more things can be held on to without allocating.
+    const fn room(&self) -> usize {
+        self.pending.capacity() - self.pending.len()
+    }
+}
+
+/// Return the callback of the output stream, which processes the latest schedule it received whenever the device needs more audio.
+///
+/// How long each call takes is recorded in [`timings`], against how long the audio it makes plays for.
+fn output_callback(
+    mut bridge: Bridge,
+    (shared, latency): (Arc<preview::Shared>, Arc<Latency>),
+    channels: usize,
+    sample_rate: u32,
+) -> impl FnMut(&mut [f32], &cpal::OutputCallbackInfo) + Send + 'static {
+    let mut schedule: Option<Schedule> = None;
+    let mut playing = false;
+    let mut monitoring = false;
+    let mut start = 0;
+    let mut preview: Option<preview::Audio> = None;
+    let mut preview_position = 0;
+    let mut preview_playing = preview::Playing::default();
+    let mut bus = PreviewBus::default();
+    let mut live: Option<Consumer<f64>> = None;
+    let mut keys = Live::new(Synth::default(), f64::from(sample_rate));
+    // These only grow when the device asks for more frames than it did before.
+    let mut buffer = Vec::new();
+    let mut muted = Vec::new();
+    let mut live_block = Vec::new();
+    move |data, info| {
+        let started = Instant::now();
+        let timestamp = info.timestamp();
+        if let Some(output) = timestamp.playback.duration_since(&timestamp.callback) {
+            latency.output.store(u64::try_from(output.as_micros()).unwrap_or(u64::MAX), Ordering::Relaxed);
+        }
+        let garbage = &mut bridge.garbage;
+        garbage.retry();
+        // Every command is done with at most one thing, and so is every preview received and the one playing, so the commands wait for the next block
+        // rather than leave the callback with something it can't send back nor hold on to.
+        while garbage.room() > PREVIEWS + 1 {
+            let Some(command) = bridge.commands.pop() else {
+                break;
+            };
+            match command {
+                Command::Schedule(mut new) => {
+                    if let Some(old) = &schedule {
+                        new.seek(old.position());
+                    }
+                    garbage.recycle(schedule.replace(new).map(Garbage::Schedule));
+                }
+                Command::Play => {
+                    playing = true;
+                    if let Some(schedule) = &mut schedule {
+                        schedule.seek(start);
+                    }
+                }
+                Command::Stop => playing = false,
+                Command::Seek(frame) => {
+                    start = frame;
+                    if let Some(schedule) = &mut schedule {
+                        schedule.seek(frame);
+                    }
+                }
+                Command::StopPreview => garbage.recycle(preview.take().map(Garbage::Preview)),
+                Command::SeekPreview(frame) => preview_position = frame * channels,
+                Command::PreviewBus(new) => bus = new,
+                Command::Monitor(new) => monitoring = new,
+                Command::Live(new) => garbage.recycle(live.replace(new).map(Garbage::Live)),
+                Command::LiveNote(LiveNote::Press { channel, key, velocity }) => keys.press(channel, key, velocity),
+                Command::LiveNote(LiveNote::Release { channel, key }) => keys.release(channel, key),
+                Command::LiveNote(LiveNote::Express { channel, dimension, value }) => keys.express(channel, dimension, value),
+                Command::LiveSynth(synth) => keys.set_synth(synth),
+            }
+        }
+        if receive_previews(&bridge.previews, shared.requested.load(Ordering::Relaxed), &mut preview, garbage) {
+            preview_position = 0;
+        }
+        let waiting = take_live_block(live.as_ref(), data.len(), &mut live_block);
+        let queued = Duration::from_secs(1) * u32::try_from(waiting / channels.max(1)).unwrap_or(u32::MAX) / sample_rate.max(1);
+        latency.queue.store(u64::try_from(queued.as_micros()).unwrap_or(u64::MAX), Ordering::Relaxed);
+
+        buffer.clear();
+        buffer.resize(data.len(), 0.);
+        match &mut schedule {
+            Some(schedule) if playing && (bus.through_master || preview.is_none()) => schedule.process(&[&live_block], &mut buffer),
+            // Keep time while the playback is muted for a preview.
+            Some(schedule) if playing => {
+                muted.resize(data.len(), 0.);
+                schedule.process(&[&live_block], &mut muted);
+            }
+            Some(schedule) if monitoring && (bus.through_master || preview.is_none()) => schedule.process_inputs(&[&live_block], &mut buffer),
+            _ => {}
+        }
+        keys.process(&mut buffer, channels);
+        garbage.recycle(mix_preview(&mut preview, &mut preview_position, &mut buffer, bus.gain).map(Garbage::Preview));
+        match &preview {
+            Some(audio) => {
+                preview_playing = preview::Playing {
+                    id: audio.id,
+                    position: preview_position / channels,
+                    length: (audio.samples.len() / channels).max(1),
+                };
+            }
+            None => preview_playing.length = 0,
+        }
+        for (output, sample) in data.iter_mut().zip(&buffer) {
+            #[allow(clippy::cast_possible_truncation, reason = "the output device takes 32-bit samples")]
+            let sample = *sample as f32;
+            *output = sample;
+        }
+        let frames = data.len() / channels.max(1);
+        let position = schedule.as_ref().filter(|_| playing).map_or(start, Schedule::position);
+        bridge.playback.write(Playback { position, buffer: frames, preview: preview_playing });
+        timings::record_audio_callback(started.elapsed(), Duration::from_secs(1) * u32::try_from(frames).unwrap_or(u32::MAX) / sample_rate.max(1));
+    }
+}
+
+/// Take up to [`PREVIEWS`] previews decoded since the last block, replacing `preview` with the one for the `requested` file, and return whether it was
+/// replaced. Audio for files that aren't wanted anymore is sent back to be dropped.
+fn receive_previews(previews: &Consumer<preview::Audio>, requested: u64, preview: &mut Option<preview::Audio>, garbage: &mut Recycler) -> bool {
+    let mut replaced = false;
+    for audio in (0..PREVIEWS).map_while(|_| previews.pop()) {
+        let wanted = audio.id == requested;
+        let old = if wanted { preview.replace(audio) } else { Some(audio) };
+        garbage.recycle(old.map(Garbage::Preview));
+        replaced |= wanted;
+    }
+    replaced
+}
+
+/// Replace `block` with the next `length` samples of live input, dropping the oldest ones first if too many are waiting, and return how many samples
+/// are waiting along with the block.
+fn take_live_block(live: Option<&Consumer<f64>>, length: usize, block: &mut Vec<f64>) -> usize {
+    block.clear();
+    let Some(live) = live else {
+        return 0;
+    };
+    if live.len() > length * LIVE_BLOCKS {
+        live.drain().take(live.len() - length).for_each(drop);
+    }
+    block.extend(live.drain().take(length));
+    // What's left waits for the next block.
+    live.len() + block.len()
+}
+
+/// Add the audio of `preview` from `position` to `buffer` at `gain` in decibels, looping it if it loops, and take it out once it's over to return it.
+fn mix_preview(preview: &mut Option<preview::Audio>, position: &mut usize, buffer: &mut [f64], gain: f64) -> Option<preview::Audio> {
+    let Some(preview::Audio { samples, looping, .. }) = preview else {
+        return None;
+    };
+    let gain = 10_f64.powf(gain / 20.);
+    let mut written = 0;
+    while written < buffer.len() && (*position < samples.len() || *looping && !samples.is_empty()) {
+        if *position >= samples.len() {
+            *position = 0;
+        }
+        let count = (buffer.len() - written).min(samples.len() - *position);
+        for (output, sample) in buffer[written..written + count].iter_mut().zip(&samples[*position..]) {
+            *output += sample * gain;
+        }
+        written += count;
+        *position += count;
+    }
+    if *position >= samples.len() && !*looping {
+        return preview.take();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use blerp::processing::bridge::{ring, Consumer};
+
+    use super::{mix_preview, preview::Audio, receive_previews, take_live_block, Garbage, Recycler, GARBAGE, LIVE_BLOCKS};
+
+    fn audio(id: u64, samples: &[f64], looping: bool) -> Audio {
+        Audio { id, samples: Arc::from(samples), looping }
+    }
+
+    /// Return the ids of the previews sent back to be dropped.
+    fn dropped(garbage: &Consumer<Garbage>) -> Vec<u64> {
+        garbage
+            .drain()
+            .map(|old| match old {
+                Garbage::Preview(audio) => audio.id,
+                _ => 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn live_blocks_are_taken_in_order() {
+        let (sender, live) = ring(64);
+        (0..6).for_each(|sample| sender.push(f64::from(sample)).unwrap());
+        let mut block = Vec::new();
+        assert_eq!(take_live_block(Some(&live), 4, &mut block), 6);
+        assert_eq!(block, [0., 1., 2., 3.]);
+        assert_eq!(take_live_block(Some(&live), 4, &mut block), 2);
+        assert_eq!(block, [4., 5.]);
+        assert_eq!(take_live_block(None, 4, &mut block), 0);
+        assert!(block.is_empty());
+    }
+
+    #[test]
+    fn live_input_falling_behind_drops_the_oldest_samples() {
+        let (sender, live) = ring(64);
+        let waiting = u32::try_from(2 * LIVE_BLOCKS + 3).unwrap();
+        (0..waiting).for_each(|sample| sender.push(f64::from(sample)).unwrap());
+        let mut block = Vec::new();
+        take_live_block(Some(&live), 2, &mut block);
+        // Only the newest block is kept, so the latency stays at a block.
+        assert_eq!(block, [f64::from(waiting - 2), f64::from(waiting - 1)]);
+        assert!(live.is_empty());
+    }
+
+    #[test]
+    fn previews_are_mixed_at_their_gain_and_taken_out_once_over() {
+        let mut preview = Some(audio(1, &[1., 2., 3.], false));
+        let (mut position, mut buffer) = (0, vec![1.; 2]);
+        assert!(mix_preview(&mut preview, &mut position, &mut buffer, 0.).is_none());
+        assert_eq!(buffer, [2., 3.]);
+        let mut buffer = vec![0.; 2];
+        let over = mix_preview(&mut preview, &mut position, &mut buffer, -20.);
+        assert_eq!(over.map(|audio| audio.id), Some(1));
+        assert!(preview.is_none());
+        assert_eq!(buffer.iter().map(|sample| (sample * 1e6).round() / 1e6).collect::<Vec<_>>(), [0.3, 0.]);
+    }
+
+    #[test]
+    fn looping_previews_start_over() {
+        let mut preview = Some(audio(1, &[1., 2.], true));
+        let (mut position, mut buffer) = (1, vec![0.; 5]);
+        assert!(mix_preview(&mut preview, &mut position, &mut buffer, 0.).is_none());
+        assert_eq!(buffer, [2., 1., 2., 1., 2.]);
+        assert_eq!(position, 2);
+    }
+
+    #[test]
+    fn only_the_requested_preview_is_kept() {
+        let (sender, previews) = ring(4);
+        let (garbage_sender, garbage) = ring(4);
+        let mut recycler = Recycler::new(garbage_sender);
+        let mut preview = Some(audio(1, &[1.], false));
+        let _ = sender.push(audio(2, &[2.], false));
+        let _ = sender.push(audio(3, &[3.], false));
+        assert!(receive_previews(&previews, 3, &mut preview, &mut recycler));
+        assert_eq!(preview.as_ref().map(|audio| audio.id), Some(3));
+        assert_eq!(dropped(&garbage), [2, 1]);
+
+        let _ = sender.push(audio(2, &[2.], false));
+        assert!(!receive_previews(&previews, 3, &mut preview, &mut recycler));
+        assert_eq!(preview.map(|audio| audio.id), Some(3));
+    }
+
+    #[test]
+    fn garbage_that_doesnt_fit_is_held_on_to_until_there_is_room() {
+        let (garbage_sender, garbage) = ring(1);
+        let mut recycler = Recycler::new(garbage_sender);
+        recycler.recycle(Some(Garbage::Preview(audio(1, &[1.], false))));
+        recycler.recycle(Some(Garbage::Preview(audio(2, &[2.], false))));
+        assert_eq!(recycler.room(), GARBAGE - 1);
+        recycler.retry();
+        assert_eq!(dropped(&garbage), [1]);
+        recycler.retry();
+        assert_eq!(dropped(&garbage), [2]);
+        assert_eq!(recycler.room(), GARBAGE);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use blerp::processing::{resample, stretch::time_stretch, synth::Synth};
+use cpal::Sample;
+use itertools::Itertools;
+use rodio::{Decoder, Source};
+
+use crate::{archive, midi};
+
+/// Files longer than this are unlikely to be loops, so they're never stretched.
+const MAX_LOOP_LENGTH: Duration = Duration::from_mins(1);
+
+/// Tells the engine what to do with the preview bus, which plays files from the browser on top of the playback.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewCommand {
+    /// Play a file from its start, replacing the one playing.
+    Play(PathBuf, PreviewOptions),
+    Seek(Duration),
+    Stop,
+    Bus(PreviewBus),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PreviewOptions {
+    /// Whether the file starts over when it ends, until it's stopped.
+    pub looping: bool,
+    /// The tempo in BPM to stretch loops to, or [`None`] to play them as they are.
+    pub tempo: Option<f64>,
+}
+
+/// How the preview bus is mixed into the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewBus {
+    /// The gain of previews, in decibels.
+    pub gain: f64,
+    /// Whether previews are heard along with the playback. Otherwise the playback is muted while a preview plays, to hear it on its own.
+    pub through_master: bool,
+}
+
+impl Default for PreviewBus {
+    fn default() -> Self {
+        Self { gain: 0., through_master: true }
+    }
+}
+
+/// What the preview bus is playing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewState {
+    pub path: PathBuf,
+    pub position: Duration,
+    /// How long the file is, or [`None`] while it's being decoded.
+    pub length: Option<Duration>,
+    /// Whether the file starts over when it ends.
+    pub looping: bool,
+}
+
+/// Decoded audio for the preview bus, in the output format.
+pub struct Audio {
+    /// The request the audio was decoded for.
+    pub id: u64,
+    pub samples: Arc<[f64]>,
+    pub looping: bool,
+}
+
+/// The requests for the preview bus, shared by the rest of the engine with the thread decoding previews and the output callback.
+///
+/// Every file asked for, and every stop, gets a new request id. Audio decoded for an older request is dropped, so a slow file can't start playing after another one was picked.
+#[derive(Debug, Default)]
+pub struct Shared {
+    /// The latest request id.
+    pub requested: AtomicU64,
+    /// The id of the latest request whose file couldn't be decoded.
+    pub failed: AtomicU64,
+}
+
+/// What the output callback last played on the preview bus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Playing {
+    /// The request whose audio was played last.
+    pub id: u64,
+    /// How many frames into the preview playback is.
+    pub position: usize,
+    /// How many frames long the preview is, or zero once it's over.
+    pub length: usize,
+}
+
+impl Shared {
+    /// Return the state of the preview of `path`, which was the latest file asked for, given what the output callback is `playing`, or [`None`] if it
+    /// ended or failed.
+    pub fn state(&self, path: &Path, looping: bool, sample_rate: u32, playing: Playing) -> Option<PreviewState> {
+        let requested = self.requested.load(Ordering::Relaxed);
+        if self.failed.load(Ordering::Relaxed) == requested {
+            return None;
+        }
+        #[allow(clippy::cast_precision_loss, reason = "lengths are well within range")]
+        let seconds = |frames: usize| Duration::from_secs_f64(frames as f64 / f64::from(sample_rate));
+        let (position, length) = if playing.id == requested {
+            if playing.length == 0 {
+                return None;
+            }
+            (seconds(playing.position), Some(seconds(playing.length)))
+        } else {
+            (Duration::ZERO, None)
+        };
+        Some(PreviewState { path: path.to_path_buf(), position, length, looping })
+    }
+}
+
+/// Return the samples of the audio file at `path` in the output format, stretched to `options.tempo` if it's short enough to be a loop. MIDI files are played with
+/// the built-in synth instead.
+pub fn load(path: &Path, options: PreviewOptions, channels: u16, sample_rate: u32) -> Result<Vec<f64>, String> {
+    if midi::is_midi(path) {
+        // MIDI files are played with the built-in synth as it is by default, at the tempo of the project when synced.
+        let file = midi::read(path)?;
+        let samples = Synth::default().play(&file.notes, &file.expression, options.tempo.unwrap_or(file.tempo), f64::from(sample_rate));
+        return Ok(resample::convert(&samples, 1, f64::from(sample_rate), usize::from(channels), f64::from(sample_rate)));
+    }
+    let file = archive::open(path).map_err(|error| error.to_string())?;
+    let decoder = Decoder::new(file).map_err(|error| error.to_string())?;
+    let length = decoder.total_duration();
+    let (file_channels, file_sample_rate) = (decoder.channels(), decoder.sample_rate());
+    let mut samples = decoder.map(f64::from_sample).collect_vec();
+    if let (Some(tempo), Some(length)) = (options.tempo, length.filter(|length| *length <= MAX_LOOP_LENGTH)) {
+        samples = time_stretch(&samples, usize::from(file_channels), f64::from(file_sample_rate), guess_bpm(path, length, tempo) / tempo);
+    }
+    Ok(resample::convert(&samples, usize::from(file_channels), f64::from(file_sample_rate), usize::from(channels), f64::from(sample_rate)))
+}
+
+/// Guess the tempo of the loop at `path` that is `length` long.
+///
+/// A number followed by "bpm" in its name is taken as its tempo. Otherwise it's assumed to last a number of 4/4 bars that's a power of two, picking the tempo closest to `near`.
+fn guess_bpm(path: &Path, length: Duration, near: f64) -> f64 {
+    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+    if let Some(bpm) = tempo_in_name(&name) {
+        return bpm;
+    }
+    (0..8)
+        .map(|power| f64::from(4 << power) * 60. / length.as_secs_f64())
+        .min_by(|a, b| (a / near).ln().abs().total_cmp(&(b / near).ln().abs()))
+        .filter(|bpm| bpm.is_normal())
+        .unwrap_or(near)
+}
+
+/// Return the number right before "bpm" in `name`, like in `drums_128bpm` or `pad 90 bpm`.
+pub fn tempo_in_name(name: &str) -> Option<f64> {
+    let before = name[..name.find("bpm")?].trim_end_matches([' ', '_', '-']);
+    let number = &before[before.trim_end_matches(|character: char| character.is_ascii_digit() || character == '.').len()..];
+    number.parse().ok().filter(|bpm| (20. ..=999.).contains(bpm))
+}
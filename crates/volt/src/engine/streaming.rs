@@ -0,0 +1,229 @@
+//! Streaming long clips from the disk while they play, rather than rendering them into their track, so that they're never held in memory whole.
+//!
+//! A thread reads ahead of playback for every clip streamed, first for the clips whose frames read so far are the closest to running out.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    mem::take,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, Weak},
+    thread,
+    time::Duration,
+};
+
+use blerp::processing::stream::Stream;
+use cpal::Sample;
+use rodio::{Decoder, Source as _};
+use tracing::error;
+
+/// How far ahead of playback clips are read, in seconds.
+const AHEAD: f64 = 4.;
+/// How much of a clip is read at once at most, in seconds, so that another clip running out doesn't wait long.
+const CHUNK: f64 = 0.5;
+/// How long the thread waits before looking again once every clip is read far enough ahead.
+const IDLE: Duration = Duration::from_millis(20);
+/// How long audio has to be for its samples not to be kept whole, so that it's streamed while it plays unless it has to be processed first.
+pub const STREAMED_LENGTH: Duration = Duration::from_secs(30);
+
+/// A clip played from its file, which is converted to the format of the output as it's read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StreamedClip {
+    pub path: PathBuf,
+    /// How many channels the file has.
+    pub channels: u16,
+    /// The sample rate of the file.
+    pub sample_rate: u32,
+    /// The frame of the file the clip starts at, at the sample rate of the file.
+    pub offset: usize,
+    /// The frame of playback the clip starts at.
+    pub start: usize,
+    /// How many frames of playback long the clip is.
+    pub length: usize,
+}
+
+/// The format of an audio file and how long it is, found without keeping its samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Format {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub length: Duration,
+}
+
+/// Return the format of the audio file at `path`, decoding it through without keeping its samples if its length isn't in its header.
+///
+/// # Errors
+/// Returns why the file couldn't be opened or decoded.
+pub fn probe(path: &Path) -> Result<Format, String> {
+    let decoder = open(path)?;
+    let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+    let length = decoder.total_duration().unwrap_or_else(|| {
+        #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+        let frames = decoder.count() as f64 / f64::from(channels.max(1));
+        Duration::from_secs_f64(frames / f64::from(sample_rate.max(1)))
+    });
+    Ok(Format { channels, sample_rate, length })
+}
+
+fn open(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    File::open(path)
+        .map_err(|error| error.to_string())
+        .and_then(|file| Decoder::new(BufReader::new(file)).map_err(|error| error.to_string()))
+}
+
+/// Reads streamed clips ahead of playback on a thread of its own, which ends along with it.
+pub struct Streamer {
+    /// Clips streamed since the thread last looked, which it takes to read from then on.
+    added: Arc<Mutex<Vec<Source>>>,
+    /// The streams handed out, which are kept while a schedule plays them.
+    streams: HashMap<StreamedClip, Arc<Stream>>,
+    channels: usize,
+    sample_rate: u32,
+}
+
+/// A streamed clip as the thread reads it.
+struct Source {
+    clip: StreamedClip,
+    stream: Weak<Stream>,
+    /// The file being decoded and the frame of it that's decoded next, or [`None`] until it's opened or if it can't be.
+    decoder: Option<(Decoder<BufReader<File>>, usize)>,
+    /// Frames decoded from the file and not converted yet, along with the frame of the file they start at, which the next frames may still be
+    /// interpolated from.
+    decoded: (Vec<f64>, usize),
+    failed: bool,
+}
+
+impl Streamer {
+    /// Start reading clips ahead, converted to `channels` and `sample_rate`.
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let (reader_added, channels) = (Arc::clone(&added), usize::from(channels));
+        let result = thread::Builder::new().name("streaming".into()).spawn(move || read_ahead(&reader_added, channels, sample_rate));
+        if let Err(error) = result {
+            error!("Couldn't start streaming: {error}");
+        }
+        Self {
+            added,
+            streams: HashMap::new(),
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Return the stream of `clip`, which is read ahead of playback as long as it's kept. Clips streamed already keep what was read of them.
+    pub fn stream(&mut self, clip: StreamedClip) -> Arc<Stream> {
+        // Streams that are only held here aren't played by any schedule anymore.
+        self.streams.retain(|_, stream| Arc::strong_count(stream) > 1);
+        let (channels, added) = (self.channels, &self.added);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lengths are positive and well within range")]
+        let ahead = (AHEAD * f64::from(self.sample_rate)) as usize;
+        let stream = self.streams.entry(clip.clone()).or_insert_with(|| {
+            let stream = Arc::new(Stream::new(channels, clip.start, clip.length, ahead));
+            added.lock().unwrap_or_else(PoisonError::into_inner).push(Source {
+                clip,
+                stream: Arc::downgrade(&stream),
+                decoder: None,
+                decoded: (Vec::new(), 0),
+                failed: false,
+            });
+            stream
+        });
+        Arc::clone(stream)
+    }
+}
+
+/// Read the streams of the sources `added` ahead of playback until the streamer is dropped, the closest to running out first. Sources are only
+/// locked to take the ones added, so that streaming more clips never waits on a file being read.
+fn read_ahead(added: &Arc<Mutex<Vec<Source>>>, channels: usize, sample_rate: u32) {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lengths are positive and well within range")]
+    let chunk = (CHUNK * f64::from(sample_rate)) as usize;
+    let mut sources = Vec::new();
+    while Arc::strong_count(added) > 1 {
+        sources.append(&mut take(&mut *added.lock().unwrap_or_else(PoisonError::into_inner)));
+        sources.retain(|source: &Source| source.stream.strong_count() > 0);
+        let next = sources
+            .iter_mut()
+            .filter_map(|source| {
+                let stream = source.stream.upgrade()?;
+                Some((stream.need()?, stream, source))
+            })
+            .min_by_key(|(need, ..)| need.buffered);
+        let Some((need, stream, source)) = next else {
+            thread::sleep(IDLE);
+            continue;
+        };
+        let frames = need.frames.len().min(chunk);
+        let samples = source.read(need.frames.start, frames, channels, sample_rate);
+        stream.push(need.frames.start, &samples);
+    }
+}
+
+impl Source {
+    /// Return `frames` frames of the clip from its frame `from`, converted to `channels` and `sample_rate` by linear interpolation. Frames past the
+    /// end of the file, or of a file that can't be read, are silent.
+    fn read(&mut self, from: usize, frames: usize, channels: usize, sample_rate: u32) -> Vec<f64> {
+        let file_channels = usize::from(self.clip.channels.max(1));
+        let ratio = f64::from(self.clip.sample_rate) / f64::from(sample_rate.max(1));
+        #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+        let position = |frame: usize| frame as f64 * ratio;
+        // The frames of the file between the first and the last position converted, and the one after that to interpolate towards.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let (first, last) = (self.clip.offset + position(from) as usize, self.clip.offset + position(from + frames) as usize + 1);
+        self.decode(first, last);
+        let (decoded, decoded_first) = &self.decoded;
+        let sample = |frame: usize, channel: usize| decoded.get(frame.checked_sub(*decoded_first)? * file_channels + channel).copied();
+        let mut samples = Vec::with_capacity(frames * channels);
+        for output in from..from + frames {
+            #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+            let position = self.clip.offset as f64 + position(output);
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+            let index = position as usize;
+            #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+            let fraction = position - index as f64;
+            samples.extend((0..channels).map(|channel| {
+                let channel = channel % file_channels;
+                let current = sample(index, channel).unwrap_or_default();
+                let next = sample(index + 1, channel).unwrap_or(current);
+                (next - current).mul_add(fraction, current)
+            }));
+        }
+        samples
+    }
+
+    /// Keep the frames of the file from `first` to `last` in [`Self::decoded`], dropping the ones before and decoding up to the last, and opening the
+    /// file again from the first if it was decoded past it.
+    fn decode(&mut self, first: usize, last: usize) {
+        let file_channels = usize::from(self.clip.channels.max(1));
+        let (decoded, decoded_first) = &mut self.decoded;
+        let decoded_end = *decoded_first + decoded.len() / file_channels;
+        if first < *decoded_first || first > decoded_end {
+            decoded.clear();
+        } else {
+            decoded.drain(..(first - *decoded_first) * file_channels);
+        }
+        *decoded_first = first;
+        let next = *decoded_first + decoded.len() / file_channels;
+        if !self.failed && !matches!(&self.decoder, Some((_, decoder_next)) if *decoder_next == next) {
+            self.decoder = Self::open(&self.clip, next);
+            self.failed = self.decoder.is_none();
+        }
+        if let Some((decoder, decoder_next)) = &mut self.decoder {
+            let before = decoded.len();
+            decoded.extend(decoder.by_ref().take(last.saturating_sub(next) * file_channels).map(f64::from_sample));
+            *decoder_next += (decoded.len() - before) / file_channels;
+        }
+    }
+
+    /// Open the file of `clip` to decode it from `frame`, seeking if the format allows it and decoding up to there otherwise.
+    fn open(clip: &StreamedClip, frame: usize) -> Option<(Decoder<BufReader<File>>, usize)> {
+        let path = &clip.path;
+        let mut decoder = open(path).inspect_err(|error| error!("Couldn't stream {}: {error}", path.display())).ok()?;
+        #[allow(clippy::cast_precision_loss, reason = "positions are well within range")]
+        let position = Duration::from_secs_f64(frame as f64 / f64::from(clip.sample_rate.max(1)));
+        if frame > 0 && decoder.try_seek(position).is_err() {
+            decoder.by_ref().take(frame * usize::from(clip.channels.max(1))).for_each(drop);
+        }
+        Some((decoder, frame))
+    }
+}
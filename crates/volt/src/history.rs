@@ -0,0 +1,189 @@
+use std::{
+    mem::replace,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::visual::{
+    browser::Browser,
+    central::{Central, CentralSnapshot},
+};
+
+/// Edits with the same description made within this long of each other are undone as one, so that dragging a value doesn't flood the history.
+const MERGE_WINDOW: Duration = Duration::from_secs(1);
+/// The oldest edits are forgotten once there are more than this many.
+const LIMIT: usize = 200;
+
+struct Entry<T> {
+    /// Tells the edit apart from every other one, see [`History::last`].
+    id: u64,
+    /// Describes the edit that turned this state into the next one.
+    description: String,
+    state: T,
+    time: Instant,
+}
+
+/// An undo/redo stack of snapshots of some state `T`.
+pub struct History<T> {
+    undo: Vec<Entry<T>>,
+    redo: Vec<Entry<T>>,
+    current: T,
+    /// The id of the next edit.
+    next_id: u64,
+    /// How many times the state changed, see [`History::revision`].
+    revision: u64,
+}
+
+impl<T> History<T> {
+    pub const fn new(initial: T) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            current: initial,
+            next_id: 0,
+            revision: 0,
+        }
+    }
+
+    /// Return how many edits can be undone and redone.
+    pub const fn steps(&self) -> usize {
+        self.undo.len() + self.redo.len()
+    }
+
+    /// Record that an edit described by `description` resulted in `state`, unless it's the same as the current state. This discards everything that
+    /// could have been redone. Returns whether the edit was recorded.
+    pub fn commit(&mut self, description: String, state: T) -> bool
+    where
+        T: PartialEq,
+    {
+        if state == self.current {
+            return false;
+        }
+        self.revision += 1;
+        self.redo.clear();
+        let previous = replace(&mut self.current, state);
+        if let Some(last) = self.undo.last_mut() {
+            if last.description == description && last.time.elapsed() < MERGE_WINDOW {
+                last.time = Instant::now();
+                return true;
+            }
+        }
+        self.undo.push(Entry {
+            id: self.next_id,
+            description,
+            state: previous,
+            time: Instant::now(),
+        });
+        self.next_id += 1;
+        if self.undo.len() > LIMIT {
+            self.undo.remove(0);
+        }
+        true
+    }
+
+    /// Step back one edit, returning the state to restore, or [`None`] if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<&T> {
+        let Entry { id, description, state, .. } = self.undo.pop()?;
+        self.revision += 1;
+        let state = replace(&mut self.current, state);
+        self.redo.push(Entry {
+            id,
+            description,
+            state,
+            time: Instant::now(),
+        });
+        Some(&self.current)
+    }
+
+    /// Step forward one edit, returning the state to restore, or [`None`] if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<&T> {
+        let Entry { id, description, state, .. } = self.redo.pop()?;
+        self.revision += 1;
+        let state = replace(&mut self.current, state);
+        self.undo.push(Entry {
+            id,
+            description,
+            state,
+            time: Instant::now(),
+        });
+        Some(&self.current)
+    }
+
+    /// Undo or redo until `position` edits have been applied, returning the state to restore if it changed.
+    pub fn go_to(&mut self, position: usize) -> Option<&T> {
+        let mut changed = false;
+        while self.position() > position && self.undo().is_some() {
+            changed = true;
+        }
+        while self.position() < position && self.redo().is_some() {
+            changed = true;
+        }
+        changed.then_some(&self.current)
+    }
+
+    /// Return the id of the last edit that led to the current state, if there is one, so that it can be undone later only if it's still the last one.
+    pub fn last(&self) -> Option<u64> {
+        self.undo.last().map(|entry| entry.id)
+    }
+
+    /// Return how many times the state was changed by committing, undoing or redoing, which tells whether it changed since, even when edits are merged.
+    pub const fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The number of edits that led to the current state.
+    pub const fn position(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Descriptions of every edit in order, including the ones that can be redone.
+    pub fn descriptions(&self) -> impl Iterator<Item = &str> {
+        self.undo.iter().chain(self.redo.iter().rev()).map(|entry| entry.description.as_str())
+    }
+}
+
+/// Everything that can be undone.
+#[derive(Clone, PartialEq)]
+pub struct Snapshot {
+    central: CentralSnapshot,
+    browser_roots: Vec<PathBuf>,
+}
+
+impl Snapshot {
+    pub fn take(central: &Central, browser: &Browser) -> Self {
+        Self {
+            central: central.snapshot(),
+            browser_roots: browser.roots().to_vec(),
+        }
+    }
+
+    pub fn restore(&self, central: &mut Central, browser: &mut Browser) {
+        central.restore(self.central.clone());
+        browser.set_roots(self.browser_roots.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+
+    #[test]
+    fn edits_that_change_nothing_are_not_recorded() {
+        let mut history = History::new(1);
+        assert!(!history.commit("Nothing".into(), 1));
+        assert_eq!((history.position(), history.revision()), (0, 0));
+        assert!(history.commit("Something".into(), 2));
+        assert_eq!((history.position(), history.revision()), (1, 1));
+    }
+
+    #[test]
+    fn recording_an_edit_discards_what_could_be_redone() {
+        let mut history = History::new(1);
+        history.commit("First".into(), 2);
+        history.commit("Second".into(), 3);
+        assert_eq!(history.undo(), Some(&2));
+        assert!(history.commit("Third".into(), 4));
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.descriptions().collect::<Vec<_>>(), ["First", "Third"]);
+    }
+}
@@ -1,7 +1,9 @@
 use std::{env::args, fs::File, io::stderr, ops::ControlFlow, path::Path, process::Command};
 
-use tracing::{info, subscriber::set_global_default};
-use tracing_subscriber::{fmt::layer, layer::SubscriberExt, EnvFilter, Registry};
+use tracing::{info, level_filters::LevelFilter, subscriber::set_global_default};
+use tracing_subscriber::{fmt::layer, layer::SubscriberExt, EnvFilter, Layer, Registry};
+
+use crate::logs::Recorder;
 
 fn get_desktop_environment() -> String {
     #[cfg(target_os = "linux")]
@@ -104,7 +106,8 @@ fn get_gpu_info() -> String {
 
     "Unknown GPU".to_string()
 }
-pub fn dump() {
+/// Return what the app runs on, one `- Name: value` line each.
+pub fn system() -> String {
     let distro = {
         #[cfg(not(target_os = "linux"))]
         {
@@ -124,15 +127,22 @@ pub fn dump() {
         }
     };
 
-    println!("- OS: {}", std::env::consts::OS);
-    println!("- Desktop Environment: {}", get_desktop_environment());
-    println!("- Compositor: {}", get_compositor());
-    println!("- CPU: {}", get_cpu_info());
-    println!("- GPU: {}", get_gpu_info());
-    println!("- OS Family: {}", std::env::consts::FAMILY);
-    println!("- OS Distribution: {distro}");
-    println!("- Architecture: {}", std::env::consts::ARCH);
-    println!("- Version: {}", env!("CARGO_PKG_VERSION"));
+    [
+        format!("- OS: {}", std::env::consts::OS),
+        format!("- Desktop Environment: {}", get_desktop_environment()),
+        format!("- Compositor: {}", get_compositor()),
+        format!("- CPU: {}", get_cpu_info()),
+        format!("- GPU: {}", get_gpu_info()),
+        format!("- OS Family: {}", std::env::consts::FAMILY),
+        format!("- OS Distribution: {distro}"),
+        format!("- Architecture: {}", std::env::consts::ARCH),
+        format!("- Version: {}", env!("CARGO_PKG_VERSION")),
+    ]
+    .join("\n")
+}
+
+pub fn dump() {
+    println!("{}", system());
 }
 
 pub fn handle_args() -> ControlFlow<(), ()> {
@@ -140,20 +150,27 @@ pub fn handle_args() -> ControlFlow<(), ()> {
         dump();
         return ControlFlow::Break(());
     }
-    if args().any(|arg| arg == "--verbose") {
+    // Lines are always kept for the log window, and only written out in verbose mode.
+    let verbose = args().any(|arg| arg == "--verbose");
+    let registry = Registry::default().with(Recorder::new().with_filter(if verbose { LevelFilter::DEBUG } else { LevelFilter::INFO }));
+    if verbose {
         let path = Path::new("debug.log");
         let file = File::create(path).unwrap();
         set_global_default(
-            Registry::default()
-                .with(layer().with_writer(stderr))
-                .with(layer().with_ansi(false).with_writer(file))
-                .with(EnvFilter::from_default_env()),
+            registry.with(
+                layer()
+                    .with_writer(stderr)
+                    .and_then(layer().with_ansi(false).with_writer(file))
+                    .with_filter(EnvFilter::from_default_env()),
+            ),
         )
         .unwrap();
         info!(
             "Running Volt in verbose mode! Various debug logs will now get logged. For convenience, a file at `{}` is also being written to.",
             path.canonicalize().unwrap().display()
         );
+    } else {
+        set_global_default(registry).unwrap();
     }
 
     ControlFlow::Continue(())
@@ -0,0 +1,99 @@
+//! Secrets like API keys, kept in the keychain of the system rather than in the settings files.
+//!
+//! The keychain is reached through the tool each system has for it, like the file dialogs are: `secret-tool` of libsecret on Linux, `security` on macOS, and
+//! PowerShell on Windows, where secrets are encrypted for the user into files in the app's folder. Secrets are only ever written to the standard input of
+//! the tools, so that they don't show in the list of processes. Each call runs a process, so they're only made in jobs.
+
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    process::{Command, Output, Stdio},
+};
+
+use crate::config;
+
+/// What the secrets are kept under, along with their name.
+const SERVICE: &str = "Volt";
+/// The environment variable the file of a secret is passed to PowerShell in on Windows.
+const FILE_VARIABLE: &str = "VOLT_SECRET_FILE";
+
+/// Return the secret called `name`, or [`None`] if none is kept.
+pub fn get(name: &str) -> Result<Option<String>, String> {
+    let output = if cfg!(target_os = "macos") {
+        let output = run(Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", name, "-w"]), "")?;
+        // The item isn't in the keychain.
+        if output.status.code() == Some(44) {
+            return Ok(None);
+        }
+        output
+    } else if cfg!(windows) {
+        let file = config::path(&format!("{name}.secret"));
+        if !file.exists() {
+            return Ok(None);
+        }
+        let script = format!("$secure = Get-Content -LiteralPath $env:{FILE_VARIABLE} | ConvertTo-SecureString; [Net.NetworkCredential]::new('', $secure).Password");
+        run(Command::new("powershell").args(["-NoProfile", "-Command", &script]).env(FILE_VARIABLE, file), "")?
+    } else {
+        let output = run(Command::new("secret-tool").args(["lookup", "service", SERVICE, "name", name]), "")?;
+        // Nothing matches, which isn't said on the standard error.
+        if !output.status.success() && output.stderr.is_empty() {
+            return Ok(None);
+        }
+        output
+    };
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let secret = String::from_utf8(output.stdout).map_err(|error| error.to_string())?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    Ok((!secret.is_empty()).then(|| secret.to_string()))
+}
+
+/// Keep `secret` as the secret called `name`, replacing the one kept before, or forget it if `secret` is empty.
+pub fn set(name: &str, secret: &str) -> Result<(), String> {
+    let output = if cfg!(target_os = "macos") {
+        if secret.is_empty() {
+            let output = run(Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", name]), "")?;
+            return if output.status.success() || output.status.code() == Some(44) { Ok(()) } else { Err(String::from_utf8_lossy(&output.stderr).trim().to_string()) };
+        }
+        // Commands given to the interactive mode are read from the standard input, which keeps the secret out of the arguments.
+        let quoted = secret.replace('\\', "\\\\").replace('"', "\\\"");
+        run(Command::new("security").arg("-i"), &format!("add-generic-password -U -s {SERVICE} -a \"{name}\" -w \"{quoted}\"\n"))?
+    } else if cfg!(windows) {
+        let file = config::path(&format!("{name}.secret"));
+        if secret.is_empty() {
+            return match fs::remove_file(&file) {
+                Err(error) if error.kind() != ErrorKind::NotFound => Err(error.to_string()),
+                _ => Ok(()),
+            };
+        }
+        let script = format!("$input | ConvertTo-SecureString -AsPlainText -Force | ConvertFrom-SecureString | Set-Content -LiteralPath $env:{FILE_VARIABLE}");
+        run(Command::new("powershell").args(["-NoProfile", "-Command", &script]).env(FILE_VARIABLE, file), secret)?
+    } else if secret.is_empty() {
+        run(Command::new("secret-tool").args(["clear", "service", SERVICE, "name", name]), "")?
+    } else {
+        let label = format!("{SERVICE} {name}");
+        run(Command::new("secret-tool").args(["store", "--label", &label, "service", SERVICE, "name", name]), secret)?
+    };
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Run `command` with `input` on its standard input, returning what it wrote.
+fn run(command: &mut Command, input: &str) -> Result<Output, String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| if error.kind() == ErrorKind::NotFound { format!("{program} isn't installed") } else { error.to_string() })?;
+    // The standard input is closed once it's dropped, so that the command doesn't wait for more.
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(|error| error.to_string())?;
+    }
+    child.wait_with_output().map_err(|error| error.to_string())
+}
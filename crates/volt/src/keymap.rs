@@ -0,0 +1,473 @@
+//! Keyboard shortcuts for the actions of the app, which can be changed and are kept between sessions.
+//!
+//! Shortcuts are saved like `Command+Shift+P`, where `Command` is Ctrl, or ⌘ on macOS. Actions can also be run from a MIDI controller, by a note
+//! saved like `note:1:36` or a control saved like `cc:1:64`, with the channel first.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+};
+
+use egui::{Button, Context, Event, Grid, Key, KeyboardShortcut, Modifiers, RichText, Window};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    config,
+    controller::{Control, Message, Transport},
+};
+
+/// Where the shortcuts are kept between sessions.
+const PATH: &str = "keymap.toml";
+
+/// The shortcut of every action as of the last change to the keymap, so that widgets that aren't given the keymap can show them in their tooltips.
+static HINTS: Mutex<BTreeMap<Action, Option<KeyboardShortcut>>> = Mutex::new(BTreeMap::new());
+
+/// Return the shortcut of `action` as it's shown to the user, if it has one.
+pub fn hint(ctx: &Context, action: Action) -> Option<String> {
+    let shortcut = HINTS.lock().unwrap().get(&action).copied().flatten()?;
+    Some(ctx.format_shortcut(&shortcut))
+}
+
+/// Something the app does that can be bound to a keyboard shortcut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    CommandPalette,
+    Undo,
+    Redo,
+    TogglePlayback,
+    Play,
+    Stop,
+    Rewind,
+    ShowPlaylist,
+    ShowGraph,
+    ToggleHistory,
+    ToggleSpectrum,
+    ToggleScope,
+    ToggleTuner,
+    ToggleLoudness,
+    ToggleTimings,
+    ToggleLog,
+    EditShortcuts,
+    ScaleUp,
+    ScaleDown,
+    ResetScale,
+}
+
+impl Action {
+    pub const ALL: [Self; 20] = [
+        Self::CommandPalette,
+        Self::Undo,
+        Self::Redo,
+        Self::TogglePlayback,
+        Self::Play,
+        Self::Stop,
+        Self::Rewind,
+        Self::ShowPlaylist,
+        Self::ShowGraph,
+        Self::ToggleHistory,
+        Self::ToggleSpectrum,
+        Self::ToggleScope,
+        Self::ToggleTuner,
+        Self::ToggleLoudness,
+        Self::ToggleTimings,
+        Self::ToggleLog,
+        Self::EditShortcuts,
+        Self::ScaleUp,
+        Self::ScaleDown,
+        Self::ResetScale,
+    ];
+
+    /// Return the name the action's shortcut is saved under.
+    const fn id(self) -> &'static str {
+        match self {
+            Self::CommandPalette => "command_palette",
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+            Self::TogglePlayback => "toggle_playback",
+            Self::Play => "play",
+            Self::Stop => "stop",
+            Self::Rewind => "rewind",
+            Self::ShowPlaylist => "show_playlist",
+            Self::ShowGraph => "show_graph",
+            Self::ToggleHistory => "toggle_history",
+            Self::ToggleSpectrum => "toggle_spectrum",
+            Self::ToggleScope => "toggle_scope",
+            Self::ToggleTuner => "toggle_tuner",
+            Self::ToggleLoudness => "toggle_loudness",
+            Self::ToggleTimings => "toggle_timings",
+            Self::ToggleLog => "toggle_log",
+            Self::EditShortcuts => "edit_shortcuts",
+            Self::ScaleUp => "scale_up",
+            Self::ScaleDown => "scale_down",
+            Self::ResetScale => "reset_scale",
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::CommandPalette => "Open the command palette",
+            Self::Undo => "Undo",
+            Self::Redo => "Redo",
+            Self::TogglePlayback => "Play or stop",
+            Self::Play => "Play",
+            Self::Stop => "Stop",
+            Self::Rewind => "Go back to the start",
+            Self::ShowPlaylist => "Show the playlist",
+            Self::ShowGraph => "Show the graph",
+            Self::ToggleHistory => "Show or hide the history",
+            Self::ToggleSpectrum => "Show or hide the spectrum analyzer",
+            Self::ToggleScope => "Show or hide the scope and goniometer",
+            Self::ToggleTuner => "Show or hide the tuner",
+            Self::ToggleLoudness => "Show or hide the loudness meter",
+            Self::ToggleTimings => "Show or hide the timings",
+            Self::ToggleLog => "Show or hide the log",
+            Self::EditShortcuts => "Edit keyboard shortcuts",
+            Self::ScaleUp => "Make the interface bigger",
+            Self::ScaleDown => "Make the interface smaller",
+            Self::ResetScale => "Reset the size of the interface",
+        }
+    }
+
+    /// Return the shortcut the action has until it's changed.
+    const fn default_shortcut(self) -> Option<KeyboardShortcut> {
+        match self {
+            Self::CommandPalette => Some(KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::P)),
+            Self::Undo => Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Z)),
+            Self::Redo => Some(KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z)),
+            Self::TogglePlayback => Some(KeyboardShortcut::new(Modifiers::NONE, Key::Space)),
+            Self::ScaleUp => Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Plus)),
+            Self::ScaleDown => Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Minus)),
+            Self::ResetScale => Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Num0)),
+            Self::Play
+            | Self::Stop
+            | Self::Rewind
+            | Self::ShowPlaylist
+            | Self::ShowGraph
+            | Self::ToggleHistory
+            | Self::ToggleSpectrum
+            | Self::ToggleScope
+            | Self::ToggleTuner
+            | Self::ToggleLoudness
+            | Self::ToggleTimings
+            | Self::ToggleLog
+            | Self::EditShortcuts => None,
+        }
+    }
+
+    /// Return whether the action's shortcut also works while text is being typed, which is only the case for shortcuts that can't be typed.
+    const fn works_while_typing(self) -> bool {
+        matches!(self, Self::CommandPalette)
+    }
+}
+
+/// A note or a control of a MIDI controller that runs an action, like a pad or a footswitch. Controls run it when they go from below half way
+/// to above it, which is when a footswitch is pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Note { channel: u8, key: u8 },
+    Control(Control),
+}
+
+impl Trigger {
+    /// Return the trigger `message` is, or would be for a control that's pressed.
+    const fn of(message: Message) -> Option<Self> {
+        match message {
+            Message::Note { channel, key, .. } => Some(Self::Note { channel, key }),
+            Message::Control(change) => Some(Self::Control(change.control)),
+            Message::Release { .. } | Message::Expression { .. } | Message::Transport(_) => None,
+        }
+    }
+
+    /// Write the trigger like `note:1:36` or `cc:1:64`.
+    fn save(self) -> String {
+        match self {
+            Self::Note { channel, key } => format!("note:{}:{key}", channel + 1),
+            Self::Control(control) => format!("cc:{}:{}", control.channel + 1, control.controller),
+        }
+    }
+
+    /// Read a trigger written by [`Self::save`].
+    fn load(text: &str) -> Option<Self> {
+        let (kind, channel, number) = text.split(':').map(str::trim).collect_tuple()?;
+        let channel = channel.parse::<u8>().ok().filter(|channel| (1..=16).contains(channel))? - 1;
+        let number = number.parse().ok().filter(|number| *number < 128)?;
+        match kind {
+            "note" => Some(Self::Note { channel, key: number }),
+            "cc" => Some(Self::Control(Control { channel, controller: number })),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Note { channel, key } => write!(f, "Note {key} on channel {}", channel + 1),
+            Self::Control(control) => control.fmt(f),
+        }
+    }
+}
+
+/// The shortcuts as they are saved, by the ID of their action. Actions without a shortcut are saved with an empty one, and actions without a MIDI
+/// trigger aren't saved in `midi`.
+#[derive(Serialize, Deserialize)]
+struct SavedKeymap {
+    shortcuts: BTreeMap<String, String>,
+    #[serde(default)]
+    midi: BTreeMap<String, String>,
+}
+
+/// The shortcut of every action, along with a window to change them.
+pub struct Keymap {
+    shortcuts: BTreeMap<Action, Option<KeyboardShortcut>>,
+    /// The action whose new shortcut is being waited for in the window, if any.
+    recording: Option<Action>,
+    triggers: BTreeMap<Action, Trigger>,
+    /// The action whose new MIDI trigger is being waited for in the window, if any.
+    learning: Option<Action>,
+    /// The controls bound to actions that are pressed, which have to be released before they run their action again.
+    held: BTreeSet<Control>,
+    /// The actions run from MIDI controllers since the last time the pressed shortcuts were returned.
+    triggered: Vec<Action>,
+    pub open: bool,
+}
+
+impl Keymap {
+    /// Return the shortcuts saved in the last session, with the default shortcut for every action that wasn't saved.
+    pub fn load() -> Self {
+        let saved = config::load(PATH, "the keymap").unwrap_or_else(|| SavedKeymap { shortcuts: BTreeMap::new(), midi: BTreeMap::new() });
+        let shortcuts = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let shortcut = saved.shortcuts.get(action.id()).map_or_else(|| action.default_shortcut(), |text| parse(text));
+                (action, shortcut)
+            })
+            .collect();
+        let triggers = Action::ALL
+            .into_iter()
+            .filter_map(|action| {
+                let text = saved.midi.get(action.id())?;
+                let trigger = Trigger::load(text);
+                if trigger.is_none() {
+                    error!("There is no MIDI note or control called {text} in the keymap");
+                }
+                Some((action, trigger?))
+            })
+            .collect();
+        let keymap = Self { shortcuts, recording: None, triggers, learning: None, held: BTreeSet::new(), triggered: Vec::new(), open: false };
+        keymap.publish();
+        keymap
+    }
+
+    /// Make the shortcuts the ones returned by [`hint`].
+    fn publish(&self) {
+        HINTS.lock().unwrap().clone_from(&self.shortcuts);
+    }
+
+    fn save(&self) {
+        self.publish();
+        let keymap = SavedKeymap {
+            shortcuts: self.shortcuts.iter().map(|(action, shortcut)| (action.id().to_string(), shortcut.map(format).unwrap_or_default())).collect(),
+            midi: self.triggers.iter().map(|(action, trigger)| (action.id().to_string(), trigger.save())).collect(),
+        };
+        config::save(PATH, &keymap, "the keymap");
+    }
+
+    pub fn shortcut(&self, action: Action) -> Option<KeyboardShortcut> {
+        self.shortcuts.get(&action).copied().flatten()
+    }
+
+    /// Return the shortcut of `action` as it's shown to the user, if it has one.
+    pub fn text(&self, ctx: &Context, action: Action) -> Option<String> {
+        self.shortcut(action).map(|shortcut| ctx.format_shortcut(&shortcut))
+    }
+
+    /// Bind `action` to `shortcut`, taking the shortcut away from any other action, and save the shortcuts.
+    fn set(&mut self, action: Action, shortcut: Option<KeyboardShortcut>) {
+        if shortcut.is_some() {
+            for other in self.shortcuts.values_mut().filter(|other| **other == shortcut) {
+                *other = None;
+            }
+        }
+        self.shortcuts.insert(action, shortcut);
+        self.save();
+    }
+
+    /// Bind `action` to `trigger`, taking the trigger away from any other action, and save the keymap.
+    fn set_trigger(&mut self, action: Action, trigger: Trigger) {
+        self.triggers.retain(|_, other| *other != trigger);
+        self.triggers.insert(action, trigger);
+        self.save();
+    }
+
+    /// Take in a message from a MIDI controller, returning whether the keymap used it, so that it isn't used for anything else. The actions it
+    /// runs are returned by [`Self::pressed`].
+    pub fn midi(&mut self, message: Message) -> bool {
+        if let Message::Transport(transport) = message {
+            self.triggered.extend(match transport {
+                Transport::Play => &[Action::Play][..],
+                Transport::Stop => &[Action::Stop],
+                Transport::Rewind => &[Action::Rewind],
+                Transport::Start => &[Action::Rewind, Action::Play],
+            });
+            return true;
+        }
+        let Some(trigger) = Trigger::of(message) else {
+            return false;
+        };
+        // Controls are only pressed when they go above half way, so that they aren't learned or run again as they're released.
+        let pressed = match (message, trigger) {
+            (Message::Control(change), Trigger::Control(control)) if change.value >= 64 => self.held.insert(control),
+            (Message::Control(_), Trigger::Control(control)) => {
+                self.held.remove(&control);
+                false
+            }
+            _ => true,
+        };
+        if let Some(action) = self.learning {
+            if pressed {
+                self.set_trigger(action, trigger);
+                self.learning = None;
+            }
+            return true;
+        }
+        let Some(action) = self.triggers.iter().find_map(|(action, other)| (*other == trigger).then_some(*action)) else {
+            return false;
+        };
+        if pressed {
+            self.triggered.push(action);
+        }
+        true
+    }
+
+    /// Return the actions run from MIDI controllers and the actions whose shortcuts were pressed, consuming them. Only shortcuts that can't be
+    /// typed work while `typing`.
+    pub fn pressed(&mut self, ctx: &Context, typing: bool) -> Vec<Action> {
+        let mut actions = std::mem::take(&mut self.triggered);
+        if self.recording.is_some() {
+            return actions;
+        }
+        // Shortcuts with more modifiers are checked first, as extra modifiers don't stop a shortcut from matching.
+        let shortcuts = self
+            .shortcuts
+            .iter()
+            .filter_map(|(action, shortcut)| Some((*action, (*shortcut)?)))
+            .filter(|(action, _)| !typing || action.works_while_typing())
+            .sorted_by_key(|(_, shortcut)| {
+                let modifiers = shortcut.modifiers;
+                std::cmp::Reverse(u8::from(modifiers.command) + u8::from(modifiers.alt) + u8::from(modifiers.shift))
+            })
+            .filter(|(_, shortcut)| {
+                // Plus is typed with shift on the key of equals on many keyboards, so that key does the same without shift, like it does for zooming in
+                // egui.
+                let equals = (shortcut.logical_key == Key::Plus).then(|| KeyboardShortcut::new(shortcut.modifiers, Key::Equals));
+                ctx.input_mut(|input| input.consume_shortcut(shortcut) || equals.is_some_and(|equals| input.consume_shortcut(&equals)))
+            })
+            .map(|(action, _)| action);
+        actions.extend(shortcuts);
+        actions
+    }
+
+    /// Show the window to change the shortcuts if it's open.
+    pub fn show(&mut self, ctx: &Context) {
+        if let Some(action) = self.recording {
+            // The next key pressed, along with its modifiers, becomes the shortcut. Escape gives up.
+            let pressed = ctx.input(|input| {
+                input.events.iter().find_map(|event| match event {
+                    Event::Key { key, pressed: true, modifiers, .. } => Some((*key, *modifiers)),
+                    _ => None,
+                })
+            });
+            match pressed {
+                Some((Key::Escape, _)) => self.recording = None,
+                Some((key, modifiers)) => {
+                    let modifiers = Modifiers { alt: modifiers.alt, shift: modifiers.shift, command: modifiers.command, ..Modifiers::NONE };
+                    self.set(action, Some(KeyboardShortcut::new(modifiers, key)));
+                    self.recording = None;
+                }
+                None => {}
+            }
+            ctx.input_mut(|input| input.events.retain(|event| !matches!(event, Event::Key { .. })));
+        }
+        let mut open = self.open;
+        let mut changes = Vec::new();
+        let mut removed = None;
+        Window::new("Keyboard shortcuts").open(&mut open).default_width(320.).show(ctx, |ui| {
+            Grid::new("shortcuts").num_columns(5).striped(true).show(ui, |ui| {
+                for (action, shortcut) in &self.shortcuts {
+                    ui.label(action.description());
+                    let text = if self.recording == Some(*action) {
+                        RichText::new("Press a key…").italics()
+                    } else {
+                        RichText::new(shortcut.map_or_else(|| "None".into(), |shortcut| ctx.format_shortcut(&shortcut)))
+                    };
+                    if ui.add(Button::new(text).min_size(egui::vec2(120., 0.))).on_hover_text("Click, then press the new shortcut").clicked() {
+                        self.recording = Some(*action);
+                    }
+                    if ui.add_enabled(shortcut.is_some(), Button::new("🗙").small()).on_hover_text("Remove the shortcut").clicked() {
+                        changes.push((*action, None));
+                    }
+                    let trigger = self.triggers.get(action);
+                    let text = if self.learning == Some(*action) {
+                        RichText::new("Press a pad or switch…").italics()
+                    } else {
+                        RichText::new(trigger.map_or_else(|| "No MIDI".into(), ToString::to_string))
+                    };
+                    if ui.add(Button::new(text).min_size(egui::vec2(120., 0.))).on_hover_text("Click, then press a note or control of a MIDI controller, or click again to give up").clicked() {
+                        self.learning = if self.learning == Some(*action) { None } else { Some(*action) };
+                    }
+                    if ui.add_enabled(trigger.is_some(), Button::new("🗙").small()).on_hover_text("Remove the MIDI note or control").clicked() {
+                        removed = Some(*action);
+                    }
+                    ui.end_row();
+                }
+            });
+            if ui.button("Reset to defaults").clicked() {
+                changes.extend(Action::ALL.map(|action| (action, action.default_shortcut())));
+            }
+        });
+        if let Some(action) = removed {
+            self.triggers.remove(&action);
+        }
+        if !changes.is_empty() || removed.is_some() {
+            self.shortcuts.extend(changes);
+            self.save();
+        }
+        self.open = open;
+        if !self.open {
+            self.recording = None;
+            self.learning = None;
+        }
+    }
+}
+
+/// Write a shortcut like `Command+Shift+P`.
+fn format(shortcut: KeyboardShortcut) -> String {
+    let modifiers = shortcut.modifiers;
+    [(modifiers.command, "Command"), (modifiers.alt, "Alt"), (modifiers.shift, "Shift")]
+        .into_iter()
+        .filter_map(|(pressed, name)| pressed.then_some(name))
+        .chain([shortcut.logical_key.name()])
+        .join("+")
+}
+
+/// Read a shortcut written by [`format`], or return [`None`] if there's no shortcut.
+fn parse(text: &str) -> Option<KeyboardShortcut> {
+    let mut parts = text.split('+').map(str::trim).collect_vec();
+    let key = Key::from_name(parts.pop()?)?;
+    let mut modifiers = Modifiers::NONE;
+    for part in parts {
+        match part {
+            "Command" => modifiers.command = true,
+            "Alt" => modifiers.alt = true,
+            "Shift" => modifiers.shift = true,
+            _ => {
+                error!("There is no modifier called {part} in the keymap");
+                return None;
+            }
+        }
+    }
+    Some(KeyboardShortcut::new(modifiers, key))
+}
@@ -0,0 +1,147 @@
+//! The latest lines logged, kept in memory so that they can be read in the app without `--verbose`.
+
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Write},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use egui::{ComboBox, Context, RichText, ScrollArea, TextEdit, TextStyle, Window};
+use itertools::Itertools;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer, Layer};
+
+/// How many lines are kept, past which the oldest are dropped.
+const CAPACITY: usize = 2000;
+
+/// A line that was logged.
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// How long after the app started it was logged.
+    pub time: Duration,
+    pub level: Level,
+    /// The module it was logged from.
+    pub target: String,
+    /// The message, followed by the other fields of the event.
+    pub message: String,
+}
+
+impl Line {
+    fn matches(&self, search: &str) -> bool {
+        search.is_empty() || self.message.to_lowercase().contains(search) || self.target.to_lowercase().contains(search)
+    }
+}
+
+impl std::fmt::Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>9.3}s {:>5} {}: {}", self.time.as_secs_f64(), self.level, self.target, self.message)
+    }
+}
+
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+static LINES: Mutex<VecDeque<Line>> = Mutex::new(VecDeque::new());
+
+/// Return the lines kept, oldest first.
+pub fn lines() -> Vec<Line> {
+    LINES.lock().unwrap().iter().cloned().collect()
+}
+
+/// Return the latest `count` lines kept, oldest first, or [`None`] if they're being written to, which is the case when a panic happens while doing so.
+pub fn recent(count: usize) -> Option<Vec<Line>> {
+    let lines = LINES.try_lock().ok()?;
+    Some(lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect())
+}
+
+/// A tracing layer that keeps the events it's given as [`Line`]s.
+pub struct Recorder;
+
+impl Recorder {
+    /// Return the layer, counting the time of lines from now on.
+    pub fn new() -> Self {
+        LazyLock::force(&START);
+        Self
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Recorder {
+    fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        let metadata = event.metadata();
+        let line = Line {
+            time: START.elapsed(),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: message.0,
+        };
+        let mut lines = LINES.lock().unwrap();
+        if lines.len() == CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// Writes the fields of an event, with the message first and the others after it like `name=value`.
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// A window showing the lines kept, which can be filtered by level and searched.
+pub struct LogViewer {
+    pub open: bool,
+    /// The most verbose level shown.
+    level: Level,
+    search: String,
+}
+
+impl LogViewer {
+    pub const fn new() -> Self {
+        Self { open: false, level: Level::INFO, search: String::new() }
+    }
+
+    pub fn show(&mut self, ctx: &Context) {
+        let mut open = self.open;
+        Window::new("Log").open(&mut open).default_width(600.).default_height(300.).show(ctx, |ui| {
+            let search = self.search.to_lowercase();
+            let lines = lines().into_iter().filter(|line| line.level <= self.level && line.matches(&search)).collect_vec();
+            ui.horizontal(|ui| {
+                ComboBox::from_id_salt("log_level").selected_text(self.level.as_str()).show_ui(ui, |ui| {
+                    for level in [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE] {
+                        ui.selectable_value(&mut self.level, level, level.as_str());
+                    }
+                });
+                ui.add(TextEdit::singleline(&mut self.search).hint_text("Search").desired_width(200.));
+                if ui.button("Copy").on_hover_text("Copy the lines shown").clicked() {
+                    ctx.copy_text(lines.iter().join("\n"));
+                }
+            });
+            ui.separator();
+            let height = ui.text_style_height(&TextStyle::Monospace);
+            ScrollArea::both().auto_shrink([false, false]).stick_to_bottom(true).show_rows(ui, height, lines.len(), |ui, rows| {
+                for line in &lines[rows] {
+                    let color = match line.level {
+                        Level::ERROR => ui.visuals().error_fg_color,
+                        Level::WARN => ui.visuals().warn_fg_color,
+                        Level::INFO => ui.visuals().text_color(),
+                        _ => ui.visuals().weak_text_color(),
+                    };
+                    ui.add(egui::Label::new(RichText::new(line.to_string()).monospace().color(color)).extend());
+                }
+            });
+        });
+        self.open = open;
+    }
+}
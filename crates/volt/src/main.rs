@@ -1,28 +1,65 @@
 #![warn(clippy::pedantic, clippy::nursery, clippy::allow_attributes_without_reason, clippy::undocumented_unsafe_blocks, clippy::clone_on_ref_ptr)]
 use std::{
-    io::{BufReader, Cursor},
-    rc::Rc, time::Duration,
+    fs,
+    io::{self, BufReader, Cursor, ErrorKind},
+    mem,
+    path::PathBuf,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use blerp::{
+    device::{Device, Direction},
+    midi::{Dimension, SLIDE_CONTROLLER},
+};
+use config::Config;
+use crossbeam_channel::{Receiver, TryRecvError};
 use eframe::{egui, run_native, App, CreationContext, NativeOptions};
-use egui::{hex_color, vec2, CentralPanel, Context, FontData, FontDefinitions, FontFamily, FontId, IconData, Margin, RichText, Rounding, Shadow, SidePanel, TextStyle, TopBottomPanel, Vec2, ViewportBuilder};
+use egui::{CentralPanel, Context, IconData, RichText, Shadow, SidePanel, TopBottomPanel, ViewportBuilder, ViewportCommand, ViewportId};
 use egui_extras::install_image_loaders;
 use human_panic::setup_panic;
 use image::{ImageFormat, ImageReader};
+use engine::{Engine, LiveNote, PreviewCommand};
+use history::{History, Snapshot};
 use info::handle_args;
+use keymap::Keymap;
+use logs::LogViewer;
+use progress::Progress;
+use project::{Project, ProjectError};
+use tasks::{Job, Kind};
 // TODO: Move everything into components (visual)
+mod archive;
+mod config;
+mod controller;
+mod crash;
+mod diagnostics;
+mod engine;
+mod history;
 mod info;
+#[cfg(feature = "freesound")]
+mod keychain;
+mod keymap;
+mod logs;
+mod midi;
+mod progress;
+mod project;
+mod script;
 mod visual;
+mod tasks;
 mod timings;
+mod update;
 
 use tap::{Pipe, Tap};
-use visual::{browser::Browser, central::Central, navbar::navbar, notification::NotificationDrawer, status::status, ThemeColors};
+use visual::{about::About, browser::Browser, central::{Central, MixdownError, Playlist}, dialog::{self, Choice}, font, icon, navbar::{navbar, MenuAction, Transport}, notification::{NotificationAction, NotificationDrawer}, onboarding::{Onboarding, Setup}, palette::{Action, Command, Palette}, relink::Relink, status::{status, Status}, theme, ThemeColors};
 
 fn main() -> eframe::Result {
     setup_panic!();
+    crash::install();
     if handle_args().is_break() {
         return Ok(());
     };
+    let config = Config::load();
     run_native(
         "Volt",
         NativeOptions {
@@ -31,7 +68,7 @@ fn main() -> eframe::Result {
                 present_mode: eframe::wgpu::PresentMode::Immediate,
                 ..Default::default()
             },
-            viewport: ViewportBuilder::default().with_drag_and_drop(true).with_icon(
+            viewport: config.viewport(ViewportBuilder::default()).with_drag_and_drop(true).with_icon(
                 ImageReader::new(BufReader::new(Cursor::new(include_bytes!("images/icons/icon.png").as_ref())))
                     .tap_mut(|reader| reader.set_format(ImageFormat::Png))
                     .decode()
@@ -44,441 +81,1200 @@ fn main() -> eframe::Result {
             ),
             ..Default::default()
         },
-        Box::new(|cc| Ok(Box::new(VoltApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(VoltApp::new(cc, config)))),
     )
 }
 
+/// Something that replaces or closes the project, for which the user is asked to save the changes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProjectAction {
+    New,
+    Open,
+    /// Open a project from an archive.
+    Import,
+    Exit,
+}
+
+/// What the file picked in a file dialog is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Picking {
+    Open,
+    /// Saving the project there, then doing `then` if it's given.
+    Save { then: Option<ProjectAction> },
+    /// Saving the project there, then collecting its files next to it.
+    Collect,
+    ExportArchive,
+    ImportArchive,
+    ImportMidi,
+    ExportMidi,
+    ExportMix,
+    ExportDiagnostics,
+}
+
+/// A task on the project running in the background, whose result is acted on once it's done.
+enum Task {
+    /// Exporting the project to an archive at the path, which returns the files that couldn't be added.
+    ExportArchive(PathBuf, Receiver<Result<Vec<(PathBuf, io::Error)>, ProjectError>>),
+    /// Extracting a project archive, which returns the project file to open.
+    ImportArchive(Receiver<Result<PathBuf, ProjectError>>),
+    /// Rendering the mix to a WAV file at the path, which returns the loudness report written next to it, if one was.
+    ExportMix(PathBuf, Receiver<Result<Option<PathBuf>, MixdownError>>),
+    /// Running a script, which started at the revision of the history and whose changes are only taken if the project wasn't edited since.
+    Script(u64, Receiver<script::Finished>),
+}
+
 struct VoltApp {
     pub browser: Browser,
     pub central: Central,
     pub notification_drawer: NotificationDrawer,
     pub theme: Rc<ThemeColors>,
-    pub showing_command_palette: bool,
-    pub command_palette_text: String,
-    pub command_palette_cursor_pos: u32,
-    pub command_palette_cursor_pos_end: u32,
-    pub command_palette_begin: Duration,
+    /// Watches the theme files, to reload the colors when the one in use is edited.
+    pub theme_watcher: theme::Watcher,
+    /// Whether the interface was dark when the colors were set, as the default colors follow it.
+    pub dark_mode: bool,
+    /// The MIDI controllers whose controls move the parameters they're mapped to.
+    pub controllers: controller::Inputs,
+    pub palette: Palette,
+    pub keymap: Keymap,
+    pub log: LogViewer,
     pub timings_toggle: bool,
-    pub show_welcome: bool,
-    pub show_about: bool
+    /// Whether the timings are shown in a window of their own.
+    pub timings_detached: bool,
+    /// The setup shown on the first launch, until it's finished.
+    pub onboarding: Option<Onboarding>,
+    pub about: About,
+    pub history: History<Snapshot>,
+    pub show_history: bool,
+    pub show_spectrum: bool,
+    pub show_scope: bool,
+    pub show_tuner: bool,
+    pub show_loudness: bool,
+    /// The window to find the files of the project that were moved, while it's open.
+    pub relink: Option<Relink>,
+    /// Plays the graph, or [`None`] if there is no output device.
+    pub engine: Option<Engine>,
+    /// The file the project was last opened from or saved to, or [`None`] if it was never saved.
+    pub project_path: Option<PathBuf>,
+    /// Whether the project changed since it was last opened or saved.
+    pub unsaved: bool,
+    /// What's waiting for the user to decide whether to save the changes to the project first.
+    pub asking: Option<ProjectAction>,
+    /// The file dialog being shown, along with what the file picked is for.
+    pub picking: Option<(Picking, Receiver<Option<PathBuf>>)>,
+    /// The tasks running in the background, whose progress is shown in notifications.
+    pub tasks: Vec<Task>,
+    /// Whether the window is closing, once the user decided what to do with the changes.
+    pub closing: bool,
+    /// The title last given to the window.
+    pub title: String,
+    pub config: Config,
+    /// The settings as they were last saved, to save them again when they change.
+    pub saved_config: Config,
+    pub config_saved_at: Instant,
 }
 
 impl VoltApp {
-    fn new(cc: &CreationContext<'_>) -> Self {
-        const MONO_FONT_NAME: &str = "IBMPlexMono";
-        const PROP_FONT_NAME: &str = "Inter";
+    fn new(cc: &CreationContext<'_>, config: Config) -> Self {
         install_image_loaders(&cc.egui_ctx);
-        cc.egui_ctx.set_fonts({
-            let mut fonts = FontDefinitions::default();
-            fonts
-                .font_data
-                .insert(MONO_FONT_NAME.to_string(), FontData::from_static(include_bytes!("fonts/ibm-plex-mono/IBMPlexMono-Regular.ttf")).into());
-            fonts.families.insert(FontFamily::Monospace, vec![MONO_FONT_NAME.to_string()]);
-            fonts
-                .font_data
-                .insert(PROP_FONT_NAME.to_string(), FontData::from_static(include_bytes!("fonts/inter/Inter.ttf")).into());
-            fonts.families.insert(FontFamily::Proportional, vec![PROP_FONT_NAME.to_string()]);
-            fonts
-        });
-        cc.egui_ctx.all_styles_mut(|style| {
-            const BODY_TEXT_SIZE: f32 = 12.;
-            let id = FontId::new(BODY_TEXT_SIZE, FontFamily::Proportional);
-            style.override_font_id = Some(id);
-            style.text_styles = [
-                (TextStyle::Heading, BODY_TEXT_SIZE * 1.5),
-                (TextStyle::Body, BODY_TEXT_SIZE),
-                (TextStyle::Button, BODY_TEXT_SIZE),
-                (TextStyle::Small, BODY_TEXT_SIZE * 0.8),
-                (TextStyle::Monospace, BODY_TEXT_SIZE),
-            ]
-            .map(|(text_style, size)| (text_style, FontId::new(size, FontFamily::Proportional)))
-            .into();
-        });
-        let theme = Rc::new(ThemeColors::default());
-        Self {
-            browser: Browser::new(Rc::clone(&theme)),
-            central: Central::new(),
+        config.apply(&cc.egui_ctx);
+        let dark_mode = cc.egui_ctx.style().visuals.dark_mode;
+        let theme = Rc::new(ThemeColors::for_mode(dark_mode));
+        let browser = Browser::new(Rc::clone(&theme));
+        let central = Central::new();
+        let mut app = Self {
+            history: History::new(Snapshot::take(&central, &browser)),
+            browser,
+            central,
             notification_drawer: NotificationDrawer::new(),
             theme,
-            showing_command_palette: false,
-            command_palette_text: String::new(),
-            command_palette_begin: Duration::default(),
-            command_palette_cursor_pos: 0,
-            command_palette_cursor_pos_end: 0,
+            theme_watcher: theme::Watcher::new(),
+            dark_mode,
+            controllers: controller::Inputs::open(&cc.egui_ctx),
+            palette: Palette::new(),
+            keymap: Keymap::load(),
+            log: LogViewer::new(),
             timings_toggle: false,
-            show_welcome: true,
-            show_about: false
+            timings_detached: false,
+            onboarding: None,
+            about: About::default(),
+            show_history: false,
+            show_spectrum: false,
+            show_scope: false,
+            show_tuner: false,
+            show_loudness: false,
+            relink: None,
+            engine: Engine::open(config.output_device.as_deref()).tap_mut(|engine| {
+                if let Some(engine) = engine {
+                    engine.set_input_device(config.input_device.clone());
+                }
+            }),
+            project_path: None,
+            unsaved: false,
+            asking: None,
+            picking: None,
+            tasks: Vec::new(),
+            closing: false,
+            title: String::new(),
+            saved_config: config.clone(),
+            config,
+            config_saved_at: Instant::now(),
+        };
+        match app.open_project(PathBuf::from(project::PATH)) {
+            Ok(()) => {}
+            Err(ProjectError::Io(error)) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                app.notification_drawer.error(format!("Couldn't open the project, {error}."));
+            }
+        }
+        if app.engine.is_none() {
+            app.notification_drawer.error("There is no audio output device to play on.").action("Retry", NotificationAction::RetryOutput);
+        }
+        app.set_colors(app.config.colors.clone());
+        // The bundled fonts are used if the saved one can't be loaded anymore, like after it was uninstalled, and it's forgotten then.
+        cc.egui_ctx.set_fonts(font::bundled());
+        let saved_font = app.config.font.take();
+        app.set_font(&cc.egui_ctx, saved_font);
+        app.set_icon_pack(app.config.icon_pack.clone());
+        if let Some(report) = crash::take_pending() {
+            app.notification_drawer
+                .error(format!("Volt crashed last time. A report was written to {}.", report.display()))
+                .action("Show in folder", NotificationAction::Reveal(report));
         }
+        if !Config::is_saved() {
+            app.onboarding = Some(Onboarding::new(app.browser.roots()));
+        }
+        if app.config.check_for_updates {
+            app.about.check(&cc.egui_ctx);
+        }
+        app.open_relink(false);
+        app.update_engine();
+        app
     }
-}
 
-fn now() -> f64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs_f64()
-}
+    /// Send the current graph to the engine, so that edits are heard.
+    fn update_engine(&mut self) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        match self.central.schedule(engine) {
+            Ok(schedule) => engine.update(schedule),
+            Err(error) => {
+                self.notification_drawer.error(format!("Couldn't play the graph, {error}."));
+            }
+        }
+    }
 
-impl App for VoltApp {
-    #[allow(clippy::too_many_lines, reason = "shut")]
-    fn update(&mut self, ctx: &Context, _: &mut eframe::Frame) {
-        let time_render_start = timings::now_ns();
-        // TODO: Move this (the command palette) to its own file. This is here primarily for testing purposes.
+    /// Return where playback is, or where the playhead is while it's stopped, as a bar and beat counted from 1 and in time.
+    fn position(&self) -> ((u32, u32), Duration) {
+        let time = self.engine.as_ref().filter(|engine| engine.is_playing()).map_or_else(|| self.central.playlist().now(), Engine::position);
+        (self.central.bar_and_beat(time), time)
+    }
 
-        // Keyboard shortcut handler
-        if ctx.input_mut(|i| i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::P))) {
-            if !self.showing_command_palette {
-                self.command_palette_begin = Duration::from_secs_f64(now());
+    fn toggle_playback(&mut self) {
+        if let Some(engine) = &mut self.engine {
+            engine.set_playing(!engine.is_playing());
+        } else {
+            self.notification_drawer.error("There is no audio output device to play on.").action("Retry", NotificationAction::RetryOutput);
+        }
+    }
+
+    /// Open the output device picked in the settings again, after it couldn't be opened.
+    fn retry_output(&mut self) {
+        if self.engine.is_some() {
+            return;
+        }
+        self.engine = Engine::open(self.config.output_device.as_deref());
+        let Some(engine) = &mut self.engine else {
+            self.notification_drawer.error("There is no audio output device to play on.").action("Retry", NotificationAction::RetryOutput);
+            return;
+        };
+        engine.set_input_device(self.config.input_device.clone());
+        engine.preview(PreviewCommand::Bus(self.browser.preview_bus()));
+        let message = format!("Playing through {}.", engine.output_name());
+        self.notification_drawer.success(message);
+        self.update_engine();
+    }
+
+    /// Do what the button clicked on a notification does.
+    fn run_notification_action(&mut self, action: NotificationAction) {
+        match action {
+            NotificationAction::Undo(edit) => {
+                if self.history.last() != Some(edit) {
+                    self.notification_drawer.info("The project was edited since, undo the edits made after it first.");
+                } else if let Some(snapshot) = self.history.undo() {
+                    snapshot.restore(&mut self.central, &mut self.browser);
+                    self.unsaved = true;
+                    self.update_engine();
+                }
+            }
+            NotificationAction::RetryOutput => self.retry_output(),
+            NotificationAction::OpenLink(url) => {
+                if let Err(error) = open::that_detached(&url) {
+                    self.notification_drawer.error(format!("Couldn't open {url}, {error}."));
+                }
+            }
+            NotificationAction::Reveal(path) => {
+                let folder = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or(&path) };
+                if let Err(error) = open::that_detached(folder) {
+                    self.notification_drawer.error(format!("Couldn't reveal {}, {error}.", path.display()));
+                }
             }
-            self.showing_command_palette = !self.showing_command_palette;
         }
+    }
+
+    /// Take the colors from the theme file called `name`, or use the default colors for the light or dark interface if there is no name. The colors are
+    /// kept if the file can't be read.
+    fn set_colors(&mut self, name: Option<String>) {
+        match name.as_deref().map_or_else(|| Ok(ThemeColors::for_mode(self.dark_mode)), theme::load) {
+            Ok(colors) => {
+                self.theme = Rc::new(colors);
+                self.browser.set_theme(Rc::clone(&self.theme));
+            }
+            Err(error) => {
+                let name = name.as_deref().unwrap_or_default();
+                tracing::error!("Couldn't load the theme {name}: {error}");
+                self.notification_drawer.error(format!("Couldn't load the theme {name}, {error}."));
+            }
+        }
+        self.config.colors = name;
+    }
 
-        // Handle queries
-        if ctx.input_mut(|i| i.key_pressed(egui::Key::Enter)) {
-            self.showing_command_palette = false;
-            // TODO: Replace this with a search query implementation rather than direct matching (after moving to palette.rs).
-            match self.command_palette_text.as_str() {
-                "timings" => {
-                    self.timings_toggle = !self.timings_toggle;
+    /// Take the icons from the icon pack called `name`, or only use the bundled icons if there is no name. The icons are kept if the pack can't be used.
+    fn set_icon_pack(&mut self, name: Option<String>) {
+        match icon::set_pack(name.as_deref()) {
+            Ok(count) => {
+                if let Some(name) = &name {
+                    tracing::info!("The icon pack {name} replaces {count} icons");
                 }
-                "info" => {
-                    info::dump();
-                    self.notification_drawer.make("Dumped system info into console!".into(), Some(Duration::from_secs(5)));
+                self.config.icon_pack = name;
+            }
+            Err(error) => {
+                let name = name.as_deref().unwrap_or_default();
+                tracing::error!("Couldn't use the icon pack {name}: {error}");
+                self.notification_drawer.error(format!("Couldn't use the icon pack {name}, {error}."));
+            }
+        }
+    }
+
+    /// Write the interface in the font called `name`, or the default one if there is no name. The font in use is kept if the other can't be loaded.
+    fn set_font(&mut self, ctx: &Context, name: Option<String>) {
+        match font::definitions(name.as_deref()) {
+            Ok(fonts) => {
+                ctx.set_fonts(fonts);
+                self.config.font = name;
+            }
+            Err(error) => {
+                let name = name.as_deref().unwrap_or_default();
+                tracing::error!("Couldn't load the font {name}: {error}");
+                self.notification_drawer.error(format!("Couldn't load the font {name}, {error}."));
+            }
+        }
+    }
+
+    /// Play through or record from `device` from now on.
+    fn switch_device(&mut self, device: Device) {
+        match device.direction {
+            Direction::Output => {
+                self.config.output_device = (!device.is_default).then(|| device.name.clone());
+                let (playing, input) = self.engine.as_ref().map_or((false, None), |engine| (engine.is_playing(), engine.input_name().map(ToString::to_string)));
+                // The old device is closed first, as some systems can't open a device twice.
+                self.engine = None;
+                self.engine = Engine::open(Some(&device.name));
+                let Some(engine) = &mut self.engine else {
+                    self.notification_drawer.error(format!("Couldn't open {}.", device.name)).action("Retry", NotificationAction::RetryOutput);
+                    return;
+                };
+                if engine.output_name() != device.name {
+                    self.notification_drawer.warning(format!("Couldn't open {}, playing through {} instead.", device.name, engine.output_name()));
                 }
-                "bug" => {
-                    println!("!!!!!!\nWhen making your bug report, add the information below!\n!!!!!!");
-                    info::dump();
-                    self.notification_drawer.make("Dumped system info into console! You'll be redirected to the official Volt bug report page in ~3 seconds.".into(), Some(Duration::from_secs(5)));
-                    std::thread::spawn(|| {
-                        std::thread::sleep(Duration::from_secs(3));
-                        info::open_link(info::BUG_REPORT_URL);
-                    });
+                engine.set_input_device(input);
+                engine.preview(PreviewCommand::Bus(self.browser.preview_bus()));
+                self.update_engine();
+                if playing {
+                    self.toggle_playback();
                 }
-                _ => {}
             }
+            Direction::Input => match &mut self.engine {
+                // Picking the default device keeps following the default if it changes.
+                Some(engine) => {
+                    self.config.input_device = (!device.is_default).then_some(device.name);
+                    engine.set_input_device(self.config.input_device.clone());
+                }
+                None => {
+                    self.notification_drawer.error("There is no audio output device to record with.");
+                }
+            },
+        }
+    }
+
+    fn undo(&mut self) {
+        // The sample editor has its own edits to undo while it's shown.
+        if self.central.undo_in_editor() {
+            return;
         }
+        if let Some(snapshot) = self.history.undo() {
+            snapshot.restore(&mut self.central, &mut self.browser);
+            self.unsaved = true;
+            self.update_engine();
+        }
+    }
 
-        // Reset the command palette input
-        if !self.showing_command_palette && !self.command_palette_text.is_empty() {
-            self.command_palette_cursor_pos = 0;
-            self.command_palette_cursor_pos_end = 0;
-            self.command_palette_text.clear();
+    /// Record the edit made since the last call in the history, if there is one, returning its id.
+    fn commit_edit(&mut self) -> Option<u64> {
+        let description = [self.central.take_edit(), self.browser.take_edit()].into_iter().flatten().next()?;
+        if !self.history.commit(description, Snapshot::take(&self.central, &self.browser)) {
+            return None;
         }
+        self.unsaved = true;
+        self.update_engine();
+        self.history.last()
+    }
 
-        // Render the command palette and handle logic
-        if self.showing_command_palette {
-            // Escaping the command palette
-            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
-                self.showing_command_palette = false;
-                ctx.request_repaint();
-            } else {
-                let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("command_palette")));
-                let screen_rect = ctx.screen_rect();
-                let palette_size = egui::vec2(300.0, 30.0);
-                let mut center_top = screen_rect.center_top();
-                center_top.y += 40.;
-                let palette_rect = egui::Rect::from_center_size(center_top, palette_size);
-
-                painter.add(Shadow {
-                    spread: 0.0,
-                    blur: 14.0,
-                    offset: vec2(0., 4.),
-                    color: egui::Color32::from_black_alpha(200),
-                }.as_shape(palette_rect, 8.0));
-
-                painter.rect_filled(palette_rect, 8.0, self.theme.command_palette);
-                painter.rect_stroke(palette_rect, 8.0, (1.0, self.theme.command_palette_border));
-
-                let palette_text_fontid = FontId::new(12., FontFamily::Monospace);
-                #[allow(clippy::cast_precision_loss, reason = "shut")]
-                #[allow(clippy::cast_possible_truncation, reason = "shut")]
-                if let Some(text) = ctx.input_mut(|i| {
-                    i.events.iter().find_map(|event| match event {
-                        egui::Event::Text(text) => Some(text.clone()),
-                        _ => None,
-                    })
-                }) {
-                    if self.command_palette_cursor_pos == self.command_palette_cursor_pos_end {
-                        self.command_palette_text.insert_str(self.command_palette_cursor_pos as usize, &text);
-                        self.command_palette_cursor_pos += 1;
-                    } else {
-                        let start = self.command_palette_cursor_pos.min(self.command_palette_cursor_pos_end) as usize;
-                        let end = self.command_palette_cursor_pos.max(self.command_palette_cursor_pos_end) as usize;
-                        self.command_palette_text.replace_range(start..end, &text);
-                        self.command_palette_cursor_pos = (start as u32) + 1;
-                    }
-                    self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                if ctx.input_mut(|i| i.key_pressed(egui::Key::Backspace)) && !self.command_palette_text.is_empty() {
-                    if self.command_palette_cursor_pos != self.command_palette_cursor_pos_end {
-                        let start = self.command_palette_cursor_pos.min(self.command_palette_cursor_pos_end) as usize;
-                        let end = self.command_palette_cursor_pos.max(self.command_palette_cursor_pos_end) as usize;
-                        self.command_palette_text.replace_range(start..end, "");
-                        self.command_palette_cursor_pos = start as u32;
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    } else if self.command_palette_cursor_pos > 0 {
-                        self.command_palette_text.remove(self.command_palette_cursor_pos as usize - 1);
-                        self.command_palette_cursor_pos -= 1;
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    }
-                    self.command_palette_begin = Duration::from_secs_f64(now());
+    fn redo(&mut self) {
+        if self.central.redo_in_editor() {
+            return;
+        }
+        if let Some(snapshot) = self.history.redo() {
+            snapshot.restore(&mut self.central, &mut self.browser);
+            self.unsaved = true;
+            self.update_engine();
+        }
+    }
+
+    /// Return the name of the project's file without its extension, or "Untitled" if it was never saved.
+    fn project_name(&self) -> String {
+        self.project_path
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map_or_else(|| "Untitled".into(), |name| name.to_string_lossy().into_owned())
+    }
+
+    /// Stop playing and start the history over, after the project was replaced.
+    fn reset(&mut self) {
+        if let Some(engine) = &mut self.engine {
+            engine.set_playing(false);
+            engine.seek(Duration::ZERO);
+        }
+        self.history = History::new(Snapshot::take(&self.central, &self.browser));
+        // Scripts that were running ran over the project that was replaced.
+        self.tasks.retain(|task| !matches!(task, Task::Script(..)));
+        self.relink = None;
+        self.unsaved = false;
+    }
+
+    /// Replace the project with an empty one.
+    fn new_project(&mut self) {
+        let graph_detached = self.central.is_graph_detached();
+        self.central = Central::new();
+        self.central.set_graph_detached(graph_detached);
+        self.project_path = None;
+        self.reset();
+        self.update_engine();
+    }
+
+    /// Replace the project with the one saved at `path`.
+    fn open_project(&mut self, path: PathBuf) -> Result<(), ProjectError> {
+        let project = Project::load(&path)?;
+        let graph_detached = self.central.is_graph_detached();
+        self.central = Central::new();
+        self.central.set_graph_detached(graph_detached);
+        self.central.set_playlist(project.playlist);
+        self.central.set_graph(project.graph);
+        self.central.set_inserts(project.inserts);
+        self.central.set_mappings(project.mappings);
+        if !project.roots.is_empty() {
+            self.browser.set_roots(project.roots);
+        }
+        self.central.set_project_path(Some(path.clone()));
+        self.project_path = Some(path);
+        self.reset();
+        Ok(())
+    }
+
+    /// Replace the project with the demo project, see [`Playlist::demo`].
+    fn open_demo(&mut self) {
+        let graph_detached = self.central.is_graph_detached();
+        self.central = Central::new();
+        self.central.set_graph_detached(graph_detached);
+        self.central.set_playlist(Playlist::demo());
+        self.project_path = None;
+        self.reset();
+        self.update_engine();
+    }
+
+    /// Show the first launch setup while it's going on, applying what's picked in it.
+    fn onboarding_window(&mut self, ctx: &Context) {
+        let Some(onboarding) = &mut self.onboarding else {
+            return;
+        };
+        let (output, input) = self.engine.as_ref().map_or((None, None), |engine| (Some(engine.output_name()), engine.input_name()));
+        match onboarding.show(ctx, output, input, &self.config) {
+            Some(Setup::Device(device)) => self.switch_device(device),
+            Some(Setup::Theme(theme)) => {
+                self.config.theme = theme;
+                ctx.set_theme(theme);
+            }
+            Some(Setup::Colors(name)) => self.set_colors(name),
+            Some(Setup::Finish { folders, demo }) => {
+                self.onboarding = None;
+                if !folders.is_empty() {
+                    self.browser.set_roots(folders);
                 }
+                if demo {
+                    self.open_demo();
+                }
+                // The setup is shown until the settings are saved, so they're saved at once in case Volt doesn't exit cleanly.
+                self.config.save();
+                self.saved_config = self.config.clone();
+            }
+            None => {}
+        }
+    }
 
-                if ctx.input_mut(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-                    if ctx.input_mut(|i| i.modifiers.shift) {
-                        if self.command_palette_cursor_pos > 0 {
-                            self.command_palette_cursor_pos -= 1;
-                        }
-                    } else if self.command_palette_cursor_pos > 0 {
-                        self.command_palette_cursor_pos -= 1;
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    } else {
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
+    fn project(&self) -> Project {
+        Project {
+            playlist: self.central.playlist().clone(),
+            graph: self.central.graph().clone(),
+            inserts: self.central.inserts().clone(),
+            roots: self.browser.roots().to_vec(),
+            mappings: self.central.mappings().to_vec(),
+        }
+    }
+
+    /// Save the project to `path`, which becomes its file. Returns whether it was saved.
+    fn save_project(&mut self, path: PathBuf) -> bool {
+        if let Err(error) = self.project().save(&path) {
+            self.notification_drawer.error(format!("Couldn't save the project, {error}."));
+            return false;
+        }
+        self.notification_drawer.success(format!("Saved {}.", path.display()));
+        self.central.set_project_path(Some(path.clone()));
+        self.project_path = Some(path);
+        self.unsaved = false;
+        true
+    }
+
+    /// Save the project to its file, or ask where to save it if it has none or `ask`, then do `then` once it's saved.
+    fn save(&mut self, ctx: &Context, ask: bool, then: Option<ProjectAction>) {
+        if self.picking.is_some() {
+            return;
+        }
+        match self.project_path.clone().filter(|_| !ask) {
+            Some(path) => {
+                if self.save_project(path) {
+                    if let Some(action) = then {
+                        self.run_project_action(ctx, action);
                     }
-                    self.command_palette_begin = Duration::from_secs_f64(now());
                 }
+            }
+            None => self.picking = Some((Picking::Save { then }, dialog::save_project(&self.project_name()))),
+        }
+    }
 
-                if ctx.input_mut(|i| i.key_pressed(egui::Key::ArrowRight)) {
-                    if ctx.input_mut(|i| i.modifiers.shift) {
-                        if (self.command_palette_cursor_pos as usize) < self.command_palette_text.len() {
-                            self.command_palette_cursor_pos += 1;
-                        }
-                    } else if (self.command_palette_cursor_pos as usize) < self.command_palette_text.len() {
-                        self.command_palette_cursor_pos += 1;
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    } else {
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    }
-                    self.command_palette_begin = Duration::from_secs_f64(now());
+    /// Copy the files used by the project that are outside of its folder into its samples folder and save it, so that the folder can be moved to another machine.
+    /// A project that was never saved is saved first, to know where its folder is.
+    fn collect_and_save(&mut self) {
+        let Some(path) = self.project_path.clone() else {
+            if self.picking.is_none() {
+                self.picking = Some((Picking::Collect, dialog::save_project(&self.project_name())));
+            }
+            return;
+        };
+        let (moves, failed) = project::collect(self.central.files(), &path);
+        for (file, error) in &failed {
+            tracing::error!("Couldn't collect {}: {error}", file.display());
+        }
+        self.central.relink(&moves, "Collect files");
+        // The edit is committed here rather than at the end of the frame, so that the project isn't seen as changed once it's saved.
+        if let Some(description) = self.central.take_edit() {
+            if self.history.commit(description, Snapshot::take(&self.central, &self.browser)) {
+                self.update_engine();
+            }
+        }
+        if self.save_project(path) {
+            if failed.is_empty() {
+                self.notification_drawer.success(format!("Collected {} file(s) into the project folder.", moves.len()));
+            } else {
+                self.notification_drawer.warning(format!("Collected {} file(s) into the project folder, but couldn't copy {}.", moves.len(), failed.len()));
+            }
+        }
+    }
+
+    /// Do `action`, asking whether to save the changes to the project first if there are any.
+    fn request_project_action(&mut self, ctx: &Context, action: ProjectAction) {
+        if self.picking.is_some() {
+            return;
+        }
+        if self.unsaved {
+            self.asking = Some(action);
+        } else {
+            self.run_project_action(ctx, action);
+        }
+    }
+
+    fn run_project_action(&mut self, ctx: &Context, action: ProjectAction) {
+        match action {
+            ProjectAction::New => self.new_project(),
+            ProjectAction::Open => self.picking = Some((Picking::Open, dialog::open_project())),
+            ProjectAction::Import => self.picking = Some((Picking::ImportArchive, dialog::open_archive())),
+            ProjectAction::Exit => {
+                self.closing = true;
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+            }
+        }
+    }
+
+    /// Show the prompt about unsaved changes and act on the file picked in a file dialog, and keep the window from closing with unsaved changes.
+    fn project_dialogs(&mut self, ctx: &Context) {
+        if ctx.input(|input| input.viewport().close_requested()) && self.unsaved && !self.closing {
+            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+            self.asking = Some(ProjectAction::Exit);
+        }
+        if let Some(action) = self.asking {
+            match dialog::unsaved_changes(ctx, &self.project_name()) {
+                Some(Choice::Save) => {
+                    self.asking = None;
+                    self.save(ctx, false, Some(action));
                 }
+                Some(Choice::Discard) => {
+                    self.asking = None;
+                    self.run_project_action(ctx, action);
+                }
+                Some(Choice::Cancel) => self.asking = None,
+                None => {}
+            }
+        }
+        let Some((picking, receiver)) = &self.picking else {
+            return;
+        };
+        let picking = *picking;
+        let Ok(picked) = receiver.try_recv() else {
+            // Keep drawing while the dialog is open, so that its answer is seen as soon as it comes.
+            ctx.request_repaint_after(Duration::from_millis(100));
+            return;
+        };
+        self.picking = None;
+        if let Some(path) = picked {
+            self.picked(ctx, picking, path);
+        }
+    }
 
-                if ctx.input_mut(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::ArrowLeft)) {
-                    let text_before = &self.command_palette_text[..(self.command_palette_cursor_pos as usize)];
-                    self.command_palette_cursor_pos = text_before.rfind(|c: char| !c.is_alphanumeric())
-                        .map(|i| i as u32 + 1)
-                        .unwrap_or(0);
-                    if !ctx.input_mut(|i| i.modifiers.shift) {
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
+    /// Act on the file picked in a file dialog.
+    fn picked(&mut self, ctx: &Context, picking: Picking, path: PathBuf) {
+        match picking {
+            Picking::Open => match self.open_project(path) {
+                Ok(()) => {
+                    self.open_relink(false);
+                    self.update_engine();
+                }
+                Err(error) => {
+                    self.notification_drawer.error(format!("Couldn't open the project, {error}."));
+                }
+            },
+            Picking::ImportArchive => {
+                let progress = Arc::new(Progress::default());
+                self.notification_drawer.progress(format!("Extracting {}…", path.display()), Arc::clone(&progress));
+                let job = Job::new(Kind::Import, format!("Extracting {}", path.display()));
+                let rx = tasks::spawn_with_progress(job, progress, move |progress| project::import(&path, progress));
+                self.tasks.push(Task::ImportArchive(rx));
+            }
+            Picking::ExportArchive => {
+                let progress = Arc::new(Progress::default());
+                self.notification_drawer.progress(format!("Exporting the project to {}…", path.display()), Arc::clone(&progress));
+                let (project, name, destination) = (self.project(), self.project_name(), path.clone());
+                let job = Job::new(Kind::Export, format!("Exporting the project to {}", path.display()));
+                let rx = tasks::spawn_with_progress(job, progress, move |progress| project.export(&name, &destination, progress));
+                self.tasks.push(Task::ExportArchive(path, rx));
+            }
+            Picking::ExportDiagnostics => match diagnostics::export(&path) {
+                Ok(()) => {
+                    self.notification_drawer
+                        .success(format!("Exported diagnostics to {}.", path.display()))
+                        .action("Show in folder", NotificationAction::Reveal(path));
+                }
+                Err(error) => {
+                    tracing::error!("Couldn't export diagnostics to {}: {error}", path.display());
+                    self.notification_drawer.error(format!("Couldn't export diagnostics, {error}."));
+                }
+            },
+            Picking::Save { then } => {
+                if self.save_project(path) {
+                    if let Some(action) = then {
+                        self.run_project_action(ctx, action);
                     }
-                    self.command_palette_begin = Duration::from_secs_f64(now());
                 }
+            }
+            Picking::Collect => {
+                if self.save_project(path) {
+                    self.collect_and_save();
+                }
+            }
+            Picking::ImportMidi => match self.central.import_midi(&path) {
+                Ok(0) => {
+                    self.notification_drawer.warning("The MIDI file has no notes.");
+                }
+                Ok(count) => {
+                    let edit = self.commit_edit();
+                    let notification = self.notification_drawer.success(format!("Imported {count} track(s) from the MIDI file."));
+                    if let Some(edit) = edit {
+                        notification.action("Undo", NotificationAction::Undo(edit));
+                    }
+                }
+                Err(error) => {
+                    self.notification_drawer.error(format!("Couldn't import the MIDI file, {error}."));
+                }
+            },
+            Picking::ExportMidi => {
+                let Some(file) = self.central.export_midi() else {
+                    return;
+                };
+                match fs::write(&path, file.write()) {
+                    Ok(()) => {
+                        self.notification_drawer.success(format!("Exported {}.", path.display())).action("Show in folder", NotificationAction::Reveal(path));
+                    }
+                    Err(error) => {
+                        self.notification_drawer.error(format!("Couldn't export the MIDI file, {error}."));
+                    }
+                }
+            }
+            Picking::ExportMix => {
+                let progress = Arc::new(Progress::default());
+                self.notification_drawer.progress(format!("Exporting the mix to {}…", path.display()), Arc::clone(&progress));
+                let (channels, sample_rate) = self.engine.as_ref().map_or((2, 48_000), |engine| (engine.channels(), engine.sample_rate()));
+                let (mixdown, report, destination) = (self.central.mixdown(channels, sample_rate), self.config.loudness_reports, path.clone());
+                let job = Job::new(Kind::Export, format!("Exporting the mix to {}", path.display()));
+                let rx = tasks::spawn_with_progress(job, progress, move |progress| mixdown.export(&destination, report, progress));
+                self.tasks.push(Task::ExportMix(path, rx));
+            }
+        }
+    }
 
-                if ctx.input_mut(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::ArrowRight)) {
-                    let text_after = &self.command_palette_text[(self.command_palette_cursor_pos as usize)..];
-                    if let Some(i) = text_after.find(|c: char| !c.is_alphanumeric()) {
-                        self.command_palette_cursor_pos = (self.command_palette_cursor_pos as usize + i) as u32;
-                    } else {
-                        self.command_palette_cursor_pos = self.command_palette_text.len() as u32;
+    /// Act on the tasks running in the background that are done.
+    fn poll_tasks(&mut self) {
+        for task in mem::take(&mut self.tasks) {
+            match task {
+                Task::ExportArchive(path, receiver) => match receiver.try_recv() {
+                    Err(TryRecvError::Empty) => self.tasks.push(Task::ExportArchive(path, receiver)),
+                    Err(TryRecvError::Disconnected) => {}
+                    Ok(Ok(failed)) if failed.is_empty() => {
+                        self.notification_drawer.success(format!("Exported the project to {}.", path.display())).action("Show in folder", NotificationAction::Reveal(path));
                     }
-                    if !ctx.input_mut(|i| i.modifiers.shift) {
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
+                    Ok(Ok(failed)) => {
+                        for (file, error) in &failed {
+                            tracing::error!("Couldn't add {} to the archive: {error}", file.display());
+                        }
+                        let message = format!("Exported the project to {}, but couldn't add {} file(s).", path.display(), failed.len());
+                        self.notification_drawer.warning(message).action("Show in folder", NotificationAction::Reveal(path));
                     }
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                if ctx.input_mut(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Backspace)) {
-                    let text_before = &self.command_palette_text[..(self.command_palette_cursor_pos as usize)];
-                    let prev_word_end = text_before.rfind(|c: char| !c.is_alphanumeric())
-                        .map(|i| i + 1)
-                        .unwrap_or(0);
-                    self.command_palette_text.drain(prev_word_end..self.command_palette_cursor_pos as usize);
-                    self.command_palette_cursor_pos = prev_word_end as u32;
-                    self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                if ctx.input_mut(|i| i.key_pressed(egui::Key::Delete)) {
-                    if self.command_palette_cursor_pos != self.command_palette_cursor_pos_end {
-                        let start = self.command_palette_cursor_pos.min(self.command_palette_cursor_pos_end) as usize;
-                        let end = self.command_palette_cursor_pos.max(self.command_palette_cursor_pos_end) as usize;
-                        self.command_palette_text.replace_range(start..end, "");
-                        self.command_palette_cursor_pos = start as u32;
-                        self.command_palette_cursor_pos_end = self.command_palette_cursor_pos;
-                    } else if (self.command_palette_cursor_pos as usize) < self.command_palette_text.len() {
-                        self.command_palette_text.remove(self.command_palette_cursor_pos as usize);
+                    Ok(Err(ProjectError::Cancelled)) => {
+                        self.notification_drawer.info("Cancelled exporting the project.");
                     }
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                if ctx.input_mut(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Delete)) && (self.command_palette_cursor_pos as usize) < self.command_palette_text.len() {
-                    let text_after = &self.command_palette_text[(self.command_palette_cursor_pos as usize)..];
-                    let next_word_start = text_after.find(|c: char| !c.is_alphanumeric())
-                        .map(|i| (self.command_palette_cursor_pos as usize) + i)
-                        .unwrap_or(self.command_palette_text.len());
-                    self.command_palette_text.drain(self.command_palette_cursor_pos as usize..next_word_start);
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                if ctx.input_mut(|i| i.modifiers.shift && i.key_pressed(egui::Key::Delete)) {
-                    self.command_palette_text.clear();
-                    self.command_palette_cursor_pos = 0;
-                    self.command_palette_cursor_pos_end = 0;
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                if ctx.input_mut(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::A)) {
-                    self.command_palette_cursor_pos = self.command_palette_text.len() as u32;
-                    self.command_palette_cursor_pos_end = 0;
-                    self.command_palette_begin = Duration::from_secs_f64(now());
-                }
-
-                let cptext_x_offset = 10.;
-                let cursor_width = 2.;
-
-                if self.command_palette_text.is_empty() {
-                    painter.text(
-                        {
-                            let mut lc = palette_rect.left_center();
-                            lc.x += cptext_x_offset;
-                            lc
-                        },
-                        egui::Align2::LEFT_CENTER,
-                        "Type a command...",
-                        palette_text_fontid.clone(),
-                        self.theme.command_palette_placeholder_text,
-                    );
-                    // Draw cursor
-                    let cursor_pos = painter.text(
-                        {
-                            let mut lc = palette_rect.left_center();
-                            lc.x += cptext_x_offset;
-                            lc
-                        },
-                        egui::Align2::LEFT_CENTER,
-                        &self.command_palette_text[..self.command_palette_cursor_pos as usize],
-                        palette_text_fontid,
-                        self.theme.command_palette_text,
-                    ).right();
-                    // Only show cursor every 500ms
-                    if (now() - self.command_palette_begin.as_secs_f64()).fract() < 0.5 {
-                        painter.rect_filled(
-                            egui::Rect::from_min_max(
-                                egui::pos2(cursor_pos, palette_rect.center().y - 8.),
-                                egui::pos2(cursor_pos + cursor_width, palette_rect.center().y + 8.),
-                            ),
-                            0.0,
-                            egui::Color32::from_rgb(0x5c, 0x5c, 0xff),
-                        );
+                    Ok(Err(error)) => {
+                        self.notification_drawer.error(format!("Couldn't export the project, {error}."));
                     }
-                } else {
-                    let (start_pos, end_pos) = if self.command_palette_cursor_pos < self.command_palette_cursor_pos_end {
-                        (self.command_palette_cursor_pos, self.command_palette_cursor_pos_end)
-                    } else {
-                        (self.command_palette_cursor_pos_end, self.command_palette_cursor_pos)
-                    };
-
-                    // Draw text before selection
-                    let selection_start = painter.text(
-                        {
-                            let mut lc = palette_rect.left_center();
-                            lc.x += cptext_x_offset;
-                            lc
-                        },
-                        egui::Align2::LEFT_CENTER,
-                        &self.command_palette_text[..start_pos as usize],
-                        palette_text_fontid.clone(),
-                        self.theme.command_palette_text,
-                    ).right();
-
-                    // Draw selection
-                    let selection_end = painter.text(
-                        egui::pos2(selection_start, palette_rect.center().y),
-                        egui::Align2::LEFT_CENTER,
-                        &self.command_palette_text[start_pos as usize..end_pos as usize],
-                        palette_text_fontid.clone(),
-                        hex_color!("8c8cff"),
-                    ).right();
-
-                    painter.rect_filled(
-                        egui::Rect::from_min_max(
-                            egui::pos2(selection_start, palette_rect.center().y - 8.),
-                            egui::pos2(selection_end, palette_rect.center().y + 8.),
-                        ),
-                        0.0,
-                        egui::Color32::from_rgba_unmultiplied(0x5c, 0x5c, 0xff, 0x20),
-                    );
-
-                    // Draw text after selection
-                    painter.text(
-                        egui::pos2(selection_end, palette_rect.center().y),
-                        egui::Align2::LEFT_CENTER,
-                        &self.command_palette_text[end_pos as usize..],
-                        palette_text_fontid,
-                        self.theme.command_palette_text,
-                    );
-
-                    // Only show cursor every 500ms
-                    if (now() - self.command_palette_begin.as_secs_f64()).fract() < 0.5 {
-                        let cursor_pos = if self.command_palette_cursor_pos <= self.command_palette_cursor_pos_end {
-                            selection_start
+                },
+                Task::ExportMix(path, receiver) => match receiver.try_recv() {
+                    Err(TryRecvError::Empty) => self.tasks.push(Task::ExportMix(path, receiver)),
+                    Err(TryRecvError::Disconnected) => {}
+                    Ok(Ok(report)) => {
+                        let message = if report.is_some() {
+                            format!("Exported the mix to {}, with its loudness report.", path.display())
                         } else {
-                            selection_end
+                            format!("Exported the mix to {}.", path.display())
                         };
+                        self.notification_drawer.success(message).action("Show in folder", NotificationAction::Reveal(path));
+                    }
+                    Ok(Err(MixdownError::Cancelled)) => {
+                        self.notification_drawer.info("Cancelled exporting the mix.");
+                    }
+                    Ok(Err(error)) => {
+                        self.notification_drawer.error(format!("Couldn't export the mix, {error}."));
+                    }
+                },
+                Task::Script(revision, receiver) => match receiver.try_recv() {
+                    Err(TryRecvError::Empty) => self.tasks.push(Task::Script(revision, receiver)),
+                    Err(TryRecvError::Disconnected) => {}
+                    Ok(finished) => self.finish_script(finished, revision),
+                },
+                Task::ImportArchive(receiver) => match receiver.try_recv() {
+                    Err(TryRecvError::Empty) => self.tasks.push(Task::ImportArchive(receiver)),
+                    Err(TryRecvError::Disconnected) => {}
+                    Ok(Err(ProjectError::Cancelled)) => {
+                        self.notification_drawer.info("Cancelled importing the project.");
+                    }
+                    Ok(result) => match result.and_then(|project| self.open_project(project)) {
+                        Ok(()) => {
+                            self.open_relink(false);
+                            self.update_engine();
+                        }
+                        Err(error) => {
+                            self.notification_drawer.error(format!("Couldn't import the project, {error}."));
+                        }
+                    },
+                },
+            }
+        }
+    }
+
+    /// Ask where to export the project and its files as an archive.
+    fn export_archive(&mut self) {
+        if self.picking.is_none() {
+            self.picking = Some((Picking::ExportArchive, dialog::save_archive(&self.project_name())));
+        }
+    }
+
+    /// Ask for a MIDI file to add to the playlist.
+    fn import_midi(&mut self) {
+        if self.picking.is_none() {
+            self.picking = Some((Picking::ImportMidi, dialog::open_midi()));
+        }
+    }
+
+    /// Ask where to export the MIDI clips of the playlist, or say that there are none.
+    fn export_midi(&mut self) {
+        if self.picking.is_some() {
+            return;
+        }
+        if self.central.export_midi().is_none() {
+            self.notification_drawer.info("There are no MIDI clips to export.");
+            return;
+        }
+        self.picking = Some((Picking::ExportMidi, dialog::save_midi(&self.project_name())));
+    }
+
+    /// Ask where to export the mix of the project.
+    fn export_mix(&mut self) {
+        if self.picking.is_none() {
+            self.picking = Some((Picking::ExportMix, dialog::save_mix(&self.project_name())));
+        }
+    }
 
-                        painter.rect_filled(
-                            egui::Rect::from_min_max(
-                                egui::pos2(cursor_pos, palette_rect.center().y - 8.),
-                                egui::pos2(cursor_pos + cursor_width, palette_rect.center().y + 8.),
-                            ),
-                            0.0,
-                            egui::Color32::from_rgb(0x5c, 0x5c, 0xff),
-                        );
+    /// Open the window to relink the files of the project that can't be found, if there are any. Otherwise, say so if `asked`.
+    fn open_relink(&mut self, asked: bool) {
+        let missing = self.central.missing_files();
+        if missing.is_empty() {
+            if asked {
+                self.notification_drawer.info("Every file used by the project is where it was.");
+            }
+            return;
+        }
+        self.relink = Some(Relink::new(missing, self.browser.roots()));
+    }
+
+    fn relink_window(&mut self, ctx: &Context) {
+        let Some(relink) = &mut self.relink else {
+            return;
+        };
+        let moves = relink.show(ctx);
+        if !relink.open {
+            self.relink = None;
+        }
+        if let Some(moves) = moves {
+            let count = self.central.relink(&moves, "Relink files");
+            let edit = self.commit_edit();
+            let notification = self.notification_drawer.success(format!("Relinked {count} clip(s) and file player(s)."));
+            if let Some(edit) = edit {
+                notification.action("Undo", NotificationAction::Undo(edit));
+            }
+        }
+    }
+
+    fn history_window(&mut self, ctx: &Context) {
+        let mut target = None;
+        egui::Window::new("History").open(&mut self.show_history).default_width(200.).show(ctx, |ui| {
+            egui::ScrollArea::vertical().auto_shrink([false, true]).show(ui, |ui| {
+                let position = self.history.position();
+                if ui.selectable_label(position == 0, "Initial state").clicked() {
+                    target = Some(0);
+                }
+                for (index, description) in self.history.descriptions().enumerate() {
+                    let text = if index < position { RichText::new(description) } else { RichText::new(description).weak() };
+                    if ui.selectable_label(index + 1 == position, text).clicked() {
+                        target = Some(index + 1);
                     }
                 }
+            });
+        });
+        if let Some(snapshot) = target.and_then(|target| self.history.go_to(target)) {
+            snapshot.restore(&mut self.central, &mut self.browser);
+            self.unsaved = true;
+            self.update_engine();
+        }
+    }
+    /// Show how much memory the app and its caches take up, with buttons to clear the caches.
+    fn memory_ui(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        ui.label(format!("memory: {}", timings::resident_memory().map_or_else(|| "unknown".into(), timings::format_bytes)));
+        let (cached_files, thumbnails) = self.browser.cache_usage();
+        ui.horizontal(|ui| {
+            ui.label(format!("browser cache: {cached_files} files, {} of thumbnails", timings::format_bytes(thumbnails as u64)));
+            if ui.small_button("Clear").clicked() {
+                self.browser.clear_caches();
+            }
+        });
+        if let Some(engine) = &mut self.engine {
+            let (count, bytes) = engine.cache_usage();
+            ui.horizontal(|ui| {
+                ui.label(format!("audio cache: {count} files and tracks, {}", timings::format_bytes(bytes as u64)));
+                if ui.small_button("Clear").clicked() {
+                    engine.clear_cache();
+                }
+            });
+        }
+        let (folders, entries) = self.browser.index_size();
+        ui.label(format!("browser index: {folders} folders, {entries} entries"));
+        ui.label(format!("history: {} steps", self.history.steps()));
+    }
 
-                ctx.request_repaint_after_secs(0.1);
+    /// Do what a keyboard shortcut is bound to.
+    fn run_action(&mut self, ctx: &Context, action: keymap::Action) {
+        match action {
+            keymap::Action::CommandPalette => self.palette.toggle(),
+            keymap::Action::Undo => self.undo(),
+            keymap::Action::Redo => self.redo(),
+            keymap::Action::TogglePlayback => self.toggle_playback(),
+            keymap::Action::Play => {
+                if !self.engine.as_ref().is_some_and(Engine::is_playing) {
+                    self.toggle_playback();
+                }
+            }
+            keymap::Action::Stop => {
+                if let Some(engine) = &mut self.engine {
+                    engine.set_playing(false);
+                }
             }
+            keymap::Action::Rewind => {
+                let position = self.central.go_to(1, 1, 1);
+                if let Some(engine) = &self.engine {
+                    engine.seek(position);
+                }
+            }
+            keymap::Action::ShowPlaylist => self.central.show_graph(false),
+            keymap::Action::ShowGraph => self.central.show_graph(true),
+            keymap::Action::ToggleHistory => self.show_history = !self.show_history,
+            keymap::Action::ToggleSpectrum => self.show_spectrum = !self.show_spectrum,
+            keymap::Action::ToggleScope => self.show_scope = !self.show_scope,
+            keymap::Action::ToggleTuner => self.show_tuner = !self.show_tuner,
+            keymap::Action::ToggleLoudness => self.show_loudness = !self.show_loudness,
+            keymap::Action::ToggleTimings => self.timings_toggle = !self.timings_toggle,
+            keymap::Action::ToggleLog => self.log.open = !self.log.open,
+            keymap::Action::EditShortcuts => self.keymap.open = true,
+            keymap::Action::ScaleUp => config::step_ui_scale(ctx, 1),
+            keymap::Action::ScaleDown => config::step_ui_scale(ctx, -1),
+            keymap::Action::ResetScale => ctx.set_zoom_factor(1.),
         }
+    }
 
-        egui::Area::new("center_area".into())
-            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
-            .show(ctx, |ui| {
-                if self.show_welcome {
-                    egui::Frame::none()
-                        .fill(self.theme.central_background)
-                        .stroke(egui::Stroke::new(1., self.theme.playlist_bar))
-                        .rounding(Rounding::ZERO.at_least(5.))
-                        .inner_margin(Margin::same(10.))
-                        .show(ui, |ui| {
-                            ui.label("Welcome to Volt!");
-                            ui.label("This is extremely work-in-progress and is not finished at all!");
-                            ui.label("If you can, please check out our GitHub repository:");
-                            ui.hyperlink_to("github.com/TheRedXD/Volt", "https://github.com/TheRedXD/Volt");
-                            ui.style_mut().spacing.item_spacing = Vec2::ZERO;
-                            let mut margin = Margin::ZERO;
-                            margin.top = 5.;
-                            egui::Frame::none()
-                                .inner_margin(margin)
-                                .show(ui, |ui| {
-                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                        let close_btn = egui::Button::new("Ok")
-                                            .fill(self.theme.command_palette)
-                                            .stroke(
-                                                egui::Stroke::new(1., self.theme.playlist_bar)
-                                            );
-                                        if ui.add(close_btn).on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
-                                            self.show_welcome = false
-                                        }
-                                    });
-                                });
-                        });
+    /// Run a command typed into the command palette.
+    fn run_command(&mut self, ctx: &Context, command: Command) {
+        match command {
+            Command::Timings => self.run_action(ctx, keymap::Action::ToggleTimings),
+            Command::Log => self.run_action(ctx, keymap::Action::ToggleLog),
+            Command::Playlist => self.run_action(ctx, keymap::Action::ShowPlaylist),
+            Command::Graph => self.run_action(ctx, keymap::Action::ShowGraph),
+            Command::History => self.run_action(ctx, keymap::Action::ToggleHistory),
+            Command::Spectrum => self.run_action(ctx, keymap::Action::ToggleSpectrum),
+            Command::Scope => self.run_action(ctx, keymap::Action::ToggleScope),
+            Command::Tuner => self.run_action(ctx, keymap::Action::ToggleTuner),
+            Command::Loudness => self.run_action(ctx, keymap::Action::ToggleLoudness),
+            Command::Shortcuts => self.run_action(ctx, keymap::Action::EditShortcuts),
+            Command::Collect => self.collect_and_save(),
+            Command::ExportDiagnostics => {
+                if self.picking.is_none() {
+                    self.picking = Some((Picking::ExportDiagnostics, dialog::save_diagnostics()));
                 }
-            });
+            }
+            Command::Info => {
+                info::dump();
+                self.notification_drawer.info("Dumped system info into console!");
+            }
+            Command::Bug => {
+                println!("!!!!!!\nWhen making your bug report, add the information below!\n!!!!!!");
+                info::dump();
+                self.notification_drawer.info("Dumped system info into console! You'll be redirected to the official Volt bug report page in ~3 seconds.");
+                std::thread::spawn(|| {
+                    std::thread::sleep(Duration::from_secs(3));
+                    info::open_link(info::BUG_REPORT_URL);
+                });
+            }
+            Command::Node(name) => {
+                self.central.add_node(name);
+            }
+            Command::Bpm(bpm) => self.central.set_bpm(bpm, false),
+            Command::Zoom(percent) => self.central.set_zoom(percent),
+            Command::Goto { bar, beat, sixteenth } => {
+                let position = self.central.go_to(bar, beat, sixteenth);
+                if let Some(engine) = &self.engine {
+                    engine.seek(position);
+                }
+            }
+            Command::Script(name) => match script::load(&name) {
+                Ok(source) => self.run_script(&name, &source),
+                Err(error) => {
+                    self.notification_drawer.error(format!("Couldn't run {name}: {error}"));
+                }
+            },
+            Command::Lua(code) => self.run_script("console", &code),
+        }
+    }
+
+    /// Run the script `source` called `name` in the background.
+    fn run_script(&mut self, name: &str, source: &str) {
+        let receiver = self.central.run_script(name, source);
+        self.tasks.push(Task::Script(self.history.revision(), receiver));
+    }
+
+    /// Take what a script that started at `revision` of the history changed, showing what it printed and whether it failed.
+    fn finish_script(&mut self, finished: script::Finished, revision: u64) {
+        let script::Finished { name, mut run, playlist, inserts } = finished;
+        let mut edit = None;
+        if run.changed {
+            if self.history.revision() == revision {
+                self.central.finish_script(&name, playlist, inserts);
+                edit = self.commit_edit();
+            } else if run.result.is_ok() {
+                run.result = Err("the project was edited while it ran, so its changes were dropped".into());
+            }
+        }
+        if !run.output.is_empty() {
+            self.notification_drawer.info(run.output.join("\n"));
+        }
+        match run.result {
+            Ok(()) if run.output.is_empty() || edit.is_some() => {
+                let notification = self.notification_drawer.success(format!("Ran {name}"));
+                if let Some(edit) = edit {
+                    notification.action("Undo", NotificationAction::Undo(edit));
+                }
+            }
+            Ok(()) => {}
+            Err(error) => {
+                self.notification_drawer.error(format!("{name} failed: {error}"));
+            }
+        }
+    }
+}
+
+impl App for VoltApp {
+    #[allow(clippy::too_many_lines, reason = "shut")]
+    fn update(&mut self, ctx: &Context, _: &mut eframe::Frame) {
+        let time_render_start = timings::now_ns();
+        crash::set_project(self.project_path.as_deref());
+        // The interface turns light or dark when the theme is changed, or with the system's when it's followed.
+        let turned = std::mem::replace(&mut self.dark_mode, ctx.style().visuals.dark_mode) != self.dark_mode;
+        if self.theme_watcher.changed(self.config.colors.as_deref()) || (turned && self.config.colors.is_none()) {
+            self.set_colors(self.config.colors.clone());
+        }
+        timings::record_frame(time_render_start);
+
+        // Keyboard shortcut handler
+        let typing = self.palette.open || ctx.wants_keyboard_input();
+        // MIDI controllers run the actions bound to them along with the shortcuts, the controls left over go to the parameters mapped to them, and
+        // the notes left over are played live.
+        for message in self.controllers.take() {
+            if self.keymap.midi(message) {
+                continue;
+            }
+            let live = match message {
+                controller::Message::Control(change) => {
+                    self.central.control_changed(change);
+                    let (control, value) = (change.control, f64::from(change.value) / 127.);
+                    (control.controller == SLIDE_CONTROLLER).then_some(LiveNote::Express { channel: control.channel, dimension: Dimension::Slide, value })
+                }
+                controller::Message::Note { channel, key, velocity } => Some(LiveNote::Press { channel, key, velocity }),
+                controller::Message::Release { channel, key } => Some(LiveNote::Release { channel, key }),
+                controller::Message::Expression { channel, dimension, value } => Some(LiveNote::Express { channel, dimension, value }),
+                controller::Message::Transport(_) => None,
+            };
+            if let (Some(engine), Some(live)) = (&self.engine, live) {
+                engine.play_live(live);
+            }
+        }
+        for action in self.keymap.pressed(ctx, typing) {
+            self.run_action(ctx, action);
+        }
+        // The window takes the keys pressed to record a shortcut, so it's shown before anything else can use them.
+        self.keymap.show(ctx);
+        self.log.show(ctx);
+
+        let palette_timer = timings::ScopedTimer::new(timings::set_palette_time);
+        let palette = self.palette.show(ctx, &self.theme, &mut self.browser, &self.keymap);
+        drop(palette_timer);
+        match palette {
+            Some(Action::Run(command)) => self.run_command(ctx, command),
+            Some(Action::Preview(path)) => self.browser.preview_file(&path),
+            Some(Action::AddAtPlayhead(path)) => self.central.add_at_playhead(&path),
+            None => {}
+        }
+
+        self.onboarding_window(ctx);
 
         TopBottomPanel::top("navbar").frame(egui::Frame::default()).show_separator_line(false).show(ctx, |ui| {
-            ui.add(navbar(&self.theme));
+            let mut action = None;
+            {
+                let _timer = timings::ScopedTimer::new(timings::set_navbar_time);
+                let transport = Transport {
+                    playing: self.engine.as_ref().is_some_and(Engine::is_playing),
+                    bpm: self.central.bpm(),
+                    time_signature: self.central.time_signature(),
+                    position: self.position(),
+                };
+                ui.add(navbar(&self.theme, &self.config, transport, &mut action));
+            }
+            match action {
+                Some(MenuAction::New) => self.request_project_action(ctx, ProjectAction::New),
+                Some(MenuAction::Open) => self.request_project_action(ctx, ProjectAction::Open),
+                Some(MenuAction::Save) => self.save(ctx, false, None),
+                Some(MenuAction::SaveAs) => self.save(ctx, true, None),
+                Some(MenuAction::CollectAndSave) => self.collect_and_save(),
+                Some(MenuAction::ExportArchive) => self.export_archive(),
+                Some(MenuAction::ImportArchive) => self.request_project_action(ctx, ProjectAction::Import),
+                Some(MenuAction::ImportMidi) => self.import_midi(),
+                Some(MenuAction::ExportMidi) => self.export_midi(),
+                Some(MenuAction::ExportMix) => self.export_mix(),
+                Some(MenuAction::Undo) => self.undo(),
+                Some(MenuAction::Redo) => self.redo(),
+                Some(MenuAction::ShowHistory) => self.show_history = true,
+                Some(MenuAction::ShowSpectrum) => self.show_spectrum = true,
+                Some(MenuAction::ShowScope) => self.show_scope = true,
+                Some(MenuAction::ShowTuner) => self.show_tuner = true,
+                Some(MenuAction::ShowLoudness) => self.show_loudness = true,
+                Some(MenuAction::ShowAbout) => self.about.open = true,
+                Some(MenuAction::RelinkFiles) => self.open_relink(true),
+                Some(MenuAction::EditShortcuts) => self.keymap.open = true,
+                Some(MenuAction::SetTheme(theme)) => {
+                    self.config.theme = theme;
+                    ctx.set_theme(theme);
+                }
+                Some(MenuAction::SetColors(name)) => self.set_colors(name),
+                Some(MenuAction::SetAccessibility(accessibility)) => {
+                    self.config.accessibility = accessibility;
+                    accessibility.apply(ctx);
+                }
+                Some(MenuAction::SetBallistics(ballistics)) => self.config.meters = ballistics,
+                Some(MenuAction::SetFont(name)) => self.set_font(ctx, name),
+                Some(MenuAction::SetIconPack(name)) => self.set_icon_pack(name),
+                Some(MenuAction::OpenIconsFolder) => {
+                    if let Err(error) = open::that_detached(icon::folder()) {
+                        self.notification_drawer.error(format!("Couldn't open the icons folder, {error}."));
+                    }
+                }
+                Some(MenuAction::TogglePlayback) => self.run_action(ctx, keymap::Action::TogglePlayback),
+                Some(MenuAction::SetBpm { bpm, live }) => self.central.set_bpm(bpm, live),
+                Some(MenuAction::SetTimeSignature(time_signature)) => self.central.set_time_signature(time_signature),
+                Some(MenuAction::OpenThemesFolder) => {
+                    if let Err(error) = open::that_detached(theme::folder()) {
+                        self.notification_drawer.error(format!("Couldn't open the themes folder, {error}."));
+                    }
+                }
+                None => {}
+            }
         });
         TopBottomPanel::bottom("status").frame(egui::Frame::default()).show_separator_line(false).show(ctx, |ui| {
-            ui.add(status(&self.theme));
+            self.central.set_meter_ballistics(self.config.meters);
+            self.central.set_monitoring_latency(self.engine.as_ref().and_then(Engine::monitoring_latency));
+            let engine = self.engine.as_ref();
+            let info = Status {
+                position: self.position(),
+                format: engine.map(|engine| (engine.sample_rate(), engine.buffer_size())),
+                load: timings::audio_load(),
+                cache: engine.map(Engine::cache_usage),
+                selection: self.central.selection_summary(),
+                tasks: tasks::list(),
+            };
+            ui.add(status(&self.theme, info, self.central.master_meter(), &mut self.notification_drawer));
         });
+        self.browser.set_tempo(self.central.bpm());
+        self.central.set_theme(Rc::clone(&self.theme));
+        self.browser.set_active_devices(self.engine.as_ref().map(Engine::output_name), self.engine.as_ref().and_then(Engine::input_name));
+        self.browser.set_active_midi_ports(self.controllers.names());
+        self.browser.set_preview_state(self.engine.as_ref().and_then(Engine::preview_state));
         SidePanel::left("browser").default_width(300.).frame(egui::Frame::default().fill(self.theme.browser)).show_separator_line(false).show(ctx, |ui| {
+            let _timer = timings::ScopedTimer::new(timings::set_browser_time);
             ui.add(&mut self.browser);
         });
         CentralPanel::default().frame(egui::Frame::default().fill(self.theme.central_background)).show(ctx, |ui| {
+            let _timer = timings::ScopedTimer::new(timings::set_central_time);
             ui.add(&mut self.central);
         });
+        if self.central.is_graph_detached() {
+            let fill = self.theme.central_background;
+            ctx.show_viewport_immediate(ViewportId::from_hash_of("graph"), ViewportBuilder::default().with_title("Graph").with_inner_size([900., 600.]), |ctx, _| {
+                CentralPanel::default().frame(egui::Frame::default().fill(fill)).show(ctx, |ui| self.central.detached_graph_ui(ui));
+                if ctx.input(|input| input.viewport().close_requested()) {
+                    self.central.set_graph_detached(false);
+                }
+            });
+        }
+        let preview_commands = self.browser.take_preview_commands();
+        if let Some(engine) = &mut self.engine {
+            for command in preview_commands {
+                engine.preview(command);
+            }
+            for error in engine.take_errors() {
+                self.notification_drawer.error(error);
+            }
+        }
+        for error in self.browser.take_errors() {
+            self.notification_drawer.error(error);
+        }
+        if let Some(progress) = self.browser.take_index_progress() {
+            self.notification_drawer.progress("Indexing the browser's folders…", progress);
+        }
+        self.poll_tasks();
+        if let Some(release) = self.about.take_release() {
+            self.notification_drawer.info(format!("Volt {} is out.", release.version)).action("Download", NotificationAction::OpenLink(release.url));
+        }
+        for action in self.notification_drawer.take_actions() {
+            self.run_notification_action(action);
+        }
+        if let Some(path) = self.browser.take_opened_in_editor() {
+            self.central.open_in_editor(&path);
+        }
+        if let Some(device) = self.browser.take_picked_device() {
+            self.switch_device(device);
+        }
+        if let Some(port) = self.browser.take_picked_midi_port() {
+            if let Err(error) = self.controllers.connect(&port) {
+                self.notification_drawer.error(format!("Couldn't connect to the MIDI input {port}: {error}"));
+            }
+        }
+        self.browser.add_recent(&self.central.take_added());
+        if let Some(error) = self.central.take_slice_error() {
+            self.notification_drawer.warning(format!("Couldn't slice the clip, {error}."));
+        }
+        let exported = self.central.take_exported();
+        if let Some(first) = exported.first() {
+            let message = format!("Exported {} clip(s), drag them from the file manager to drop them elsewhere.", exported.len());
+            self.notification_drawer.success(message).action("Show in folder", NotificationAction::Reveal(first.clone()));
+        }
+        self.relink_window(ctx);
+        self.commit_edit();
+        self.project_dialogs(ctx);
+        self.config.update(ctx);
+        // Settings like the place of the window change continuously while they're being changed, so they're saved at most once a second.
+        if self.config != self.saved_config && self.config_saved_at.elapsed() >= Duration::from_secs(1) {
+            self.config.save();
+            self.saved_config = self.config.clone();
+            self.config_saved_at = Instant::now();
+        }
+        let title = format!("{}{} — Volt", self.project_name(), if self.unsaved { "*" } else { "" });
+        if title != self.title {
+            ctx.send_viewport_cmd(ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+        if self.show_history {
+            self.history_window(ctx);
+        }
+        if self.central.analyzer_windows(ctx, &mut self.show_spectrum, &mut self.show_scope) {
+            self.update_engine();
+        }
+        if self.central.tuner_window(ctx, &mut self.show_tuner) {
+            self.update_engine();
+        }
+        if self.central.loudness_window(ctx, &mut self.show_loudness, &mut self.config.loudness_reports) {
+            self.update_engine();
+        }
+        self.about.window(ctx, &mut self.config.check_for_updates);
 
         egui::Area::new("notifications_area".into())
             .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(ctx.screen_rect().max.x, ctx.screen_rect().max.y))
@@ -492,6 +1288,7 @@ impl App for VoltApp {
                     stroke: egui::Stroke::NONE,
                 }
                 .show(ui, |ui| {
+                    let _timer = timings::ScopedTimer::new(timings::set_notifications_time);
                     ui.add(&mut self.notification_drawer);
                 });
             });
@@ -500,7 +1297,9 @@ impl App for VoltApp {
         timings::set_render_time(time_render_elapsed);
 
         if self.timings_toggle {
-            timings::show_timings(ctx, "Timings", 4);
+            let (mut open, mut detached) = (true, self.timings_detached);
+            visual::detachable_window(ctx, "Timings", &mut open, &mut detached, |ui| timings::timings_ui(ui, 4, |ui| self.memory_ui(ui)));
+            (self.timings_toggle, self.timings_detached) = (open, detached);
         }
     }
 
@@ -509,8 +1308,8 @@ impl App for VoltApp {
         println!("Volt is exiting!");
 
         // Perform any final saves or cleanup
-        // For example, you might want to save user preferences or state
-        // self.save_state();
+        self.config.save();
+        self.browser.save_session();
 
         // Close any open connections or files
         // self.close_connections();
@@ -0,0 +1,19 @@
+//! Reading MIDI files from the disk or from archives, so that they can be browsed and previewed like audio.
+
+use std::{io::Read, path::Path};
+
+use blerp::midi::MidiFile;
+
+use crate::archive;
+
+/// Return whether `path` is a MIDI file, going by its extension.
+pub fn is_midi(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("mid") || extension.eq_ignore_ascii_case("midi"))
+}
+
+/// Read the MIDI file at `path`, which may be in an archive.
+pub fn read(path: &Path) -> Result<MidiFile, String> {
+    let mut bytes = Vec::new();
+    archive::open(path).and_then(|mut file| file.read_to_end(&mut bytes)).map_err(|error| error.to_string())?;
+    MidiFile::read(&bytes).map_err(|error| error.to_string())
+}
@@ -0,0 +1,45 @@
+//! How far tasks running in the background have got, like exporting a project or indexing the browser's roots, so that it can be shown and they can be cancelled.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+/// How far a task running in the background has got, shared between the task and what shows it.
+///
+/// The task is over once it drops its handle, whether it was done, failed or was cancelled, so that it can't forget to say so.
+#[derive(Debug, Default)]
+pub struct Progress {
+    done: AtomicU64,
+    total: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    /// Say that `done` out of `total` steps of the task are done, in whatever steps suit the task.
+    pub fn set(&self, done: u64, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+        self.done.store(done.min(total), Ordering::Relaxed);
+    }
+
+    /// Return how much of the task is done from 0 to 1, or [`None`] if it hasn't said yet.
+    pub fn fraction(&self) -> Option<f64> {
+        let total = self.total.load(Ordering::Relaxed);
+        #[allow(clippy::cast_precision_loss, reason = "steps are well within range")]
+        (total > 0).then(|| self.done.load(Ordering::Relaxed) as f64 / total as f64)
+    }
+
+    /// Ask the task to stop, which it does the next time it checks.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Return whether the task is over, which is when only `progress` is left of the handles to its progress.
+    pub fn is_over(progress: &Arc<Self>) -> bool {
+        Arc::strong_count(progress) == 1
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs,
+    io::{self, ErrorKind},
+    path::{self, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive, config,
+    controller::Mapping,
+    progress::Progress,
+    visual::{
+        central::{Graph, Playlist},
+        dialog::PROJECT_EXTENSION,
+    },
+};
+
+/// The project opened on startup when it's there, which is where projects were kept before they could be saved elsewhere.
+pub const PATH: &str = "project.volt";
+/// Where files taken out of archives for the project are kept, and the folder next to the project file that files are collected into.
+pub const SAMPLES: &str = "samples";
+
+/// Everything about a project that is saved to its file, stored as TOML.
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    /// The clips and their tracks, along with the tempo, time signature and snapping.
+    #[serde(default)]
+    pub playlist: Playlist,
+    pub graph: Graph,
+    /// The insert chain of each track that has one.
+    #[serde(default, with = "track_keys")]
+    pub inserts: BTreeMap<u32, Graph>,
+    /// The roots of the browser, or none to keep the roots already shown.
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+    /// The controls of MIDI controllers mapped to parameters.
+    #[serde(default)]
+    pub mappings: Vec<Mapping>,
+}
+
+/// Saves a map by track as a table keyed by the track's number, as TOML keys have to be strings.
+pub mod track_keys {
+    use std::collections::BTreeMap;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, T: Serialize>(map: &BTreeMap<u32, T>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(track, value)| (track.to_string(), value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<BTreeMap<u32, T>, D::Error> {
+        BTreeMap::<String, T>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(track, value)| track.parse().map(|track| (track, value)).map_err(|_| D::Error::custom(format!("\"{track}\" is not a track"))))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(io::Error),
+    Read(toml::de::Error),
+    Write(toml::ser::Error),
+    /// A task on the project was cancelled before it was done.
+    Cancelled,
+}
+
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Read(error) => write!(f, "the project file is invalid: {error}"),
+            Self::Write(error) => write!(f, "the project can't be saved: {error}"),
+            Self::Cancelled => write!(f, "it was cancelled"),
+        }
+    }
+}
+
+impl Project {
+    /// Read the project saved at `path`, where files are found relative to the folder of the project file.
+    pub fn load(path: &Path) -> Result<Self, ProjectError> {
+        let text = fs::read_to_string(path).map_err(ProjectError::Io)?;
+        let mut project: Self = toml::from_str(&text).map_err(ProjectError::Read)?;
+        let folder = folder(path).map_err(ProjectError::Io)?;
+        for file in project.files_mut() {
+            if file.is_relative() {
+                *file = folder.join(&*file);
+            }
+        }
+        Ok(project)
+    }
+
+    /// Save the project to `path`. Files in the folder of the project file are saved relative to it, so that the folder can be moved along with them.
+    pub fn save(mut self, path: &Path) -> Result<(), ProjectError> {
+        let folder = folder(path).map_err(ProjectError::Io)?;
+        for file in self.files_mut() {
+            if let Some(relative) = path::absolute(&*file).ok().and_then(|absolute| absolute.strip_prefix(&folder).ok().map(Path::to_path_buf)) {
+                *file = relative;
+            }
+        }
+        let text = toml::to_string(&self).map_err(ProjectError::Write)?;
+        fs::write(path, text).map_err(ProjectError::Io)
+    }
+
+    /// Write the project called `name` to an archive at `path` along with every file it uses, which are put in its samples folder, so that the whole project can be
+    /// handed over as one file and opened again with [`import`].
+    ///
+    /// Returns the files that couldn't be added and why, which the project in the archive keeps pointing to where they were. `progress` is kept up to date in bytes of
+    /// the files added, and the archive is removed if it's cancelled or fails.
+    pub fn export(self, name: &str, path: &Path, progress: &Progress) -> Result<Vec<(PathBuf, io::Error)>, ProjectError> {
+        let result = self.write_archive(name, path, progress);
+        if result.is_err() {
+            let _ = fs::remove_file(path);
+        }
+        result
+    }
+
+    fn write_archive(mut self, name: &str, path: &Path, progress: &Progress) -> Result<Vec<(PathBuf, io::Error)>, ProjectError> {
+        let mut archive = archive::Writer::create(path).map_err(ProjectError::Io)?;
+        let mut names = HashMap::new();
+        let mut taken = HashSet::new();
+        let mut failed = Vec::new();
+        let files = self.files_mut().into_iter().map(|file| file.clone()).collect::<BTreeSet<_>>();
+        let size = |file: &Path| fs::metadata(file).map_or(0, |metadata| metadata.len());
+        let total = files.iter().map(|file| size(file)).sum();
+        let mut done = 0;
+        for file in files {
+            if progress.is_cancelled() {
+                return Err(ProjectError::Cancelled);
+            }
+            progress.set(done, total);
+            done += size(&file);
+            let Some(file_name) = names_for(&file).find(|file_name| !taken.contains(file_name)) else {
+                continue;
+            };
+            match fs::read(&file).and_then(|data| archive.add(&format!("{SAMPLES}/{file_name}"), &data)) {
+                Ok(()) => {
+                    taken.insert(file_name.clone());
+                    names.insert(file, Path::new(SAMPLES).join(file_name));
+                }
+                Err(error) => failed.push((file, error)),
+            }
+        }
+        for file in self.files_mut() {
+            if let Some(name) = names.get(&*file) {
+                file.clone_from(name);
+            }
+        }
+        let text = toml::to_string(&self).map_err(ProjectError::Write)?;
+        archive.add(&format!("{name}.{PROJECT_EXTENSION}"), text.as_bytes()).map_err(ProjectError::Io)?;
+        archive.finish().map_err(ProjectError::Io)?;
+        Ok(failed)
+    }
+
+    /// Return the files used by clips and file players.
+    fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+        let mut files = self.playlist.files_mut();
+        files.extend(self.graph.files_mut());
+        files.extend(self.inserts.values_mut().flat_map(Graph::files_mut));
+        files
+    }
+}
+
+/// Return the folder that files made for the project whose file is at `path` are kept in, which is the samples folder next to it, or the one in the app's
+/// folder for a project that was never saved.
+pub fn samples_folder(path: Option<&Path>) -> PathBuf {
+    path.and_then(|path| folder(path).ok()).map_or_else(|| config::path(SAMPLES), |folder| folder.join(SAMPLES))
+}
+
+/// Return the absolute path of the folder that the project file at `path` is in.
+fn folder(path: &Path) -> io::Result<PathBuf> {
+    let path = path::absolute(path)?;
+    Ok(path.parent().map_or_else(|| path.clone(), Path::to_path_buf))
+}
+
+/// Copy the `files` that aren't in the folder of the project file at `path` into its samples folder, so that the project can be moved along with its folder.
+///
+/// Returns where each file was copied to, along with the files that couldn't be copied and why. Files are renamed when another file already has their name.
+pub fn collect(files: impl IntoIterator<Item = PathBuf>, path: &Path) -> (HashMap<PathBuf, PathBuf>, Vec<(PathBuf, io::Error)>) {
+    let mut moves = HashMap::new();
+    let mut failed = Vec::new();
+    let folder = match folder(path) {
+        Ok(folder) => folder,
+        Err(error) => {
+            failed.push((path.to_path_buf(), error));
+            return (moves, failed);
+        }
+    };
+    let samples = folder.join(SAMPLES);
+    for file in files {
+        if path::absolute(&file).is_ok_and(|absolute| absolute.starts_with(&folder)) {
+            continue;
+        }
+        let result = fs::create_dir_all(&samples).and_then(|()| {
+            let destination = free_path(&samples, &file);
+            fs::copy(&file, &destination).map(|_| destination)
+        });
+        match result {
+            Ok(destination) => {
+                moves.insert(file, destination);
+            }
+            Err(error) => failed.push((file, error)),
+        }
+    }
+    (moves, failed)
+}
+
+/// Extract the project archive at `path`, written by [`Project::export`], into a new folder next to it named after it, returning the path of the project file to
+/// open. `progress` is kept up to date in files extracted, and the folder is removed if it's cancelled or fails.
+pub fn import(path: &Path, progress: &Progress) -> Result<PathBuf, ProjectError> {
+    let entries = archive::list(path).map_err(ProjectError::Io)?;
+    let project = entries
+        .iter()
+        .find(|entry| !entry.name.contains('/') && Path::new(&entry.name).extension().is_some_and(|extension| extension == PROJECT_EXTENSION))
+        .ok_or_else(|| ProjectError::Io(io::Error::new(ErrorKind::InvalidData, "there is no project in the archive")))?;
+    let parent = folder(path).map_err(ProjectError::Io)?;
+    // The archive's name without its extension, which is made unique so that nothing is overwritten.
+    let folder = free_path(&parent, Path::new(path.file_stem().unwrap_or_default()));
+    let result = extract(path, &entries, &folder, progress);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&folder);
+    }
+    result.map(|()| folder.join(&project.name))
+}
+
+/// Extract every one of `entries` of the archive at `path` into `folder`.
+fn extract(path: &Path, entries: &[archive::Entry], folder: &Path, progress: &Progress) -> Result<(), ProjectError> {
+    for (index, entry) in (0..).zip(entries) {
+        if progress.is_cancelled() {
+            return Err(ProjectError::Cancelled);
+        }
+        progress.set(index, entries.len() as u64);
+        let destination = folder.join(&entry.name);
+        let result = archive::read_entry(path, entry).and_then(|data| {
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&destination, data)
+        });
+        result.map_err(ProjectError::Io)?;
+    }
+    Ok(())
+}
+
+/// Return a path in `folder` with the name of `file` that isn't taken, adding a number to the name if it is.
+pub fn free_path(folder: &Path, file: &Path) -> PathBuf {
+    names_for(file).map(|name| folder.join(name)).find(|candidate| !candidate.exists()).unwrap_or_else(|| folder.join(file.file_name().unwrap_or_default()))
+}
+
+/// Return the names `file` can be given, starting with its own and followed by it with a number added.
+fn names_for(file: &Path) -> impl Iterator<Item = String> {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let extension = file.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    (1..=u32::MAX).map(move |number| if number == 1 { format!("{stem}{extension}") } else { format!("{stem} ({number}){extension}") })
+}
@@ -0,0 +1,395 @@
+//! Lua scripts run over the project, to batch edits and keep macros. Scripts are kept in the `scripts` folder of the configuration folder and run from the
+//! command palette with `> script <name>`, while `> lua <code>` runs a line of Lua right away.
+//!
+//! Scripts reach the project through the `project` table, where tracks and clips are counted from 1 as they are in the playlist:
+//!
+//! - `project.clips()` returns a table for every clip, with its `index`, `name`, `track`, `start` in beats, whether it's `selected` and whether it has
+//!   `audio`, along with its processing: `reversed`, `inverted`, `normalize` (like `{ peak = -1 }` or `{ loudness = -14 }`), `fade_in` and `fade_out`.
+//! - `project.update(clip)` saves the changes made to such a table into the clip.
+//! - `project.tracks()` and `project.selected_tracks()` return the tracks used, or those of the selected clips.
+//! - `project.new_track()` adds an empty track and returns it.
+//! - `project.effects()` lists the effects there are, and `project.add_effect(track, effect, parameters)` adds one to the end of a track's insert chain,
+//!   with its parameters set by name, like `{ factor = 0.5 }`.
+//! - `project.bpm()` and `project.set_bpm(bpm)` get and set the tempo.
+//!
+//! What scripts `print` is shown once they're done. They run in the background over copies of the project, which replace it once they're done, and can't
+//! reach the files or the system, as only the `string`, `table`, `math`, `utf8` and `coroutine` libraries are loaded.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use blerp::processing::{effects::normalize::NormalizeTarget, registry};
+use crossbeam_channel::Receiver;
+use itertools::Itertools;
+use mlua::{Error, Function, HookTriggers, Lua, LuaOptions, StdLib, Table, Value, Variadic};
+use tracing::error;
+
+use crate::{
+    config,
+    tasks::{self, Job, Kind},
+    visual::central::{Clip, ClipData, Graph, NodeData, Playlist, Tempo, Time},
+};
+
+/// The folder scripts are kept in, in the configuration folder.
+const FOLDER: &str = "scripts";
+/// The script written when the folder is created, to start new scripts from.
+const EXAMPLE: (&str, &str) = ("normalize-selected-tracks", include_str!("script/normalize-selected-tracks.lua"));
+/// How long a script can run before it's stopped, so that scripts that never end don't hold a worker forever.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How many bytes a script can allocate, so that scripts making huge strings or tables fail rather than take the memory of the whole app.
+const MEMORY_LIMIT: usize = 256 << 20;
+
+/// Return the folder scripts are kept in, creating it along with an example script if it doesn't exist yet.
+pub fn folder() -> PathBuf {
+    let folder = config::path(FOLDER);
+    if !folder.exists() {
+        if let Err(error) = fs::create_dir_all(&folder).and_then(|()| fs::write(path(&folder, EXAMPLE.0), EXAMPLE.1)) {
+            error!("Couldn't create the scripts folder: {error}");
+        }
+    }
+    folder
+}
+
+fn path(folder: &Path, name: &str) -> PathBuf {
+    folder.join(format!("{name}.lua"))
+}
+
+/// Return the names of the scripts there are, in alphabetical order.
+pub fn available() -> Vec<String> {
+    match fs::read_dir(folder()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .sorted()
+            .collect(),
+        Err(error) => {
+            error!("Couldn't read the scripts folder: {error}");
+            Vec::new()
+        }
+    }
+}
+
+/// Read the script called `name`.
+///
+/// # Errors
+///
+/// Returns why the script couldn't be read, to be shown to the user.
+pub fn load(name: &str) -> Result<String, String> {
+    match fs::read_to_string(path(&folder(), name)) {
+        Ok(text) => Ok(text),
+        Err(error) if error.kind() == ErrorKind::NotFound => Err("it doesn't exist".into()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// What a script did.
+#[derive(Debug)]
+pub struct Run {
+    /// The lines the script printed.
+    pub output: Vec<String>,
+    /// Whether the script changed the project, which it may have done before failing too.
+    pub changed: bool,
+    /// Why the script failed, to be shown to the user.
+    pub result: Result<(), String>,
+}
+
+/// The parts of the project scripts can change.
+struct State<'a> {
+    playlist: &'a mut Playlist,
+    inserts: &'a mut BTreeMap<u32, Graph>,
+    run: Run,
+}
+
+impl State<'_> {
+    /// Return the tracks used by clips or insert chains, counting from 0.
+    fn tracks(&self) -> BTreeSet<u32> {
+        self.playlist.clips.iter().map(|clip| clip.track).chain(self.inserts.keys().copied()).collect()
+    }
+}
+
+/// Return the track counted from 0 of a track counted from 1 by a script.
+fn track(track: u32) -> Result<u32, Error> {
+    track.checked_sub(1).ok_or_else(|| Error::runtime("tracks are counted from 1"))
+}
+
+/// Return a table with what scripts can know about `clip`, which is at `index` in the playlist and may be `selected`.
+fn clip_table<'lua>(lua: &'lua Lua, index: usize, clip: &Clip, selected: bool) -> Result<Table<'lua>, Error> {
+    let table = lua.create_table()?;
+    table.set("index", index + 1)?;
+    table.set("name", clip.name.as_str())?;
+    table.set("track", clip.track + 1)?;
+    table.set("start", clip.start.beats())?;
+    table.set("selected", selected)?;
+    table.set("audio", matches!(clip.data, ClipData::Audio { .. }))?;
+    table.set("reversed", clip.processing.reversed)?;
+    table.set("inverted", clip.processing.inverted)?;
+    let normalize = match clip.processing.normalize {
+        Some(NormalizeTarget::Peak(decibels)) => Some(lua.create_table_from([("peak", decibels)])?),
+        Some(NormalizeTarget::Loudness(lufs)) => Some(lua.create_table_from([("loudness", lufs)])?),
+        None => None,
+    };
+    table.set("normalize", normalize)?;
+    table.set("fade_in", clip.processing.fade_in)?;
+    table.set("fade_out", clip.processing.fade_out)?;
+    Ok(table)
+}
+
+/// Change `clip` to match `table`, which was returned by [`clip_table`] and may have been changed since.
+fn update_clip(clip: &mut Clip, table: &Table) -> Result<(), Error> {
+    let start: f64 = table.get("start")?;
+    if !start.is_finite() || start < 0. {
+        return Err(Error::runtime("clips can't start before the start of the project"));
+    }
+    clip.name = table.get("name")?;
+    clip.track = track(table.get("track")?)?;
+    clip.start = Time::from_beats(start).unwrap_or_default();
+    clip.processing.reversed = table.get("reversed")?;
+    clip.processing.inverted = table.get("inverted")?;
+    clip.processing.normalize = match table.get::<_, Option<Table>>("normalize")? {
+        Some(normalize) => match (normalize.get::<_, Option<f64>>("peak")?, normalize.get::<_, Option<f64>>("loudness")?) {
+            (Some(decibels), None) => Some(NormalizeTarget::Peak(decibels)),
+            (None, Some(lufs)) => Some(NormalizeTarget::Loudness(lufs)),
+            _ => return Err(Error::runtime("clips are normalized to either a peak or a loudness, like { peak = -1 }")),
+        },
+        None => None,
+    };
+    clip.processing.fade_in = table.get::<_, f64>("fade_in")?.max(0.);
+    clip.processing.fade_out = table.get::<_, f64>("fade_out")?.max(0.);
+    Ok(())
+}
+
+/// Add the effect with the ID or name `effect` to the end of the insert chain of `track` in `inserts`, with the parameters named in `parameters` set.
+fn add_effect(inserts: &mut BTreeMap<u32, Graph>, track: u32, effect: &str, parameters: Option<Table>) -> Result<(), Error> {
+    let effect = registry::find_by_id(effect).or_else(|| registry::find(effect)).ok_or_else(|| Error::runtime(format!("there is no effect called \"{effect}\"")))?;
+    let mut data = NodeData::effect(effect);
+    if let (NodeData::Middle { parameters: values, .. }, Some(parameters)) = (&mut data, parameters) {
+        for pair in parameters.pairs::<String, f64>() {
+            let (name, value) = pair?;
+            let index = effect
+                .parameters
+                .iter()
+                .position(|parameter| parameter.name.eq_ignore_ascii_case(&name))
+                .ok_or_else(|| Error::runtime(format!("{} has no parameter called \"{name}\"", effect.name)))?;
+            values[index] = value.clamp(*effect.parameters[index].range.start(), *effect.parameters[index].range.end());
+        }
+    }
+    inserts.entry(track).or_insert_with(Graph::inserts).append(data);
+    Ok(())
+}
+
+/// Stop the script run by `lua` once it has run for longer than [`TIMEOUT`].
+fn stop_after_timeout(lua: &Lua) {
+    let started = Instant::now();
+    lua.set_hook(HookTriggers::new().every_nth_instruction(10_000), move |_, _| {
+        if started.elapsed() > TIMEOUT {
+            return Err(Error::runtime(format!("the script was stopped after running for {} seconds", TIMEOUT.as_secs())));
+        }
+        Ok(())
+    });
+}
+
+/// Return the interpreter a script runs in, which only has the standard libraries that can't reach the files or the system.
+fn interpreter() -> Result<Lua, Error> {
+    let lua = Lua::new_with(StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE, LuaOptions::default())?;
+    // The base library is always loaded, and these run files.
+    for name in ["dofile", "loadfile"] {
+        lua.globals().set(name, Value::Nil)?;
+    }
+    stop_after_timeout(&lua);
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    Ok(lua)
+}
+
+/// A script that ran in the background, with the copies of the playlist and the insert chains it ran over.
+pub struct Finished {
+    pub name: String,
+    pub run: Run,
+    pub playlist: Playlist,
+    pub inserts: BTreeMap<u32, Graph>,
+}
+
+/// Run the script `source` called `name` in the background over `playlist` and `inserts`, which are sent back along with what it did, see [`run`].
+pub fn spawn(name: String, source: String, mut playlist: Playlist, mut inserts: BTreeMap<u32, Graph>) -> Receiver<Finished> {
+    tasks::spawn(Job::new(Kind::Script, format!("Running {name}")), move || {
+        let run = run(&name, &source, &mut playlist, &mut inserts);
+        Finished { name, run, playlist, inserts }
+    })
+}
+
+/// Run the script `source`, called `name` in its errors, over `playlist` and the insert chains of its tracks in `inserts`. What a script changed before
+/// failing stays changed.
+#[allow(clippy::too_many_lines, reason = "the functions scripts are given are made in place, as they borrow the state for as long as the script runs")]
+pub fn run(name: &str, source: &str, playlist: &mut Playlist, inserts: &mut BTreeMap<u32, Graph>) -> Run {
+    let lua = match interpreter() {
+        Ok(lua) => lua,
+        Err(error) => {
+            return Run {
+                output: Vec::new(),
+                changed: false,
+                result: Err(error.to_string()),
+            }
+        }
+    };
+    let state = RefCell::new(State {
+        playlist,
+        inserts,
+        run: Run {
+            output: Vec::new(),
+            changed: false,
+            result: Ok(()),
+        },
+    });
+    let result = lua.scope(|scope| {
+        let state = &state;
+        let project = lua.create_table()?;
+        project.set(
+            "clips",
+            scope.create_function(|lua, ()| {
+                let state = state.borrow();
+                let clips = state.playlist.clips.iter().enumerate().map(|(index, clip)| clip_table(lua, index, clip, state.playlist.selection.contains(&index)));
+                lua.create_sequence_from(clips.collect::<Result<Vec<_>, Error>>()?)
+            })?,
+        )?;
+        project.set(
+            "update",
+            scope.create_function(|_, table: Table| {
+                let mut state = state.borrow_mut();
+                let index: usize = table.get("index")?;
+                let clip = index.checked_sub(1).and_then(|index| state.playlist.clips.get_mut(index)).ok_or_else(|| Error::runtime(format!("there is no clip {index}")))?;
+                update_clip(clip, &table)?;
+                state.run.changed = true;
+                Ok(())
+            })?,
+        )?;
+        project.set("tracks", scope.create_function(|_, ()| Ok(state.borrow().tracks().into_iter().map(|track| track + 1).collect_vec()))?)?;
+        project.set(
+            "selected_tracks",
+            scope.create_function(|_, ()| {
+                let state = state.borrow();
+                Ok(state.playlist.selection.iter().filter_map(|index| state.playlist.clips.get(*index)).map(|clip| clip.track + 1).unique().sorted().collect_vec())
+            })?,
+        )?;
+        project.set(
+            "new_track",
+            scope.create_function(|_, ()| {
+                let mut state = state.borrow_mut();
+                let track = state.tracks().last().map_or(0, |track| track + 1);
+                state.inserts.insert(track, Graph::inserts());
+                state.run.changed = true;
+                Ok(track + 1)
+            })?,
+        )?;
+        project.set(
+            "effects",
+            lua.create_function(|lua, ()| {
+                let effects = registry::EFFECTS.iter().map(|effect| lua.create_table_from([("id", effect.id), ("name", effect.name), ("category", effect.category.name())]));
+                lua.create_sequence_from(effects.collect::<Result<Vec<_>, Error>>()?)
+            })?,
+        )?;
+        project.set(
+            "add_effect",
+            scope.create_function(|_, (number, effect, parameters): (u32, String, Option<Table>)| {
+                let mut state = state.borrow_mut();
+                add_effect(state.inserts, track(number)?, &effect, parameters)?;
+                state.run.changed = true;
+                Ok(())
+            })?,
+        )?;
+        project.set("bpm", scope.create_function(|_, ()| Ok(state.borrow().playlist.tempo.bpm()))?)?;
+        project.set(
+            "set_bpm",
+            scope.create_function(|_, bpm: f64| {
+                if !(1. ..=999.).contains(&bpm) {
+                    return Err(Error::runtime("the tempo has to be from 1 to 999 BPM"));
+                }
+                let mut state = state.borrow_mut();
+                state.playlist.tempo = Tempo::from_bpm(bpm);
+                state.run.changed = true;
+                Ok(())
+            })?,
+        )?;
+        lua.globals().set("project", project)?;
+        let to_string: Function = lua.globals().get("tostring")?;
+        lua.globals().set(
+            "print",
+            scope.create_function(move |_, values: Variadic<Value>| {
+                let line = values.into_iter().map(|value| to_string.call::<_, String>(value)).collect::<Result<Vec<_>, Error>>()?.join("\t");
+                state.borrow_mut().run.output.push(line);
+                Ok(())
+            })?,
+        )?;
+        lua.load(source).set_name(name).exec()
+    });
+    let mut run = state.into_inner().run;
+    run.result = result.map_err(|error| match error {
+        Error::MemoryError(_) => format!("the script ran out of memory, as scripts can only use {} MB", MEMORY_LIMIT >> 20),
+        error => error.to_string(),
+    });
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use super::run;
+    use crate::visual::central::{Clip, ClipData, Playlist, Time};
+
+    /// Return a playlist with an empty MIDI clip on the first track.
+    fn playlist() -> Playlist {
+        let data = ClipData::Midi {
+            notes: Arc::new([]),
+            length: Time::from_beats(4.).unwrap_or_default(),
+            expression: Arc::new([]),
+        };
+        Playlist {
+            clips: vec![Clip { name: "Bass".into(), ..Clip::new(Time::default(), 0, data) }],
+            ..Playlist::default()
+        }
+    }
+
+    #[test]
+    fn scripts_change_the_clips_and_the_tempo() {
+        let mut playlist = playlist();
+        let source = "for _, clip in ipairs(project.clips()) do clip.name = clip.name .. ' 2'; clip.track = 3; project.update(clip) end project.set_bpm(90)";
+        let run = run("test", source, &mut playlist, &mut BTreeMap::new());
+        assert_eq!(run.result, Ok(()));
+        assert!(run.changed);
+        assert_eq!(playlist.clips[0].name, "Bass 2");
+        assert_eq!(playlist.clips[0].track, 2);
+        assert!((playlist.tempo.bpm() - 90.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn what_scripts_printed_and_changed_before_failing_is_kept() {
+        let mut playlist = playlist();
+        let mut inserts = BTreeMap::new();
+        let run = run("test", "print('tracks', #project.tracks()) project.new_track() error('stop')", &mut playlist, &mut inserts);
+        assert_eq!(run.output, ["tracks\t1"]);
+        assert!(run.changed);
+        assert!(run.result.is_err_and(|error| error.contains("stop")));
+        assert_eq!(inserts.keys().copied().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn scripts_cant_reach_the_files_or_the_system() {
+        let run = run("test", "print(io, os, debug, package, dofile, loadfile)", &mut playlist(), &mut BTreeMap::new());
+        assert_eq!(run.output, ["nil\tnil\tnil\tnil\tnil\tnil"]);
+        assert!(!run.changed);
+    }
+
+    #[test]
+    fn scripts_running_out_of_memory_fail() {
+        let run = run("test", "local huge = string.rep('x', 1e9) print(#huge)", &mut playlist(), &mut BTreeMap::new());
+        assert!(run.output.is_empty());
+        assert!(run.result.as_ref().is_err_and(|error| error.contains("ran out of memory")), "{:?}", run.result);
+    }
+}
@@ -0,0 +1,15 @@
+-- Normalize every audio clip on the tracks of the selected clips to a peak of -1 dBFS.
+local tracks = {}
+for _, track in ipairs(project.selected_tracks()) do
+  tracks[track] = true
+end
+
+local count = 0
+for _, clip in ipairs(project.clips()) do
+  if clip.audio and tracks[clip.track] then
+    clip.normalize = { peak = -1 }
+    project.update(clip)
+    count = count + 1
+  end
+end
+print("Normalized " .. count .. " clips")
@@ -0,0 +1,360 @@
+//! Running jobs in the background, like indexing the browser's roots, drawing waveforms, analysing files and exporting projects, on a few worker
+//! threads shared by the whole app rather than on a thread of their own each.
+//!
+//! Jobs wait in a queue until a worker is free, the most urgent first, and are listed with their [`Progress`] so that the status bar can show what's
+//! going on and cancel them. A job cancelled while it waits is taken off the queue, and one cancelled while it runs stops if it checks its progress.
+//! Jobs that wait on something else, like a file dialog or a device, are better off on a thread of their own, as they'd
+//! hold a worker up.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering},
+        Arc, Condvar, LazyLock, Mutex, Weak,
+    },
+    thread::{self, available_parallelism},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{bounded, Receiver};
+use tracing::error;
+
+use crate::progress::Progress;
+
+/// How many workers there are at least, so that a long job doesn't hold up every other.
+const MIN_WORKERS: usize = 2;
+
+static SCHEDULER: LazyLock<Scheduler> = LazyLock::new(Scheduler::new);
+
+/// What a job does, which sets how urgent it is unless it's said otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Listing the entries of a folder or an archive being expanded in the browser.
+    Listing,
+    /// Going through the browser's roots to search them.
+    Indexing,
+    /// Searching what was found going through the browser's roots.
+    Searching,
+    Waveforms,
+    /// Reading the details of files, or finding their tempo and key.
+    Analysis,
+    Export,
+    Import,
+    /// Searching folders for the files of a project that were moved.
+    Relink,
+    /// Asking a web service for something, or downloading from it.
+    Network,
+    /// Running a Lua script over the project.
+    Script,
+}
+
+impl Kind {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Listing => "Listing",
+            Self::Indexing => "Indexing",
+            Self::Searching => "Searching",
+            Self::Waveforms => "Waveforms",
+            Self::Analysis => "Analysis",
+            Self::Export => "Export",
+            Self::Import => "Import",
+            Self::Relink => "Relink",
+            Self::Network => "Network",
+            Self::Script => "Script",
+        }
+    }
+
+    const fn priority(self) -> Priority {
+        match self {
+            Self::Listing | Self::Searching | Self::Export | Self::Import | Self::Script => Priority::High,
+            Self::Waveforms | Self::Analysis | Self::Relink | Self::Network => Priority::Normal,
+            Self::Indexing => Priority::Low,
+        }
+    }
+}
+
+/// How soon a job is started once a worker is free, before the jobs that were queued before it with a lower priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Normal,
+    /// Something the user is waiting on.
+    High,
+}
+
+/// A job to run in the background.
+pub struct Job {
+    kind: Kind,
+    description: String,
+    priority: Priority,
+}
+
+impl Job {
+    /// A job of `kind`, which is described by `description` in the list of tasks.
+    pub fn new(kind: Kind, description: impl Into<String>) -> Self {
+        Self { kind, description: description.into(), priority: kind.priority() }
+    }
+
+    #[must_use]
+    pub const fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// A job as it's listed while it's queued or running.
+struct Entry {
+    kind: Kind,
+    description: String,
+    priority: Priority,
+    progress: Arc<Progress>,
+    /// Whether the job checks whether its progress was cancelled, so that it can be cancelled while it runs.
+    cancellable: bool,
+    queued_at: Instant,
+    /// When a worker started the job, in nanoseconds since it was queued, or 0 while it waits.
+    started_after: AtomicU64,
+}
+
+/// A job of the list of tasks, as of when the list was taken.
+pub struct Task {
+    pub kind: Kind,
+    pub description: String,
+    pub priority: Priority,
+    pub progress: Arc<Progress>,
+    /// Whether the job can be cancelled with [`cancel`], which every job can while it waits for a worker.
+    pub cancellable: bool,
+    /// How long the job has been running, or [`None`] while it waits for a worker.
+    pub running: Option<Duration>,
+}
+
+struct Queued {
+    priority: Priority,
+    /// When the job was queued relative to the others, so that jobs of the same priority start in order.
+    order: u64,
+    entry: Arc<Entry>,
+    work: Box<dyn FnOnce(&Progress) + Send>,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+struct Scheduler {
+    queue: Mutex<BinaryHeap<Queued>>,
+    queued: Condvar,
+    /// Every job queued or running, along with the ones that ended since the list was last cleaned up.
+    entries: Mutex<Vec<Weak<Entry>>>,
+    order: AtomicU64,
+    started: AtomicBool,
+}
+
+impl Scheduler {
+    const fn new() -> Self {
+        Self {
+            queue: Mutex::new(BinaryHeap::new()),
+            queued: Condvar::new(),
+            entries: Mutex::new(Vec::new()),
+            order: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Start the workers, unless they were started already.
+    fn start_workers(&'static self) {
+        if self.started.swap(true, AtomicOrdering::Relaxed) {
+            return;
+        }
+        let workers = available_parallelism().map_or(MIN_WORKERS, |count| count.get().saturating_sub(1)).max(MIN_WORKERS);
+        for index in 0..workers {
+            let result = thread::Builder::new().name(format!("worker {index}")).spawn(move || self.work());
+            if let Err(error) = result {
+                error!("Couldn't start a worker: {error}");
+            }
+        }
+    }
+
+    /// Run jobs from the queue as they come, forever.
+    fn work(&self) {
+        loop {
+            let mut queue = self.queue.lock().unwrap();
+            let job = loop {
+                match queue.pop() {
+                    Some(job) => break job,
+                    None => queue = self.queued.wait(queue).unwrap(),
+                }
+            };
+            drop(queue);
+            #[allow(clippy::cast_possible_truncation, reason = "jobs don't wait for centuries")]
+            let waited = job.entry.queued_at.elapsed().as_nanos().max(1) as u64;
+            job.entry.started_after.store(waited, AtomicOrdering::Relaxed);
+            // A job that panics disconnects its receiver, but the worker goes on to the next one.
+            let Queued { entry, work, .. } = job;
+            if panic::catch_unwind(AssertUnwindSafe(|| work(&entry.progress))).is_err() {
+                error!("A {} job panicked: {}", entry.kind.name().to_lowercase(), entry.description);
+            }
+        }
+    }
+
+    fn queue(&'static self, job: Job, progress: Arc<Progress>, cancellable: bool, work: Box<dyn FnOnce(&Progress) + Send>) {
+        self.start_workers();
+        let entry = Arc::new(Entry {
+            kind: job.kind,
+            description: job.description,
+            priority: job.priority,
+            progress,
+            cancellable,
+            queued_at: Instant::now(),
+            started_after: AtomicU64::new(0),
+        });
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|entry| entry.strong_count() > 0);
+            entries.push(Arc::downgrade(&entry));
+        }
+        let order = self.order.fetch_add(1, AtomicOrdering::Relaxed);
+        self.queue.lock().unwrap().push(Queued { priority: job.priority, order, entry, work });
+        self.queued.notify_one();
+    }
+
+    /// Cancel the jobs of `progress`, taking the ones still queued off the queue.
+    fn cancel(&self, progress: &Progress) {
+        progress.cancel();
+        self.queue.lock().unwrap().retain(|queued| !ptr::eq(Arc::as_ptr(&queued.entry.progress), progress));
+    }
+}
+
+/// Run `work` in the background, whose result is sent once it's done. The receiver is disconnected if `work` panics.
+pub fn spawn<T: Send + 'static>(job: Job, work: impl FnOnce() -> T + Send + 'static) -> Receiver<T> {
+    let (tx, rx) = bounded(1);
+    SCHEDULER.queue(
+        job,
+        Arc::new(Progress::default()),
+        false,
+        Box::new(move |_| {
+            let _ = tx.send(work());
+        }),
+    );
+    rx
+}
+
+/// Run `work` in the background with `progress`, which it's given to say how far it got and to check whether it was cancelled, and whose other
+/// handles can show it. The result is sent once it's done.
+pub fn spawn_with_progress<T: Send + 'static>(job: Job, progress: Arc<Progress>, work: impl FnOnce(&Progress) -> T + Send + 'static) -> Receiver<T> {
+    let (tx, rx) = bounded(1);
+    SCHEDULER.queue(
+        job,
+        progress,
+        true,
+        Box::new(move |progress| {
+            let _ = tx.send(work(progress));
+        }),
+    );
+    rx
+}
+
+/// Cancel the jobs of `progress`, taking the ones waiting for a worker off the queue so that they never run and their receivers are disconnected.
+pub fn cancel(progress: &Progress) {
+    SCHEDULER.cancel(progress);
+}
+
+/// Return the jobs queued or running, running ones first, then by how urgent they are.
+pub fn list() -> Vec<Task> {
+    let mut tasks = SCHEDULER
+        .entries
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|entry| {
+            let started_after = entry.started_after.load(AtomicOrdering::Relaxed);
+            Task {
+                kind: entry.kind,
+                description: entry.description.clone(),
+                priority: entry.priority,
+                progress: Arc::clone(&entry.progress),
+                cancellable: entry.cancellable || started_after == 0,
+                running: (started_after > 0).then(|| entry.queued_at.elapsed().saturating_sub(Duration::from_nanos(started_after))),
+            }
+        })
+        .collect::<Vec<_>>();
+    tasks.sort_by_key(|task| (task.running.is_none(), Reverse(task.priority)));
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(priority: Priority, order: u64) -> Queued {
+        let entry = Entry {
+            kind: Kind::Analysis,
+            description: String::new(),
+            priority,
+            progress: Arc::new(Progress::default()),
+            cancellable: false,
+            queued_at: Instant::now(),
+            started_after: AtomicU64::new(0),
+        };
+        Queued { priority, order, entry: Arc::new(entry), work: Box::new(|_| {}) }
+    }
+
+    fn popped(scheduler: &Scheduler) -> Vec<(Priority, u64)> {
+        let mut queue = scheduler.queue.lock().unwrap();
+        std::iter::from_fn(|| queue.pop()).map(|queued| (queued.priority, queued.order)).collect()
+    }
+
+    #[test]
+    fn higher_priorities_come_first() {
+        assert!(queued(Priority::High, 5) > queued(Priority::Normal, 0));
+        assert!(queued(Priority::Normal, 9) > queued(Priority::Low, 1));
+    }
+
+    #[test]
+    fn earlier_jobs_of_the_same_priority_come_first() {
+        assert!(queued(Priority::Normal, 1) > queued(Priority::Normal, 2));
+        assert_eq!(queued(Priority::Low, 3).cmp(&queued(Priority::Low, 3)), Ordering::Equal);
+    }
+
+    #[test]
+    fn queue_pops_by_priority_then_order() {
+        let scheduler = Scheduler::new();
+        for (priority, order) in [(Priority::Low, 0), (Priority::Normal, 1), (Priority::High, 2), (Priority::Normal, 3), (Priority::High, 4)] {
+            scheduler.queue.lock().unwrap().push(queued(priority, order));
+        }
+        let expected = [(Priority::High, 2), (Priority::High, 4), (Priority::Normal, 1), (Priority::Normal, 3), (Priority::Low, 0)];
+        assert_eq!(popped(&scheduler), expected);
+    }
+
+    #[test]
+    fn cancelling_takes_queued_jobs_off_the_queue() {
+        let scheduler = Scheduler::new();
+        let cancelled = queued(Priority::Normal, 1);
+        let progress = Arc::clone(&cancelled.entry.progress);
+        for job in [queued(Priority::Normal, 0), cancelled, queued(Priority::Normal, 2)] {
+            scheduler.queue.lock().unwrap().push(job);
+        }
+        scheduler.cancel(&progress);
+        assert!(progress.is_cancelled());
+        assert!(Progress::is_over(&progress));
+        assert_eq!(popped(&scheduler), [(Priority::Normal, 0), (Priority::Normal, 2)]);
+    }
+}
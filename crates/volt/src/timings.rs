@@ -1,4 +1,12 @@
-use std::sync::{Mutex, Arc};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use egui::{pos2, vec2, Color32, Rect, Sense, Stroke};
 use lazy_static::lazy_static;
 
 pub fn now_ns() -> f64 {
@@ -12,6 +20,248 @@ pub fn ns_to_ms(ns: f64) -> f64 {
     ns / 1_000_000.0
 }
 
+/// Return how much memory the app takes up in bytes, or [`None`] if it can't be told on this system.
+pub fn resident_memory() -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?;
+        let kilobytes = line.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()?;
+        Some(kilobytes * 1024)
+    } else {
+        None
+    }
+}
+
+/// Write a number of bytes in the largest unit it's at least one of.
+pub fn format_bytes(bytes: u64) -> String {
+    #[allow(clippy::cast_precision_loss, reason = "only used for showing")]
+    let size = bytes as f64;
+    if size < 1024. * 1024. {
+        format!("{:.0} KB", size / 1024.)
+    } else if size < 1024. * 1024. * 1024. {
+        format!("{:.1} MB", size / 1024. / 1024.)
+    } else {
+        format!("{:.2} GB", size / 1024. / 1024. / 1024.)
+    }
+}
+
+/// How many of the latest frames are graphed.
+const FRAME_WINDOW: usize = 240;
+
+/// How long the latest frames took in nanoseconds from the start of one to the start of the next, oldest first.
+static FRAMES: Mutex<VecDeque<f64>> = Mutex::new(VecDeque::new());
+
+/// When the last frame started in nanoseconds, or 0 before the first one.
+static LAST_FRAME: Mutex<f64> = Mutex::new(0.);
+
+/// Record that a frame started at `now`, in nanoseconds.
+pub fn record_frame(now: f64) {
+    let last = std::mem::replace(&mut *LAST_FRAME.lock().unwrap(), now);
+    if last > 0. {
+        let mut frames = FRAMES.lock().unwrap();
+        if frames.len() == FRAME_WINDOW {
+            frames.pop_front();
+        }
+        frames.push_back(now - last);
+    }
+}
+
+/// Return the frame time under which `percentile` percent of `sorted` are.
+fn percentile(sorted: &[f64], percentile: usize) -> f64 {
+    sorted[(sorted.len() * percentile / 100).min(sorted.len() - 1)]
+}
+
+/// Graph the latest frames, with lines at the frame times of 60 and 30 fps, followed by their percentiles.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss, reason = "frame times and counts are well within range")]
+fn frames_ui(ui: &mut egui::Ui, accuracy: usize) {
+    let frames = FRAMES.lock().unwrap().clone();
+    let (rect, _) = ui.allocate_exact_size(vec2(FRAME_WINDOW as f32, 80.), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0., ui.visuals().extreme_bg_color);
+    // The graph fits the slowest frame, but always shows the 30 fps line.
+    let top = frames.iter().copied().fold(40_000_000., f64::max);
+    let y = |ns: f64| ((ns / top) as f32).mul_add(-rect.height(), rect.bottom());
+    for (fps, color) in [(60., Color32::from_rgb(0x5f, 0xb3, 0x6f)), (30., Color32::from_rgb(0xe3, 0xb3, 0x41))] {
+        let line = y(1_000_000_000. / fps);
+        painter.hline(rect.x_range(), line, Stroke::new(1., color.gamma_multiply(0.6)));
+        painter.text(pos2(rect.left() + 2., line - 1.), egui::Align2::LEFT_BOTTOM, format!("{fps} fps"), egui::FontId::monospace(9.), color);
+    }
+    // The newest frame is on the right, so the graph scrolls to the left as frames come in.
+    let left = rect.right() - frames.len() as f32;
+    for (i, frame) in frames.iter().enumerate() {
+        let x = left + i as f32;
+        let color = if *frame > 1_000_000_000. / 30. { ui.visuals().error_fg_color } else { ui.visuals().text_color() };
+        painter.rect_filled(Rect::from_x_y_ranges(x..=x + 1., y(*frame)..=rect.bottom()), 0., color);
+    }
+    if let Some(percentiles) = frame_percentiles(frames.into(), accuracy) {
+        ui.label(percentiles);
+    }
+}
+
+/// Describe the percentiles of the frame times in `frames`, or return [`None`] if there are none.
+fn frame_percentiles(mut frames: Vec<f64>, accuracy: usize) -> Option<String> {
+    frames.sort_by(f64::total_cmp);
+    let max = *frames.last()?;
+    Some(format!(
+        "frame p50: {:.accuracy$}ms, p95: {:.accuracy$}ms, p99: {:.accuracy$}ms, max: {:.accuracy$}ms",
+        ns_to_ms(percentile(&frames, 50)),
+        ns_to_ms(percentile(&frames, 95)),
+        ns_to_ms(percentile(&frames, 99)),
+        ns_to_ms(max),
+        accuracy = accuracy
+    ))
+}
+
+/// How many of the latest audio callbacks the worst case is taken over, which is a few seconds at usual buffer sizes.
+const AUDIO_WINDOW: usize = 256;
+
+/// The latest audio callbacks, kept without locking since they're recorded from the audio thread.
+struct AudioTimings {
+    /// How long each callback took in nanoseconds, as a ring starting at `next`.
+    durations: [AtomicU64; AUDIO_WINDOW],
+    /// How long the buffer each callback filled plays for in nanoseconds, which is how long it had.
+    budgets: [AtomicU64; AUDIO_WINDOW],
+    next: AtomicUsize,
+}
+
+static AUDIO_TIMINGS: AudioTimings = AudioTimings {
+    durations: [const { AtomicU64::new(0) }; AUDIO_WINDOW],
+    budgets: [const { AtomicU64::new(0) }; AUDIO_WINDOW],
+    next: AtomicUsize::new(0),
+};
+
+/// Record that an audio callback took `duration` to fill a buffer that plays for `budget`.
+pub fn record_audio_callback(duration: Duration, budget: Duration) {
+    let index = AUDIO_TIMINGS.next.fetch_add(1, Ordering::Relaxed) % AUDIO_WINDOW;
+    AUDIO_TIMINGS.durations[index].store(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX), Ordering::Relaxed);
+    AUDIO_TIMINGS.budgets[index].store(u64::try_from(budget.as_nanos()).unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// How long a callback took in nanoseconds, and how full it filled its buffer's time from 0 to 1, past which the audio drops out.
+#[derive(Debug, Clone, Copy, Default)]
+struct AudioCallback {
+    duration: f64,
+    load: f64,
+}
+
+impl AudioCallback {
+    #[allow(clippy::cast_precision_loss, reason = "callbacks take well under the range of an `f64`")]
+    fn at(index: usize) -> Self {
+        let duration = AUDIO_TIMINGS.durations[index % AUDIO_WINDOW].load(Ordering::Relaxed) as f64;
+        let budget = AUDIO_TIMINGS.budgets[index % AUDIO_WINDOW].load(Ordering::Relaxed) as f64;
+        Self {
+            duration,
+            load: if budget > 0. { duration / budget } else { 0. },
+        }
+    }
+}
+
+/// Return the latest audio callback and the worst one of the latest [`AUDIO_WINDOW`], or [`None`] if there are none yet.
+fn audio_callbacks() -> Option<(AudioCallback, AudioCallback)> {
+    let recorded = AUDIO_TIMINGS.next.load(Ordering::Relaxed);
+    let latest = AudioCallback::at(recorded.checked_sub(1)?);
+    let worst = (recorded.saturating_sub(AUDIO_WINDOW)..recorded).map(AudioCallback::at).fold(AudioCallback::default(), |worst, callback| {
+        if callback.load > worst.load {
+            callback
+        } else {
+            worst
+        }
+    });
+    Some((latest, worst))
+}
+
+/// Return how full the latest [`AUDIO_WINDOW`] audio callbacks filled their buffers' time on average and at worst, from 0 to 1, past which the audio
+/// drops out, or [`None`] if there are none yet.
+pub fn audio_load() -> Option<(f64, f64)> {
+    let recorded = AUDIO_TIMINGS.next.load(Ordering::Relaxed);
+    let callbacks = (recorded.saturating_sub(AUDIO_WINDOW)..recorded).map(AudioCallback::at).collect::<Vec<_>>();
+    let worst = callbacks.iter().map(|callback| callback.load).reduce(f64::max)?;
+    #[allow(clippy::cast_precision_loss, reason = "the window is small")]
+    let average = callbacks.iter().map(|callback| callback.load).sum::<f64>() / callbacks.len() as f64;
+    Some((average, worst))
+}
+
+/// Show the latest audio callback and the worst one of the latest [`AUDIO_WINDOW`], or that there are none yet.
+fn audio_timings_ui(ui: &mut egui::Ui, accuracy: usize) {
+    let Some((latest, worst)) = audio_callbacks() else {
+        ui.label("audio: no callbacks yet");
+        return;
+    };
+    ui.label(format!("audio: {:.accuracy$}ms, {:.0}% of the buffer", ns_to_ms(latest.duration), latest.load * 100., accuracy = accuracy));
+    let text = format!("audio worst: {:.accuracy$}ms, {:.0}% of the buffer", ns_to_ms(worst.duration), worst.load * 100., accuracy = accuracy);
+    // Callbacks that take longer than their buffer plays for make the audio drop out.
+    if worst.load >= 1. {
+        ui.colored_label(ui.visuals().error_fg_color, text);
+    } else if worst.load >= 0.7 {
+        ui.colored_label(ui.visuals().warn_fg_color, text);
+    } else {
+        ui.label(text);
+    }
+}
+
+/// Describe every timing as text, for bug reports.
+pub fn report() -> String {
+    let mut lines = all_timings().into_iter().map(|(name, time)| format!("{name}: {:.4}ms", ns_to_ms(time))).collect::<Vec<_>>();
+    lines.extend(frame_percentiles(FRAMES.lock().unwrap().iter().copied().collect(), 4));
+    if let Some((latest, worst)) = audio_callbacks() {
+        lines.push(format!("audio: {:.4}ms, {:.0}% of the buffer", ns_to_ms(latest.duration), latest.load * 100.));
+        lines.push(format!("audio worst: {:.4}ms, {:.0}% of the buffer", ns_to_ms(worst.duration), worst.load * 100.));
+    }
+    lines.extend(resident_memory().map(|bytes| format!("memory: {}", format_bytes(bytes))));
+    lines.join("\n")
+}
+
+/// Times the scope it's kept in, which is set with `set` once it's dropped.
+pub struct ScopedTimer {
+    start: f64,
+    set: fn(f64),
+}
+
+impl ScopedTimer {
+    pub fn new(set: fn(f64)) -> Self {
+        Self { start: now_ns(), set }
+    }
+}
+
+impl Drop for ScopedTimer {
+    fn drop(&mut self) {
+        (self.set)(now_ns() - self.start);
+    }
+}
+
+/// Show how long each part of the frame took, as a table that can be sorted by name or by time, along with the part of [`get_render_time`] each took.
+fn breakdown_ui(ui: &mut egui::Ui, mut timings: Vec<(&'static str, f64)>, accuracy: usize) {
+    let render = get_render_time();
+    ui.label(format!("render: {:.accuracy$}ms", ns_to_ms(render), accuracy = accuracy));
+    let id = ui.id().with("sort_by_name");
+    let mut by_name = ui.data(|data| data.get_temp::<bool>(id)).unwrap_or_default();
+    timings.retain(|(name, _)| *name != "render");
+    if by_name {
+        timings.sort_by_key(|(name, _)| *name);
+    } else {
+        timings.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    }
+    // What isn't timed on its own, like handling input and laying out the windows.
+    let other = render - timings.iter().map(|(_, time)| time).sum::<f64>();
+    egui::Grid::new(id).num_columns(3).striped(true).show(ui, |ui| {
+        if ui.selectable_label(by_name, "part").clicked() {
+            by_name = true;
+        }
+        if ui.selectable_label(!by_name, "time").clicked() {
+            by_name = false;
+        }
+        ui.label("share");
+        ui.end_row();
+        for (name, time) in timings.into_iter().chain([("other", other.max(0.))]) {
+            ui.label(name);
+            ui.label(format!("{:.accuracy$}ms", ns_to_ms(time), accuracy = accuracy));
+            ui.label(if render > 0. { format!("{:.0}%", time / render * 100.) } else { String::new() });
+            ui.end_row();
+        }
+    });
+    ui.data_mut(|data| data.insert_temp(id, by_name));
+}
+
 macro_rules! generate_timings {
     ($($name:ident),*) => {
         struct SharedTimings {
@@ -42,21 +292,30 @@ macro_rules! generate_timings {
             }
         )*
 
+        /// Return the name and the latest time of every timing.
+        fn all_timings() -> Vec<(&'static str, f64)> {
+            vec![$(paste::item! { (stringify!($name), [<get_ $name _time>]()) }),*]
+        }
+
+        /// Show the timings, followed by `add_contents`.
         #[allow(dead_code)]
-        pub fn show_timings(ctx: &egui::Context, window_name: &str, accuracy: usize) {
-            egui::Window::new(window_name)
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    $(
-                        paste::item! {
-                            ui.label(format!("{}: {:.accuracy$}ms", stringify!($name), ns_to_ms([<get_ $name _time>]()), accuracy = accuracy));
-                        }
-                    )*
-                });
+        pub fn timings_ui(ui: &mut egui::Ui, accuracy: usize, add_contents: impl FnOnce(&mut egui::Ui)) {
+            frames_ui(ui, accuracy);
+            breakdown_ui(ui, all_timings(), accuracy);
+            audio_timings_ui(ui, accuracy);
+            add_contents(ui);
+            // Frames are drawn continuously while the timings are shown, so that frame times show how fast they can be drawn rather than how often
+            // something changed.
+            ui.ctx().request_repaint();
         }
     };
 }
 
 generate_timings!(
-    render
+    render,
+    palette,
+    navbar,
+    browser,
+    central,
+    notifications
 );
\ No newline at end of file
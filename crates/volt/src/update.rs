@@ -0,0 +1,112 @@
+//! Checking whether a newer version of Volt was released on GitHub.
+//!
+//! The latest release is asked for with `curl`, like the Freesound API is, so that no HTTP client has to be built in.
+
+use std::{io::ErrorKind, process::Command};
+
+use crossbeam_channel::{bounded, Receiver};
+use egui::Context;
+use serde::Deserialize;
+
+use crate::tasks::{self, Job, Kind};
+
+const LATEST: &str = "https://api.github.com/repos/SharliBeicon/Volt/releases/latest";
+/// The version of this build.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A release of Volt on GitHub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub version: String,
+    /// The page of the release, where it can be downloaded from.
+    pub url: String,
+}
+
+/// What GitHub answers about a release.
+#[derive(Deserialize)]
+struct Latest {
+    tag_name: String,
+    html_url: Option<String>,
+}
+
+/// Ask GitHub for the latest release in the background, which is sent once it's newer than this build, or [`None`] if it isn't. `ctx` is repainted
+/// once the answer is sent, so that it doesn't have to be polled for.
+pub fn check(ctx: &Context) -> Receiver<Result<Option<Release>, String>> {
+    let (tx, rx) = bounded(1);
+    let ctx = ctx.clone();
+    let _ = tasks::spawn(Job::new(Kind::Network, "Checking for updates"), move || {
+        let result = latest().map(|release| newer(&release.version, VERSION).then_some(release));
+        let _ = tx.send(result.map_err(|error| format!("Couldn't check for updates, {error}")));
+        ctx.request_repaint();
+    });
+    rx
+}
+
+fn latest() -> Result<Release, String> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--header", "Accept: application/vnd.github+json", LATEST])
+        .output()
+        .map_err(|error| if error.kind() == ErrorKind::NotFound { "curl isn't installed".into() } else { error.to_string() })?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let latest: Latest = serde_json::from_slice(&output.stdout).map_err(|error| error.to_string())?;
+    Ok(Release {
+        version: latest.tag_name.trim_start_matches('v').to_string(),
+        url: latest.html_url.unwrap_or_else(|| "https://github.com/SharliBeicon/Volt/releases".into()),
+    })
+}
+
+/// Return whether `version` comes after `current`, comparing their numbers one by one. Pre-release suffixes like `-beta` are ignored.
+fn newer(version: &str, current: &str) -> bool {
+    let numbers = |version: &str| {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|number| number.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    let (mut version, mut current) = (numbers(version), numbers(current));
+    let length = version.len().max(current.len());
+    version.resize(length, 0);
+    current.resize(length, 0);
+    version > current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_numbers_one_by_one() {
+        assert!(newer("0.2.0", "0.1.9"));
+        assert!(newer("0.10.0", "0.9.0"));
+        assert!(newer("1.0.0", "0.99.99"));
+        assert!(!newer("0.1.0", "0.1.0"));
+        assert!(!newer("0.1.0", "0.2.0"));
+    }
+
+    #[test]
+    fn missing_numbers_are_zero() {
+        assert!(newer("0.1.1", "0.1"));
+        assert!(!newer("0.1", "0.1.0"));
+        assert!(!newer("0.1.0", "0.1"));
+    }
+
+    #[test]
+    fn tags_may_start_with_v() {
+        assert!(newer("v0.2.0", "0.1.0"));
+        assert!(!newer("v0.1.0", "0.1.0"));
+    }
+
+    #[test]
+    fn pre_release_suffixes_are_ignored() {
+        assert!(!newer("0.1.0-beta", "0.1.0"));
+        assert!(!newer("0.1.0", "0.1.0-beta"));
+        assert!(newer("0.2.0-rc.1", "0.1.0"));
+        assert!(!newer("0.1.0+build.5", "0.1.0"));
+    }
+}
@@ -1,41 +1,83 @@
+use std::time::Duration;
+
 use blerp::utils::zip;
-use egui::{hex_color, Color32, ColorImage};
+use egui::{hex_color, CentralPanel, Color32, ColorImage, Context, Response, RichText, Ui, ViewportBuilder, ViewportId, Window};
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::keymap::{self, Action};
 
 // Expose components
+pub mod about;
 pub mod browser;
 pub mod central;
 pub mod navbar;
 pub mod switch;
 pub mod notification;
+pub mod onboarding;
+pub mod palette;
 pub mod dialog;
+pub mod font;
+pub mod icon;
+pub mod relink;
 pub mod status;
+pub mod theme;
 
 // Theming
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ThemeColors {
+    #[serde(with = "theme::hex")]
     pub navbar_background_gradient_top: Color32,
+    #[serde(with = "theme::hex")]
     pub navbar_background_gradient_bottom: Color32,
+    #[serde(with = "theme::hex")]
     pub navbar_outline: Color32,
+    #[serde(with = "theme::hex")]
     pub navbar_widget: Color32,
+    #[serde(with = "theme::hex")]
     pub central_background: Color32,
+    #[serde(with = "theme::hex")]
     pub browser: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_outline: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_selected_button_fg: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_unselected_button_fg: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_unselected_hover_button_fg: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_invalid_name_bg: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_unselected_hover_button_fg_invalid: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_unselected_button_fg_invalid: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_folder_text: Color32,
+    #[serde(with = "theme::hex")]
     pub browser_folder_hover_text: Color32,
+    #[serde(with = "theme::hex")]
     pub playlist_bar: Color32,
+    #[serde(with = "theme::hex")]
     pub playlist_beat: Color32,
+    #[serde(with = "theme::hex")]
     pub bg_text: Color32,
+    #[serde(with = "theme::hex")]
     pub command_palette: Color32,
+    #[serde(with = "theme::hex")]
     pub command_palette_border: Color32,
+    #[serde(with = "theme::hex")]
     pub command_palette_text: Color32,
+    #[serde(with = "theme::hex")]
     pub command_palette_placeholder_text: Color32,
+    #[serde(with = "theme::hex")]
+    pub command_palette_invalid_text: Color32,
+    /// The color of monochrome icons, see [`icon`].
+    #[serde(with = "theme::hex")]
+    pub icon: Color32,
+    #[serde(with = "theme::hex")]
+    pub play_icon: Color32,
 }
 
 impl Default for ThemeColors {
@@ -63,10 +105,117 @@ impl Default for ThemeColors {
             command_palette_border: hex_color!("3d3b4b"),
             command_palette_text: hex_color!("928ea7"),
             command_palette_placeholder_text: hex_color!("928ea740"),
+            command_palette_invalid_text: hex_color!("f591b5"),
+            icon: Color32::WHITE,
+            play_icon: Color32::GREEN,
         }
     }
 }
 
+impl ThemeColors {
+    /// Return the default colors for a light interface.
+    pub fn light() -> Self {
+        Self {
+            navbar_background_gradient_top: hex_color!("f4f3fa"),
+            navbar_background_gradient_bottom: hex_color!("e8e7f1"),
+            navbar_outline: hex_color!("c6c2db"),
+            navbar_widget: hex_color!("ffffff80"),
+            central_background: hex_color!("eceaf4"),
+            browser: hex_color!("eceaf4"),
+            browser_outline: hex_color!("d5d2e5"),
+            browser_selected_button_fg: hex_color!("b36b00"),
+            browser_unselected_button_fg: hex_color!("6b7290"),
+            browser_unselected_hover_button_fg: hex_color!("464d6b"),
+            browser_invalid_name_bg: hex_color!("ff000010"),
+            browser_unselected_button_fg_invalid: hex_color!("a4506d"),
+            browser_unselected_hover_button_fg_invalid: hex_color!("c0306d"),
+            browser_folder_text: hex_color!("4f4b63"),
+            browser_folder_hover_text: hex_color!("1d1b2b"),
+            playlist_bar: hex_color!("a9a5c0"),
+            playlist_beat: hex_color!("d7d4e5"),
+            bg_text: hex_color!("8a8ea8"),
+            command_palette: hex_color!("f7f6fc"),
+            command_palette_border: hex_color!("c6c2db"),
+            command_palette_text: hex_color!("4f4b63"),
+            command_palette_placeholder_text: hex_color!("4f4b6360"),
+            command_palette_invalid_text: hex_color!("c0306d"),
+            icon: hex_color!("2e2b3f"),
+            play_icon: hex_color!("1f9d3a"),
+        }
+    }
+
+    /// Return the default colors for a dark interface if `dark_mode`, or for a light one.
+    pub fn for_mode(dark_mode: bool) -> Self {
+        if dark_mode {
+            Self::default()
+        } else {
+            Self::light()
+        }
+    }
+}
+
+/// Tooltips that end with the keyboard shortcut doing the same as the widget, so that shortcuts are shown the same way everywhere.
+pub trait Tooltip {
+    /// Show `text` when hovered, followed by `shortcut`.
+    fn on_hover_shortcut(self, text: &str, shortcut: &str) -> Self;
+
+    /// Show the description of `action` when hovered, followed by its shortcut in the keymap if it has one.
+    fn on_hover_action(self, action: Action) -> Self;
+}
+
+impl Tooltip for Response {
+    fn on_hover_shortcut(self, text: &str, shortcut: &str) -> Self {
+        self.on_hover_ui(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(text);
+                ui.label(RichText::new(shortcut).weak());
+            });
+        })
+    }
+
+    fn on_hover_action(self, action: Action) -> Self {
+        match keymap::hint(&self.ctx, action) {
+            Some(shortcut) => self.on_hover_shortcut(action.description(), &shortcut),
+            None => self.on_hover_text(action.description()),
+        }
+    }
+}
+
+/// Show `add_contents` in a window called `title` inside the app, with a button to pop it out into a window of the system. While it's `detached`, it's
+/// shown in a window of the system instead, which docks it back into the app when it's closed. `open` is cleared when the window inside the app is closed.
+pub fn detachable_window(ctx: &Context, title: &str, open: &mut bool, detached: &mut bool, add_contents: impl FnOnce(&mut Ui)) {
+    if *detached {
+        // The window is drawn along with the app, so what it shows is always in sync with it.
+        let mut add_contents = Some(add_contents);
+        ctx.show_viewport_immediate(ViewportId::from_hash_of(title), ViewportBuilder::default().with_title(title).with_inner_size([600., 400.]), |ctx, _| {
+            CentralPanel::default().show(ctx, |ui| {
+                if ui.small_button("Dock").on_hover_text("Show it inside the app again").clicked() {
+                    *detached = false;
+                }
+                if let Some(add_contents) = add_contents.take() {
+                    add_contents(ui);
+                }
+            });
+            if ctx.input(|input| input.viewport().close_requested()) {
+                *detached = false;
+            }
+        });
+    } else {
+        Window::new(title).open(open).show(ctx, |ui| {
+            if ui.small_button("Pop out").on_hover_text("Show it in a window of its own, which can be moved to another screen").clicked() {
+                *detached = true;
+            }
+            add_contents(ui);
+        });
+    }
+}
+
+/// Write `time` as minutes, seconds and milliseconds, like 1:05.250.
+pub fn format_time(time: Duration) -> String {
+    let (minutes, seconds) = (time.as_secs() / 60, time.as_secs() % 60);
+    format!("{minutes}:{seconds:02}.{:03}", time.subsec_millis())
+}
+
 // Gradient func
 pub fn build_gradient(height: usize, a: Color32, b: Color32) -> ColorImage {
     ColorImage::from_rgba_unmultiplied(
@@ -0,0 +1,110 @@
+use std::env::consts::{ARCH, OS};
+
+use crossbeam_channel::Receiver;
+use egui::{Button, CollapsingHeader, Context, Grid, RichText, ScrollArea, Spinner, Ui, Window};
+
+use crate::update::{self, Release};
+
+const REPOSITORY: &str = "https://github.com/SharliBeicon/Volt";
+/// The fonts bundled into Volt, with their licenses.
+const FONTS: [(&str, &str); 2] = [("Inter", include_str!("../fonts/inter/OFL.txt")), ("IBM Plex Mono", include_str!("../fonts/ibm-plex-mono/OFL.txt"))];
+/// The crates Volt is built with, as listed in its manifest when it was built.
+const CRATES: &[&str] = &include!(concat!(env!("OUT_DIR"), "/crates.rs"));
+
+/// A window showing the version of Volt, how it was built and the licenses of what's bundled into it, from which updates can be checked for.
+#[derive(Default)]
+pub struct About {
+    pub open: bool,
+    checking: Option<Receiver<Result<Option<Release>, String>>>,
+    /// The result of the last check, shown in the window.
+    checked: Option<Result<Option<Release>, String>>,
+    /// A newer release found since the last call to [`About::take_release`].
+    found: Option<Release>,
+}
+
+impl About {
+    /// Start checking for a newer release, unless a check is already going on.
+    pub fn check(&mut self, ctx: &Context) {
+        if self.checking.is_none() {
+            self.checking = Some(update::check(ctx));
+            self.checked = None;
+        }
+    }
+
+    /// Return the newer release found by a check since the last call, for the app to tell about it.
+    pub fn take_release(&mut self) -> Option<Release> {
+        if let Some(Ok(result)) = self.checking.as_ref().map(Receiver::try_recv) {
+            self.checking = None;
+            if let Ok(Some(release)) = &result {
+                self.found = Some(release.clone());
+            }
+            self.checked = Some(result);
+        }
+        self.found.take()
+    }
+
+    /// Show the window while it's open, where `check_at_startup` is whether updates are checked for whenever Volt starts.
+    pub fn window(&mut self, ctx: &Context, check_at_startup: &mut bool) {
+        let mut open = self.open;
+        Window::new("About Volt").open(&mut open).collapsible(false).default_width(420.).show(ctx, |ui| {
+            ui.heading(format!("Volt {}", update::VERSION));
+            ui.hyperlink(REPOSITORY);
+            ui.add_space(6.);
+            Grid::new("about build").num_columns(2).show(ui, |ui| {
+                ui.label("Build");
+                ui.label(if cfg!(debug_assertions) { "Debug" } else { "Release" });
+                ui.end_row();
+                ui.label("Platform");
+                ui.label(format!("{OS} {ARCH}"));
+                ui.end_row();
+                ui.label("Freesound");
+                ui.label(if cfg!(feature = "freesound") { "Built in" } else { "Not built in" });
+                ui.end_row();
+                ui.label("License");
+                ui.label("MPL-2.0");
+                ui.end_row();
+            });
+            ui.add_space(6.);
+            self.add_update(ui, check_at_startup);
+            ui.add_space(6.);
+            CollapsingHeader::new("Fonts").show(ui, |ui| {
+                for (name, license) in FONTS {
+                    CollapsingHeader::new(format!("{name}, SIL Open Font License 1.1")).show(ui, |ui| {
+                        ScrollArea::vertical().id_salt(name).max_height(200.).show(ui, |ui| ui.label(RichText::new(license).small().monospace()));
+                    });
+                }
+            });
+            CollapsingHeader::new("Crates").show(ui, |ui| {
+                ui.weak("The license of each crate is on its page.");
+                for name in CRATES {
+                    ui.hyperlink_to(*name, format!("https://crates.io/crates/{name}"));
+                }
+            });
+        });
+        self.open = open;
+    }
+
+    fn add_update(&mut self, ui: &mut Ui, check_at_startup: &mut bool) {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.checking.is_none(), Button::new("Check for updates")).clicked() {
+                self.check(ui.ctx());
+            }
+            if self.checking.is_some() {
+                ui.add(Spinner::new());
+            }
+            match &self.checked {
+                Some(Ok(Some(release))) => {
+                    ui.hyperlink_to(format!("Volt {} is out", release.version), &release.url);
+                }
+                Some(Ok(None)) => {
+                    ui.label("Volt is up to date");
+                }
+                Some(Err(error)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                None => {}
+            }
+        });
+        ui.checkbox(check_at_startup, "Check for updates when Volt starts").on_hover_text("Asks GitHub for the latest release, which needs curl");
+    }
+}
@@ -1,37 +1,63 @@
-use blerp::utils::zip;
+use blerp::{
+    device::{Device, DeviceEntry, DeviceHandler, Direction},
+    processing::{
+        analysis::{self, Analysis},
+        overview::Peak,
+        registry,
+    },
+    utils::zip,
+};
 use itertools::Itertools;
 use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use open::that_detached;
-use rodio::{Decoder, OutputStream, Sink, Source};
 use unicode_truncate::UnicodeTruncateStr;
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap, HashSet},
     f32::consts::FRAC_PI_2,
-    fs::{read_dir, File},
-    io::BufReader,
+    fs::{read_dir, rename},
     iter::Iterator,
-    ops::BitOr,
+    mem,
     path::{Path, PathBuf},
     rc::Rc,
-    str::FromStr,
     string::ToString,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, PoisonError},
     task::Poll,
-    thread::spawn,
     time::{Duration, Instant},
 };
 use strum::Display;
 use tap::Pipe;
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 
 use egui::{
-    emath::{self, TSTransform}, epaint::text::FontPriority, include_image, vec2, Button, Color32, Context, CursorIcon, DragAndDrop, DroppedFile, FontId, Id, Image, Label, LayerId, Margin, Order, Response, RichText, ScrollArea, Sense, Separator, Shape, Stroke, Ui, UiBuilder, Vec2, Widget
+    emath::{self, TSTransform}, hex_color, Align2, Rect, epaint::text::FontPriority, text::{LayoutJob, TextFormat}, vec2, Button, CollapsingHeader, ComboBox, Color32, Context, CursorIcon, DragAndDrop, DragValue, DroppedFile, FontId, Id, Key, Label, LayerId, Margin, Modifiers, Order, Response, RichText, ScrollArea, Sense, Separator, Shape, Slider, Stroke, TextEdit, Ui, UiBuilder, Vec2, Widget
 };
 
-use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+
+use crate::{
+    archive, config, controller,
+    engine::{PreviewBus, PreviewCommand, PreviewOptions, PreviewState},
+    midi,
+    progress::Progress,
+    tasks::{self, Job, Kind},
+    visual::{browser, central::NodeData, dialog, icon::Icon, ThemeColors},
+};
 
-use crate::visual::{browser, ThemeColors};
+mod collections;
+mod details;
+mod favorites;
+#[cfg(feature = "freesound")]
+mod freesound;
+pub mod lazy_cache;
+mod recent;
+mod roots;
+mod search;
+mod sort;
+mod tags;
+mod thumbnails;
+mod trash;
 
 // https://veykril.github.io/tlborm/decl-macros/building-blocks/counting.html#bit-twiddling
 macro_rules! count_tts {
@@ -64,6 +90,17 @@ enum_with_array! {
     pub enum Category {
         Files,
         Devices,
+        Plugins,
+    }
+}
+
+impl Category {
+    const fn description(self) -> &'static str {
+        match self {
+            Self::Files => "Audio and MIDI files in the folders picked",
+            Self::Devices => "The devices to play on and record from",
+            Self::Plugins => "The effects that can be added to the graph",
+        }
     }
 }
 
@@ -83,177 +120,482 @@ struct EntryData {
 pub enum EntryKind {
     Directory,
     Audio,
+    Midi,
     File,
 }
 
+/// The preview bus of the engine, as the browser sees it. Commands are queued for the engine, which reports back what it's playing every frame.
+#[derive(Default)]
 pub struct Preview {
-    pub path: Option<Arc<Path>>,
-    pub path_tx: Sender<Arc<Path>>,
-    pub file_data_rx: Receiver<PreviewData>,
-    pub file_data: Option<PreviewData>,
+    /// What the engine is playing or loading.
+    state: Option<PreviewState>,
+    /// How files are previewed from the next time one is played.
+    pub options: PreviewOptions,
+    pub bus: PreviewBus,
+    commands: Vec<PreviewCommand>,
 }
 
 impl Preview {
-    pub fn play_file(&mut self, path: Arc<Path>) {
-        self.path = Some(Arc::clone(&path));
-        self.path_tx.send(path).unwrap();
-        self.file_data = None;
+    /// Play the file at `path` from its start, or stop it if it's the file already playing.
+    pub fn play_file(&mut self, path: &Path) {
+        if self.of(path).is_some_and(|state| state.length.is_some()) {
+            self.stop();
+            return;
+        }
+        self.commands.push(PreviewCommand::Play(path.to_path_buf(), self.options));
+        // The engine reports the file as loading from the next frame on, but it's shown right away.
+        self.state = Some(PreviewState { path: path.to_path_buf(), position: Duration::ZERO, length: None, looping: self.options.looping });
     }
 
-    pub fn data(&mut self) -> Option<PreviewData> {
-        self.file_data = match self.file_data_rx.try_recv() {
-            Ok(data) => Some(data),
-            Err(_) => self.file_data,
-        };
-        if self.file_data.is_some_and(|data| data.length.is_some_and(|length| data.progress() > length)) {
-            self.path = None;
-            self.file_data = None;
+    /// Continue playing the file from `position`.
+    pub fn seek(&mut self, position: Duration) {
+        self.commands.push(PreviewCommand::Seek(position));
+        if let Some(state) = &mut self.state {
+            state.position = position;
         }
-        self.file_data
     }
-}
 
-#[derive(Clone, Copy)]
-pub struct PreviewData {
-    pub length: Option<Duration>,
-    pub started_playing: Instant,
-}
-
-impl PreviewData {
-    fn progress(&self) -> Duration {
-        self.started_playing.elapsed()
+    pub fn stop(&mut self) {
+        self.commands.push(PreviewCommand::Stop);
+        self.state = None;
     }
 
-    fn remaining(&self) -> Option<Duration> {
-        self.length.map(|length| length - self.progress())
+    /// Return the state of the preview if it's of `path`.
+    fn of(&self, path: &Path) -> Option<&PreviewState> {
+        self.state.as_ref().filter(|state| state.path == path)
     }
 
-    fn percentage(&self) -> Option<f32> {
-        self.length.map(|length| self.progress().as_secs_f32() / length.as_secs_f32())
+    fn path(&self) -> Option<&Path> {
+        self.state.as_ref().map(|state| state.path.as_path())
     }
 }
 
 pub struct Browser {
     selected_category: Category,
+    /// The folders listed in the files category, in order, which are saved between sessions along with the expanded folders.
     open_paths: Vec<PathBuf>,
     expanded_paths: Vec<Arc<Path>>,
+    /// How far the files are scrolled down, and whether it was just restored and has to be applied to the scroll area.
+    scroll: (f32, bool),
+    /// The folder dialog being shown to add a root, if there is one.
+    picking_root: Option<Receiver<Option<PathBuf>>>,
+    /// The audio entries selected, in the order they were picked, which are dragged together. Ctrl adds entries to the selection and shift adds a range.
+    selection: Vec<Arc<Path>>,
+    /// The entries listed in the last frame, in order, to select ranges of the audio entries among them.
+    shown: Rows,
     preview: Preview,
     theme: Rc<ThemeColors>,
     cached_entries: FsWatcherCache<CachedEntries>,
-    cached_entry_kinds: Arc<RwLock<FsWatcherCache<EntryKind>>>,
+    /// The entries under the roots as of the last time they were listed, which are only listed again once something changes.
+    flattened: Option<Flattened>,
+    subtrees: Subtrees,
+    /// The entries shown as of the last time something was filtered out, which are only filtered again once something changes.
+    filtered: Option<Filtered>,
+    entry_kinds: EntryKinds,
+    /// How the entries are ordered in each category, by the index of the category.
+    sorts: [sort::Sort; Category::VARIANTS.len()],
+    /// Files and folders shown above the roots, which are saved between sessions.
+    favorites: Vec<PathBuf>,
+    /// Files previewed or dropped onto the playlist lately, most recent first, which are saved between sessions.
+    recent: Vec<PathBuf>,
+    /// Text that the names of the entries shown have to match, or nothing to show every entry.
+    filter: String,
+    tags: tags::Tags,
+    /// Tags that the entries shown have to have, on top of matching the filter.
+    tag_filter: BTreeSet<String>,
+    /// The tag being typed in the menu of an entry.
+    new_tag: String,
+    /// The entry being renamed from its menu, along with the name typed so far.
+    renaming: Option<(Arc<Path>, String)>,
+    index: search::Index,
+    thumbnails: lazy_cache::LazyCache<Arc<[Peak]>>,
+    details: lazy_cache::LazyCache<details::Details>,
+    midi_details: lazy_cache::LazyCache<details::MidiDetails>,
+    /// The tempo and key of audio files, which are only worked out for the files shown with the analysis columns or the analysis filter.
+    analyses: lazy_cache::LazyCache<Analysis>,
+    analysis_filter: details::AnalysisFilter,
+    /// The details shown next to audio entries, in order.
+    columns: Vec<details::Column>,
+    /// The results of searching the index for the filter.
+    results: search::Search<(Entry, Vec<usize>)>,
+    /// The files found by the last search from the command palette.
+    quick_open_results: search::Search<(Arc<Path>, Vec<usize>)>,
+    /// Description of an edit made since the last call to [`Browser::take_edit`].
+    edit: Option<String>,
+    /// What went wrong since the last call to [`Browser::take_errors`].
+    errors: Vec<String>,
+    /// The audio devices found, which are only looked for once the devices are shown.
+    devices: Option<Vec<DeviceEntry>>,
+    /// The name of the output device being played through, if there is one.
+    active_output: Option<String>,
+    /// The name of the capture device picked, or [`None`] if the default one is used.
+    active_input: Option<String>,
+    /// A device picked since the last call to [`Browser::take_picked_device`].
+    picked_device: Option<Device>,
+    /// The MIDI input ports found, which are looked for along with the audio devices.
+    midi_ports: Vec<String>,
+    /// The names of the MIDI input ports connected to.
+    active_midi_ports: Vec<String>,
+    /// A MIDI input port picked since the last call to [`Browser::take_picked_midi_port`].
+    picked_midi_port: Option<String>,
+    /// A file double-clicked to edit it since the last call to [`Browser::take_opened_in_editor`].
+    opened_in_editor: Option<PathBuf>,
+    /// The project tempo in BPM.
+    tempo: f64,
+    collections: Vec<collections::Collection>,
+    /// The files of each collection, which are searched for again whenever the index or the collections change.
+    collected: search::Search<Vec<PathBuf>>,
+    #[cfg(feature = "freesound")]
+    freesound: freesound::Freesound,
 }
 
+/// Entries in the order they're shown, with the indices of the characters of their names that match the search.
+type Rows = Rc<[(Entry, Vec<usize>)]>;
+
+/// An entry of a folder or an archive, as it's listed.
+type Listed = (EntryKind, Arc<Path>);
+
+/// The expanded folders among the entries of a folder or an archive, along with the index of their entry, whose subtrees are shown right after it.
+type Expanded = Rc<[(usize, Arc<Path>)]>;
+
 struct CachedEntries {
-    rx: Receiver<Vec<(EntryKind, Arc<Path>)>>,
-    data: Poll<Vec<(EntryKind, Arc<Path>)>>,
+    rx: Receiver<Vec<Listed>>,
+    /// Shared so that listing a huge folder again doesn't copy it.
+    data: Poll<Arc<[Listed]>>,
+}
+
+/// The entries under the roots, flattened in the order they're shown, along with what they were listed from.
+struct Flattened {
+    sort: sort::Sort,
+    roots: Vec<PathBuf>,
+    expanded: Vec<Arc<Path>>,
+    /// The revisions of the caches of listings and of entry kinds.
+    revisions: (u64, u64),
+    entries: Rows,
+    /// Whether some entries are still being listed, so that the list has to be built again until they are.
+    pending: bool,
+}
+
+/// The entries listed in an expanded folder or archive, along with where the expanded folders among them are.
+struct Subtree {
+    listed: Arc<[Listed]>,
+    /// The expanded folders that were looked for among the listed entries, in order.
+    wanted: Vec<Arc<Path>>,
+    /// The expanded folders found among the listed entries.
+    expanded: Expanded,
+}
+
+/// How long building the list of entries in the browser may take, as it's done while a frame is drawn.
+const BUDGET: Duration = Duration::from_millis(4);
+
+/// The subtrees of the expanded folders by path, kept between listings so that only the folders that changed are gone through again.
+#[derive(Default)]
+struct Subtrees {
+    data: HashMap<Arc<Path>, Subtree>,
+}
+
+impl Subtrees {
+    /// Return the entries under `roots` in the order they're shown, going through the folders that are `expanded`.
+    fn flatten(
+        &mut self,
+        roots: &[PathBuf],
+        expanded: &[Arc<Path>],
+        sort: sort::Sort,
+        cached_entries: &mut FsWatcherCache<CachedEntries>,
+        entry_kinds: &mut EntryKinds,
+    ) -> Vec<Entry> {
+        // Folders are found among the entries of their parent, so only the folders that have expanded ones in them are looked through.
+        let mut by_parent = expanded.iter().filter_map(|path| Some((path.parent()?, Arc::clone(path)))).into_group_map();
+        for paths in by_parent.values_mut() {
+            paths.sort();
+        }
+        let mut entries = Vec::new();
+        for root in roots {
+            // Roots whose kind isn't known yet are shown as loading, but are still listed if they're expanded.
+            let data = entry_kinds.get(root).map(|kind| EntryData { path: Arc::from(root.as_path()), kind });
+            entries.push(Entry { data, depth: 0 });
+            if expanded.iter().any(|path| **path == **root) {
+                self.extend(&mut entries, &Arc::from(root.as_path()), 1, sort, cached_entries, &by_parent);
+            }
+        }
+        entries
+    }
+
+    /// Add the entries under the folder or archive at `path` whose entries are `depth` deep to `entries`, going through the folders in it that are
+    /// expanded, which are listed `by_parent`. The entries are made right into `entries`, so that each of them is only made once.
+    fn extend(
+        &mut self,
+        entries: &mut Vec<Entry>,
+        path: &Arc<Path>,
+        depth: usize,
+        sort: sort::Sort,
+        cached_entries: &mut FsWatcherCache<CachedEntries>,
+        by_parent: &HashMap<&Path, Vec<Arc<Path>>>,
+    ) {
+        let Some((listed, expanded)) = self.get(path, sort, cached_entries, by_parent) else {
+            entries.push(Entry { data: Poll::Pending, depth });
+            return;
+        };
+        let entry = |(kind, path): &Listed| Entry { data: Poll::Ready(EntryData { path: Arc::clone(path), kind: *kind }), depth };
+        let mut start = 0;
+        for (index, folder) in expanded.iter() {
+            entries.extend(listed[start..=*index].iter().map(entry));
+            self.extend(entries, folder, depth + 1, sort, cached_entries, by_parent);
+            start = index + 1;
+        }
+        entries.extend(listed[start..].iter().map(entry));
+    }
+
+    /// Return the entries listed in the folder or archive at `path` along with the expanded folders among them, which are listed `by_parent`, or
+    /// [`None`] while it's being listed. It's only gone through again if its entries or the expanded folders in it changed.
+    fn get(
+        &mut self,
+        path: &Arc<Path>,
+        sort: sort::Sort,
+        cached_entries: &mut FsWatcherCache<CachedEntries>,
+        by_parent: &HashMap<&Path, Vec<Arc<Path>>>,
+    ) -> Option<(Arc<[Listed]>, Expanded)> {
+        let CachedEntries { data, rx } = Browser::list_cached(path, sort, cached_entries);
+        if data.is_pending() {
+            match rx.try_recv() {
+                Ok(list) => *data = Poll::Ready(Arc::from(list)),
+                Err(TryRecvError::Disconnected) => *data = Poll::Ready(Arc::from([])),
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        let Poll::Ready(listed) = data else {
+            return None;
+        };
+        let listed = Arc::clone(listed);
+        let wanted = by_parent.get(&**path).map_or(&[][..], Vec::as_slice);
+        if let Some(subtree) = self.data.get(&**path).filter(|subtree| Arc::ptr_eq(&subtree.listed, &listed) && subtree.wanted == wanted) {
+            return Some((listed, Rc::clone(&subtree.expanded)));
+        }
+        // The expanded folders are sorted like paths, which is how they're looked for.
+        let expanded = listed
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, entry))| wanted.binary_search(entry).is_ok())
+            .map(|(index, (_, entry))| (index, Arc::clone(entry)))
+            .collect::<Rc<[_]>>();
+        self.data.insert(Arc::clone(path), Subtree { listed: Arc::clone(&listed), wanted: wanted.to_vec(), expanded: Rc::clone(&expanded) });
+        Some((listed, expanded))
+    }
+
+    /// Forget the subtrees of the folders that aren't `expanded` anymore.
+    fn retain(&mut self, expanded: &[Arc<Path>]) {
+        let expanded = expanded.iter().map(AsRef::as_ref).collect::<HashSet<&Path>>();
+        self.data.retain(|path, _| expanded.contains(&**path));
+    }
 }
 
+/// The entries shown while something is filtered out, along with what they were filtered from.
+struct Filtered {
+    filter: String,
+    tag_filter: BTreeSet<String>,
+    analysis_filter: details::AnalysisFilter,
+    /// The entries under the roots, which are searched until the index is built.
+    flattened: Rows,
+    /// The revisions of the search results, of the tags and of the analyses.
+    revisions: (u64, u64, u64),
+    entries: Rows,
+    /// Whether the kinds of some entries are still being found, so that they have to be filtered again until they are.
+    pending: bool,
+}
+
+/// Data about paths that's dropped once they change on disk, so that it's found again.
 struct FsWatcherCache<T> {
     data: HashMap<PathBuf, T>,
-    watcher: RecommendedWatcher,
+    /// How many times data was dropped because it changed, to find out whether what was built from it is out of date.
+    revision: u64,
+    /// Shared with the jobs that fill the cache, which watch the paths they stat so that the UI thread never touches the disk. [`None`] if changes
+    /// can't be watched for, in which case the data is kept until Volt restarts.
+    watcher: Arc<Mutex<Option<RecommendedWatcher>>>,
     rx: Receiver<notify::Result<Event>>,
 }
 
 impl<T> Default for FsWatcherCache<T> {
     fn default() -> Self {
         let (tx, rx) = unbounded();
+        let watcher = recommended_watcher(tx).inspect_err(|error| error!("Couldn't watch for changes on disk: {error}")).ok();
 
         Self {
             data: HashMap::new(),
-            watcher: recommended_watcher(tx).unwrap(),
+            revision: 0,
+            watcher: Arc::new(Mutex::new(watcher)),
             rx,
         }
     }
 }
 
+impl<T> FsWatcherCache<T> {
+    /// Drop the data of the paths that changed since the last call, along with that of their parents. Whether a path is a folder isn't checked, as
+    /// that would stat it.
+    fn forget_changed(&mut self) {
+        for event in self.rx.try_iter() {
+            let Ok(event) = event.inspect_err(|error| error!("Couldn't watch for changes: {error}")) else {
+                continue;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in event.paths.iter().flat_map(|path| [Some(path.as_path()), path.parent()]).flatten() {
+                trace!("invalidating cache for {:?}", path);
+                if self.data.remove(path).is_some() {
+                    self.revision += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Watch `path` for changes with `watcher`, if there is one, from a job.
+fn watch(watcher: &Mutex<Option<RecommendedWatcher>>, path: &Path) {
+    // A job that panicked while watching leaves the watcher as it was.
+    let watch_result = watcher.lock().unwrap_or_else(PoisonError::into_inner).as_mut().map(|watcher| watcher.watch(path, RecursiveMode::NonRecursive));
+    if let Some(Err(error)) = watch_result {
+        error!("Unexpected error while trying to watch directory: {:?}", error);
+    }
+}
+
+/// The kinds of the entries shown outside of folder listings, like the roots and tagged files, which are found in the background.
+struct EntryKinds {
+    cache: FsWatcherCache<Poll<EntryKind>>,
+    found_tx: Sender<(PathBuf, EntryKind)>,
+    found_rx: Receiver<(PathBuf, EntryKind)>,
+}
+
+impl Default for EntryKinds {
+    fn default() -> Self {
+        let (found_tx, found_rx) = unbounded();
+        Self { cache: FsWatcherCache::default(), found_tx, found_rx }
+    }
+}
+
+impl EntryKinds {
+    /// Drop the kinds of the paths that changed, and keep the ones found since the last call.
+    fn refresh(&mut self) {
+        self.cache.forget_changed();
+        for (path, kind) in self.found_rx.try_iter() {
+            // The kinds of paths that changed in the meantime were forgotten, and are being found again.
+            if let Some(entry @ Poll::Pending) = self.cache.data.get_mut(&path) {
+                *entry = Poll::Ready(kind);
+            }
+        }
+    }
+
+    /// Return the kind of the entry at `path`, or [`Poll::Pending`] while it's being found.
+    fn get(&mut self, path: &Path) -> Poll<EntryKind> {
+        self.refresh();
+        *self.cache.data.entry(path.to_path_buf()).or_insert_with(|| {
+            trace!("entry kind cache miss for {:?}", path);
+            let (path, tx, watcher) = (path.to_path_buf(), self.found_tx.clone(), Arc::clone(&self.cache.watcher));
+            let _ = tasks::spawn(Job::new(Kind::Listing, path.display().to_string()), move || {
+                // Files in archives can't be watched, and never change as long as their archive doesn't.
+                if archive::split(&path).is_none() {
+                    watch(&watcher, path.parent().unwrap_or(&path));
+                }
+                let kind = Browser::entry_kind_of(&path);
+                let _ = tx.send((path, kind));
+            });
+            Poll::Pending
+        })
+    }
+}
+
 impl Browser {
     const ENTRY_HEIGHT: f32 = 20.;
+    /// How many of the best matches of a search are shown.
+    const MAX_RESULTS: usize = 500;
+    /// How many of the best matches of a search from the command palette are listed.
+    const MAX_QUICK_OPEN_RESULTS: usize = 8;
 
     pub fn new(theme: Rc<ThemeColors>) -> Self {
-        Self {
+        let session = roots::load();
+        let mut browser = Self {
             selected_category: Category::Files,
-            open_paths: vec![PathBuf::from_str("/").unwrap()],
-            expanded_paths: Vec::new(),
-            preview: {
-                let (path_tx, path_rx) = unbounded();
-                let (file_data_tx, file_data_rx) = unbounded();
-                // FIXME: Temporary rodio playback, might need to use cpal or make rodio proper
-                spawn(move || {
-                    let (_stream, handle) = OutputStream::try_default().unwrap();
-                    let sink = Sink::try_new(&handle).unwrap();
-                    let mut last_path = None;
-                    loop {
-                        let Ok(path) = path_rx.recv() else {
-                            break;
-                        };
-                        let source = Decoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
-                        let empty = sink.empty();
-                        sink.stop();
-                        if last_path.is_none_or(|last_path| last_path != path) || empty {
-                            file_data_tx
-                                .send(PreviewData {
-                                    length: source.total_duration(),
-                                    started_playing: Instant::now(),
-                                })
-                                .unwrap();
-                            sink.append(source);
-                        }
-                        last_path = Some(path);
-                    }
-                });
-                Preview {
-                    path_tx,
-                    file_data_rx,
-                    path: None,
-                    file_data: None,
-                }
-            },
+            open_paths: session.roots,
+            expanded_paths: session.expanded.into_iter().map(Arc::from).collect(),
+            scroll: (session.scroll, true),
+            picking_root: None,
+            selection: Vec::new(),
+            shown: Rc::new([]),
+            preview: Preview::default(),
             theme,
             cached_entries: FsWatcherCache::default(),
-            cached_entry_kinds: Arc::new(RwLock::new(FsWatcherCache::default())),
-        }
+            flattened: None,
+            subtrees: Subtrees::default(),
+            filtered: None,
+            entry_kinds: EntryKinds::default(),
+            sorts: [sort::Sort::default(); Category::VARIANTS.len()],
+            favorites: favorites::load(),
+            recent: recent::load(),
+            filter: String::new(),
+            tags: tags::Tags::load(),
+            tag_filter: BTreeSet::new(),
+            new_tag: String::new(),
+            renaming: None,
+            index: search::Index::new(),
+            thumbnails: thumbnails::cache(),
+            details: details::cache(),
+            midi_details: details::midi_cache(),
+            analyses: details::analysis_cache(),
+            analysis_filter: details::AnalysisFilter::default(),
+            columns: vec![details::Column::Duration],
+            results: search::Search::default(),
+            quick_open_results: search::Search::default(),
+            edit: None,
+            errors: Vec::new(),
+            devices: None,
+            active_output: None,
+            active_input: None,
+            picked_device: None,
+            midi_ports: Vec::new(),
+            active_midi_ports: Vec::new(),
+            picked_midi_port: None,
+            opened_in_editor: None,
+            tempo: 120.,
+            collections: collections::load(),
+            collected: search::Search::default(),
+            #[cfg(feature = "freesound")]
+            freesound: freesound::Freesound::new(),
+        };
+        browser.index.set_roots(&browser.open_paths);
+        browser
     }
 
-    fn entry_kind_of(path: impl AsRef<Path>, cached_entry_kinds: &mut FsWatcherCache<EntryKind>) -> EntryKind {
-        let path = path.as_ref();
-        for event in cached_entry_kinds.rx.try_iter() {
-            let event = event.unwrap();
-            match event.kind {
-                EventKind::Access(_) => {}
-                _ => {
-                    for path in event.paths.iter().map(|path| if path.is_dir() { path } else { path.parent().unwrap() }) {
-                        trace!("invalidating entry kind cache for {:?}", path);
-                        cached_entry_kinds.data.remove(path);
-                    }
-                }
-            }
+    /// Return the kind of the entry at `path`, which stats it, so it's only called in jobs.
+    fn entry_kind_of(path: &Path) -> EntryKind {
+        if archive::split(path).is_some() {
+            Self::file_kind(path)
+        } else if path.is_dir() || archive::is_archive(path) {
+            EntryKind::Directory
+        } else {
+            Self::file_kind(path)
         }
+    }
 
-        *cached_entry_kinds.data.entry(path.to_path_buf()).or_insert_with(|| {
-            let watch_result = cached_entry_kinds.watcher.watch(path.parent().unwrap_or(path), RecursiveMode::NonRecursive);
-            if let Err(error) = watch_result {
-                error!("Unexpected error while trying to watch directory: {:?}", error);
-            };
-            trace!("entry kind cache miss for {:?}", path);
-            if path.is_dir() {
-                EntryKind::Directory
+    /// Return whether the file at `path` is audio or MIDI, going by its extension.
+    fn file_kind(path: &Path) -> EntryKind {
+        path.extension().and_then(|ext| ext.to_str()).map_or(EntryKind::File, |extension| {
+            const AUDIO_EXTENSIONS: [&str; 6] = ["flac", "mp3", "ogg", "opus", "wav", "wave"];
+            if AUDIO_EXTENSIONS.into_iter().any(|other| other.eq_ignore_ascii_case(extension)) {
+                EntryKind::Audio
+            } else if midi::is_midi(path) {
+                EntryKind::Midi
             } else {
-                path.extension().and_then(|ext| ext.to_str()).map_or(EntryKind::File, |extension| {
-                    const AUDIO_EXTENSIONS: [&str; 6] = ["flac", "mp3", "ogg", "opus", "wav", "wave"];
-                    if AUDIO_EXTENSIONS.into_iter().any(|other| other.eq_ignore_ascii_case(extension)) {
-                        EntryKind::Audio
-                    } else {
-                        EntryKind::File
-                    }
-                })
+                EntryKind::File
             }
         })
     }
 
     // Animations
-    fn loading(ui: &mut Ui) -> Response {
+    fn loading(ui: &mut Ui, theme: &ThemeColors) -> Response {
+        if config::reduced_motion() {
+            return ui.add_sized(vec2(16., 16.), Icon::Loading.image(theme));
+        }
         #[allow(clippy::cast_possible_truncation, reason = "this is a visual effect")]
-        let rotated = Image::new(include_image!("../images/icons/loading.png")).rotate(ui.input(|i| i.time * 6.0) as f32, vec2(0.5, 0.5));
+        let rotated = Icon::Loading.image(theme).rotate(ui.input(|i| i.time * 6.0) as f32, vec2(0.5, 0.5));
         ui.ctx().request_repaint();
         ui.add_sized(vec2(16., 16.), rotated)
     }
@@ -296,13 +638,65 @@ impl Browser {
         }
     }
 
-    fn add_files(&mut self, ui: &mut Ui, scroll_area: ScrollArea, browser_width: f32) -> Response {
+    fn add_files(&mut self, ui: &mut Ui, mut scroll_area: ScrollArea, browser_width: f32) -> Response {
         self.handle_file_or_folder_drop(ui.ctx());
-        let entries = self.open_paths.iter().fold(Vec::new(), |mut entries, path| {
-            Self::entries(&mut entries, path, 0, &mut self.cached_entries, &self.cached_entry_kinds, &self.expanded_paths);
-            entries
-        });
-        scroll_area
+        if let Some(Ok(picked)) = self.picking_root.as_ref().map(Receiver::try_recv) {
+            self.picking_root = None;
+            if let Some(path) = picked.filter(|path| !self.open_paths.contains(path)) {
+                self.open_paths.push(path);
+                self.index.set_roots(&self.open_paths);
+                self.edit = Some("Add browser root".into());
+            }
+        }
+        let filter_response = ui.add(TextEdit::singleline(&mut self.filter).hint_text("Search").desired_width(browser_width - 16.));
+        self.add_tag_filter(ui);
+        if (!self.filter.trim().is_empty() || !self.tag_filter.is_empty()) && ui.small_button("Save as collection").on_hover_text("Keep this search as a folder that's always up to date").clicked() {
+            let name = if self.filter.trim().is_empty() { self.tag_filter.iter().join(", ") } else { self.filter.trim().to_string() };
+            self.collections.push(collections::Collection {
+                name,
+                query: self.filter.trim().to_string(),
+                tags: self.tag_filter.clone(),
+                tempo: None,
+            });
+            collections::save(&self.collections);
+            self.collected.refresh();
+        }
+        self.add_list_options(ui);
+        if self.filter.trim().is_empty() && self.tag_filter.is_empty() {
+            if !self.recent.is_empty() {
+                self.add_pinned(ui, "Recent", &self.recent.clone(), false, browser_width);
+            }
+            if !self.favorites.is_empty() {
+                self.add_pinned(ui, "Favorites", &self.favorites.clone(), true, browser_width);
+            }
+            if !self.collections.is_empty() {
+                self.add_collections(ui, browser_width);
+            }
+            #[cfg(feature = "freesound")]
+            self.add_freesound(ui);
+        }
+        let flattened = self.flattened();
+        let entries = if self.filter.trim().is_empty() && self.tag_filter.is_empty() && self.analysis_filter.is_empty() {
+            // Nothing is filtered out, so the list is shown as it is instead of being copied.
+            flattened
+        } else {
+            self.filtered(ui.ctx(), flattened)
+        };
+        self.shown = Rc::clone(&entries);
+        if filter_response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+            let top_result = entries.iter().find_map(|(entry, _)| match &entry.data {
+                Poll::Ready(EntryData { path, kind: EntryKind::Audio | EntryKind::Midi }) => Some(Arc::clone(path)),
+                _ => None,
+            });
+            if let Some(path) = top_result {
+                self.preview_file(&path);
+            }
+        }
+        if self.scroll.1 {
+            scroll_area = scroll_area.vertical_scroll_offset(self.scroll.0);
+            self.scroll.1 = false;
+        }
+        let output = scroll_area
             .show_rows(ui, Self::ENTRY_HEIGHT, entries.len(), |ui, row_range| {
                 egui::Frame::default()
                     .inner_margin(Margin::same(8.))
@@ -311,112 +705,253 @@ impl Browser {
                             ui.visuals_mut().widgets.noninteractive.fg_stroke.color = self.theme.browser_folder_text;
                             ui.visuals_mut().widgets.hovered.fg_stroke.color = self.theme.browser_folder_hover_text;
                             ui.style_mut().spacing.item_spacing.x = 4.;
-                            let entries_iter = entries.into_iter();
-                            for entry in entries_iter.skip(row_range.start).take(row_range.len()+8) {
-                                self.add_entry(entry, ui, browser_width);
+                            let end = (row_range.end + 8).min(entries.len());
+                            for (entry, highlights) in &entries[row_range.start.min(end)..end] {
+                                self.add_entry(entry.clone(), highlights, ui, browser_width);
                             }
                         })
                     })
                     .response
-            })
-            .inner
+            });
+        self.scroll.0 = output.state.offset.y;
+        output.inner
     }
 
-    fn list_cached<'a>(path: &Path, cached_entries: &'a mut FsWatcherCache<CachedEntries>, cached_entry_kinds: &Arc<RwLock<FsWatcherCache<EntryKind>>>) -> &'a mut CachedEntries {
-        for event in cached_entries.rx.try_iter() {
-            let event = event.unwrap();
-            match event.kind {
-                EventKind::Access(_) => {}
-                _ => {
-                    for path in event.paths.iter().map(|path| if path.is_dir() { path } else { path.parent().unwrap() }) {
-                        trace!("invalidating cached entries cache for {:?}", path);
-                        cached_entries.data.remove(path);
+    /// Show a menu to add a root with the folder dialog of the system, and to remove the roots or move them up and down.
+    fn add_roots_menu(&mut self, ui: &mut Ui) {
+        ui.menu_button(RichText::new("Roots").size(11.), |ui| {
+            // The index of the root to move, and where to, or nowhere to remove it.
+            let mut edit = None;
+            for (index, root) in self.open_paths.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(index > 0, Button::new("⏶").small()).on_hover_text("Move up").clicked() {
+                        edit = Some((index, Some(index - 1)));
+                    }
+                    if ui.add_enabled(index + 1 < self.open_paths.len(), Button::new("⏷").small()).on_hover_text("Move down").clicked() {
+                        edit = Some((index, Some(index + 1)));
                     }
+                    if ui.small_button("🗙").on_hover_text("Remove").clicked() {
+                        edit = Some((index, None));
+                    }
+                    ui.label(root.display().to_string());
+                });
+            }
+            match edit {
+                Some((index, None)) => {
+                    self.open_paths.remove(index);
+                    self.edit = Some("Remove browser root".into());
+                }
+                Some((index, Some(other))) => {
+                    self.open_paths.swap(index, other);
+                    self.edit = Some("Reorder browser roots".into());
                 }
+                None => {}
+            }
+            if edit.is_some() {
+                self.index.set_roots(&self.open_paths);
             }
+            if !self.open_paths.is_empty() {
+                ui.separator();
+            }
+            let picking = self.picking_root.is_some();
+            if ui.add_enabled(!picking, Button::new(if picking { "Picking a folder…" } else { "Add folder…" })).clicked() {
+                self.picking_root = Some(dialog::pick_folder());
+                ui.close_menu();
+            }
+        })
+        .response
+        .on_hover_text("The folders listed in the browser");
+    }
+
+    /// Save the roots along with the expanded folders and the scroll position, to restore them in the next session.
+    pub fn save_session(&self) {
+        roots::save(&roots::Session {
+            roots: self.open_paths.clone(),
+            expanded: self.expanded_paths.iter().map(|path| path.to_path_buf()).collect(),
+            scroll: self.scroll.0,
+        });
+    }
+
+    /// Return the entries left once the filter, the tags picked and the analysis filter are applied to the `flattened` ones, which are only filtered
+    /// again once the filters or what they're applied to change.
+    fn filtered(&mut self, ctx: &Context, flattened: Rows) -> Rows {
+        let searching = !self.filter.trim().is_empty();
+        if searching && !self.index.is_empty() {
+            // The results come in in the background, and are only taken when they're asked for.
+            self.search(ctx);
+        }
+        let revisions = (self.results.revision(), self.tags.revision(), self.analyses.revision());
+        if let Some(filtered) = self.filtered.as_ref().filter(|filtered| {
+            !filtered.pending
+                && filtered.filter == self.filter
+                && filtered.tag_filter == self.tag_filter
+                && filtered.analysis_filter == self.analysis_filter
+                && filtered.revisions == revisions
+                && Rc::ptr_eq(&filtered.flattened, &flattened)
+        }) {
+            return Rc::clone(&filtered.entries);
         }
+        let mut entries = if searching {
+            if self.index.is_empty() {
+                Self::search_shown(flattened.iter().map(|(entry, _)| entry.clone()), &self.filter)
+            } else {
+                self.search(ctx).to_vec()
+            }
+        } else if !self.tag_filter.is_empty() {
+            self.tags
+                .tagged(&self.tag_filter)
+                .sorted_by_key(|path| path.file_name().map(|name| name.to_string_lossy().to_lowercase()))
+                .map(|path| {
+                    let data = self.entry_kinds.get(path).map(|kind| EntryData { path: Arc::from(path), kind });
+                    (Entry { data, depth: 0 }, Vec::new())
+                })
+                .collect_vec()
+        } else {
+            flattened.to_vec()
+        };
+        self.filter_entries(&mut entries);
+        let entries = Rc::<[_]>::from(entries);
+        self.filtered = Some(Filtered {
+            filter: self.filter.clone(),
+            tag_filter: self.tag_filter.clone(),
+            analysis_filter: self.analysis_filter,
+            flattened,
+            // Analyses that came in while filtering are in the entries already.
+            revisions: (revisions.0, revisions.1, self.analyses.revision()),
+            entries: Rc::clone(&entries),
+            pending: entries.iter().any(|(entry, _)| entry.data.is_pending()),
+        });
+        entries
+    }
+
+    /// Return the entries under the roots whose names match the filter, best first and out of their folders, along with the indices of the matching characters of their names.
+    /// The index is searched in the background, and the previous results are returned until it's done.
+    fn search(&mut self, ctx: &Context) -> &[(Entry, Vec<usize>)] {
+        let roots = self.open_paths.clone();
+        self.results
+            .results(ctx, &self.index, &self.filter, move |found| {
+                found
+                    .into_iter()
+                    .filter(|(path, ..)| roots.iter().any(|root| path.starts_with(root)))
+                    .sorted_by_key(|(.., found)| Reverse(found.score))
+                    .take(Self::MAX_RESULTS)
+                    .map(|(path, is_folder, found)| {
+                        let kind = if is_folder { EntryKind::Directory } else { Self::file_kind(&path) };
+                        let data = Poll::Ready(EntryData { path: Arc::from(path), kind });
+                        (Entry { data, depth: 0 }, found.indices)
+                    })
+                    .collect()
+            })
+    }
+
+    /// Return the audio and MIDI files under the roots whose names match `query`, best first, along with the indices of the matching characters of their names.
+    /// They're searched for in the background, and the previous results are returned until that's done.
+    pub fn quick_open(&mut self, ctx: &Context, query: &str) -> &[(Arc<Path>, Vec<usize>)] {
+        let roots = self.open_paths.clone();
+        self.quick_open_results.results(ctx, &self.index, query, move |found| {
+            found
+                .into_iter()
+                .filter(|(path, is_folder, _)| !is_folder && roots.iter().any(|root| path.starts_with(root)))
+                .filter(|(path, ..)| matches!(Self::file_kind(path), EntryKind::Audio | EntryKind::Midi))
+                .sorted_by_key(|(.., found)| Reverse(found.score))
+                .take(Self::MAX_QUICK_OPEN_RESULTS)
+                .map(|(path, _, found)| (Arc::from(path), found.indices))
+                .collect()
+        })
+    }
+
+    /// Return the entries whose names match `query`, best first and out of their folders, along with the indices of the matching characters of their names.
+    fn search_shown(entries: impl Iterator<Item = Entry>, query: &str) -> Vec<(Entry, Vec<usize>)> {
+        entries
+            .filter_map(|entry| {
+                let Poll::Ready(EntryData { path, .. }) = &entry.data else {
+                    return None;
+                };
+                let found = search::fuzzy_match(query, &path.file_name()?.to_string_lossy())?;
+                Some((found.score, Entry { depth: 0, ..entry }, found.indices))
+            })
+            .sorted_by_key(|(score, ..)| Reverse(*score))
+            .map(|(_, entry, indices)| (entry, indices))
+            .collect()
+    }
 
+    /// Return the entries of the folder or archive at `path`, which are listed in the background the first time, and again once it changes.
+    fn list_cached<'a>(path: &Path, sort: sort::Sort, cached_entries: &'a mut FsWatcherCache<CachedEntries>) -> &'a mut CachedEntries {
+        cached_entries.forget_changed();
+        let watcher = Arc::clone(&cached_entries.watcher);
         cached_entries.data.entry(path.to_path_buf()).or_insert_with(|| {
             trace!("list cache miss for {:?}", path);
-            let watch_result = cached_entries.watcher.watch(path.parent().unwrap_or(path), RecursiveMode::NonRecursive);
-            if let Err(error) = watch_result {
-                error!("Unexpected error while trying to watch directory: {:?}", error);
-            }
-            let (tx, rx) = bounded(1);
-            let Ok(read_dir) = read_dir(path) else {
-                error!("Failed to read directory: {:?}", path);
-                return CachedEntries { data: Poll::Ready(Vec::new()), rx };
-            };
-            let cached_entry_kinds = Arc::clone(cached_entry_kinds);
-            spawn(move || {
+            let path = path.to_path_buf();
+            let rx = tasks::spawn(Job::new(Kind::Listing, path.display().to_string()), move || {
+                if archive::is_archive(&path) {
+                    let entries = archive::list(&path)
+                        .inspect_err(|error| error!("Couldn't list {}: {error}", path.display()))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|entry| Arc::from(path.join(entry.name)))
+                        .map(|path: Arc<Path>| (Self::file_kind(&path), path))
+                        .filter(|(kind, _)| matches!(kind, EntryKind::Audio | EntryKind::Midi))
+                        .collect_vec();
+                    return sort.apply(entries);
+                }
+                watch(&watcher, &path);
+                let Ok(read_dir) = read_dir(&path) else {
+                    error!("Failed to read directory: {:?}", path);
+                    return Vec::new();
+                };
                 let read_dir = read_dir
+                    .filter_map(|entry| entry.inspect_err(|error| error!("Couldn't read an entry of a folder: {error}")).ok())
                     .map(|entry| {
-                        let path = entry.unwrap().path();
-                        (Self::entry_kind_of(&path, &mut cached_entry_kinds.write().unwrap()), Arc::from(path.as_path()))
+                        let path = entry.path();
+                        // Links are followed to find out whether they point to folders.
+                        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir() || file_type.is_symlink() && path.is_dir());
+                        let kind = if is_dir || archive::is_archive(&path) { EntryKind::Directory } else { Self::file_kind(&path) };
+                        (kind, Arc::from(path.as_path()))
                     })
-                    .sorted_unstable()
                     .collect_vec();
-                tx.send(read_dir).unwrap();
+                // The folder may have been listed again in the meantime, which drops the receiver.
+                sort.apply(read_dir)
             });
-
             CachedEntries { data: Poll::Pending, rx }
         })
     }
 
-    fn entries(
-        entries: &mut Vec<Entry>,
-        path: &Path,
-        mut depth: usize,
-        cached_entries: &mut FsWatcherCache<CachedEntries>,
-        cached_entry_kinds: &Arc<RwLock<FsWatcherCache<EntryKind>>>,
-        expanded_paths: &[Arc<Path>],
-    ) {
-        if depth == 0 {
-            entries.push(Entry {
-                data: Poll::Ready(EntryData {
-                    path: Arc::from(path),
-                    kind: Self::entry_kind_of(path, &mut cached_entry_kinds.write().unwrap()),
-                }),
-                depth,
-            });
+    /// Return the entries under the roots in the order they're shown, which are listed again only when the roots, the expanded folders, the sort
+    /// or the files changed, or when some were still being listed. Only the expanded folders that changed are gone through again, so a huge folder
+    /// costs nothing once it's listed, and nothing at all while it's collapsed.
+    fn flattened(&mut self) -> Rows {
+        let sort = self.sorts[self.selected_category as usize];
+        self.cached_entries.forget_changed();
+        self.entry_kinds.refresh();
+        let revisions = (self.cached_entries.revision, self.entry_kinds.cache.revision);
+        if let Some(flattened) = self.flattened.as_ref().filter(|flattened| {
+            !flattened.pending && flattened.sort == sort && flattened.revisions == revisions && flattened.roots == self.open_paths && flattened.expanded == self.expanded_paths
+        }) {
+            return Rc::clone(&flattened.entries);
         }
-        if !expanded_paths.iter().any(|expanded| **expanded == *path) {
-            return;
-        }
-        depth += 1;
-        let CachedEntries { data, rx } = Self::list_cached(path, cached_entries, cached_entry_kinds);
-        match data {
-            Poll::Ready(list) => {
-                for (kind, entry) in list.clone() {
-                    entries.push(Entry {
-                        data: Poll::Ready(EntryData { path: Arc::from(Path::new("")), kind }),
-                        depth,
-                    });
-                    let len = entries.len();
-                    if expanded_paths.iter().any(|expanded| **expanded == *entry) {
-                        Self::entries(entries, &entry, depth, cached_entries, cached_entry_kinds, expanded_paths);
-                    }
-                    match &mut entries[len - 1].data {
-                        Poll::Ready(EntryData { path, .. }) => *path = entry,
-                        Poll::Pending => unreachable!(),
-                    };
-                }
-            }
-            Poll::Pending => match rx.try_recv() {
-                Ok(list) => {
-                    *data = Poll::Ready(list);
-                }
-                Err(TryRecvError::Disconnected) => {
-                    *data = Poll::Ready(Vec::new());
-                }
-                Err(TryRecvError::Empty) => {
-                    entries.push(Entry { data: Poll::Pending, depth });
-                }
-            },
+        let started = Instant::now();
+        self.subtrees.retain(&self.expanded_paths);
+        let entries = self.subtrees.flatten(&self.open_paths, &self.expanded_paths, sort, &mut self.cached_entries, &mut self.entry_kinds);
+        let pending = entries.iter().any(|entry| entry.data.is_pending());
+        let entries = entries.into_iter().map(|entry| (entry, Vec::new())).collect::<Rc<[_]>>();
+        let elapsed = started.elapsed();
+        if elapsed > BUDGET {
+            warn!("Listing the {} entries of the browser took {elapsed:?}", entries.len());
         }
+        self.flattened = Some(Flattened {
+            sort,
+            roots: self.open_paths.clone(),
+            expanded: self.expanded_paths.clone(),
+            // Changes found while building the list are in it already.
+            revisions: (self.cached_entries.revision, self.entry_kinds.cache.revision),
+            entries: Rc::clone(&entries),
+            pending,
+        });
+        entries
     }
 
-    fn add_entry(&mut self, Entry { data, depth }: Entry, ui: &mut Ui, browser_width: f32) -> Response {
+    /// Show an entry, with the characters of its name at `highlights` standing out.
+    fn add_entry(&mut self, Entry { data, depth }: Entry, highlights: &[usize], ui: &mut Ui, browser_width: f32) -> Response {
         const INDENT_SIZE: f32 = 16.;
         let Poll::Ready(EntryData { path, kind }) = data else {
             return ui
@@ -424,7 +959,7 @@ impl Browser {
                     #[allow(clippy::cast_possible_truncation, reason = "this is a visual effect")]
                     #[allow(clippy::cast_precision_loss, reason = "this is a visual effect")]
                     ui.add_space(INDENT_SIZE * depth as f32);
-                    ui.add(Self::loading);
+                    Self::loading(ui, &self.theme);
                 })
                 .response;
         };
@@ -453,6 +988,19 @@ impl Browser {
             final_text = final_text.unicode_truncate(final_char_length).0.to_string();
         }
         let button = |theme: &ThemeColors| -> Button<'static> {
+            if !highlights.is_empty() {
+                let color = if matches!(&name, &Cow::Owned(_)) { theme.browser_unselected_button_fg_invalid } else { Color32::PLACEHOLDER };
+                let mut job = LayoutJob::default();
+                for (index, character) in final_text.chars().enumerate() {
+                    let format = TextFormat {
+                        font_id: FontId::proportional(12.),
+                        color: if highlights.contains(&index) { theme.browser_selected_button_fg } else { color },
+                        ..TextFormat::default()
+                    };
+                    job.append(character.encode_utf8(&mut [0; 4]), 0., format);
+                }
+                return Button::new(job);
+            }
             Button::new(RichText::new(final_text.clone()).font(FontId::proportional(12.)).pipe(|text| {
                 if matches!(&name, &Cow::Owned(_)) {
                     text.color(theme.browser_unselected_button_fg_invalid)
@@ -467,76 +1015,698 @@ impl Browser {
                     #[allow(clippy::cast_possible_truncation, reason = "this is a visual effect")]
                     #[allow(clippy::cast_precision_loss, reason = "this is a visual effect")]
                     ui.add_space(INDENT_SIZE * depth as f32);
-                    match kind {
-                        EntryKind::Audio => self.add_audio_entry(&path, ui, &Rc::clone(&self.theme), button),
-                        EntryKind::File => Self::add_file(ui, button(&self.theme)),
+                    let response = match kind {
+                        EntryKind::Audio | EntryKind::Midi => self.add_audio_entry(&path, kind, ui, &Rc::clone(&self.theme), button),
+                        EntryKind::File => Self::add_file(ui, &self.theme, button(&self.theme)),
                         EntryKind::Directory => {
                             ui.horizontal(|ui| ui.add(self.collapsing_header_icon(f32::from(self.expanded_paths.iter().any(|expanded| *expanded == path)))) | ui.add(button(&self.theme)))
                                 .inner
                         }
+                    };
+                    let tags = self.tags.of(&path).join(", ");
+                    if !tags.is_empty() {
+                        ui.label(RichText::new(tags).size(10.).weak());
                     }
+                    self.add_favorite_toggle(ui, &path);
+                    response
                 })
             })
             .inner
             .inner;
+        response.context_menu(|ui| self.add_entry_menu(ui, &path, kind));
+        self.add_preview_controls(ui, &path, depth, browser_width);
+        if matches!(kind, EntryKind::Audio | EntryKind::Midi) && self.selection.contains(&path) {
+            ui.painter().rect_filled(response.rect.expand(1.), 2., self.theme.browser_selected_button_fg.gamma_multiply(0.15));
+        }
+        if response.double_clicked() && kind == EntryKind::Audio {
+            self.opened_in_editor = Some(path.to_path_buf());
+        }
         if response.clicked() {
-            match kind {
-                EntryKind::Audio => {
-                    // TODO: Proper preview implementation with cpal. This is temporary (or at least make it work well with a proper preview widget)
-                    // Also, don't spawn a new thread - instead, dedicate a thread for preview
-                    self.preview.play_file(Arc::clone(&path));
+            self.open_entry(ui, path, kind);
+        }
+        response
+    }
+
+    /// Preview the audio file at `path` after its entry was clicked, open another kind of file, or expand or collapse a folder.
+    fn open_entry(&mut self, ui: &Ui, path: Arc<Path>, kind: EntryKind) {
+        match kind {
+            EntryKind::Audio | EntryKind::Midi => {
+                let modifiers = ui.input(|input| input.modifiers);
+                self.select(&path, modifiers);
+                if !modifiers.command && !modifiers.shift {
+                    self.preview_file(&path);
                 }
-                EntryKind::File => {
-                    that_detached(path.as_os_str()).unwrap();
+            }
+            EntryKind::File => {
+                if let Err(error) = that_detached(path.as_os_str()) {
+                    self.report(format!("Couldn't open {}, {error}.", path.display()));
                 }
-                EntryKind::Directory => {
-                    if let Some(index) = self.expanded_paths.iter().position(|expanded| expanded == &path) {
-                        self.expanded_paths.swap_remove(index);
+            }
+            EntryKind::Directory => {
+                if let Some(index) = self.expanded_paths.iter().position(|expanded| expanded == &path) {
+                    self.expanded_paths.swap_remove(index);
+                } else {
+                    self.expanded_paths.push(path);
+                }
+            }
+        }
+    }
+
+    /// Select the audio entry at `path` after it was clicked while holding `modifiers`. Ctrl toggles it, shift selects the entries shown between it and the last one picked, and
+    /// otherwise it's selected on its own.
+    fn select(&mut self, path: &Arc<Path>, modifiers: Modifiers) {
+        if modifiers.command {
+            if let Some(index) = self.selection.iter().position(|selected| selected == path) {
+                self.selection.remove(index);
+            } else {
+                self.selection.push(Arc::clone(path));
+            }
+        } else if modifiers.shift {
+            let shown_audio = self
+                .shown
+                .iter()
+                .filter_map(|(entry, _)| match &entry.data {
+                    Poll::Ready(EntryData { path, kind: EntryKind::Audio | EntryKind::Midi }) => Some(Arc::clone(path)),
+                    _ => None,
+                })
+                .collect_vec();
+            let position = |path: &Arc<Path>| shown_audio.iter().position(|shown| shown == path);
+            let (Some(from), Some(to)) = (self.selection.last().and_then(position), position(path)) else {
+                self.selection = vec![Arc::clone(path)];
+                return;
+            };
+            let range = if from <= to { shown_audio[from..=to].to_vec() } else { shown_audio[to..=from].iter().rev().cloned().collect() };
+            self.selection.retain(|selected| !range.contains(selected));
+            self.selection.extend(range);
+        } else {
+            self.selection = vec![Arc::clone(path)];
+        }
+    }
+
+    /// Show a bar under the entry of `path` if it's being previewed, which can be clicked or dragged to seek, along with buttons to stop the preview and replay it.
+    fn add_preview_controls(&mut self, ui: &mut Ui, path: &Path, depth: usize, browser_width: f32) {
+        const BAR_HEIGHT: f32 = 4.;
+        let Some(&PreviewState { position, length: Some(length), .. }) = self.preview.of(path) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            #[allow(clippy::cast_precision_loss, reason = "this is a visual effect")]
+            ui.add_space(16_f32.mul_add(depth as f32, 20.));
+            if ui.small_button("⏹").on_hover_text("Stop").clicked() {
+                self.preview.stop();
+            }
+            if ui.small_button("⏮").on_hover_text("Play from the start").clicked() {
+                self.preview.seek(Duration::ZERO);
+            }
+            let width = (browser_width - ui.cursor().left() + ui.max_rect().left() - 24.).max(40.);
+            let (rect, response) = ui.allocate_exact_size(vec2(width, Self::ENTRY_HEIGHT / 2.), Sense::click_and_drag());
+            let bar = Rect::from_center_size(rect.center(), vec2(rect.width(), BAR_HEIGHT));
+            let fraction = response
+                .interact_pointer_pos()
+                .filter(|_| response.is_pointer_button_down_on())
+                .map(|pos| ((pos.x - rect.left()) / rect.width()).clamp(0., 1.));
+            let shown = fraction.unwrap_or_else(|| position.as_secs_f32() / length.as_secs_f32()).clamp(0., 1.);
+            ui.painter().rect_filled(bar, 2., self.theme.browser_unselected_button_fg.gamma_multiply(0.4));
+            ui.painter().rect_filled(Rect::from_min_size(bar.min, vec2(bar.width() * shown, bar.height())), 2., self.theme.browser_selected_button_fg);
+            // Seeking follows the pointer while dragging, so the preview can be scrubbed.
+            let moved = response.clicked() || (response.dragged() && response.drag_delta().x != 0.);
+            if let Some(fraction) = fraction.filter(|_| moved) {
+                self.preview.seek(length.mul_f32(fraction));
+            }
+            response.on_hover_cursor(CursorIcon::PointingHand);
+        });
+    }
+
+    /// Show a star that adds `path` to the favorites or removes it, which only shows up on hover unless it's a favorite.
+    fn add_favorite_toggle(&mut self, ui: &mut Ui, path: &Path) {
+        let index = self.favorites.iter().position(|favorite| favorite == path);
+        if index.is_none() && !ui.rect_contains_pointer(ui.max_rect()) {
+            return;
+        }
+        let star = RichText::new(if index.is_some() { "★" } else { "☆" }).size(12.).color(self.theme.browser_selected_button_fg);
+        let hover_text = if index.is_some() { "Remove from favorites" } else { "Add to favorites" };
+        if ui.add(Button::new(star)).on_hover_text(hover_text).clicked() {
+            if let Some(index) = index {
+                self.favorites.remove(index);
+            } else {
+                self.favorites.push(path.to_path_buf());
+            }
+            favorites::save(&self.favorites);
+        }
+    }
+
+    /// Show every tag, which can be picked to only show the entries that have all the picked tags.
+    fn add_tag_filter(&mut self, ui: &mut Ui) {
+        let all = self.tags.all();
+        self.tag_filter.retain(|tag| all.contains(tag.as_str()));
+        if all.is_empty() {
+            return;
+        }
+        let all = all.into_iter().map(ToString::to_string).collect_vec();
+        ui.horizontal_wrapped(|ui| {
+            for tag in all {
+                let picked = self.tag_filter.contains(&tag);
+                if ui.selectable_label(picked, RichText::new(&tag).size(11.)).clicked() {
+                    if picked {
+                        self.tag_filter.remove(&tag);
                     } else {
-                        self.expanded_paths.push(path);
+                        self.tag_filter.insert(tag);
                     }
                 }
             }
+            if !self.tag_filter.is_empty() && ui.small_button("Clear").clicked() {
+                self.tag_filter.clear();
+            }
+        });
+    }
+
+    /// Show what can be done with the entry at `path` other than opening it.
+    fn add_entry_menu(&mut self, ui: &mut Ui, path: &Arc<Path>, kind: EntryKind) {
+        if ui.button("Reveal in file manager").clicked() {
+            if let Err(error) = that_detached(path.parent().unwrap_or(path)) {
+                self.report(format!("Couldn't reveal {}, {error}.", path.display()));
+            }
+            ui.close_menu();
         }
-        response
+        if ui.button("Copy path").clicked() {
+            ui.ctx().copy_text(path.to_string_lossy().into_owned());
+            ui.close_menu();
+        }
+        if kind == EntryKind::Directory && !self.open_paths.iter().any(|root| root == &**path) && ui.button("Add folder as root").clicked() {
+            self.open_paths.push(path.to_path_buf());
+            self.index.set_roots(&self.open_paths);
+            self.edit = Some("Add browser root".into());
+            ui.close_menu();
+        }
+        ui.menu_button("Rename", |ui| self.add_rename_field(ui, path));
+        if ui.button("Move to trash").clicked() {
+            if let Err(error) = trash::trash(path) {
+                self.report(format!("Couldn't move {} to the trash, {error}.", path.display()));
+            }
+            ui.close_menu();
+        }
+        ui.separator();
+        ui.menu_button("Tags", |ui| self.add_tag_menu(ui, path));
+    }
+
+    /// Show a field to type a new name for the entry at `path`, which is renamed when enter is pressed. Its favorites and tags move along with it.
+    fn add_rename_field(&mut self, ui: &mut Ui, path: &Arc<Path>) {
+        if self.renaming.as_ref().is_none_or(|(renamed, _)| renamed != path) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            self.renaming = Some((Arc::clone(path), name));
+        }
+        let Some((_, name)) = &mut self.renaming else {
+            return;
+        };
+        let response = ui.add(TextEdit::singleline(name).desired_width(160.));
+        if !(response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter))) || name.trim().is_empty() {
+            return;
+        }
+        let to = path.with_file_name(name.trim());
+        self.renaming = None;
+        ui.close_menu();
+        if to.exists() {
+            self.report(format!("Couldn't rename {} as {} already exists.", path.display(), to.display()));
+            return;
+        }
+        if let Err(error) = rename(path, &to) {
+            self.report(format!("Couldn't rename {}, {error}.", path.display()));
+            return;
+        }
+        self.tags.rename(path, &to);
+        self.collected.refresh();
+        let mut renamed_favorites = false;
+        for favorite in &mut self.favorites {
+            if let Some(renamed) = favorite.strip_prefix(path).ok().map(|rest| to.join(rest)) {
+                *favorite = renamed;
+                renamed_favorites = true;
+            }
+        }
+        if renamed_favorites {
+            favorites::save(&self.favorites);
+        }
+        if let Some(expanded) = self.expanded_paths.iter_mut().find(|expanded| *expanded == path) {
+            *expanded = Arc::from(to.as_path());
+        }
+    }
+
+    /// Show the tags that can be given to `path`, along with a field to give it a new one.
+    fn add_tag_menu(&mut self, ui: &mut Ui, path: &Path) {
+        ui.label(RichText::new("Tags").strong());
+        for tag in self.tags.all().into_iter().map(ToString::to_string).collect_vec() {
+            let mut tagged = self.tags.of(path).contains(&tag.as_str());
+            if ui.checkbox(&mut tagged, &tag).changed() {
+                self.tags.set(path, &tag, tagged);
+                self.collected.refresh();
+            }
+        }
+        ui.horizontal(|ui| {
+            let response = ui.add(TextEdit::singleline(&mut self.new_tag).hint_text("New tag").desired_width(100.));
+            let entered = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+            if (ui.button("Add").clicked() || entered) && !self.new_tag.trim().is_empty() {
+                self.tags.set(path, &self.new_tag, true);
+                self.new_tag.clear();
+                self.collected.refresh();
+            }
+        });
+    }
+
+    /// Show menus to sort the entries and to pick the detail columns, along with the titles of the columns picked.
+    fn add_list_options(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            self.add_roots_menu(ui);
+            self.add_sort_menu(ui);
+            self.add_preview_options(ui);
+            ui.menu_button(RichText::new("Columns").size(11.), |ui| {
+                for column in details::Column::VARIANTS {
+                    let mut shown = self.columns.contains(&column);
+                    if ui.checkbox(&mut shown, column.title()).changed() {
+                        self.columns.retain(|other| *other != column);
+                        if shown {
+                            // Keep the columns in the same order however they're picked.
+                            self.columns.push(column);
+                            self.columns.sort_by_key(|column| details::Column::VARIANTS.iter().position(|other| other == column));
+                        }
+                    }
+                }
+            })
+            .response
+            .on_hover_text("The details shown at the right of audio files");
+            self.add_analysis_filter(ui);
+            let right = ui.max_rect().right() - 14.;
+            for (index, column) in self.columns.iter().rev().enumerate() {
+                #[allow(clippy::cast_precision_loss, reason = "there are few columns")]
+                let position = emath::pos2((index as f32).mul_add(-details::Column::WIDTH, right), ui.min_rect().center().y);
+                ui.painter().text(position, Align2::RIGHT_CENTER, column.title(), FontId::proportional(11.), self.theme.browser_unselected_button_fg);
+            }
+        });
+    }
+
+    /// Leave out the `entries` that don't have the tags picked, or a tempo and key that match the analysis filter.
+    fn filter_entries(&self, entries: &mut Vec<(Entry, Vec<usize>)>) {
+        if !self.tag_filter.is_empty() {
+            entries.retain(|(entry, _)| matches!(&entry.data, Poll::Ready(EntryData { path, .. }) if self.tags.has_all(path, &self.tag_filter)));
+        }
+        if !self.analysis_filter.is_empty() {
+            // Folders stay so that they can be expanded. Only the analyses kept are used, as analysing decodes the files: audio files stay until
+            // they're shown and analysed, and are hidden then if they don't match. The analyses that came in were taken with their revision.
+            entries.retain(|(entry, _)| match &entry.data {
+                Poll::Ready(EntryData { kind: EntryKind::Directory, .. }) => true,
+                Poll::Ready(EntryData { path, kind: EntryKind::Audio }) => match self.analyses.peek(path) {
+                    None | Some(lazy_cache::Value::Pending) => true,
+                    Some(lazy_cache::Value::Failed) => false,
+                    Some(lazy_cache::Value::Ready(analysis)) => self.analysis_filter.matches(analysis),
+                },
+                _ => false,
+            });
+        }
+    }
+
+    /// Show a menu to only show audio files with a tempo or key that analysis found.
+    fn add_analysis_filter(&mut self, ui: &mut Ui) {
+        let title = if self.analysis_filter.is_empty() { RichText::new("Filter") } else { RichText::new("Filter ●").color(self.theme.browser_selected_button_fg) };
+        ui.menu_button(title.size(11.), |ui| {
+            let filter = &mut self.analysis_filter;
+            let mut by_tempo = filter.tempo.is_some();
+            let (mut lowest, mut highest) = filter.tempo.unwrap_or((90., 100.));
+            ui.checkbox(&mut by_tempo, "Tempo");
+            ui.add_enabled_ui(by_tempo, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(&mut lowest).range(20. ..=999.).suffix(" BPM"));
+                    ui.label("to");
+                    ui.add(DragValue::new(&mut highest).range(lowest..=999.).suffix(" BPM"));
+                });
+            });
+            filter.tempo = by_tempo.then_some((lowest, highest.max(lowest)));
+            ComboBox::from_label("Key").selected_text(filter.key.map_or_else(|| "Any".into(), |key| key.to_string())).show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.key, None, "Any");
+                for key in analysis::Key::all() {
+                    ui.selectable_value(&mut filter.key, Some(key), key.to_string());
+                }
+            });
+            ui.label(RichText::new("Audio files are analysed as they're shown, and are hidden once they are if they don't match").weak());
+        })
+        .response
+        .on_hover_text("Only show audio files with a tempo or key");
+    }
+
+    /// Show toggles for looping previews and stretching them to the project tempo. A file being previewed is played again with the new options.
+    fn add_preview_options(&mut self, ui: &mut Ui) {
+        let mut options = self.preview.options;
+        ui.toggle_value(&mut options.looping, RichText::new("Loop").size(11.)).on_hover_text("Start previews over when they end");
+        let mut synced = options.tempo.is_some();
+        ui.toggle_value(&mut synced, RichText::new("Sync").size(11.))
+            .on_hover_text(format!("Stretch loops to the project tempo of {:.2} BPM", self.tempo));
+        options.tempo = synced.then_some(self.tempo);
+        if options != self.preview.options {
+            self.preview.options = options;
+            if let Some(path) = self.preview.path().map(Path::to_path_buf) {
+                self.preview.stop();
+                self.preview.play_file(&path);
+            }
+        }
+        ui.menu_button(RichText::new("Bus").size(11.), |ui| {
+            let mut bus = self.preview.bus;
+            ui.add(Slider::new(&mut bus.gain, -48. ..=12.).suffix(" dB").text("Gain"));
+            ui.checkbox(&mut bus.through_master, "Through master")
+                .on_hover_text("Hear previews along with the playback, rather than muting the playback while they play");
+            if bus != self.preview.bus {
+                self.preview.bus = bus;
+                self.preview.commands.push(PreviewCommand::Bus(bus));
+            }
+        })
+        .response
+        .on_hover_text("How previews are mixed into the output");
+    }
+
+    /// Return messages for what went wrong since the last call, like files that couldn't be renamed or moved to the trash.
+    pub fn take_errors(&mut self) -> Vec<String> {
+        mem::take(&mut self.errors)
+    }
+
+    /// Return how many files the caches of details, analyses and thumbnails hold values for, and roughly how many bytes the thumbnails take.
+    pub fn cache_usage(&self) -> (usize, usize) {
+        let thumbnails = self.thumbnails.values().map(|peaks| size_of_val::<[Peak]>(peaks)).sum();
+        let files = self.thumbnails.values().count() + self.details.values().count() + self.midi_details.values().count() + self.analyses.values().count();
+        (files, thumbnails)
+    }
+
+    /// Forget the details, analyses and thumbnails of files, which are worked out again for the files shown.
+    pub fn clear_caches(&mut self) {
+        self.thumbnails.clear();
+        self.details.clear();
+        self.midi_details.clear();
+        self.analyses.clear();
+    }
+
+    /// Return how many folders the index of the roots holds, and how many files and folders they hold.
+    pub fn index_size(&self) -> (usize, usize) {
+        self.index.size()
+    }
+
+    /// Draw the browser with `theme` from now on.
+    pub fn set_theme(&mut self, theme: Rc<ThemeColors>) {
+        self.theme = theme;
+    }
+
+    /// Keep the index of the browser's roots up to date, returning the progress of indexing them if it started taking long since the last call.
+    pub fn take_index_progress(&mut self) -> Option<Arc<Progress>> {
+        self.index.take_progress()
+    }
+
+    /// Log `message` and keep it to be shown.
+    fn report(&mut self, message: String) {
+        error!("{message}");
+        self.errors.push(message);
+    }
+
+    /// Return the commands for the preview bus of the engine queued since the last frame.
+    pub fn take_preview_commands(&mut self) -> Vec<PreviewCommand> {
+        mem::take(&mut self.preview.commands)
+    }
+
+    /// Tell the browser what the preview bus of the engine is playing.
+    pub fn set_preview_state(&mut self, state: Option<PreviewState>) {
+        self.preview.state = state;
+    }
+
+    /// Return how previews are mixed, for an engine that was just opened.
+    pub const fn preview_bus(&self) -> PreviewBus {
+        self.preview.bus
+    }
+
+    /// Tell the browser the project tempo in BPM, which loops are stretched to when previews are synced.
+    pub const fn set_tempo(&mut self, bpm: f64) {
+        self.tempo = bpm;
+        if let Some(tempo) = &mut self.preview.options.tempo {
+            *tempo = bpm;
+        }
+    }
+
+    /// Show a menu to pick how the entries of the selected category are sorted. Folders that were listed already are listed again in the new order.
+    fn add_sort_menu(&mut self, ui: &mut Ui) {
+        let sort = self.sorts[self.selected_category as usize];
+        let mut new = sort;
+        let title = format!("{} {}", new.key.title(), if new.descending { "↓" } else { "↑" });
+        ui.menu_button(RichText::new(title).size(11.), |ui| {
+            for key in sort::SortKey::VARIANTS {
+                ui.radio_value(&mut new.key, key, key.title());
+            }
+            ui.separator();
+            ui.checkbox(&mut new.descending, "Descending");
+        })
+        .response
+        .on_hover_text("How the entries are sorted");
+        if new != sort {
+            self.sorts[self.selected_category as usize] = new;
+            self.cached_entries.data.clear();
+        }
+    }
+
+    /// Show the detail columns of the audio file at `path` at the right of its entry's `row`, over its name if that's too long.
+    fn add_details(&mut self, ui: &Ui, row: Rect, path: &Path) {
+        if self.columns.is_empty() {
+            return;
+        }
+        let right = ui.clip_rect().right() - 8.;
+        #[allow(clippy::cast_precision_loss, reason = "there are few columns")]
+        let left = (self.columns.len() as f32).mul_add(-details::Column::WIDTH, right);
+        ui.painter().rect_filled(Rect::from_x_y_ranges(left..=right, row.y_range()), 0., self.theme.browser);
+        let Some(details) = self.details.get(path) else {
+            if self.details.is_pending(path) {
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+            }
+            return;
+        };
+        let analysis = if self.columns.iter().any(|column| column.is_analysed()) { self.analyses.get(path) } else { None };
+        if self.analyses.is_pending(path) {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+        for (index, column) in self.columns.iter().rev().enumerate() {
+            #[allow(clippy::cast_precision_loss, reason = "there are few columns")]
+            let position = emath::pos2((index as f32).mul_add(-details::Column::WIDTH, right), row.center().y);
+            ui.painter().text(position, Align2::RIGHT_CENTER, column.text(&details, analysis.as_ref()), FontId::proportional(11.), self.theme.browser_folder_text);
+        }
+    }
+
+    /// Show a collapsible section of `paths` called `title` above the roots, like the favorites. They're listed like the roots, so that folders can be expanded too.
+    fn add_pinned(&mut self, ui: &mut Ui, title: &str, paths: &[PathBuf], default_open: bool, browser_width: f32) {
+        const MAX_HEIGHT: f32 = 160.;
+        let sort = self.sorts[self.selected_category as usize];
+        let entries = self.subtrees.flatten(paths, &self.expanded_paths, sort, &mut self.cached_entries, &mut self.entry_kinds);
+        CollapsingHeader::new(RichText::new(title).size(12.)).default_open(default_open).show(ui, |ui| {
+            ScrollArea::vertical().id_salt(title).max_height(MAX_HEIGHT).auto_shrink([false, true]).show(ui, |ui| {
+                ui.visuals_mut().widgets.noninteractive.fg_stroke.color = self.theme.browser_folder_text;
+                ui.visuals_mut().widgets.hovered.fg_stroke.color = self.theme.browser_folder_hover_text;
+                ui.style_mut().spacing.item_spacing.x = 4.;
+                for entry in entries {
+                    self.add_entry(entry, &[], ui, browser_width);
+                }
+            });
+        });
+        ui.separator();
+    }
+
+    /// Show the collections above the roots as folders of the audio and MIDI files that match them, which are searched for again whenever the index changes.
+    fn add_collections(&mut self, ui: &mut Ui, browser_width: f32) {
+        const MAX_HEIGHT: f32 = 160.;
+        let (roots, collections, tags) = (self.open_paths.clone(), self.collections.clone(), self.tags.clone());
+        let collected = self.collected.results(ui.ctx(), &self.index, "", move |found| {
+            let files = found
+                .into_iter()
+                .filter(|(path, is_folder, _)| !is_folder && roots.iter().any(|root| path.starts_with(root)) && matches!(Self::file_kind(path), EntryKind::Audio | EntryKind::Midi))
+                .map(|(path, ..)| path)
+                .sorted_by_key(|path| path.file_name().map(|name| name.to_string_lossy().to_lowercase()))
+                .collect_vec();
+            collections
+                .iter()
+                .map(|collection| files.iter().filter(|path| collection.matches(path, &tags)).take(Self::MAX_RESULTS).cloned().collect())
+                .collect()
+        });
+        // Until the collections that changed are searched for again, the previous results are shown for as many of them as there were.
+        let collected = (0..self.collections.len()).map(|index| collected.get(index).cloned().unwrap_or_default()).collect_vec();
+        let mut removed = None;
+        CollapsingHeader::new(RichText::new("Collections").size(12.)).default_open(true).show(ui, |ui| {
+            for (index, files) in collected.into_iter().enumerate() {
+                let title = RichText::new(format!("{} ({})", self.collections[index].name, files.len())).size(12.);
+                let response = CollapsingHeader::new(title).id_salt(("collection", index)).show(ui, |ui| {
+                    ScrollArea::vertical().id_salt(("collection", index)).max_height(MAX_HEIGHT).auto_shrink([false, true]).show(ui, |ui| {
+                        ui.visuals_mut().widgets.noninteractive.fg_stroke.color = self.theme.browser_folder_text;
+                        ui.visuals_mut().widgets.hovered.fg_stroke.color = self.theme.browser_folder_hover_text;
+                        ui.style_mut().spacing.item_spacing.x = 4.;
+                        for path in files {
+                            let kind = Self::file_kind(&path);
+                            let data = Poll::Ready(EntryData { path: Arc::from(path), kind });
+                            self.add_entry(Entry { data, depth: 0 }, &[], ui, browser_width);
+                        }
+                    });
+                });
+                response.header_response.context_menu(|ui| {
+                    if self.add_collection_menu(ui, index) {
+                        removed = Some(index);
+                    }
+                });
+            }
+        });
+        if let Some(index) = removed {
+            self.collections.remove(index);
+            collections::save(&self.collections);
+            self.collected.refresh();
+        }
+        ui.separator();
     }
 
-    fn add_audio_entry(&mut self, path: &Path, ui: &mut Ui, theme: &Rc<ThemeColors>, button: impl Fn(&ThemeColors) -> Button<'static>) -> Response {
-        let mut add_contents = |ui: &mut Ui| {
+    /// Show the online sources, playing the previews downloaded from them.
+    #[cfg(feature = "freesound")]
+    fn add_freesound(&mut self, ui: &mut Ui) {
+        let preview = CollapsingHeader::new(RichText::new("Freesound").size(12.)).show(ui, |ui| self.freesound.show(ui)).body_returned.flatten();
+        if let Some(path) = preview {
+            self.preview.play_file(&path);
+        }
+        ui.separator();
+    }
+
+    /// Show fields to change the collection at `index`, which is searched for again after every change. Returns whether it should be removed.
+    fn add_collection_menu(&mut self, ui: &mut Ui, index: usize) -> bool {
+        let all_tags = self.tags.all().into_iter().map(ToString::to_string).collect_vec();
+        let collection = &mut self.collections[index];
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            changed |= ui.add(TextEdit::singleline(&mut collection.name).desired_width(140.)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Search");
+            changed |= ui.add(TextEdit::singleline(&mut collection.query).desired_width(140.)).changed();
+        });
+        let mut by_tempo = collection.tempo.is_some();
+        let (mut lowest, mut highest) = collection.tempo.unwrap_or((90., 100.));
+        changed |= ui.checkbox(&mut by_tempo, "Tempo").on_hover_text("Only files with a tempo in their name, like drums_95bpm.wav").changed();
+        ui.add_enabled_ui(by_tempo, |ui| {
             ui.horizontal(|ui| {
-                ui.add(Image::new(include_image!("../images/icons/audio.png"))).union(ui.add(button(theme))).pipe(|response| {
-                    let data = self.preview.data();
-                    if let Some(data @ PreviewData { length: Some(length), .. }) = self.preview.path.as_ref().filter(|preview_path| ***preview_path == *path).zip(data).map(|(_, data)| data) {
-                        ui.ctx().request_repaint();
-                        response
-                            | ui.label(format!(
-                                "{:>02}:{:>02} of {:>02}:{:>02}",
-                                data.progress().as_secs() / 60,
-                                data.progress().as_secs() % 60,
-                                length.as_secs() / 60,
-                                length.as_secs() % 60
-                            ))
-                    } else {
-                        response
+                changed |= ui.add(DragValue::new(&mut lowest).range(20. ..=999.).suffix(" BPM")).changed();
+                ui.label("to");
+                changed |= ui.add(DragValue::new(&mut highest).range(lowest..=999.).suffix(" BPM")).changed();
+            });
+        });
+        collection.tempo = by_tempo.then_some((lowest, highest.max(lowest)));
+        if !all_tags.is_empty() {
+            ui.label(RichText::new("Tags").strong());
+        }
+        for tag in all_tags {
+            let mut picked = collection.tags.contains(&tag);
+            if ui.checkbox(&mut picked, &tag).changed() {
+                if picked {
+                    collection.tags.insert(tag);
+                } else {
+                    collection.tags.remove(&tag);
+                }
+                changed = true;
+            }
+        }
+        if changed {
+            collections::save(&self.collections);
+            self.collected.refresh();
+        }
+        ui.separator();
+        let removed = ui.button("Remove collection").clicked();
+        if removed {
+            ui.close_menu();
+        }
+        removed
+    }
+
+    /// Preview the file at `path` and add it to the recent files.
+    pub fn preview_file(&mut self, path: &Path) {
+        self.preview.play_file(path);
+        recent::add(&mut self.recent, path);
+    }
+
+    /// Add files that were used elsewhere, like dropped onto the playlist, to the recent files.
+    pub fn add_recent(&mut self, paths: &[PathBuf]) {
+        for path in paths {
+            recent::add(&mut self.recent, path);
+        }
+    }
+
+    fn add_audio_entry(&mut self, path: &Path, kind: EntryKind, ui: &mut Ui, theme: &Rc<ThemeColors>, button: impl Fn(&ThemeColors) -> Button<'static>) -> Response {
+        let is_midi = kind == EntryKind::Midi;
+        // MIDI files have no waveform, so what they hold is shown instead.
+        let (peaks, midi) = if is_midi { (None, self.midi_details.get(path)) } else { (self.thumbnails.get(path), None) };
+        if is_midi && self.midi_details.is_pending(path) {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+        // Audio files shown are analysed for the filter, which hides them once they are if they don't match.
+        if !is_midi && !self.analysis_filter.is_empty() {
+            let _ = self.analyses.get(path);
+            if self.analyses.is_pending(path) {
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+            }
+        }
+        let add_contents = |ui: &mut Ui| {
+            ui.horizontal(|ui| {
+                let mut response = ui.add(Icon::Audio.image(theme));
+                if !is_midi {
+                    response |= thumbnails::show(ui, peaks.as_deref(), self.thumbnails.is_pending(path), theme.browser_folder_text);
+                }
+                response |= ui.add(button(theme));
+                if let Some(details) = midi {
+                    response |= ui.label(RichText::new(details.text()).size(10.).weak());
+                }
+                response.pipe(|response| {
+                    match self.preview.of(path) {
+                        Some(PreviewState { position, length: Some(length), .. }) => {
+                            ui.ctx().request_repaint();
+                            response
+                                | ui.label(format!(
+                                    "{:>02}:{:>02} of {:>02}:{:>02}",
+                                    position.as_secs() / 60,
+                                    position.as_secs() % 60,
+                                    length.as_secs() / 60,
+                                    length.as_secs() % 60
+                                ))
+                        }
+                        Some(PreviewState { length: None, .. }) => {
+                            ui.ctx().request_repaint();
+                            response | ui.label(RichText::new("Loading…").weak())
+                        }
+                        None => response,
                     }
                 })
             })
         };
         let mut response = if ui.ctx().is_being_dragged(Id::new(path.to_owned())) {
-            DragAndDrop::set_payload(ui.ctx(), path.to_path_buf());
+            // Dragging a selected entry drags the whole selection.
+            let count = if self.selection.len() > 1 && self.selection.iter().any(|selected| **selected == *path) {
+                DragAndDrop::set_payload(ui.ctx(), self.selection.iter().map(|selected| selected.to_path_buf()).collect_vec());
+                self.selection.len()
+            } else {
+                DragAndDrop::set_payload(ui.ctx(), path.to_path_buf());
+                1
+            };
             let layer_id = LayerId::new(Order::Tooltip, Id::new(path.to_owned()));
-            let response = ui.scope_builder(UiBuilder::new().layer_id(layer_id), add_contents).response;
+            let response = ui
+                .scope_builder(UiBuilder::new().layer_id(layer_id), |ui| {
+                    add_contents(ui);
+                    if count > 1 {
+                        ui.label(RichText::new(format!("{count} files")).size(11.).weak());
+                    }
+                })
+                .response;
             if let Some(pointer_pos) = ui.ctx().pointer_interact_pos() {
                 let delta = pointer_pos - response.rect.center();
                 ui.ctx().transform_layer_shapes(layer_id, TSTransform::from_translation(delta));
             }
             response
         } else {
-            let response = ui.scope(&mut add_contents).response;
+            let response = ui.scope(add_contents).response;
             let dnd_response = ui.interact(response.rect, Id::new(path.to_owned()), Sense::click_and_drag()).on_hover_cursor(CursorIcon::Grab);
             dnd_response | response
         };
         response.layer_id = ui.layer_id();
+        if kind == EntryKind::Audio {
+            self.add_details(ui, response.rect, path);
+        }
         response
     }
 
@@ -544,12 +1714,131 @@ impl Browser {
         ctx.input(|input| {
             for path in input.raw.dropped_files.iter().filter_map(|DroppedFile { path, .. }| path.as_deref()) {
                 self.open_paths.push(path.to_path_buf());
+                self.index.set_roots(&self.open_paths);
+                self.edit = Some("Add browser root".into());
             }
         });
     }
 
-    fn add_file(ui: &mut Ui, button: Button<'_>) -> Response {
-        ui.horizontal(|ui| ui.add(Image::new(include_image!("../images/icons/file.png"))) | (ui.add(button))).inner
+    /// Return a description of the last edit made to the browser roots, if there was one since this was last called.
+    pub const fn take_edit(&mut self) -> Option<String> {
+        self.edit.take()
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.open_paths
+    }
+
+    pub fn set_roots(&mut self, roots: Vec<PathBuf>) {
+        if roots != self.open_paths {
+            self.index.set_roots(&roots);
+        }
+        self.open_paths = roots;
+    }
+
+    /// Show the output and input devices and the MIDI inputs, highlighting the ones in use. Clicking another one picks it.
+    fn add_devices(&mut self, ui: &mut Ui) -> Response {
+        if self.devices.is_none() {
+            self.devices = Some(DeviceHandler::audio().devices());
+            self.midi_ports = controller::ports();
+        }
+        egui::Frame::default()
+            .inner_margin(Margin::same(8.))
+            .show(ui, |ui| {
+                ui.visuals_mut().widgets.inactive.fg_stroke.color = self.theme.browser_folder_text;
+                ui.visuals_mut().widgets.hovered.fg_stroke.color = self.theme.browser_folder_hover_text;
+                if ui.add(Button::new(RichText::new("Refresh").size(11.))).on_hover_text("Look for devices again").clicked() {
+                    self.devices = Some(DeviceHandler::audio().devices());
+                    self.midi_ports = controller::ports();
+                }
+                let devices = self.devices.as_deref().unwrap_or_default();
+                for (direction, title) in [(Direction::Output, "Outputs"), (Direction::Input, "Inputs")] {
+                    ui.add_space(4.);
+                    ui.label(RichText::new(title).size(12.).color(self.theme.browser_unselected_button_fg));
+                    let mut found = false;
+                    for Device { name, is_default, .. } in devices.iter().map(|entry| &entry.device).filter(|device| device.direction == direction) {
+                        found = true;
+                        let active = match direction {
+                            Direction::Output => self.active_output.as_ref() == Some(name),
+                            Direction::Input => self.active_input.as_ref().map_or(*is_default, |active| active == name),
+                        };
+                        let text = RichText::new(name).size(12.).pipe(|text| if active { text.color(self.theme.browser_selected_button_fg) } else { text });
+                        let response = ui.add(Button::new(text));
+                        let response = if *is_default { response.on_hover_text("The system's default device") } else { response };
+                        if response.clicked() && !active {
+                            self.picked_device = devices.iter().map(|entry| &entry.device).find(|device| device.direction == direction && device.name == *name).cloned();
+                        }
+                    }
+                    if !found {
+                        ui.weak("None found");
+                    }
+                }
+                ui.add_space(4.);
+                ui.label(RichText::new("MIDI inputs").size(12.).color(self.theme.browser_unselected_button_fg));
+                for name in &self.midi_ports {
+                    let active = self.active_midi_ports.contains(name);
+                    let text = RichText::new(name).size(12.).pipe(|text| if active { text.color(self.theme.browser_selected_button_fg) } else { text });
+                    let response = ui.add(Button::new(text)).on_hover_text(if active { "Connected" } else { "Connect to read the controls and notes sent" });
+                    if response.clicked() && !active {
+                        self.picked_midi_port = Some(name.clone());
+                    }
+                }
+                if self.midi_ports.is_empty() {
+                    ui.weak("None found");
+                }
+            })
+            .response
+    }
+
+    /// Show the registered effects by category, and the other nodes, that can be dragged into the graph or onto a playlist track, which adds them to the
+    /// track's insert chain.
+    fn add_plugins(&self, ui: &mut Ui) -> Response {
+        let (sources, utilities): (Vec<_>, Vec<_>) = NodeData::built_in().into_iter().partition(|(_, data)| !data.has_input());
+        let registered = registry::Category::ALL.map(|category| (category.name(), registry::in_category(category).map(|effect| (effect.name, NodeData::effect(effect))).collect_vec()));
+        egui::Frame::default()
+            .inner_margin(Margin::same(8.))
+            .show(ui, |ui| {
+                for (title, plugins) in registered.into_iter().chain([("Sources", sources), ("Nodes", utilities)]) {
+                    ui.add_space(4.);
+                    ui.label(RichText::new(title).size(12.).color(self.theme.browser_unselected_button_fg));
+                    for (name, data) in plugins {
+                        ui.dnd_drag_source(Id::new(("plugin", name)), data, |ui| ui.label(RichText::new(name).size(12.).color(self.theme.browser_folder_text)))
+                            .response
+                            .on_hover_text("Drag into the graph or onto a track");
+                    }
+                }
+            })
+            .response
+    }
+
+    /// Tell the browser which devices are in use, to highlight them.
+    pub fn set_active_devices(&mut self, output: Option<&str>, input: Option<&str>) {
+        self.active_output = output.map(ToString::to_string);
+        self.active_input = input.map(ToString::to_string);
+    }
+
+    /// Tell the browser which MIDI input ports are connected to, to highlight them.
+    pub fn set_active_midi_ports(&mut self, names: Vec<String>) {
+        self.active_midi_ports = names;
+    }
+
+    /// Return the MIDI input port picked in the devices tab, if one was picked since this was last called.
+    pub const fn take_picked_midi_port(&mut self) -> Option<String> {
+        self.picked_midi_port.take()
+    }
+
+    /// Return the device picked in the devices tab, if one was picked since this was last called.
+    pub const fn take_picked_device(&mut self) -> Option<Device> {
+        self.picked_device.take()
+    }
+
+    /// Return the audio file double-clicked to open it in the sample editor, if one was since this was last called.
+    pub const fn take_opened_in_editor(&mut self) -> Option<PathBuf> {
+        self.opened_in_editor.take()
+    }
+
+    fn add_file(ui: &mut Ui, theme: &ThemeColors, button: Button<'_>) -> Response {
+        ui.horizontal(|ui| ui.add(Icon::File.image(theme)) | (ui.add(button))).inner
     }
 }
 
@@ -563,23 +1852,17 @@ impl Widget for &mut Browser {
             ui.horizontal(|ui| {
                 ui.spacing_mut().item_spacing.x = 16.;
                 ui.columns_const(|uis| {
-                    zip(Category::VARIANTS, uis.each_mut())
-                        .map(|(category, ui)| {
-                            let selected = self.selected_category == category;
-                            let string = category.to_string();
-                            let response = ui.add(Browser::button(&self.theme, selected, &string));
-                            if response.clicked() {
-                                self.selected_category = category;
-                            }
-                            response
-                        })
-                        .into_iter()
-                        .reduce(Response::bitor)
-                        .unwrap()
-                })
+                    for (category, ui) in zip(Category::VARIANTS, uis.each_mut()) {
+                        let selected = self.selected_category == category;
+                        let string = category.to_string();
+                        if ui.add(Browser::button(&self.theme, selected, &string)).on_hover_text(category.description()).clicked() {
+                            self.selected_category = category;
+                        }
+                    }
+                });
             });
             ui.add_space(4.);
-            ui.visuals_mut().extreme_bg_color = Color32::from_hex("#7676a340").unwrap();
+            ui.visuals_mut().extreme_bg_color = hex_color!("7676a340");
             // ui.style_mut().spacing.scroll.floating = false;
             let scroll_area = ScrollArea::both()
                 .drag_to_scroll(false)
@@ -591,10 +1874,8 @@ impl Widget for &mut Browser {
                 .show(ui, |ui| {
                     match self.selected_category {
                         Category::Files => self.add_files(ui, scroll_area, browser_width),
-                        Category::Devices => {
-                            // TODO: Show some devices here!
-                            ui.label("Devices")
-                        }
+                        Category::Devices => self.add_devices(ui),
+                        Category::Plugins => self.add_plugins(ui),
                     }
                 })
                 .response
@@ -602,3 +1883,107 @@ impl Widget for &mut Browser {
         .inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        rc::Rc,
+        slice,
+        sync::Arc,
+        task::Poll,
+        time::Instant,
+    };
+
+    use crossbeam_channel::never;
+    use itertools::Itertools;
+
+    use super::{sort::Sort, CachedEntries, Entry, EntryData, EntryKind, EntryKinds, FsWatcherCache, Listed, Subtrees, BUDGET};
+
+    const FOLDERS: usize = 100;
+    const FILES: usize = 1000;
+
+    /// Return the listings of a root holding [`FOLDERS`] folders of [`FILES`] audio files each, along with the root and the folders.
+    fn fixture() -> (FsWatcherCache<CachedEntries>, PathBuf, Vec<Arc<Path>>) {
+        let root = PathBuf::from("/fixture");
+        let folders = (0..FOLDERS).map(|folder| Arc::from(root.join(format!("folder {folder:03}")))).collect_vec();
+        let mut cached_entries = FsWatcherCache::default();
+        let mut list = |path: &Path, listed: Vec<Listed>| {
+            cached_entries.data.insert(path.to_path_buf(), CachedEntries { rx: never(), data: Poll::Ready(Arc::from(listed)) });
+        };
+        list(&root, folders.iter().map(|folder| (EntryKind::Directory, Arc::clone(folder))).collect());
+        for folder in &folders {
+            list(folder, (0..FILES).map(|file| (EntryKind::Audio, Arc::from(folder.join(format!("{file:04}.wav"))))).collect());
+        }
+        (cached_entries, root, folders)
+    }
+
+    fn path(entry: &Entry) -> &Path {
+        match &entry.data {
+            Poll::Ready(EntryData { path, .. }) => path,
+            Poll::Pending => panic!("{entry:?} is still pending"),
+        }
+    }
+
+    #[test]
+    fn a_hundred_thousand_entries_are_flattened_in_order() {
+        let (mut cached_entries, root, folders) = fixture();
+        let mut expanded = vec![Arc::from(root.as_path())];
+        expanded.extend(folders.iter().cloned());
+        let (mut subtrees, mut entry_kinds) = (Subtrees::default(), EntryKinds::default());
+        let entries = subtrees.flatten(slice::from_ref(&root), &expanded, Sort::default(), &mut cached_entries, &mut entry_kinds);
+        assert_eq!(entries.len(), 1 + FOLDERS * (FILES + 1));
+        assert_eq!(entries[0].depth, 0);
+        let (folder, file) = (&entries[1 + 3 * (FILES + 1)], &entries[1 + 3 * (FILES + 1) + 8]);
+        assert_eq!((path(folder), folder.depth), (&*folders[3], 1));
+        assert_eq!((path(file), file.depth), (&*folders[3].join("0007.wav"), 2));
+    }
+
+    #[test]
+    fn only_the_folders_that_changed_are_gone_through_again() {
+        let (mut cached_entries, root, folders) = fixture();
+        let mut expanded = vec![Arc::from(root.as_path())];
+        expanded.extend(folders.iter().cloned());
+        let (mut subtrees, mut entry_kinds) = (Subtrees::default(), EntryKinds::default());
+        subtrees.flatten(slice::from_ref(&root), &expanded, Sort::default(), &mut cached_entries, &mut entry_kinds);
+        let subtree = |subtrees: &Subtrees, folder: &Path| Rc::clone(&subtrees.data[folder].expanded);
+        let (kept, collapsed) = (subtree(&subtrees, &folders[0]), subtree(&subtrees, &root));
+
+        expanded.retain(|path| *path != folders[FOLDERS - 1]);
+        subtrees.retain(&expanded);
+        let entries = subtrees.flatten(slice::from_ref(&root), &expanded, Sort::default(), &mut cached_entries, &mut entry_kinds);
+        assert_eq!(entries.len(), 1 + FOLDERS * (FILES + 1) - FILES);
+        assert!(Rc::ptr_eq(&subtree(&subtrees, &folders[0]), &kept));
+        assert!(!subtrees.data.contains_key(&folders[FOLDERS - 1]));
+        assert!(!Rc::ptr_eq(&subtree(&subtrees, &root), &collapsed));
+
+        let unchanged = subtree(&subtrees, &folders[1]);
+        let changed = folders[0].join("new.wav");
+        cached_entries.data.insert(folders[0].to_path_buf(), CachedEntries { rx: never(), data: Poll::Ready(Arc::from([(EntryKind::Audio, Arc::from(changed.as_path()))])) });
+        let entries = subtrees.flatten(slice::from_ref(&root), &expanded, Sort::default(), &mut cached_entries, &mut entry_kinds);
+        assert_eq!(path(&entries[2]), changed);
+        assert!(Rc::ptr_eq(&subtree(&subtrees, &folders[1]), &unchanged));
+        assert!(!Rc::ptr_eq(&subtree(&subtrees, &folders[0]), &kept));
+    }
+
+    #[test]
+    fn a_hundred_thousand_entries_are_flattened_within_the_budget() {
+        let (mut cached_entries, root, folders) = fixture();
+        let mut expanded = vec![Arc::from(root.as_path())];
+        expanded.extend(folders.iter().cloned());
+        let (mut subtrees, mut entry_kinds) = (Subtrees::default(), EntryKinds::default());
+        let started = Instant::now();
+        let entries = subtrees.flatten(slice::from_ref(&root), &expanded, Sort::default(), &mut cached_entries, &mut entry_kinds);
+        let elapsed = started.elapsed();
+        assert!(elapsed < BUDGET, "building the list took {elapsed:?}");
+        assert_eq!(entries.len(), 1 + FOLDERS * (FILES + 1));
+
+        let changed = folders[0].join("new.wav");
+        cached_entries.data.insert(folders[0].to_path_buf(), CachedEntries { rx: never(), data: Poll::Ready(Arc::from([(EntryKind::Audio, Arc::from(changed.as_path()))])) });
+        let started = Instant::now();
+        let entries = subtrees.flatten(slice::from_ref(&root), &expanded, Sort::default(), &mut cached_entries, &mut entry_kinds);
+        let elapsed = started.elapsed();
+        assert!(elapsed < BUDGET, "building the list again after a folder changed took {elapsed:?}");
+        assert_eq!(entries.len(), 1 + (FOLDERS - 1) * (FILES + 1) + 2);
+    }
+}
@@ -0,0 +1,48 @@
+use std::{collections::BTreeSet, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{search::fuzzy_match, tags::Tags};
+use crate::{config, engine::tempo_in_name};
+
+/// Where the collections are kept between sessions.
+const PATH: &str = "collections.toml";
+
+/// A saved search, shown in the browser as a folder of every indexed file that matches it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    /// Matched loosely against file names like the search field, so that an empty query matches every file.
+    pub query: String,
+    /// The tags that files need all of.
+    pub tags: BTreeSet<String>,
+    /// The lowest and highest tempo in BPM, going by the tempo in file names like `drums_95bpm.wav`, or [`None`] to match files with any tempo or none.
+    pub tempo: Option<(f64, f64)>,
+}
+
+impl Collection {
+    /// Return whether the file at `path`, with `tags` given to files, belongs in the collection.
+    pub fn matches(&self, path: &Path, tags: &Tags) -> bool {
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_lowercase()) else {
+            return false;
+        };
+        fuzzy_match(&self.query, &name).is_some()
+            && (self.tags.is_empty() || tags.has_all(path, &self.tags))
+            && self.tempo.is_none_or(|(lowest, highest)| tempo_in_name(&name).is_some_and(|tempo| (lowest..=highest).contains(&tempo)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Collections {
+    collections: Vec<Collection>,
+}
+
+/// Return the collections saved in the last session.
+pub fn load() -> Vec<Collection> {
+    config::load(PATH, "the collections").map_or_else(Vec::new, |collections: Collections| collections.collections)
+}
+
+pub fn save(collections: &[Collection]) {
+    let collections = Collections { collections: collections.to_vec() };
+    config::save(PATH, &collections, "the collections");
+}
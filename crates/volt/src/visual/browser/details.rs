@@ -0,0 +1,184 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use blerp::{
+    processing::analysis::{analyse, Analysis, Key},
+    wavefile::WaveFormat,
+};
+use cpal::Sample;
+use rodio::{Decoder, Source};
+use tracing::error;
+
+use super::lazy_cache::LazyCache;
+use crate::{archive, midi, tasks::Kind};
+
+/// How much of the start of a file is read to find the format of wave files.
+const HEADER_LENGTH: u64 = 64 * 1024;
+/// How much of the start of a file is analysed, which is plenty to find the tempo and key of a sample while keeping long files quick.
+const ANALYSED_LENGTH: Duration = Duration::from_mins(1);
+
+/// What the headers of an audio file say about it.
+#[derive(Debug, Clone, Copy)]
+pub struct Details {
+    /// How long the audio is, if the format says so without decoding all of it.
+    pub duration: Option<Duration>,
+    pub sample_rate: u32,
+    /// The bits per sample, which is only known for wave files.
+    pub bit_depth: Option<u16>,
+    pub channels: u16,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// Return a cache of the details of audio files.
+pub fn cache() -> LazyCache<Details> {
+    LazyCache::new(Kind::Analysis, |path| read(path).inspect_err(|error| error!("Couldn't read the details of {}: {error}", path.display())).ok())
+}
+
+fn read(path: &Path) -> Result<Details, String> {
+    let mut file = archive::open(path).map_err(|error| error.to_string())?;
+    let size = file.seek(SeekFrom::End(0)).map_err(|error| error.to_string())?;
+    file.rewind().map_err(|error| error.to_string())?;
+    let mut start = Vec::new();
+    file.by_ref().take(HEADER_LENGTH).read_to_end(&mut start).map_err(|error| error.to_string())?;
+    file.rewind().map_err(|error| error.to_string())?;
+    let wave = WaveFormat::read(&start).ok();
+    let decoder = Decoder::new(file).map_err(|error| error.to_string())?;
+    Ok(Details {
+        duration: decoder.total_duration(),
+        sample_rate: decoder.sample_rate(),
+        bit_depth: wave.map(|wave| wave.bytes_per_sample * 8),
+        channels: decoder.channels(),
+        size,
+    })
+}
+
+/// Return a cache of the tempo and key of audio files, which decodes them so it's only used for the files that are shown.
+pub fn analysis_cache() -> LazyCache<Analysis> {
+    LazyCache::new(Kind::Analysis, |path| {
+        let decoder = archive::open(path)
+            .map_err(|error| error.to_string())
+            .and_then(|file| Decoder::new(file).map_err(|error| error.to_string()))
+            .inspect_err(|error| error!("Couldn't analyse {}: {error}", path.display()))
+            .ok()?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        #[allow(clippy::cast_possible_truncation, reason = "the length is well within range")]
+        let length = ANALYSED_LENGTH.as_secs() as usize * sample_rate as usize * usize::from(channels);
+        let samples = decoder.take(length).map(f64::from_sample).collect::<Vec<_>>();
+        Some(analyse(&samples, usize::from(channels), f64::from(sample_rate)))
+    })
+}
+
+/// Limits on the tempo and key of audio files, to only show those that analysis found to be in them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AnalysisFilter {
+    /// The lowest and highest tempo in BPM.
+    pub tempo: Option<(f64, f64)>,
+    pub key: Option<Key>,
+}
+
+impl AnalysisFilter {
+    pub const fn is_empty(&self) -> bool {
+        self.tempo.is_none() && self.key.is_none()
+    }
+
+    pub fn matches(&self, analysis: &Analysis) -> bool {
+        let tempo = self.tempo.is_none_or(|(lowest, highest)| analysis.tempo.is_some_and(|tempo| (lowest..=highest).contains(&tempo.round())));
+        tempo && self.key.is_none_or(|key| analysis.key == Some(key))
+    }
+}
+
+/// What a MIDI file holds, shown next to its entry.
+#[derive(Debug, Clone, Copy)]
+pub struct MidiDetails {
+    pub notes: usize,
+    /// How long the file is at its own tempo.
+    pub duration: Duration,
+}
+
+impl MidiDetails {
+    pub fn text(&self) -> String {
+        format!("{} notes, {}:{:>02}", self.notes, self.duration.as_secs() / 60, self.duration.as_secs() % 60)
+    }
+}
+
+/// Return a cache of the details of MIDI files.
+pub fn midi_cache() -> LazyCache<MidiDetails> {
+    LazyCache::new(Kind::Analysis, |path| {
+        midi::read(path)
+            .inspect_err(|error| error!("Couldn't read the MIDI file {}: {error}", path.display()))
+            .ok()
+            .map(|file| MidiDetails { notes: file.notes.len(), duration: Duration::from_secs_f64(file.duration()) })
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Duration,
+    SampleRate,
+    BitDepth,
+    Channels,
+    Size,
+    Tempo,
+    Key,
+}
+
+impl Column {
+    pub const VARIANTS: [Self; 7] = [Self::Duration, Self::SampleRate, Self::BitDepth, Self::Channels, Self::Size, Self::Tempo, Self::Key];
+    /// How wide every column is.
+    pub const WIDTH: f32 = 60.;
+
+    pub const fn title(self) -> &'static str {
+        match self {
+            Self::Duration => "Duration",
+            Self::SampleRate => "Rate",
+            Self::BitDepth => "Depth",
+            Self::Channels => "Channels",
+            Self::Size => "Size",
+            Self::Tempo => "BPM",
+            Self::Key => "Key",
+        }
+    }
+
+    /// Return whether the column shows what analysis found, rather than what the headers say.
+    pub const fn is_analysed(self) -> bool {
+        matches!(self, Self::Tempo | Self::Key)
+    }
+
+    /// Return what to show in this column for a file with `details`, and with `analysis` once it has been analysed.
+    pub fn text(self, details: &Details, analysis: Option<&Analysis>) -> String {
+        match self {
+            Self::Duration => details.duration.map_or_else(
+                || "-".into(),
+                |duration| {
+                    if duration.as_secs() < 60 {
+                        format!("{:.2} s", duration.as_secs_f64())
+                    } else {
+                        format!("{}:{:>02}", duration.as_secs() / 60, duration.as_secs() % 60)
+                    }
+                },
+            ),
+            Self::SampleRate => format!("{} kHz", f64::from(details.sample_rate) / 1000.),
+            Self::BitDepth => details.bit_depth.map_or_else(|| "-".into(), |bits| format!("{bits}-bit")),
+            Self::Channels => match details.channels {
+                1 => "Mono".into(),
+                2 => "Stereo".into(),
+                channels => channels.to_string(),
+            },
+            Self::Tempo => analysis.map_or_else(|| "…".into(), |analysis| analysis.tempo.map_or_else(|| "-".into(), |tempo| format!("{tempo:.0}"))),
+            Self::Key => analysis.map_or_else(|| "…".into(), |analysis| analysis.key.map_or_else(|| "-".into(), |key| key.to_string())),
+            Self::Size => {
+                #[allow(clippy::cast_precision_loss, reason = "only used for showing")]
+                let size = details.size as f64;
+                if size < 1024. * 1024. {
+                    format!("{:.0} KB", size / 1024.)
+                } else {
+                    format!("{:.1} MB", size / 1024. / 1024.)
+                }
+            }
+        }
+    }
+}
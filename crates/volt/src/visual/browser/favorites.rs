@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Where the favorites are kept between sessions.
+const PATH: &str = "favorites.toml";
+
+#[derive(Serialize, Deserialize)]
+struct Favorites {
+    paths: Vec<PathBuf>,
+}
+
+/// Return the favorites saved in the last session.
+pub fn load() -> Vec<PathBuf> {
+    config::load(PATH, "the favorites").map_or_else(Vec::new, |favorites: Favorites| favorites.paths)
+}
+
+pub fn save(paths: &[PathBuf]) {
+    let favorites = Favorites { paths: paths.to_vec() };
+    config::save(PATH, &favorites, "the favorites");
+}
@@ -0,0 +1,513 @@
+//! Searching Freesound for samples, previewing them and downloading them into a library folder along with their license.
+//!
+//! Requests are made with `curl`, so that no HTTP client has to be built in, and secrets are given to it on its standard input rather than in its arguments.
+//! The API key and the session are kept in the system's keychain. Searching and previewing only need the API key, which is the client secret of the
+//! application registered on Freesound, while downloading the original files of sounds needs signing in with `OAuth2` first.
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::Receiver;
+use egui::{Button, Key, RichText, ScrollArea, TextEdit, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config, keychain,
+    tasks::{self, Job, Kind, Priority},
+    visual::dialog::pick_folder,
+};
+
+const API: &str = "https://freesound.org/apiv2";
+/// Where the client id and the library folder are kept between sessions.
+const SETTINGS_PATH: &str = "freesound.toml";
+/// The names of the API key and of the token that renews the session in the keychain.
+const API_KEY: &str = "freesound-api-key";
+const REFRESH_TOKEN: &str = "freesound-refresh-token";
+/// How many sounds are asked for in a search.
+const PAGE_SIZE: usize = 30;
+/// How long before it expires a session is renewed, so that it doesn't expire during a download.
+const EXPIRY_MARGIN: Duration = Duration::from_mins(1);
+
+#[derive(Serialize, Deserialize)]
+struct Settings {
+    /// The API key, which was kept here before it was kept in the keychain. It's moved there when it's found, and never saved here again.
+    #[serde(default, skip_serializing)]
+    api_key: String,
+    /// The client id of the application registered on Freesound, which signing in needs.
+    #[serde(default)]
+    client_id: String,
+    /// Where downloaded sounds go.
+    library: PathBuf,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            client_id: String::new(),
+            library: PathBuf::from("freesound"),
+        }
+    }
+}
+
+/// A sound found on Freesound, as the search gives it.
+#[derive(Debug, Clone, Deserialize)]
+struct Sound {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    username: String,
+    /// The URL of the license the sound is shared under.
+    #[serde(default)]
+    license: String,
+    #[serde(default)]
+    duration: f64,
+    /// The format of the original file, which is its extension.
+    #[serde(rename = "type", default)]
+    format: String,
+    previews: Previews,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Previews {
+    /// The URL of the high quality MP3 preview.
+    #[serde(rename = "preview-hq-mp3")]
+    high_quality_mp3: String,
+}
+
+#[derive(Deserialize)]
+struct Results {
+    results: Vec<Sound>,
+}
+
+/// What Freesound answers when signing in or renewing the session.
+#[derive(Deserialize)]
+struct Tokens {
+    access_token: String,
+    refresh_token: String,
+    /// How many seconds the access token lasts.
+    expires_in: u64,
+}
+
+/// What is kept next to a downloaded sound, so that its license and author are known when it's used.
+#[derive(Serialize)]
+struct Attribution<'a> {
+    name: &'a str,
+    author: &'a str,
+    license: &'a str,
+    url: String,
+}
+
+/// A sound being downloaded.
+struct Download {
+    id: u64,
+    /// Whether the sound is played once it's downloaded, rather than kept in the library.
+    previewed: bool,
+    rx: Receiver<Result<PathBuf, String>>,
+}
+
+/// What's needed to download the original files of sounds.
+struct Session {
+    access_token: String,
+    expires: Instant,
+}
+
+/// The online sources section of the browser, which searches Freesound.
+pub struct Freesound {
+    settings: Settings,
+    /// The API key, which is read from the keychain in the background at first, and defaults to the `FREESOUND_API_KEY` environment variable.
+    api_key: String,
+    /// Whether a token to renew the session is kept in the keychain, so that the user doesn't have to sign in again.
+    signed_in: bool,
+    session: Option<Session>,
+    /// The keychain being read, or written to once the API key is changed or the user signs in or out.
+    keychain: Option<Receiver<Result<Keychain, String>>>,
+    /// The code Freesound shows once the user allowed Volt to download for them, being typed.
+    code: String,
+    /// Signing in or renewing the session, in the background.
+    signing_in: Option<Receiver<Result<Tokens, String>>>,
+    /// Sounds to download once the user is signed in.
+    waiting: Vec<Sound>,
+    query: String,
+    results: Vec<Sound>,
+    searching: Option<Receiver<Result<Vec<Sound>, String>>>,
+    downloads: Vec<Download>,
+    picking_library: Option<Receiver<Option<PathBuf>>>,
+    /// What went wrong with the last search, download or access to the keychain.
+    error: Option<String>,
+}
+
+/// What was read from the keychain.
+struct Keychain {
+    api_key: Option<String>,
+    signed_in: bool,
+}
+
+impl Freesound {
+    pub fn new() -> Self {
+        let mut settings: Settings = config::load(SETTINGS_PATH, "the Freesound settings").unwrap_or_default();
+        let legacy_key = std::mem::take(&mut settings.api_key);
+        let keychain = tasks::spawn(Job::new(Kind::Network, "Reading the Freesound API key"), move || {
+            if !legacy_key.is_empty() {
+                keychain::set(API_KEY, &legacy_key)?;
+            }
+            Ok(Keychain {
+                api_key: keychain::get(API_KEY)?,
+                signed_in: keychain::get(REFRESH_TOKEN)?.is_some(),
+            })
+        });
+        let freesound = Self {
+            settings,
+            api_key: String::new(),
+            signed_in: false,
+            session: None,
+            keychain: Some(keychain),
+            code: String::new(),
+            signing_in: None,
+            waiting: Vec::new(),
+            query: String::new(),
+            results: Vec::new(),
+            searching: None,
+            downloads: Vec::new(),
+            picking_library: None,
+            error: None,
+        };
+        // A key that was kept in the settings is left out of them once they're saved again.
+        freesound.save_settings();
+        freesound
+    }
+
+    fn save_settings(&self) {
+        config::save(SETTINGS_PATH, &self.settings, "the Freesound settings");
+    }
+
+    /// Keep the API key in the keychain in the background.
+    fn save_api_key(&mut self) {
+        let api_key = self.api_key.clone();
+        let signed_in = self.signed_in;
+        self.keychain = Some(tasks::spawn(Job::new(Kind::Network, "Keeping the Freesound API key"), move || {
+            keychain::set(API_KEY, &api_key)?;
+            Ok(Keychain { api_key: Some(api_key), signed_in })
+        }));
+    }
+
+    /// Show the search field and the sounds found. Returns a preview that finished downloading, to be played.
+    pub fn show(&mut self, ui: &mut Ui) -> Option<PathBuf> {
+        let preview = self.poll();
+        ui.horizontal(|ui| {
+            ui.label("API key");
+            let response = ui.add_enabled(self.keychain.is_none(), TextEdit::singleline(&mut self.api_key).password(true).desired_width(160.));
+            if response.on_hover_text("Kept in the system's keychain").lost_focus() {
+                self.save_api_key();
+            }
+        });
+        self.add_account(ui);
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("Library: {}", self.settings.library.display())).weak());
+            if ui.add_enabled(self.picking_library.is_none(), Button::new("Change…").small()).clicked() {
+                self.picking_library = Some(pick_folder());
+            }
+        });
+        ui.horizontal(|ui| {
+            let response = ui.add(TextEdit::singleline(&mut self.query).hint_text("Search Freesound").desired_width(160.));
+            let entered = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+            let can_search = self.searching.is_none() && !self.query.trim().is_empty() && !self.api_key.is_empty();
+            if ui.add_enabled(can_search, Button::new("Search")).clicked() || (entered && can_search) {
+                self.searching = Some(search(self.query.trim().to_string(), self.api_key.clone()));
+                self.error = None;
+            }
+            if self.searching.is_some() {
+                ui.spinner();
+            }
+        });
+        if self.api_key.is_empty() && self.keychain.is_none() {
+            ui.label(RichText::new("Searching needs an API key from freesound.org/apiv2/apply").weak());
+        }
+        if let Some(error) = &self.error {
+            ui.label(RichText::new(error).color(ui.visuals().error_fg_color));
+        }
+        let mut started = Vec::new();
+        ScrollArea::vertical().id_salt("freesound").max_height(200.).auto_shrink([false, true]).show(ui, |ui| {
+            for sound in &self.results {
+                let downloading = self.downloads.iter().any(|download| download.id == sound.id) || self.waiting.iter().any(|waiting| waiting.id == sound.id);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!downloading, Button::new("▶").small()).on_hover_text("Preview").clicked() {
+                        started.push((sound.clone(), true));
+                    }
+                    let can_download = !downloading && (self.signed_in || self.session.is_some());
+                    if ui.add_enabled(can_download, Button::new("⬇").small()).on_hover_text("Download the original file into the library").clicked() {
+                        started.push((sound.clone(), false));
+                    }
+                    if downloading {
+                        ui.spinner();
+                    }
+                    ui.label(RichText::new(&sound.name).size(12.)).on_hover_text(format!("by {}, {}", sound.username, sound.license));
+                    ui.label(RichText::new(format!("{:.1} s", sound.duration)).size(10.).weak());
+                });
+            }
+        });
+        for (sound, previewed) in started {
+            if previewed {
+                let rx = download(sound.clone(), env::temp_dir().join("volt-freesound"), None);
+                self.downloads.push(Download { id: sound.id, previewed, rx });
+            } else {
+                self.waiting.push(sound);
+            }
+        }
+        self.start_waiting();
+        preview
+    }
+
+    /// Show how to sign in to download the original files of sounds, or how to sign out.
+    fn add_account(&mut self, ui: &mut Ui) {
+        if self.signed_in || self.session.is_some() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Signed in to download original files").weak());
+                if ui.add_enabled(self.keychain.is_none(), Button::new("Sign out").small()).clicked() {
+                    self.session = None;
+                    self.signed_in = false;
+                    let api_key = self.api_key.clone();
+                    self.keychain = Some(tasks::spawn(Job::new(Kind::Network, "Signing out of Freesound"), move || {
+                        keychain::set(REFRESH_TOKEN, "")?;
+                        Ok(Keychain { api_key: Some(api_key), signed_in: false })
+                    }));
+                }
+            });
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Client id");
+            if ui.add(TextEdit::singleline(&mut self.settings.client_id).desired_width(160.)).lost_focus() {
+                self.save_settings();
+            }
+        });
+        ui.horizontal(|ui| {
+            let can_sign_in = !self.settings.client_id.trim().is_empty() && !self.api_key.is_empty();
+            if ui.add_enabled(can_sign_in, Button::new("Sign in…").small()).on_hover_text("Allow Volt to download original files for you").clicked() {
+                let url = format!("{API}/oauth2/authorize/?client_id={}&response_type=code", encode(self.settings.client_id.trim()));
+                if let Err(error) = open::that_detached(&url) {
+                    self.error = Some(format!("Couldn't open {url}, {error}"));
+                }
+            }
+            ui.add(TextEdit::singleline(&mut self.code).hint_text("Code shown").desired_width(100.));
+            let can_use = can_sign_in && !self.code.trim().is_empty() && self.signing_in.is_none();
+            if ui.add_enabled(can_use, Button::new("Use").small()).clicked() {
+                let form = format!(
+                    "client_id={}&client_secret={}&grant_type=authorization_code&code={}",
+                    encode(self.settings.client_id.trim()),
+                    encode(&self.api_key),
+                    encode(self.code.trim())
+                );
+                self.signing_in = Some(sign_in(form));
+                self.code.clear();
+            }
+            if self.signing_in.is_some() {
+                ui.spinner();
+            }
+        });
+        ui.label(RichText::new("Downloading original files needs signing in with the client id of the API key").weak());
+    }
+
+    /// Download the sounds that are waiting once there's a session, renewing it first if it expired.
+    fn start_waiting(&mut self) {
+        if self.waiting.is_empty() || self.signing_in.is_some() {
+            return;
+        }
+        match self.session.as_ref().filter(|session| session.expires > Instant::now() + EXPIRY_MARGIN) {
+            Some(session) => {
+                for sound in self.waiting.drain(..) {
+                    let rx = download(sound.clone(), self.settings.library.clone(), Some(session.access_token.clone()));
+                    self.downloads.push(Download { id: sound.id, previewed: false, rx });
+                }
+            }
+            None if self.signed_in => self.signing_in = Some(renew(self.settings.client_id.trim().to_string(), self.api_key.clone())),
+            None => {
+                self.waiting.clear();
+                self.error = Some("Sign in to download the original files of sounds".into());
+            }
+        }
+    }
+
+    /// Pick up finished searches, downloads and changes to the session, returning a downloaded preview.
+    fn poll(&mut self) -> Option<PathBuf> {
+        if let Some(Ok(result)) = self.keychain.as_ref().map(Receiver::try_recv) {
+            self.keychain = None;
+            match result {
+                Ok(Keychain { api_key, signed_in }) => {
+                    self.api_key = api_key.or_else(|| env::var("FREESOUND_API_KEY").ok()).unwrap_or_default();
+                    self.signed_in = signed_in;
+                }
+                Err(error) => self.error = Some(format!("Couldn't reach the keychain, {error}")),
+            }
+        }
+        if let Some(Ok(result)) = self.signing_in.as_ref().map(Receiver::try_recv) {
+            self.signing_in = None;
+            match result {
+                Ok(tokens) => {
+                    self.signed_in = true;
+                    self.session = Some(Session {
+                        access_token: tokens.access_token,
+                        expires: Instant::now() + Duration::from_secs(tokens.expires_in),
+                    });
+                }
+                Err(error) => {
+                    self.signed_in = false;
+                    self.session = None;
+                    self.waiting.clear();
+                    self.error = Some(error);
+                }
+            }
+        }
+        if let Some(Ok(result)) = self.searching.as_ref().map(Receiver::try_recv) {
+            self.searching = None;
+            match result {
+                Ok(results) => self.results = results,
+                Err(error) => self.error = Some(error),
+            }
+        }
+        if let Some(Ok(picked)) = self.picking_library.as_ref().map(Receiver::try_recv) {
+            self.picking_library = None;
+            if let Some(library) = picked {
+                self.settings.library = library;
+                self.save_settings();
+            }
+        }
+        let mut preview = None;
+        self.downloads.retain(|download| match download.rx.try_recv() {
+            Ok(Ok(path)) => {
+                if download.previewed {
+                    preview = Some(path);
+                }
+                false
+            }
+            Ok(Err(error)) => {
+                self.error = Some(error);
+                false
+            }
+            Err(_) => true,
+        });
+        preview
+    }
+}
+
+/// Search Freesound for `query` on another thread.
+fn search(query: String, api_key: String) -> Receiver<Result<Vec<Sound>, String>> {
+    tasks::spawn(Job::new(Kind::Network, format!("Searching Freesound for {query}")), move || {
+        let url = format!("{API}/search/text/?query={}&fields=id,name,username,license,duration,type,previews&page_size={PAGE_SIZE}", encode(&query));
+        let result = curl(&["--header", "@-", &url], &format!("Authorization: Token {api_key}\n"))
+            .and_then(|body| serde_json::from_slice::<Results>(&body).map_err(|error| error.to_string()));
+        result.map(|results| results.results).map_err(|error| format!("Couldn't search Freesound, {error}"))
+    })
+}
+
+/// Sign in to Freesound with the `form` of the `OAuth2` code the user was given, on another thread. The token that renews the session is kept in the keychain.
+fn sign_in(form: String) -> Receiver<Result<Tokens, String>> {
+    tasks::spawn(Job::new(Kind::Network, "Signing in to Freesound"), move || {
+        let tokens = request_tokens(&form).map_err(|error| format!("Couldn't sign in to Freesound, {error}"))?;
+        keychain::set(REFRESH_TOKEN, &tokens.refresh_token).map_err(|error| format!("Couldn't keep the Freesound session in the keychain, {error}"))?;
+        Ok(tokens)
+    })
+}
+
+/// Renew the session with the token kept in the keychain, on another thread, keeping the new one there.
+fn renew(client_id: String, api_key: String) -> Receiver<Result<Tokens, String>> {
+    tasks::spawn(Job::new(Kind::Network, "Renewing the Freesound session"), move || {
+        let refresh_token = keychain::get(REFRESH_TOKEN)?.ok_or("Sign in to Freesound again")?;
+        let form = format!("client_id={}&client_secret={}&grant_type=refresh_token&refresh_token={}", encode(&client_id), encode(&api_key), encode(&refresh_token));
+        let tokens = request_tokens(&form).map_err(|error| format!("Couldn't renew the Freesound session, sign in again. {error}"))?;
+        keychain::set(REFRESH_TOKEN, &tokens.refresh_token)?;
+        Ok(tokens)
+    })
+}
+
+/// Post `form` to the token endpoint, returning the tokens Freesound answers with.
+fn request_tokens(form: &str) -> Result<Tokens, String> {
+    let body = curl(&["--data", "@-", &format!("{API}/oauth2/access_token/")], form)?;
+    serde_json::from_slice(&body).map_err(|error| error.to_string())
+}
+
+/// Download `sound` into `folder` on another thread, returning where it went. With an `access_token`, it's the original file that's downloaded and kept along
+/// with its license. Otherwise it's the preview, which is only heard, and previews downloaded before are reused.
+fn download(sound: Sound, folder: PathBuf, access_token: Option<String>) -> Receiver<Result<PathBuf, String>> {
+    // Previews are downloaded to be heard right away.
+    let priority = if access_token.is_some() { Priority::Normal } else { Priority::High };
+    let job = Job::new(Kind::Network, format!("Downloading {}", sound.name)).priority(priority);
+    tasks::spawn(job, move || {
+        let name = format!("{}_{}", sound.id, sound.name.replace(['/', '\\', ':'], "_"));
+        let extension = if access_token.is_some() && !sound.format.is_empty() { sound.format.as_str() } else { "mp3" };
+        let path = folder.join(Path::new(&name).with_extension(extension));
+        let result = fs::create_dir_all(&folder).map_err(|error| error.to_string()).and_then(|()| match &access_token {
+            Some(access_token) => {
+                let url = format!("{API}/sounds/{}/download/", sound.id);
+                fetch(&path, &["--header", "@-", &url], &format!("Authorization: Bearer {access_token}\n"))
+                    .and_then(|()| write_license(&sound, &folder.join(format!("{name}.license.toml"))))
+            }
+            None if path.exists() => Ok(()),
+            None => fetch(&path, &[&sound.previews.high_quality_mp3], ""),
+        });
+        result.map(|()| path).map_err(|error| format!("Couldn't download {}, {error}", sound.name))
+    })
+}
+
+fn write_license(sound: &Sound, path: &Path) -> Result<(), String> {
+    let attribution = Attribution {
+        name: &sound.name,
+        author: &sound.username,
+        license: &sound.license,
+        url: format!("https://freesound.org/people/{}/sounds/{}/", sound.username, sound.id),
+    };
+    toml::to_string(&attribution).map_err(|error| error.to_string()).and_then(|text| fs::write(path, text).map_err(|error| error.to_string()))
+}
+
+/// Run `curl` with `args`, giving it `input` on its standard input, and return what it downloaded.
+fn curl(args: &[&str], input: &str) -> Result<Vec<u8>, String> {
+    run_curl(args.iter().map(OsStr::new), input)
+}
+
+/// Download to `path` with `curl`, like [`curl`] does.
+fn fetch(path: &Path, args: &[&str], input: &str) -> Result<(), String> {
+    let result = run_curl([OsStr::new("--output"), path.as_os_str()].into_iter().chain(args.iter().map(OsStr::new)), input);
+    if result.is_err() {
+        // A failed download leaves part of the file behind, which would be mistaken for a finished one.
+        let _ = fs::remove_file(path);
+    }
+    result.map(|_| ())
+}
+
+fn run_curl<'a>(args: impl IntoIterator<Item = &'a OsStr>, input: &str) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| if error.kind() == ErrorKind::NotFound { "curl isn't installed".into() } else { error.to_string() })?;
+    // The standard input is closed once it's dropped, so that curl doesn't wait for more.
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(|error| error.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|error| error.to_string())?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Percent-encode `text` to put it in a URL query or a form.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => char::from(byte).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::tasks::{self, Job, Kind};
+
+/// How the value of a file is worked out, or [`None`] if it can't be.
+type Work<T> = Arc<dyn Fn(&Path) -> Option<T> + Send + Sync>;
+
+/// The value of a file, as far as it has been worked out.
+pub enum Value<T> {
+    Pending,
+    /// The value couldn't be worked out, which isn't tried again until the cache is cleared.
+    Failed,
+    Ready(T),
+}
+
+/// Values worked out from files in the background the first time they're needed, which are kept afterwards, like the files they couldn't be worked
+/// out for.
+pub struct LazyCache<T> {
+    values: HashMap<PathBuf, Value<T>>,
+    /// What working out a value is listed as in the tasks.
+    kind: Kind,
+    work: Work<T>,
+    result_sender: Sender<(PathBuf, Option<T>)>,
+    results: Receiver<(PathBuf, Option<T>)>,
+    /// How many times values were worked out or forgotten, to find out whether what was made of them is out of date.
+    revision: u64,
+}
+
+impl<T: Clone + Send + 'static> LazyCache<T> {
+    /// Create a cache whose values are worked out by `work` as jobs of `kind`, which returns [`None`] for files it can't work with.
+    pub fn new(kind: Kind, work: impl Fn(&Path) -> Option<T> + Send + Sync + 'static) -> Self {
+        let (result_sender, results) = unbounded();
+        Self {
+            values: HashMap::new(),
+            kind,
+            work: Arc::new(work),
+            result_sender,
+            results,
+            revision: 0,
+        }
+    }
+
+    /// Return the value for the file at `path`, or [`None`] until it has been worked out or if it couldn't be.
+    pub fn get(&mut self, path: &Path) -> Option<T> {
+        self.receive();
+        let value = self.values.entry(path.to_path_buf()).or_insert_with(|| {
+            let (work, result_sender, path) = (Arc::clone(&self.work), self.result_sender.clone(), path.to_path_buf());
+            let job = Job::new(self.kind, path.display().to_string());
+            // The result goes to the cache rather than to the receiver of the job, so that all of them are taken at once.
+            let _ = tasks::spawn(job, move || {
+                let value = work(&path);
+                let _ = result_sender.send((path, value));
+            });
+            Value::Pending
+        });
+        match value {
+            Value::Ready(value) => Some(value.clone()),
+            Value::Pending | Value::Failed => None,
+        }
+    }
+
+    /// Return where the value for the file at `path` is at, without working it out if it wasn't, or [`None`] if it wasn't. Values that came in since
+    /// they were last received aren't taken.
+    pub fn peek(&self, path: &Path) -> Option<&Value<T>> {
+        self.values.get(path)
+    }
+
+    /// Return whether the value for the file at `path` is still being worked out.
+    pub fn is_pending(&self, path: &Path) -> bool {
+        matches!(self.values.get(path), Some(Value::Pending))
+    }
+
+    /// Return how many times values were worked out or forgotten so far.
+    pub fn revision(&mut self) -> u64 {
+        self.receive();
+        self.revision
+    }
+
+    /// Keep the values worked out since the last call.
+    fn receive(&mut self) {
+        for (path, value) in self.results.try_iter() {
+            self.values.insert(path, value.map_or(Value::Failed, Value::Ready));
+            self.revision += 1;
+        }
+    }
+
+    /// Return the values kept, leaving out the files still being worked on and the ones that couldn't be.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.values.values().filter_map(|value| if let Value::Ready(value) = value { Some(value) } else { None })
+    }
+
+    /// Forget every value, so that they're worked out again when they're next needed.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.revision += 1;
+    }
+}
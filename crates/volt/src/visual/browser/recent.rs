@@ -0,0 +1,32 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Where the recent files are kept between sessions.
+const PATH: &str = "recent.toml";
+/// How many files are remembered, after which the oldest are forgotten.
+const MAX: usize = 20;
+
+#[derive(Serialize, Deserialize)]
+struct Recent {
+    paths: Vec<PathBuf>,
+}
+
+/// Return the recent files saved in the last session, most recent first.
+pub fn load() -> Vec<PathBuf> {
+    config::load(PATH, "the recent files").map_or_else(Vec::new, |recent: Recent| recent.paths)
+}
+
+/// Move `path` to the top of the recent files in `paths`, forgetting the oldest if there are too many, and save them.
+pub fn add(paths: &mut Vec<PathBuf>, path: &Path) {
+    if paths.first().is_some_and(|first| first == path) {
+        return;
+    }
+    paths.retain(|other| other != path);
+    paths.insert(0, path.to_path_buf());
+    paths.truncate(MAX);
+    let recent = Recent { paths: paths.clone() };
+    config::save(PATH, &recent, "the recent files");
+}
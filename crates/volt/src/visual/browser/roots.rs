@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Where the roots are kept between sessions.
+const PATH: &str = "browser.toml";
+
+/// The roots of the browser and how they were left, which are restored in the next session.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub roots: Vec<PathBuf>,
+    /// The folders that were expanded.
+    pub expanded: Vec<PathBuf>,
+    /// How far the entries were scrolled down, in points.
+    pub scroll: f32,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self { roots: vec![PathBuf::from("/")], expanded: Vec::new(), scroll: 0. }
+    }
+}
+
+/// Return the session saved last time, or the default one if there is none.
+pub fn load() -> Session {
+    config::load(PATH, "the browser roots").unwrap_or_default()
+}
+
+pub fn save(session: &Session) {
+    config::save(PATH, session, "the browser roots");
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, PoisonError, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
+use egui::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    progress::Progress,
+    tasks::{self, Job, Kind, Priority},
+};
+
+/// How well a name matches a search, and which of its characters match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    /// The indices of the matching characters of the name, in order.
+    pub indices: Vec<usize>,
+}
+
+/// Match `name` against `query` loosely, so that the characters of the query have to appear in the name in order but not necessarily next to each other.
+/// Case and whitespace in the query are ignored.
+///
+/// Characters following the previous match or starting a word score higher, and gaps between matches score lower.
+pub fn fuzzy_match(query: &str, name: &str) -> Option<Match> {
+    let query = query.chars().filter(|character| !character.is_whitespace()).flat_map(char::to_lowercase).collect::<Vec<_>>();
+    let mut wanted = query.iter().peekable();
+    let mut score = 0;
+    let mut indices = Vec::<usize>::new();
+    let mut previous = None::<char>;
+    for (index, character) in name.chars().enumerate() {
+        let Some(&&next) = wanted.peek() else {
+            break;
+        };
+        if character.to_lowercase().next() == Some(next) {
+            let last = indices.last().copied();
+            let starts_word = previous.is_none_or(|previous| !previous.is_alphanumeric() || (previous.is_lowercase() && character.is_uppercase()));
+            score += 1;
+            if last.is_some_and(|last| last + 1 == index) {
+                score += 4;
+            } else if starts_word {
+                score += 3;
+            }
+            if let Some(last) = last {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, reason = "the gap is at most 3")]
+                let gap = (index - last - 1).min(3) as i32;
+                score -= gap;
+            }
+            indices.push(index);
+            wanted.next();
+        }
+        previous = Some(character);
+    }
+    wanted.peek().is_none().then_some(Match { score, indices })
+}
+
+/// Where the index is kept between sessions.
+const INDEX_PATH: &str = "browser_index.toml";
+/// How long after the roots were last gone through they're gone through again, to pick up changes to the files.
+const REINDEX_INTERVAL: Duration = Duration::from_mins(1);
+/// How many folders are read between updates of the revision, so that searches pick up new entries while a large library is being indexed.
+const FOLDERS_PER_REVISION: usize = 256;
+/// How many folders deep under a root the index goes, so that a root holding a huge tree of folders can't keep the index busy for long.
+const MAX_DEPTH: usize = 12;
+
+/// Every file and folder under the browser's roots, kept up to date by background jobs so that searches can look inside folders that aren't expanded.
+///
+/// Only folders that changed since they were last read are read again, and the index is saved so that it's ready right away in the next session.
+/// Roots that are a whole disk, like the one the browser starts with, are only browsed: the index doesn't go through them, nor into other file
+/// systems mounted under a root, like `/proc` or network shares.
+pub struct Index {
+    folders: Arc<RwLock<HashMap<PathBuf, Folder>>>,
+    /// Incremented whenever the index changes.
+    revision: Arc<AtomicU64>,
+    roots: Vec<PathBuf>,
+    /// Whether the roots changed since the last time they were gone through.
+    stale: bool,
+    /// The job going through the roots, if one is going on.
+    pass: Option<Receiver<()>>,
+    /// When the roots were last gone through.
+    indexed_at: Instant,
+    announce: Sender<Arc<Progress>>,
+    /// The progress of going through the roots, when it takes long enough to be shown.
+    progress: Receiver<Arc<Progress>>,
+}
+
+/// The contents of a folder, as of when it was last modified.
+#[derive(Serialize, Deserialize)]
+struct Folder {
+    path: PathBuf,
+    modified: SystemTime,
+    entries: Vec<IndexedEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedEntry {
+    name: String,
+    is_folder: bool,
+}
+
+/// The index as it is saved, with the folders by reference when saving it.
+#[derive(Serialize, Deserialize)]
+struct SavedIndex<F> {
+    folders: Vec<F>,
+}
+
+impl Index {
+    /// Load the index saved in the last session, which is kept up to date by [`Self::take_progress`].
+    pub fn new() -> Self {
+        let (announce, progress) = unbounded();
+        Self {
+            folders: Arc::new(RwLock::new(load())),
+            revision: Arc::new(AtomicU64::new(0)),
+            roots: Vec::new(),
+            stale: false,
+            pass: None,
+            indexed_at: Instant::now(),
+            announce,
+            progress,
+        }
+    }
+
+    /// Go through the roots again if they changed or haven't been gone through for a while, unless that's going on. Returns the progress of going
+    /// through them if it started taking long since the last call, which can be cancelled until the next time they're gone through.
+    pub fn take_progress(&mut self) -> Option<Arc<Progress>> {
+        if self.pass.as_ref().is_some_and(|pass| !matches!(pass.try_recv(), Err(TryRecvError::Empty))) {
+            self.pass = None;
+            self.indexed_at = Instant::now();
+        }
+        if self.pass.is_none() && (self.stale || self.indexed_at.elapsed() >= REINDEX_INTERVAL) {
+            // The roots are gone through sooner once they changed, as their files are missing from searches until then.
+            let job = Job::new(Kind::Indexing, "Going through the browser's folders").priority(if self.stale { Priority::Normal } else { Priority::Low });
+            self.stale = false;
+            self.indexed_at = Instant::now();
+            let (roots, folders, revision, announce) = (self.roots.clone(), Arc::clone(&self.folders), Arc::clone(&self.revision), self.announce.clone());
+            let progress = Arc::new(Progress::default());
+            let shown = Arc::clone(&progress);
+            self.pass = Some(tasks::spawn_with_progress(job, progress, move |progress| {
+                if update(&roots, &folders, &revision, progress, || drop(announce.send(shown))) {
+                    save(&folders.read().unwrap());
+                }
+            }));
+        }
+        self.progress.try_iter().last()
+    }
+
+    /// Index the files and folders under `roots` instead of the previous ones.
+    pub fn set_roots(&mut self, roots: &[PathBuf]) {
+        self.roots = roots.to_vec();
+        self.stale = true;
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+
+    /// Return how many folders are indexed, and how many files and folders they hold.
+    pub fn size(&self) -> (usize, usize) {
+        let folders = self.folders.read().unwrap();
+        (folders.len(), folders.values().map(|folder| folder.entries.len()).sum())
+    }
+
+    /// Return whether nothing has been indexed yet.
+    pub fn is_empty(&self) -> bool {
+        self.folders.read().unwrap().is_empty()
+    }
+
+}
+
+/// Return every indexed file and folder whose name matches `query`, along with whether it's a folder and how it matches, or [`None`] if `progress`
+/// was cancelled.
+fn search(folders: &RwLock<HashMap<PathBuf, Folder>>, query: &str, progress: &Progress) -> Option<Vec<(PathBuf, bool, Match)>> {
+    let folders = folders.read().unwrap_or_else(PoisonError::into_inner);
+    let mut found = Vec::new();
+    for folder in folders.values() {
+        if progress.is_cancelled() {
+            return None;
+        }
+        found.extend(
+            folder
+                .entries
+                .iter()
+                .filter_map(|entry| Some((folder.path.join(&entry.name), entry.is_folder, fuzzy_match(query, &entry.name)?))),
+        );
+    }
+    drop(folders);
+    Some(found)
+}
+
+/// A search of the [`Index`] running in the background, whose results are kept until the query or the index changes so that it's only searched
+/// again then.
+pub struct Search<T> {
+    /// The query and the revision of the index that the results are for, or [`None`] if they're out of date.
+    searched: Option<(String, u64)>,
+    results: Vec<T>,
+    running: Option<Running<T>>,
+    /// How many times results came in, to find out whether what was made of them is out of date.
+    revision: u64,
+}
+
+/// A search of the index going on, for `query` as of the revision `revision` of it.
+struct Running<T> {
+    query: String,
+    revision: u64,
+    results: Receiver<Vec<T>>,
+    /// The progress of the search, to cancel it once it's out of date.
+    progress: Arc<Progress>,
+}
+
+impl<T> Default for Search<T> {
+    fn default() -> Self {
+        Self { searched: None, results: Vec::new(), running: None, revision: 0 }
+    }
+}
+
+impl<T: Send + 'static> Search<T> {
+    /// Return the results for `query`, which are the previous ones until `index` is searched for it in the background. `found` turns what matches
+    /// into results there, and `ctx` is drawn again once they're in.
+    pub fn results(&mut self, ctx: &Context, index: &Index, query: &str, found: impl FnOnce(Vec<(PathBuf, bool, Match)>) -> Vec<T> + Send + 'static) -> &[T] {
+        if let Some(running) = &self.running {
+            match running.results.try_recv() {
+                Ok(results) => {
+                    self.searched = Some((running.query.clone(), running.revision));
+                    self.results = results;
+                    self.running = None;
+                    self.revision += 1;
+                }
+                // The search was cancelled or panicked.
+                Err(TryRecvError::Disconnected) => self.running = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        let revision = index.revision();
+        let is_wanted = |searched: &str, searched_revision: u64| searched == query && searched_revision == revision;
+        let searched = self.searched.as_ref().is_some_and(|(searched, searched_revision)| is_wanted(searched, *searched_revision));
+        let running = self.running.as_ref().is_some_and(|running| is_wanted(&running.query, running.revision));
+        if !searched && !running {
+            if let Some(running) = self.running.take() {
+                tasks::cancel(&running.progress);
+            }
+            let (tx, rx) = bounded(1);
+            let (folders, owned_query, ctx, progress) = (Arc::clone(&index.folders), query.to_string(), ctx.clone(), Arc::new(Progress::default()));
+            let job = Job::new(Kind::Searching, format!("Searching the browser's folders for \"{query}\""));
+            drop(tasks::spawn_with_progress(job, Arc::clone(&progress), move |progress| {
+                if let Some(matches) = search(&folders, &owned_query, progress) {
+                    // The results are sent before drawing again, so that they're there to be shown then.
+                    let _ = tx.send(found(matches));
+                    ctx.request_repaint();
+                }
+            }));
+            self.running = Some(Running { query: query.to_string(), revision, results: rx, progress });
+        }
+        &self.results
+    }
+
+    /// Return how many times results came in so far.
+    pub const fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Search again the next time the results are asked for, as what `found` makes of the matches changed. The results are kept until then.
+    pub fn refresh(&mut self) {
+        self.searched = None;
+    }
+}
+
+/// Read the folders under `roots` that changed since they were last read, and forget those that aren't there anymore. Returns whether anything changed.
+///
+/// Once many folders were read, `announce` is called for the `progress` to be shown. If it's cancelled, the folders that weren't gone through yet are kept as they
+/// were.
+fn update(roots: &[PathBuf], folders: &RwLock<HashMap<PathBuf, Folder>>, revision: &AtomicU64, progress: &Progress, announce: impl FnOnce()) -> bool {
+    // Folders to read, along with how deep under their root they are and the device holding the root.
+    let mut pending = roots
+        .iter()
+        .filter(|root| root.parent().is_some())
+        .filter_map(|root| Some((root.clone(), 0, device(&fs::metadata(root).ok()?))))
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let mut read = 0;
+    let mut announce = Some(announce);
+    while let Some((path, depth, root_device)) = pending.pop() {
+        if progress.is_cancelled() {
+            revision.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        // Folders are found along the way, so the total grows as they are.
+        progress.set(seen.len() as u64, (seen.len() + pending.len() + 1) as u64);
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        let Some((modified, folder_device)) = fs::metadata(&path).ok().and_then(|metadata| Some((metadata.modified().ok()?, device(&metadata)))) else {
+            continue;
+        };
+        if folder_device != root_device {
+            continue;
+        }
+        // The folders below the deepest ones indexed are left out.
+        let subfolder = |name: &str| (depth < MAX_DEPTH).then(|| (path.join(name), depth + 1, root_device));
+        let unchanged = folders
+            .read()
+            .unwrap()
+            .get(&path)
+            .filter(|folder| folder.modified == modified)
+            .map(|folder| folder.entries.iter().filter(|entry| entry.is_folder).filter_map(|entry| subfolder(&entry.name)).collect::<Vec<_>>());
+        if let Some(subfolders) = unchanged {
+            pending.extend(subfolders);
+            continue;
+        }
+        let Ok(read_dir) = fs::read_dir(&path) else {
+            continue;
+        };
+        let entries = read_dir
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                // Hidden files are left out, as they're rarely samples and can be numerous. Symbolic links aren't followed, so that they can't lead in circles.
+                (!name.starts_with('.')).then(|| IndexedEntry {
+                    is_folder: entry.file_type().is_ok_and(|file_type| file_type.is_dir()),
+                    name,
+                })
+            })
+            .collect::<Vec<_>>();
+        pending.extend(entries.iter().filter(|entry| entry.is_folder).filter_map(|entry| subfolder(&entry.name)));
+        folders.write().unwrap().insert(path.clone(), Folder { path, modified, entries });
+        read += 1;
+        if read % FOLDERS_PER_REVISION == 0 {
+            revision.fetch_add(1, Ordering::Relaxed);
+            if let Some(announce) = announce.take() {
+                announce();
+            }
+        }
+    }
+    let removed = {
+        let mut folders = folders.write().unwrap();
+        let count = folders.len();
+        folders.retain(|path, _| seen.contains(path));
+        folders.len() != count
+    };
+    let changed = read > 0 || removed;
+    if changed {
+        revision.fetch_add(1, Ordering::Relaxed);
+    }
+    changed
+}
+
+/// Return the device holding the file whose metadata is `metadata`, or 0 where that can't be told.
+fn device(metadata: &fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::MetadataExt::dev(metadata)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        0
+    }
+}
+
+fn load() -> HashMap<PathBuf, Folder> {
+    // An index that couldn't be read is rebuilt.
+    let folders = config::load(INDEX_PATH, "the browser index").map_or_else(Vec::new, |saved: SavedIndex<Folder>| saved.folders);
+    folders.into_iter().map(|folder| (folder.path.clone(), folder)).collect()
+}
+
+fn save(folders: &HashMap<PathBuf, Folder>) {
+    // TOML can only hold paths that are valid Unicode.
+    let saved = SavedIndex {
+        folders: folders.values().filter(|folder| folder.path.to_str().is_some()).collect(),
+    };
+    config::save(INDEX_PATH, &saved, "the browser index");
+}
@@ -0,0 +1,70 @@
+use std::{
+    fs,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use itertools::Itertools;
+use rodio::{Decoder, Source};
+
+use super::EntryKind;
+use crate::archive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Modified,
+    Size,
+    Duration,
+}
+
+impl SortKey {
+    pub const VARIANTS: [Self; 4] = [Self::Name, Self::Modified, Self::Size, Self::Duration];
+
+    pub const fn title(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Modified => "Date modified",
+            Self::Size => "Size",
+            Self::Duration => "Duration",
+        }
+    }
+}
+
+/// How the entries of a folder are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    /// Sort the `entries` of a folder, keeping folders first and files grouped by kind. Entries with the same key are sorted by name.
+    pub fn apply(self, entries: Vec<(EntryKind, Arc<Path>)>) -> Vec<(EntryKind, Arc<Path>)> {
+        match self.key {
+            SortKey::Name => self.by(entries, |_| ()),
+            SortKey::Modified => self.by(entries, |path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok()),
+            SortKey::Size => self.by(entries, |path| fs::metadata(path).map(|metadata| metadata.len()).ok()),
+            SortKey::Duration => self.by(entries, duration),
+        }
+    }
+
+    fn by<K: Ord>(self, entries: Vec<(EntryKind, Arc<Path>)>, key: impl Fn(&Path) -> K) -> Vec<(EntryKind, Arc<Path>)> {
+        entries
+            .into_iter()
+            .map(|(kind, path)| (kind, key(&path), path))
+            .sorted_by(|a, b| {
+                let order = a.1.cmp(&b.1).then_with(|| a.2.cmp(&b.2));
+                a.0.cmp(&b.0).then(if self.descending { order.reverse() } else { order })
+            })
+            .map(|(kind, _, path)| (kind, path))
+            .collect()
+    }
+}
+
+/// Return how long the audio file at `path` is, if its headers say so.
+fn duration(path: &Path) -> Option<Duration> {
+    Decoder::new(archive::open(path).ok()?).ok()?.total_duration()
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Where the tags are kept between sessions.
+const PATH: &str = "tags.toml";
+
+/// Tags given to files and folders, like "kick" or "ambient", to find them by.
+#[derive(Clone, Default)]
+pub struct Tags {
+    paths: HashMap<PathBuf, BTreeSet<String>>,
+    /// How many times the tags changed, to find out whether what was made of them is out of date.
+    revision: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTags {
+    entries: Vec<Tagged>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Tagged {
+    path: PathBuf,
+    tags: BTreeSet<String>,
+}
+
+impl Tags {
+    /// Return the tags saved in the last session.
+    pub fn load() -> Self {
+        let saved = config::load(PATH, "the tags").map_or_else(Vec::new, |saved: SavedTags| saved.entries);
+        Self {
+            paths: saved.into_iter().map(|Tagged { path, tags }| (path, tags)).collect(),
+            revision: 0,
+        }
+    }
+
+    fn save(&self) {
+        let mut entries = self.paths.iter().map(|(path, tags)| Tagged { path: path.clone(), tags: tags.clone() }).collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        config::save(PATH, &SavedTags { entries }, "the tags");
+    }
+
+    /// Return how many times the tags changed so far.
+    pub const fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Return every tag given to something, in order.
+    pub fn all(&self) -> BTreeSet<&str> {
+        self.paths.values().flatten().map(String::as_str).collect()
+    }
+
+    /// Return the tags of `path`, in order.
+    pub fn of(&self, path: &Path) -> impl Iterator<Item = &str> {
+        self.paths.get(path).into_iter().flatten().map(String::as_str)
+    }
+
+    /// Return whether `path` has every one of `tags`.
+    pub fn has_all(&self, path: &Path, tags: &BTreeSet<String>) -> bool {
+        self.paths.get(path).is_some_and(|own| own.is_superset(tags))
+    }
+
+    /// Return everything that has every one of `tags`.
+    pub fn tagged<'a>(&'a self, tags: &'a BTreeSet<String>) -> impl Iterator<Item = &'a Path> {
+        self.paths.iter().filter(|(_, own)| own.is_superset(tags)).map(|(path, _)| path.as_path())
+    }
+
+    /// Move the tags of `from` and of everything in it to `to`, after it was renamed.
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        let moved = self.paths.keys().filter(|path| path.starts_with(from)).cloned().collect::<Vec<_>>();
+        if moved.is_empty() {
+            return;
+        }
+        for path in moved {
+            if let (Some(tags), Ok(rest)) = (self.paths.remove(&path), path.strip_prefix(from)) {
+                self.paths.insert(to.join(rest), tags);
+            }
+        }
+        self.revision += 1;
+        self.save();
+    }
+
+    /// Give `tag` to `path` or take it away, and save the tags. Tags are trimmed and lowercase so that the same tag isn't typed in two ways.
+    pub fn set(&mut self, path: &Path, tag: &str, tagged: bool) {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            return;
+        }
+        if tagged {
+            self.paths.entry(path.to_path_buf()).or_default().insert(tag);
+        } else if let Some(tags) = self.paths.get_mut(path) {
+            tags.remove(&tag);
+            if tags.is_empty() {
+                self.paths.remove(path);
+            }
+        }
+        self.revision += 1;
+        self.save();
+    }
+}
@@ -0,0 +1,50 @@
+use std::{sync::Arc, time::Duration};
+
+use blerp::processing::overview::{overview, Peak};
+use cpal::Sample;
+use egui::{pos2, vec2, Color32, Response, Sense, Shape, Stroke, Ui};
+use rodio::{Decoder, Source};
+use tracing::error;
+
+use super::lazy_cache::LazyCache;
+use crate::{archive, tasks::Kind};
+
+/// How many stretches of audio a thumbnail shows, which is also its width in points.
+const WIDTH: usize = 40;
+const HEIGHT: f32 = 14.;
+
+/// Return a cache of waveform overviews of audio files.
+pub fn cache() -> LazyCache<Arc<[Peak]>> {
+    LazyCache::new(Kind::Waveforms, |path| {
+        let decoder = archive::open(path)
+            .map_err(|error| error.to_string())
+            .and_then(|file| Decoder::new(file).map_err(|error| error.to_string()))
+            .inspect_err(|error| error!("Couldn't decode {} for its thumbnail: {error}", path.display()))
+            .ok()?;
+        let channels = usize::from(decoder.channels());
+        let samples = decoder.map(f64::from_sample).collect::<Vec<_>>();
+        Some(overview(&samples, channels, WIDTH).into())
+    })
+}
+
+/// Show a waveform thumbnail of `peaks`, or an empty one while they're being worked out, which is `pending`, or if they couldn't be.
+pub fn show(ui: &mut Ui, peaks: Option<&[Peak]>, pending: bool, color: Color32) -> Response {
+    #[allow(clippy::cast_precision_loss, reason = "the width is small")]
+    let (rect, response) = ui.allocate_exact_size(vec2(WIDTH as f32, HEIGHT), Sense::hover());
+    let Some(peaks) = peaks else {
+        if pending {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+        ui.painter().hline(rect.x_range(), rect.center().y, Stroke::new(1., color.gamma_multiply(0.4)));
+        return response;
+    };
+    let shapes = peaks.iter().enumerate().map(|(index, peak)| {
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, reason = "only used for drawing")]
+        let (x, top, bottom) = (rect.left() + index as f32 + 0.5, peak.max.clamp(-1., 1.) as f32, peak.min.clamp(-1., 1.) as f32);
+        let (top, bottom) = (rect.center().y - top * HEIGHT / 2., rect.center().y - bottom * HEIGHT / 2.);
+        // Silent stretches still get a dot, so that the length of the audio shows.
+        Shape::line_segment([pos2(x, top.min(rect.center().y - 0.5)), pos2(x, bottom.max(rect.center().y + 0.5))], Stroke::new(1., color))
+    });
+    ui.painter().extend(shapes);
+    response
+}
@@ -0,0 +1,72 @@
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Move the file or folder at `path` to the trash of the user's home, so that it can still be restored from the file manager.
+///
+/// Files on other drives can't be moved there, and are left alone with an error rather than being deleted for good.
+pub fn trash(path: &Path) -> io::Result<()> {
+    let path = path.canonicalize()?;
+    let name = path.file_name().ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "the root can't be moved to the trash"))?;
+    if cfg!(target_os = "macos") {
+        let trash = home()?.join(".Trash");
+        return fs::rename(&path, unused_path(&trash, &name.to_string_lossy()));
+    }
+    if cfg!(windows) {
+        return Err(io::Error::new(ErrorKind::Unsupported, "moving to the trash isn't supported on this system"));
+    }
+    // https://specifications.freedesktop.org/trash-spec/latest/
+    let trash = env::var_os("XDG_DATA_HOME").map_or_else(|| home().map(|home| home.join(".local/share")), |data| Ok(PathBuf::from(data)))?.join("Trash");
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+    let destination = unused_path(&files, &name.to_string_lossy());
+    let trashed_name = destination.file_name().unwrap_or(name).to_string_lossy();
+    let info_path = info.join(format!("{trashed_name}.trashinfo"));
+    fs::write(&info_path, format!("[Trash Info]\nPath={}\nDeletionDate={}\n", escape(&path), now()))?;
+    fs::rename(&path, &destination).inspect_err(|_| {
+        let _ = fs::remove_file(&info_path);
+    })
+}
+
+fn home() -> io::Result<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from).ok_or_else(|| io::Error::new(ErrorKind::NotFound, "there is no home folder"))
+}
+
+/// Return a path in `folder` for something called `name` that isn't taken yet, numbering it if it has to be.
+fn unused_path(folder: &Path, name: &str) -> PathBuf {
+    let mut path = folder.join(name);
+    let mut number = 1;
+    while path.exists() {
+        number += 1;
+        path = folder.join(format!("{name} {number}"));
+    }
+    path
+}
+
+/// Escape `path` the way URLs are, except for its slashes.
+fn escape(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|byte| if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) { char::from(byte).to_string() } else { format!("%{byte:02X}") })
+        .collect()
+}
+
+/// Return the current time as `YYYY-MM-DDThh:mm:ss`, in UTC.
+fn now() -> String {
+    let seconds = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}", time / 3600, time % 3600 / 60, time % 60)
+}
@@ -1,202 +1,100 @@
+use std::borrow::Cow;
 use std::ops::BitOr;
-use std::path::PathBuf;
-use std::{collections::HashMap, num::NonZeroU64};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    num::NonZeroU64,
+    time::Duration,
+};
 
-use blerp::processing::effects::clip::ClipEffect;
-use blerp::processing::effects::scale::ScaleEffect;
+use blerp::midi::MidiFile;
+use blerp::processing::effects::normalize::NormalizeTarget;
+use blerp::processing::graph::{self as schedule, CycleError, Schedule, Tap};
+use blerp::processing::registry::{self, Category};
+use blerp::processing::{
+    drum_rack,
+    synth::{self, Setting},
+};
+use crossbeam_channel::{Receiver, TryRecvError};
 use eframe::egui;
 use egui::{
-    hex_color, pos2, scroll_area::ScrollBarVisibility, vec2, Align, Align2, Color32, CursorIcon, Frame, Id, InputState, Layout, Rect, Response, ScrollArea, Sense, Stroke, Ui, UiBuilder, Vec2, Widget,
+    emath::TSTransform, hex_color, pos2, scroll_area::ScrollBarVisibility, vec2, Align, Align2, Area, Button, Checkbox, Color32, ComboBox, Context, CursorIcon, DragAndDrop, DragValue, Event, FontId, Frame, Grid, Id, InputState, Key, LayerId, Layout, Modifiers, Order, Painter, PopupCloseBehavior, Pos2, Rect, Response, ScrollArea, Sense, Slider, Stroke, TextEdit, Ui, UiBuilder, Vec2, Widget, Window,
 };
-use graph::{Graph, Node, NodeData, NodeId};
+use analyzer::{Analyzer, Source};
+use editor::{Request, SampleEditor};
+use graph::{Axis, Edge, Node, PendingConnection, SendTarget, Subgraph};
 use itertools::Itertools;
-use playlist::{Clip, ClipData, Playlist, Time};
+use loudness::Loudness;
+use meters::{Meters, Point};
+use playlist::{BusSend, ClipProcessing, FileLength, InputSettings, Instrument, Monitoring, SliceError, SliceInto, Sliced, Stretch, TempoMarker, TrackMix};
+use serde::Serialize;
+use tuner::{Listen, Tuner};
 
-use super::ThemeColors;
-
-mod graph {
-    use blerp::processing::effects::Effect;
-    use egui::Vec2;
-    use std::collections::HashMap;
-    use std::fmt::Debug;
-    use std::num::NonZeroU64;
+use super::{
+    browser::lazy_cache::LazyCache,
+    dialog::{self, Choice},
+    ThemeColors, Tooltip,
+};
+use crate::{
+    archive,
+    config::Ballistics,
+    controller::{ControlChange, Mapping, Mappings, Target},
+    engine::Engine,
+    keymap::Action,
+    midi, project, script,
+    tasks::{self, Job, Kind},
+};
 
-    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-    pub enum NodeId {
-        Output,
-        Middle(NonZeroU64),
-    }
+mod analyzer;
+mod editor;
+mod graph;
+mod loudness;
+mod meters;
+mod mixdown;
+mod playlist;
+mod tuner;
+mod visualization;
 
-    pub struct Graph {
-        pub nodes: HashMap<NodeId, Node>,
-        pub pan_offset: Vec2,
-        pub drag_start_offset: Option<Vec2>,
-    }
+pub use graph::{Graph, NodeData, NodeId};
+pub use mixdown::{Mixdown, MixdownError};
+pub use playlist::{Clip, ClipData, Playlist, Tempo, Time, TimeSignature};
 
-    pub struct Node {
-        pub position: Vec2,
-        pub data: NodeData,
-        pub drag_start_offset: Option<Vec2>,
-    }
+/// The names of the notes of an octave, from C up.
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
 
-    pub enum NodeData {
-        Output,
-        Middle { effect: Box<dyn Effect>, output: Option<NodeId> },
-    }
+/// Return the name of the MIDI note `key`, numbering octaves like scientific pitch notation does, where the A at 440 Hz is A4 and `key` 60 is C4.
+fn note_name(key: u8) -> String {
+    format!("{}{}", NOTE_NAMES[usize::from(key % 12)], i32::from(key / 12) - 1)
 }
 
-mod playlist {
-    use cpal::Sample;
-    use egui::{vec2, Vec2};
-    use itertools::Itertools;
-    use rodio::{Decoder, Source};
-    use std::{fs::File, io::BufReader, path::PathBuf, time::Duration};
-
-    #[derive(Debug)]
-    pub struct Playlist {
-        pub clips: Vec<Clip>,
-        pub time_signature: TimeSignature,
-        pub tempo: Tempo,
-        pub time: Time,
-        /// The zoom factor for the playlist view. `[400.0 60.0]` means a measure is 400 pixels wide and a track is 60 pixels tall.
-        pub zoom: Vec2,
-        pub snapping: Snapping,
-    }
-
-    impl Default for Playlist {
-        fn default() -> Self {
-            Self {
-                clips: Vec::new(),
-                time_signature: TimeSignature::default(),
-                tempo: Tempo::default(),
-                time: Time::default(),
-                zoom: vec2(400., 60.),
-                snapping: Snapping::default(),
-            }
-        }
-    }
-
-    #[derive(Debug, Clone, Copy)]
-    pub enum Snapping {
-        None,
-        /// Snaps to the nearest beat divided by the given number, normally a power of 2.
-        Beats {
-            divisor: u32,
-        },
-    }
-
-    impl Default for Snapping {
-        fn default() -> Self {
-            Self::Beats { divisor: 4 }
-        }
-    }
-
-    #[derive(Debug, Clone, Copy)]
-    pub struct Tempo {
-        beats_per_hectominute: u32,
-    }
-
-    impl Default for Tempo {
-        fn default() -> Self {
-            Self::from_bpm(120.)
-        }
-    }
-
-    impl Tempo {
-        pub fn from_bpm(bpm: f64) -> Self {
-            #[allow(clippy::cast_sign_loss, reason = "bpm is always positive")]
-            #[allow(clippy::cast_possible_truncation, reason = "bpm only goes up to 999.99, so never truncates")]
-            let beats_per_hectominute = (bpm as u32 * 100).clamp(1, 99999);
-            Self { beats_per_hectominute }
-        }
-
-        pub fn bpm(self) -> f64 {
-            f64::from(self.beats_per_hectominute) / 100.
-        }
-
-        pub fn bps(self) -> f64 {
-            self.bpm() / 60.
-        }
-    }
-
-    #[derive(Debug, Clone)]
-    pub struct Clip {
-        pub start: Time,
-        pub track: u32,
-        pub data: ClipData,
-    }
-
-    #[derive(Debug, Clone)]
-    pub enum ClipData {
-        Audio { path: PathBuf, samples: Vec<f64>, length: Duration },
-        Midi { length: Time },
-    }
-
-    impl ClipData {
-        pub fn from_path(path: PathBuf) -> Self {
-            let decoder = Decoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
-            let length = decoder.total_duration().unwrap();
-            let samples = decoder.map(f64::from_sample).collect_vec();
-            Self::Audio { path, samples, length }
-        }
-    }
-
-    #[derive(Debug, Clone, Copy, Default)]
-    pub struct Time {
-        beats: f64,
-    }
-
-    impl Time {
-        pub fn from_beats(beats: f64) -> Option<Self> {
-            (beats > 0.).then_some(Self { beats })
-        }
-
-        pub const fn beats(self) -> f64 {
-            self.beats
-        }
-    }
-
-    #[derive(Debug, Clone, Copy)]
-    pub struct TimeSignature {
-        pub beats_per_measure: u32,
-        pub beat_unit: u32,
-    }
-
-    impl Default for TimeSignature {
-        fn default() -> Self {
-            Self { beats_per_measure: 4, beat_unit: 4 }
-        }
-    }
-
-    impl Playlist {
-        pub fn now(&self) -> Duration {
-            Duration::from_secs_f64(self.time.beats / self.tempo.bpm() * 60.)
-        }
-
-        pub const fn measure(&self) -> u32 {
-            #[allow(clippy::cast_possible_truncation, reason = "truncation is intentional")]
-            #[allow(clippy::cast_sign_loss, reason = "beats cannot be negative")]
-            {
-                self.time.beats as u32 / self.time_signature.beats_per_measure
-            }
-        }
-
-        pub fn beats_to_duration(&self, beats: f64) -> Duration {
-            Duration::from_secs_f64(beats / self.tempo.bps())
-        }
-
-        pub fn duration_of_clip(&self, clip: &ClipData) -> Duration {
-            match clip {
-                ClipData::Audio { length, .. } => *length,
-                ClipData::Midi { length } => self.beats_to_duration(length.beats()),
-            }
-        }
-    }
+/// What the playlist opened for a track or clip, to be shown once the playlist is.
+enum TrackView {
+    Inserts(u32),
+    DrumRack(u32),
+    /// The sample editor, for the audio clip at the index.
+    SampleEditor(usize),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Mode {
     Playlist,
     Graph,
+    /// The insert chain of a track.
+    Inserts(u32),
+    /// The sample being edited.
+    SampleEditor,
+}
+
+impl Mode {
+    /// Return the track whose insert chain is shown, if any.
+    const fn track(self) -> Option<u32> {
+        match self {
+            Self::Inserts(track) => Some(track),
+            Self::Playlist | Self::Graph | Self::SampleEditor => None,
+        }
+    }
 }
 
 impl Default for Mode {
@@ -206,9 +104,100 @@ impl Default for Mode {
 }
 
 pub struct Central {
+    /// The playlist, or the graph or insert chain shown, which is always one of the latter while the graph is detached.
     mode: Mode,
+    /// Whether the graph is shown in a window of its own, leaving the playlist in the main window.
+    graph_detached: bool,
     playlist: Playlist,
     graph: Graph,
+    /// The insert chain of each track that has one, which the track's audio goes through before reaching the graph's track inputs.
+    inserts: BTreeMap<u32, Graph>,
+    /// The groups entered to edit their contents, starting from the main graph or insert chain.
+    group_path: Vec<NodeId>,
+    /// Description of an edit made since the last call to [`Central::take_edit`].
+    edit: Option<String>,
+    /// Files of clips dragged out of the window since the last call to [`Central::take_exported`].
+    exported: Vec<PathBuf>,
+    /// Files dropped onto the playlist since the last call to [`Central::take_added`].
+    added: Vec<PathBuf>,
+    /// The files used from archives being extracted to the samples folder, which are used from there once they are, see [`Central::extract_archived`].
+    extracting: Option<Receiver<HashMap<PathBuf, PathBuf>>>,
+    /// The audio clips being sliced at their hits in the background, see [`Central::slice`].
+    slicing: Vec<Receiver<Result<Sliced, SliceError>>>,
+    /// Why a clip couldn't be sliced, since the last call to [`Central::take_slice_error`].
+    slice_error: Option<SliceError>,
+    /// How long the files dragged over the playlist are, to show where their clips would go.
+    file_lengths: LazyCache<FileLength>,
+    /// Incremented whenever the playlist may have changed, so that tracks rendered for playback are only rendered again when needed.
+    playlist_revision: u64,
+    /// The audio going through each visualization node by the track whose insert chain it's in, if any, and its path through groups, as of the last schedule.
+    taps: HashMap<(Option<u32>, Vec<NodeId>), Arc<Tap>>,
+    /// The controls of MIDI controllers mapped to parameters of the graph and insert chains.
+    mappings: Mappings,
+    /// The track whose drum rack is shown in a window, if any, and the pad whose settings are shown in it.
+    drum_rack: Option<u32>,
+    selected_pad: usize,
+    /// The sample open in the sample editor, if any.
+    editor: Option<SampleEditor>,
+    /// What happens to the sample editor once what to do with the unsaved changes to its sample is picked, while that's being asked.
+    leaving_editor: Option<Leaving>,
+    /// The file of the project, if it was saved, next to which the files made for it are kept.
+    project_path: Option<PathBuf>,
+    /// Whether the tempo changed live since the last change recorded in the history, see [`Central::set_bpm`].
+    tempo_changing: bool,
+    analyzer: Analyzer,
+    /// The level meters of the tracks, the mixers and the master output.
+    meters: Meters,
+    tuner: Tuner,
+    loudness: Loudness,
+    /// How far behind the capture device monitored inputs are heard, if they're recorded from.
+    monitoring_latency: Option<Duration>,
+    theme: Rc<ThemeColors>,
+}
+
+/// What the sample editor is left for.
+enum Leaving {
+    Close,
+    /// Edit the audio of a clip or a file, see [`Central::open_editor`].
+    Open(PathBuf, ClipData, Option<u64>),
+}
+
+/// A file used by the project, which may have been moved or deleted since.
+pub struct FileReference {
+    pub path: PathBuf,
+    /// The samples and channel count of a clip of the file, which stay in the project when the file is gone.
+    pub audio: Option<(Arc<[f64]>, u16)>,
+}
+
+/// The undoable state of a [`Central`], see [`crate::history`].
+#[derive(Clone, Serialize)]
+pub struct CentralSnapshot {
+    clips: Vec<Clip>,
+    nodes: HashMap<NodeId, Node>,
+    /// Compared as a set rather than in the order it would be saved in, see [`CentralSnapshot::eq`].
+    #[serde(skip)]
+    edges: HashSet<Edge>,
+    /// Compared on its own, as it isn't saved.
+    #[serde(skip)]
+    solo: Option<NodeId>,
+    inserts: BTreeMap<u32, Graph>,
+    mappings: Vec<Mapping>,
+    instruments: BTreeMap<u32, Instrument>,
+    inputs: BTreeMap<u32, InputSettings>,
+    mixes: BTreeMap<u32, TrackMix>,
+    tempo: Tempo,
+    tempo_changes: Vec<TempoMarker>,
+    time_signature: TimeSignature,
+}
+
+/// Snapshots are compared by what would be saved of them, which leaves out the state of the view like what's selected, so that an edit that
+/// changed nothing isn't recorded.
+impl PartialEq for CentralSnapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.edges == other.edges
+            && self.solo == other.solo
+            && matches!((serde_json::to_value(self), serde_json::to_value(other)), (Ok(this), Ok(other)) if this == other)
+    }
 }
 
 impl Default for Central {
@@ -221,32 +210,39 @@ impl Central {
     pub fn new() -> Self {
         Self {
             mode: Mode::Playlist,
+            graph_detached: false,
             playlist: Playlist::default(),
 
             graph: Graph {
                 drag_start_offset: Some(vec2(0., 0.)),
                 pan_offset: vec2(0., 0.),
+                zoom: 1.,
+                show_minimap: false,
                 nodes: [
                     (
                         NodeId::Middle(NonZeroU64::new(1).unwrap()),
                         Node {
                             data: NodeData::Middle {
-                                effect: Box::new(ClipEffect::new_symmetrical(0.5)),
-                                output: Some(NodeId::Middle(NonZeroU64::new(2).unwrap())),
+                                effect: registry::find("Clip").unwrap(),
+                                parameters: vec![0.5],
                             },
                             position: vec2(-200., -20.),
+                            bypassed: false,
                             drag_start_offset: None,
+                            size: Vec2::ZERO,
                         },
                     ),
                     (
                         NodeId::Middle(NonZeroU64::new(2).unwrap()),
                         Node {
                             data: NodeData::Middle {
-                                effect: Box::new(ScaleEffect::new(2.)),
-                                output: Some(NodeId::Output),
+                                effect: registry::find("Scale").unwrap(),
+                                parameters: vec![2.],
                             },
                             position: vec2(-30., 80.),
+                            bypassed: false,
                             drag_start_offset: None,
+                            size: Vec2::ZERO,
                         },
                     ),
                     (
@@ -254,16 +250,679 @@ impl Central {
                         Node {
                             data: NodeData::Output,
                             position: vec2(150., 10.),
+                            bypassed: false,
                             drag_start_offset: None,
+                            size: Vec2::ZERO,
                         },
                     ),
                 ]
                 .into(),
+                edges: [
+                    Edge {
+                        from: NodeId::Middle(NonZeroU64::new(1).unwrap()),
+                        to: NodeId::Middle(NonZeroU64::new(2).unwrap()),
+                    },
+                    Edge {
+                        from: NodeId::Middle(NonZeroU64::new(2).unwrap()),
+                        to: NodeId::Output,
+                    },
+                ]
+                .into(),
+                pending_connection: None,
+                selection: HashSet::new(),
+                solo: None,
+                marquee: None,
             },
+            inserts: BTreeMap::new(),
+            group_path: Vec::new(),
+            edit: None,
+            exported: Vec::new(),
+            added: Vec::new(),
+            extracting: None,
+            slicing: Vec::new(),
+            slice_error: None,
+            file_lengths: LazyCache::new(Kind::Analysis, FileLength::read),
+            playlist_revision: 0,
+            taps: HashMap::new(),
+            mappings: Mappings::default(),
+            drum_rack: None,
+            selected_pad: 0,
+            editor: None,
+            leaving_editor: None,
+            project_path: None,
+            tempo_changing: false,
+            analyzer: Analyzer::default(),
+            meters: Meters::default(),
+            tuner: Tuner::default(),
+            loudness: Loudness::default(),
+            monitoring_latency: None,
+            theme: Rc::new(ThemeColors::default()),
+        }
+    }
+
+    /// Return the files of clips that were dragged out of the window, if any were since this was last called.
+    pub fn take_exported(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.exported)
+    }
+
+    /// Return why a clip couldn't be sliced, if one couldn't since this was last called.
+    pub const fn take_slice_error(&mut self) -> Option<SliceError> {
+        self.slice_error.take()
+    }
+
+    /// Return the files that were dropped onto the playlist, if any were since this was last called.
+    pub fn take_added(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.added)
+    }
+
+    /// Add a node for the registered effect called `name` in the middle of the graph view and show the graph. Returns `false` if there is no such effect.
+    pub fn add_node(&mut self, name: &str) -> bool {
+        let Some(effect) = registry::find(name) else {
+            return false;
+        };
+        let graph = self.current_graph();
+        graph.add_node(NodeData::effect(effect), graph.view_center());
+        if matches!(self.mode, Mode::Playlist | Mode::SampleEditor) {
+            self.mode = Mode::Graph;
+        }
+        self.edit = Some("Add node".into());
+        true
+    }
+
+    /// Show the playlist, or the graph if `graph` is true.
+    pub const fn show_graph(&mut self, graph: bool) {
+        self.mode = if graph { Mode::Graph } else { Mode::Playlist };
+    }
+
+    /// Return whether the graph is shown in a window of its own, see [`Central::detached_graph_ui`].
+    pub const fn is_graph_detached(&self) -> bool {
+        self.graph_detached
+    }
+
+    /// Show the graph in a window of its own if `detached` is true, or along with the playlist otherwise.
+    pub const fn set_graph_detached(&mut self, detached: bool) {
+        self.graph_detached = detached;
+    }
+
+    /// Set the file of the project, next to which the files made for it are kept, or [`None`] if it was never saved.
+    pub fn set_project_path(&mut self, path: Option<PathBuf>) {
+        self.project_path = path;
+    }
+
+    /// Return the folder that files made for the project are kept in, see [`project::samples_folder`].
+    fn samples_folder(&self) -> PathBuf {
+        project::samples_folder(self.project_path.as_deref())
+    }
+
+    /// Show the graph or insert chain, for a window of its own while the graph is detached.
+    pub fn detached_graph_ui(&mut self, ui: &mut Ui) -> Response {
+        if matches!(self.mode, Mode::Playlist | Mode::SampleEditor) {
+            self.mode = Mode::Graph;
+        }
+        let fit = self.add_toolbar(ui);
+        self.add_current_graph(ui, fit)
+    }
+
+    /// Return a description of the last edit made to the playlist or graph, if there was one since this was last called. The files from archives the
+    /// edit started using are extracted then.
+    pub fn take_edit(&mut self) -> Option<String> {
+        let edit = self.edit.take()?;
+        self.extract_archived();
+        Some(edit)
+    }
+
+    /// Extract the files in archives that the project uses, which it can play from the archives, to the samples folder in the background, so that the
+    /// project doesn't need the archives once they're taken from there.
+    fn extract_archived(&mut self) {
+        if self.extracting.is_some() {
+            return;
+        }
+        let files = self.graph.files().into_iter().chain(self.inserts.values().flat_map(Graph::files)).chain(self.playlist.files());
+        let archived = files.filter(|path| archive::split(path).is_some()).cloned().collect::<BTreeSet<_>>();
+        if archived.is_empty() {
+            return;
+        }
+        let folder = self.samples_folder();
+        let job = Job::new(Kind::Import, format!("Extracting {} file(s) from archives", archived.len()));
+        self.extracting = Some(tasks::spawn(job, move || {
+            archived
+                .into_iter()
+                .filter_map(|path| match archive::extract(&path, &folder) {
+                    Ok(extracted) => Some((path, extracted)),
+                    Err(error) => {
+                        tracing::error!("Couldn't extract {}: {error}", path.display());
+                        None
+                    }
+                })
+                .collect()
+        }));
+    }
+
+    /// Use the files extracted by [`Central::extract_archived`] once they are, in place of those in archives.
+    fn poll_extracted(&mut self) {
+        if let Some(Ok(extracted)) = self.extracting.as_ref().map(Receiver::try_recv) {
+            self.extracting = None;
+            self.relink(&extracted, "Extract files from archives");
+        }
+    }
+
+    /// Slice the audio clip at `index` `into` clips or a drum rack at its hits in the background, see [`playlist::Slicer::slice`]. The slices of a drum
+    /// rack are written into the samples folder.
+    fn slice(&mut self, index: usize, into: SliceInto, sensitivity: f64) {
+        let (slicer, folder) = (self.playlist.slicer(index), self.samples_folder());
+        let job = Job::new(Kind::Analysis, format!("Slicing {}", self.playlist.clips[index].name));
+        self.slicing.push(tasks::spawn(job, move || slicer.slice(into, sensitivity, &folder)));
+    }
+
+    /// Slice the clips sliced by [`Central::slice`] once they are.
+    fn poll_sliced(&mut self) {
+        for receiver in std::mem::take(&mut self.slicing) {
+            match receiver.try_recv() {
+                Err(TryRecvError::Empty) => self.slicing.push(receiver),
+                Err(TryRecvError::Disconnected) => {}
+                Ok(Ok(sliced)) => {
+                    if let Some(edit) = self.playlist.apply_slices(sliced) {
+                        self.edit = Some(edit.into());
+                        self.playlist_revision += 1;
+                    }
+                }
+                Ok(Err(error)) => self.slice_error = Some(error),
+            }
+        }
+    }
+
+    /// Set the colors the playlist is drawn with.
+    pub fn set_theme(&mut self, theme: Rc<ThemeColors>) {
+        self.theme = theme;
+    }
+
+    /// Return the tempo of the playlist in BPM.
+    pub fn bpm(&self) -> f64 {
+        self.playlist.tempo.bpm()
+    }
+
+    /// Set the tempo of the playlist in BPM.
+    /// Set the tempo to `bpm`. While it's `live`, like while the tempo is dragged, the changes are only recorded in the history as one edit once it isn't.
+    pub fn set_bpm(&mut self, bpm: f64, live: bool) {
+        let tempo = Tempo::from_bpm(bpm);
+        if tempo != self.playlist.tempo {
+            self.playlist.tempo = tempo;
+            // Clips that follow the tempo have to be rendered again.
+            self.playlist_revision += 1;
+            self.tempo_changing = true;
+        }
+        if !live && std::mem::take(&mut self.tempo_changing) {
+            self.edit = Some("Change tempo".into());
+        }
+    }
+
+    pub const fn time_signature(&self) -> TimeSignature {
+        self.playlist.time_signature
+    }
+
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.playlist.time_signature = time_signature;
+        self.edit = Some("Change time signature".into());
+    }
+
+    /// Zoom the playlist horizontally to `percent` of its default zoom, and show it.
+    pub fn set_zoom(&mut self, percent: f32) {
+        self.playlist.zoom.x = Playlist::DEFAULT_ZOOM.x * percent / 100.;
+        self.mode = Mode::Playlist;
+    }
+
+    /// Add a clip of the file at `path` at the playhead, on the first track that's free there, and show the playlist.
+    pub fn add_at_playhead(&mut self, path: &Path) {
+        let path = path.to_path_buf();
+        let start = self.playlist.time;
+        let track = self.playlist.free_track(start);
+        self.playlist.add_files(start, track, std::slice::from_ref(&path), false);
+        self.playlist_revision += 1;
+        self.mode = Mode::Playlist;
+        self.edit = Some("Add clip".into());
+        self.added.push(path);
+    }
+
+    /// Open the audio file at `path` in the sample editor and show it.
+    pub fn open_in_editor(&mut self, path: &Path) {
+        if let Some(data) = ClipData::read(path.to_path_buf(), None) {
+            self.open_editor(path.to_path_buf(), &data, None);
+        }
+    }
+
+    /// Open the audio of `data`, from the file at `path` and the clip with the id `clip` if it's from one, in the sample editor and show it. The
+    /// clipboard of the sample edited before is kept, and what to do with its unsaved changes is asked first.
+    fn open_editor(&mut self, path: PathBuf, data: &ClipData, clip: Option<u64>) {
+        if self.editor.as_ref().is_some_and(|editor| editor.unsaved) {
+            self.leaving_editor = Some(Leaving::Open(path, data.clone(), clip));
+            self.mode = Mode::SampleEditor;
+            return;
+        }
+        let Some(mut editor) = SampleEditor::new(path, data, clip) else {
+            return;
+        };
+        editor.clipboard = self.editor.take().and_then(|editor| editor.clipboard);
+        self.editor = Some(editor);
+        self.mode = Mode::SampleEditor;
+        // The editor is a tab of the main window, which only shows the playlist while the graph is in a window of its own.
+        self.graph_detached = false;
+    }
+
+    /// Undo the last edit in the sample editor if it's shown, whose edits aren't part of the project's history until they're saved. Returns `false`
+    /// if it isn't shown.
+    pub fn undo_in_editor(&mut self) -> bool {
+        let Some(editor) = self.editor.as_mut().filter(|_| self.mode == Mode::SampleEditor) else {
+            return false;
+        };
+        editor.undo();
+        true
+    }
+
+    /// Like [`Central::undo_in_editor`], but redoing.
+    pub fn redo_in_editor(&mut self) -> bool {
+        let Some(editor) = self.editor.as_mut().filter(|_| self.mode == Mode::SampleEditor) else {
+            return false;
+        };
+        editor.redo();
+        true
+    }
+
+    /// Add the tracks of the MIDI file at `path` as clips on new tracks and take its tempo, and show the playlist, returning how many clips were added.
+    pub fn import_midi(&mut self, path: &Path) -> Result<usize, String> {
+        let file = midi::read(path)?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let count = self.playlist.import_midi(&file, &name);
+        if count > 0 {
+            self.playlist_revision += 1;
+            self.mode = Mode::Playlist;
+            self.edit = Some("Import MIDI file".into());
+        }
+        Ok(count)
+    }
+
+    /// Return the MIDI clips of the playlist as a MIDI file, or [`None`] if there are none.
+    pub fn export_midi(&self) -> Option<MidiFile> {
+        Some(self.playlist.export_midi()).filter(|file| !file.tracks.is_empty())
+    }
+
+    /// Move the playhead to a bar, beat and sixteenth counted from 1, returning where that is in time.
+    pub fn go_to(&mut self, bar: u32, beat: u32, sixteenth: u32) -> Duration {
+        let beats = f64::from(bar - 1).mul_add(f64::from(self.playlist.time_signature.beats_per_measure), f64::from(beat - 1)) + f64::from(sixteenth - 1) / 4.;
+        self.playlist.time = Time::from_beats(beats).unwrap_or_default();
+        self.playlist.now()
+    }
+
+    /// Return the bar and beat `time` into the playlist falls on, counted from 1.
+    pub fn bar_and_beat(&self, time: Duration) -> (u32, u32) {
+        let beats_per_measure = self.playlist.time_signature.beats_per_measure.max(1);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let beat = (time.as_secs_f64() * self.playlist.tempo.bps()) as u32;
+        (beat / beats_per_measure + 1, beat % beats_per_measure + 1)
+    }
+
+    /// Describe what's selected in the view shown, or return [`None`] if nothing is.
+    pub fn selection_summary(&self) -> Option<String> {
+        let nodes = |graph: Option<&Graph>| graph.and_then(|graph| graph.group(&self.group_path)).map_or(0, |graph| graph.selection.len());
+        match self.mode {
+            Mode::Playlist => Some(self.playlist.selection.len()).filter(|count| *count > 0).map(|count| format!("{count} clip(s) selected")),
+            Mode::Graph => Some(nodes(Some(&self.graph))).filter(|count| *count > 0).map(|count| format!("{count} node(s) selected")),
+            Mode::Inserts(track) => Some(nodes(self.inserts.get(&track))).filter(|count| *count > 0).map(|count| format!("{count} node(s) selected")),
+            Mode::SampleEditor => self.editor.as_ref()?.selected().map(|length| format!("{:.3} s selected", length.as_secs_f64())),
+        }
+    }
+
+    pub const fn playlist(&self) -> &Playlist {
+        &self.playlist
+    }
+
+    /// Replace the playlist, for example with one loaded from a project, reading the audio of its clips.
+    pub fn set_playlist(&mut self, mut playlist: Playlist) {
+        playlist.read_audio();
+        self.playlist = playlist;
+        self.playlist_revision += 1;
+    }
+
+    pub const fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    /// Replace the graph, for example with one loaded from a project.
+    pub fn set_graph(&mut self, graph: Graph) {
+        self.graph = graph;
+        self.group_path.clear();
+    }
+
+    pub const fn inserts(&self) -> &BTreeMap<u32, Graph> {
+        &self.inserts
+    }
+
+    /// Replace the insert chains of the tracks, for example with those loaded from a project.
+    pub fn set_inserts(&mut self, inserts: BTreeMap<u32, Graph>) {
+        self.inserts = inserts;
+        self.group_path.clear();
+    }
+
+    pub fn mappings(&self) -> &[Mapping] {
+        self.mappings.mappings()
+    }
+
+    /// Replace the MIDI mappings, for example with those loaded from a project.
+    pub fn set_mappings(&mut self, mappings: Vec<Mapping>) {
+        self.mappings.set(mappings);
+    }
+
+    /// Move the parameters the control of `change` is mapped to, or map it to the parameter waiting for a control to be moved.
+    pub fn control_changed(&mut self, change: ControlChange) {
+        let Some(mappings) = self.mappings.changed(change) else {
+            self.edit = Some("Map MIDI control".into());
+            return;
+        };
+        for mapping in mappings {
+            let root = match mapping.target.track {
+                Some(track) => self.inserts.get_mut(&track),
+                None => Some(&mut self.graph),
+            };
+            let Some(NodeData::Middle { effect, parameters }) = root.and_then(|root| root.node_mut(&mapping.target.path)).map(|node| &mut node.data) else {
+                continue;
+            };
+            let index = mapping.target.parameter;
+            let (Some(parameter), Some(current)) = (effect.parameters.get(index), parameters.get_mut(index)) else {
+                continue;
+            };
+            if let Some(value) = mapping.value(*current, change.value, &parameter.range) {
+                *current = value;
+                self.edit = Some("Change parameter".into());
+            }
+        }
+    }
+
+    /// Run the script `source` called `name` in the background over copies of the playlist and the insert chains, see [`script`].
+    pub fn run_script(&self, name: &str, source: &str) -> Receiver<script::Finished> {
+        script::spawn(name.to_string(), source.to_string(), self.playlist.clone(), self.inserts.clone())
+    }
+
+    /// Take what the script called `name` changed in the copies of the playlist and the insert chains it ran over.
+    pub fn finish_script(&mut self, name: &str, playlist: Playlist, inserts: BTreeMap<u32, Graph>) {
+        // How the playlist is viewed may have changed while the script ran, and scripts don't change it.
+        self.playlist.clips = playlist.clips;
+        self.playlist.tempo = playlist.tempo;
+        self.playlist.selection.retain(|index| *index < self.playlist.clips.len());
+        self.inserts = inserts;
+        self.playlist_revision += 1;
+        self.edit = Some(format!("Run {name}"));
+    }
+
+    /// Return the files used by clips and file players that don't exist anymore.
+    pub fn missing_files(&self) -> Vec<FileReference> {
+        let mut missing = BTreeMap::new();
+        let instruments = self.playlist.instruments.values().flat_map(Instrument::files);
+        for path in self.graph.files().into_iter().chain(self.inserts.values().flat_map(Graph::files)).chain(instruments) {
+            missing.entry(path.clone()).or_insert(None);
+        }
+        for clip in &self.playlist.clips {
+            // Clips of files that were already missing when the project was opened have no audio to compare.
+            if let ClipData::Audio { path, samples, channels, .. } = &clip.data {
+                let audio = missing.entry(path.clone()).or_insert(None);
+                if !samples.is_empty() {
+                    *audio = Some((Arc::clone(samples), *channels));
+                }
+            }
+        }
+        missing.into_iter().filter(|(path, _)| !path.exists()).map(|(path, audio)| FileReference { path, audio }).collect()
+    }
+
+    /// Return every file used by clips and file players.
+    pub fn files(&self) -> BTreeSet<PathBuf> {
+        self.graph.files().into_iter().chain(self.inserts.values().flat_map(Graph::files)).chain(self.playlist.files()).cloned().collect()
+    }
+
+    /// Point the clips and file players that use a file in `moves` to where it was moved, as the edit called `description`, returning how many were changed.
+    pub fn relink(&mut self, moves: &HashMap<PathBuf, PathBuf>, description: &str) -> usize {
+        let mut count = 0;
+        for path in self.graph.files_mut().into_iter().chain(self.inserts.values_mut().flat_map(Graph::files_mut)).chain(self.playlist.files_mut()) {
+            if let Some(new) = moves.get(path) {
+                path.clone_from(new);
+                count += 1;
+            }
         }
+        if count > 0 {
+            // Clips that had no audio because their files were missing can be heard again.
+            self.playlist.read_audio();
+            self.playlist_revision += 1;
+            self.edit = Some(description.into());
+        }
+        count
+    }
+
+    /// Show the spectrum analyzer and the scope in windows while `spectrum` and `scope` are true, returning whether the project has to be scheduled
+    /// again for them, see [`Analyzer::windows`].
+    pub fn analyzer_windows(&mut self, ctx: &Context, spectrum: &mut bool, scope: &mut bool) -> bool {
+        let tracks = self.playlist.clips.iter().map(|clip| clip.track + 1).chain(self.inserts.keys().map(|track| track + 1)).max().unwrap_or_default();
+        self.analyzer.windows(ctx, spectrum, scope, tracks)
+    }
+
+    /// Show the tuner in a window while `open` is true, returning whether the project has to be scheduled again for it, see [`Tuner::window`].
+    pub fn tuner_window(&mut self, ctx: &Context, open: &mut bool) -> bool {
+        let tracks = self.playlist.clips.iter().map(|clip| clip.track + 1).chain(self.inserts.keys().map(|track| track + 1)).max().unwrap_or_default();
+        self.tuner.window(ctx, open, tracks)
+    }
+
+    /// Show the loudness of the master output in a window while `open` is true, returning whether the project has to be scheduled again for it,
+    /// see [`Loudness::window`].
+    pub fn loudness_window(&mut self, ctx: &Context, open: &mut bool, reports: &mut bool) -> bool {
+        self.loudness.window(ctx, open, reports)
+    }
+
+    /// Return the project as it is now, to render its mix in the background in `channels` channels at `sample_rate`.
+    pub fn mixdown(&self, channels: u16, sample_rate: u32) -> Mixdown {
+        Mixdown {
+            graph: self.graph.clone(),
+            inserts: self.inserts.clone(),
+            playlist: self.playlist.clone(),
+            channels,
+            sample_rate,
+        }
+    }
+
+    pub const fn set_monitoring_latency(&mut self, latency: Option<Duration>) {
+        self.monitoring_latency = latency;
+    }
+
+    pub const fn set_meter_ballistics(&mut self, ballistics: Ballistics) {
+        self.meters.ballistics = ballistics;
+    }
+
+    /// Return the level meter of the master output, for the status bar.
+    pub fn master_meter(&mut self) -> impl Widget + '_ {
+        |ui: &mut Ui| self.meters.add(ui, &Point::Master, vec2(160., 10.))
+    }
+
+    /// Return the graph being edited, which is the main graph or the insert chain being shown, or a group in either.
+    fn current_graph(&mut self) -> &mut Graph {
+        let root = match self.mode.track() {
+            Some(track) => self.inserts.entry(track).or_insert_with(Graph::inserts),
+            None => &mut self.graph,
+        };
+        // The group may be gone, for example after undoing its creation.
+        if root.group(&self.group_path).is_none() {
+            self.group_path.clear();
+        }
+        root.group_mut(&self.group_path).unwrap()
+    }
+
+    pub fn snapshot(&self) -> CentralSnapshot {
+        CentralSnapshot {
+            clips: self.playlist.clips.clone(),
+            nodes: self.graph.nodes.clone(),
+            edges: self.graph.edges.clone(),
+            solo: self.graph.solo,
+            inserts: self.inserts.clone(),
+            mappings: self.mappings.mappings().to_vec(),
+            instruments: self.playlist.instruments.clone(),
+            inputs: self.playlist.inputs.clone(),
+            mixes: self.playlist.mixes.clone(),
+            tempo: self.playlist.tempo,
+            tempo_changes: self.playlist.tempo_changes.clone(),
+            time_signature: self.playlist.time_signature,
+        }
+    }
+
+    pub fn restore(&mut self, CentralSnapshot { clips, nodes, edges, solo, inserts, mappings, instruments, inputs, mixes, tempo, tempo_changes, time_signature }: CentralSnapshot) {
+        self.playlist.clips = clips;
+        self.playlist.tempo = tempo;
+        self.playlist.tempo_changes = tempo_changes;
+        self.playlist.time_signature = time_signature;
+        self.playlist.instruments = instruments;
+        self.playlist.inputs = inputs;
+        self.playlist.mixes = mixes;
+        self.playlist.selection.clear();
+        self.playlist_revision += 1;
+        self.graph.nodes = nodes;
+        self.graph.edges = edges;
+        self.graph.solo = solo;
+        self.graph.pending_connection = None;
+        self.graph.selection.clear();
+        self.inserts = inserts;
+        self.mappings.set(mappings);
+    }
+
+    /// Prepare the graph for playback by `engine`, feeding track inputs with the audio of the playlist after their insert chains.
+    pub fn schedule(&mut self, engine: &mut Engine) -> Result<Schedule, CycleError> {
+        let (channels, sample_rate) = (engine.channels(), engine.sample_rate());
+        let is_live_input = |data: &NodeData| matches!(data, NodeData::LiveInput);
+        let tuning_input = self.tuner.listening == Some(Listen::Input);
+        // Monitored tracks play the live input through their insert chain instead of their clips, even while playback is stopped.
+        let monitored = self.playlist.inputs.iter().filter(|(_, settings)| settings.is_monitored()).map(|(track, _)| *track).collect::<BTreeSet<_>>();
+        engine.set_monitoring(!monitored.is_empty());
+        engine.set_live_synth(self.playlist.live_synth());
+        engine.set_live_input(
+            tuning_input || !monitored.is_empty() || self.graph.contains(&is_live_input) || self.inserts.values().any(|graph| graph.contains(&is_live_input)),
+        );
+        let (graph, inserts) = (&self.graph, &self.inserts);
+        self.taps.retain(|(track, path), tap| {
+            let node = track.map_or_else(|| graph.node(path), |track| inserts.get(&track).and_then(|graph| graph.node(path)));
+            node.is_some_and(|node| node.data.is_visualization()) && tap.channels() == usize::from(channels)
+        });
+        // Meters, the analyzer and the tuner listen to the output of a track's insert chain, so a track without one goes through an empty one.
+        let tapped = self.analyzer.tapped;
+        let analyzer_tap = tapped.map(|_| self.analyzer.tap(usize::from(channels), f64::from(sample_rate)));
+        let tuned = match self.tuner.listening {
+            Some(Listen::Track(track)) => Some(track),
+            Some(Listen::Input) | None => None,
+        };
+        let analyzed = match tapped {
+            Some(Source::Track(track)) => Some(track),
+            Some(Source::Master) | None => None,
+        };
+        let mut inserts = Cow::Borrowed(&self.inserts);
+        for track in self.playlist.clips.iter().map(|clip| clip.track).chain(analyzed).chain(tuned).chain(monitored.iter().copied()) {
+            if !inserts.contains_key(&track) {
+                inserts.to_mut().insert(track, Graph::inserts());
+            }
+        }
+        // Meters only measure levels, so their taps keep no audio, unless the tuner or the loudness window listens there. Where the analyzer listens, the meter shares its tap.
+        let mut meter_taps = HashMap::new();
+        let mut meter_tap = |point: Point, length: usize, shared: Option<&Arc<Tap>>| {
+            let tap = shared.map_or_else(|| Arc::new(Tap::new(usize::from(channels), f64::from(sample_rate), length)), Arc::clone);
+            meter_taps.insert(point, Arc::clone(&tap));
+            schedule::Node::Tap(tap)
+        };
+        let mut schedule = self.graph.schedule(&inserts, &self.playlist.mixes, channels, sample_rate, |track, path, data| match data {
+            NodeData::Output if path == [NodeId::Output] => {
+                let shared = analyzer_tap.as_ref().filter(|_| tapped == Some(track.map_or(Source::Master, Source::Track)));
+                let length = match track {
+                    Some(_) if track == tuned => tuner::LENGTH,
+                    None if self.loudness.listening => loudness::LENGTH,
+                    Some(_) | None => 0,
+                };
+                meter_tap(track.map_or(Point::Master, Point::Track), length, shared)
+            }
+            NodeData::Mixer => meter_tap(Point::Mixer(track, path.to_vec()), 0, None),
+            NodeData::Output | NodeData::Group { .. } | NodeData::Bus { .. } | NodeData::GroupInput => schedule::Node::Sum,
+            NodeData::FilePlayer { path, looping } => path.as_deref().and_then(|path| engine.file_player(path, *looping)).unwrap_or(schedule::Node::Sum),
+            NodeData::TrackInput { track } if monitored.contains(track) => schedule::Node::Input(0),
+            NodeData::TrackInput { track } => {
+                let files = self.playlist.instrument_files(*track).into_iter().filter_map(|path| Some((path.clone(), engine.file(path)?))).collect();
+                schedule::Node::Track {
+                    samples: engine.track(*track, self.playlist_revision, || self.playlist.render_track(*track, channels, sample_rate, &files, false)),
+                    streams: self.playlist.streamed_clips(*track, sample_rate).into_iter().map(|clip| engine.stream(clip)).collect(),
+                }
+            }
+            NodeData::LiveInput => schedule::Node::Input(0),
+            NodeData::Meter | NodeData::Scope | NodeData::Spectrum => schedule::Node::Tap(Arc::clone(
+                self.taps
+                    .entry((track, path.to_vec()))
+                    .or_insert_with(|| Arc::new(Tap::new(usize::from(channels), f64::from(sample_rate), visualization::LENGTH))),
+            )),
+            NodeData::Middle { effect, parameters } => schedule::Node::Effect(effect.build(parameters)),
+        })?;
+        schedule.set_boundaries(self.playlist.clip_boundaries(sample_rate));
+        self.meters.set_taps(meter_taps);
+        self.tuner.tap = match self.tuner.listening {
+            Some(Listen::Input) => Some(engine.input_tap()),
+            Some(Listen::Track(track)) => self.meters.tap(&Point::Track(track)).cloned(),
+            None => None,
+        };
+        self.loudness.tap = self.loudness.listening.then(|| self.meters.tap(&Point::Master).cloned()).flatten();
+        Ok(schedule)
+    }
+
+    fn handle_playlist_keys(ui: &Ui, playlist: &mut Playlist, edit: &mut Option<String>) {
+        if ui.ctx().wants_keyboard_input() {
+            return;
+        }
+        let step = playlist.snapping.step();
+        let has_selection = !playlist.selection.is_empty();
+        ui.input_mut(|input| {
+            if input.consume_key(Modifiers::COMMAND, Key::ArrowUp) && has_selection {
+                playlist.move_selection_tracks(1);
+                *edit = Some("Move clips to another track".into());
+            }
+            if input.consume_key(Modifiers::COMMAND, Key::ArrowDown) && has_selection {
+                playlist.move_selection_tracks(-1);
+                *edit = Some("Move clips to another track".into());
+            }
+            if input.consume_key(Modifiers::NONE, Key::ArrowLeft) && has_selection {
+                playlist.nudge_selection(-step);
+                *edit = Some("Nudge clips".into());
+            }
+            if input.consume_key(Modifiers::NONE, Key::ArrowRight) && has_selection {
+                playlist.nudge_selection(step);
+                *edit = Some("Nudge clips".into());
+            }
+            if (input.consume_key(Modifiers::NONE, Key::Delete) || input.consume_key(Modifiers::NONE, Key::Backspace)) && has_selection {
+                playlist.delete_selection();
+                *edit = Some("Delete clips".into());
+            }
+            // Shift+Tab has to be checked first, as Tab on its own also matches it.
+            if input.consume_key(Modifiers::SHIFT, Key::Tab) {
+                playlist.cycle_selection(true);
+            }
+            if input.consume_key(Modifiers::NONE, Key::Tab) {
+                playlist.cycle_selection(false);
+            }
+        });
     }
 
-    fn add_playlist(ui: &mut Ui, playlist: &mut Playlist) -> Response {
+    /// Show the playlist with the level meter of each track, setting `opened` to a track whose insert chain or drum rack is to be shown, or a clip to
+    /// edit, and adding files dropped onto it to `added`.
+    #[allow(clippy::too_many_arguments, reason = "the playlist is shown from borrows of several fields of `Central`")]
+    fn add_playlist(
+        ui: &mut Ui,
+        playlist: &mut Playlist,
+        inserts: &mut BTreeMap<u32, Graph>,
+        meters: &mut Meters,
+        monitoring_latency: Option<Duration>,
+        opened: &mut Option<TrackView>,
+        edit: &mut Option<String>,
+        exported: &mut Vec<PathBuf>,
+        added: &mut Vec<PathBuf>,
+        slice: &mut Option<(usize, SliceInto, f64)>,
+        file_lengths: &mut LazyCache<FileLength>,
+        buses: &[SendTarget],
+        theme: &ThemeColors,
+    ) -> Response {
+        Self::handle_playlist_keys(ui, playlist, edit);
         playlist.zoom = playlist.zoom * ui.input(InputState::zoom_delta_2d);
         playlist.zoom += ui.input(|input| input.modifiers.alt.then_some(input.smooth_scroll_delta)).unwrap_or_default();
         playlist.zoom = playlist.zoom.max(vec2(50., 50.));
@@ -273,47 +932,74 @@ impl Central {
             .enable_scrolling(ui.input(|input| !input.modifiers.alt))
             .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
             .show(ui, |ui| {
+                let mut rows = Vec::new();
                 let response = ui
                     .with_layout(Layout::top_down(Align::Min), |ui| {
                         (0..=playlist.clips.iter().map(|clip| clip.track + 1).max().unwrap_or_default())
                             .rev()
                             .map(|y| {
                                 Frame::default()
-                                    .fill(ThemeColors::default().central_background)
+                                    .fill(theme.central_background)
                                     .show(ui, |ui| {
-                                        let (response, painter) = ui.allocate_painter(vec2(f32::INFINITY, playlist.zoom.y), Sense::hover());
-                                        if let Some(path) = response.dnd_release_payload::<PathBuf>() {
-                                            if let Some(start) = Time::from_beats(
-                                                f64::from((ui.input(|input| input.pointer.latest_pos().unwrap().x) - response.rect.min.x) / playlist.zoom.x)
-                                                    * f64::from(playlist.time_signature.beats_per_measure),
-                                            ) {
-                                                playlist.clips.push(Clip {
-                                                    start,
-                                                    track: y,
-                                                    data: ClipData::from_path((*path).clone()),
-                                                });
-                                            }
-                                        };
+                                        let (response, painter) = ui.allocate_painter(vec2(f32::INFINITY, playlist.zoom.y), Sense::click());
+                                        if response.clicked() {
+                                            playlist.selection.clear();
+                                        }
+                                        let inserts_response = Self::add_track_inserts(ui, &painter, response.rect, y, inserts.get(&y));
+                                        if inserts_response.double_clicked() {
+                                            *opened = Some(TrackView::Inserts(y));
+                                        }
+                                        inserts_response.context_menu(|ui| Self::add_track_instrument(ui, &mut playlist.instruments, y, opened, edit));
+                                        let meter_rect = Rect::from_min_size(inserts_response.rect.right_top() + vec2(4., 0.), vec2(60., inserts_response.rect.height()));
+                                        meters.show(ui, &Point::Track(y), meter_rect);
+                                        let input_end = Self::add_track_input(ui, &painter, meter_rect.right_top() + vec2(4., 0.), y, &mut playlist.inputs, monitoring_latency, edit);
+                                        Self::add_track_mix(ui, &painter, input_end + vec2(4., 0.), y, &mut playlist.mixes, buses, edit);
+                                        Self::handle_track_drop(ui, &response, playlist, inserts, y, edit, added);
+                                        rows.push((y, response.rect));
                                         #[allow(clippy::cast_precision_loss, reason = "rounding errors are negligible because this is a visual effect")]
                                         #[allow(clippy::cast_possible_truncation, reason = "truncation only occurs at unreasonably high numbers")]
-                                        for Clip { start, track, data } in &playlist.clips {
+                                        for index in 0..playlist.clips.len() {
+                                            // The context menu may have removed clips.
+                                            let Some(clip) = playlist.clips.get(index) else {
+                                                break;
+                                            };
+                                            let Clip {
+                                                start,
+                                                track,
+                                                processing,
+                                                name,
+                                                color,
+                                                ..
+                                            } = clip;
                                             if track != &y {
                                                 continue;
                                             }
                                             let left = (start.beats() as f32 / playlist.time_signature.beats_per_measure as f32).mul_add(playlist.zoom.x, response.rect.min.x);
                                             let width =
-                                                playlist.duration_of_clip(data).as_secs_f32() * playlist.tempo.bps() as f32 / playlist.time_signature.beats_per_measure as f32 * playlist.zoom.x;
+                                                playlist.duration_of_clip(clip).as_secs_f32() * playlist.tempo.bps() as f32 / playlist.time_signature.beats_per_measure as f32 * playlist.zoom.x;
                                             let rect = Rect::from_min_size(pos2(left, painter.clip_rect().top()), vec2(width, painter.clip_rect().height()));
-                                            painter.rect(rect, 4., Color32::GRAY, Stroke::new(2., Color32::DARK_GRAY));
+                                            let stroke_color = if playlist.selection.contains(&index) { Color32::WHITE } else { Color32::DARK_GRAY };
+                                            painter.rect(rect, 4., *color, Stroke::new(2., stroke_color));
                                             painter.debug_text(
                                                 rect.left_top(),
                                                 Align2::LEFT_TOP,
                                                 Color32::BLUE,
-                                                match data {
-                                                    ClipData::Audio { path, .. } => path.file_name().unwrap().to_string_lossy(),
-                                                    ClipData::Midi { .. } => "<midi data>".into(),
-                                                },
+                                                processing.describe().map_or_else(|| name.clone(), |description| format!("{name} ({description})")),
                                             );
+                                            let clip_response = ui.interact(rect, Id::new(("clip", index)), Sense::click_and_drag()).on_hover_and_drag_cursor(CursorIcon::Grab);
+                                            if clip_response.double_clicked() && matches!(clip.data, ClipData::Audio { .. }) {
+                                                *opened = Some(TrackView::SampleEditor(index));
+                                            }
+                                            if clip_response.clicked() {
+                                                if !ui.input(|input| input.modifiers.command || input.modifiers.shift) {
+                                                    playlist.selection.clear();
+                                                }
+                                                if !playlist.selection.remove(&index) {
+                                                    playlist.selection.insert(index);
+                                                }
+                                            }
+                                            Self::export_dragged_clips(ui, &clip_response, playlist, index, exported);
+                                            Self::add_clip_context_menu(&clip_response, response.rect.min.x, playlist, index, edit, slice);
                                         }
                                     })
                                     .response
@@ -322,114 +1008,1371 @@ impl Central {
                             .unwrap()
                     })
                     .response;
-                #[allow(clippy::cast_possible_truncation, reason = "truncation is intentional")]
-                #[allow(clippy::cast_precision_loss, reason = "rounding errors are negligible because this is a visual effect")]
-                for index in ((ui.clip_rect().left() - response.rect.min.x) / playlist.zoom.x) as i32..((ui.clip_rect().right() - response.rect.min.x) / playlist.zoom.x).ceil() as i32 {
-                    let x = (index as f32).mul_add(playlist.zoom.x, response.rect.min.x);
-                    ui.painter().vline(x, ui.clip_rect().y_range(), Stroke::new(1., hex_color!("5e5a75")));
-                    for sub_index in 1..playlist.time_signature.beats_per_measure {
-                        let x = (sub_index as f32).mul_add(playlist.zoom.x / playlist.time_signature.beats_per_measure as f32, x);
-                        ui.painter().vline(x, ui.clip_rect().y_range(), Stroke::new(1., hex_color!("2e2b3f")));
-                    }
-                }
+                // Files dropped below the tracks go on a new one.
+                let new_track = playlist.clips.iter().map(|clip| clip.track + 1).max().unwrap_or_default();
+                let below = ui.allocate_response(vec2(response.rect.width(), ui.available_height().max(playlist.zoom.y)), Sense::hover());
+                Self::handle_track_drop(ui, &below, playlist, inserts, new_track, edit, added);
+                Self::paint_grid(ui, playlist, response.rect.min.x, theme);
+                // The row of a new track is shown at the top of the space below the tracks.
+                rows.push((new_track, Rect::from_min_size(below.rect.left_top(), vec2(below.rect.width(), playlist.zoom.y))));
+                Self::paint_drop_preview(ui, playlist, file_lengths, &rows, below.rect);
                 response
             })
             .inner
     }
 
-    fn add_graph(ui: &mut Ui, Graph { nodes, pan_offset, drag_start_offset }: &mut Graph) -> Response {
-        let (_, rect) = ui.allocate_space(ui.available_size());
-        let painter = ui.painter_at(rect);
-        Frame::default()
-            .show(ui, |ui| {
-                let responses: HashMap<_, _> = nodes
-                    .iter()
-                    .map(|(id, node)| {
-                        let response = ui
-                            .allocate_new_ui(UiBuilder::new().max_rect(Rect::from_min_size(rect.center() + node.position + *pan_offset, Vec2::INFINITY)), |ui| {
-                                Frame::default()
-                                    .rounding(4.)
-                                    .inner_margin(4.)
-                                    .stroke(Stroke::new(1., hex_color!("80808080")))
-                                    .show(ui, |ui| {
-                                        ui.label("Effect");
-                                        ui.label(match &node.data {
-                                            NodeData::Output => "Output".to_string(),
-                                            NodeData::Middle { effect, output } => format!("{effect} to {output:?}"),
-                                        });
-                                    })
-                                    .response
-                            })
-                            .inner;
-                        (*id, response)
-                    })
-                    .collect();
-                let is_being_dragged = ui.ctx().is_being_dragged(Id::new("graph background"));
-                if is_being_dragged {
-                    let pos = ui.ctx().pointer_interact_pos().unwrap();
-                    if let Some(drag_start_offset) = drag_start_offset {
-                        *pan_offset = pos - rect.center() - *drag_start_offset;
-                    } else {
-                        *drag_start_offset = Some(pos - rect.center() - *pan_offset);
-                    }
-                } else {
-                    ui.interact(rect, Id::new("graph background"), Sense::click_and_drag()).on_hover_and_drag_cursor(CursorIcon::Grab);
-                    *drag_start_offset = None;
-                }
-                for (id, node) in nodes.iter_mut() {
-                    let is_being_dragged = ui.ctx().is_being_dragged(Id::new(id));
-                    if is_being_dragged {
-                        let pos = ui.ctx().pointer_interact_pos().unwrap();
-                        if let Some(drag_start_offset) = node.drag_start_offset {
-                            node.position = pos - rect.center() - drag_start_offset;
+    /// Draw a line at the start of every measure in view, and fainter ones at every beat, where `track_left` is where the tracks start on screen.
+    fn paint_grid(ui: &Ui, playlist: &Playlist, track_left: f32, theme: &ThemeColors) {
+        #[allow(clippy::cast_possible_truncation, reason = "truncation is intentional")]
+        #[allow(clippy::cast_precision_loss, reason = "rounding errors are negligible because this is a visual effect")]
+        for index in ((ui.clip_rect().left() - track_left) / playlist.zoom.x) as i32..((ui.clip_rect().right() - track_left) / playlist.zoom.x).ceil() as i32 {
+            let x = (index as f32).mul_add(playlist.zoom.x, track_left);
+            ui.painter().vline(x, ui.clip_rect().y_range(), Stroke::new(1., theme.playlist_bar));
+            for sub_index in 1..playlist.time_signature.beats_per_measure {
+                let x = (sub_index as f32).mul_add(playlist.zoom.x / playlist.time_signature.beats_per_measure as f32, x);
+                ui.painter().vline(x, ui.clip_rect().y_range(), Stroke::new(1., theme.playlist_beat));
+            }
+        }
+    }
+
+    /// Return where in the playlist the pointer at `x` is, in beats snapped to the grid, where `track_left` is where the tracks start on screen.
+    fn snapped_beats(playlist: &Playlist, x: f32, track_left: f32) -> f64 {
+        let beats = f64::from((x - track_left) / playlist.zoom.x) * f64::from(playlist.time_signature.beats_per_measure);
+        playlist.snapping.snap(beats.max(0.))
+    }
+
+    /// While files from the browser are dragged over the playlist, highlight the track they'd be dropped on and draw the clips they'd add where they'd go.
+    /// `rows` are the tracks shown with their rows, the last of which is the new track made by dropping them in the space `below` the tracks.
+    fn paint_drop_preview(ui: &Ui, playlist: &Playlist, file_lengths: &mut LazyCache<FileLength>, rows: &[(u32, Rect)], below: Rect) {
+        let paths = DragAndDrop::payload::<PathBuf>(ui.ctx())
+            .map(|path| vec![(*path).clone()])
+            .or_else(|| DragAndDrop::payload::<Vec<PathBuf>>(ui.ctx()).map(|paths| (*paths).clone()));
+        let (Some(paths), Some(pointer)) = (paths, ui.input(|input| input.pointer.hover_pos())) else {
+            return;
+        };
+        let row_of = |track: u32| rows.iter().find(|(other, _)| *other == track).map(|(_, rect)| *rect);
+        let target = if below.contains(pointer) { rows.last().map(|(track, _)| *track) } else { rows.iter().find(|(_, rect)| rect.y_range().contains(pointer.y)).map(|(track, _)| *track) };
+        let Some(track) = target.filter(|_| ui.clip_rect().contains(pointer)) else {
+            return;
+        };
+        if let Some(row) = row_of(track) {
+            ui.painter().rect_filled(row, 0., Color32::from_white_alpha(12));
+        }
+        let sequential = ui.input(|input| input.modifiers.shift);
+        let mut start = Self::snapped_beats(playlist, pointer.x, below.left());
+        for (index, path) in (0..).zip(&paths) {
+            let track = if sequential { track } else { track + index };
+            // Until the file is read, its clip is shown a beat long.
+            let beats = file_lengths.get(path).map_or(1., |length| playlist.beats_of(length));
+            if file_lengths.is_pending(path) {
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+            }
+            if let Some(row) = row_of(track) {
+                #[allow(clippy::cast_possible_truncation, reason = "this is a visual effect")]
+                let (left, width) = ((start / f64::from(playlist.time_signature.beats_per_measure)) as f32, (beats / f64::from(playlist.time_signature.beats_per_measure)) as f32);
+                let rect = Rect::from_min_size(pos2(left.mul_add(playlist.zoom.x, below.left()), row.top()), vec2(width * playlist.zoom.x, row.height()));
+                ui.painter().rect(rect, 4., Clip::DEFAULT_COLOR.gamma_multiply(0.5), Stroke::new(2., Color32::WHITE));
+                let name = path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
+                ui.painter().text(rect.left_top() + vec2(4., 4.), Align2::LEFT_TOP, name, FontId::proportional(11.), Color32::WHITE);
+            }
+            if sequential {
+                start += beats;
+            }
+        }
+    }
+
+    /// Add what was dropped on the row of `track` in the playlist: an effect to its insert chain, or clips of files from the browser snapped to the grid, which are
+    /// also added to `added`.
+    fn handle_track_drop(ui: &Ui, response: &Response, playlist: &mut Playlist, inserts: &mut BTreeMap<u32, Graph>, track: u32, edit: &mut Option<String>, added: &mut Vec<PathBuf>) {
+        if let Some(data) = response.dnd_release_payload::<NodeData>() {
+            inserts.entry(track).or_insert_with(Graph::inserts).insert_before_output((*data).clone());
+            *edit = Some("Add insert".into());
+        }
+        // Several files dragged from the browser go on the tracks from this one up, or one after the other on this one while holding shift.
+        let paths = response
+            .dnd_release_payload::<PathBuf>()
+            .map(|path| vec![(*path).clone()])
+            .or_else(|| response.dnd_release_payload::<Vec<PathBuf>>().map(|paths| (*paths).clone()));
+        if let Some(paths) = paths.filter(|paths| !paths.is_empty()) {
+            let x = ui.input(|input| input.pointer.latest_pos().unwrap().x);
+            if let Some(start) = Time::from_beats(Self::snapped_beats(playlist, x, response.rect.min.x)) {
+                playlist.add_files(start, track, &paths, ui.input(|input| input.modifiers.shift));
+                *edit = Some(if paths.len() > 1 { "Add clips" } else { "Add clip" }.into());
+                added.extend(paths);
+            }
+        }
+    }
+
+    /// Show the insert chain of a track at the left of its row in `rect`, staying in view when scrolling.
+    fn add_track_inserts(ui: &Ui, painter: &Painter, rect: Rect, track: u32, inserts: Option<&Graph>) -> Response {
+        let count = inserts.map(|graph| graph.nodes.values().filter(|node| !matches!(node.data, NodeData::Output | NodeData::GroupInput)).count());
+        let text = match count {
+            Some(count @ 1..) => format!("Inserts ({count})"),
+            _ => "Inserts".into(),
+        };
+        let galley = painter.layout_no_wrap(text, FontId::proportional(11.), ui.visuals().text_color());
+        let inserts_rect = Rect::from_min_size(pos2(ui.clip_rect().left().max(rect.left()), rect.top()) + vec2(4., 4.), galley.size() + vec2(8., 4.));
+        let response = ui
+            .interact(inserts_rect, Id::new(("inserts", track)), Sense::click())
+            .on_hover_text("Double-click to edit the track's insert chain, right-click to pick the instrument playing its MIDI clips");
+        let fill = if response.hovered() { hex_color!("00000080") } else { hex_color!("00000050") };
+        painter.rect_filled(inserts_rect, 4., fill);
+        painter.galley(inserts_rect.min + vec2(4., 2.), galley, Color32::PLACEHOLDER);
+        response
+    }
+
+    /// Show whether `track` is armed and how its input is monitored from `pos` on, in the height of its insert chain, returning where that ends. `latency`
+    /// is how far behind the input is heard while it's monitored.
+    fn add_track_input(ui: &Ui, painter: &Painter, pos: Pos2, track: u32, inputs: &mut BTreeMap<u32, InputSettings>, latency: Option<Duration>, edit: &mut Option<String>) -> Pos2 {
+        let mut settings = inputs.get(&track).copied().unwrap_or_default();
+        let font = FontId::proportional(11.);
+        let height = painter.layout_no_wrap("Inserts".into(), font.clone(), Color32::PLACEHOLDER).size().y + 4.;
+        let arm_rect = Rect::from_min_size(pos, Vec2::splat(height));
+        let arm = ui
+            .interact(arm_rect, Id::new(("arm", track)), Sense::click())
+            .on_hover_text("Arm the track to take in the live input, which is then heard through it when its monitoring is on Auto");
+        painter.rect_filled(arm_rect, 4., if arm.hovered() { hex_color!("00000080") } else { hex_color!("00000050") });
+        painter.circle_filled(arm_rect.center(), height / 4., if settings.armed { hex_color!("ff5c5c") } else { Color32::GRAY });
+        if arm.clicked() {
+            settings.armed = !settings.armed;
+            *edit = Some(if settings.armed { "Arm track" } else { "Disarm track" }.into());
+        }
+        let heard_after = latency.filter(|_| settings.is_monitored()).map(|latency| format!(" ({} ms)", latency.as_millis())).unwrap_or_default();
+        let galley = painter.layout_no_wrap(format!("Monitor: {}{heard_after}", settings.monitoring.name()), font, ui.visuals().text_color());
+        let monitoring_rect = Rect::from_min_size(arm_rect.right_top() + vec2(4., 0.), vec2(galley.size().x + 8., height));
+        let monitoring = ui.interact(monitoring_rect, Id::new(("monitoring", track)), Sense::click()).on_hover_ui(|ui| {
+            ui.label("Click to change whether the live input is heard through the track's insert chain, in place of its clips: never, while it's armed, or always");
+            match latency {
+                Some(latency) => ui.weak(format!("The input is heard {:.1} ms after it's played", latency.as_secs_f64() * 1000.)),
+                None => ui.weak("The latency shows once the input is recorded from"),
+            };
+        });
+        painter.rect_filled(monitoring_rect, 4., if monitoring.hovered() { hex_color!("00000080") } else { hex_color!("00000050") });
+        painter.galley(monitoring_rect.min + vec2(4., 2.), galley, Color32::PLACEHOLDER);
+        if monitoring.clicked() {
+            let next = Monitoring::ALL.iter().position(|mode| *mode == settings.monitoring).map_or(0, |index| (index + 1) % Monitoring::ALL.len());
+            settings.monitoring = Monitoring::ALL[next];
+            *edit = Some("Change monitoring".into());
+        }
+        if settings == InputSettings::default() {
+            inputs.remove(&track);
+        } else {
+            inputs.insert(track, settings);
+        }
+        monitoring_rect.right_top()
+    }
+
+    /// Show the fader of `track` from `pos` on, in the height of its insert chain, which opens a popup to change it along with how much of the track is
+    /// sent to each of the `buses` of the main graph.
+    fn add_track_mix(ui: &Ui, painter: &Painter, pos: Pos2, track: u32, mixes: &mut BTreeMap<u32, TrackMix>, buses: &[SendTarget], edit: &mut Option<String>) {
+        let mut mix = mixes.get(&track).cloned().unwrap_or_default();
+        let font = FontId::proportional(11.);
+        let height = painter.layout_no_wrap("Inserts".into(), font.clone(), Color32::PLACEHOLDER).size().y + 4.;
+        let sends = mix.sends.iter().filter(|send| buses.iter().any(|target| target.bus == send.bus)).count();
+        let text = if sends == 0 { format!("{:+.1} dB", mix.fader) } else { format!("{:+.1} dB, {sends} send(s)", mix.fader) };
+        let galley = painter.layout_no_wrap(text, font, ui.visuals().text_color());
+        let rect = Rect::from_min_size(pos, vec2(galley.size().x + 8., height));
+        let response = ui
+            .interact(rect, Id::new(("mix", track)), Sense::click())
+            .on_hover_text("Click to change the track's fader and how much of it is sent to each bus");
+        painter.rect_filled(rect, 4., if response.hovered() { hex_color!("00000080") } else { hex_color!("00000050") });
+        painter.galley(rect.min + vec2(4., 2.), galley, Color32::PLACEHOLDER);
+        let popup_id = response.id.with("sends");
+        if response.clicked() {
+            ui.memory_mut(|memory| memory.toggle_popup(popup_id));
+        }
+        egui::popup_below_widget(ui, popup_id, &response, PopupCloseBehavior::CloseOnClickOutside, |ui| {
+            ui.set_min_width(280.);
+            if ui.add(Slider::new(&mut mix.fader, -60.0..=12.0).suffix(" dB").text("Fader")).changed() {
+                *edit = Some("Change fader".into());
+            }
+            ui.separator();
+            if buses.is_empty() {
+                ui.weak("Add a bus to the main graph to send the track to it");
+            }
+            for SendTarget { bus, name, tracks } in buses {
+                ui.horizontal(|ui| {
+                    let index = mix.sends.iter().position(|send| send.bus == *bus);
+                    let mut sending = index.is_some();
+                    // A track played inside the bus would feed back into it.
+                    let feeds_back = tracks.contains(&track);
+                    let checkbox = ui
+                        .add_enabled(!feeds_back || sending, Checkbox::new(&mut sending, *name))
+                        .on_disabled_hover_text("The track is played inside this bus, so sending it there would feed back into the bus");
+                    if checkbox.changed() {
+                        if let Some(index) = index {
+                            mix.sends.remove(index);
+                            *edit = Some("Remove send".into());
                         } else {
-                            node.drag_start_offset = Some(pos - rect.center() - node.position);
+                            mix.sends.push(BusSend { bus: *bus, level: BusSend::DEFAULT_LEVEL, pre_fader: false });
+                            *edit = Some("Add send".into());
+                        }
+                    }
+                    let Some(send) = mix.sends.iter_mut().find(|send| send.bus == *bus) else {
+                        return;
+                    };
+                    if ui.add(Slider::new(&mut send.level, -60.0..=6.0).suffix(" dB")).changed() {
+                        *edit = Some("Change send level".into());
+                    }
+                    let pre_fader = ui
+                        .selectable_label(send.pre_fader, "Pre")
+                        .on_hover_text("Send the track before its fader, so that the fader doesn't change how much of it reaches the bus");
+                    if pre_fader.clicked() {
+                        send.pre_fader = !send.pre_fader;
+                        *edit = Some("Change send".into());
+                    }
+                });
+            }
+        });
+        if mix == TrackMix::default() {
+            mixes.remove(&track);
+        } else {
+            mixes.insert(track, mix);
+        }
+    }
+
+    /// Show the instrument playing the MIDI clips of `track`, with a menu for each section of the synth's settings, setting `opened` to the track if
+    /// its drum rack is to be shown.
+    fn add_track_instrument(ui: &mut Ui, instruments: &mut BTreeMap<u32, Instrument>, track: u32, opened: &mut Option<TrackView>, edit: &mut Option<String>) {
+        let instrument = instruments.get(&track).cloned().unwrap_or_default();
+        let is_drum_rack = matches!(instrument, Instrument::DrumRack { .. });
+        if ui.selectable_label(!is_drum_rack, "Synth").clicked() && is_drum_rack {
+            instruments.remove(&track);
+            *edit = Some("Use the synth".into());
+        }
+        if ui.selectable_label(is_drum_rack, "Drum rack").clicked() && !is_drum_rack {
+            instruments.insert(track, Instrument::drum_rack());
+            *opened = Some(TrackView::DrumRack(track));
+            *edit = Some("Use a drum rack".into());
+            ui.close_menu();
+        }
+        ui.separator();
+        let Instrument::Synth { mut values } = instrument else {
+            if ui.button("Edit drum rack…").clicked() {
+                *opened = Some(TrackView::DrumRack(track));
+                ui.close_menu();
+            }
+            return;
+        };
+        let mut changed = false;
+        let mut settings = synth::settings().zip(&mut values);
+        for section in synth::SECTIONS {
+            ui.menu_button(section.name, |ui| {
+                for (setting, value) in settings.by_ref().take(section.settings.len()) {
+                    changed |= Self::add_setting(ui, setting, value);
+                }
+            });
+        }
+        ui.separator();
+        if ui.add_enabled(instruments.contains_key(&track), Button::new("Reset synth")).clicked() {
+            instruments.remove(&track);
+            *edit = Some("Reset synth".into());
+            ui.close_menu();
+        } else if changed {
+            instruments.insert(track, Instrument::Synth { values });
+            *edit = Some("Change synth".into());
+        }
+    }
+
+    /// Show a setting of an instrument, as a slider or a list of its choices, returning whether it was changed.
+    fn add_setting(ui: &mut Ui, setting: &Setting, value: &mut f64) -> bool {
+        let parameter = &setting.parameter;
+        if setting.choices.is_empty() {
+            return ui.add(Slider::new(value, parameter.range.clone()).text(parameter.name)).changed();
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "choices are within their range")]
+        let selected = (value.round() as usize).min(setting.choices.len() - 1);
+        let mut changed = false;
+        ComboBox::from_label(parameter.name).selected_text(setting.choices[selected]).show_ui(ui, |ui| {
+            for (index, choice) in setting.choices.iter().enumerate() {
+                #[allow(clippy::cast_precision_loss, reason = "there are only a few choices")]
+                let index = index as f64;
+                changed |= ui.selectable_value(value, index, *choice).changed();
+            }
+        });
+        changed
+    }
+
+    /// Show the drum rack of the track it's open for in a window, where files dropped from the browser onto a pad become its sample.
+    fn drum_rack_window(&mut self, ctx: &Context) {
+        let Some(track) = self.drum_rack else {
+            return;
+        };
+        // The drum rack may be gone, for example after undoing its creation.
+        let Some(Instrument::DrumRack { pads }) = self.playlist.instruments.get_mut(&track) else {
+            self.drum_rack = None;
+            return;
+        };
+        let mut open = true;
+        let mut edit = None;
+        Window::new(format!("Track {} drum rack", track + 1)).id(Id::new("drum rack")).open(&mut open).resizable(false).show(ctx, |ui| {
+            Grid::new("pads").spacing(vec2(4., 4.)).show(ui, |ui| {
+                // The first pad is at the bottom left, like on drum machines.
+                for row in (0..4).rev() {
+                    for index in row * 4..(row * 4 + 4).min(pads.len()) {
+                        let pad = &mut pads[index];
+                        let name = pad.path.as_ref().and_then(|path| path.file_stem()).map_or_else(|| "Empty".into(), |name| name.to_string_lossy());
+                        #[allow(clippy::cast_possible_truncation, reason = "there are only a few pads")]
+                        let key = drum_rack::FIRST_KEY + index as u8;
+                        let text = format!("{}\n{}", note_name(key), name.chars().take(12).collect::<String>());
+                        let response = ui.add(Button::new(text).selected(self.selected_pad == index).min_size(vec2(80., 44.))).on_hover_text("Drop a file from the browser to play it on this pad");
+                        if response.clicked() {
+                            self.selected_pad = index;
+                        }
+                        let dropped = response.dnd_release_payload::<PathBuf>().map(|path| (*path).clone()).or_else(|| response.dnd_release_payload::<Vec<PathBuf>>()?.first().cloned());
+                        if let Some(path) = dropped {
+                            self.added.push(path.clone());
+                            pad.path = Some(path);
+                            self.selected_pad = index;
+                            edit = Some("Set the sample of a pad".into());
                         }
-                    } else {
-                        ui.interact(responses.get(id).unwrap().rect, Id::new(id), Sense::click_and_drag())
-                            .on_hover_and_drag_cursor(CursorIcon::Move);
-                        node.drag_start_offset = None;
                     }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            let Some(pad) = pads.get_mut(self.selected_pad) else {
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.label(pad.path.as_ref().map_or_else(|| "No sample".into(), |path| path.display().to_string()));
+                if ui.add_enabled(pad.path.is_some(), Button::new("🗙").small()).on_hover_text("Remove the sample").clicked() {
+                    pad.path = None;
+                    edit = Some("Remove the sample of a pad".into());
+                }
+            });
+            let mut changed = false;
+            for (setting, value) in drum_rack::SETTINGS.iter().zip(&mut pad.values) {
+                changed |= Self::add_setting(ui, setting, value);
+            }
+            if changed {
+                edit = Some("Change pad".into());
+            }
+        });
+        if !open {
+            self.drum_rack = None;
+        }
+        if edit.is_some() {
+            self.edit = edit;
+            self.playlist_revision += 1;
+        }
+    }
+
+    /// Export the clip at `index`, or the selection if it is part of it, once it has been dragged outside of the window.
+    ///
+    /// The windowing backend can't start a drag and drop into other applications, so the files are revealed in the file manager instead, from where they can be
+    /// dropped anywhere.
+    fn export_dragged_clips(ui: &Ui, response: &Response, playlist: &Playlist, index: usize, exported: &mut Vec<PathBuf>) {
+        let exported_id = response.id.with("exported");
+        if response.drag_stopped() {
+            ui.data_mut(|data| data.remove::<bool>(exported_id));
+        }
+        let outside = ui.input(|input| input.pointer.latest_pos()).is_none_or(|pos| !ui.ctx().screen_rect().contains(pos));
+        if !response.dragged() || !outside || ui.data(|data| data.get_temp(exported_id)).unwrap_or_default() {
+            return;
+        }
+        ui.data_mut(|data| data.insert_temp(exported_id, true));
+        let directory = std::env::temp_dir().join("volt");
+        let indices = if playlist.selection.contains(&index) { playlist.selection.iter().copied().collect_vec() } else { vec![index] };
+        let files = indices.iter().filter_map(|index| playlist.clips[*index].export(playlist.tempo, &directory)).collect_vec();
+        for folder in files.iter().filter_map(|file| file.parent()).unique() {
+            if let Err(error) = open::that_detached(folder) {
+                tracing::error!("Couldn't reveal {}: {error}", folder.display());
+            }
+        }
+        exported.extend(files);
+    }
+
+    /// Attach [`Self::clip_context_menu`] to a clip, splitting it where the menu was opened. `track_left` is where the track starts on screen.
+    fn add_clip_context_menu(response: &Response, track_left: f32, playlist: &mut Playlist, index: usize, edit: &mut Option<String>, slice: &mut Option<(usize, SliceInto, f64)>) {
+        let split_id = response.id.with("split");
+        if response.secondary_clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let beats = f64::from((pos.x - track_left) / playlist.zoom.x) * f64::from(playlist.time_signature.beats_per_measure);
+                response.ctx.data_mut(|data| data.insert_temp(split_id, playlist.snapping.snap(beats)));
+            }
+        }
+        response.context_menu(|ui| {
+            let split_at = ui.data(|data| data.get_temp(split_id)).and_then(Time::from_beats);
+            if let Some(description) = Self::clip_context_menu(ui, playlist, index, split_at, slice) {
+                *edit = Some(description.into());
+            }
+        });
+    }
+
+    /// Show everything that can be done to the clip at `index`, returning a description of the edit if one was made.
+    ///
+    /// `split_at` is where "Split here" splits the clip, normally where the menu was opened. Slicing the clip at its hits is asked for in `slice`.
+    fn clip_context_menu(ui: &mut Ui, playlist: &mut Playlist, index: usize, split_at: Option<Time>, slice: &mut Option<(usize, SliceInto, f64)>) -> Option<&'static str> {
+        let tempo = playlist.tempo;
+        let clip = &mut playlist.clips[index];
+        let mut edit = None;
+        ui.horizontal(|ui| {
+            if ui.color_edit_button_srgba(&mut clip.color).changed() {
+                edit = Some("Change clip color");
+            }
+            if ui.text_edit_singleline(&mut clip.name).changed() {
+                edit = Some("Rename clip");
+            }
+        });
+        ui.separator();
+        if Self::clip_processing_menu(ui, clip, tempo) {
+            edit = Some("Change clip processing");
+        }
+        let folder = match &clip.data {
+            ClipData::Audio { path, .. } => path.parent().map(Path::to_path_buf),
+            ClipData::Midi { .. } => None,
+        };
+        let is_audio = matches!(clip.data, ClipData::Audio { .. });
+        ui.separator();
+        if ui.add_enabled(split_at.is_some(), Button::new("Split here")).clicked() {
+            if split_at.is_some_and(|at| playlist.split_clip(index, at)) {
+                edit = Some("Split clip");
+            }
+            ui.close_menu();
+        }
+        if ui.button("Duplicate").clicked() {
+            playlist.duplicate_clip(index);
+            edit = Some("Duplicate clip");
+            ui.close_menu();
+        }
+        if ui.button("Delete").clicked() {
+            playlist.remove_clip(index);
+            edit = Some("Delete clip");
+            ui.close_menu();
+        }
+        if is_audio {
+            *slice = Self::slice_menu(ui).map(|(into, sensitivity)| (index, into, sensitivity)).or(*slice);
+        }
+        ui.separator();
+        if ui.add_enabled(folder.is_some(), Button::new("Open containing folder")).clicked() {
+            if let Some(folder) = folder {
+                if let Err(error) = open::that_detached(&folder) {
+                    tracing::error!("Couldn't open {}: {error}", folder.display());
+                }
+            }
+            ui.close_menu();
+        }
+        edit
+    }
+
+    /// Show how an audio clip can be sliced at its hits, returning what into and with which sensitivity if it's asked to be.
+    fn slice_menu(ui: &mut Ui) -> Option<(SliceInto, f64)> {
+        let mut slice = None;
+        ui.menu_button("Slice at transients", |ui| {
+            let id = Id::new("slice sensitivity");
+            let mut sensitivity = ui.data(|data| data.get_temp(id)).unwrap_or(0.5);
+            ui.add(Slider::new(&mut sensitivity, 0.0..=1.0).text("Sensitivity"));
+            ui.data_mut(|data| data.insert_temp(id, sensitivity));
+            if ui.button("Into clips").on_hover_text("Split the clip at every hit").clicked() {
+                slice = Some((SliceInto::Clips, sensitivity));
+                ui.close_menu();
+            }
+            let hover = format!("Put the slices on the pads of a drum rack on a new track, with a clip playing them like the original. There can be up to {}", drum_rack::PADS);
+            if ui.button("Onto a drum rack").on_hover_text(hover).clicked() {
+                slice = Some((SliceInto::DrumRack, sensitivity));
+                ui.close_menu();
+            }
+        });
+        slice
+    }
+
+    /// Show the processing options of a clip, returning whether any of them changed.
+    fn clip_processing_menu(ui: &mut Ui, Clip { data, processing, .. }: &mut Clip, tempo: Tempo) -> bool {
+        let before = *processing;
+        ui.add_enabled_ui(matches!(data, ClipData::Audio { .. }), |ui| {
+            ui.checkbox(&mut processing.reversed, "Reverse");
+            ui.checkbox(&mut processing.inverted, "Invert phase");
+            ui.menu_button("Normalize", |ui| {
+                let normalize = &mut processing.normalize;
+                let is_peak = matches!(normalize, Some(NormalizeTarget::Peak(_)));
+                let is_loudness = matches!(normalize, Some(NormalizeTarget::Loudness(_)));
+                if ui.radio(normalize.is_none(), "Off").clicked() {
+                    *normalize = None;
+                }
+                if ui.radio(is_peak, "Peak").clicked() && !is_peak {
+                    *normalize = Some(NormalizeTarget::Peak(ClipProcessing::DEFAULT_PEAK));
                 }
-                for (a, b) in nodes.iter().filter_map(move |(id, node)| {
-                    if let NodeData::Middle { output: Some(output), .. } = &node.data {
-                        Some((responses.get(id).unwrap().rect, responses.get(output).unwrap().rect))
+                if ui.radio(is_loudness, "Loudness").clicked() && !is_loudness {
+                    *normalize = Some(NormalizeTarget::Loudness(ClipProcessing::DEFAULT_LOUDNESS));
+                }
+                match normalize {
+                    Some(NormalizeTarget::Peak(decibels)) => ui.add(DragValue::new(decibels).range(-60.0..=0.0).speed(0.1).suffix(" dBFS")),
+                    Some(NormalizeTarget::Loudness(lufs)) => ui.add(DragValue::new(lufs).range(-60.0..=0.0).speed(0.1).suffix(" LUFS")),
+                    None => return,
+                };
+            });
+            ui.menu_button("Stretch to tempo", |ui| {
+                let detected_bpm = data.detect_bpm();
+                let mut enabled = matches!(processing.stretch, Stretch::Tempo { .. });
+                if ui.checkbox(&mut enabled, "Follow project tempo").changed() {
+                    processing.stretch = if enabled {
+                        Stretch::Tempo {
+                            source_bpm: detected_bpm.unwrap_or_else(|| tempo.bpm()),
+                        }
                     } else {
-                        None
+                        Stretch::Off
+                    };
+                }
+                if let Stretch::Tempo { source_bpm } = &mut processing.stretch {
+                    ui.horizontal(|ui| {
+                        ui.label("Source tempo");
+                        ui.add(DragValue::new(source_bpm).range(20.0..=999.0).speed(0.1).suffix(" BPM"));
+                    });
+                    if let Some(bpm) = detected_bpm {
+                        if ui.button(format!("Use detected tempo ({bpm:.2} BPM)")).clicked() {
+                            *source_bpm = bpm;
+                        }
+                    }
+                }
+            });
+            ui.menu_button("Fades", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Fade in");
+                    ui.add(DragValue::new(&mut processing.fade_in).range(0.0..=60.0).speed(0.01).suffix(" s"));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Fade out");
+                    ui.add(DragValue::new(&mut processing.fade_out).range(0.0..=60.0).speed(0.01).suffix(" s"));
+                });
+            });
+        });
+        *processing != before
+    }
+
+    /// How far duplicated nodes are placed from the originals.
+    const DUPLICATE_OFFSET: Vec2 = vec2(40., 40.);
+
+    /// Handle the graph's shortcuts, pasting nodes around `paste_position`. Returns the group to open, if any.
+    fn handle_graph_keys(ui: &Ui, graph: &mut Graph, paste_position: Vec2, edit: &mut Option<String>) -> Option<NodeId> {
+        if ui.ctx().wants_keyboard_input() {
+            return None;
+        }
+        let (copy, cut, pasted) = ui.input(|input| {
+            (
+                input.events.contains(&Event::Copy),
+                input.events.contains(&Event::Cut),
+                input.events.iter().find_map(|event| match event {
+                    Event::Paste(text) => Some(text.clone()),
+                    _ => None,
+                }),
+            )
+        });
+        // Anything else on the clipboard isn't meant for the graph, so it's ignored.
+        if let Some(subgraph) = pasted.and_then(|text| toml::from_str::<Subgraph>(&text).ok()) {
+            graph.paste(subgraph, paste_position);
+            *edit = Some("Paste nodes".into());
+        }
+        if graph.selection.is_empty() {
+            return None;
+        }
+        if copy || cut {
+            // Copied nodes are saved like a project, so that they can be pasted into another one.
+            match graph.copy_selection().map(|subgraph| toml::to_string(&subgraph)) {
+                Some(Ok(text)) => ui.ctx().copy_text(text),
+                Some(Err(error)) => tracing::error!("Couldn't copy the nodes: {error}"),
+                None => {}
+            }
+        }
+        if cut {
+            graph.remove_selection();
+            *edit = Some("Cut nodes".into());
+        }
+        if ui.input_mut(|input| input.consume_key(Modifiers::COMMAND, Key::D)) {
+            if let Some(subgraph) = graph.copy_selection() {
+                let position = subgraph.center() + Self::DUPLICATE_OFFSET;
+                graph.paste(subgraph, position);
+                *edit = Some("Duplicate nodes".into());
+            }
+        }
+        if ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Delete) || input.consume_key(Modifiers::NONE, Key::Backspace)) {
+            graph.remove_selection();
+            *edit = Some("Delete nodes".into());
+        }
+        if ui.input_mut(|input| input.consume_key(Modifiers::COMMAND, Key::G)) && graph.group_selection().is_some() {
+            *edit = Some("Group nodes".into());
+        }
+        // Bypass all of the selected nodes, unless they all already are. The output can't be bypassed.
+        let selection = graph.selection.iter().filter(|id| **id != NodeId::Output).copied().collect_vec();
+        if !selection.is_empty() && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::B)) {
+            let bypassed = !selection.iter().all(|id| graph.nodes[id].bypassed);
+            for id in selection {
+                graph.nodes.get_mut(&id).unwrap().bypassed = bypassed;
+            }
+            *edit = Some("Bypass nodes".into());
+        }
+        let id = *graph.selection.iter().exactly_one().ok()?;
+        // Like bypassing, soloing the output would do nothing.
+        if id != NodeId::Output && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::S)) {
+            graph.solo = if graph.solo == Some(id) { None } else { Some(id) };
+            *edit = Some("Solo node".into());
+        }
+        let is_group = matches!(graph.nodes[&id].data, NodeData::Group { .. } | NodeData::Bus { .. });
+        (is_group && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Enter))).then_some(id)
+    }
+
+    /// Show the graph, with its contents in a layer of their own so that they can be zoomed.
+    ///
+    /// `group_path` leads to the group whose graph is shown from the main graph or the insert chain of `track`, and the groups opened from it are added to it.
+    /// The faders and sends of the tracks in `mixes` decide which nodes the solo leaves audible.
+    #[allow(clippy::too_many_arguments, reason = "the graph is shown from borrows of several fields of `Central`")]
+    fn add_graph(
+        ui: &mut Ui,
+        graph: &mut Graph,
+        track: Option<u32>,
+        group_path: &mut Vec<NodeId>,
+        taps: &HashMap<(Option<u32>, Vec<NodeId>), Arc<Tap>>,
+        mixes: &BTreeMap<u32, TrackMix>,
+        meters: &mut Meters,
+        mappings: &mut Mappings,
+        fit: bool,
+        edit: &mut Option<String>,
+    ) -> Response {
+        let (id, rect) = ui.allocate_space(ui.available_size());
+        let area_id = id.with("contents");
+        let layer_id = LayerId::new(Order::Background, area_id);
+        let hover_pos = ui
+            .ctx()
+            .pointer_hover_pos()
+            .filter(|pos| rect.contains(*pos) && ui.ctx().layer_id_at(*pos).is_some_and(|layer| layer == ui.layer_id() || layer == layer_id));
+        if let Some(hover_pos) = hover_pos {
+            let (scroll, zoom) = ui.input(|input| (input.smooth_scroll_delta.y, input.zoom_delta()));
+            let factor = zoom * (scroll / 200.).exp();
+            if (factor - 1.).abs() > f32::EPSILON {
+                graph.zoom_around(rect, hover_pos, factor);
+            }
+        }
+        let fit = fit || (!ui.ctx().wants_keyboard_input() && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::F)));
+        let transform = graph.transform(rect);
+        let paste_position = hover_pos.map_or_else(|| graph.view_center(), |pos| (transform.inverse() * pos).to_vec2());
+        let mut opened = Self::handle_graph_keys(ui, graph, paste_position, edit);
+        ui.ctx().set_transform_layer(layer_id, transform);
+        ui.ctx().set_sublayer(ui.layer_id(), layer_id);
+        let muted = graph.nodes.keys().copied().filter(|id| !graph.is_audible(*id, mixes)).collect::<HashSet<_>>();
+        Area::new(area_id)
+            .order(Order::Background)
+            .fixed_pos(Pos2::ZERO)
+            .constrain(false)
+            .sense(Sense::hover())
+            .show(ui.ctx(), |ui| {
+                let view = transform.inverse() * rect;
+                ui.set_clip_rect(view);
+                let painter = ui.painter().clone();
+                let Graph { nodes, selection, solo, .. } = &mut *graph;
+                let mut headers = HashMap::new();
+                let responses: HashMap<_, _> = nodes
+                    .iter_mut()
+                    .map(|(id, node)| {
+                        let (response, header) = ui
+                            .allocate_new_ui(UiBuilder::new().max_rect(Rect::from_min_size(node.position.to_pos2(), Vec2::INFINITY)), |ui| {
+                                if node.bypassed || muted.contains(id) {
+                                    ui.multiply_opacity(0.5);
+                                }
+                                let path = [group_path.as_slice(), &[*id]].concat();
+                                let tap = taps.get(&(track, path.clone()));
+                                let target = Target { track, path, parameter: 0 };
+                                Self::add_node_body(ui, *id, node, selection.contains(id), solo, (tap.map(AsRef::as_ref), meters), (mappings, target), edit)
+                            })
+                            .inner;
+                        node.size = response.rect.size();
+                        headers.insert(*id, header);
+                        (*id, response)
+                    })
+                    .collect();
+                let background = Self::drag_background(ui, rect, transform, &painter, graph, &responses);
+                let node_responses = Self::drag_nodes(ui, transform, &mut graph.nodes, &mut graph.selection, &headers, edit);
+                let pointer_pos = ui.ctx().pointer_latest_pos().map(|pos| transform.inverse() * pos);
+                let hovered_edge = Self::draw_connections(pointer_pos, &painter, &graph.edges, &responses);
+                Self::add_ports(ui, pointer_pos, &painter, graph, &responses);
+                Self::finish_pending_connection(ui, pointer_pos, graph, &responses, edit);
+                for (id, response) in node_responses {
+                    if response.double_clicked() && matches!(graph.nodes[&id].data, NodeData::Group { .. } | NodeData::Bus { .. }) {
+                        opened = Some(id);
                     }
-                }) {
-                    const RESOLUTION: usize = 20;
-                    let a = a.right_center();
-                    let b = b.left_center();
-                    let strength = 100_f32.min(a.distance(b) / 2.);
-
-                    for (a, b) in (0..=RESOLUTION)
-                        .map(|t| {
-                            #[allow(clippy::cast_precision_loss, reason = "rounding errors are negligible because this is a visual effect")]
-                            let t = t as f32 / RESOLUTION as f32;
-
-                            (1. - t).powi(3) * a
-                                + (3. * (1. - t).powi(2) * t * (a + vec2(strength, 0.))).to_vec2()
-                                + (3. * (1. - t) * t.powi(2) * (b - vec2(strength, 0.))).to_vec2()
-                                + (t.powi(3) * b).to_vec2()
-                        })
-                        .tuple_windows()
-                    {
-                        #[allow(clippy::tuple_array_conversions, reason = "this looks fine")]
-                        painter.line_segment([a, b], Stroke::new(2., hex_color!("#80808080")));
+                    Self::add_node_context_menu(&response, id, graph, edit);
+                }
+                if let Some(background) = background {
+                    if let Some(data) = background.dnd_release_payload::<NodeData>() {
+                        graph.add_node((*data).clone(), paste_position);
+                        *edit = Some("Add node".into());
                     }
+                    Self::add_graph_background_menu(&background, hovered_edge, graph, track.is_none() && group_path.is_empty(), edit);
                 }
+                let bounds = responses.values().map(|response| response.rect).reduce(Rect::union);
+                if graph.show_minimap {
+                    Self::add_minimap(ui, rect, bounds.unwrap_or(view), graph);
+                }
+                if let Some(bounds) = bounds.filter(|_| fit) {
+                    graph.fit(rect, bounds);
+                }
+                group_path.extend(opened);
             })
             .response
     }
+
+    /// Let the view be moved by dragging the background, or nodes be selected by dragging a rectangle around them while holding shift.
+    ///
+    /// Returns the response of the background, unless it's being dragged.
+    fn drag_background(ui: &Ui, rect: Rect, transform: TSTransform, painter: &Painter, graph: &mut Graph, responses: &HashMap<NodeId, Response>) -> Option<Response> {
+        let view = transform.inverse() * rect;
+        let background = if ui.ctx().is_being_dragged(Id::new("graph background")) {
+            let pos = ui.ctx().pointer_interact_pos().unwrap();
+            if let Some((_, end)) = &mut graph.marquee {
+                *end = transform.inverse() * pos;
+            } else if let Some(drag_start_offset) = graph.drag_start_offset {
+                graph.pan_offset = pos - rect.center() - drag_start_offset;
+            } else if ui.input(|input| input.modifiers.shift) {
+                let start = ui.input(|input| input.pointer.press_origin()).unwrap_or(pos);
+                graph.marquee = Some((transform.inverse() * start, transform.inverse() * pos));
+            } else {
+                graph.drag_start_offset = Some(pos - rect.center() - graph.pan_offset);
+            }
+            None
+        } else {
+            graph.drag_start_offset = None;
+            if let Some((start, end)) = graph.marquee.take() {
+                let marquee = Rect::from_two_pos(start, end);
+                graph.selection.extend(responses.iter().filter(|(_, response)| marquee.intersects(response.rect)).map(|(id, _)| *id));
+            }
+            Some(ui.interact(view, Id::new("graph background"), Sense::click_and_drag()).on_hover_and_drag_cursor(CursorIcon::Grab))
+        };
+        if let Some((start, end)) = graph.marquee {
+            let color = ui.visuals().selection.bg_fill;
+            painter.rect(Rect::from_two_pos(start, end), 0., color.gamma_multiply(0.2), Stroke::new(1. / transform.scaling, color));
+        }
+        if background.as_ref().is_some_and(Response::clicked) {
+            graph.selection.clear();
+        }
+        background
+    }
+
+    /// Let nodes be moved by their headers and selected by clicking them, returning the responses of the headers that aren't being dragged.
+    fn drag_nodes(
+        ui: &Ui,
+        transform: TSTransform,
+        nodes: &mut HashMap<NodeId, Node>,
+        selection: &mut HashSet<NodeId>,
+        headers: &HashMap<NodeId, Rect>,
+        edit: &mut Option<String>,
+    ) -> Vec<(NodeId, Response)> {
+        let mut node_responses = Vec::new();
+        for (id, node) in nodes.iter_mut() {
+            let is_being_dragged = ui.ctx().is_being_dragged(Id::new(id));
+            if is_being_dragged {
+                let pos = transform.inverse() * ui.ctx().pointer_interact_pos().unwrap();
+                if let Some(drag_start_offset) = node.drag_start_offset {
+                    node.position = pos.to_vec2() - drag_start_offset;
+                } else {
+                    node.drag_start_offset = Some(pos.to_vec2() - node.position);
+                }
+            } else {
+                let response = ui
+                    .interact(headers[id], Id::new(id), Sense::click_and_drag())
+                    .on_hover_and_drag_cursor(CursorIcon::Move);
+                if response.clicked() {
+                    if !ui.input(|input| input.modifiers.command || input.modifiers.shift) {
+                        selection.clear();
+                    }
+                    if !selection.remove(id) {
+                        selection.insert(*id);
+                    }
+                }
+                node_responses.push((*id, response));
+                if node.drag_start_offset.take().is_some() {
+                    *edit = Some("Move node".into());
+                }
+            }
+        }
+        node_responses
+    }
+
+    /// Show an overview of the graph in the corner of `rect`, where `bounds` surrounds every node. Clicking or dragging on it moves the view there.
+    fn add_minimap(ui: &Ui, rect: Rect, bounds: Rect, graph: &mut Graph) {
+        const SIZE: Vec2 = vec2(160., 100.);
+        const MARGIN: f32 = 8.;
+        let transform = graph.transform(rect);
+        let view = transform.inverse() * rect;
+        // The minimap stays the same size on the screen, so it is placed in graph coordinates from where it is on the screen.
+        let minimap = transform.inverse() * Rect::from_min_size(rect.right_bottom() - SIZE - Vec2::splat(MARGIN), SIZE);
+        let scale = (minimap.size() / bounds.size().max(Vec2::splat(1.))).min_elem();
+        let to_minimap = |rect: Rect| Rect::from_center_size(minimap.center() + (rect.center() - bounds.center()) * scale, rect.size() * scale);
+        let painter = ui.painter_at(minimap);
+        painter.rect_filled(minimap, 4. / graph.zoom, hex_color!("000000a0"));
+        for (id, node) in &graph.nodes {
+            let node_rect = Rect::from_min_size(node.position.to_pos2(), node.size);
+            let color = if graph.selection.contains(id) { Color32::WHITE } else { hex_color!("808080") };
+            painter.rect_filled(to_minimap(node_rect), 0., color);
+        }
+        painter.rect_stroke(to_minimap(view), 0., Stroke::new(1. / graph.zoom, hex_color!("8c8cff")));
+        let response = ui.interact(minimap, ui.id().with("minimap"), Sense::click_and_drag());
+        if response.clicked() || response.dragged() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                graph.center_on(bounds.center() + (pos - minimap.center()) / scale);
+            }
+        }
+    }
+
+    /// Show a node and an editor for each of its effect's parameters, returning the node's response and the header that it is dragged by.
+    ///
+    /// Controls are mapped to the parameters of the node, which are found at `target`, through `mappings`.
+    #[allow(clippy::too_many_arguments, reason = "the node is shown from borrows of several fields of its graph")]
+    fn add_node_body(
+        ui: &mut Ui,
+        id: NodeId,
+        node: &mut Node,
+        selected: bool,
+        solo: &mut Option<NodeId>,
+        taps: (Option<&Tap>, &mut Meters),
+        (mappings, target): (&mut Mappings, Target),
+        edit: &mut Option<String>,
+    ) -> (Response, Rect) {
+        let stroke_color = if *solo == Some(id) {
+            hex_color!("ffd24d")
+        } else if selected {
+            Color32::WHITE
+        } else {
+            hex_color!("80808080")
+        };
+        let mut header = Rect::NOTHING;
+        let response = Frame::default()
+            .rounding(4.)
+            .inner_margin(4.)
+            .stroke(Stroke::new(1., stroke_color))
+            .show(ui, |ui| {
+                Self::add_node_parameters(ui, &mut node.data, &mut header, taps, mappings, target, edit);
+                if id != NodeId::Output {
+                    ui.horizontal(|ui| {
+                        if ui.toggle_value(&mut node.bypassed, "Bypass").on_hover_shortcut("Let the audio through without the effect", "B").changed() {
+                            *edit = Some("Bypass nodes".into());
+                        }
+                        let mut soloed = *solo == Some(id);
+                        if ui.toggle_value(&mut soloed, "Solo").on_hover_shortcut("Only hear what goes through this node", "S").changed() {
+                            *solo = soloed.then_some(id);
+                            *edit = Some("Solo node".into());
+                        }
+                    });
+                }
+            })
+            .response;
+        if let NodeData::FilePlayer { path, .. } = &mut node.data {
+            if let Some(dropped) = response.dnd_release_payload::<PathBuf>() {
+                *path = Some((*dropped).clone());
+                *edit = Some("Change parameter".into());
+            }
+        }
+        let header = Rect::from_x_y_ranges(response.rect.x_range(), response.rect.top()..=header.bottom());
+        (response, header)
+    }
+
+    /// Show the name of a node, setting `header` to where it is, followed by editors for its settings, the audio in `tap`, or its level in `meters`.
+    fn add_node_parameters(
+        ui: &mut Ui,
+        data: &mut NodeData,
+        header: &mut Rect,
+        (tap, meters): (Option<&Tap>, &mut Meters),
+        mappings: &mut Mappings,
+        mut target: Target,
+        edit: &mut Option<String>,
+    ) {
+        match data {
+            NodeData::Output => *header = ui.label("Output").rect,
+            NodeData::Mixer => {
+                *header = ui.label("Mixer").rect;
+                meters.add(ui, &Point::Mixer(target.track, target.path.clone()), vec2(160., 12.));
+            }
+            NodeData::FilePlayer { path, looping } => {
+                *header = ui.label("File player").rect;
+                match path {
+                    Some(path) => ui.label(path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())),
+                    None => ui.weak("Drop a file here"),
+                };
+                if ui.checkbox(looping, "Loop").changed() {
+                    *edit = Some("Change parameter".into());
+                }
+            }
+            NodeData::TrackInput { track } => {
+                *header = ui.label("Track input").rect;
+                let track_number = DragValue::new(track)
+                    .prefix("Track ")
+                    .custom_formatter(|track, _| (track + 1.).to_string())
+                    .custom_parser(|text| text.parse::<f64>().ok().map(|track| track - 1.));
+                if ui.add(track_number).changed() {
+                    *edit = Some("Change parameter".into());
+                }
+            }
+            NodeData::LiveInput => {
+                *header = ui.label("Live input").rect;
+                ui.weak("Default capture device");
+            }
+            NodeData::Meter => {
+                *header = ui.label("Meter").rect;
+                visualization::meter(ui, tap);
+            }
+            NodeData::Scope => {
+                *header = ui.label("Scope").rect;
+                visualization::scope(ui, tap);
+            }
+            NodeData::Spectrum => {
+                *header = ui.label("Spectrum").rect;
+                visualization::spectrum(ui, tap);
+            }
+            NodeData::Group { name, graph } => {
+                *header = ui.label("Group").rect;
+                if ui.add(TextEdit::singleline(name).desired_width(120.)).changed() {
+                    *edit = Some("Rename group".into());
+                }
+                ui.weak(format!("{} nodes, double-click to open", graph.nodes.len()));
+            }
+            NodeData::Bus { name, graph } => {
+                *header = ui.label("Bus").rect;
+                if ui.add(TextEdit::singleline(name).desired_width(120.)).changed() {
+                    *edit = Some("Rename bus".into());
+                }
+                ui.weak(format!("{} inserts, double-click to open", graph.nodes.values().filter(|node| !matches!(node.data, NodeData::Output | NodeData::GroupInput)).count()));
+            }
+            NodeData::GroupInput => {
+                *header = ui.label("Group input").rect;
+                ui.weak("What goes into the group");
+            }
+            NodeData::Middle { effect, parameters } => {
+                *header = ui.label(effect.name).rect;
+                for (index, (parameter, value)) in effect.parameters.iter().zip(parameters).enumerate() {
+                    let response = ui.add(Slider::new(value, parameter.range.clone()).text(parameter.name));
+                    if response.changed() {
+                        *edit = Some("Change parameter".into());
+                    }
+                    target.parameter = index;
+                    if let Some(mapping_edit) = mappings.parameter_menu(&response, &target) {
+                        *edit = Some(mapping_edit);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw every connection, returning the one under the pointer if there is one.
+    fn draw_connections(pointer_pos: Option<Pos2>, painter: &Painter, edges: &HashSet<Edge>, responses: &HashMap<NodeId, Response>) -> Option<Edge> {
+        let mut hovered_edge = None;
+        for edge in edges {
+            let curve = Self::connection_curve(responses[&edge.from].rect.right_center(), responses[&edge.to].rect.left_center());
+            let hovered = hovered_edge.is_none() && pointer_pos.is_some_and(|pointer_pos| curve.iter().any(|point| point.distance(pointer_pos) < Self::PORT_RADIUS));
+            if hovered {
+                hovered_edge = Some(*edge);
+            }
+            Self::draw_connection(painter, &curve, hovered);
+        }
+        hovered_edge
+    }
+
+    /// Add the input and output ports of every node, starting to drag a connection when one of them is dragged, and draw the connection being dragged.
+    fn add_ports(ui: &Ui, pointer_pos: Option<Pos2>, painter: &Painter, graph: &mut Graph, responses: &HashMap<NodeId, Response>) {
+        for (id, node) in &graph.nodes {
+            let node_rect = responses[id].rect;
+            if node.data.has_input() {
+                let port = Self::add_port(ui, painter, node_rect.left_center(), Id::new(("input port", id)));
+                if port.drag_started() {
+                    // Pick the existing connection up, so that it can be moved to another input or dropped to remove it.
+                    if let Some(edge) = graph.edges.iter().find(|edge| edge.to == *id).copied() {
+                        graph.edges.remove(&edge);
+                        graph.pending_connection = Some(PendingConnection { from: edge.from, detached: Some(edge) });
+                    }
+                }
+            }
+            if node.data.has_output() && Self::add_port(ui, painter, node_rect.right_center(), Id::new(("output port", id))).drag_started() {
+                graph.pending_connection = Some(PendingConnection { from: *id, detached: None });
+            }
+        }
+        if let Some(PendingConnection { from, .. }) = graph.pending_connection {
+            if let Some(pointer_pos) = pointer_pos {
+                Self::draw_connection(painter, &Self::connection_curve(responses[&from].rect.right_center(), pointer_pos), false);
+            }
+        }
+    }
+
+    /// Size of the circles that connections are dragged from and to.
+    const PORT_RADIUS: f32 = 5.;
+
+    fn add_port(ui: &Ui, painter: &Painter, center: Pos2, id: Id) -> Response {
+        let response = ui.interact(Rect::from_center_size(center, Vec2::splat(Self::PORT_RADIUS * 3.)), id, Sense::drag()).on_hover_cursor(CursorIcon::Crosshair);
+        let fill = if response.hovered() { Color32::WHITE } else { hex_color!("808080") };
+        painter.circle(center, Self::PORT_RADIUS, fill, Stroke::new(1., hex_color!("2e2b3f")));
+        response
+    }
+
+    /// Once the pointer is released, connect the connection being dragged to the input under the pointer, or drop it.
+    fn finish_pending_connection(ui: &Ui, pointer_pos: Option<Pos2>, graph: &mut Graph, responses: &HashMap<NodeId, Response>, edit: &mut Option<String>) {
+        let Some(PendingConnection { from, detached }) = graph.pending_connection else {
+            return;
+        };
+        if ui.input(|input| input.pointer.any_down()) {
+            return;
+        }
+        graph.pending_connection = None;
+        let target = graph
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.data.has_input())
+            .map(|(id, _)| *id)
+            .find(|id| pointer_pos.is_some_and(|pointer_pos| responses[id].rect.left_center().distance(pointer_pos) <= Self::PORT_RADIUS * 2.));
+        let connected = target.filter(|to| graph.connect(from, *to));
+        if connected.is_some_and(|to| detached != Some(Edge { from, to })) {
+            *edit = Some("Connect nodes".into());
+        } else if detached.is_some() && connected.is_none() {
+            *edit = Some("Disconnect nodes".into());
+        }
+    }
+
+    /// Return points along the curve of a connection going from `a` to `b`.
+    fn connection_curve(a: Pos2, b: Pos2) -> Vec<Pos2> {
+        const RESOLUTION: usize = 20;
+        let strength = 100_f32.min(a.distance(b) / 2.);
+        (0..=RESOLUTION)
+            .map(|t| {
+                #[allow(clippy::cast_precision_loss, reason = "rounding errors are negligible because this is a visual effect")]
+                let t = t as f32 / RESOLUTION as f32;
+
+                (1. - t).powi(3) * a
+                    + (3. * (1. - t).powi(2) * t * (a + vec2(strength, 0.))).to_vec2()
+                    + (3. * (1. - t) * t.powi(2) * (b - vec2(strength, 0.))).to_vec2()
+                    + (t.powi(3) * b).to_vec2()
+            })
+            .collect()
+    }
+
+    fn draw_connection(painter: &Painter, curve: &[Pos2], highlighted: bool) {
+        let color = if highlighted { Color32::WHITE } else { hex_color!("#80808080") };
+        for (a, b) in curve.iter().tuple_windows() {
+            painter.line_segment([*a, *b], Stroke::new(2., color));
+        }
+    }
+
+    fn add_node_context_menu(response: &Response, id: NodeId, graph: &mut Graph, edit: &mut Option<String>) {
+        response.context_menu(|ui| {
+            if let Some(node) = graph.nodes.get_mut(&id).filter(|_| id != NodeId::Output) {
+                if ui.checkbox(&mut node.bypassed, "Bypass").changed() {
+                    *edit = Some("Bypass nodes".into());
+                }
+                let mut soloed = graph.solo == Some(id);
+                if ui.checkbox(&mut soloed, "Solo").changed() {
+                    graph.solo = soloed.then_some(id);
+                    *edit = Some("Solo node".into());
+                }
+                ui.separator();
+            }
+            if graph.selection.len() > 1 && graph.selection.contains(&id) {
+                Self::add_alignment_menus(ui, graph, edit);
+            }
+            if ui.add_enabled(!graph.selection.is_empty(), Button::new("Group selected nodes")).clicked() {
+                if graph.group_selection().is_some() {
+                    *edit = Some("Group nodes".into());
+                }
+                ui.close_menu();
+            }
+            if ui.button("Disconnect").clicked() {
+                if graph.disconnect(id) {
+                    *edit = Some("Disconnect nodes".into());
+                }
+                ui.close_menu();
+            }
+            if ui.add_enabled(id != NodeId::Output, Button::new("Delete")).clicked() {
+                graph.remove_node(id);
+                *edit = Some("Delete nodes".into());
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Show the names of the groups entered to get to the graph being edited, which can be clicked to go back to them.
+    fn add_group_path(&mut self, ui: &mut Ui) {
+        if self.group_path.is_empty() {
+            return;
+        }
+        ui.separator();
+        let (root, root_name) = match self.mode.track() {
+            Some(track) => (self.inserts.get(&track), "Insert chain"),
+            None => (Some(&self.graph), "Main graph"),
+        };
+        let mut depth = ui.link(root_name).clicked().then_some(0);
+        for end in 1..=self.group_path.len() {
+            let Some(NodeData::Group { name, .. } | NodeData::Bus { name, .. }) = root.and_then(|root| root.node(&self.group_path[..end])).map(|node| &node.data) else {
+                break;
+            };
+            ui.label("›");
+            if ui.link(name).clicked() {
+                depth = Some(end);
+            }
+        }
+        if let Some(depth) = depth {
+            self.group_path.truncate(depth);
+        }
+    }
+
+    /// Show the menus for lining up the selected nodes.
+    fn add_alignment_menus(ui: &mut Ui, graph: &mut Graph, edit: &mut Option<String>) {
+        ui.menu_button("Align", |ui| {
+            for (name, axis, align) in [
+                ("Left", Axis::Horizontal, Align::Min),
+                ("Center", Axis::Horizontal, Align::Center),
+                ("Right", Axis::Horizontal, Align::Max),
+                ("Top", Axis::Vertical, Align::Min),
+                ("Middle", Axis::Vertical, Align::Center),
+                ("Bottom", Axis::Vertical, Align::Max),
+            ] {
+                if ui.button(name).clicked() {
+                    graph.align_selection(axis, align);
+                    *edit = Some("Align nodes".into());
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.menu_button("Distribute", |ui| {
+            for (name, axis) in [("Horizontally", Axis::Horizontal), ("Vertically", Axis::Vertical)] {
+                if ui.add_enabled(graph.selection.len() > 2, Button::new(name)).clicked() {
+                    graph.distribute_selection(axis);
+                    *edit = Some("Distribute nodes".into());
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.separator();
+    }
+
+    /// Attach the menu for adding nodes to the graph's background, placing them where it was opened.
+    ///
+    /// If the menu is opened over a connection, `hovered_edge`, it can be removed from there too. Buses can only be added to the `main` graph, as
+    /// tracks only send to the buses there.
+    fn add_graph_background_menu(response: &Response, hovered_edge: Option<Edge>, graph: &mut Graph, main: bool, edit: &mut Option<String>) {
+        let position_id = response.id.with("position");
+        let edge_id = response.id.with("edge");
+        if response.secondary_clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                response.ctx.data_mut(|data| {
+                    data.insert_temp(position_id, pos.to_vec2());
+                    data.insert_temp(edge_id, hovered_edge);
+                });
+            }
+        }
+        response.context_menu(|ui| {
+            if let Some(edge) = ui.data(|data| data.get_temp::<Option<Edge>>(edge_id)).flatten() {
+                if ui.button("Remove connection").clicked() {
+                    graph.edges.remove(&edge);
+                    *edit = Some("Disconnect nodes".into());
+                    ui.close_menu();
+                }
+            }
+            ui.menu_button("Add node", |ui| {
+                let position = ui.data(|data| data.get_temp(position_id)).unwrap_or_else(|| graph.view_center());
+                for category in Category::ALL {
+                    ui.menu_button(category.name(), |ui| {
+                        for effect in registry::in_category(category) {
+                            if ui.button(effect.name).clicked() {
+                                graph.add_node(NodeData::effect(effect), position);
+                                *edit = Some("Add node".into());
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
+                ui.separator();
+                for (name, data) in NodeData::built_in().into_iter().chain([("Group input", NodeData::GroupInput)]) {
+                    if ui.button(name).clicked() {
+                        graph.add_node(data, position);
+                        *edit = Some("Add node".into());
+                        ui.close_menu();
+                    }
+                }
+                if main && ui.button("Bus").on_hover_text("A bus that tracks send to, with an insert chain of its own").clicked() {
+                    let name = format!("Bus {}", graph.buses().len() + 1);
+                    let id = graph.add_node(NodeData::Bus { name, graph: Box::new(Graph::inserts()) }, position);
+                    graph.connect(id, NodeId::Output);
+                    *edit = Some("Add bus".into());
+                    ui.close_menu();
+                }
+            });
+        });
+    }
+
+    /// Add the tabs and the buttons for the graph shown, returning whether it should be fit to the view.
+    fn add_toolbar(&mut self, ui: &mut Ui) -> bool {
+        let mut fit = false;
+        ui.horizontal(|ui| {
+            let mode = self.mode;
+            // The playlist stays in the main window while the graph is detached.
+            if !self.graph_detached {
+                ui.selectable_value(&mut self.mode, Mode::Playlist, "Playlist").on_hover_action(Action::ShowPlaylist);
+            }
+            ui.selectable_value(&mut self.mode, Mode::Graph, "Graph").on_hover_action(Action::ShowGraph);
+            if let Mode::Inserts(track) = mode {
+                ui.selectable_value(&mut self.mode, mode, format!("Track {} inserts", track + 1)).on_hover_text("The effects the track goes through before the graph");
+            }
+            if let Some(editor) = &self.editor {
+                let name = format!("{}{}", editor.name(), if editor.unsaved { " •" } else { "" });
+                ui.selectable_value(&mut self.mode, Mode::SampleEditor, name).on_hover_text("The sample editor");
+            }
+            if self.mode != mode {
+                self.group_path.clear();
+            }
+            if matches!(self.mode, Mode::Graph | Mode::Inserts(_)) {
+                ui.separator();
+                fit = ui.button("Fit").on_hover_shortcut("Fit the nodes into the view", "F").clicked();
+                ui.toggle_value(&mut self.current_graph().show_minimap, "Minimap").on_hover_text("Show where the view is among all the nodes");
+                if ui.button("Arrange").on_hover_text("Lay the nodes out from left to right").clicked() {
+                    self.current_graph().arrange();
+                    self.edit = Some("Arrange nodes".into());
+                }
+                let (text, hover) = if self.graph_detached {
+                    ("Dock", "Show the graph in the main window again")
+                } else {
+                    ("Pop out", "Show the graph in a window of its own, which can be moved to another screen")
+                };
+                if ui.button(text).on_hover_text(hover).clicked() {
+                    self.graph_detached = !self.graph_detached;
+                }
+                self.add_group_path(ui);
+            }
+        });
+        fit
+    }
+
+    fn add_current_playlist(&mut self, ui: &mut Ui) -> Response {
+        let (mut opened, mut slice) = (None, None);
+        let buses = self.graph.buses();
+        let response = Self::add_playlist(
+            ui,
+            &mut self.playlist,
+            &mut self.inserts,
+            &mut self.meters,
+            self.monitoring_latency,
+            &mut opened,
+            &mut self.edit,
+            &mut self.exported,
+            &mut self.added,
+            &mut slice,
+            &mut self.file_lengths,
+            &buses,
+            &self.theme,
+        );
+        if self.edit.is_some() {
+            self.playlist_revision += 1;
+        }
+        if let Some((index, into, sensitivity)) = slice {
+            self.slice(index, into, sensitivity);
+        }
+        match opened {
+            Some(TrackView::Inserts(track)) => {
+                self.mode = Mode::Inserts(track);
+                self.group_path.clear();
+            }
+            Some(TrackView::DrumRack(track)) => {
+                self.drum_rack = Some(track);
+                self.selected_pad = 0;
+            }
+            Some(TrackView::SampleEditor(index)) => {
+                let clip = &self.playlist.clips[index];
+                if let ClipData::Audio { path, .. } = &clip.data {
+                    self.open_editor(path.clone(), &clip.data.clone(), Some(clip.id));
+                }
+            }
+            None => {}
+        }
+        response
+    }
+
+    fn add_current_editor(&mut self, ui: &mut Ui) -> Response {
+        let Some(editor) = &mut self.editor else {
+            self.mode = Mode::Playlist;
+            return self.add_current_playlist(ui);
+        };
+        let response = ui.scope(|ui| editor.show(ui));
+        match response.inner {
+            Some(Request::Save) => self.save_edited_sample(),
+            Some(Request::Close) if editor.unsaved => self.leaving_editor = Some(Leaving::Close),
+            Some(Request::Close) => self.leave_editor(Leaving::Close),
+            None => {}
+        }
+        if let Some(leaving) = self.leaving_editor.take() {
+            let name = self.editor.as_ref().map(|editor| editor.name().into_owned()).unwrap_or_default();
+            match dialog::unsaved_changes(ui.ctx(), &name) {
+                Some(Choice::Save) => {
+                    self.save_edited_sample();
+                    // The editor is only left once the sample is saved.
+                    if self.editor.as_ref().is_some_and(|editor| !editor.unsaved) {
+                        self.leave_editor(leaving);
+                    }
+                }
+                Some(Choice::Discard) => self.leave_editor(leaving),
+                Some(Choice::Cancel) => {}
+                None => self.leaving_editor = Some(leaving),
+            }
+        }
+        response.response
+    }
+
+    /// Close the sample editor or edit another sample, whatever was edited before.
+    fn leave_editor(&mut self, leaving: Leaving) {
+        match leaving {
+            Leaving::Close => {
+                self.editor = None;
+                self.mode = Mode::Playlist;
+            }
+            Leaving::Open(path, data, clip) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.unsaved = false;
+                }
+                self.open_editor(path, &data, clip);
+            }
+        }
+    }
+
+    /// Write the sample being edited to a new file, adding it to `added` and giving its audio to the clip it was opened from, if that clip is still of
+    /// the original file.
+    fn save_edited_sample(&mut self) {
+        let folder = self.samples_folder();
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+        let original = editor.path.clone();
+        let Some(path) = editor.save(&folder) else {
+            return;
+        };
+        let clip = editor.clip.and_then(|id| self.playlist.clips.iter_mut().find(|clip| clip.id == id));
+        if let Some(clip) = clip.filter(|clip| matches!(&clip.data, ClipData::Audio { path, .. } if *path == original)) {
+            clip.data = editor.data();
+            self.playlist_revision += 1;
+            self.edit = Some("Edit sample".into());
+        }
+        self.added.push(path);
+    }
+
+    fn add_current_graph(&mut self, ui: &mut Ui, fit: bool) -> Response {
+        // Leaves the group being edited if it's gone, and creates the insert chain if it's new.
+        self.current_graph();
+        let track = self.mode.track();
+        let root = match track {
+            Some(track) => self.inserts.get_mut(&track).unwrap(),
+            None => &mut self.graph,
+        };
+        let graph = root.group_mut(&self.group_path).unwrap();
+        Self::add_graph(ui, graph, track, &mut self.group_path, &self.taps, &self.playlist.mixes, &mut self.meters, &mut self.mappings, fit, &mut self.edit)
+    }
 }
 
 impl Widget for &mut Central {
     fn ui(self, ui: &mut Ui) -> Response {
+        self.poll_extracted();
+        self.poll_sliced();
         Frame::default()
-            .show(ui, |ui| match &mut self.mode {
-                Mode::Playlist => Central::add_playlist(ui, &mut self.playlist),
-                Mode::Graph => Central::add_graph(ui, &mut self.graph),
+            .show(ui, |ui| {
+                self.drum_rack_window(ui.ctx());
+                if self.graph_detached {
+                    return self.add_current_playlist(ui);
+                }
+                let fit = self.add_toolbar(ui);
+                match self.mode {
+                    Mode::Playlist => self.add_current_playlist(ui),
+                    Mode::Graph | Mode::Inserts(_) => self.add_current_graph(ui, fit),
+                    Mode::SampleEditor => self.add_current_editor(ui),
+                }
             })
             .response
     }
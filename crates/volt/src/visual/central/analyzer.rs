@@ -0,0 +1,274 @@
+//! Windows that show the audio of the master output or of a track after its insert chain while the project plays: a spectrum analyzer, and a scope
+//! along with a goniometer for checking the phase of stereo audio. Both listen to the same source, which is picked in either.
+
+use std::{f64::consts::SQRT_2, ops::RangeInclusive, sync::Arc, time::Instant};
+
+use blerp::processing::{graph::Tap, spectrum::amplitudes};
+use egui::{hex_color, pos2, vec2, Align2, Color32, ComboBox, Context, FontId, Rect, Sense, Shape, Slider, Stroke, Ui, Window};
+use itertools::Itertools;
+
+/// The sizes of FFT that can be picked, where bigger ones tell frequencies apart better but follow the audio more slowly.
+const SIZES: [usize; 4] = [1024, 2048, 4096, 8192];
+/// The lowest frequency shown, in Hz.
+const LOWEST: f64 = 20.;
+/// The quietest level shown, in dBFS.
+const FLOOR: f64 = -90.;
+/// How fast held peaks fall back, in dB per second.
+const PEAK_FALL: f64 = 15.;
+const BACKGROUND: Color32 = Color32::from_black_alpha(0x60);
+const LINE: Color32 = Color32::from_rgb(0x8c, 0x8c, 0xff);
+const PEAK: Color32 = Color32::from_rgb(0xff, 0xd2, 0x4d);
+/// The range of how much audio the scope shows, in milliseconds.
+const SCOPE_LENGTHS: RangeInclusive<f64> = 5.0..=100.0;
+/// How many of the latest frames the goniometer and the correlation meter look at.
+const GONIOMETER_FRAMES: usize = 2048;
+const GRID: Color32 = Color32::from_rgba_premultiplied(0x18, 0x18, 0x18, 0x18);
+
+/// What the analyzer listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Master,
+    /// A track after its insert chain, as it goes into the graph.
+    Track(u32),
+}
+
+pub struct Analyzer {
+    source: Source,
+    /// What the tap is given the audio of in the schedule, if it's in one.
+    pub tapped: Option<Source>,
+    pub tap: Option<Arc<Tap>>,
+    size: usize,
+    /// How much of the spectrum shown before is kept each time it's measured again, from 0 for none of it.
+    averaging: f64,
+    peak_hold: bool,
+    /// The amplitude of each frequency, averaged over time.
+    average: Vec<f64>,
+    /// The loudest level of each frequency lately, in dBFS.
+    peaks: Vec<f64>,
+    measured_at: Instant,
+    /// How much audio the scope shows, in milliseconds.
+    scope_length: f64,
+    /// How alike the left and right channels are lately, from -1 when they cancel out to 1 when they're the same.
+    correlation: f64,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            source: Source::Master,
+            tapped: None,
+            tap: None,
+            size: 4096,
+            averaging: 0.7,
+            peak_hold: true,
+            average: Vec::new(),
+            peaks: Vec::new(),
+            measured_at: Instant::now(),
+            scope_length: 20.,
+            correlation: 1.,
+        }
+    }
+}
+
+impl Analyzer {
+    /// Return the tap for a schedule of audio with `channels` channels at `sample_rate`, which is kept as long as the format doesn't change.
+    pub fn tap(&mut self, channels: usize, sample_rate: f64) -> Arc<Tap> {
+        let tap = self
+            .tap
+            .take()
+            .filter(|tap| tap.channels() == channels && (tap.sample_rate() - sample_rate).abs() < f64::EPSILON)
+            .unwrap_or_else(|| Arc::new(Tap::new(channels, sample_rate, SIZES[SIZES.len() - 1])));
+        self.tap = Some(Arc::clone(&tap));
+        tap
+    }
+
+    /// Show the spectrum analyzer and the scope in windows while `spectrum` and `scope` are true, with `tracks` to pick from. Returns whether the
+    /// project has to be scheduled again for the tap to listen to what's picked, or to stop listening once both windows are closed.
+    pub fn windows(&mut self, ctx: &Context, spectrum: &mut bool, scope: &mut bool, tracks: u32) -> bool {
+        if *spectrum {
+            Window::new("Spectrum analyzer").open(spectrum).default_size([520., 280.]).show(ctx, |ui| {
+                self.add_spectrum_settings(ui, tracks);
+                self.add_spectrum(ui);
+            });
+        }
+        if *scope {
+            Window::new("Scope").open(scope).default_size([520., 240.]).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    self.add_source(ui, tracks);
+                    ui.add(Slider::new(&mut self.scope_length, SCOPE_LENGTHS).text("ms").logarithmic(true));
+                });
+                self.add_scope(ui);
+            });
+        }
+        let wanted = (*spectrum || *scope).then_some(self.source);
+        let changed = wanted != self.tapped;
+        self.tapped = wanted;
+        changed
+    }
+
+    /// Return the latest interleaved samples of the tap with its channel count and sample rate, or silence if there is no tap yet.
+    fn latest(&self) -> (Vec<f64>, usize, f64) {
+        self.tap.as_ref().map_or_else(|| (vec![0.; SIZES[SIZES.len() - 1]], 1, 48_000.), |tap| (tap.samples(), tap.channels(), tap.sample_rate()))
+    }
+
+    fn add_source(&mut self, ui: &mut Ui, tracks: u32) {
+        let name = |source: Source| match source {
+            Source::Master => "Master".to_string(),
+            Source::Track(track) => format!("Track {}", track + 1),
+        };
+        ComboBox::from_id_salt(ui.id().with("source")).selected_text(name(self.source)).show_ui(ui, |ui| {
+            for source in std::iter::once(Source::Master).chain((0..tracks).map(Source::Track)) {
+                ui.selectable_value(&mut self.source, source, name(source));
+            }
+        });
+    }
+
+    fn add_spectrum_settings(&mut self, ui: &mut Ui, tracks: u32) {
+        ui.horizontal(|ui| {
+            self.add_source(ui, tracks);
+            ComboBox::from_id_salt("analyzer size").selected_text(format!("FFT {}", self.size)).show_ui(ui, |ui| {
+                for size in SIZES {
+                    ui.selectable_value(&mut self.size, size, size.to_string());
+                }
+            });
+            ui.add(Slider::new(&mut self.averaging, 0.0..=0.95).text("Averaging"));
+            if ui.checkbox(&mut self.peak_hold, "Peak hold").changed() {
+                self.peaks.clear();
+            }
+        });
+    }
+
+    /// Measure the spectrum of the latest audio of the tap, averaging it with the one before and holding its peaks, and show it.
+    fn add_spectrum(&mut self, ui: &mut Ui) {
+        // The spectrum follows the audio, so it has to be redrawn continuously.
+        ui.ctx().request_repaint();
+        let (rect, _) = ui.allocate_exact_size(ui.available_size().max(vec2(200., 100.)), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., BACKGROUND);
+        let (samples, channels, sample_rate) = self.latest();
+        #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+        let mono = samples[samples.len().saturating_sub(self.size * channels)..].chunks_exact(channels).map(|frame| frame.iter().sum::<f64>() / channels as f64);
+        let amplitudes = amplitudes(&mono.collect_vec());
+        if self.average.len() == amplitudes.len() {
+            for (average, amplitude) in self.average.iter_mut().zip(amplitudes) {
+                *average = self.averaging.mul_add(*average - amplitude, amplitude);
+            }
+        } else {
+            // The size changed, so there's nothing to average with.
+            self.average = amplitudes;
+            self.peaks.clear();
+        }
+        let elapsed = self.measured_at.elapsed().as_secs_f64();
+        self.measured_at = Instant::now();
+        let levels = self.average.iter().map(|amplitude| (20. * amplitude.log10()).max(FLOOR)).collect_vec();
+        if self.peak_hold {
+            self.peaks.resize(levels.len(), FLOOR);
+            for (peak, level) in self.peaks.iter_mut().zip(&levels) {
+                *peak = PEAK_FALL.mul_add(-elapsed, *peak).max(*level);
+            }
+        }
+
+        let nyquist = sample_rate / 2.;
+        #[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+        let position = |frequency: f64, level: f64| rect.lerp_inside(vec2((frequency / LOWEST).log(nyquist / LOWEST) as f32, (level / FLOOR) as f32));
+        for (frequency, label) in [(100., "100"), (1000., "1k"), (10000., "10k")] {
+            let x = position(frequency, 0.).x;
+            painter.vline(x, rect.y_range(), Stroke::new(1., GRID));
+            painter.text(pos2(x + 2., rect.bottom() - 2.), Align2::LEFT_BOTTOM, label, FontId::proportional(10.), Color32::GRAY);
+        }
+        for level in (1..).map(|step| f64::from(step) * -18.).take_while(|level| *level > FLOOR) {
+            let y = position(LOWEST, level).y;
+            painter.hline(rect.x_range(), y, Stroke::new(1., GRID));
+            painter.text(pos2(rect.left() + 2., y - 1.), Align2::LEFT_BOTTOM, format!("{level} dB"), FontId::proportional(10.), Color32::GRAY);
+        }
+        #[allow(clippy::cast_precision_loss, reason = "sizes are small")]
+        let line = |levels: &[f64], color: Color32| {
+            let points = levels
+                .iter()
+                .enumerate()
+                .map(|(index, level)| (index as f64 * sample_rate / self.size as f64, *level))
+                .filter(|(frequency, _)| *frequency >= LOWEST)
+                .map(|(frequency, level)| position(frequency, level))
+                .collect_vec();
+            Shape::line(points, Stroke::new(1., color))
+        };
+        painter.add(line(&levels, LINE));
+        if self.peak_hold {
+            painter.add(line(&self.peaks, PEAK));
+        }
+    }
+
+    /// Show the waveform of every channel of the latest audio, the goniometer and the correlation meter.
+    fn add_scope(&mut self, ui: &mut Ui) {
+        // The scope follows the audio, so it has to be redrawn continuously.
+        ui.ctx().request_repaint();
+        let (samples, channels, sample_rate) = self.latest();
+        let frames = samples.len() / channels;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lengths are short and positive")]
+        let length = ((self.scope_length / 1000. * sample_rate) as usize).clamp(2, frames / 2);
+        // Starting where the first channel rises through zero keeps a steady wave still.
+        let rises = (frames - length * 2..frames - length).rev().find(|frame| samples[frame * channels] <= 0. && samples[(frame + 1) * channels] > 0.);
+        let start = rises.unwrap_or(frames - length);
+        let size = ui.available_height().min(ui.available_width() / 2.).max(100.) - 20.;
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width() - size - ui.spacing().item_spacing.x, size), Sense::hover());
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 2., BACKGROUND);
+            painter.hline(rect.x_range(), rect.center().y, Stroke::new(1., GRID));
+            for (channel, color) in (0..channels.min(2)).zip([LINE, PEAK]) {
+                #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, reason = "only used for drawing")]
+                let points = (0..length)
+                    .map(|frame| rect.lerp_inside(vec2(frame as f32 / (length - 1) as f32, (1. - samples[(start + frame) * channels + channel].clamp(-1., 1.) as f32) / 2.)))
+                    .collect_vec();
+                painter.add(Shape::line(points, Stroke::new(1., color)));
+            }
+            Self::add_goniometer(ui, &samples, channels, size);
+        });
+        self.add_correlation(ui, &samples, channels);
+    }
+
+    /// Show how the left and right channels of the latest frames of interleaved `samples` move together, with their sum up and their difference
+    /// across, so mono audio is a vertical line and audio out of phase is a horizontal one.
+    fn add_goniometer(ui: &mut Ui, samples: &[f64], channels: usize, size: f32) {
+        let (rect, _) = ui.allocate_exact_size(vec2(size, size), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., BACKGROUND);
+        painter.vline(rect.center().x, rect.y_range(), Stroke::new(1., GRID));
+        painter.hline(rect.x_range(), rect.center().y, Stroke::new(1., GRID));
+        let recent = &samples[samples.len().saturating_sub(GONIOMETER_FRAMES * channels)..];
+        #[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+        let points = recent
+            .chunks_exact(channels)
+            .map(|frame| {
+                let (left, right) = (frame[0], frame[1 % channels]);
+                let (sum, difference) = ((left + right) / SQRT_2, (left - right) / SQRT_2);
+                rect.lerp_inside(vec2(f32::midpoint(difference.clamp(-1., 1.) as f32, 1.), (1. - sum.clamp(-1., 1.) as f32) / 2.))
+            })
+            .collect_vec();
+        painter.add(Shape::line(points, Stroke::new(1., LINE.gamma_multiply(0.6))));
+    }
+
+    /// Show how alike the left and right channels of the latest frames of interleaved `samples` are, from -1 when they cancel each other out to 1
+    /// when they're the same.
+    fn add_correlation(&mut self, ui: &mut Ui, samples: &[f64], channels: usize) {
+        let recent = &samples[samples.len().saturating_sub(GONIOMETER_FRAMES * channels)..];
+        let (product, left, right) = recent.chunks_exact(channels).fold((0., 0., 0.), |(product, left, right), frame| {
+            let (sample, other) = (frame[0], frame[1 % channels]);
+            (sample.mul_add(other, product), sample.mul_add(sample, left), other.mul_add(other, right))
+        });
+        // Silence counts as being in phase, so the meter rests in the middle of the good side.
+        let correlation = if left * right > f64::EPSILON { product / (left * right).sqrt() } else { 1. };
+        self.correlation = 0.8f64.mul_add(self.correlation - correlation, correlation);
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width() - 110., 10.), Sense::hover());
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 2., BACKGROUND);
+            painter.vline(rect.center().x, rect.y_range(), Stroke::new(1., GRID));
+            #[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+            let x = rect.lerp_inside(vec2(f32::midpoint(self.correlation as f32, 1.), 0.)).x;
+            let color = if self.correlation < 0. { hex_color!("ff5c5c") } else { hex_color!("5cff8c") };
+            painter.rect_filled(Rect::from_x_y_ranges(x.min(rect.center().x)..=x.max(rect.center().x), rect.y_range()), 2., color);
+            ui.weak(format!("Correlation {:+.2}", self.correlation));
+        });
+    }
+}
@@ -0,0 +1,440 @@
+//! A destructive editor for the audio of a clip or of a file from the browser, whose edits are written to a new file in the project when they're saved.
+
+use std::{
+    borrow::Cow,
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use blerp::processing::{
+    effects::{
+        fade::FadeEffect,
+        normalize::{NormalizeEffect, NormalizeTarget},
+        reverse::ReverseEffect,
+        Effect, Stuff,
+    },
+    overview::{overview, Peak},
+    resample,
+};
+use egui::{pos2, Button, Color32, CursorIcon, Rect, Sense, Shape, Stroke, Ui};
+
+use super::playlist::{write_wave, ClipData};
+use crate::project;
+
+const WAVEFORM: Color32 = Color32::from_rgb(0x8c, 0x8c, 0xff);
+const SELECTION: Color32 = Color32::from_rgba_premultiplied(0x40, 0x40, 0x80, 0x60);
+/// How many edits can be undone, past which the oldest are forgotten.
+const UNDO_LIMIT: usize = 100;
+
+/// Audio cut or copied in a [`SampleEditor`], which can be pasted into another sample.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    samples: Vec<f64>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+/// An edit of the samples as the samples it replaced, which is enough to undo it without keeping the whole sample.
+#[derive(Debug)]
+struct Change {
+    /// The first sample replaced.
+    start: usize,
+    /// The samples replaced.
+    samples: Vec<f64>,
+    /// How many samples replaced them.
+    length: usize,
+    /// The selection before the edit.
+    selection: Range<usize>,
+}
+
+/// What was asked of a [`SampleEditor`] that it can't do on its own.
+pub enum Request {
+    Save,
+    Close,
+}
+
+pub struct SampleEditor {
+    /// The file the audio was read from.
+    pub path: PathBuf,
+    /// The id of the clip the editor was opened from, if it was, whose audio is replaced by the edited file when it's saved.
+    pub clip: Option<u64>,
+    /// Interleaved samples of every channel.
+    samples: Vec<f64>,
+    channels: u16,
+    sample_rate: u32,
+    /// The frames selected. Edits apply to every frame while it's empty, and audio is pasted where it starts.
+    selection: Range<usize>,
+    /// The frame a drag to select started from.
+    anchor: usize,
+    pub clipboard: Option<Clipboard>,
+    /// The edits that can be undone, and the edits that were undone, the latest last.
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    /// Whether there are edits that weren't saved.
+    pub unsaved: bool,
+    /// The waveform as it was last drawn, which is only worked out again after an edit or when the width changes.
+    peaks: Vec<Peak>,
+}
+
+impl SampleEditor {
+    /// Return an editor of the audio of `data`, which is from the file at `path`, or [`None`] for MIDI data. `clip` is the id of the clip it's from, if any.
+    pub fn new(path: PathBuf, data: &ClipData, clip: Option<u64>) -> Option<Self> {
+        let (ClipData::Audio { channels, sample_rate, .. }, Some(samples)) = (data, data.samples()) else {
+            return None;
+        };
+        Some(Self {
+            path,
+            clip,
+            samples: samples.to_vec(),
+            channels: (*channels).max(1),
+            sample_rate: *sample_rate,
+            selection: 0..0,
+            anchor: 0,
+            clipboard: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            unsaved: false,
+            peaks: Vec::new(),
+        })
+    }
+
+    /// Return the name of the file being edited.
+    pub fn name(&self) -> Cow<'_, str> {
+        self.path.file_name().map_or_else(|| self.path.to_string_lossy(), |name| name.to_string_lossy())
+    }
+
+    fn frames(&self) -> usize {
+        self.samples.len() / usize::from(self.channels)
+    }
+
+    /// Return the range of samples of `frames`.
+    fn samples_of(&self, frames: &Range<usize>) -> Range<usize> {
+        let channels = usize::from(self.channels);
+        frames.start * channels..frames.end * channels
+    }
+
+    /// Return how long the selection lasts, or [`None`] if nothing is selected.
+    pub fn selected(&self) -> Option<Duration> {
+        #[allow(clippy::cast_precision_loss, reason = "frame counts are well within range")]
+        let frames = self.selection.len() as f64;
+        (frames > 0.).then(|| Duration::from_secs_f64(frames / f64::from(self.sample_rate)))
+    }
+
+    /// Return the frames edits apply to, which are the selected ones, or every frame if none are.
+    fn target(&self) -> Range<usize> {
+        if self.selection.is_empty() {
+            0..self.frames()
+        } else {
+            self.selection.clone()
+        }
+    }
+
+    /// Replace the `samples` in a range with `with` as an edit that can be undone.
+    fn replace(&mut self, samples: Range<usize>, with: Vec<f64>) {
+        let change = self.change(samples, with, self.selection.clone());
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(change);
+        self.redo.clear();
+    }
+
+    /// Replace the `samples` in a range with `with` and select `selection`, returning the change that puts them back.
+    fn change(&mut self, samples: Range<usize>, with: Vec<f64>, selection: Range<usize>) -> Change {
+        let (start, length) = (samples.start, with.len());
+        let replaced = self.samples.splice(samples, with).collect();
+        self.unsaved = true;
+        self.peaks.clear();
+        Change {
+            start,
+            samples: replaced,
+            length,
+            selection: mem::replace(&mut self.selection, selection),
+        }
+    }
+
+    /// Undo the last edit, returning `false` if there is none.
+    pub fn undo(&mut self) -> bool {
+        let Some(Change { start, samples, length, selection }) = self.undo.pop() else {
+            return false;
+        };
+        let change = self.change(start..start + length, samples, selection);
+        self.redo.push(change);
+        true
+    }
+
+    /// Redo the last edit that was undone, returning `false` if there is none.
+    pub fn redo(&mut self) -> bool {
+        let Some(Change { start, samples, length, selection }) = self.redo.pop() else {
+            return false;
+        };
+        let change = self.change(start..start + length, samples, selection);
+        self.undo.push(change);
+        true
+    }
+
+    /// Apply `effect` to the frames edits apply to, which keeps how many there are.
+    fn apply(&mut self, effect: &dyn Effect) {
+        let range = self.samples_of(&self.target());
+        let input = Stuff {
+            time: 0.,
+            sample_rate: f64::from(self.sample_rate),
+            channels: usize::from(self.channels),
+            samples: Cow::Borrowed(&self.samples[range.clone()]),
+        };
+        let Ok(output) = effect.apply(input);
+        self.replace(range, output.samples.into_owned());
+    }
+
+    fn copy(&mut self) {
+        self.clipboard = Some(Clipboard {
+            samples: self.samples[self.samples_of(&self.selection)].to_vec(),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+        });
+    }
+
+    fn cut(&mut self) {
+        self.copy();
+        self.replace(self.samples_of(&self.selection), Vec::new());
+        self.selection = self.selection.start..self.selection.start;
+    }
+
+    /// Replace the selection with the clipboard, converted to the format of the sample, and select what was pasted.
+    fn paste(&mut self) {
+        let Some(clipboard) = &self.clipboard else {
+            return;
+        };
+        let pasted = resample::convert(
+            &clipboard.samples,
+            usize::from(clipboard.channels),
+            f64::from(clipboard.sample_rate),
+            usize::from(self.channels),
+            f64::from(self.sample_rate),
+        );
+        let frames = pasted.len() / usize::from(self.channels);
+        self.replace(self.samples_of(&self.selection), pasted);
+        self.selection = self.selection.start..self.selection.start + frames;
+    }
+
+    fn silence(&mut self) {
+        let range = self.samples_of(&self.target());
+        let silence = vec![0.; range.len()];
+        self.replace(range, silence);
+    }
+
+    /// Return how long the frames edits apply to last, in seconds.
+    fn target_seconds(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+        let frames = self.target().len() as f64;
+        frames / f64::from(self.sample_rate)
+    }
+
+    /// Write the edited audio to a new file in `folder`, which is the project's samples folder, named after the file being edited, and edit that file from
+    /// now on. Returns the new file, or [`None`] if it couldn't be written.
+    pub fn save(&mut self, folder: &Path) -> Option<PathBuf> {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        // Saving again doesn't keep adding to the name.
+        let stem = stem.trim_end_matches(" (edited)");
+        let path = project::free_path(folder, Path::new(&format!("{stem} (edited).wav")));
+        write_wave(&self.samples, self.channels, self.sample_rate, &path)?;
+        self.path.clone_from(&path);
+        self.unsaved = false;
+        Some(path)
+    }
+
+    /// Return the edited audio as clip data of the file it was saved to.
+    pub fn data(&self) -> ClipData {
+        #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+        let seconds = self.frames() as f64 / f64::from(self.sample_rate);
+        ClipData::Audio {
+            path: self.path.clone(),
+            samples: Arc::from(self.samples.as_slice()),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            length: Duration::from_secs_f64(seconds),
+            segment: None,
+        }
+    }
+
+    /// Show the buttons and the waveform, returning what was asked of the editor that it can't do on its own.
+    pub fn show(&mut self, ui: &mut Ui) -> Option<Request> {
+        let mut request = None;
+        ui.horizontal(|ui| {
+            let selected = !self.selection.is_empty();
+            if ui.add_enabled(!self.undo.is_empty(), Button::new("Undo")).clicked() {
+                self.undo();
+            }
+            if ui.add_enabled(!self.redo.is_empty(), Button::new("Redo")).clicked() {
+                self.redo();
+            }
+            ui.separator();
+            if ui.add_enabled(selected, Button::new("Cut")).clicked() {
+                self.cut();
+            }
+            if ui.add_enabled(selected, Button::new("Copy")).clicked() {
+                self.copy();
+            }
+            if ui.add_enabled(self.clipboard.is_some(), Button::new("Paste")).on_hover_text("Replace the selection, or insert where it starts").clicked() {
+                self.paste();
+            }
+            ui.separator();
+            // These apply to the whole sample while nothing is selected.
+            if ui.button("Silence").clicked() {
+                self.silence();
+            }
+            if ui.button("Fade in").clicked() {
+                self.apply(&FadeEffect::new(self.target_seconds(), 0.));
+            }
+            if ui.button("Fade out").clicked() {
+                self.apply(&FadeEffect::new(0., self.target_seconds()));
+            }
+            if ui.button("Normalize").on_hover_text("Bring the loudest sample to 0 dBFS").clicked() {
+                self.apply(&NormalizeEffect::new(NormalizeTarget::Peak(0.)));
+            }
+            if ui.button("Reverse").clicked() {
+                self.apply(&ReverseEffect);
+            }
+            ui.separator();
+            if ui.add_enabled(self.unsaved, Button::new("Save")).on_hover_text("Write the audio to a new file in the project, leaving the original untouched").clicked() {
+                request = Some(Request::Save);
+            }
+            if ui.button("Close").clicked() {
+                request = Some(Request::Close);
+            }
+        });
+        self.add_waveform(ui);
+        request
+    }
+
+    /// Show the waveform, where dragging selects frames and clicking puts the start of an empty selection.
+    fn add_waveform(&mut self, ui: &mut Ui) {
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        let response = response.on_hover_cursor(CursorIcon::Text);
+        let frames = self.frames();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "only used for drawing")]
+        let frame_at = |x: f32| (((x - rect.left()) / rect.width()).clamp(0., 1.) * frames as f32).round() as usize;
+        #[allow(clippy::cast_precision_loss, reason = "only used for drawing")]
+        let x_of = |frame: usize| (frame as f32 / frames.max(1) as f32).mul_add(rect.width(), rect.left());
+        if let Some(pos) = response.interact_pointer_pos() {
+            let frame = frame_at(pos.x).min(frames);
+            if response.drag_started() || response.clicked() {
+                self.anchor = frame;
+            }
+            self.selection = self.anchor.min(frame)..self.anchor.max(frame);
+        }
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., Color32::from_black_alpha(0x60));
+        if self.selection.is_empty() {
+            painter.vline(x_of(self.selection.start), rect.y_range(), Stroke::new(1., Color32::WHITE));
+        } else {
+            painter.rect_filled(Rect::from_x_y_ranges(x_of(self.selection.start)..=x_of(self.selection.end), rect.y_range()), 0., SELECTION);
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "widths are small and positive")]
+        let width = rect.width() as usize;
+        if self.peaks.len() != width.min(frames) {
+            self.peaks = overview(&self.samples, usize::from(self.channels), width);
+        }
+        let height = rect.height() / 2.;
+        #[allow(clippy::cast_precision_loss, reason = "only used for drawing")]
+        let step = rect.width() / self.peaks.len().max(1) as f32;
+        let shapes = self.peaks.iter().enumerate().map(|(index, peak)| {
+            #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, reason = "only used for drawing")]
+            let (x, top, bottom) = ((index as f32 + 0.5).mul_add(step, rect.left()), peak.max.clamp(-1., 1.) as f32, peak.min.clamp(-1., 1.) as f32);
+            Shape::line_segment([pos2(x, top.mul_add(-height, rect.center().y)), pos2(x, bottom.mul_add(-height, rect.center().y).max(rect.center().y + 0.5))], Stroke::new(1., WAVEFORM))
+        });
+        painter.extend(shapes);
+        painter.hline(rect.x_range(), rect.center().y, Stroke::new(1., WAVEFORM.gamma_multiply(0.4)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{SampleEditor, UNDO_LIMIT};
+    use crate::visual::central::playlist::ClipData;
+
+    /// Return an editor of a mono sample whose samples count up from 0.
+    fn editor(frames: u32) -> SampleEditor {
+        let data = ClipData::Audio {
+            path: PathBuf::from("sample.wav"),
+            samples: (0..frames).map(f64::from).collect(),
+            channels: 1,
+            sample_rate: 48000,
+            length: Duration::from_secs_f64(f64::from(frames) / 48000.),
+            segment: None,
+        };
+        SampleEditor::new(PathBuf::from("sample.wav"), &data, None).unwrap()
+    }
+
+    fn counting(frames: impl IntoIterator<Item = u32>) -> Vec<f64> {
+        frames.into_iter().map(f64::from).collect()
+    }
+
+    #[test]
+    fn cutting_removes_the_selection_and_pasting_puts_it_back() {
+        let mut editor = editor(8);
+        editor.selection = 2..5;
+        editor.cut();
+        assert_eq!(editor.samples, counting([0, 1, 5, 6, 7]));
+        assert_eq!(editor.selection, 2..2);
+        assert!(editor.unsaved);
+        editor.selection = 4..4;
+        editor.paste();
+        assert_eq!(editor.samples, counting([0, 1, 5, 6, 2, 3, 4, 7]));
+        assert_eq!(editor.selection, 4..7);
+    }
+
+    #[test]
+    fn pasting_replaces_the_selection() {
+        let mut editor = editor(6);
+        editor.selection = 0..2;
+        editor.copy();
+        editor.selection = 3..6;
+        editor.paste();
+        assert_eq!(editor.samples, counting([0, 1, 2, 0, 1]));
+        assert_eq!(editor.selection, 3..5);
+    }
+
+    #[test]
+    fn edits_are_undone_and_redone_in_order() {
+        let mut editor = editor(6);
+        editor.selection = 1..3;
+        editor.cut();
+        editor.selection = 0..0;
+        editor.paste();
+        editor.selection = 2..4;
+        editor.silence();
+        assert_eq!(editor.samples, counting([1, 2, 0, 0, 4, 5]));
+        assert!(editor.undo());
+        assert_eq!((editor.samples.clone(), editor.selection.clone()), (counting([1, 2, 0, 3, 4, 5]), 2..4));
+        assert!(editor.undo());
+        assert_eq!((editor.samples.clone(), editor.selection.clone()), (counting([0, 3, 4, 5]), 0..0));
+        assert!(editor.undo());
+        assert_eq!((editor.samples.clone(), editor.selection.clone()), (counting(0..6), 1..3));
+        assert!(!editor.undo());
+        assert!(editor.redo());
+        assert!(editor.redo());
+        assert_eq!(editor.samples, counting([1, 2, 0, 3, 4, 5]));
+        editor.selection = 0..1;
+        editor.cut();
+        assert!(!editor.redo());
+        assert_eq!(editor.samples, counting([2, 0, 3, 4, 5]));
+    }
+
+    #[test]
+    fn only_the_latest_edits_are_kept() {
+        let mut editor = editor(4);
+        editor.selection = 0..1;
+        for _ in 0..=UNDO_LIMIT {
+            editor.silence();
+        }
+        assert_eq!(editor.undo.len(), UNDO_LIMIT);
+        assert!(editor.undo.iter().all(|change| change.samples.len() == 1));
+        assert_eq!(editor.samples, counting(0..4));
+    }
+}
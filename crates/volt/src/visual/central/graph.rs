@@ -0,0 +1,1033 @@
+use blerp::processing::graph::{self as schedule, CycleError, Schedule};
+use blerp::processing::registry::{self, RegisteredEffect};
+use egui::{emath::TSTransform, vec2, Align, Pos2, Rect, Vec2};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use itertools::Itertools;
+
+use super::playlist::{amplitude, TrackMix};
+
+const fn default_zoom() -> f32 {
+    1.
+}
+
+/// Identifies a node of a [`Graph`]. Saved as `"output"` or the number of a middle node, so that it can be used as a key in project files.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum NodeId {
+    Output,
+    Middle(NonZeroU64),
+}
+
+impl From<NodeId> for String {
+    fn from(id: NodeId) -> Self {
+        match id {
+            NodeId::Output => "output".into(),
+            NodeId::Middle(id) => id.to_string(),
+        }
+    }
+}
+
+impl TryFrom<String> for NodeId {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        if id == "output" {
+            return Ok(Self::Output);
+        }
+        id.parse().map(Self::Middle).map_err(|_| format!("\"{id}\" is not a node id"))
+    }
+}
+
+/// A network of nodes processing audio. Everything connected to an input is summed, and an output can feed any number of inputs.
+///
+/// Only the nodes and their connections are saved, the rest is the state of the view.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: HashMap<NodeId, Node>,
+    pub edges: HashSet<Edge>,
+    /// How far the view is moved from the center of the graph, in screen points.
+    #[serde(skip)]
+    pub pan_offset: Vec2,
+    /// How many screen points a point of the graph takes up.
+    #[serde(skip, default = "default_zoom")]
+    pub zoom: f32,
+    #[serde(skip)]
+    pub show_minimap: bool,
+    #[serde(skip)]
+    pub drag_start_offset: Option<Vec2>,
+    /// The node whose output is being dragged to an input, to connect them.
+    #[serde(skip)]
+    pub pending_connection: Option<PendingConnection>,
+    #[serde(skip)]
+    pub selection: HashSet<NodeId>,
+    /// The node being listened to on its own, muting every branch that doesn't go through it.
+    #[serde(skip)]
+    pub solo: Option<NodeId>,
+    /// Where the rectangle being dragged to select nodes starts and ends, in graph coordinates.
+    #[serde(skip)]
+    pub marquee: Option<(Pos2, Pos2)>,
+}
+
+/// Nodes copied from a graph along with the connections between them, which can be pasted into any graph.
+#[derive(Serialize, Deserialize)]
+pub struct Subgraph {
+    pub nodes: HashMap<NodeId, Node>,
+    pub edges: HashSet<Edge>,
+}
+
+impl Subgraph {
+    /// Return the middle of the nodes' positions.
+    pub fn center(&self) -> Vec2 {
+        Rect::from_points(&self.nodes.values().map(|node| node.position.to_pos2()).collect::<Vec<_>>()).center().to_vec2()
+    }
+}
+
+/// A connection being dragged from an output to an input.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingConnection {
+    pub from: NodeId,
+    /// The connection that was picked up from an input to start the drag, if any.
+    pub detached: Option<Edge>,
+}
+
+/// A connection carrying the output of one node to the input of another.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: NodeId,
+    pub to: NodeId,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(with = "vec2")]
+    pub position: Vec2,
+    pub data: NodeData,
+    /// Whether the node passes its input through unchanged instead of processing it.
+    #[serde(default)]
+    pub bypassed: bool,
+    #[serde(skip)]
+    pub drag_start_offset: Option<Vec2>,
+    /// How big the node was when it was last shown, for arranging it.
+    #[serde(skip)]
+    pub size: Vec2,
+}
+
+/// A direction to line nodes up in.
+#[derive(Debug, Clone, Copy)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    const fn index(self) -> usize {
+        match self {
+            Self::Horizontal => 0,
+            Self::Vertical => 1,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NodeData {
+    Output,
+    /// Sums its inputs without processing them, to gather several branches into one.
+    Mixer,
+    /// Plays an audio file from its start whenever playback starts.
+    FilePlayer {
+        path: Option<PathBuf>,
+        looping: bool,
+    },
+    /// The clips of a playlist track, after going through the track's insert chain if it has one.
+    TrackInput {
+        track: u32,
+    },
+    /// Audio recorded from the default capture device.
+    LiveInput,
+    /// Shows the level of the audio going through it.
+    Meter,
+    /// Shows the waveform of the audio going through it.
+    Scope,
+    /// Shows the frequencies in the audio going through it.
+    Spectrum,
+    /// Nodes collapsed into one. What goes into the group comes out of its group inputs, and what reaches its output comes out of the group.
+    Group {
+        name: String,
+        graph: Box<Graph>,
+    },
+    /// The audio going into the group that the graph belongs to, or the audio of the track in an insert chain.
+    GroupInput,
+    /// A bus that tracks send part of their audio to, which goes through the bus's own insert chain along with its inputs, like a group. Only buses
+    /// at the top of the main graph are fed by sends, see [`TrackMix`].
+    Bus {
+        name: String,
+        graph: Box<Graph>,
+    },
+    Middle {
+        #[serde(with = "effect_id")]
+        effect: &'static RegisteredEffect,
+        /// The value of each of the effect's parameters, in order.
+        parameters: Vec<f64>,
+    },
+}
+
+/// Saves a position as `[x, y]`.
+mod vec2 {
+    use super::{Deserialize, Deserializer, Serialize, Serializer, Vec2};
+
+    #[allow(clippy::trivially_copy_pass_by_ref, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(vector: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+        [vector.x, vector.y].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+        <[f32; 2]>::deserialize(deserializer).map(Vec2::from)
+    }
+}
+
+/// Saves a registered effect as its ID. Projects saved before effects had IDs saved their names, which are still read.
+mod effect_id {
+    use super::{registry, Deserialize, Deserializer, Error, RegisteredEffect, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(effect: &&'static RegisteredEffect, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(effect.id)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'static RegisteredEffect, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        registry::find_by_id(&id).or_else(|| registry::find(&id)).ok_or_else(|| D::Error::custom(format!("there is no effect \"{id}\"")))
+    }
+}
+
+impl NodeData {
+    /// Return the data of a node for `effect` with its default parameters.
+    pub fn effect(effect: &'static RegisteredEffect) -> Self {
+        Self::Middle {
+            effect,
+            parameters: effect.defaults(),
+        }
+    }
+
+    /// Return the nodes other than effects and group inputs that can be added to any graph, along with their names.
+    pub const fn built_in() -> [(&'static str, Self); 7] {
+        [
+            ("Mixer", Self::Mixer),
+            ("File player", Self::FilePlayer { path: None, looping: false }),
+            ("Track input", Self::TrackInput { track: 0 }),
+            ("Live input", Self::LiveInput),
+            ("Meter", Self::Meter),
+            ("Scope", Self::Scope),
+            ("Spectrum", Self::Spectrum),
+        ]
+    }
+
+    /// Return whether the node shows the audio going through it, which needs a [`schedule::Tap`].
+    pub const fn is_visualization(&self) -> bool {
+        matches!(self, Self::Meter | Self::Scope | Self::Spectrum)
+    }
+
+    pub const fn has_input(&self) -> bool {
+        matches!(self, Self::Output | Self::Mixer | Self::Meter | Self::Scope | Self::Spectrum | Self::Group { .. } | Self::Bus { .. } | Self::Middle { .. })
+    }
+
+    pub const fn has_output(&self) -> bool {
+        !matches!(self, Self::Output)
+    }
+
+    /// Add the tracks played by the node to `tracks`, which for groups and buses are the tracks played inside them.
+    fn played_tracks(&self, tracks: &mut Vec<u32>) {
+        match self {
+            Self::TrackInput { track } => tracks.push(*track),
+            Self::Group { graph, .. } | Self::Bus { graph, .. } => graph.nodes.values().for_each(|node| node.data.played_tracks(tracks)),
+            _ => {}
+        }
+    }
+}
+
+impl Graph {
+    const MIN_ZOOM: f32 = 0.2;
+    const MAX_ZOOM: f32 = 4.;
+
+    /// Return a graph of `nodes` and `edges`, viewed from its center.
+    pub fn new(nodes: HashMap<NodeId, Node>, edges: HashSet<Edge>) -> Self {
+        Self {
+            nodes,
+            edges,
+            pan_offset: Vec2::ZERO,
+            zoom: default_zoom(),
+            show_minimap: false,
+            drag_start_offset: None,
+            pending_connection: None,
+            selection: HashSet::new(),
+            solo: None,
+            marquee: None,
+        }
+    }
+
+    /// Return an insert chain that passes the audio of its track through unchanged.
+    pub fn inserts() -> Self {
+        let mut graph = Self::new(HashMap::new(), HashSet::new());
+        let input = graph.add_node(NodeData::GroupInput, vec2(-150., 0.));
+        graph.nodes.insert(
+            NodeId::Output,
+            Node {
+                position: vec2(150., 0.),
+                data: NodeData::Output,
+                bypassed: false,
+                drag_start_offset: None,
+                size: Vec2::ZERO,
+            },
+        );
+        graph.edges.insert(Edge { from: input, to: NodeId::Output });
+        graph
+    }
+
+    /// Return the graph of the group that `path` leads to through groups nested in this graph, or this graph if `path` is empty. The insert chains
+    /// of buses are entered like groups.
+    pub fn group(&self, path: &[NodeId]) -> Option<&Self> {
+        let Some((id, path)) = path.split_first() else {
+            return Some(self);
+        };
+        match &self.nodes.get(id)?.data {
+            NodeData::Group { graph, .. } | NodeData::Bus { graph, .. } => graph.group(path),
+            _ => None,
+        }
+    }
+
+    /// Like [`Graph::group`], but mutable.
+    pub fn group_mut(&mut self, path: &[NodeId]) -> Option<&mut Self> {
+        let Some((id, path)) = path.split_first() else {
+            return Some(self);
+        };
+        match &mut self.nodes.get_mut(id)?.data {
+            NodeData::Group { graph, .. } | NodeData::Bus { graph, .. } => graph.group_mut(path),
+            _ => None,
+        }
+    }
+
+    /// Return the node that `path` leads to through groups nested in this graph.
+    pub fn node(&self, path: &[NodeId]) -> Option<&Node> {
+        let (id, groups) = path.split_last()?;
+        self.group(groups)?.nodes.get(id)
+    }
+
+    /// Like [`Graph::node`], but mutable.
+    pub fn node_mut(&mut self, path: &[NodeId]) -> Option<&mut Node> {
+        let (id, groups) = path.split_last()?;
+        self.group_mut(groups)?.nodes.get_mut(id)
+    }
+
+    /// Return whether any node of the graph or the groups in it matches `predicate`.
+    pub fn contains(&self, predicate: &impl Fn(&NodeData) -> bool) -> bool {
+        self.nodes.values().any(|node| match &node.data {
+            NodeData::Group { graph, .. } | NodeData::Bus { graph, .. } => graph.contains(predicate),
+            data => predicate(data),
+        })
+    }
+
+    /// Return the files played by the nodes of the graph and the groups in it.
+    pub fn files(&self) -> Vec<&PathBuf> {
+        self.nodes
+            .values()
+            .flat_map(|node| match &node.data {
+                NodeData::FilePlayer { path: Some(path), .. } => vec![path],
+                NodeData::Group { graph, .. } | NodeData::Bus { graph, .. } => graph.files(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Like [`Graph::files`], but mutable.
+    pub fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.nodes
+            .values_mut()
+            .flat_map(|node| match &mut node.data {
+                NodeData::FilePlayer { path: Some(path), .. } => vec![path],
+                NodeData::Group { graph, .. } | NodeData::Bus { graph, .. } => graph.files_mut(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Return the buses of the graph that tracks can send to, with their names and the tracks played inside them.
+    pub fn buses(&self) -> Vec<SendTarget<'_>> {
+        self.nodes
+            .iter()
+            .filter_map(|(id, node)| match &node.data {
+                NodeData::Bus { name, .. } => {
+                    let mut tracks = Vec::new();
+                    node.data.played_tracks(&mut tracks);
+                    Some(SendTarget { bus: *id, name, tracks })
+                }
+                _ => None,
+            })
+            .sorted_by(|a, b| a.name.cmp(b.name))
+            .collect()
+    }
+
+    /// Return the transform from graph coordinates to the screen, when the graph is shown in `rect`.
+    pub fn transform(&self, rect: Rect) -> TSTransform {
+        TSTransform::new(rect.center().to_vec2() + self.pan_offset, self.zoom)
+    }
+
+    /// Return the point of the graph shown in the middle of the view.
+    pub fn view_center(&self) -> Vec2 {
+        -self.pan_offset / self.zoom
+    }
+
+    /// Move the view so that `point` of the graph is in its middle.
+    pub fn center_on(&mut self, point: Pos2) {
+        self.pan_offset = -point.to_vec2() * self.zoom;
+    }
+
+    /// Multiply the zoom by `factor`, keeping the point of the graph at `anchor` on the screen where it is.
+    pub fn zoom_around(&mut self, rect: Rect, anchor: Pos2, factor: f32) {
+        let point = self.transform(rect).inverse() * anchor;
+        self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        self.pan_offset = anchor - rect.center() - point.to_vec2() * self.zoom;
+    }
+
+    /// Zoom and move the view so that `bounds`, in graph coordinates, fills most of `rect`.
+    pub fn fit(&mut self, rect: Rect, bounds: Rect) {
+        if bounds.is_positive() {
+            self.zoom = ((rect.size() / bounds.size()).min_elem() * 0.9).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+            self.center_on(bounds.center());
+        }
+    }
+
+    /// Return an id that no node in the graph has.
+    pub fn next_id(&self) -> NodeId {
+        let highest = self
+            .nodes
+            .keys()
+            .filter_map(|id| match id {
+                NodeId::Output => None,
+                NodeId::Middle(id) => Some(id.get()),
+            })
+            .max()
+            .unwrap_or_default();
+        NodeId::Middle(NonZeroU64::MIN.saturating_add(highest))
+    }
+
+    /// Add an unconnected node at `position`, relative to the center of the graph, returning its id.
+    pub fn add_node(&mut self, data: NodeData, position: Vec2) -> NodeId {
+        let id = self.next_id();
+        self.nodes.insert(
+            id,
+            Node {
+                position,
+                data,
+                bypassed: false,
+                drag_start_offset: None,
+                size: Vec2::ZERO,
+            },
+        );
+        id
+    }
+
+    /// Add a node right before the output, so that what went into the output goes through the node first, and move the output to the right to make room for it.
+    ///
+    /// Nodes without an input are only connected to the output.
+    pub fn insert_before_output(&mut self, data: NodeData) -> NodeId {
+        const SPACING: f32 = 150.;
+        let has_input = data.has_input();
+        let position = self.nodes.get(&NodeId::Output).map_or(Vec2::ZERO, |output| output.position);
+        let id = self.add_node(data, position);
+        if let Some(output) = self.nodes.get_mut(&NodeId::Output) {
+            output.position.x += SPACING;
+        }
+        if has_input {
+            let into_output = self.edges.iter().filter(|edge| edge.to == NodeId::Output).copied().collect::<Vec<_>>();
+            for edge in into_output {
+                self.edges.remove(&edge);
+                self.edges.insert(Edge { from: edge.from, to: id });
+            }
+        }
+        self.connect(id, NodeId::Output);
+        id
+    }
+
+    /// Remove a node along with every connection to and from it. The output node can't be removed.
+    pub fn remove_node(&mut self, id: NodeId) {
+        if id == NodeId::Output {
+            return;
+        }
+        self.nodes.remove(&id);
+        self.selection.remove(&id);
+        if self.solo == Some(id) {
+            self.solo = None;
+        }
+        self.disconnect(id);
+    }
+
+    /// Remove the selected nodes, except for the output.
+    pub fn remove_selection(&mut self) {
+        for id in self.selection.clone() {
+            self.remove_node(id);
+        }
+    }
+
+    /// Collapse the selected nodes into a group in their middle, returning its id.
+    ///
+    /// Connections coming into the selection go through the group's input, and connections leaving it through the group's output.
+    /// Returns [`None`] without changing anything if nothing but the output is selected, or if the group would be part of a cycle.
+    pub fn group_selection(&mut self) -> Option<NodeId> {
+        const MARGIN: f32 = 150.;
+        let Subgraph { nodes, edges } = self.copy_selection()?;
+        let bounds = Rect::from_points(&nodes.values().map(|node| node.position.to_pos2()).collect::<Vec<_>>());
+        let mut inner = Self::new(nodes, edges);
+        let input = inner.add_node(NodeData::GroupInput, vec2(bounds.left() - MARGIN, bounds.center().y));
+        inner.nodes.insert(
+            NodeId::Output,
+            Node {
+                position: vec2(bounds.right() + MARGIN, bounds.center().y),
+                data: NodeData::Output,
+                bypassed: false,
+                drag_start_offset: None,
+                size: Vec2::ZERO,
+            },
+        );
+        inner.center_on(bounds.center());
+
+        let mut grouped = self.clone();
+        grouped.remove_selection();
+        let group = grouped.next_id();
+        for edge in &self.edges {
+            match (inner.nodes.contains_key(&edge.from), inner.nodes.contains_key(&edge.to)) {
+                (false, true) => {
+                    inner.edges.insert(Edge { from: input, to: edge.to });
+                    grouped.edges.insert(Edge { from: edge.from, to: group });
+                }
+                (true, false) => {
+                    inner.edges.insert(Edge { from: edge.from, to: NodeId::Output });
+                    grouped.edges.insert(Edge { from: group, to: edge.to });
+                }
+                _ => {}
+            }
+        }
+        // A node fed by the selection that also feeds into it would now be both before and after the group.
+        if grouped.edges.iter().any(|edge| edge.from == group && grouped.reaches(edge.to, group)) {
+            return None;
+        }
+        grouped.add_node(
+            NodeData::Group {
+                name: "Group".into(),
+                graph: Box::new(inner),
+            },
+            bounds.center().to_vec2(),
+        );
+        grouped.selection = HashSet::from([group]);
+        *self = grouped;
+        Some(group)
+    }
+
+    /// Remove every connection to and from a node, returning whether there were any.
+    pub fn disconnect(&mut self, id: NodeId) -> bool {
+        let count = self.edges.len();
+        self.edges.retain(|edge| edge.from != id && edge.to != id);
+        self.edges.len() != count
+    }
+
+    /// Return the selected nodes and the connections between them, or [`None`] if nothing but the output is selected.
+    pub fn copy_selection(&self) -> Option<Subgraph> {
+        let nodes = self
+            .selection
+            .iter()
+            .filter(|id| **id != NodeId::Output)
+            .map(|id| (*id, self.nodes[id].clone()))
+            .collect::<HashMap<_, _>>();
+        if nodes.is_empty() {
+            return None;
+        }
+        let edges = self.edges.iter().filter(|edge| nodes.contains_key(&edge.from) && nodes.contains_key(&edge.to)).copied().collect();
+        Some(Subgraph { nodes, edges })
+    }
+
+    /// Add the nodes of `subgraph` with new ids, keeping their positions relative to each other with their middle at `position`, and select them.
+    pub fn paste(&mut self, subgraph: Subgraph, position: Vec2) {
+        let offset = position - subgraph.center();
+        let mut ids = HashMap::new();
+        self.selection.clear();
+        for (old_id, node) in subgraph.nodes {
+            if old_id == NodeId::Output {
+                continue;
+            }
+            let id = self.next_id();
+            self.nodes.insert(
+                id,
+                Node {
+                    position: node.position + offset,
+                    drag_start_offset: None,
+                    ..node
+                },
+            );
+            self.selection.insert(id);
+            ids.insert(old_id, id);
+        }
+        // Pasted text could come from anywhere, so the connections are checked like any other.
+        for edge in subgraph.edges {
+            if let (Some(from), Some(to)) = (ids.get(&edge.from), ids.get(&edge.to)) {
+                self.connect(*from, *to);
+            }
+        }
+    }
+
+    /// Move the selected nodes along `axis` so that their sides, or their middles, line up with the furthest of them in that direction.
+    pub fn align_selection(&mut self, axis: Axis, align: Align) {
+        let axis = axis.index();
+        let factor = align.to_factor();
+        let edges = self.selection.iter().map(|id| self.nodes[id].size[axis].mul_add(factor, self.nodes[id].position[axis]));
+        let target = match align {
+            Align::Min => edges.fold(f32::INFINITY, f32::min),
+            Align::Center => {
+                #[allow(clippy::cast_precision_loss, reason = "selections are small")]
+                let count = self.selection.len() as f32;
+                edges.sum::<f32>() / count
+            }
+            Align::Max => edges.fold(f32::NEG_INFINITY, f32::max),
+        };
+        for id in &self.selection {
+            let node = self.nodes.get_mut(id).unwrap();
+            node.position[axis] = node.size[axis].mul_add(-factor, target);
+        }
+    }
+
+    /// Move the selected nodes along `axis` so that the gaps between them are the same, keeping the first and last of them in place.
+    pub fn distribute_selection(&mut self, axis: Axis) {
+        let axis = axis.index();
+        let mut ids = self.selection.iter().copied().collect::<Vec<_>>();
+        if ids.len() < 3 {
+            return;
+        }
+        ids.sort_by(|a, b| self.nodes[a].position[axis].total_cmp(&self.nodes[b].position[axis]));
+        let first = &self.nodes[&ids[0]];
+        let last = &self.nodes[&ids[ids.len() - 1]];
+        let span = last.position[axis] + last.size[axis] - first.position[axis];
+        let sizes = ids.iter().map(|id| self.nodes[id].size[axis]).sum::<f32>();
+        #[allow(clippy::cast_precision_loss, reason = "selections are small")]
+        let gap = (span - sizes) / (ids.len() - 1) as f32;
+        let mut next = first.position[axis];
+        for id in ids {
+            let node = self.nodes.get_mut(&id).unwrap();
+            node.position[axis] = next;
+            next += node.size[axis] + gap;
+        }
+    }
+
+    /// Arrange every node in columns from left to right, so that connections go from one column to a later one with as few of them crossing as possible.
+    ///
+    /// Each node goes in the column after the last of its inputs, and the nodes of each column are ordered by where their inputs are.
+    pub fn arrange(&mut self) {
+        const GAP: Vec2 = vec2(80., 30.);
+        const SWEEPS: usize = 4;
+        if self.nodes.is_empty() {
+            return;
+        }
+        let old_bounds = Rect::from_points(&self.nodes.values().map(|node| node.position.to_pos2()).collect::<Vec<_>>());
+
+        // The graph has no cycles, so going through the nodes in the order they're processed gives each node's inputs a column first.
+        let mut columns = HashMap::<NodeId, usize>::new();
+        let mut remaining = self.nodes.keys().copied().collect::<HashSet<_>>();
+        while !remaining.is_empty() {
+            let ready = remaining
+                .iter()
+                .copied()
+                .filter(|id| !self.edges.iter().any(|edge| edge.to == *id && remaining.contains(&edge.from)))
+                .collect::<Vec<_>>();
+            // Only a graph that somehow has a cycle runs out of nodes whose inputs all have a column.
+            if ready.is_empty() {
+                return;
+            }
+            for id in ready {
+                let column = self.edges.iter().filter(|edge| edge.to == id).map(|edge| columns[&edge.from] + 1).max().unwrap_or_default();
+                columns.insert(id, column);
+                remaining.remove(&id);
+            }
+        }
+        let mut layers = vec![Vec::new(); columns.values().max().unwrap() + 1];
+        for (id, column) in &columns {
+            layers[*column].push(*id);
+        }
+        for layer in &mut layers {
+            layer.sort_by(|a, b| self.nodes[a].position.y.total_cmp(&self.nodes[b].position.y));
+        }
+
+        // Order each column by the average place of the nodes feeding it in theirs, a few times over so that the order settles.
+        for _ in 0..SWEEPS {
+            let mut places = HashMap::new();
+            for layer in &mut layers {
+                #[allow(clippy::cast_precision_loss, reason = "graphs are small")]
+                let barycenter = |id: &NodeId| {
+                    let inputs = self.edges.iter().filter(|edge| edge.to == *id).filter_map(|edge| places.get(&edge.from)).collect::<Vec<_>>();
+                    (!inputs.is_empty()).then(|| inputs.iter().copied().sum::<f32>() / inputs.len() as f32)
+                };
+                // Nodes without inputs keep their place.
+                #[allow(clippy::cast_precision_loss, reason = "graphs are small")]
+                let keys = layer
+                    .iter()
+                    .enumerate()
+                    .map(|(index, id)| (*id, barycenter(id).unwrap_or(index as f32 / layer.len() as f32)))
+                    .collect::<HashMap<_, _>>();
+                layer.sort_by(|a, b| keys[a].total_cmp(&keys[b]));
+                for (index, id) in layer.iter().enumerate() {
+                    #[allow(clippy::cast_precision_loss, reason = "graphs are small")]
+                    places.insert(*id, index as f32 / layer.len() as f32);
+                }
+            }
+        }
+
+        let mut x = 0.;
+        for layer in &layers {
+            let height = layer.iter().map(|id| self.nodes[id].size.y + GAP.y).sum::<f32>() - GAP.y;
+            let width = layer.iter().map(|id| self.nodes[id].size.x).fold(0., f32::max);
+            let mut y = -height / 2.;
+            for id in layer {
+                let node = self.nodes.get_mut(id).unwrap();
+                node.position = vec2(x, y);
+                y += node.size.y + GAP.y;
+            }
+            x += width + GAP.x;
+        }
+        let new_bounds = Rect::from_points(&self.nodes.values().map(|node| node.position.to_pos2()).collect::<Vec<_>>());
+        for node in self.nodes.values_mut() {
+            node.position += old_bounds.center() - new_bounds.center();
+        }
+    }
+
+    /// Return whether `node` feeds into `target`, directly or through other nodes.
+    pub fn reaches(&self, node: NodeId, target: NodeId) -> bool {
+        self.reaches_through(node, target, &BTreeMap::new())
+    }
+
+    /// Return whether `node` feeds into `target` like [`Self::reaches`], counting the sends in `mixes` of the tracks played by a node as connections
+    /// from it to the buses they go to.
+    fn reaches_through(&self, node: NodeId, target: NodeId, mixes: &BTreeMap<u32, TrackMix>) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if node == target {
+                return true;
+            }
+            if !visited.insert(node) {
+                continue;
+            }
+            stack.extend(self.edges.iter().filter(|edge| edge.from == node).map(|edge| edge.to));
+            if let Some(graph_node) = self.nodes.get(&node).filter(|_| !mixes.is_empty()) {
+                let mut tracks = Vec::new();
+                graph_node.data.played_tracks(&mut tracks);
+                let sends = tracks.iter().filter_map(|track| mixes.get(track)).flat_map(|mix| &mix.sends);
+                stack.extend(sends.map(|send| send.bus).filter(|bus| self.nodes.contains_key(bus)));
+            }
+        }
+        false
+    }
+
+    /// Return whether `node` can be heard with the current solo, which is when it feeds into the soloed node or is fed by it, including through the
+    /// sends in `mixes` of the tracks it plays.
+    pub fn is_audible(&self, node: NodeId, mixes: &BTreeMap<u32, TrackMix>) -> bool {
+        self.solo.is_none_or(|solo| node == solo || self.reaches_through(node, solo, mixes) || self.reaches_through(solo, node, mixes))
+    }
+
+    /// Return whether audio going through `edge` can be heard with the current solo, which is when it leads to the soloed node or comes from it, so
+    /// that branches going around the soloed node are muted even if they come from a node feeding it.
+    fn is_edge_audible(&self, edge: Edge, mixes: &BTreeMap<u32, TrackMix>) -> bool {
+        self.solo.is_none_or(|solo| {
+            edge.to == solo || edge.from == solo || self.reaches_through(edge.to, solo, mixes) || self.reaches_through(solo, edge.from, mixes)
+        })
+    }
+
+    /// Add a node between the output and what it was connected to, moving the output along to make room for it.
+    pub fn append(&mut self, data: NodeData) -> NodeId {
+        let output = self.nodes.get(&NodeId::Output).map_or(Vec2::ZERO, |node| node.position);
+        let id = self.add_node(data, output);
+        if let Some(node) = self.nodes.get_mut(&NodeId::Output) {
+            node.position.x += 150.;
+        }
+        let before: Vec<_> = self.edges.iter().filter(|edge| edge.to == NodeId::Output).copied().collect();
+        for edge in before {
+            self.edges.remove(&edge);
+            self.edges.insert(Edge { from: edge.from, to: id });
+        }
+        self.edges.insert(Edge { from: id, to: NodeId::Output });
+        id
+    }
+
+    /// Connect the output of `from` to the input of `to`.
+    ///
+    /// Returns `false` without changing anything if either node lacks the port, or if the connection would create a cycle.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) -> bool {
+        let has_ports = self.nodes.get(&from).is_some_and(|node| node.data.has_output()) && self.nodes.get(&to).is_some_and(|node| node.data.has_input());
+        if !has_ports || self.reaches(to, from) {
+            return false;
+        }
+        self.edges.insert(Edge { from, to });
+        true
+    }
+    /// Prepare the graph for playback, with `node` deciding how each node is processed.
+    ///
+    /// `node` is given the track whose insert chain the node is in, if any, the path to the node through groups, and the node's data.
+    /// The audio of track inputs goes through the track's insert chain in `inserts`, if it has one, and then through its fader in `mixes`, which
+    /// also sends it to buses.
+    /// Bypassed nodes pass their input through, and connections muted by the solo are left out, along with the sends of tracks it mutes.
+    pub fn schedule(
+        &self,
+        inserts: &BTreeMap<u32, Self>,
+        mixes: &BTreeMap<u32, TrackMix>,
+        channels: u16,
+        sample_rate: u32,
+        node: impl FnMut(Option<u32>, &[NodeId], &NodeData) -> schedule::Node,
+    ) -> Result<Schedule, CycleError> {
+        let mut flattening = Flattening {
+            inserts,
+            mixes,
+            buses: HashMap::new(),
+            sends: Vec::new(),
+            track: None,
+            path: Vec::new(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            node,
+        };
+        let output = self.flatten(&mut flattening, None);
+        // Sends to buses that were removed are left out, and so are sends that would feed back into themselves, like from a track played in the bus it's
+        // sent to, so that one send can't silence the whole graph.
+        for (send, bus) in std::mem::take(&mut flattening.sends) {
+            let Some(bus) = flattening.buses.get(&bus).copied() else {
+                continue;
+            };
+            if !feeds(&flattening.edges, bus, send) {
+                flattening.edges.push((send, bus));
+            }
+        }
+        Schedule::new(flattening.nodes, &flattening.edges, output, usize::from(channels), f64::from(sample_rate))
+    }
+
+    /// Add the nodes and connections of the graph to `flattening` with the contents of its groups and insert chains in place of them, returning the index of its output.
+    ///
+    /// `input` is the node feeding the graph's group inputs, if it's the graph of a group or an insert chain.
+    fn flatten(&self, flattening: &mut Flattening<impl FnMut(Option<u32>, &[NodeId], &NodeData) -> schedule::Node>, input: Option<usize>) -> usize {
+        // Where connections go into each node and where they come out of it, which differ for groups.
+        let mut ports = HashMap::new();
+        for (id, graph_node) in &self.nodes {
+            let index = flattening.nodes.len();
+            flattening.path.push(*id);
+            let output = match &graph_node.data {
+                NodeData::GroupInput => {
+                    flattening.nodes.push(schedule::Node::Sum);
+                    flattening.edges.extend(input.map(|input| (input, index)));
+                    index
+                }
+                // A bypassed bus still takes its sends, and passes them through along with its inputs.
+                NodeData::Bus { graph, .. } => {
+                    flattening.nodes.push(schedule::Node::Sum);
+                    if flattening.track.is_none() && flattening.path.len() == 1 {
+                        flattening.buses.insert(*id, index);
+                    }
+                    if graph_node.bypassed {
+                        index
+                    } else {
+                        graph.flatten(flattening, Some(index))
+                    }
+                }
+                _ if graph_node.bypassed => {
+                    flattening.nodes.push(schedule::Node::Sum);
+                    index
+                }
+                NodeData::Group { graph, .. } => {
+                    flattening.nodes.push(schedule::Node::Sum);
+                    graph.flatten(flattening, Some(index))
+                }
+                // Insert chains can't contain other insert chains, so a track input in one plays the track as it is.
+                NodeData::TrackInput { track } if flattening.track.is_none() && flattening.inserts.contains_key(track) => {
+                    flattening.nodes.push((flattening.node)(None, &flattening.path, &graph_node.data));
+                    let path = std::mem::take(&mut flattening.path);
+                    flattening.track = Some(*track);
+                    let inserts = flattening.inserts;
+                    let output = inserts[track].flatten(flattening, Some(index));
+                    flattening.track = None;
+                    flattening.path = path;
+                    flattening.mix(*track, output, self.is_audible(*id, flattening.mixes))
+                }
+                NodeData::TrackInput { track } if flattening.track.is_none() => {
+                    flattening.nodes.push((flattening.node)(None, &flattening.path, &graph_node.data));
+                    flattening.mix(*track, index, self.is_audible(*id, flattening.mixes))
+                }
+                data => {
+                    flattening.nodes.push((flattening.node)(flattening.track, &flattening.path, data));
+                    index
+                }
+            };
+            flattening.path.pop();
+            ports.insert(*id, (index, output));
+        }
+        flattening.edges.extend(
+            self.edges
+                .iter()
+                .filter(|edge| self.is_edge_audible(**edge, flattening.mixes))
+                .filter_map(|edge| Some((ports.get(&edge.from)?.1, ports.get(&edge.to)?.0))),
+        );
+        ports[&NodeId::Output].1
+    }
+}
+
+/// Return whether the node at `from` feeds into the one at `to` through `edges`, which are pairs of indices like those of a [`Schedule`].
+fn feeds(edges: &[(usize, usize)], from: usize, to: usize) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(node) = stack.pop() {
+        if node == to {
+            return true;
+        }
+        if visited.insert(node) {
+            stack.extend(edges.iter().filter(|(edge_from, _)| *edge_from == node).map(|(_, edge_to)| *edge_to));
+        }
+    }
+    false
+}
+
+/// A bus of the main graph that tracks can be sent to, see [`Graph::buses`].
+pub struct SendTarget<'a> {
+    pub bus: NodeId,
+    pub name: &'a str,
+    /// The tracks played inside the bus, which can't be sent to it as they'd feed back into it.
+    pub tracks: Vec<u32>,
+}
+
+/// The nodes and connections of a [`Schedule`] being built by [`Graph::flatten`], along with where in the graph it is.
+struct Flattening<'a, F> {
+    inserts: &'a BTreeMap<u32, Graph>,
+    mixes: &'a BTreeMap<u32, TrackMix>,
+    /// Where the audio going into each bus of the main graph is summed.
+    buses: HashMap<NodeId, usize>,
+    /// The nodes sending audio to a bus, which are connected to it once every bus is flattened.
+    sends: Vec<(usize, NodeId)>,
+    /// The track whose insert chain is being flattened, if any.
+    track: Option<u32>,
+    /// The groups leading to the graph being flattened, from the main graph or the insert chain.
+    path: Vec<NodeId>,
+    nodes: Vec<schedule::Node>,
+    edges: Vec<(usize, usize)>,
+    node: F,
+}
+
+impl<F> Flattening<'_, F> {
+    /// Add the fader of `track` after `output`, where the track comes out of its insert chain, and its sends if they're `audible`, returning where
+    /// the track comes out of its fader.
+    fn mix(&mut self, track: u32, output: usize, audible: bool) -> usize {
+        let Some(mix) = self.mixes.get(&track) else {
+            return output;
+        };
+        let fader = self.nodes.len();
+        self.nodes.push(schedule::Node::Gain(amplitude(mix.fader)));
+        self.edges.push((output, fader));
+        for send in mix.sends.iter().filter(|_| audible) {
+            let index = self.nodes.len();
+            self.nodes.push(schedule::Node::Gain(amplitude(send.level)));
+            self.edges.push((if send.pre_fader { output } else { fader }, index));
+            self.sends.push((index, send.bus));
+        }
+        fader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use blerp::processing::{graph as schedule, registry};
+    use egui::vec2;
+
+    use super::{Graph, NodeData, NodeId};
+    use crate::visual::central::playlist::{BusSend, TrackMix};
+
+    /// Return a main graph playing track 0 straight to the output, and a bus connected to the output, along with the ids of both.
+    fn main_graph() -> (Graph, NodeId, NodeId) {
+        let mut graph = Graph::inserts();
+        let track = graph.add_node(NodeData::TrackInput { track: 0 }, vec2(0., 0.));
+        let bus = graph.add_node(NodeData::Bus { name: "Bus".into(), graph: Box::new(Graph::inserts()) }, vec2(0., 0.));
+        graph.connect(track, NodeId::Output);
+        graph.connect(bus, NodeId::Output);
+        (graph, track, bus)
+    }
+
+    fn mix(fader: f64, bus: NodeId, pre_fader: bool) -> BTreeMap<u32, TrackMix> {
+        BTreeMap::from([(0, TrackMix { fader, sends: vec![BusSend { bus, level: 0., pre_fader }] })])
+    }
+
+    /// Return the first sample of the output of `graph`, where every track plays ones and effects are built from their parameters.
+    fn play(graph: &Graph, mixes: &BTreeMap<u32, TrackMix>) -> f64 {
+        let mut schedule = graph
+            .schedule(&BTreeMap::new(), mixes, 1, 4, |_, _, data| match data {
+                NodeData::TrackInput { .. } => schedule::Node::Samples { samples: Arc::from([1.; 4]), looping: false },
+                NodeData::Middle { effect, parameters } => schedule::Node::Effect(effect.build(parameters)),
+                _ => schedule::Node::Sum,
+            })
+            .unwrap();
+        let mut output = [0.; 4];
+        schedule.process(&[], &mut output);
+        output[0]
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} isn't {expected}");
+    }
+
+    /// The level that halves amplitude, in decibels.
+    const HALF: f64 = -6.020_599_913_279_624;
+
+    #[test]
+    fn faders_apply_before_post_fader_sends() {
+        let (graph, _, bus) = main_graph();
+        // The track comes out of its fader at half, and is sent from there at full level.
+        assert_close(play(&graph, &mix(HALF, bus, false)), 1.);
+    }
+
+    #[test]
+    fn pre_fader_sends_ignore_the_fader() {
+        let (graph, _, bus) = main_graph();
+        assert_close(play(&graph, &mix(HALF, bus, true)), 1.5);
+    }
+
+    #[test]
+    fn soloed_buses_play_their_sends_alone() {
+        let (mut graph, track, bus) = main_graph();
+        let mixes = mix(HALF, bus, true);
+        graph.solo = Some(bus);
+        assert!(graph.is_audible(track, &mixes));
+        assert_close(play(&graph, &mixes), 1.);
+        graph.solo = Some(track);
+        assert_close(play(&graph, &mixes), 1.5);
+    }
+
+    #[test]
+    fn bypassed_buses_pass_their_sends_through() {
+        let (mut graph, _, bus) = main_graph();
+        let effect = registry::find("Scale").unwrap();
+        let NodeData::Bus { graph: inserts, .. } = &mut graph.nodes.get_mut(&bus).unwrap().data else {
+            unreachable!();
+        };
+        inserts.append(NodeData::Middle { effect, parameters: vec![0.] });
+        let mixes = mix(HALF, bus, false);
+        assert_close(play(&graph, &mixes), 0.5);
+        graph.nodes.get_mut(&bus).unwrap().bypassed = true;
+        assert_close(play(&graph, &mixes), 1.);
+    }
+
+    #[test]
+    fn tracks_played_in_a_bus_are_not_sent_to_it() {
+        let (mut graph, _, bus) = main_graph();
+        let NodeData::Bus { graph: inserts, .. } = &mut graph.nodes.get_mut(&bus).unwrap().data else {
+            unreachable!();
+        };
+        inserts.append(NodeData::TrackInput { track: 0 });
+        assert_eq!(graph.buses()[0].tracks, [0]);
+        assert!(play(&graph, &mix(0., bus, false)).is_finite());
+    }
+}
@@ -0,0 +1,141 @@
+//! A window following the loudness of the master output while the project plays, as defined by EBU R 128, with the momentary and short-term
+//! loudness over the last minute and the integrated loudness since the measurement was reset.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use blerp::processing::{graph::Tap, loudness::LoudnessMeter};
+use egui::{pos2, vec2, Align2, Color32, Context, FontId, Grid, RichText, Sense, Shape, Stroke, Ui, Window};
+use itertools::Itertools;
+
+/// How many of the latest frames the tap keeps, which is how long the interface can take between two frames without audio being missed.
+pub const LENGTH: usize = 1 << 16;
+/// How much audio each point of the history spans, in seconds.
+const INTERVAL: f64 = 0.1;
+/// How many points of history are kept, which is a minute of audio.
+const HISTORY: usize = 600;
+/// The quietest loudness shown, in LUFS.
+const FLOOR: f64 = -60.;
+const BACKGROUND: Color32 = Color32::from_black_alpha(0x60);
+const GRID: Color32 = Color32::from_rgba_premultiplied(0x18, 0x18, 0x18, 0x18);
+const MOMENTARY: Color32 = Color32::from_rgba_premultiplied(0x2e, 0x64, 0x80, 0x80);
+const SHORT_TERM: Color32 = Color32::from_rgb(0xff, 0xd2, 0x4d);
+
+#[derive(Default)]
+pub struct Loudness {
+    /// Whether the tap is given the audio of the master output, which is the case while the window is shown.
+    pub listening: bool,
+    pub tap: Option<Arc<Tap>>,
+    meter: Option<LoudnessMeter>,
+    /// The tap read from, and how many frames of it were read, so that each sample is measured once.
+    read: Option<(Arc<Tap>, usize)>,
+    /// How many frames were measured since the last point of history.
+    pending: usize,
+    /// The momentary and short-term loudness every [`INTERVAL`] of audio, oldest first.
+    history: VecDeque<(f64, f64)>,
+}
+
+impl Loudness {
+    /// Show the loudness in a window while `open` is true, where `reports` is whether loudness reports are written next to exported mixes. Returns
+    /// whether the project has to be scheduled again for the tap to listen to the master output, or to stop listening once the window is closed.
+    pub fn window(&mut self, ctx: &Context, open: &mut bool, reports: &mut bool) -> bool {
+        if *open {
+            Window::new("Loudness").open(open).default_size([420., 240.]).show(ctx, |ui| {
+                self.measure();
+                self.add_readout(ui);
+                ui.checkbox(reports, "Write a loudness report next to exported mixes")
+                    .on_hover_text("The integrated loudness, true peak and loudness range of the mix exported from the File menu");
+                self.add_history(ui);
+            });
+        }
+        let changed = *open != self.listening;
+        self.listening = *open;
+        changed
+    }
+
+    /// Measure the audio that went through the tap since it was last read.
+    fn measure(&mut self) {
+        let Some(tap) = &self.tap else {
+            return;
+        };
+        let tap = Arc::clone(tap);
+        let (channels, sample_rate) = (tap.channels(), tap.sample_rate());
+        let read = match &self.read {
+            Some((read, frames)) if Arc::ptr_eq(read, &tap) => *frames,
+            // A new tap is made whenever the project is scheduled again, and it's read from its start. The measurement goes on through it unless
+            // the audio device changed.
+            Some((read, _)) if read.channels() != channels || (read.sample_rate() - sample_rate).abs() >= f64::EPSILON => {
+                self.reset();
+                0
+            }
+            _ => 0,
+        };
+        let (samples, frames) = tap.samples_since(read);
+        self.read = Some((tap, frames));
+        let meter = self.meter.get_or_insert_with(|| LoudnessMeter::new(channels, sample_rate));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "intervals are short and positive")]
+        let interval = ((INTERVAL * sample_rate) as usize).max(1);
+        for chunk in samples.chunks(interval * channels) {
+            meter.push(chunk);
+            self.pending += chunk.len() / channels;
+            if self.pending >= interval {
+                self.pending -= interval;
+                self.history.push_back((meter.momentary(), meter.short_term()));
+                if self.history.len() > HISTORY {
+                    self.history.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Forget everything measured, to measure the integrated loudness of a new pass.
+    fn reset(&mut self) {
+        self.meter = None;
+        self.pending = 0;
+        self.history.clear();
+    }
+
+    fn add_readout(&mut self, ui: &mut Ui) {
+        let lufs = |loudness: f64| if loudness > FLOOR { format!("{loudness:.1} LUFS") } else { "–".to_string() };
+        let (momentary, short_term, integrated) =
+            self.meter.as_ref().map_or((f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY), |meter| (meter.momentary(), meter.short_term(), meter.integrated()));
+        ui.horizontal(|ui| {
+            Grid::new("loudness readout").num_columns(2).show(ui, |ui| {
+                ui.label(RichText::new("Momentary").color(MOMENTARY));
+                ui.label(lufs(momentary));
+                ui.end_row();
+                ui.label(RichText::new("Short-term").color(SHORT_TERM));
+                ui.label(lufs(short_term));
+                ui.end_row();
+                ui.label("Integrated");
+                ui.label(RichText::new(lufs(integrated)).strong());
+                ui.end_row();
+            });
+            if ui.button("Reset").on_hover_text("Start measuring the integrated loudness again").clicked() {
+                self.reset();
+            }
+        });
+    }
+
+    /// Show the momentary and short-term loudness of the last minute of audio, newest at the right.
+    fn add_history(&self, ui: &mut Ui) {
+        // The history follows the audio, so it has to be redrawn continuously.
+        ui.ctx().request_repaint();
+        let (rect, _) = ui.allocate_exact_size(ui.available_size().max(vec2(200., 100.)), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., BACKGROUND);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss, reason = "only used for drawing")]
+        let position = |index: usize, loudness: f64| rect.lerp_inside(vec2(index as f32 / (HISTORY - 1) as f32, (loudness / FLOOR).clamp(0., 1.) as f32));
+        for loudness in (1..).map(|step| f64::from(step) * -12.).take_while(|loudness| *loudness > FLOOR) {
+            let y = position(0, loudness).y;
+            painter.hline(rect.x_range(), y, Stroke::new(1., GRID));
+            painter.text(pos2(rect.left() + 2., y - 1.), Align2::LEFT_BOTTOM, format!("{loudness} LUFS"), FontId::proportional(10.), Color32::GRAY);
+        }
+        let start = HISTORY - self.history.len();
+        let line = |loudness: fn(&(f64, f64)) -> f64, width: f32, color: Color32| {
+            let points = self.history.iter().enumerate().map(|(index, point)| position(start + index, loudness(point).max(FLOOR))).collect_vec();
+            Shape::line(points, Stroke::new(width, color))
+        };
+        painter.add(line(|point| point.0, 1., MOMENTARY));
+        painter.add(line(|point| point.1, 2., SHORT_TERM));
+    }
+}
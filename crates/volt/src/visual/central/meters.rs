@@ -0,0 +1,184 @@
+//! Peak and RMS level meters of the tracks, the mixers of the graph and the master output. The engine measures every sample going through their
+//! taps, and each meter takes what was measured since it was last drawn, so the audio is followed at its own rate and shown at the rate of the
+//! interface. Meters rise at once and fall back as set by their [`Ballistics`], and latch a clip indicator once the audio reaches full scale.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use blerp::processing::graph::{Level, Tap};
+use egui::{hex_color, vec2, Color32, Id, Rect, Response, Sense, Ui, Vec2};
+
+use super::graph::NodeId;
+use crate::config::Ballistics;
+
+/// The quietest level shown, in dBFS.
+const FLOOR: f64 = -60.;
+/// How often a silent meter checks whether audio started going through it.
+const IDLE_REFRESH: Duration = Duration::from_millis(100);
+/// The width of the clip indicator at the right of a meter.
+const CLIP_WIDTH: f32 = 6.;
+const BACKGROUND: Color32 = Color32::from_black_alpha(0x60);
+const CLIPPED: Color32 = Color32::from_rgb(0xff, 0x5c, 0x5c);
+
+/// Where a meter listens.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Point {
+    Master,
+    /// A track after its insert chain, as it goes into the graph.
+    Track(u32),
+    /// A mixer node, in the insert chain of the track if any, at its path through groups.
+    Mixer(Option<u32>, Vec<NodeId>),
+}
+
+/// The levels shown for a channel, in dBFS.
+struct Channel {
+    peak: f64,
+    rms: f64,
+    /// The highest peak lately, and when it was reached.
+    held: f64,
+    held_at: Instant,
+}
+
+struct Meter {
+    channels: Vec<Channel>,
+    /// Whether the audio reached full scale since the indicator was last reset.
+    clipped: bool,
+    updated_at: Instant,
+}
+
+impl Meter {
+    fn new() -> Self {
+        Self {
+            channels: Vec::new(),
+            clipped: false,
+            updated_at: Instant::now(),
+        }
+    }
+
+    /// Take in the `levels` measured since the last update, if any, letting the levels shown fall back otherwise.
+    fn update(&mut self, levels: Option<&[Level]>, ballistics: Ballistics) {
+        let now = Instant::now();
+        let fall = f64::from(ballistics.release) * now.duration_since(self.updated_at).as_secs_f64();
+        let hold = Duration::from_secs_f32(ballistics.peak_hold.max(0.));
+        self.updated_at = now;
+        if let Some(levels) = levels {
+            self.channels.resize_with(levels.len(), || Channel {
+                peak: FLOOR,
+                rms: FLOOR,
+                held: FLOOR,
+                held_at: now,
+            });
+        }
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let level = levels.and_then(|levels| levels.get(index)).copied().unwrap_or_default();
+            let (peak, rms) = (decibels(level.peak), decibels(level.rms));
+            channel.peak = peak.max(channel.peak - fall).max(FLOOR);
+            channel.rms = rms.max(channel.rms - fall).max(FLOOR);
+            if peak >= channel.held || now.duration_since(channel.held_at) > hold {
+                channel.held = channel.peak;
+                channel.held_at = now;
+            }
+            self.clipped |= level.peak >= 1.;
+        }
+    }
+
+    fn is_silent(&self) -> bool {
+        self.channels.iter().all(|channel| channel.held <= FLOOR)
+    }
+}
+
+fn decibels(amplitude: f64) -> f64 {
+    20. * amplitude.log10()
+}
+
+/// Return how far `decibels` is between [`FLOOR`] and 0 dBFS.
+#[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+fn fraction(decibels: f64) -> f32 {
+    ((decibels - FLOOR) / -FLOOR).clamp(0., 1.) as f32
+}
+
+fn color(decibels: f64) -> Color32 {
+    if decibels >= 0. {
+        CLIPPED
+    } else if decibels >= -6. {
+        hex_color!("ffd24d")
+    } else {
+        hex_color!("5cff8c")
+    }
+}
+
+/// The meters of a project, with the taps they listen to as of the last schedule.
+#[derive(Default)]
+pub struct Meters {
+    taps: HashMap<Point, Arc<Tap>>,
+    /// What each meter shows.
+    levels: HashMap<Point, Meter>,
+    pub ballistics: Ballistics,
+}
+
+impl Meters {
+    /// Listen to `taps` from now on, forgetting the meters of points that are no longer in the schedule.
+    pub fn set_taps(&mut self, taps: HashMap<Point, Arc<Tap>>) {
+        self.levels.retain(|point, _| taps.contains_key(point));
+        self.taps = taps;
+    }
+
+    /// Return the tap the meter of `point` listens to, if it's in the schedule.
+    pub fn tap(&self, point: &Point) -> Option<&Arc<Tap>> {
+        self.taps.get(point)
+    }
+
+    /// Show the meter of `point` in `rect`, with a bar for each channel and the clip indicator at the right, which is reset by clicking the meter.
+    pub fn show(&mut self, ui: &Ui, point: &Point, rect: Rect) -> Response {
+        let levels = self.taps.get(point).and_then(|tap| tap.take_levels());
+        let meter = self.levels.entry(point.clone()).or_insert_with(Meter::new);
+        meter.update(levels.as_deref(), self.ballistics);
+        if meter.is_silent() {
+            ui.ctx().request_repaint_after(IDLE_REFRESH);
+        } else {
+            // Meters follow the audio, so they have to be redrawn continuously while there's something to show.
+            ui.ctx().request_repaint();
+        }
+        let response = ui.interact(rect, Id::new(("meter", point)), Sense::click());
+        if response.clicked() {
+            meter.clipped = false;
+        }
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., BACKGROUND);
+        let bars = rect.with_max_x(rect.right() - CLIP_WIDTH - 1.);
+        let clip = rect.with_min_x(rect.right() - CLIP_WIDTH);
+        painter.rect_filled(clip, 2., if meter.clipped { CLIPPED } else { BACKGROUND });
+        #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+        let height = bars.height() / meter.channels.len().max(1) as f32;
+        for (index, channel) in meter.channels.iter().enumerate() {
+            #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+            let row = Rect::from_min_size(bars.min + vec2(0., index as f32 * height), vec2(bars.width(), height)).shrink2(vec2(0., 0.5));
+            let bar = |decibels| Rect::from_min_size(row.min, vec2(row.width() * fraction(decibels), row.height()));
+            painter.rect_filled(bar(channel.peak), 1., color(channel.peak).gamma_multiply(0.4));
+            painter.rect_filled(bar(channel.rms), 1., color(channel.rms));
+            if channel.held > FLOOR {
+                let x = row.width().mul_add(fraction(channel.held), row.left());
+                painter.vline(x, row.y_range(), (1., color(channel.held)));
+            }
+        }
+        let loudest = |level: fn(&Channel) -> f64| meter.channels.iter().map(level).fold(FLOOR, f64::max);
+        let (peak, rms) = (loudest(|channel| channel.held), loudest(|channel| channel.rms));
+        response.on_hover_ui(|ui| {
+            if peak > FLOOR {
+                ui.label(format!("Peak {peak:.1} dBFS, RMS {rms:.1} dBFS"));
+            } else {
+                ui.label("Silent");
+            }
+            ui.weak("Click to reset the clip indicator");
+        })
+    }
+
+    /// Allocate space of `size` in `ui` and show the meter of `point` in it, see [`Self::show`].
+    pub fn add(&mut self, ui: &mut Ui, point: &Point, size: Vec2) -> Response {
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        self.show(ui, point, rect)
+    }
+}
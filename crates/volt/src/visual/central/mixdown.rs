@@ -0,0 +1,123 @@
+//! Rendering the mix of the whole project into an audio file, which is what the master output plays from the start of the playlist until its last
+//! clip has ended, along with a report of its loudness if it's asked for.
+//!
+//! The project is copied when the mix is asked for and rendered in the background, faster than it plays. Files are decoded whole rather than
+//! streamed, and live inputs are silent.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display, Formatter},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use blerp::processing::{graph as schedule, loudness};
+use tracing::error;
+
+use super::{
+    graph::{Graph, NodeData},
+    playlist::{self, Playlist},
+};
+use crate::{engine, progress::Progress};
+
+/// How long the mix goes on after the last clip has ended, so that the tails of reverbs and delays aren't cut off.
+const TAIL: Duration = Duration::from_secs(2);
+/// How many frames are rendered at a time, between which the render checks whether it was cancelled.
+const BLOCK: usize = 4096;
+
+#[derive(Debug)]
+pub enum MixdownError {
+    /// The graph feeds back into itself, so it can't be played.
+    Cycle,
+    Write,
+    Cancelled,
+}
+
+impl Display for MixdownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle => write!(f, "the graph feeds back into itself"),
+            Self::Write => write!(f, "the file couldn't be written"),
+            Self::Cancelled => write!(f, "it was cancelled"),
+        }
+    }
+}
+
+/// The project as it was when its mix was asked for, to render in the background in `channels` channels at `sample_rate`.
+pub struct Mixdown {
+    pub graph: Graph,
+    pub inserts: BTreeMap<u32, Graph>,
+    pub playlist: Playlist,
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+impl Mixdown {
+    /// Render the mix into a WAV file at `path`, and write a loudness report next to it if `report` is true, whose path is returned.
+    pub fn export(self, path: &Path, report: bool, progress: &Progress) -> Result<Option<PathBuf>, MixdownError> {
+        let samples = self.render(progress)?;
+        playlist::write_wave(&samples, self.channels, self.sample_rate, path).ok_or(MixdownError::Write)?;
+        if !report {
+            return Ok(None);
+        }
+        let report = loudness::report(&samples, usize::from(self.channels), f64::from(self.sample_rate));
+        let report_path = path.with_extension("loudness.txt");
+        let name = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+        let text = format!(
+            "{name}\nIntegrated loudness: {:.1} LUFS\nTrue peak: {:.1} dBTP\nLoudness range: {:.1} LU\n",
+            report.integrated, report.true_peak, report.range
+        );
+        fs::write(&report_path, text).inspect_err(|error| error!("Couldn't write {}: {error}", report_path.display())).map_err(|_| MixdownError::Write)?;
+        Ok(Some(report_path))
+    }
+
+    /// Return the interleaved samples of the mix.
+    fn render(&self, progress: &Progress) -> Result<Vec<f64>, MixdownError> {
+        let Self { graph, playlist, channels, sample_rate, .. } = self;
+        let (channels, sample_rate) = (*channels, *sample_rate);
+        // Tracks without an insert chain go through an empty one, like when they're played.
+        let mut inserts = self.inserts.clone();
+        for clip in &playlist.clips {
+            inserts.entry(clip.track).or_insert_with(Graph::inserts);
+        }
+        let mut files = HashMap::new();
+        let mut decode = |path: &Path| -> Option<Arc<[f64]>> {
+            files
+                .entry(path.to_path_buf())
+                .or_insert_with(|| engine::decode(path, channels, sample_rate).inspect_err(|error| error!("Couldn't decode {}: {error}", path.display())).ok().map(Arc::from))
+                .clone()
+        };
+        let mut schedule = graph
+            .schedule(&inserts, &playlist.mixes, channels, sample_rate, |_, _, data| match data {
+                NodeData::FilePlayer { path, looping } => {
+                    path.as_deref().and_then(&mut decode).map_or(schedule::Node::Sum, |samples| schedule::Node::Samples { samples, looping: *looping })
+                }
+                NodeData::TrackInput { track } => {
+                    let files = playlist.instrument_files(*track).into_iter().filter_map(|path| Some((path.clone(), decode(path)?))).collect();
+                    schedule::Node::Track {
+                        samples: playlist.render_track(*track, channels, sample_rate, &files, true).into(),
+                        streams: Vec::new(),
+                    }
+                }
+                NodeData::Middle { effect, parameters } => schedule::Node::Effect(effect.build(parameters)),
+                _ => schedule::Node::Sum,
+            })
+            .map_err(|_| MixdownError::Cycle)?;
+        schedule.set_boundaries(playlist.clip_boundaries(sample_rate));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "lengths are positive and well within range")]
+        let frames = ((playlist.end() + TAIL).as_secs_f64() * f64::from(sample_rate)).round() as usize;
+        let channels = usize::from(channels);
+        let mut samples = vec![0.; frames * channels];
+        let total = samples.len() as u64;
+        for (index, block) in samples.chunks_mut(BLOCK * channels).enumerate() {
+            if progress.is_cancelled() {
+                return Err(MixdownError::Cancelled);
+            }
+            schedule.process(&[], block);
+            progress.set(((index + 1) * BLOCK * channels) as u64, total);
+        }
+        Ok(samples)
+    }
+}
@@ -0,0 +1,1601 @@
+use blerp::processing::effects::{
+    fade::FadeEffect,
+    normalize::{NormalizeEffect, NormalizeTarget},
+    reverse::ReverseEffect,
+    scale::ScaleEffect,
+    Effect, Stuff,
+};
+use blerp::midi::{Expression, MidiFile, Note, TempoChange, Track};
+use blerp::processing::{
+    analysis,
+    drum_rack::{self, DrumRack},
+    resample,
+    synth::{self, Synth},
+};
+use blerp::processing::stretch::StretchEffect;
+use blerp::wavefile::{WaveFile, WriteError};
+use cpal::Sample;
+use egui::{vec2, Color32, Vec2};
+use itertools::Itertools;
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    iter,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::error;
+
+use super::graph::NodeId;
+use crate::{
+    archive,
+    engine::{self, StreamedClip, STREAMED_LENGTH},
+    midi,
+};
+
+/// The clips of a project laid out on tracks over time, along with the tempo they're played at.
+///
+/// Audio clips are saved as the part of their file they play, which is read again when the playlist is loaded, and the playhead and the view aren't saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Playlist {
+    pub clips: Vec<Clip>,
+    pub time_signature: TimeSignature,
+    pub tempo: Tempo,
+    /// The changes to the tempo after the start, which are kept from imported MIDI files to be written to exported ones. The playlist is played at
+    /// [`Self::tempo`] throughout.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tempo_changes: Vec<TempoMarker>,
+    #[serde(skip)]
+    pub time: Time,
+    /// The zoom factor for the playlist view. `[400.0 60.0]` means a measure is 400 pixels wide and a track is 60 pixels tall.
+    #[serde(skip)]
+    pub zoom: Vec2,
+    pub snapping: Snapping,
+    /// Indices into [`Self::clips`] of the selected clips.
+    #[serde(skip)]
+    pub selection: BTreeSet<usize>,
+    /// What plays the MIDI clips of each track whose instrument was changed from the default synth.
+    #[serde(alias = "synths", with = "instruments")]
+    pub instruments: BTreeMap<u32, Instrument>,
+    /// How each track whose settings were changed from the default takes in the live input.
+    #[serde(with = "crate::project::track_keys")]
+    pub inputs: BTreeMap<u32, InputSettings>,
+    /// The fader and the sends of each track whose mix was changed from the default.
+    #[serde(with = "crate::project::track_keys")]
+    pub mixes: BTreeMap<u32, TrackMix>,
+}
+
+impl Default for Playlist {
+    fn default() -> Self {
+        Self {
+            clips: Vec::new(),
+            time_signature: TimeSignature::default(),
+            tempo: Tempo::default(),
+            tempo_changes: Vec::new(),
+            time: Time::default(),
+            zoom: Self::DEFAULT_ZOOM,
+            snapping: Snapping::default(),
+            selection: BTreeSet::new(),
+            instruments: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            mixes: BTreeMap::new(),
+        }
+    }
+}
+
+/// A change to the tempo of the playlist.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoMarker {
+    /// When the tempo changes, in beats from the start.
+    pub beat: f64,
+    pub bpm: f64,
+}
+
+/// Whether the live input is heard through a track's insert chain, in place of its clips.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Monitoring {
+    #[default]
+    Off,
+    /// Only while the track is armed.
+    Auto,
+    On,
+}
+
+impl Monitoring {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Auto, Self::On];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Auto => "Auto",
+            Self::On => "On",
+        }
+    }
+}
+
+/// How a track takes in the live input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    /// Whether the track is ready to take in the input, which is also when it's monitored in [`Monitoring::Auto`].
+    pub armed: bool,
+    pub monitoring: Monitoring,
+}
+
+impl InputSettings {
+    /// Return whether the input is heard through the track.
+    pub const fn is_monitored(self) -> bool {
+        match self.monitoring {
+            Monitoring::Off => false,
+            Monitoring::Auto => self.armed,
+            Monitoring::On => true,
+        }
+    }
+}
+
+/// How loud a track goes into the graph, and how much of it is sent to each bus.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackMix {
+    /// The gain of the track after its insert chain, in decibels.
+    pub fader: f64,
+    pub sends: Vec<BusSend>,
+}
+
+/// Part of a track sent to a bus of the graph, see [`super::NodeData::Bus`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BusSend {
+    /// The bus node of the main graph.
+    pub bus: NodeId,
+    /// The gain of the send, in decibels.
+    pub level: f64,
+    /// Whether the send takes the track before its fader, so that the fader doesn't change how much of it reaches the bus.
+    #[serde(default)]
+    pub pre_fader: bool,
+}
+
+impl BusSend {
+    /// The level of a new send, which is quiet enough not to drown the track in an effect.
+    pub const DEFAULT_LEVEL: f64 = -12.;
+}
+
+/// Return the amplitude that `decibels` multiply audio by.
+pub fn amplitude(decibels: f64) -> f64 {
+    10_f64.powf(decibels / 20.)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Snapping {
+    None,
+    /// Snaps to the nearest beat divided by the given number, normally a power of 2.
+    Beats {
+        divisor: u32,
+    },
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self::Beats { divisor: 4 }
+    }
+}
+
+impl Snapping {
+    /// The amount clips are nudged by when snapping is turned off, in beats.
+    const FREE_STEP: f64 = 1. / 64.;
+
+    /// Round a position in beats to the nearest step.
+    pub fn snap(self, beats: f64) -> f64 {
+        (beats / self.step()).round() * self.step()
+    }
+
+    /// Return the smallest distance, in beats, that clips move by.
+    pub fn step(self) -> f64 {
+        match self {
+            Self::None => Self::FREE_STEP,
+            Self::Beats { divisor } => 1. / f64::from(divisor.max(1)),
+        }
+    }
+}
+
+/// Saved as its BPM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "f64", from = "f64")]
+pub struct Tempo {
+    beats_per_hectominute: u32,
+}
+
+impl Default for Tempo {
+    fn default() -> Self {
+        Self::from_bpm(120.)
+    }
+}
+
+impl Tempo {
+    pub fn from_bpm(bpm: f64) -> Self {
+        #[allow(clippy::cast_sign_loss, reason = "bpm is always positive")]
+        #[allow(clippy::cast_possible_truncation, reason = "bpm only goes up to 999.99, so never truncates")]
+        let beats_per_hectominute = ((bpm * 100.).round() as u32).clamp(1, 99999);
+        Self { beats_per_hectominute }
+    }
+
+    pub fn bpm(self) -> f64 {
+        f64::from(self.beats_per_hectominute) / 100.
+    }
+
+    pub fn bps(self) -> f64 {
+        self.bpm() / 60.
+    }
+}
+
+impl From<f64> for Tempo {
+    fn from(bpm: f64) -> Self {
+        Self::from_bpm(bpm)
+    }
+}
+
+impl From<Tempo> for f64 {
+    fn from(tempo: Tempo) -> Self {
+        tempo.bpm()
+    }
+}
+
+/// What plays the MIDI clips of a track.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Instrument {
+    /// The built-in synth, with the values of its settings, see [`blerp::processing::synth`].
+    Synth { values: Vec<f64> },
+    /// A drum rack, whose pads are played by the notes from [`drum_rack::FIRST_KEY`] up, see [`blerp::processing::drum_rack`].
+    DrumRack { pads: Vec<PadData> },
+}
+
+impl Default for Instrument {
+    fn default() -> Self {
+        Self::Synth { values: synth::defaults() }
+    }
+}
+
+/// A pad of a drum rack, with the file of its sample if it has one and the values of its settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PadData {
+    pub path: Option<PathBuf>,
+    pub values: Vec<f64>,
+}
+
+impl Instrument {
+    /// Return an empty drum rack.
+    pub fn drum_rack() -> Self {
+        Self::DrumRack {
+            pads: vec![PadData { path: None, values: drum_rack::defaults() }; drum_rack::PADS],
+        }
+    }
+
+    /// Play `notes` and their `expression` at `tempo` BPM, returning the interleaved samples of `channels` channels at `sample_rate`. `files` has the
+    /// samples of the files of the pads in that format.
+    pub fn play(&self, notes: &[Note], expression: &[Expression], tempo: f64, channels: usize, sample_rate: u32, files: &HashMap<PathBuf, Arc<[f64]>>) -> Vec<f64> {
+        match self {
+            Self::Synth { values } => {
+                let samples = Synth::new(values).play(notes, expression, tempo, f64::from(sample_rate));
+                resample::convert(&samples, 1, f64::from(sample_rate), channels, f64::from(sample_rate))
+            }
+            Self::DrumRack { pads } => {
+                let mut rack = DrumRack::default();
+                for (pad, data) in rack.pads.iter_mut().zip(pads) {
+                    *pad = data
+                        .path
+                        .as_ref()
+                        .and_then(|path| files.get(path))
+                        .map(|samples| drum_rack::Pad::new(Arc::clone(samples), channels, f64::from(sample_rate), &data.values));
+                }
+                rack.play(notes, tempo, channels, f64::from(sample_rate))
+            }
+        }
+    }
+
+    /// Return the files of the pads that have one.
+    pub fn files(&self) -> Vec<&PathBuf> {
+        match self {
+            Self::Synth { .. } => Vec::new(),
+            Self::DrumRack { pads } => pads.iter().filter_map(|pad| pad.path.as_ref()).collect(),
+        }
+    }
+
+    /// Like [`Instrument::files`], but mutable.
+    pub fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+        match self {
+            Self::Synth { .. } => Vec::new(),
+            Self::DrumRack { pads } => pads.iter_mut().filter_map(|pad| pad.path.as_mut()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clip {
+    /// Tells the clip apart from the others as clips are added and removed around it, for as long as the app runs. It isn't saved.
+    #[serde(skip, default = "Clip::next_id")]
+    pub id: u64,
+    pub start: Time,
+    pub track: u32,
+    pub data: ClipData,
+    #[serde(default)]
+    pub processing: ClipProcessing,
+    pub name: String,
+    #[serde(with = "color")]
+    pub color: Color32,
+}
+
+impl Clip {
+    pub const DEFAULT_COLOR: Color32 = Color32::GRAY;
+
+    /// Return an id that no other clip has.
+    pub fn next_id() -> u64 {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Return a new unprocessed [`Clip`], named after its source file.
+    pub fn new(start: Time, track: u32, data: ClipData) -> Self {
+        let name = match &data {
+            ClipData::Audio { path, .. } => path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy()).into_owned(),
+            ClipData::Midi { .. } => "<midi data>".into(),
+        };
+        Self {
+            id: Self::next_id(),
+            start,
+            track,
+            data,
+            processing: ClipProcessing::default(),
+            name,
+            color: Self::DEFAULT_COLOR,
+        }
+    }
+
+    /// Return the clip's samples with its [`ClipProcessing`] applied at the given project tempo, or [`None`] if the clip has no audio.
+    ///
+    /// The source samples are never modified, so every operation can be toggled off again.
+    pub fn render(&self, tempo: Tempo) -> Option<Vec<f64>> {
+        let ClipData::Audio { channels, sample_rate, .. } = &self.data else {
+            return None;
+        };
+        let samples = self.data.samples()?;
+        let input = Stuff {
+            time: 0.,
+            sample_rate: f64::from(*sample_rate),
+            channels: usize::from(*channels),
+            samples: Cow::Borrowed(&samples[..]),
+        };
+        let Ok(output) = self.processing.effects(tempo).iter().try_fold(input, |stuff, effect| effect.apply(stuff));
+        Some(output.samples.into_owned())
+    }
+
+    /// Return a file containing the clip's audio as it sounds in the project, so it can be handed to other applications.
+    ///
+    /// Unprocessed clips covering their whole source file are that file. Other clips are rendered into `directory` as 32-bit float WAV files.
+    /// Returns [`None`] for MIDI clips, or if rendering fails.
+    pub fn export(&self, tempo: Tempo, directory: &Path) -> Option<PathBuf> {
+        let ClipData::Audio {
+            path, channels, sample_rate, segment, ..
+        } = &self.data
+        else {
+            return None;
+        };
+        let description = self.processing.describe();
+        if description.is_none() && segment.is_none() {
+            return Some(path.clone());
+        }
+        let samples = self.render(tempo)?;
+        let name = self.name.replace(['/', '\\'], "_");
+        let export_path = directory.join(description.map_or_else(|| format!("{name}.wav"), |description| format!("{name} ({description}).wav")));
+        write_wave(&samples, *channels, *sample_rate, &export_path)?;
+        Some(export_path)
+    }
+}
+
+/// Write interleaved `samples` with `channels` channels to `path` as a 32-bit float WAV file, creating its folder if needed. Returns [`None`] if it fails.
+pub fn write_wave(samples: &[f64], channels: u16, sample_rate: u32, path: &Path) -> Option<()> {
+    let channels = usize::from(channels).max(1);
+    let wave_file = WaveFile::from_samples::<f32, _>((0..channels).map(|channel| samples.iter().skip(channel).step_by(channels).copied()), sample_rate)
+        .inspect_err(|error| error!("Couldn't render {}: {error}", path.display()))
+        .ok()?;
+    path.parent()
+        .map_or(Ok(()), create_dir_all)
+        .and_then(|()| File::create(path))
+        .map_err(WriteError::from)
+        .and_then(|file| wave_file.write(&mut BufWriter::new(file)))
+        .inspect_err(|error| error!("Couldn't write {}: {error}", path.display()))
+        .ok()
+}
+
+/// Operations applied to a clip's audio whenever it is rendered, rather than to the audio file itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipProcessing {
+    pub reversed: bool,
+    pub inverted: bool,
+    #[serde(with = "normalize_target")]
+    pub normalize: Option<NormalizeTarget>,
+    pub stretch: Stretch,
+    /// Length of the fade in from silence, in seconds.
+    pub fade_in: f64,
+    /// Length of the fade out to silence, in seconds.
+    pub fade_out: f64,
+}
+
+/// How a clip's audio follows the project tempo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Stretch {
+    /// Play the audio at its original speed, whatever the project tempo.
+    #[default]
+    Off,
+    /// Time-stretch the audio, which was recorded at `source_bpm`, to the project tempo.
+    Tempo { source_bpm: f64 },
+}
+
+impl ClipProcessing {
+    pub const DEFAULT_PEAK: f64 = -1.;
+    pub const DEFAULT_LOUDNESS: f64 = -14.;
+
+    fn effects(self, tempo: Tempo) -> Vec<Box<dyn Effect>> {
+        let mut effects: Vec<Box<dyn Effect>> = Vec::new();
+        if self.reversed {
+            effects.push(Box::new(ReverseEffect));
+        }
+        if self.inverted {
+            effects.push(Box::new(ScaleEffect::new(-1.)));
+        }
+        if let Some(target) = self.normalize {
+            effects.push(Box::new(NormalizeEffect::new(target)));
+        }
+        if let Stretch::Tempo { source_bpm } = self.stretch {
+            effects.push(Box::new(StretchEffect::from_tempos(source_bpm, tempo.bpm())));
+        }
+        // Fades come last, so that their lengths are the ones heard in the project.
+        if self.fade_in > 0. || self.fade_out > 0. {
+            effects.push(Box::new(FadeEffect::new(self.fade_in, self.fade_out)));
+        }
+        effects
+    }
+
+    /// Return a short, human-readable list of the enabled operations, or [`None`] if there are none.
+    pub fn describe(self) -> Option<String> {
+        let descriptions = [
+            self.reversed.then(|| "reversed".to_string()),
+            self.inverted.then(|| "phase inverted".to_string()),
+            self.normalize.map(|target| match target {
+                NormalizeTarget::Peak(decibels) => format!("normalized to {decibels:.1} dBFS"),
+                NormalizeTarget::Loudness(lufs) => format!("normalized to {lufs:.1} LUFS"),
+            }),
+            match self.stretch {
+                Stretch::Off => None,
+                Stretch::Tempo { source_bpm } => Some(format!("stretched from {source_bpm:.2} BPM")),
+            },
+            (self.fade_in > 0. || self.fade_out > 0.).then(|| "faded".to_string()),
+        ];
+        let descriptions = descriptions.into_iter().flatten().collect_vec();
+        (!descriptions.is_empty()).then(|| descriptions.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClipData {
+    Audio {
+        path: PathBuf,
+        /// Interleaved samples of every channel, shared between copies of the clip. They aren't saved, and are empty until they're read.
+        #[serde(skip)]
+        samples: Arc<[f64]>,
+        channels: u16,
+        sample_rate: u32,
+        #[serde(with = "seconds")]
+        length: Duration,
+        /// The part of the source file that `samples` cover, or [`None`] if they cover all of it.
+        #[serde(default, with = "segment")]
+        segment: Option<Range<Duration>>,
+    },
+    Midi {
+        /// The notes, timed in beats from the start of the clip.
+        #[serde(with = "notes")]
+        notes: Arc<[Note]>,
+        length: Time,
+        /// How the notes are played while they're held, timed in beats from the start of the clip.
+        #[serde(default, with = "expression")]
+        expression: Arc<[Expression]>,
+    },
+}
+
+impl ClipData {
+    /// Read the audio file at `path`, keeping only `segment` of it if it's given, or return [`None`] if the file can't be read. The samples of
+    /// clips long enough to be streamed aren't kept, see [`Self::samples`].
+    pub fn read(path: PathBuf, segment: Option<Range<Duration>>) -> Option<Self> {
+        let format = engine::probe(&path).inspect_err(|error| error!("Couldn't read {}: {error}", path.display())).ok()?;
+        let length = segment.as_ref().map_or(format.length, |segment| segment.end.min(format.length).saturating_sub(segment.start));
+        let samples = if length >= STREAMED_LENGTH { Arc::default() } else { Self::decode(&path, segment.as_ref())?.into() };
+        Some(Self::Audio {
+            path,
+            samples,
+            channels: format.channels,
+            sample_rate: format.sample_rate,
+            length,
+            segment,
+        })
+    }
+
+    /// Return the interleaved samples of `segment` of the audio file at `path`, or of all of it, or [`None`] if the file can't be read.
+    fn decode(path: &Path, segment: Option<&Range<Duration>>) -> Option<Vec<f64>> {
+        let decoder = archive::open(path).map_err(|error| error.to_string()).and_then(|file| Decoder::new(file).map_err(|error| error.to_string()));
+        let decoder = match decoder {
+            Ok(decoder) => decoder,
+            Err(error) => {
+                error!("Couldn't read {}: {error}", path.display());
+                return None;
+            }
+        };
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let mut samples = decoder.map(f64::from_sample).collect_vec();
+        if let Some(segment) = segment {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+            let index = |time: Duration| ((time.as_secs_f64() * f64::from(sample_rate)).round() as usize * usize::from(channels)).min(samples.len());
+            let range = index(segment.start)..index(segment.end).max(index(segment.start));
+            samples = samples.drain(range).collect();
+        }
+        Some(samples)
+    }
+
+    /// Return the interleaved samples of every channel of audio data, which are read from the file for clips long enough that they aren't kept, or
+    /// [`None`] for MIDI data or if the file can't be read.
+    pub fn samples(&self) -> Option<Arc<[f64]>> {
+        let Self::Audio { path, samples, length, segment, .. } = self else {
+            return None;
+        };
+        if samples.is_empty() && *length >= STREAMED_LENGTH {
+            return Self::decode(path, segment.as_ref()).map(Arc::from);
+        }
+        Some(Arc::clone(samples))
+    }
+
+    /// Return the notes of a MIDI `file` as MIDI data.
+    pub fn from_midi(file: &MidiFile) -> Self {
+        Self::Midi {
+            notes: file.notes.clone().into(),
+            length: Time::from_beats(file.length).unwrap_or_default(),
+            expression: file.expression.clone().into(),
+        }
+    }
+
+    /// Split audio into the part before `at` and the part after it, or return [`None`] for MIDI data.
+    fn split_audio(&self, at: Duration) -> Option<(Self, Self)> {
+        let Self::Audio {
+            path,
+            samples,
+            channels,
+            sample_rate,
+            length,
+            segment,
+        } = self
+        else {
+            return None;
+        };
+        let at = at.min(*length);
+        let offset = segment.as_ref().map_or(Duration::ZERO, |segment| segment.start);
+        if samples.is_empty() && *length >= STREAMED_LENGTH {
+            // The parts are read again, so that the ones too short to be streamed keep their samples.
+            let part = |range: Range<Duration>| Self::read(path.clone(), Some(offset + range.start..offset + range.end));
+            return Some((part(Duration::ZERO..at)?, part(at..*length)?));
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let frame = (at.as_secs_f64() * f64::from(*sample_rate)).round() as usize;
+        let (before, after) = samples.split_at((frame * usize::from(*channels)).min(samples.len()));
+        let part = |samples: &[f64], range: Range<Duration>| Self::Audio {
+            path: path.clone(),
+            samples: samples.into(),
+            channels: *channels,
+            sample_rate: *sample_rate,
+            length: range.end.saturating_sub(range.start),
+            segment: Some(offset + range.start..offset + range.end),
+        };
+        Some((part(before, Duration::ZERO..at), part(after, at..*length)))
+    }
+
+    /// Guess the tempo an audio clip was recorded at, first from its file name (like `loop_120bpm.wav`), then by assuming it is a loop of a power-of-two
+    /// number of beats at a typical tempo. Returns [`None`] for MIDI clips, or if the clip is too short to be a loop.
+    pub fn detect_bpm(&self) -> Option<f64> {
+        const TYPICAL_BPM: Range<f64> = 80.0..160.0;
+        let Self::Audio { path, length, .. } = self else {
+            return None;
+        };
+        let from_name = path.file_stem().and_then(|name| {
+            let name = name.to_string_lossy().to_lowercase();
+            let index = name.find("bpm")?;
+            let before = name[..index].trim_end_matches([' ', '_', '-']);
+            let digits_before = &before[before.trim_end_matches(|c: char| c.is_ascii_digit()).len()..];
+            let after = name[index + 3..].trim_start_matches([' ', '_', '-']);
+            let digits_after = &after[..after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len()];
+            let bpm = [digits_before, digits_after].into_iter().find_map(|digits| digits.parse::<f64>().ok());
+            bpm.filter(|bpm| (20.0..=999.0).contains(bpm))
+        });
+        from_name.or_else(|| (0..8).map(|power| f64::from(1_u32 << power) * 60. / length.as_secs_f64()).find(|bpm| TYPICAL_BPM.contains(bpm)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Time {
+    beats: f64,
+}
+
+impl Time {
+    pub fn from_beats(beats: f64) -> Option<Self> {
+        (beats > 0.).then_some(Self { beats })
+    }
+
+    pub const fn beats(self) -> f64 {
+        self.beats
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub beats_per_measure: u32,
+    pub beat_unit: u32,
+}
+
+impl TimeSignature {
+    /// The time signatures that can be picked.
+    pub const COMMON: [Self; 7] = [
+        Self { beats_per_measure: 2, beat_unit: 4 },
+        Self { beats_per_measure: 3, beat_unit: 4 },
+        Self { beats_per_measure: 4, beat_unit: 4 },
+        Self { beats_per_measure: 5, beat_unit: 4 },
+        Self { beats_per_measure: 6, beat_unit: 8 },
+        Self { beats_per_measure: 7, beat_unit: 8 },
+        Self { beats_per_measure: 12, beat_unit: 8 },
+    ];
+}
+
+impl std::fmt::Display for TimeSignature {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}/{}", self.beats_per_measure, self.beat_unit)
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self { beats_per_measure: 4, beat_unit: 4 }
+    }
+}
+
+impl Playlist {
+    /// The zoom of the playlist view when it's first shown.
+    pub const DEFAULT_ZOOM: Vec2 = vec2(400., 60.);
+
+    pub fn now(&self) -> Duration {
+        Duration::from_secs_f64(self.time.beats / self.tempo.bpm() * 60.)
+    }
+
+    pub const fn measure(&self) -> u32 {
+        #[allow(clippy::cast_possible_truncation, reason = "truncation is intentional")]
+        #[allow(clippy::cast_sign_loss, reason = "beats cannot be negative")]
+        {
+            self.time.beats as u32 / self.time_signature.beats_per_measure
+        }
+    }
+
+    pub fn beats_to_duration(&self, beats: f64) -> Duration {
+        Duration::from_secs_f64(beats / self.tempo.bps())
+    }
+
+    /// Mix the audio of every clip on `track` at its position, converted to the given format. `files` has the samples of the files of the track's
+    /// drum rack in that format, see [`Playlist::instrument_files`]. Clips streamed while they play are left out, unless `streamed` is true.
+    pub fn render_track(&self, track: u32, channels: u16, sample_rate: u32, files: &HashMap<PathBuf, Arc<[f64]>>, streamed: bool) -> Vec<f64> {
+        let default = Instrument::default();
+        let instrument = self.instruments.get(&track).unwrap_or(&default);
+        let channels = usize::from(channels);
+        let mut output = Vec::new();
+        for clip in self.clips.iter().filter(|clip| clip.track == track) {
+            let samples = match &clip.data {
+                ClipData::Audio {
+                    channels: clip_channels,
+                    sample_rate: clip_sample_rate,
+                    ..
+                } => {
+                    if !streamed && Self::is_streamed(clip) {
+                        continue;
+                    }
+                    let Some(samples) = clip.render(self.tempo) else {
+                        continue;
+                    };
+                    resample::convert(&samples, usize::from(*clip_channels), f64::from(*clip_sample_rate), channels, f64::from(sample_rate))
+                }
+                ClipData::Midi { notes, expression, .. } => instrument.play(notes, expression, self.tempo.bpm(), channels, sample_rate, files),
+            };
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+            let start = (self.beats_to_duration(clip.start.beats()).as_secs_f64() * f64::from(sample_rate)).round() as usize * channels;
+            if output.len() < start + samples.len() {
+                output.resize(start + samples.len(), 0.);
+            }
+            for (output, sample) in output[start..].iter_mut().zip(&samples) {
+                *output += sample;
+            }
+        }
+        output
+    }
+
+    /// Return whether `clip` is long enough to be played from the disk rather than rendered into its track, which it can only be if it's an
+    /// unprocessed audio clip.
+    fn is_streamed(clip: &Clip) -> bool {
+        matches!(&clip.data, ClipData::Audio { length, .. } if *length >= STREAMED_LENGTH) && clip.processing == ClipProcessing::default()
+    }
+
+    /// Return the clips of `track` played from the disk at `sample_rate`, which [`Playlist::render_track`] leaves out.
+    pub fn streamed_clips(&self, track: u32, sample_rate: u32) -> Vec<StreamedClip> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let frame = |duration: Duration| (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize;
+        self.clips
+            .iter()
+            .filter(|clip| clip.track == track && Self::is_streamed(clip))
+            .filter_map(|clip| {
+                let ClipData::Audio {
+                    path, channels, sample_rate: file_sample_rate, length, segment, ..
+                } = &clip.data
+                else {
+                    return None;
+                };
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+                let offset = segment.as_ref().map_or(0, |segment| (segment.start.as_secs_f64() * f64::from(*file_sample_rate)).round() as usize);
+                Some(StreamedClip {
+                    path: path.clone(),
+                    channels: *channels,
+                    sample_rate: *file_sample_rate,
+                    offset,
+                    // Rounded like the clips are when tracks are rendered.
+                    start: frame(self.beats_to_duration(clip.start.beats())),
+                    length: frame(*length),
+                })
+            })
+            .collect()
+    }
+
+    /// Return the frames at `sample_rate` where clips start and end, where playback splits its blocks so that effects follow them exactly.
+    pub fn clip_boundaries(&self, sample_rate: u32) -> Vec<usize> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "positions are positive and well within range")]
+        let frame = |duration: Duration| (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize;
+        self.clips
+            .iter()
+            .flat_map(|clip| {
+                // Rounded like the clips are when tracks are rendered.
+                let start = frame(self.beats_to_duration(clip.start.beats()));
+                [start, start + frame(self.duration_of_clip(clip))]
+            })
+            .collect()
+    }
+
+    /// Move every selected clip by `beats`, without moving any of them before the start of the playlist.
+    pub fn nudge_selection(&mut self, beats: f64) {
+        for index in &self.selection {
+            let start = &mut self.clips[*index].start;
+            *start = Time::from_beats(start.beats() + beats).unwrap_or_default();
+        }
+    }
+
+    /// Move every selected clip by `tracks` tracks, stopping at the first track.
+    pub fn move_selection_tracks(&mut self, tracks: i32) {
+        for index in &self.selection {
+            let track = &mut self.clips[*index].track;
+            *track = track.saturating_add_signed(tracks);
+        }
+    }
+
+    pub fn delete_selection(&mut self) {
+        let mut index = 0;
+        self.clips.retain(|_| {
+            index += 1;
+            !self.selection.contains(&(index - 1))
+        });
+        self.selection.clear();
+    }
+
+    /// Select the clip after the last selected one (or before the first one, if `backwards`), ordered by start time and then track.
+    pub fn cycle_selection(&mut self, backwards: bool) {
+        let order = (0..self.clips.len())
+            .sorted_by(|a, b| {
+                self.clips[*a]
+                    .start
+                    .beats()
+                    .total_cmp(&self.clips[*b].start.beats())
+                    .then(self.clips[*a].track.cmp(&self.clips[*b].track))
+            })
+            .collect_vec();
+        if order.is_empty() {
+            return;
+        }
+        let is_selected = |index: &usize| self.selection.contains(index);
+        let next = if backwards {
+            order
+                .iter()
+                .position(is_selected)
+                .map_or(order[order.len() - 1], |position| order[(position + order.len() - 1) % order.len()])
+        } else {
+            order.iter().rposition(is_selected).map_or(order[0], |position| order[(position + 1) % order.len()])
+        };
+        self.selection = BTreeSet::from([next]);
+    }
+
+    /// Remove the clip at `index`, keeping the rest of the selection intact.
+    pub fn remove_clip(&mut self, index: usize) {
+        self.clips.remove(index);
+        self.selection = self
+            .selection
+            .iter()
+            .filter(|selected| **selected != index)
+            .map(|selected| if *selected > index { selected - 1 } else { *selected })
+            .collect();
+    }
+
+    /// Return the files of the audio clips, once for each clip, and of the pads of drum racks.
+    pub fn files(&self) -> Vec<&PathBuf> {
+        self.clips
+            .iter()
+            .filter_map(|clip| match &clip.data {
+                ClipData::Audio { path, .. } => Some(path),
+                ClipData::Midi { .. } => None,
+            })
+            .chain(self.instruments.values().flat_map(Instrument::files))
+            .collect()
+    }
+
+    /// Return the synth notes played live are heard through, which is that of the first armed track played by a synth, or the default one.
+    pub fn live_synth(&self) -> Synth {
+        self.inputs
+            .iter()
+            .filter(|(_, settings)| settings.armed)
+            .find_map(|(track, _)| match self.instruments.get(track) {
+                Some(Instrument::Synth { values }) => Some(Synth::new(values)),
+                Some(Instrument::DrumRack { .. }) => None,
+                None => Some(Synth::default()),
+            })
+            .unwrap_or_default()
+    }
+
+    /// Return the files of the instrument of `track`, which have to be given to [`Playlist::render_track`].
+    pub fn instrument_files(&self, track: u32) -> Vec<&PathBuf> {
+        self.instruments.get(&track).map(Instrument::files).unwrap_or_default()
+    }
+
+    /// Like [`Playlist::files`], but mutable.
+    pub fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.clips
+            .iter_mut()
+            .filter_map(|clip| match &mut clip.data {
+                ClipData::Audio { path, .. } => Some(path),
+                ClipData::Midi { .. } => None,
+            })
+            .chain(self.instruments.values_mut().flat_map(Instrument::files_mut))
+            .collect()
+    }
+
+    /// Read the audio of the clips that don't have it yet, like those of a playlist that was just loaded, and return the files that couldn't be read. Their clips stay
+    /// silent until the files are relinked.
+    pub fn read_audio(&mut self) -> Vec<PathBuf> {
+        let mut unreadable = Vec::new();
+        for clip in &mut self.clips {
+            let ClipData::Audio { path, samples, segment, length, .. } = &clip.data else {
+                continue;
+            };
+            if !samples.is_empty() {
+                continue;
+            }
+            // Clips long enough to be streamed don't keep their samples, so their files only have to be there.
+            if *length >= STREAMED_LENGTH {
+                if engine::probe(path).is_err() {
+                    unreadable.push(path.clone());
+                }
+                continue;
+            }
+            match ClipData::read(path.clone(), segment.clone()) {
+                Some(data) => clip.data = data,
+                None => unreadable.push(path.clone()),
+            }
+        }
+        unreadable.sort();
+        unreadable.dedup();
+        unreadable
+    }
+
+    /// Add a clip for each file in `paths` at `start`, one after the other on `track` if `sequential` or each on its own track from `track` up otherwise.
+    pub fn add_files(&mut self, start: Time, track: u32, paths: &[PathBuf], sequential: bool) {
+        let mut start = start;
+        let mut track = track;
+        for path in paths {
+            let clip = if midi::is_midi(path) {
+                match midi::read(path) {
+                    Ok(file) => Clip {
+                        name: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                        ..Clip::new(start, track, ClipData::from_midi(&file))
+                    },
+                    Err(error) => {
+                        error!("Couldn't read the MIDI file {}: {error}", path.display());
+                        continue;
+                    }
+                }
+            } else {
+                let Some(data) = ClipData::read(path.clone(), None) else {
+                    continue;
+                };
+                Clip::new(start, track, data)
+            };
+            if sequential {
+                let beats = self.duration_of_clip(&clip).as_secs_f64() * self.tempo.bps();
+                start = Time::from_beats(start.beats() + beats).unwrap_or(start);
+            } else {
+                track += 1;
+            }
+            self.clips.push(clip);
+        }
+    }
+
+    /// Add a MIDI clip of each track of `file` at the start of the playlist, each on its own track after the last one used, and play at the tempo the file starts
+    /// at, keeping its later tempo changes. Clips are named after their track, or `name` if it has none. Returns how many clips were added.
+    pub fn import_midi(&mut self, file: &MidiFile, name: &str) -> usize {
+        self.tempo = Tempo::from_bpm(file.tempo);
+        self.tempo_changes = file.tempos.iter().filter(|change| change.start > 0.).map(|change| TempoMarker { beat: change.start, bpm: change.bpm }).collect();
+        let first = self.clips.iter().map(|clip| clip.track + 1).max().unwrap_or_default();
+        let length = Time::from_beats(file.length).unwrap_or_default();
+        let count = self.clips.len();
+        for (track, midi_track) in (first..=u32::MAX).zip(&file.tracks) {
+            let data = ClipData::Midi {
+                notes: midi_track.notes.clone().into(),
+                length,
+                expression: midi_track.expression.clone().into(),
+            };
+            self.clips.push(Clip {
+                name: midi_track.name.clone().unwrap_or_else(|| name.into()),
+                ..Clip::new(Time::default(), track, data)
+            });
+        }
+        self.clips.len() - count
+    }
+
+    /// Return a short project to try things with, which is four bars of chords and a bass line played by the built-in synth.
+    pub fn demo() -> Self {
+        // The roots of A minor, F, C and G, a bar each.
+        const CHORDS: [(u8, bool); 4] = [(57, true), (53, false), (60, false), (55, false)];
+        let note = |key, start, length| Note { key, velocity: 96, start, length, channel: 0 };
+        let chords = (0_u32..)
+            .zip(CHORDS)
+            .flat_map(|(bar, (root, minor))| [0, if minor { 3 } else { 4 }, 7].map(|interval| note(root + interval, f64::from(bar) * 4., 4.)))
+            .collect_vec();
+        let bass = (0_u32..)
+            .zip(CHORDS)
+            .flat_map(|(bar, (root, _))| (0_u32..8).map(move |eighth| note(root - 24, f64::from(bar).mul_add(4., f64::from(eighth) * 0.5), 0.5)))
+            .collect_vec();
+        let track = |name: &str, notes: Vec<Note>| Track { name: Some(name.into()), notes, expression: Vec::new() };
+        let file = MidiFile {
+            notes: chords.iter().chain(&bass).copied().sorted_by(|a, b| a.start.total_cmp(&b.start)).collect(),
+            expression: Vec::new(),
+            tempo: 110.,
+            length: 16.,
+            tracks: vec![track("Chords", chords), track("Bass", bass)],
+            tempos: Vec::new(),
+        };
+        let mut playlist = Self::default();
+        playlist.import_midi(&file, "Demo");
+        playlist
+    }
+
+    /// Return the MIDI clips as a MIDI file at the tempo of the playlist and its tempo changes, with a track for each track of the playlist that has any. Notes and expression are
+    /// cut short at the end of their clip.
+    pub fn export_midi(&self) -> MidiFile {
+        let mut tracks = BTreeMap::<u32, (Vec<Note>, Vec<Expression>)>::new();
+        for clip in &self.clips {
+            let ClipData::Midi { notes, length, expression } = &clip.data else {
+                continue;
+            };
+            let (start, length) = (clip.start.beats(), length.beats());
+            let notes = notes.iter().filter(|note| note.start < length).map(|note| Note {
+                start: start + note.start,
+                length: note.length.min(length - note.start),
+                ..*note
+            });
+            let expression = expression.iter().filter(|change| change.start < length).map(|change| Expression { start: start + change.start, ..*change });
+            let (track_notes, track_expression) = tracks.entry(clip.track).or_default();
+            track_notes.extend(notes);
+            track_expression.extend(expression);
+        }
+        let by_start = |a: &Note, b: &Note| a.start.total_cmp(&b.start).then(a.key.cmp(&b.key));
+        let tracks = tracks
+            .into_iter()
+            .map(|(track, (mut notes, mut expression))| {
+                notes.sort_by(by_start);
+                expression.sort_by(|a, b| a.start.total_cmp(&b.start));
+                Track { name: Some(format!("Track {}", track + 1)), notes, expression }
+            })
+            .collect_vec();
+        let notes = tracks.iter().flat_map(|track| track.notes.iter().copied()).sorted_by(by_start).collect_vec();
+        let expression = tracks.iter().flat_map(|track| track.expression.iter().copied()).sorted_by(|a, b| a.start.total_cmp(&b.start)).collect_vec();
+        MidiFile {
+            length: notes.iter().map(|note| note.start + note.length).fold(0., f64::max),
+            notes,
+            expression,
+            tempo: self.tempo.bpm(),
+            tracks,
+            tempos: iter::once(TempoChange { start: 0., bpm: self.tempo.bpm() })
+                .chain(self.tempo_changes.iter().map(|change| TempoChange { start: change.beat, bpm: change.bpm }))
+                .collect(),
+        }
+    }
+
+    /// Return the first track with no clip playing at `time`.
+    pub fn free_track(&self, time: Time) -> u32 {
+        let busy = self
+            .clips
+            .iter()
+            .filter(|clip| {
+                let beats = self.duration_of_clip(clip).as_secs_f64() * self.tempo.bps();
+                (clip.start.beats()..clip.start.beats() + beats).contains(&time.beats())
+            })
+            .map(|clip| clip.track)
+            .collect::<BTreeSet<_>>();
+        (0..=u32::MAX).find(|track| !busy.contains(track)).unwrap_or_default()
+    }
+
+    /// Add a copy of the clip at `index` right after it on the same track.
+    pub fn duplicate_clip(&mut self, index: usize) {
+        let clip = &self.clips[index];
+        let beats = self.duration_of_clip(clip).as_secs_f64() * self.tempo.bps();
+        let mut duplicate = clip.clone();
+        duplicate.id = Clip::next_id();
+        duplicate.start = Time::from_beats(clip.start.beats() + beats).unwrap_or(clip.start);
+        self.clips.push(duplicate);
+    }
+
+    /// Split the clip at `index` in two at `at`. Returns `false` if `at` is not inside the clip.
+    pub fn split_clip(&mut self, index: usize, at: Time) -> bool {
+        let clip = &self.clips[index];
+        let offset = at.beats() - clip.start.beats();
+        let duration = self.duration_of_clip(clip);
+        let offset_duration = self.beats_to_duration(offset.max(0.));
+        if offset <= 0. || offset_duration >= duration {
+            return false;
+        }
+        let (before, after) = match &clip.data {
+            ClipData::Audio { length, .. } => {
+                // The position in the source audio, which is stretched and may be played backwards.
+                let source_offset = length.mul_f64(offset_duration.as_secs_f64() / duration.as_secs_f64());
+                if clip.processing.reversed {
+                    let Some((before, after)) = clip.data.split_audio(length.saturating_sub(source_offset)) else {
+                        return false;
+                    };
+                    (after, before)
+                } else {
+                    let Some(parts) = clip.data.split_audio(source_offset) else {
+                        return false;
+                    };
+                    parts
+                }
+            }
+            ClipData::Midi { notes, length, expression } => {
+                let (Some(before), Some(after)) = (Time::from_beats(offset), Time::from_beats(length.beats() - offset)) else {
+                    return false;
+                };
+                // Notes held over the split are cut short rather than started again in the second clip.
+                let (first, second): (Vec<_>, Vec<_>) = notes.iter().partition(|note| note.start < offset);
+                let first = first.into_iter().map(|note: &Note| Note { length: note.length.min(offset - note.start), ..*note }).collect();
+                let second = second.into_iter().map(|note| Note { start: note.start - offset, ..*note }).collect();
+                // The second clip starts from the expression each channel had at the split.
+                let (first_expression, second_expression): (Vec<&Expression>, Vec<_>) = expression.iter().partition(|change| change.start < offset);
+                let held = first_expression.iter().rev().unique_by(|change| (change.channel, change.dimension)).map(|change| Expression { start: 0., ..**change });
+                let second_expression = held.collect_vec().into_iter().rev().chain(second_expression.into_iter().map(|change| Expression { start: change.start - offset, ..*change }));
+                (
+                    ClipData::Midi {
+                        notes: first,
+                        length: before,
+                        expression: first_expression.into_iter().copied().collect(),
+                    },
+                    ClipData::Midi {
+                        notes: second,
+                        length: after,
+                        expression: second_expression.collect(),
+                    },
+                )
+            }
+        };
+        let mut second = clip.clone();
+        second.id = Clip::next_id();
+        second.start = at;
+        second.data = after;
+        second.processing.fade_in = 0.;
+        let first = &mut self.clips[index];
+        first.data = before;
+        first.processing.fade_out = 0.;
+        self.clips.push(second);
+        true
+    }
+
+    /// Return the audio clip at `index` as it sounds in the project now, to slice at its hits in the background.
+    pub fn slicer(&self, index: usize) -> Slicer {
+        let clip = &self.clips[index];
+        Slicer { clip: clip.clone(), tempo: self.tempo, duration: self.duration_of_clip(clip) }
+    }
+
+    /// Slice the clip that was `sliced` in the background, if it's still there. Returns a description of the edit, or [`None`] if nothing changed.
+    pub fn apply_slices(&mut self, sliced: Sliced) -> Option<&'static str> {
+        match sliced {
+            Sliced::Clips { clip, transients } => {
+                let index = self.clips.iter().position(|other| other.id == clip)?;
+                let start = self.clips[index].start.beats();
+                // Splitting from the end keeps the clip at `index` as the part before every split.
+                let splits = transients.iter().rev().filter(|beats| Time::from_beats(start + **beats).is_some_and(|at| self.split_clip(index, at))).count();
+                (splits > 0).then_some("Slice clip")
+            }
+            Sliced::DrumRack { clip, pads, starts, end } => {
+                let clip = self.clips.iter().find(|other| other.id == clip)?;
+                let ends = starts.iter().skip(1).copied().chain([end]);
+                let notes = starts
+                    .iter()
+                    .zip(ends)
+                    .zip(drum_rack::FIRST_KEY..)
+                    .map(|((start, end), key)| Note { key, velocity: 100, start: *start, length: end - start, channel: 0 })
+                    .collect();
+                let track = self.clips.iter().map(|clip| clip.track + 1).max().unwrap_or_default();
+                let groove = Clip {
+                    name: format!("{} groove", clip.name),
+                    color: clip.color,
+                    ..Clip::new(
+                        clip.start,
+                        track,
+                        ClipData::Midi {
+                            notes,
+                            length: Time::from_beats(end).unwrap_or_default(),
+                            expression: Arc::new([]),
+                        },
+                    )
+                };
+                self.clips.push(groove);
+                self.instruments.insert(track, Instrument::DrumRack { pads });
+                Some("Slice clip onto a drum rack")
+            }
+        }
+    }
+
+    /// Return when the last clip ends.
+    pub fn end(&self) -> Duration {
+        self.clips.iter().map(|clip| self.beats_to_duration(clip.start.beats()) + self.duration_of_clip(clip)).max().unwrap_or_default()
+    }
+
+    pub fn duration_of_clip(&self, clip: &Clip) -> Duration {
+        match (&clip.data, clip.processing.stretch) {
+            (ClipData::Audio { length, .. }, Stretch::Off) => *length,
+            (ClipData::Audio { length, .. }, Stretch::Tempo { source_bpm }) => length.mul_f64(source_bpm / self.tempo.bpm()),
+            (ClipData::Midi { length, .. }, _) => self.beats_to_duration(length.beats()),
+        }
+    }
+
+    /// Return how many beats a clip of a file `length` long lasts.
+    pub fn beats_of(&self, length: FileLength) -> f64 {
+        match length {
+            FileLength::Audio(duration) => duration.as_secs_f64() * self.tempo.bps(),
+            FileLength::Midi(beats) => beats,
+        }
+    }
+}
+
+/// How long a file lasts once it's added as a clip, to show where it goes before it's read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileLength {
+    Audio(Duration),
+    /// The length of a MIDI file in beats, which it keeps whatever the tempo.
+    Midi(f64),
+}
+
+impl FileLength {
+    /// Find out how long the file at `path` is, which may be in an archive, decoding it if its format doesn't say.
+    pub fn read(path: &Path) -> Option<Self> {
+        if midi::is_midi(path) {
+            return midi::read(path).ok().map(|file| Self::Midi(file.length));
+        }
+        let decoder = archive::open(path).map_err(|error| error.to_string()).and_then(|file| Decoder::new(file).map_err(|error| error.to_string()));
+        let decoder = decoder.inspect_err(|error| error!("Couldn't read {}: {error}", path.display())).ok()?;
+        if let Some(duration) = decoder.total_duration() {
+            return Some(Self::Audio(duration));
+        }
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        #[allow(clippy::cast_precision_loss, reason = "sample counts are well within range")]
+        let seconds = decoder.count() as f64 / f64::from(channels.max(1)) / f64::from(sample_rate.max(1));
+        Some(Self::Audio(Duration::from_secs_f64(seconds)))
+    }
+}
+
+/// What an audio clip is sliced into at its hits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceInto {
+    /// Split the clip at every hit.
+    Clips,
+    /// Put the slices on the pads of a drum rack on a new track, with a MIDI clip playing them in the groove of the original.
+    DrumRack,
+}
+
+/// An audio clip sliced at its hits in the background, to apply with [`Playlist::apply_slices`].
+#[derive(Debug)]
+pub enum Sliced {
+    /// Where to split the clip with the id, in beats from its start.
+    Clips { clip: u64, transients: Vec<f64> },
+    /// The pads playing the slices of the clip with the id, which were written as files, where the slices start in beats from its start and where
+    /// the clip ends.
+    DrumRack { clip: u64, pads: Vec<PadData>, starts: Vec<f64>, end: f64 },
+}
+
+#[derive(Debug)]
+pub enum SliceError {
+    NoAudio,
+    /// The clip has more slices than a drum rack has pads.
+    TooMany(usize),
+    Write,
+}
+
+impl std::fmt::Display for SliceError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAudio => write!(formatter, "it has no audio"),
+            Self::TooMany(count) => write!(formatter, "it has {count} slices but a drum rack has {} pads, lower the sensitivity", drum_rack::PADS),
+            Self::Write => write!(formatter, "the slices couldn't be written"),
+        }
+    }
+}
+
+/// An audio clip as it sounded in the project when it was asked to be sliced, see [`Playlist::slicer`].
+pub struct Slicer {
+    clip: Clip,
+    tempo: Tempo,
+    /// How long the clip lasts in the project.
+    duration: Duration,
+}
+
+impl Slicer {
+    /// Slice the clip `into` clips or a drum rack at its hits, see [`analysis::onsets`] for `sensitivity`. The slices of a drum rack are written as WAV
+    /// files into `directory`, and there can't be more of them than it has pads.
+    pub fn slice(&self, into: SliceInto, sensitivity: f64, directory: &Path) -> Result<Sliced, SliceError> {
+        let (ClipData::Audio { channels, sample_rate, .. }, Some(samples)) = (&self.clip.data, self.clip.render(self.tempo)) else {
+            return Err(SliceError::NoAudio);
+        };
+        let (channels, sample_rate) = (*channels, *sample_rate);
+        let bps = self.tempo.bps();
+        let end = self.duration.as_secs_f64() * bps;
+        // Hits right at either end would make slices too short to hear.
+        let margin = bps * 0.01;
+        let transients = analysis::onsets(&samples, usize::from(channels), f64::from(sample_rate), sensitivity)
+            .into_iter()
+            .map(|seconds| seconds * bps)
+            .filter(|beats| (margin..end - margin).contains(beats))
+            .collect_vec();
+        if into == SliceInto::Clips {
+            return Ok(Sliced::Clips { clip: self.clip.id, transients });
+        }
+        let starts = iter::once(0.).chain(transients).collect_vec();
+        if starts.len() > drum_rack::PADS {
+            return Err(SliceError::TooMany(starts.len()));
+        }
+        let frame = |beats: f64| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "times are positive and well within range")]
+            let frame = (beats / bps * f64::from(sample_rate)) as usize;
+            (frame * usize::from(channels.max(1))).min(samples.len())
+        };
+        let name = self.clip.name.replace(['/', '\\'], "_");
+        let mut pads = vec![PadData { path: None, values: drum_rack::defaults() }; drum_rack::PADS];
+        let ends = starts.iter().skip(1).copied().chain([end]);
+        for (number, ((start, end), pad)) in starts.iter().zip(ends).zip(&mut pads).enumerate() {
+            let path = crate::project::free_path(directory, Path::new(&format!("{name} slice {}.wav", number + 1)));
+            write_wave(&samples[frame(*start)..frame(end)], channels, sample_rate, &path).ok_or(SliceError::Write)?;
+            pad.path = Some(path);
+        }
+        Ok(Sliced::DrumRack { clip: self.clip.id, pads, starts, end })
+    }
+}
+
+/// Saves a color as a hex string like `#808080ff`.
+mod color {
+    use egui::Color32;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(color: &Color32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&color.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color32, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Color32::from_hex(&hex).map_err(|_| D::Error::custom(format!("\"{hex}\" is not a color")))
+    }
+}
+
+/// Saves a normalization target as `{ Peak = -1.0 }` or `{ Loudness = -14.0 }`.
+mod normalize_target {
+    use blerp::processing::effects::normalize::NormalizeTarget;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    enum Target {
+        Peak(f64),
+        Loudness(f64),
+    }
+
+    #[allow(clippy::ref_option, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(target: &Option<NormalizeTarget>, serializer: S) -> Result<S::Ok, S::Error> {
+        target
+            .map(|target| match target {
+                NormalizeTarget::Peak(decibels) => Target::Peak(decibels),
+                NormalizeTarget::Loudness(lufs) => Target::Loudness(lufs),
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NormalizeTarget>, D::Error> {
+        Ok(Option::<Target>::deserialize(deserializer)?.map(|target| match target {
+            Target::Peak(decibels) => NormalizeTarget::Peak(decibels),
+            Target::Loudness(lufs) => NormalizeTarget::Loudness(lufs),
+        }))
+    }
+}
+
+/// Saves a duration in seconds.
+mod seconds {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_secs_f64(f64::deserialize(deserializer)?.max(0.)))
+    }
+}
+
+/// Saves the part of a file a clip plays as `[start, end]` in seconds.
+mod segment {
+    use std::{ops::Range, time::Duration};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[allow(clippy::ref_option, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(segment: &Option<Range<Duration>>, serializer: S) -> Result<S::Ok, S::Error> {
+        segment.as_ref().map(|segment| [segment.start.as_secs_f64(), segment.end.as_secs_f64()]).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Range<Duration>>, D::Error> {
+        Ok(Option::<[f64; 2]>::deserialize(deserializer)?.map(|[start, end]| Duration::from_secs_f64(start.max(0.))..Duration::from_secs_f64(end.max(start).max(0.))))
+    }
+}
+
+/// Saves the instrument of each track like [`crate::project::track_keys`] does. Projects saved before there were drum racks kept only the values of
+/// the settings of the synth of each track, under `synths`, which are read as synths.
+mod instruments {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Instrument;
+    use crate::project::track_keys;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Instrument(Instrument),
+        Synth(Vec<f64>),
+    }
+
+    pub fn serialize<S: Serializer>(instruments: &BTreeMap<u32, Instrument>, serializer: S) -> Result<S::Ok, S::Error> {
+        track_keys::serialize(instruments, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<u32, Instrument>, D::Error> {
+        let saved: BTreeMap<u32, Saved> = track_keys::deserialize(deserializer)?;
+        Ok(saved
+            .into_iter()
+            .map(|(track, saved)| match saved {
+                Saved::Instrument(instrument) => (track, instrument),
+                Saved::Synth(values) => (track, Instrument::Synth { values }),
+            })
+            .collect())
+    }
+}
+
+/// Saves MIDI notes as a list of tables.
+mod notes {
+    use std::sync::Arc;
+
+    use blerp::midi::Note;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedNote {
+        key: u8,
+        velocity: u8,
+        start: f64,
+        length: f64,
+        #[serde(default)]
+        channel: u8,
+    }
+
+    pub fn serialize<S: Serializer>(notes: &Arc<[Note]>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(notes.iter().map(|note| SavedNote {
+            key: note.key,
+            velocity: note.velocity,
+            start: note.start,
+            length: note.length,
+            channel: note.channel,
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<[Note]>, D::Error> {
+        let notes = Vec::<SavedNote>::deserialize(deserializer)?;
+        Ok(notes
+            .into_iter()
+            .map(|SavedNote { key, velocity, start, length, channel }| Note { key, velocity, start, length, channel })
+            .collect())
+    }
+}
+
+/// Saves the expression of MIDI notes as a list of tables.
+mod expression {
+    use std::sync::Arc;
+
+    use blerp::midi::{Dimension, Expression};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum SavedDimension {
+        PitchBend,
+        Pressure,
+        Slide,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedExpression {
+        channel: u8,
+        start: f64,
+        dimension: SavedDimension,
+        value: f64,
+    }
+
+    pub fn serialize<S: Serializer>(expression: &Arc<[Expression]>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(expression.iter().map(|change| SavedExpression {
+            channel: change.channel,
+            start: change.start,
+            dimension: match change.dimension {
+                Dimension::PitchBend => SavedDimension::PitchBend,
+                Dimension::Pressure => SavedDimension::Pressure,
+                Dimension::Slide => SavedDimension::Slide,
+            },
+            value: change.value,
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<[Expression]>, D::Error> {
+        let expression = Vec::<SavedExpression>::deserialize(deserializer)?;
+        Ok(expression
+            .into_iter()
+            .map(|SavedExpression { channel, start, dimension, value }| Expression {
+                channel,
+                start,
+                dimension: match dimension {
+                    SavedDimension::PitchBend => Dimension::PitchBend,
+                    SavedDimension::Pressure => Dimension::Pressure,
+                    SavedDimension::Slide => Dimension::Slide,
+                },
+                value,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, sync::Arc, time::Duration};
+
+    use blerp::midi::{MidiFile, Note, TempoChange, Track};
+
+    use super::{Clip, ClipData, Instrument, Playlist, SliceError, SliceInto, Sliced, Time};
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    /// Return a playlist with an audio clip of `count` clicks, one every half second.
+    fn clicks(count: u32) -> Playlist {
+        let mut noise = 1_u32;
+        let samples = (0..SAMPLE_RATE / 2 * count)
+            .map(|index| {
+                noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let time = f64::from(index) / f64::from(SAMPLE_RATE);
+                (f64::from(noise >> 8) / f64::from(1 << 24) - 0.5) * (-(time % 0.5) * 60.).exp()
+            })
+            .collect::<Arc<[f64]>>();
+        let data = ClipData::Audio {
+            path: "clicks.wav".into(),
+            samples,
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            length: Duration::from_millis(500) * count,
+            segment: None,
+        };
+        let mut playlist = Playlist::default();
+        playlist.clips.push(Clip::new(Time::default(), 0, data));
+        playlist
+    }
+
+    #[test]
+    fn clips_are_split_at_every_hit() {
+        let mut playlist = clicks(4);
+        let sliced = playlist.slicer(0).slice(SliceInto::Clips, 0.5, &env::temp_dir()).unwrap();
+        assert_eq!(playlist.apply_slices(sliced), Some("Slice clip"));
+        assert_eq!(playlist.clips.len(), 4);
+    }
+
+    #[test]
+    fn slices_are_put_onto_a_drum_rack() {
+        let directory = env::temp_dir().join("volt slices test");
+        let mut playlist = clicks(4);
+        let sliced = playlist.slicer(0).slice(SliceInto::DrumRack, 0.5, &directory).unwrap();
+        assert_eq!(playlist.apply_slices(sliced), Some("Slice clip onto a drum rack"));
+        let _ = fs::remove_dir_all(&directory);
+        assert!(matches!(&playlist.instruments[&1], Instrument::DrumRack { pads } if pads.iter().filter(|pad| pad.path.is_some()).count() == 4));
+        assert!(matches!(&playlist.clips[1].data, ClipData::Midi { notes, .. } if notes.len() == 4));
+    }
+
+    #[test]
+    fn slicing_onto_a_drum_rack_is_refused_with_more_slices_than_pads() {
+        let directory = env::temp_dir().join("volt refused slices test");
+        let sliced = clicks(20).slicer(0).slice(SliceInto::DrumRack, 0.5, &directory);
+        assert!(matches!(sliced, Err(SliceError::TooMany(20))));
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn slices_of_a_removed_clip_are_dropped() {
+        let mut playlist = clicks(4);
+        let sliced = playlist.slicer(0).slice(SliceInto::Clips, 0.5, &env::temp_dir()).unwrap();
+        playlist.clips.clear();
+        assert!(matches!(sliced, Sliced::Clips { .. }));
+        assert_eq!(playlist.apply_slices(sliced), None);
+    }
+
+    #[test]
+    fn synths_saved_before_there_were_drum_racks_are_read() {
+        let playlist: Playlist = toml::from_str("[synths]\n2 = [0.25, 0.5]\n").unwrap();
+        assert!(matches!(&playlist.instruments[&2], Instrument::Synth { values } if *values == [0.25, 0.5]));
+    }
+
+    #[test]
+    fn instruments_are_read_back() {
+        let mut playlist = Playlist::default();
+        playlist.instruments.insert(1, Instrument::drum_rack());
+        let read: Playlist = toml::from_str(&toml::to_string(&playlist).unwrap()).unwrap();
+        assert!(matches!(&read.instruments[&1], Instrument::DrumRack { pads } if pads.len() == 16));
+    }
+
+    #[test]
+    fn tempo_changes_are_kept_from_imported_midi_files_to_exported_ones() {
+        let note = Note { key: 60, velocity: 100, start: 0., length: 1., channel: 0 };
+        let tempos = vec![TempoChange { start: 0., bpm: 100. }, TempoChange { start: 4., bpm: 140. }];
+        let track = Track { name: None, notes: vec![note], expression: Vec::new() };
+        let file = MidiFile { notes: vec![note], expression: Vec::new(), tempo: 100., length: 8., tracks: vec![track], tempos: tempos.clone() };
+        let mut playlist = Playlist::default();
+        playlist.import_midi(&file, "Imported");
+        let read: Playlist = toml::from_str(&toml::to_string(&playlist).unwrap()).unwrap();
+        assert_eq!(read.export_midi().tempos, tempos);
+    }
+}
@@ -0,0 +1,165 @@
+//! A window showing the pitch of what's played into the capture device or on a track, and how far it is from the nearest note, for tuning an
+//! instrument before recording it.
+
+use std::{
+    ops::RangeInclusive,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use blerp::processing::{graph::Tap, pitch};
+use egui::{hex_color, pos2, vec2, Align2, Color32, ComboBox, Context, DragValue, FontId, Rect, RichText, Sense, Stroke, Ui, Window};
+
+use super::note_name;
+
+/// How many of the latest frames the pitch is found in, which is enough for the lowest string of a bass.
+pub const LENGTH: usize = 4096;
+/// How often the pitch is found again.
+const INTERVAL: Duration = Duration::from_millis(50);
+/// How long the last pitch found stays shown once there's no clear pitch, so the needle doesn't drop out as a note fades.
+const HOLD: Duration = Duration::from_millis(600);
+/// How far a new pitch can be from the one shown, in cents, to be smoothed into it rather than jumped to.
+const SMOOTHING_RANGE: f64 = 50.;
+/// How much of the pitch shown is kept when a close one is found, from 0 for none of it.
+const SMOOTHING: f64 = 0.6;
+/// How far off a note can be, in cents, to be taken as in tune.
+const IN_TUNE: f64 = 3.;
+/// The range of pitches A4 can be tuned to, in Hz.
+const REFERENCES: RangeInclusive<f64> = 415.0..=466.0;
+const BACKGROUND: Color32 = Color32::from_black_alpha(0x60);
+const GRID: Color32 = Color32::from_rgba_premultiplied(0x18, 0x18, 0x18, 0x18);
+
+/// What the tuner listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listen {
+    /// The capture device picked in the browser.
+    Input,
+    /// A track after its insert chain, while the project plays.
+    Track(u32),
+}
+
+pub struct Tuner {
+    listen: Listen,
+    /// What the tap is given the audio of, if the tuner is shown.
+    pub listening: Option<Listen>,
+    pub tap: Option<Arc<Tap>>,
+    /// The pitch of A4 in Hz, which the other notes are tuned from.
+    reference: f64,
+    /// The pitch shown in Hz, if one was found lately, and when it was last found.
+    pitch: Option<(f64, Instant)>,
+    measured_at: Instant,
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Self {
+            listen: Listen::Input,
+            listening: None,
+            tap: None,
+            reference: 440.,
+            pitch: None,
+            measured_at: Instant::now(),
+        }
+    }
+}
+
+impl Tuner {
+    /// Show the tuner in a window while `open` is true, with `tracks` to pick from. Returns whether the project has to be scheduled again for the
+    /// tap to listen to what's picked, or to stop listening once the window is closed.
+    pub fn window(&mut self, ctx: &Context, open: &mut bool, tracks: u32) -> bool {
+        if *open {
+            Window::new("Tuner").open(open).default_size([320., 200.]).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    self.add_source(ui, tracks);
+                    ui.add(DragValue::new(&mut self.reference).range(REFERENCES).speed(0.1).prefix("A4 = ").suffix(" Hz"));
+                });
+                self.measure();
+                self.add_display(ui);
+            });
+        }
+        let wanted = open.then_some(self.listen);
+        let changed = wanted != self.listening;
+        self.listening = wanted;
+        changed
+    }
+
+    fn add_source(&mut self, ui: &mut Ui, tracks: u32) {
+        let name = |listen: Listen| match listen {
+            Listen::Input => "Input".to_string(),
+            Listen::Track(track) => format!("Track {}", track + 1),
+        };
+        ComboBox::from_id_salt(ui.id().with("tuner source")).selected_text(name(self.listen)).show_ui(ui, |ui| {
+            for listen in std::iter::once(Listen::Input).chain((0..tracks).map(Listen::Track)) {
+                ui.selectable_value(&mut self.listen, listen, name(listen));
+            }
+        });
+    }
+
+    /// Find the pitch of the latest audio of the tap, smoothing it into the pitch shown if it's close.
+    fn measure(&mut self) {
+        if self.measured_at.elapsed() < INTERVAL {
+            return;
+        }
+        self.measured_at = Instant::now();
+        let Some(tap) = &self.tap else {
+            self.pitch = None;
+            return;
+        };
+        let (samples, channels) = (tap.samples(), tap.channels());
+        #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+        let mono = samples[samples.len().saturating_sub(LENGTH * channels)..]
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f64>() / channels as f64)
+            .collect::<Vec<_>>();
+        match (pitch::detect(&mono, tap.sample_rate()), self.pitch) {
+            (Some(found), Some((shown, _))) if (1200. * (found / shown).log2()).abs() < SMOOTHING_RANGE => {
+                // Pitches are smoothed in cents rather than in Hz, so the needle moves as evenly for low notes as for high ones.
+                self.pitch = Some((SMOOTHING.mul_add(shown.log2() - found.log2(), found.log2()).exp2(), Instant::now()));
+            }
+            (Some(found), _) => self.pitch = Some((found, Instant::now())),
+            (None, Some((_, found_at))) if found_at.elapsed() > HOLD => self.pitch = None,
+            (None, _) => {}
+        }
+    }
+
+    /// Show the nearest note to the pitch, with a needle for how many cents off it the pitch is.
+    fn add_display(&self, ui: &mut Ui) {
+        // The tuner follows the audio, so it has to be redrawn continuously.
+        ui.ctx().request_repaint();
+        let reading = self.pitch.map(|(frequency, _)| (frequency, pitch::nearest_key(frequency, self.reference)));
+        let in_tune = reading.is_some_and(|(_, (_, cents))| cents.abs() <= IN_TUNE);
+        let color = if in_tune { hex_color!("5cff8c") } else { hex_color!("ffd24d") };
+        ui.vertical_centered(|ui| {
+            match reading {
+                Some((frequency, (key, cents))) => {
+                    ui.label(RichText::new(note_name(key)).size(40.).color(color));
+                    ui.label(format!("{frequency:.1} Hz, {cents:+.0} cents"));
+                }
+                None if self.tap.is_none() => {
+                    ui.label(RichText::new("–").size(40.));
+                    ui.weak("There is no audio device");
+                }
+                None => {
+                    ui.label(RichText::new("–").size(40.));
+                    ui.weak("Play a note");
+                }
+            }
+        });
+        let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width().max(200.), 36.), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., BACKGROUND);
+        // The scale goes from 50 cents flat on the left to 50 cents sharp on the right.
+        #[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+        let x = |cents: f64| rect.lerp_inside(vec2(((cents + 50.) / 100.) as f32, 0.)).x;
+        for cents in (-40..=40).step_by(10) {
+            let height = if cents == 0 { rect.height() } else { rect.height() / 3. };
+            painter.vline(x(f64::from(cents)), rect.bottom() - height..=rect.bottom(), Stroke::new(1., GRID));
+        }
+        painter.rect_filled(Rect::from_x_y_ranges(x(-IN_TUNE)..=x(IN_TUNE), rect.y_range()), 0., hex_color!("5cff8c30"));
+        painter.text(pos2(rect.left() + 2., rect.top() + 2.), Align2::LEFT_TOP, "♭", FontId::proportional(12.), Color32::GRAY);
+        painter.text(pos2(rect.right() - 2., rect.top() + 2.), Align2::RIGHT_TOP, "♯", FontId::proportional(12.), Color32::GRAY);
+        if let Some((_, (_, cents))) = reading {
+            painter.vline(x(cents), rect.y_range(), Stroke::new(3., color));
+        }
+    }
+}
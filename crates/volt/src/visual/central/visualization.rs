@@ -0,0 +1,104 @@
+use blerp::processing::{graph::Tap, spectrum::amplitudes};
+use egui::{hex_color, vec2, Color32, Painter, Rect, Sense, Shape, Stroke, Ui, Vec2};
+use itertools::Itertools;
+
+/// How many frames of audio are kept for visualization nodes, which is also the size of the spectrum's FFT.
+pub const LENGTH: usize = 2048;
+/// How many of the latest frames the meter and scope show.
+const RECENT: usize = 512;
+const SIZE: Vec2 = vec2(160., 60.);
+/// The quietest level shown, in dBFS.
+const FLOOR: f64 = -60.;
+const BACKGROUND: Color32 = Color32::from_black_alpha(0x60);
+const LINE: Color32 = Color32::from_rgb(0x8c, 0x8c, 0xff);
+
+/// Return the latest audio of `tap` mixed down to mono, or silence if there is no tap yet, for example because there is no audio output device.
+fn mono(tap: Option<&Tap>) -> Vec<f64> {
+    let Some(tap) = tap else {
+        return vec![0.; LENGTH];
+    };
+    #[allow(clippy::cast_precision_loss, reason = "channel counts are small")]
+    let channels = tap.channels() as f64;
+    tap.samples().chunks_exact(tap.channels()).map(|frame| frame.iter().sum::<f64>() / channels).collect()
+}
+
+fn allocate(ui: &mut Ui, size: Vec2) -> (Rect, Painter) {
+    // Visualizations follow the audio, so they have to be redrawn continuously.
+    ui.ctx().request_repaint();
+    let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2., BACKGROUND);
+    (rect, painter)
+}
+
+/// Return how far `decibels` is between [`FLOOR`] and 0 dBFS.
+#[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+fn level(decibels: f64) -> f32 {
+    ((decibels - FLOOR) / -FLOOR).clamp(0., 1.) as f32
+}
+
+/// Show the peak level of each channel of the latest audio.
+pub fn meter(ui: &mut Ui, tap: Option<&Tap>) {
+    let channels = tap.map_or(1, Tap::channels);
+    let samples = tap.map(Tap::samples).unwrap_or_default();
+    let recent = &samples[samples.len().saturating_sub(RECENT * channels)..];
+    let mut loudest = f64::NEG_INFINITY;
+    for channel in 0..channels {
+        let peak = recent.iter().skip(channel).step_by(channels).fold(0., |peak: f64, sample| peak.max(sample.abs()));
+        let decibels = 20. * peak.log10();
+        loudest = loudest.max(decibels);
+        let (rect, painter) = allocate(ui, vec2(SIZE.x, 6.));
+        let color = if decibels >= 0. {
+            hex_color!("ff5c5c")
+        } else if decibels >= -6. {
+            hex_color!("ffd24d")
+        } else {
+            hex_color!("5cff8c")
+        };
+        painter.rect_filled(Rect::from_min_size(rect.min, vec2(rect.width() * level(decibels), rect.height())), 2., color);
+    }
+    if loudest > FLOOR {
+        ui.weak(format!("{loudest:.1} dBFS"));
+    } else {
+        ui.weak("Silent");
+    }
+}
+
+/// Show the waveform of the latest audio.
+pub fn scope(ui: &mut Ui, tap: Option<&Tap>) {
+    let samples = mono(tap);
+    let recent = &samples[samples.len().saturating_sub(RECENT)..];
+    let (rect, painter) = allocate(ui, SIZE);
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, reason = "only used for drawing")]
+    let points = recent
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| rect.lerp_inside(vec2(index as f32 / RECENT as f32, (1. - sample.clamp(-1., 1.) as f32) / 2.)))
+        .collect_vec();
+    painter.add(Shape::line(points, Stroke::new(1., LINE)));
+}
+
+/// Show the frequencies in the latest audio, from 20 Hz to the highest one the sample rate allows, on a logarithmic scale.
+pub fn spectrum(ui: &mut Ui, tap: Option<&Tap>) {
+    const LOWEST: f64 = 20.;
+    let sample_rate = tap.map_or(48_000., Tap::sample_rate);
+    let samples = mono(tap);
+    #[allow(clippy::cast_precision_loss, reason = "lengths are small")]
+    let length = samples.len() as f64;
+    let (rect, painter) = allocate(ui, SIZE);
+    let nyquist = sample_rate / 2.;
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, reason = "only used for drawing")]
+    let points = amplitudes(&samples)
+        .into_iter()
+        .enumerate()
+        .skip(1)
+        .filter_map(|(index, amplitude)| {
+            let frequency = index as f64 * sample_rate / length;
+            (frequency >= LOWEST).then(|| {
+                let x = (frequency / LOWEST).log(nyquist / LOWEST);
+                rect.lerp_inside(vec2(x as f32, 1. - level(20. * amplitude.log10())))
+            })
+        })
+        .collect_vec();
+    painter.add(Shape::line(points, Stroke::new(1., LINE)));
+}
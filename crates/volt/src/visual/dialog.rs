@@ -1,7 +1,208 @@
-struct Dialog {
-    show: bool
+//! Dialogs: the file dialogs of the system, which are run on another thread so that the app keeps drawing, and the prompt about unsaved changes.
+
+use std::{io::ErrorKind, path::PathBuf, process::Command, thread::spawn};
+
+use crossbeam_channel::{bounded, Receiver};
+use egui::{Context, Id, Key, Modal, RichText};
+use tracing::error;
+
+/// The extension of project files.
+pub const PROJECT_EXTENSION: &str = "volt";
+/// The extension of project archives, which are ZIP archives so that they can be opened anywhere.
+pub const ARCHIVE_EXTENSION: &str = "zip";
+
+/// The environment variable the name suggested by a save dialog is passed in, rather than in the script of the dialog, so that a name can't change what
+/// the script does.
+const NAME_VARIABLE: &str = "VOLT_FILE_NAME";
+
+/// What to do with the changes to a project that weren't saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Save,
+    Discard,
+    Cancel,
 }
 
-impl Dialog {
+/// Show a prompt asking whether to save the changes to the project called `name`, returning the choice once one is made. Escape cancels.
+pub fn unsaved_changes(ctx: &Context, name: &str) -> Option<Choice> {
+    let response = Modal::new(Id::new("unsaved_changes")).show(ctx, |ui| {
+        ui.set_width(280.);
+        ui.label(RichText::new(format!("Save the changes to {name}?")).strong());
+        ui.label("Your changes will be lost if you don't save them.");
+        ui.add_space(8.);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                Some(Choice::Save)
+            } else if ui.button("Don't save").clicked() {
+                Some(Choice::Discard)
+            } else if ui.button("Cancel").clicked() {
+                Some(Choice::Cancel)
+            } else {
+                None
+            }
+        })
+        .inner
+    });
+    response.inner.or_else(|| ctx.input(|input| input.key_pressed(Key::Escape)).then_some(Choice::Cancel))
+}
+
+/// Ask for a folder. The receiver gets the folder picked, or [`None`] if the dialog was cancelled or couldn't be shown.
+pub fn pick_folder() -> Receiver<Option<PathBuf>> {
+    let dialogs = if cfg!(target_os = "macos") {
+        vec![("osascript", args(&["-e", "POSIX path of (choose folder with prompt \"Add a root folder\")"]))]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            args(&[
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName System.Windows.Forms; $dialog = New-Object System.Windows.Forms.FolderBrowserDialog; if ($dialog.ShowDialog() -eq 'OK') { $dialog.SelectedPath }",
+            ]),
+        )]
+    } else {
+        vec![
+            ("zenity", args(&["--file-selection", "--directory", "--title=Add a root folder"])),
+            ("kdialog", args(&["--getexistingdirectory"])),
+        ]
+    };
+    show(dialogs)
+}
 
-}
\ No newline at end of file
+/// Ask for a project file to open. The receiver gets the file picked, or [`None`] if the dialog was cancelled or couldn't be shown.
+pub fn open_project() -> Receiver<Option<PathBuf>> {
+    open_file("Open a project", "Volt projects", &[PROJECT_EXTENSION])
+}
+
+/// Ask where to save a project, suggesting `name` for its file. The receiver gets the file picked, with the project extension added if it was left out, or [`None`] if
+/// the dialog was cancelled or couldn't be shown.
+pub fn save_project(name: &str) -> Receiver<Option<PathBuf>> {
+    save_file("Save the project", "Volt projects", PROJECT_EXTENSION, name)
+}
+
+/// Ask for a project archive to import, like [`open_project`].
+pub fn open_archive() -> Receiver<Option<PathBuf>> {
+    open_file("Import a project archive", "Project archives", &[ARCHIVE_EXTENSION])
+}
+
+/// Ask where to export a project archive, suggesting `name` for it, like [`save_project`].
+pub fn save_archive(name: &str) -> Receiver<Option<PathBuf>> {
+    save_file("Export a project archive", "Project archives", ARCHIVE_EXTENSION, name)
+}
+
+/// Ask for a MIDI file to import, like [`open_project`].
+pub fn open_midi() -> Receiver<Option<PathBuf>> {
+    open_file("Import a MIDI file", "MIDI files", &["mid", "midi"])
+}
+
+/// Ask where to export a MIDI file, suggesting `name` for it, like [`save_project`].
+pub fn save_midi(name: &str) -> Receiver<Option<PathBuf>> {
+    save_file("Export a MIDI file", "MIDI files", "mid", name)
+}
+
+/// Ask where to export the mix of the project, suggesting `name` for it, like [`save_project`].
+pub fn save_mix(name: &str) -> Receiver<Option<PathBuf>> {
+    save_file("Export the mix", "WAV files", "wav", name)
+}
+
+/// Ask where to export a diagnostics bundle, like [`save_project`].
+pub fn save_diagnostics() -> Receiver<Option<PathBuf>> {
+    save_file("Export diagnostics", "Diagnostics bundles", ARCHIVE_EXTENSION, "volt-diagnostics")
+}
+
+/// Ask for a file with one of `extensions`, which are `description`, with `title` as the title or prompt of the dialog.
+fn open_file(title: &str, description: &str, extensions: &[&str]) -> Receiver<Option<PathBuf>> {
+    let dialogs = if cfg!(target_os = "macos") {
+        let types = extensions.iter().map(|extension| format!("\"{extension}\"")).collect::<Vec<_>>().join(", ");
+        vec![("osascript", args(&["-e", &format!("POSIX path of (choose file with prompt \"{title}\" of type {{{types}}})")]))]
+    } else if cfg!(windows) {
+        let patterns = extensions.iter().map(|extension| format!("*.{extension}")).collect::<Vec<_>>().join(";");
+        vec![(
+            "powershell",
+            args(&[
+                "-NoProfile",
+                "-Command",
+                &format!(
+                    "Add-Type -AssemblyName System.Windows.Forms; $dialog = New-Object System.Windows.Forms.OpenFileDialog; $dialog.Filter = '{description} ({patterns})|{patterns}'; if ($dialog.ShowDialog() -eq 'OK') {{ $dialog.FileName }}"
+                ),
+            ]),
+        )]
+    } else {
+        let patterns = extensions.iter().map(|extension| format!("*.{extension}")).collect::<Vec<_>>().join(" ");
+        vec![
+            ("zenity", args(&["--file-selection", &format!("--title={title}"), &format!("--file-filter={description} | {patterns}")])),
+            ("kdialog", args(&["--getopenfilename", ".", &patterns])),
+        ]
+    };
+    show(dialogs)
+}
+
+/// Ask where to save a file with `extension`, which is `description`, suggesting `name` for it and adding the extension if it's left out.
+fn save_file(title: &str, description: &str, extension: &'static str, name: &str) -> Receiver<Option<PathBuf>> {
+    let file_name = format!("{name}.{extension}");
+    let dialogs = if cfg!(target_os = "macos") {
+        vec![("osascript", args(&["-e", &format!("POSIX path of (choose file name with prompt \"{title}\" default name (system attribute \"{NAME_VARIABLE}\"))")]))]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            args(&[
+                "-NoProfile",
+                "-Command",
+                &format!(
+                    "Add-Type -AssemblyName System.Windows.Forms; $dialog = New-Object System.Windows.Forms.SaveFileDialog; $dialog.Filter = '{description} (*.{extension})|*.{extension}'; $dialog.FileName = $env:{NAME_VARIABLE}; if ($dialog.ShowDialog() -eq 'OK') {{ $dialog.FileName }}"
+                ),
+            ]),
+        )]
+    } else {
+        vec![
+            (
+                "zenity",
+                args(&["--file-selection", "--save", "--confirm-overwrite", &format!("--title={title}"), &format!("--filename={file_name}")]),
+            ),
+            ("kdialog", args(&["--getsavefilename", &file_name, &format!("*.{extension}")])),
+        ]
+    };
+    let (tx, rx) = bounded(1);
+    spawn(move || {
+        let path = run(&dialogs, &[(NAME_VARIABLE, &file_name)]).map(|mut path| {
+            if path.extension().is_none() {
+                path.set_extension(extension);
+            }
+            path
+        });
+        let _ = tx.send(path);
+    });
+    rx
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(ToString::to_string).collect()
+}
+
+fn show(dialogs: Vec<(&'static str, Vec<String>)>) -> Receiver<Option<PathBuf>> {
+    let (tx, rx) = bounded(1);
+    spawn(move || {
+        let _ = tx.send(run(&dialogs, &[]));
+    });
+    rx
+}
+
+/// Run the first of `dialogs`, given as programs and their arguments, that's installed with the environment variables `env`, and return the path it
+/// printed.
+fn run(dialogs: &[(&str, Vec<String>)], env: &[(&str, &str)]) -> Option<PathBuf> {
+    for (program, args) in dialogs {
+        match Command::new(program).args(args).envs(env.iter().copied()).output() {
+            // The dialog exits with an error when it's cancelled.
+            Ok(output) => {
+                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                return (output.status.success() && !path.is_empty()).then(|| PathBuf::from(path));
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                error!("Couldn't show the file dialog: {error}");
+                return None;
+            }
+        }
+    }
+    error!("There is no file dialog, install zenity or kdialog to pick files and folders");
+    None
+}
@@ -0,0 +1,111 @@
+//! The font the interface is written in, which is either bundled with the app or one of the fonts installed on the system.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use egui::{FontData, FontDefinitions, FontFamily};
+use itertools::Itertools;
+
+/// The bundled font used for monospace text, which can be chosen for the rest too.
+pub const MONO: &str = "IBM Plex Mono";
+/// The bundled font used for the rest of the text unless another is chosen.
+const PROPORTIONAL: &str = "Inter";
+/// The name the chosen font is given in the font definitions.
+const CHOSEN: &str = "Chosen";
+/// The bytes TrueType and OpenType font files start with.
+const SIGNATURES: [&[u8]; 3] = [&[0, 1, 0, 0], b"OTTO", b"true"];
+
+/// A font file installed on the system.
+#[derive(Debug, Clone)]
+pub struct SystemFont {
+    /// The name of the file, without its extension.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// The fonts installed on the system, sorted by name, which are only looked for once.
+static SYSTEM: LazyLock<Vec<SystemFont>> = LazyLock::new(|| {
+    let mut fonts = Vec::new();
+    for folder in folders() {
+        find(&folder, &mut fonts);
+    }
+    fonts.into_iter().sorted_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase())).dedup_by(|a, b| a.name == b.name).collect()
+});
+
+/// Return the folders the system keeps fonts in.
+fn folders() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    if cfg!(windows) {
+        let windows = env::var_os("WINDIR").map_or_else(|| PathBuf::from("C:\\Windows"), PathBuf::from);
+        let user = env::var_os("LOCALAPPDATA").map(|folder| PathBuf::from(folder).join("Microsoft\\Windows\\Fonts"));
+        [Some(windows.join("Fonts")), user].into_iter().flatten().collect()
+    } else if cfg!(target_os = "macos") {
+        [Some(PathBuf::from("/System/Library/Fonts")), Some(PathBuf::from("/Library/Fonts")), home.map(|home| home.join("Library/Fonts"))].into_iter().flatten().collect()
+    } else {
+        let data = env::var_os("XDG_DATA_HOME").map(PathBuf::from).filter(|folder| folder.is_absolute()).or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+        [Some(PathBuf::from("/usr/share/fonts")), Some(PathBuf::from("/usr/local/share/fonts")), data.map(|data| data.join("fonts")), home.map(|home| home.join(".fonts"))]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// Add the fonts in `folder` and its subfolders to `fonts`. Font collections are skipped, as only the first font of them could be used.
+fn find(folder: &Path, fonts: &mut Vec<SystemFont>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            find(&path, fonts);
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ttf") || extension.eq_ignore_ascii_case("otf")) {
+            if let Some(name) = path.file_stem() {
+                fonts.push(SystemFont { name: name.to_string_lossy().into_owned(), path });
+            }
+        }
+    }
+}
+
+/// Return the fonts installed on the system, sorted by name.
+pub fn system() -> &'static [SystemFont] {
+    &SYSTEM
+}
+
+/// Return the fonts bundled with the app: IBM Plex Mono for monospace text and Inter for the rest.
+pub fn bundled() -> FontDefinitions {
+    let mut fonts = FontDefinitions::default();
+    fonts.font_data.insert(MONO.to_string(), FontData::from_static(include_bytes!("../fonts/ibm-plex-mono/IBMPlexMono-Regular.ttf")).into());
+    fonts.families.insert(FontFamily::Monospace, vec![MONO.to_string()]);
+    fonts.font_data.insert(PROPORTIONAL.to_string(), FontData::from_static(include_bytes!("../fonts/inter/Inter.ttf")).into());
+    fonts.families.insert(FontFamily::Proportional, vec![PROPORTIONAL.to_string()]);
+    fonts
+}
+
+/// Return the fonts of the app, with the text that isn't monospace written in the font called `name`, or the default one if there is no name. The
+/// bundled fonts stay after the chosen one, to draw what it has no glyphs for.
+///
+/// # Errors
+///
+/// Returns why the font couldn't be loaded, to be shown to the user.
+pub fn definitions(name: Option<&str>) -> Result<FontDefinitions, String> {
+    let mut fonts = bundled();
+    let chosen = match name {
+        None => return Ok(fonts),
+        Some(MONO) => MONO.to_string(),
+        Some(name) => {
+            let font = system().iter().find(|font| font.name == name).ok_or("it isn't installed")?;
+            let bytes = fs::read(&font.path).map_err(|error| error.to_string())?;
+            // egui panics on fonts it can't read, so files that aren't fonts at all are turned down here.
+            if !SIGNATURES.iter().any(|signature| bytes.starts_with(signature)) {
+                return Err("it isn't a TrueType or OpenType font".into());
+            }
+            fonts.font_data.insert(CHOSEN.to_string(), FontData::from_owned(bytes).into());
+            CHOSEN.to_string()
+        }
+    };
+    fonts.families.entry(FontFamily::Proportional).or_default().insert(0, chosen);
+    Ok(fonts)
+}
@@ -0,0 +1,118 @@
+//! The icons of the app, which come bundled with it unless an icon pack replaces them.
+//!
+//! Icon packs are folders in the `icons` folder of the configuration folder, holding files named after [`Icon::name`], like `play.svg` or
+//! `audio.png`. Icons a pack leaves out are the bundled ones. Monochrome icons are drawn in white, to be tinted with the colors of the theme.
+
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+use egui::{include_image, Color32, Image, ImageSource};
+use itertools::Itertools;
+use tracing::error;
+
+use super::ThemeColors;
+use crate::config;
+
+/// The folder icon packs are kept in, in the configuration folder.
+const FOLDER: &str = "icons";
+/// The extensions icons of packs can have, in the order they're looked for.
+const EXTENSIONS: [&str; 2] = ["svg", "png"];
+
+/// The URIs of the icons replaced by the pack in use.
+static PACK: Mutex<BTreeMap<Icon, String>> = Mutex::new(BTreeMap::new());
+
+/// An icon of the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Icon {
+    Logo,
+    Play,
+    Audio,
+    File,
+    Loading,
+}
+
+impl Icon {
+    pub const ALL: [Self; 5] = [Self::Logo, Self::Play, Self::Audio, Self::File, Self::Loading];
+
+    /// Return the name of the icon's file in icon packs, without its extension.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Logo => "logo",
+            Self::Play => "play",
+            Self::Audio => "audio",
+            Self::File => "file",
+            Self::Loading => "loading",
+        }
+    }
+
+    const fn bundled(self) -> ImageSource<'static> {
+        match self {
+            Self::Logo => include_image!("../images/icons/navbar-icon.svg"),
+            Self::Play => include_image!("../images/icons/play-icon.svg"),
+            Self::Audio => include_image!("../images/icons/audio.png"),
+            Self::File => include_image!("../images/icons/file.png"),
+            Self::Loading => include_image!("../images/icons/loading.png"),
+        }
+    }
+
+    /// Return the color the icon is tinted with in `theme`, which leaves icons in color as they are.
+    const fn tint(self, theme: &ThemeColors) -> Color32 {
+        match self {
+            Self::Logo => theme.icon,
+            Self::Play => theme.play_icon,
+            Self::Audio | Self::File | Self::Loading => Color32::WHITE,
+        }
+    }
+
+    /// Return the icon from the pack in use, or the bundled one, tinted with the colors of `theme`.
+    pub fn image(self, theme: &ThemeColors) -> Image<'static> {
+        let source = PACK.lock().unwrap().get(&self).map_or_else(|| self.bundled(), |uri| ImageSource::Uri(uri.clone().into()));
+        Image::new(source).tint(self.tint(theme))
+    }
+}
+
+/// Return the folder icon packs are kept in, creating it if it doesn't exist yet.
+pub fn folder() -> PathBuf {
+    let folder = config::path(FOLDER);
+    if let Err(error) = fs::create_dir_all(&folder) {
+        error!("Couldn't create the icons folder: {error}");
+    }
+    folder
+}
+
+/// Return the names of the icon packs there are, in alphabetical order.
+pub fn packs() -> Vec<String> {
+    match fs::read_dir(folder()) {
+        Ok(entries) => entries.flatten().filter(|entry| entry.path().is_dir()).map(|entry| entry.file_name().to_string_lossy().into_owned()).sorted().collect(),
+        Err(error) => {
+            error!("Couldn't read the icons folder: {error}");
+            Vec::new()
+        }
+    }
+}
+
+/// Use the icons of the pack called `name`, or only the bundled icons if there is no name, returning how many icons the pack replaces.
+///
+/// # Errors
+///
+/// Returns why the pack can't be used, to be shown to the user.
+pub fn set_pack(name: Option<&str>) -> Result<usize, String> {
+    let icons = match name {
+        Some(name) => {
+            let pack = folder().join(name);
+            if !pack.is_dir() {
+                return Err("it doesn't exist".into());
+            }
+            Icon::ALL
+                .into_iter()
+                .filter_map(|icon| {
+                    let path = EXTENSIONS.iter().map(|extension| pack.join(format!("{}.{extension}", icon.name()))).find(|path| path.is_file())?;
+                    Some((icon, format!("file://{}", path.display())))
+                })
+                .collect()
+        }
+        None => BTreeMap::new(),
+    };
+    let count = icons.len();
+    *PACK.lock().unwrap() = icons;
+    Ok(count)
+}
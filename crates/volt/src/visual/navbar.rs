@@ -1,9 +1,213 @@
+use std::time::Duration;
+
 use eframe::egui;
-use egui::{include_image, Color32, Image, RichText, TextureOptions, Ui, Vec2, Widget};
+use egui::{Button, Color32, ComboBox, DragValue, Label, Response, RichText, TextureOptions, Ui, Vec2, Widget};
+
+use super::{central::TimeSignature, font, icon::{self, Icon}, theme, ThemeColors};
+use crate::{
+    config::{self, Accessibility, Ballistics, Config, Theme},
+    keymap::{self, Action},
+};
+
+/// The range of tempos that can be set, in BPM.
+const BPM_RANGE: std::ops::RangeInclusive<f64> = 1.0..=999.;
+
+/// Whether the project plays, its tempo and where playback is, shown and edited in the navbar.
+#[derive(Debug, Clone, Copy)]
+pub struct Transport {
+    pub playing: bool,
+    pub bpm: f64,
+    pub time_signature: TimeSignature,
+    /// Where playback is, as a bar and beat counted from 1, and in time.
+    pub position: ((u32, u32), Duration),
+}
+
+/// Menu entries that need to be handled by the app itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuAction {
+    New,
+    Open,
+    Save,
+    SaveAs,
+    CollectAndSave,
+    ExportArchive,
+    ImportArchive,
+    ImportMidi,
+    ExportMidi,
+    ExportMix,
+    Undo,
+    Redo,
+    ShowHistory,
+    ShowSpectrum,
+    ShowScope,
+    ShowTuner,
+    ShowLoudness,
+    ShowAbout,
+    RelinkFiles,
+    EditShortcuts,
+    SetTheme(Theme),
+    /// Take the colors from the theme file with this name, or use the default colors.
+    SetColors(Option<String>),
+    SetAccessibility(Accessibility),
+    SetBallistics(Ballistics),
+    /// Use the icon pack with this name, or only the bundled icons.
+    SetIconPack(Option<String>),
+    OpenIconsFolder,
+    /// Write the interface in the font with this name, or the default font.
+    SetFont(Option<String>),
+    OpenThemesFolder,
+    TogglePlayback,
+    /// Set the tempo of the project in BPM, live while it's still being dragged or typed.
+    SetBpm { bpm: f64, live: bool },
+    SetTimeSignature(TimeSignature),
+}
+
+/// Add a menu entry for `action`, showing its shortcut in the keymap if it has one.
+fn shortcut_button(ui: &mut Ui, text: &str, action: Action) -> Response {
+    ui.add(Button::new(text).shortcut_text(keymap::hint(ui.ctx(), action).unwrap_or_default()))
+}
+
+/// Show the entries of the View menu, where `config` has the colors and font in use.
+fn view_menu(ui: &mut Ui, config: &Config, action: &mut Option<MenuAction>) {
+    ui.menu_button("Theme", |ui| {
+        let current = ui.ctx().options(|options| options.theme_preference);
+        for theme in Theme::ALL {
+            if ui.radio(current == theme.into(), theme.name()).clicked() {
+                *action = Some(MenuAction::SetTheme(theme));
+                ui.close_menu();
+            }
+        }
+    });
+    ui.menu_button("Colors", |ui| colors_menu(ui, config.colors.as_deref(), action));
+    ui.menu_button("Font", |ui| font_menu(ui, config.font.as_deref(), action));
+    ui.menu_button("Icons", |ui| icons_menu(ui, config.icon_pack.as_deref(), action));
+    ui.menu_button("Interface size", |ui| {
+        let mut percent = ui.ctx().zoom_factor() * 100.;
+        let range = config::UI_SCALE_RANGE.start() * 100. ..=config::UI_SCALE_RANGE.end() * 100.;
+        if ui.add(egui::Slider::new(&mut percent, range).step_by(5.).suffix("%")).changed() {
+            ui.ctx().set_zoom_factor(percent / 100.);
+        }
+        if shortcut_button(ui, "Reset", Action::ResetScale).clicked() {
+            ui.ctx().set_zoom_factor(1.);
+            ui.close_menu();
+        }
+    });
+    ui.menu_button("Accessibility", |ui| accessibility_menu(ui, config.accessibility, action));
+    ui.menu_button("Tooltip delay", |ui| {
+        let mut delay = ui.style().interaction.tooltip_delay;
+        if ui.add(egui::Slider::new(&mut delay, config::TOOLTIP_DELAY_RANGE).step_by(0.05).suffix(" s")).on_hover_text("How long to hover something before its tooltip shows").changed() {
+            ui.ctx().all_styles_mut(|style| style.interaction.tooltip_delay = delay);
+        }
+    });
+    ui.menu_button("Meters", |ui| meters_menu(ui, config.meters, action));
+    ui.separator();
+    if shortcut_button(ui, "Spectrum analyzer", Action::ToggleSpectrum).clicked() {
+        *action = Some(MenuAction::ShowSpectrum);
+        ui.close_menu();
+    }
+    if shortcut_button(ui, "Scope and goniometer", Action::ToggleScope).clicked() {
+        *action = Some(MenuAction::ShowScope);
+        ui.close_menu();
+    }
+    if shortcut_button(ui, "Tuner", Action::ToggleTuner).clicked() {
+        *action = Some(MenuAction::ShowTuner);
+        ui.close_menu();
+    }
+    if shortcut_button(ui, "Loudness", Action::ToggleLoudness).clicked() {
+        *action = Some(MenuAction::ShowLoudness);
+        ui.close_menu();
+    }
+    ui.separator();
+    if ui.button("Zoom In").clicked() {}
+    if ui.button("Zoom Out").clicked() {}
+    if ui.button("Fit to Screen").clicked() {}
+}
+
+/// Show the accessibility options, where `current` are the ones in use.
+fn accessibility_menu(ui: &mut Ui, current: Accessibility, action: &mut Option<MenuAction>) {
+    let mut accessibility = current;
+    ui.checkbox(&mut accessibility.high_contrast, "High contrast").on_hover_text("Stronger colors for text and outlines, and a thick outline around the focused widget");
+    ui.checkbox(&mut accessibility.reduced_motion, "Reduce motion").on_hover_text("No spinning, fading, sliding or blinking");
+    ui.add(egui::Slider::new(&mut accessibility.min_text_size, config::MIN_TEXT_SIZE_RANGE).step_by(1.).suffix(" pt").text("Minimum text size"));
+    if accessibility != current {
+        *action = Some(MenuAction::SetAccessibility(accessibility));
+    }
+}
+
+/// Show how the level meters move, where `current` is how they move now.
+fn meters_menu(ui: &mut Ui, current: Ballistics, action: &mut Option<MenuAction>) {
+    let mut ballistics = current;
+    ui.add(egui::Slider::new(&mut ballistics.release, config::RELEASE_RANGE).suffix(" dB/s").text("Release"))
+        .on_hover_text("How fast the meters fall back once the audio gets quieter");
+    ui.add(egui::Slider::new(&mut ballistics.peak_hold, config::PEAK_HOLD_RANGE).step_by(0.1).suffix(" s").text("Peak hold"))
+        .on_hover_text("How long the highest peak stays marked");
+    if ui.button("Reset").clicked() {
+        ballistics = Ballistics::default();
+    }
+    if ballistics != current {
+        *action = Some(MenuAction::SetBallistics(ballistics));
+    }
+}
 
-use super::ThemeColors;
+/// List the theme files to take the colors from, where `colors` is the one in use.
+fn colors_menu(ui: &mut Ui, colors: Option<&str>, action: &mut Option<MenuAction>) {
+    if ui.radio(colors.is_none(), "Default").clicked() {
+        *action = Some(MenuAction::SetColors(None));
+        ui.close_menu();
+    }
+    for name in theme::available() {
+        if ui.radio(colors == Some(name.as_str()), &name).clicked() {
+            *action = Some(MenuAction::SetColors(Some(name)));
+            ui.close_menu();
+        }
+    }
+    ui.separator();
+    if ui.button("Open themes folder").on_hover_text("Themes are TOML files of colors, which are reloaded as they're edited").clicked() {
+        *action = Some(MenuAction::OpenThemesFolder);
+        ui.close_menu();
+    }
+}
 
-pub fn navbar_menu_buttons(ui: &mut Ui) -> egui::Response {
+/// List the icon packs, where `current` is the one in use, if any.
+fn icons_menu(ui: &mut Ui, current: Option<&str>, action: &mut Option<MenuAction>) {
+    if ui.radio(current.is_none(), "Default").clicked() {
+        *action = Some(MenuAction::SetIconPack(None));
+        ui.close_menu();
+    }
+    for name in icon::packs() {
+        if ui.radio(current == Some(name.as_str()), &name).clicked() {
+            *action = Some(MenuAction::SetIconPack(Some(name)));
+            ui.close_menu();
+        }
+    }
+    ui.separator();
+    if ui.button("Open icons folder").on_hover_text("Icon packs are folders of SVG or PNG icons named like play.svg, which replace the icons they have").clicked() {
+        *action = Some(MenuAction::OpenIconsFolder);
+        ui.close_menu();
+    }
+}
+
+/// List the fonts to write the interface in, where `current` is the one in use if it isn't the default one.
+fn font_menu(ui: &mut Ui, current: Option<&str>, action: &mut Option<MenuAction>) {
+    for (name, text) in [(None, "Default"), (Some(font::MONO), font::MONO)] {
+        if ui.radio(current == name, text).clicked() {
+            *action = Some(MenuAction::SetFont(name.map(str::to_string)));
+            ui.close_menu();
+        }
+    }
+    ui.separator();
+    egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+        for system_font in font::system() {
+            if ui.radio(current == Some(system_font.name.as_str()), &system_font.name).clicked() {
+                *action = Some(MenuAction::SetFont(Some(system_font.name.clone())));
+                ui.close_menu();
+            }
+        }
+    });
+}
+
+/// `config` has the colors and font in use, to mark them in the View menu.
+pub fn navbar_menu_buttons(ui: &mut Ui, config: &Config, action: &mut Option<MenuAction>) -> egui::Response {
     egui::Frame::none().show(ui, |ui| {
         ui.scope(|ui| {
             ui.visuals_mut().widgets.inactive.weak_bg_fill = Color32::TRANSPARENT;
@@ -11,40 +215,122 @@ pub fn navbar_menu_buttons(ui: &mut Ui) -> egui::Response {
             ui.visuals_mut().widgets.active.weak_bg_fill = Color32::TRANSPARENT;
             ui.add_space(5.0);
             ui.menu_button("File", |ui| {
-                if ui.button("New").clicked() {}
-                if ui.button("Open").clicked() {}
-                if ui.button("Save").clicked() {}
+                if ui.button("New").clicked() {
+                    *action = Some(MenuAction::New);
+                    ui.close_menu();
+                }
+                if ui.button("Open…").clicked() {
+                    *action = Some(MenuAction::Open);
+                    ui.close_menu();
+                }
+                if ui.button("Save").clicked() {
+                    *action = Some(MenuAction::Save);
+                    ui.close_menu();
+                }
+                if ui.button("Save As…").clicked() {
+                    *action = Some(MenuAction::SaveAs);
+                    ui.close_menu();
+                }
+                if ui.button("Collect and save").on_hover_text("Copy the files used by the project into its folder, then save it").clicked() {
+                    *action = Some(MenuAction::CollectAndSave);
+                    ui.close_menu();
+                }
+                if ui.button("Export project archive…").on_hover_text("Save the project and every file it uses into one compressed file").clicked() {
+                    *action = Some(MenuAction::ExportArchive);
+                    ui.close_menu();
+                }
+                if ui.button("Import project archive…").on_hover_text("Extract a project archive next to it and open the project").clicked() {
+                    *action = Some(MenuAction::ImportArchive);
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Import MIDI file…").on_hover_text("Add the tracks of a MIDI file as clips on new tracks").clicked() {
+                    *action = Some(MenuAction::ImportMidi);
+                    ui.close_menu();
+                }
+                if ui.button("Export MIDI file…").on_hover_text("Save the MIDI clips with a track for each track of the playlist").clicked() {
+                    *action = Some(MenuAction::ExportMidi);
+                    ui.close_menu();
+                }
+                if ui.button("Export mix…").on_hover_text("Render what the master output plays from the start of the playlist to a WAV file").clicked() {
+                    *action = Some(MenuAction::ExportMix);
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Relink missing files").clicked() {
+                    *action = Some(MenuAction::RelinkFiles);
+                    ui.close_menu();
+                }
                 if ui.button("Exit").clicked() {
                     ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
                 }
             });
             ui.add_space(5.0);
             ui.menu_button("Edit", |ui| {
-                if ui.button("Undo").clicked() {}
-                if ui.button("Redo").clicked() {}
+                if shortcut_button(ui, "Undo", Action::Undo).clicked() {
+                    *action = Some(MenuAction::Undo);
+                    ui.close_menu();
+                }
+                if shortcut_button(ui, "Redo", Action::Redo).clicked() {
+                    *action = Some(MenuAction::Redo);
+                    ui.close_menu();
+                }
+                if shortcut_button(ui, "History", Action::ToggleHistory).clicked() {
+                    *action = Some(MenuAction::ShowHistory);
+                    ui.close_menu();
+                }
+                if shortcut_button(ui, "Keyboard shortcuts", Action::EditShortcuts).clicked() {
+                    *action = Some(MenuAction::EditShortcuts);
+                    ui.close_menu();
+                }
                 if ui.button("Cut").clicked() {}
                 if ui.button("Copy").clicked() {}
                 if ui.button("Paste").clicked() {}
             });
             ui.add_space(5.0);
-            ui.menu_button("View", |ui| {
-                if ui.button("Zoom In").clicked() {}
-                if ui.button("Zoom Out").clicked() {}
-                if ui.button("Fit to Screen").clicked() {}
-            });
+            ui.menu_button("View", |ui| view_menu(ui, config, action));
             ui.add_space(5.0);
             ui.menu_button("Help", |ui| {
                 if ui.button("Documentation").clicked() {}
                 if ui.button("About").clicked() {
-                    
+                    *action = Some(MenuAction::ShowAbout);
+                    ui.close_menu();
                 }
             });
         });
     }).response
 }
 
-pub fn navbar(themes: &ThemeColors) -> impl Widget + use<'_> {
-    |ui: &mut Ui| {
+/// Show the play button, the tempo, the time signature and the position of `transport`.
+fn transport_section(ui: &mut Ui, themes: &ThemeColors, transport: Transport, action: &mut Option<MenuAction>) {
+    ui.spacing_mut().item_spacing = Vec2::new(8., 0.);
+    let play = Button::image(Icon::Play.image(themes).fit_to_exact_size(Vec2::splat(16.))).frame(false).selected(transport.playing);
+    if ui.add(play).on_hover_text(if transport.playing { "Stop" } else { "Play" }).clicked() {
+        *action = Some(MenuAction::TogglePlayback);
+    }
+    let mut bpm = transport.bpm;
+    let response = ui.add(DragValue::new(&mut bpm).range(BPM_RANGE).speed(0.1).max_decimals(2).suffix(" BPM")).on_hover_text("Drag or click to type the tempo");
+    // The tempo is heard as it changes, but only recorded in the history once it's let go of.
+    let done = response.drag_stopped() || response.lost_focus();
+    if response.changed() || done {
+        *action = Some(MenuAction::SetBpm { bpm, live: !done });
+    }
+    let mut time_signature = transport.time_signature;
+    ComboBox::from_id_salt("time signature").width(48.).selected_text(time_signature.to_string()).show_ui(ui, |ui| {
+        for common in TimeSignature::COMMON {
+            ui.selectable_value(&mut time_signature, common, common.to_string());
+        }
+    });
+    if time_signature != transport.time_signature {
+        *action = Some(MenuAction::SetTimeSignature(time_signature));
+    }
+    let ((bar, beat), time) = transport.position;
+    let position = format!("{bar:>3}.{beat}  {}", super::format_time(time));
+    ui.add(Label::new(RichText::new(position).monospace()).selectable(false)).on_hover_text("Bar and beat, and time");
+}
+
+pub fn navbar<'a>(themes: &'a ThemeColors, config: &'a Config, transport: Transport, action: &'a mut Option<MenuAction>) -> impl Widget + use<'a> {
+    move |ui: &mut Ui| {
         let navbar_texture_image = super::build_gradient(40, themes.navbar_background_gradient_top, themes.navbar_background_gradient_bottom);
         let navbar_texture = ui.ctx().load_texture("navbar_texture", navbar_texture_image, TextureOptions::default());
 
@@ -69,13 +355,13 @@ pub fn navbar(themes: &ThemeColors) -> impl Widget + use<'_> {
                                     egui::Frame::none()
                                         .inner_margin(egui::Margin::symmetric(5., 0.))
                                         .show(ui, |ui| {
-                                            ui.add(Image::new(include_image!("../images/icons/navbar-icon.svg")).fit_to_exact_size(Vec2::splat(16.)));
+                                            ui.add(Icon::Logo.image(themes).fit_to_exact_size(Vec2::splat(16.)));
                                         });
                                     ui.vertical(|ui| {
                                         ui.add_space(2.0);
                                         ui.add(egui::Separator::default().vertical().grow(7.).spacing(16.));
                                     });
-                                    navbar_menu_buttons(ui);
+                                    navbar_menu_buttons(ui, config, action);
                                     ui.add_space(8.0);
                                 });
                             ui.centered_and_justified(|ui| {
@@ -86,7 +372,7 @@ pub fn navbar(themes: &ThemeColors) -> impl Widget + use<'_> {
                                         .rounding(egui::Rounding::same(5.))
                                         .fill(themes.navbar_widget)
                                         .show(ui, |ui| {
-                                            ui.add(Image::new(include_image!("../images/icons/play-icon.svg")).tint(egui::Color32::GREEN).fit_to_exact_size(Vec2::splat(16.)));
+                                            ui.horizontal(|ui| transport_section(ui, themes, transport, action));
                                         });
                                 });
                             });
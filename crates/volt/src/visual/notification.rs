@@ -1,46 +1,160 @@
-use std::time::Duration;
+use std::{collections::VecDeque, mem, path::PathBuf, sync::Arc, time::Duration};
 
-use egui::Color32;
+use egui::{
+    emath::easing, emath::TSTransform, hex_color, pos2, vec2, AboveOrBelow, Button, Color32, CursorIcon, Painter, PopupCloseBehavior, ProgressBar, Rect, Response, RichText, ScrollArea, Sense,
+    Shape, Stroke, Ui, UiBuilder,
+};
 
-use crate::timings::now_ns;
+use crate::{config, progress::Progress, tasks, timings::now_ns};
+
+/// How far notifications slide from the right as they appear and disappear, in points.
+const SLIDE_DISTANCE: f32 = 40.;
+/// How many past notifications the bell lists.
+const HISTORY: usize = 50;
+/// How long notifications take to fade in and out.
+const FADE: Duration = Duration::from_millis(200);
+
+/// How serious a notification is, which sets its color, its icon and how long it's shown by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Success,
+    Warning,
+    /// Something failed. Errors stay until they're dismissed, so that they aren't missed.
+    Error,
+}
+
+impl Level {
+    pub const fn color(self) -> Color32 {
+        match self {
+            Self::Info => Color32::from_rgb(0x76, 0x76, 0xa3),
+            Self::Success => Color32::from_rgb(0x5f, 0xb3, 0x6f),
+            Self::Warning => Color32::from_rgb(0xe3, 0xb3, 0x41),
+            Self::Error => Color32::from_rgb(0xe0, 0x55, 0x55),
+        }
+    }
+
+    /// Return how long notifications of this level are shown, or [`None`] if they're shown until they're dismissed.
+    pub const fn duration(self) -> Option<Duration> {
+        match self {
+            Self::Info | Self::Success => Some(Duration::from_secs(3)),
+            Self::Warning => Some(Duration::from_secs(6)),
+            Self::Error => None,
+        }
+    }
+
+    /// Paint the icon of the level in `rect`, with `mark` as the color of the symbol on it.
+    fn paint_icon(self, painter: &Painter, rect: Rect, opacity: f32, mark: Color32) {
+        let color = self.color().gamma_multiply(opacity);
+        let (center, radius) = (rect.center(), rect.width().min(rect.height()) / 2.);
+        let point = |x: f32, y: f32| center + vec2(x, y) * radius;
+        let stroke = Stroke::new(radius / 4., mark);
+        match self {
+            Self::Info => {
+                painter.circle_filled(center, radius, color);
+                painter.line_segment([point(0., -0.05), point(0., 0.5)], stroke);
+                painter.circle_filled(point(0., -0.45), radius / 7., mark);
+            }
+            Self::Success => {
+                painter.circle_filled(center, radius, color);
+                painter.add(Shape::line(vec![point(-0.45, 0.), point(-0.1, 0.35), point(0.45, -0.3)], stroke));
+            }
+            Self::Warning => {
+                painter.add(Shape::convex_polygon(vec![point(0., -1.), point(1., 0.85), point(-1., 0.85)], color, Stroke::NONE));
+                painter.line_segment([point(0., -0.4), point(0., 0.25)], stroke);
+                painter.circle_filled(point(0., 0.55), radius / 7., mark);
+            }
+            Self::Error => {
+                painter.circle_filled(center, radius, color);
+                painter.line_segment([point(-0.35, -0.35), point(0.35, 0.35)], stroke);
+                painter.line_segment([point(-0.35, 0.35), point(0.35, -0.35)], stroke);
+            }
+        }
+    }
+}
+
+/// What a button on a notification does, which is done by the app once it's clicked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationAction {
+    /// Undo the edit with this id in the history, which is the one the notification is about, as long as it's still the last one.
+    Undo(u64),
+    /// Try opening the output device again.
+    RetryOutput,
+    /// Show the file or folder at this path in the file manager.
+    Reveal(PathBuf),
+    /// Open this web page in the browser.
+    OpenLink(String),
+}
 
 #[derive(Debug, Clone)]
 pub struct Notification {
+    pub level: Level,
     pub message: String,
     pub duration: Option<Duration>,
-    pub add_time: Duration
+    pub add_time: Duration,
+    /// The buttons shown under the message, with their labels. Clicking one also dismisses the notification.
+    pub actions: Vec<(String, NotificationAction)>,
+    /// The task running in the background that the notification shows the progress of, which removes the notification once it's over. Until
+    /// then, clicking the notification doesn't dismiss it.
+    pub progress: Option<Arc<Progress>>,
 }
 
 impl Notification {
-    pub fn new(message: String, duration: Option<Duration>) -> Self {
-        let add_time = Duration::from_nanos(now_ns() as u64);
-        Notification {
+    pub fn new(level: Level, message: String, duration: Option<Duration>) -> Self {
+        let add_time = now();
+        Self {
+            level,
             message,
             duration,
-            add_time
+            add_time,
+            actions: Vec::new(),
+            progress: None,
         }
     }
 
-    pub fn with_duration(message: String, duration: Duration) -> Self {
-        Notification::new(message, Some(duration))
+    /// Add a button labelled `label` that does `action`.
+    pub fn action(&mut self, label: impl Into<String>, action: NotificationAction) -> &mut Self {
+        self.actions.push((label.into(), action));
+        self
+    }
+
+    pub fn with_duration(level: Level, message: String, duration: Duration) -> Self {
+        Self::new(level, message, Some(duration))
     }
 
-    pub fn without_duration(message: String) -> Self {
-        Notification::new(message, None)
+    pub fn without_duration(level: Level, message: String) -> Self {
+        Self::new(level, message, None)
     }
 }
 
 pub struct NotificationDrawer {
     notifications: Vec<Notification>,
+    /// The actions whose buttons were clicked since the last call to [`NotificationDrawer::take_actions`].
+    clicked: Vec<NotificationAction>,
+    /// When the notifications were last shown, to know how long hovered ones were paused for.
+    last_shown: Duration,
+    /// The latest messages notified with their levels, oldest first, which the bell lists once they're gone.
+    history: VecDeque<(Level, String)>,
+    /// How many of them came since the bell's list was last opened.
+    unread: usize,
 }
 
 impl NotificationDrawer {
-    pub fn new() -> Self {
-        NotificationDrawer {
+    pub const fn new() -> Self {
+        Self {
             notifications: Vec::new(),
+            clicked: Vec::new(),
+            last_shown: Duration::ZERO,
+            history: VecDeque::new(),
+            unread: 0,
         }
     }
 
+    /// Return the actions of the buttons clicked on notifications since the last call, for the app to do.
+    pub fn take_actions(&mut self) -> Vec<NotificationAction> {
+        mem::take(&mut self.clicked)
+    }
+
     pub fn add_notification(&mut self, notification: Notification) {
         self.notifications.push(notification);
     }
@@ -51,43 +165,164 @@ impl NotificationDrawer {
         }
     }
 
-    pub fn get_notifications(&self) -> &Vec<Notification> {
+    pub const fn get_notifications(&self) -> &Vec<Notification> {
         &self.notifications
     }
 
-    pub fn make(&mut self, message: String, duration: Option<Duration>) {
-        let notification = Notification::new(message, duration);
+    /// Show `message` at `level` for as long as the level's notifications are shown, returning the notification to add buttons to it. A message that's already shown
+    /// is shown again from the start instead of twice.
+    pub fn notify(&mut self, level: Level, message: impl Into<String>) -> &mut Notification {
+        let message = message.into();
+        self.history.push_back((level, message.clone()));
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+        self.unread = (self.unread + 1).min(HISTORY);
+        self.notifications.retain(|notification| notification.level != level || notification.message != message);
+        self.add_notification(Notification::new(level, message, level.duration()));
+        self.notifications.last_mut().unwrap()
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) -> &mut Notification {
+        self.notify(Level::Info, message)
+    }
+
+    pub fn success(&mut self, message: impl Into<String>) -> &mut Notification {
+        self.notify(Level::Success, message)
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) -> &mut Notification {
+        self.notify(Level::Warning, message)
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) -> &mut Notification {
+        self.notify(Level::Error, message)
+    }
+
+    /// Show `message` with the progress of a task running in the background and a button to cancel it, until the task is over.
+    pub fn progress(&mut self, message: impl Into<String>, progress: Arc<Progress>) -> &mut Notification {
+        let mut notification = Notification::new(Level::Info, message.into(), None);
+        notification.progress = Some(progress);
         self.add_notification(notification);
+        self.notifications.last_mut().unwrap()
+    }
+}
+
+impl NotificationDrawer {
+    /// Show a bell with how many notifications came since it was last clicked, which lists the latest ones when clicked.
+    pub fn bell(&mut self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(vec2(18., 18.), Sense::click());
+        let color = if response.hovered() { ui.visuals().strong_text_color() } else { hex_color!("777490") };
+        paint_bell(ui.painter(), rect.shrink(2.), color);
+        let worst = self.history.iter().rev().take(self.unread).map(|(level, _)| *level).max_by_key(|level| *level as u8);
+        if let Some(level) = worst {
+            let badge = pos2(rect.right() - 3., rect.top() + 3.);
+            ui.painter().circle_filled(badge, 5., level.color());
+            let count = if self.unread > 9 { "9+".to_string() } else { self.unread.to_string() };
+            ui.painter().text(badge, egui::Align2::CENTER_CENTER, count, egui::FontId::proportional(7.), Color32::WHITE);
+        }
+        let popup_id = response.id.with("history");
+        if response.clicked() {
+            ui.memory_mut(|memory| memory.toggle_popup(popup_id));
+            self.unread = 0;
+        }
+        egui::popup_above_or_below_widget(ui, popup_id, &response, AboveOrBelow::Above, PopupCloseBehavior::CloseOnClickOutside, |ui| {
+            ui.set_min_width(280.);
+            if self.history.is_empty() {
+                ui.weak("No notifications");
+                return;
+            }
+            ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                for (level, message) in self.history.iter().rev() {
+                    ui.horizontal(|ui| {
+                        let (icon, _) = ui.allocate_exact_size(vec2(12., 12.), Sense::hover());
+                        level.paint_icon(ui.painter(), icon, 1., ui.visuals().extreme_bg_color);
+                        ui.label(message);
+                    });
+                }
+            });
+            if ui.button("Clear").clicked() {
+                self.history.clear();
+            }
+        });
+        response.on_hover_text("Notifications")
+    }
+}
+
+/// Paint a bell in `rect`, for the notifications.
+fn paint_bell(painter: &Painter, rect: Rect, color: Color32) {
+    let point = |x: f32, y: f32| rect.lerp_inside(vec2(x, y));
+    let body = vec![point(0.5, 0.), point(0.78, 0.2), point(0.85, 0.75), point(0.15, 0.75), point(0.22, 0.2)];
+    painter.add(Shape::convex_polygon(body, color, Stroke::NONE));
+    painter.rect_filled(Rect::from_min_max(point(0., 0.7), point(1., 0.8)), 1., color);
+    painter.circle_filled(point(0.5, 0.9), rect.width() / 8., color);
+}
+
+/// Return the time since the epoch, which notifications are timed by.
+fn now() -> Duration {
+    Duration::from_secs_f64(now_ns() / 1e9)
+}
+
+/// Show how far the task of `progress` has got, guessing how long it has left from how long it took so far, which is `elapsed`.
+#[allow(clippy::cast_possible_truncation, reason = "the fraction is between 0 and 1")]
+fn progress_ui(ui: &mut egui::Ui, progress: &Progress, elapsed: Duration, opacity: f32) {
+    let text_color = Color32::WHITE.gamma_multiply(opacity);
+    // Tasks that haven't said how far they are yet are shown as busy.
+    let bar = progress.fraction().map_or_else(
+        || ProgressBar::new(0.).animate(!config::reduced_motion()),
+        |fraction| {
+            // The first moments are too uneven to go by.
+            let left = (fraction > 0.01 && elapsed > Duration::from_secs(1)).then(|| elapsed.mul_f64((1. - fraction) / fraction));
+            let text = match left {
+                Some(left) if left >= Duration::from_mins(1) => format!("{:.0}%, about {} min left", fraction * 100., left.as_secs().div_ceil(60)),
+                Some(left) => format!("{:.0}%, about {} s left", fraction * 100., left.as_secs() + 1),
+                None => format!("{:.0}%", fraction * 100.),
+            };
+            ProgressBar::new(fraction as f32).text(RichText::new(text).color(text_color))
+        },
+    );
+    ui.add(bar.desired_width(200.));
+    if progress.is_cancelled() {
+        ui.label(RichText::new("Cancelling…").color(text_color));
+    } else if ui.add(Button::new(RichText::new("Cancel").color(text_color)).small()).clicked() {
+        tasks::cancel(progress);
     }
 }
 
 impl egui::Widget for &mut NotificationDrawer {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         let mut response = ui.allocate_response(egui::Vec2::ZERO, egui::Sense::hover());
+        let now = now();
+        let since_shown = now.saturating_sub(mem::replace(&mut self.last_shown, now));
 
         if !self.notifications.is_empty() {
-            let now = now_ns() as u64;
             let mut indices_to_remove = Vec::new();
+            let mut hovered = Vec::new();
 
             for (i, notification) in self.notifications.iter().enumerate() {
-                let age = now - notification.add_time.as_nanos() as u64;
-                let fade_duration_ns = 0.2 * 1_000_000_000.0;
-                let lifetime_ns = notification.duration.map(|d| d.as_nanos() as f64).unwrap_or(f64::MAX);
-                let mut opacity: f32 = if age as f64 <= fade_duration_ns {
-                    (age as f64 / fade_duration_ns) as f32
-                } else if lifetime_ns - age as f64 <= fade_duration_ns {
-                    ((lifetime_ns - age as f64) / fade_duration_ns) as f32
-                } else {
+                let age = now.saturating_sub(notification.add_time);
+                let left = notification.duration.map(|duration| duration.saturating_sub(age));
+                let mut opacity: f32 = if config::reduced_motion() {
                     1.0
+                } else if age <= FADE {
+                    age.div_duration_f32(FADE)
+                } else {
+                    left.filter(|left| *left <= FADE).map_or(1., |left| left.div_duration_f32(FADE))
                 };
 
                 if opacity <= 0.0 {
                     opacity = 0.01;
                 }
 
-                let color = Color32::from_hex("#222222").unwrap().gamma_multiply(opacity);
+                let background = hex_color!("222222");
+                let color = background.gamma_multiply(opacity);
+                let border = Stroke::new(1., notification.level.color().gamma_multiply(opacity));
 
-                egui::Frame::none().fill(color).inner_margin(egui::Margin::same(10.)).show(ui, |ui| {
+                // Notifications slide in from the right as they fade in, and back out as they fade out. Only how they look is moved, so they can be clicked
+                // where they end up from the start.
+                let slide = vec2((1. - easing::cubic_out(opacity.min(1.))) * SLIDE_DISTANCE, 0.);
+                // The notification is dismissed by clicking it anywhere other than on its buttons, which get their clicks first, unless it shows progress.
+                let frame = ui.with_visual_transform(TSTransform::from_translation(slide), |ui| ui.scope_builder(UiBuilder::new().sense(Sense::click()), |ui| egui::Frame::none().fill(color).stroke(border).inner_margin(egui::Margin::same(10.)).show(ui, |ui| {
                     let width = ui.ctx().screen_rect().width();
                     let min_width = if width < 200. {
                         width
@@ -96,14 +331,46 @@ impl egui::Widget for &mut NotificationDrawer {
                     };
                     ui.set_min_width(min_width);
                     ui.allocate_ui(ui.available_size(), |ui| {
-                        let text_color = Color32::WHITE.gamma_multiply(opacity);
-                        ui.label(egui::RichText::new(&notification.message).color(text_color));
+                        ui.horizontal(|ui| {
+                            let (icon, _) = ui.allocate_exact_size(vec2(14., 14.), Sense::hover());
+                            notification.level.paint_icon(ui.painter(), icon, opacity, color);
+                            let text_color = Color32::WHITE.gamma_multiply(opacity);
+                            ui.label(egui::RichText::new(&notification.message).color(text_color));
+                        });
+                        if let Some(progress) = &notification.progress {
+                            progress_ui(ui, progress, age, opacity);
+                        }
+                        if !notification.actions.is_empty() {
+                            ui.horizontal(|ui| {
+                                for (label, action) in &notification.actions {
+                                    let button = Button::new(RichText::new(label).color(Color32::WHITE.gamma_multiply(opacity))).small();
+                                    if ui.add(button).clicked() {
+                                        self.clicked.push(action.clone());
+                                        indices_to_remove.push(i);
+                                    }
+                                }
+                            });
+                        }
                     });
-                });
+                })).response).inner;
+
+                if frame.contains_pointer() {
+                    hovered.push(i);
+                }
+
+                // Notifications showing progress stay until their task is over, as dismissing them would leave it running out of sight. It's cancelled
+                // with its button instead.
+                if notification.progress.is_none() && frame.on_hover_cursor(CursorIcon::PointingHand).on_hover_text("Click to dismiss").clicked() {
+                    indices_to_remove.push(i);
+                }
+
+                if notification.progress.as_ref().is_some_and(Progress::is_over) {
+                    indices_to_remove.push(i);
+                }
 
                 // Schedule removal if a duration is specified
                 if let Some(duration) = notification.duration {
-                    if (notification.add_time.as_nanos() as u64) + (duration.as_nanos() as u64) < now {
+                    if notification.add_time + duration < now {
                         indices_to_remove.push(i);
                     }
                 }
@@ -111,7 +378,16 @@ impl egui::Widget for &mut NotificationDrawer {
                 ui.ctx().request_repaint_after_secs(0.03);
             }
 
+            // Hovered notifications don't count down, so that there's time to read them. They're brought back if they were fading out.
+            for i in hovered {
+                let notification = &mut self.notifications[i];
+                if let Some(duration) = notification.duration {
+                    notification.add_time = (notification.add_time + since_shown).max((now + FADE).saturating_sub(duration)).min(now);
+                }
+            }
+
             // Remove notifications in reverse order to avoid index invalidation
+            indices_to_remove.dedup();
             for index in indices_to_remove.into_iter().rev() {
                 self.remove_notification(index);
             }
@@ -0,0 +1,222 @@
+//! The setup shown on the first launch, which is when there are no saved settings yet. It walks through picking the audio devices, the folders of
+//! samples the browser indexes and the look of the interface, and can open a demo project to try things with.
+
+use std::{env, path::PathBuf};
+
+use blerp::device::{Device, DeviceHandler, Direction};
+use crossbeam_channel::Receiver;
+use egui::{Align, Align2, Button, ComboBox, Context, Label, Layout, ScrollArea, Ui, Window};
+use itertools::Itertools;
+
+use super::{dialog::pick_folder, theme};
+use crate::config::{Config, Theme};
+
+/// Folders in the home folder that often hold samples, which are offered when they exist.
+const SUGGESTED_FOLDERS: [&str; 4] = ["Music", "Samples", "Music/Samples", "Documents/Samples"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Welcome,
+    Audio,
+    Samples,
+    Look,
+    Project,
+}
+
+impl Step {
+    const ALL: [Self; 5] = [Self::Welcome, Self::Audio, Self::Samples, Self::Look, Self::Project];
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Welcome => "Welcome to Volt",
+            Self::Audio => "Audio devices",
+            Self::Samples => "Samples",
+            Self::Look => "Look",
+            Self::Project => "First project",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|step| *step == self).unwrap_or_default()
+    }
+}
+
+/// Something picked in the setup, for the app to apply right away so that it can be heard or seen.
+pub enum Setup {
+    Device(Device),
+    Theme(Theme),
+    /// Take the colors from the theme file with this name, or use the default colors.
+    Colors(Option<String>),
+    /// The setup is over, with the folders the browser indexes and whether the demo project is opened.
+    Finish { folders: Vec<PathBuf>, demo: bool },
+}
+
+pub struct Onboarding {
+    step: Step,
+    /// The devices found, once the audio step was shown.
+    devices: Option<Vec<Device>>,
+    /// The folders of samples picked, and whether each is kept.
+    folders: Vec<(PathBuf, bool)>,
+    picking_folder: Option<Receiver<Option<PathBuf>>>,
+    demo: bool,
+}
+
+impl Onboarding {
+    /// Start the setup, offering `roots`, the folders the browser shows already, along with usual sample folders.
+    pub fn new(roots: &[PathBuf]) -> Self {
+        let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from);
+        let suggested = home.iter().flat_map(|home| SUGGESTED_FOLDERS.map(|folder| home.join(folder))).filter(|folder| folder.is_dir());
+        Self {
+            step: Step::Welcome,
+            devices: None,
+            folders: roots.iter().cloned().map(|root| (root, true)).chain(suggested.map(|folder| (folder, false))).unique_by(|(folder, _)| folder.clone()).collect(),
+            picking_folder: None,
+            demo: true,
+        }
+    }
+
+    /// Show the setup, where `output` and `input` are the names of the devices in use and `config` holds the look in use. Returns what was
+    /// picked this frame, if anything.
+    pub fn show(&mut self, ctx: &Context, output: Option<&str>, input: Option<&str>, config: &Config) -> Option<Setup> {
+        if let Some(Ok(picked)) = self.picking_folder.as_ref().map(Receiver::try_recv) {
+            self.picking_folder = None;
+            if let Some(folder) = picked.filter(|folder| !self.folders.iter().any(|(other, _)| other == folder)) {
+                self.folders.push((folder, true));
+            }
+        }
+        let mut setup = None;
+        Window::new(self.step.title())
+            .id("onboarding".into())
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0., 0.])
+            .fixed_size([440., 280.])
+            .show(ctx, |ui| {
+                ui.weak(format!("Step {} of {}", self.step.index() + 1, Step::ALL.len()));
+                ui.add_space(6.);
+                match self.step {
+                    Step::Welcome => Self::add_welcome(ui),
+                    Step::Audio => setup = self.add_audio(ui, output, input),
+                    Step::Samples => self.add_samples(ui),
+                    Step::Look => setup = add_look(ui, config),
+                    Step::Project => self.add_project(ui),
+                }
+                ui.with_layout(Layout::bottom_up(Align::Max), |ui| {
+                    ui.horizontal(|ui| {
+                        if let Some(finish) = self.add_navigation(ui) {
+                            setup = Some(finish);
+                        }
+                    });
+                });
+            });
+        setup
+    }
+
+    fn add_welcome(ui: &mut Ui) {
+        ui.label("Volt is a digital audio workstation. A few things can be set up before you start, and all of them can be changed later.");
+        ui.add_space(4.);
+        ui.label("Volt is still early in its development, so expect things to change and break.");
+        ui.hyperlink_to("Report issues on GitHub", "https://github.com/SharliBeicon/Volt/issues");
+    }
+
+    fn add_audio(&mut self, ui: &mut Ui, output: Option<&str>, input: Option<&str>) -> Option<Setup> {
+        let all = self.devices.get_or_insert_with(|| DeviceHandler::audio().devices().into_iter().map(|entry| entry.device).collect());
+        ui.label("Pick the device to play through and the one to record from. Playback follows the pick right away.");
+        ui.add_space(6.);
+        let mut picked = None;
+        for (direction, label, current) in [(Direction::Output, "Output", output), (Direction::Input, "Input", input)] {
+            let devices = all.iter().filter(|device| device.direction == direction).collect_vec();
+            // The input follows the default device until another one is picked.
+            let current = current.map(ToString::to_string).or_else(|| devices.iter().find(|device| device.is_default).map(|device| device.name.clone()));
+            ui.horizontal(|ui| {
+                ui.add_sized([60., 18.], Label::new(label));
+                ComboBox::from_id_salt(("onboarding device", label)).width(300.).selected_text(current.as_deref().unwrap_or("None found")).show_ui(ui, |ui| {
+                    for device in devices {
+                        let name = if device.is_default { format!("{} (default)", device.name) } else { device.name.clone() };
+                        if ui.selectable_label(current.as_ref() == Some(&device.name), name).clicked() && current.as_ref() != Some(&device.name) {
+                            picked = Some(Setup::Device(device.clone()));
+                        }
+                    }
+                });
+            });
+        }
+        if ui.button("Refresh").on_hover_text("Look for devices again").clicked() {
+            self.devices = None;
+        }
+        picked
+    }
+
+    fn add_samples(&mut self, ui: &mut Ui) {
+        ui.label("Pick the folders your samples are in. The browser shows them and indexes the audio files in them, so they can be searched.");
+        ui.add_space(6.);
+        ScrollArea::vertical().max_height(140.).show(ui, |ui| {
+            for (folder, kept) in &mut self.folders {
+                ui.checkbox(kept, folder.display().to_string());
+            }
+            if self.folders.is_empty() {
+                ui.weak("No folders yet");
+            }
+        });
+        if ui.add_enabled(self.picking_folder.is_none(), Button::new("Add folder…")).clicked() {
+            self.picking_folder = Some(pick_folder());
+        }
+    }
+
+    fn add_project(&mut self, ui: &mut Ui) {
+        ui.label("Start with an empty project, or with a short demo of chords and a bass line played by the built-in synth.");
+        ui.add_space(6.);
+        ui.radio_value(&mut self.demo, false, "Empty project");
+        ui.radio_value(&mut self.demo, true, "Demo project");
+    }
+
+    /// Show the buttons to go through the steps, returning the end of the setup once it's finished or skipped.
+    fn add_navigation(&mut self, ui: &mut Ui) -> Option<Setup> {
+        let index = self.step.index();
+        let last = index + 1 == Step::ALL.len();
+        let mut finish = false;
+        if ui.button(if last { "Start" } else { "Next" }).clicked() {
+            if last {
+                finish = true;
+            } else {
+                self.step = Step::ALL[index + 1];
+            }
+        }
+        if index > 0 && ui.button("Back").clicked() {
+            self.step = Step::ALL[index - 1];
+        }
+        if !last && ui.button("Skip setup").on_hover_text("Keep the defaults for the remaining steps").clicked() {
+            finish = true;
+            self.demo = false;
+        }
+        finish.then(|| Setup::Finish {
+            folders: self.folders.iter().filter(|(_, kept)| *kept).map(|(folder, _)| folder.clone()).collect(),
+            demo: self.demo,
+        })
+    }
+}
+
+fn add_look(ui: &mut Ui, config: &Config) -> Option<Setup> {
+    ui.label("Pick how the interface looks. More colors can be added as theme files later.");
+    ui.add_space(6.);
+    let mut picked = None;
+    ui.horizontal(|ui| {
+        for theme in Theme::ALL {
+            if ui.selectable_label(config.theme == theme, theme.name()).clicked() {
+                picked = Some(Setup::Theme(theme));
+            }
+        }
+    });
+    ui.add_space(4.);
+    let colors = config.colors.as_deref();
+    ComboBox::from_label("Colors").selected_text(colors.unwrap_or("Default")).show_ui(ui, |ui| {
+        if ui.selectable_label(colors.is_none(), "Default").clicked() {
+            picked = Some(Setup::Colors(None));
+        }
+        for name in theme::available() {
+            if ui.selectable_label(colors == Some(name.as_str()), &name).clicked() {
+                picked = Some(Setup::Colors(Some(name)));
+            }
+        }
+    });
+    picked
+}
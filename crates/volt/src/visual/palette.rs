@@ -1,3 +1,422 @@
-pub fn palette() {
+//! What can be typed into the command palette: the name of a file to open, or a command like `> bpm 128`, `> goto 33.1.1` or `> lua print(project.bpm())`.
 
-}
\ No newline at end of file
+use std::path::{Path, PathBuf};
+
+use blerp::processing::registry::{self, EFFECTS};
+use egui::{
+    hex_color, pos2,
+    text::{CCursor, LayoutJob, TextWrapping},
+    text_selection::CCursorRange,
+    vec2, Align2, Area, Color32, Context, Event, FontFamily, FontId, Frame, Id, Key, Margin, Modifiers, Order, RichText, Sense, Shadow, Stroke, TextEdit, TextFormat,
+};
+use itertools::Itertools;
+
+use history::History;
+use super::{browser::Browser, ThemeColors};
+use crate::{
+    keymap::{self, Keymap},
+    script,
+};
+
+mod history;
+
+/// The narrowest and widest the playlist can be zoomed to with `zoom`, in percent of its default zoom.
+const ZOOM_RANGE: (f32, f32) = (25., 1000.);
+
+/// A command typed into the command palette, along with its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Timings,
+    Log,
+    Playlist,
+    Graph,
+    History,
+    Spectrum,
+    Scope,
+    Tuner,
+    Loudness,
+    Info,
+    Bug,
+    Shortcuts,
+    /// Copy the files used by the project into its folder, and save it.
+    Collect,
+    /// Write the system info, the log, the timings and the settings into an archive, for bug reports.
+    ExportDiagnostics,
+    /// Add a node for the registered effect with this name.
+    Node(&'static str),
+    /// Set the tempo of the playlist in BPM.
+    Bpm(f64),
+    /// Zoom the playlist horizontally, in percent of its default zoom.
+    Zoom(f32),
+    /// Move the playhead, counting bars, beats and sixteenths from 1.
+    Goto { bar: u32, beat: u32, sixteenth: u32 },
+    /// Run the script with this name from the scripts folder.
+    Script(String),
+    /// Run this line of Lua.
+    Lua(String),
+}
+
+/// The name of every command and the arguments it takes.
+const COMMANDS: &[(&str, &str)] = &[
+    ("timings", ""),
+    ("log", ""),
+    ("playlist", ""),
+    ("graph", ""),
+    ("history", ""),
+    ("spectrum", ""),
+    ("scope", ""),
+    ("tuner", ""),
+    ("loudness", ""),
+    ("info", ""),
+    ("bug", ""),
+    ("shortcuts", ""),
+    ("collect", ""),
+    ("export", "diagnostics"),
+    ("node", "<effect>"),
+    ("bpm", "<1-999>"),
+    ("zoom", "<25-1000>%"),
+    ("goto", "<bar>.<beat>.<sixteenth>"),
+    ("script", "<name>"),
+    ("lua", "<code>"),
+];
+
+/// The commands that do the same as an action of the keymap, whose shortcuts are shown along with them.
+const ACTIONS: &[(&str, keymap::Action)] = &[
+    ("timings", keymap::Action::ToggleTimings),
+    ("log", keymap::Action::ToggleLog),
+    ("playlist", keymap::Action::ShowPlaylist),
+    ("graph", keymap::Action::ShowGraph),
+    ("history", keymap::Action::ToggleHistory),
+    ("spectrum", keymap::Action::ToggleSpectrum),
+    ("scope", keymap::Action::ToggleScope),
+    ("tuner", keymap::Action::ToggleTuner),
+    ("loudness", keymap::Action::ToggleLoudness),
+    ("shortcuts", keymap::Action::EditShortcuts),
+];
+
+/// What to show under the command palette while a command is being typed.
+pub enum Hint {
+    /// The commands or arguments that can be typed.
+    Usage(String),
+    /// Why the command can't be run as it is.
+    Invalid(String),
+}
+
+/// Split `text` into the name of a command and its arguments.
+fn split(text: &str) -> (String, &str) {
+    let text = text.trim();
+    let (name, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    (name.to_lowercase(), arguments.trim())
+}
+
+fn usage(name: &str, arguments: &str) -> String {
+    if arguments.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name} {arguments}")
+    }
+}
+
+/// Parse the command typed in `text`, where `scripts` are the names of the scripts there are.
+///
+/// # Errors
+///
+/// Returns why the command is invalid, to be shown to the user.
+pub fn parse(text: &str, scripts: &[String]) -> Result<Command, String> {
+    let (name, arguments) = split(text);
+    let Some(&(_, expected)) = COMMANDS.iter().find(|(other, _)| *other == name) else {
+        return Err(format!("There is no command called \"{name}\""));
+    };
+    if expected.is_empty() && !arguments.is_empty() {
+        return Err(format!("{name} doesn't take any arguments"));
+    }
+    if !expected.is_empty() && arguments.is_empty() {
+        return Err(format!("Usage: {}", usage(&name, expected)));
+    }
+    Ok(match name.as_str() {
+        "timings" => Command::Timings,
+        "log" => Command::Log,
+        "playlist" => Command::Playlist,
+        "graph" => Command::Graph,
+        "history" => Command::History,
+        "spectrum" => Command::Spectrum,
+        "scope" => Command::Scope,
+        "tuner" => Command::Tuner,
+        "loudness" => Command::Loudness,
+        "info" => Command::Info,
+        "bug" => Command::Bug,
+        "shortcuts" => Command::Shortcuts,
+        "collect" => Command::Collect,
+        "export" if arguments.eq_ignore_ascii_case("diagnostics") => Command::ExportDiagnostics,
+        "export" => return Err(format!("Usage: {}", usage(&name, expected))),
+        "node" => Command::Node(registry::find(arguments).ok_or_else(|| format!("There is no effect called \"{arguments}\""))?.name),
+        "bpm" => arguments
+            .parse()
+            .ok()
+            .filter(|bpm| (1. ..=999.).contains(bpm))
+            .map(Command::Bpm)
+            .ok_or("The tempo has to be a number from 1 to 999")?,
+        "zoom" => arguments
+            .strip_suffix('%')
+            .unwrap_or(arguments)
+            .trim_end()
+            .parse()
+            .ok()
+            .filter(|percent| (ZOOM_RANGE.0..=ZOOM_RANGE.1).contains(percent))
+            .map(Command::Zoom)
+            .ok_or("The zoom has to be a percentage from 25 to 1000")?,
+        "goto" => parse_position(arguments)?,
+        "script" if scripts.iter().any(|name| name == arguments) => Command::Script(arguments.to_string()),
+        "script" => return Err(format!("There is no script called \"{arguments}\"")),
+        _ => Command::Lua(arguments.to_string()),
+    })
+}
+
+/// Parse a position like `33.1.1`, where the beat and sixteenth can be left out.
+fn parse_position(text: &str) -> Result<Command, String> {
+    let parts = text.split('.').map(|part| part.trim().parse::<u32>().ok().filter(|&part| part >= 1)).collect_vec();
+    match parts[..] {
+        [Some(bar)] => Ok(Command::Goto { bar, beat: 1, sixteenth: 1 }),
+        [Some(bar), Some(beat)] => Ok(Command::Goto { bar, beat, sixteenth: 1 }),
+        [Some(bar), Some(beat), Some(sixteenth @ 1..=4)] => Ok(Command::Goto { bar, beat, sixteenth }),
+        [_, _, Some(_)] => Err("A beat only has 4 sixteenths".into()),
+        _ => Err("The position has to be a bar, beat and sixteenth counted from 1, like 33.1.1".into()),
+    }
+}
+
+/// Return what to show under the command palette while the command `text` is typed, with the commands it could be ranked by how they were used in `history`
+/// and followed by their shortcuts in `keymap`, and the `scripts` it could run.
+fn hint(text: &str, history: &History, keymap: &Keymap, scripts: &[String], ctx: &Context) -> Hint {
+    let with_shortcut = |name: &str, arguments: &str| {
+        let shortcut = ACTIONS.iter().find(|(other, _)| *other == name).and_then(|(_, action)| keymap.text(ctx, *action));
+        shortcut.map_or_else(|| usage(name, arguments), |shortcut| format!("{} ({shortcut})", usage(name, arguments)))
+    };
+    let (name, arguments) = split(text);
+    // While the name is being typed, the commands it could be are listed.
+    if !text.trim_start().contains(char::is_whitespace) {
+        let matching = COMMANDS
+            .iter()
+            .filter(|(other, _)| other.starts_with(&name))
+            .sorted_by_key(|(other, _)| history.rank(other))
+            .map(|(other, expected)| with_shortcut(other, expected))
+            .join("   ");
+        if !matching.is_empty() {
+            return Hint::Usage(matching);
+        }
+    }
+    let expected = COMMANDS.iter().find(|(other, _)| *other == name).map(|(_, expected)| *expected);
+    if expected.is_some_and(|expected| !expected.is_empty()) && arguments.is_empty() {
+        return Hint::Usage(usage(&name, expected.unwrap_or_default()));
+    }
+    if name == "node" && registry::find(arguments).is_none() {
+        let matching = EFFECTS.iter().map(|effect| effect.name).filter(|effect| effect.to_lowercase().starts_with(&arguments.to_lowercase())).join("   ");
+        if !matching.is_empty() {
+            return Hint::Usage(matching);
+        }
+    }
+    if name == "script" {
+        let matching = scripts.iter().filter(|script| script.starts_with(arguments) && *script != arguments).join("   ");
+        if !matching.is_empty() {
+            return Hint::Usage(matching);
+        }
+    }
+    match parse(text, scripts) {
+        Ok(_) => Hint::Usage(with_shortcut(&name, expected.unwrap_or_default())),
+        Err(error) => Hint::Invalid(error),
+    }
+}
+
+/// Lay out a file found by quick open: its name, with the characters at `highlights` standing out, followed by its folder.
+pub fn quick_open_job(path: &Path, highlights: &[usize], font: &FontId, theme: &ThemeColors) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    for (index, character) in path.file_name().unwrap_or_default().to_string_lossy().chars().enumerate() {
+        let color = if highlights.contains(&index) { hex_color!("8c8cff") } else { theme.command_palette_text };
+        job.append(character.encode_utf8(&mut [0; 4]), 0., TextFormat::simple(font.clone(), color));
+    }
+    let folder = path.parent().map(|folder| folder.display().to_string()).unwrap_or_default();
+    job.append(&folder, 12., TextFormat::simple(font.clone(), theme.command_palette_placeholder_text));
+    job
+}
+
+/// The command palette, which finds files to open and runs commands, VS Code style. It's opened over everything else and takes every key pressed while it's open.
+pub struct Palette {
+    pub open: bool,
+    text: String,
+    /// The file picked out of those found, counting from the best match.
+    selected: usize,
+    history: History,
+    /// How many commands ago the command filled in with the up arrow was run, until something else is typed.
+    recalled: Option<usize>,
+    /// The names of the scripts there are, which are listed again whenever the palette is opened.
+    scripts: Vec<String>,
+}
+
+/// Something picked in the command palette.
+pub enum Action {
+    Run(Command),
+    /// Listen to a file that was found.
+    Preview(PathBuf),
+    /// Add a clip of a file that was found at the playhead.
+    AddAtPlayhead(PathBuf),
+}
+
+impl Palette {
+    const WIDTH: f32 = 300.;
+
+    /// Return a closed palette, with the commands run in previous sessions.
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            text: String::new(),
+            selected: 0,
+            history: History::load(),
+            recalled: None,
+            scripts: Vec::new(),
+        }
+    }
+
+    /// Open the palette, or close it if it's open.
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        if self.open {
+            self.scripts = script::available();
+        }
+        self.text.clear();
+        self.selected = 0;
+        self.recalled = None;
+    }
+
+    /// Fill in an older command run if `older`, or a newer one otherwise, which goes back to an empty palette past the newest.
+    fn recall(&mut self, older: bool) {
+        let index = match self.recalled {
+            None => older.then_some(0),
+            Some(index) if older => Some(if self.history.recent(index + 1).is_some() { index + 1 } else { index }),
+            Some(index) => index.checked_sub(1),
+        };
+        self.recalled = index.filter(|&index| self.history.recent(index).is_some());
+        self.text = self.recalled.and_then(|index| self.history.recent(index)).map_or_else(String::new, |text| format!(">{text}"));
+    }
+
+    /// Show the palette if it's open, returning what was picked in it, which closes it.
+    pub fn show(&mut self, ctx: &Context, theme: &ThemeColors, browser: &mut Browser, keymap: &Keymap) -> Option<Action> {
+        if !self.open {
+            return None;
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+            self.toggle();
+            return None;
+        }
+        let (up, down) = ctx.input_mut(|i| (i.consume_key(Modifiers::NONE, Key::ArrowUp), i.consume_key(Modifiers::NONE, Key::ArrowDown)));
+        // The arrows go through the commands run before from an empty palette, and through the files found otherwise.
+        let recalling = (up || down) && (self.text.is_empty() || self.recalled.is_some());
+        if recalling {
+            self.recall(up);
+        } else if up {
+            self.selected = self.selected.saturating_sub(1);
+        } else if down {
+            self.selected += 1;
+        }
+        let (entered, shift) = ctx.input(|i| (i.key_pressed(Key::Enter), i.modifiers.shift));
+        let frame = Frame::none()
+            .fill(theme.command_palette)
+            .stroke(Stroke::new(1., theme.command_palette_border))
+            .rounding(8.)
+            .inner_margin(Margin::symmetric(10., 7.));
+        let highlight = Color32::from_rgba_unmultiplied(0x5c, 0x5c, 0xff, 0x20);
+        let mut action = None;
+        Area::new(Id::new("command_palette")).order(Order::Foreground).anchor(Align2::CENTER_TOP, vec2(0., 25.)).show(ctx, |ui| {
+            ui.set_width(Self::WIDTH);
+            ui.visuals_mut().text_cursor.stroke = Stroke::new(2., Color32::from_rgb(0x5c, 0x5c, 0xff));
+            ui.visuals_mut().selection.bg_fill = highlight;
+            let typed = self.text.clone();
+            let shadow = Shadow {
+                spread: 0.,
+                blur: 14.,
+                offset: vec2(0., 4.),
+                color: Color32::from_black_alpha(200),
+            };
+            frame.shadow(shadow).show(ui, |ui| {
+                let mut output = TextEdit::singleline(&mut self.text)
+                    .frame(false)
+                    .font(FontId::new(12., FontFamily::Monospace))
+                    .text_color(theme.command_palette_text)
+                    .hint_text(RichText::new("Search files, or type > for commands...").color(theme.command_palette_placeholder_text))
+                    .desired_width(f32::INFINITY)
+                    .show(ui);
+                output.response.request_focus();
+                // A recalled command is edited from its end.
+                if recalling {
+                    output.state.cursor.set_char_range(Some(CCursorRange::one(CCursor::new(self.text.chars().count()))));
+                    output.state.store(ui.ctx(), output.response.id);
+                }
+            });
+            // The best match is picked again whenever the search changes.
+            if self.text != typed {
+                self.selected = 0;
+                self.recalled = None;
+            }
+            ui.add_space(6.);
+            let font = FontId::new(11., FontFamily::Monospace);
+            let query = self.text.trim();
+            if let Some(command) = self.text.strip_prefix('>') {
+                let (text, color) = match hint(command, &self.history, keymap, &self.scripts, ctx) {
+                    Hint::Usage(text) => (text, theme.command_palette_text),
+                    Hint::Invalid(text) => (text, theme.command_palette_invalid_text),
+                };
+                frame.show(ui, |ui| ui.label(RichText::new(text).font(font).color(color)));
+                // Invalid commands stay in the palette to be fixed, as the hint says what's wrong with them.
+                if let Some(parsed) = parse(command, &self.scripts).ok().filter(|_| entered) {
+                    self.history.add(&split(command).0, command.trim());
+                    action = Some(Action::Run(parsed));
+                }
+            } else if query.is_empty() {
+                if entered {
+                    self.toggle();
+                }
+            } else {
+                let found = browser.quick_open(ui.ctx(), query);
+                self.selected = self.selected.min(found.len().saturating_sub(1));
+                frame.show(ui, |ui| {
+                    ui.spacing_mut().item_spacing.y = 0.;
+                    for (index, (path, highlights)) in found.iter().enumerate() {
+                        let mut job = quick_open_job(path, highlights, &font, theme);
+                        job.wrap = TextWrapping::truncate_at_width(ui.available_width());
+                        let galley = ui.painter().layout_job(job);
+                        let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), galley.size().y + 4.), Sense::click());
+                        if index == self.selected || response.hovered() {
+                            ui.painter().rect_filled(rect.expand2(vec2(6., 0.)), 4., highlight);
+                        }
+                        ui.painter().galley(pos2(rect.left(), rect.center().y - galley.size().y / 2.), galley, theme.command_palette_text);
+                        // Shift puts the file in the project, while otherwise it's only listened to.
+                        if response.clicked() || (entered && index == self.selected) {
+                            let path = path.to_path_buf();
+                            action = Some(if shift { Action::AddAtPlayhead(path) } else { Action::Preview(path) });
+                        }
+                    }
+                    let footer = if found.is_empty() { "No files found" } else { "Enter to preview, Shift+Enter to add at the playhead" };
+                    ui.add_space(4.);
+                    ui.label(RichText::new(footer).font(font).color(theme.command_palette_placeholder_text));
+                });
+            }
+        });
+        // The palette is modal, so keys meant for it shouldn't also edit whatever is behind it.
+        ctx.input_mut(|i| i.events.retain(|event| !matches!(event, Event::Key { .. })));
+        if action.is_some() {
+            self.toggle();
+        }
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Command};
+
+    #[test]
+    fn only_the_scripts_listed_can_be_run() {
+        let scripts = ["tidy".to_string()];
+        assert_eq!(parse("script tidy", &scripts), Ok(Command::Script("tidy".into())));
+        assert!(parse("script missing", &scripts).is_err());
+        assert_eq!(parse("lua print(1)", &scripts), Ok(Command::Lua("print(1)".into())));
+    }
+}
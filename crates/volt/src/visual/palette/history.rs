@@ -0,0 +1,47 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Where the commands run are kept between sessions.
+const PATH: &str = "commands.toml";
+/// How many commands are remembered, after which the oldest are forgotten.
+const MAX: usize = 50;
+
+/// The commands run from the palette, to rank the commands used most and recall the ones run lately.
+#[derive(Default, Serialize, Deserialize)]
+pub struct History {
+    /// The commands as they were typed, most recent first.
+    recent: Vec<String>,
+    /// How many times each command was run, by name.
+    uses: BTreeMap<String, u32>,
+}
+
+impl History {
+    /// Return the commands run in previous sessions.
+    pub fn load() -> Self {
+        config::load(PATH, "the command history").unwrap_or_default()
+    }
+
+    /// Remember that `text` was run as the command called `name`, and save the history.
+    pub fn add(&mut self, name: &str, text: &str) {
+        *self.uses.entry(name.to_string()).or_default() += 1;
+        self.recent.retain(|other| other != text);
+        self.recent.insert(0, text.to_string());
+        self.recent.truncate(MAX);
+        config::save(PATH, self, "the command history");
+    }
+
+    /// Return the command run `index` commands ago, as it was typed.
+    pub fn recent(&self, index: usize) -> Option<&str> {
+        self.recent.get(index).map(String::as_str)
+    }
+
+    /// Return how a command ranks against others, where lower comes first: the most used, then the most recently used.
+    pub fn rank(&self, name: &str) -> (Reverse<u32>, usize) {
+        let uses = self.uses.get(name).copied().unwrap_or_default();
+        let last_used = self.recent.iter().position(|text| text.split_whitespace().next().is_some_and(|first| first.eq_ignore_ascii_case(name)));
+        (Reverse(uses), last_used.unwrap_or(usize::MAX))
+    }
+}
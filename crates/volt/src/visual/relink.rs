@@ -0,0 +1,212 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use blerp::processing::overview::overview;
+use cpal::Sample;
+use crossbeam_channel::Receiver;
+use egui::{Button, Context, RichText, ScrollArea, Window};
+use itertools::Itertools;
+use rodio::{Decoder, Source};
+
+use super::{central::FileReference, dialog::pick_folder};
+use crate::{
+    archive,
+    tasks::{self, Job, Kind},
+};
+
+/// How many stretches of audio a fingerprint measures.
+const FINGERPRINT_WIDTH: usize = 64;
+/// How far apart the shapes of two fingerprints can be, on average, for them to be the same audio.
+const FINGERPRINT_TOLERANCE: f64 = 0.05;
+
+/// A window to find the files of a project that were moved, by searching folders for files with the same names, or the same audio if it's known.
+pub struct Relink {
+    missing: Vec<FileReference>,
+    /// Where each missing file was found.
+    found: HashMap<PathBuf, PathBuf>,
+    /// The folders searched, along with the folders in them.
+    folders: Vec<PathBuf>,
+    /// Whether files are also compared by their audio, which finds renamed files but has to decode every audio file in the folders.
+    fingerprints: bool,
+    searching: Option<Receiver<HashMap<PathBuf, PathBuf>>>,
+    /// The folder dialog being shown to add a folder, if there is one.
+    picking_folder: Option<Receiver<Option<PathBuf>>>,
+    pub open: bool,
+}
+
+/// The shape of some audio, to find it again under another name.
+struct Fingerprint {
+    frames: usize,
+    shape: Vec<f64>,
+}
+
+impl Fingerprint {
+    fn new(samples: &[f64], channels: u16) -> Self {
+        let channels = usize::from(channels).max(1);
+        let peaks = overview(samples, channels, FINGERPRINT_WIDTH);
+        let loudest = peaks.iter().map(|peak| peak.max - peak.min).fold(f64::EPSILON, f64::max);
+        Self {
+            frames: samples.len() / channels,
+            shape: peaks.iter().map(|peak| (peak.max - peak.min) / loudest).collect(),
+        }
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let decoder = Decoder::new(archive::open(path).ok()?).ok()?;
+        let channels = decoder.channels();
+        Some(Self::new(&decoder.map(f64::from_sample).collect_vec(), channels))
+    }
+
+    /// Return how far apart the shapes of the fingerprints are, or [`None`] if they're too different in length to be the same audio.
+    #[allow(clippy::cast_precision_loss, reason = "lengths are well within range")]
+    fn distance(&self, other: &Self) -> Option<f64> {
+        if self.frames.abs_diff(other.frames) as f64 > self.frames as f64 * 0.01 || self.shape.len() != other.shape.len() {
+            return None;
+        }
+        Some(self.shape.iter().zip(&other.shape).map(|(a, b)| (a - b).abs()).sum::<f64>() / self.shape.len().max(1) as f64)
+    }
+}
+
+impl Relink {
+    /// Return a window to relink the `missing` files, searching `folders` by default.
+    pub fn new(missing: Vec<FileReference>, folders: &[PathBuf]) -> Self {
+        Self {
+            missing,
+            found: HashMap::new(),
+            // Searching the whole file system would take too long.
+            folders: folders.iter().filter(|folder| folder.parent().is_some()).cloned().collect(),
+            fingerprints: false,
+            searching: None,
+            picking_folder: None,
+            open: true,
+        }
+    }
+
+    /// Show the window, returning where files were found once the user relinks them.
+    pub fn show(&mut self, ctx: &Context) -> Option<HashMap<PathBuf, PathBuf>> {
+        if let Some(Ok(found)) = self.searching.as_ref().map(Receiver::try_recv) {
+            self.searching = None;
+            self.found = found;
+        }
+        if let Some(Ok(picked)) = self.picking_folder.as_ref().map(Receiver::try_recv) {
+            self.picking_folder = None;
+            self.folders.extend(picked.filter(|folder| !self.folders.contains(folder)));
+        }
+        let mut relinked = None;
+        let mut open = self.open;
+        Window::new("Missing files").open(&mut open).default_width(420.).show(ctx, |ui| {
+            ui.label(format!("{} file(s) used by the project can't be found.", self.missing.len()));
+            ScrollArea::vertical().id_salt("missing").max_height(200.).auto_shrink([false, true]).show(ui, |ui| {
+                for reference in &self.missing {
+                    ui.label(reference.path.display().to_string());
+                    match self.found.get(&reference.path) {
+                        Some(found) => ui.label(RichText::new(format!("→ {}", found.display())).weak()),
+                        None => ui.label(RichText::new("Not found").weak()),
+                    };
+                }
+            });
+            ui.separator();
+            ui.label("Search in");
+            let mut removed = None;
+            for (index, folder) in self.folders.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("🗙").on_hover_text("Don't search this folder").clicked() {
+                        removed = Some(index);
+                    }
+                    ui.label(folder.display().to_string());
+                });
+            }
+            if let Some(index) = removed {
+                self.folders.remove(index);
+            }
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.picking_folder.is_none(), Button::new("Add folder…")).clicked() {
+                    self.picking_folder = Some(pick_folder());
+                }
+                ui.checkbox(&mut self.fingerprints, "Compare audio")
+                    .on_hover_text("Also find files that were renamed by comparing their audio with the audio of clips, which is slower");
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                let searching = self.searching.is_some();
+                if ui.add_enabled(!searching && !self.folders.is_empty(), Button::new("Search")).clicked() {
+                    self.searching = Some(self.search());
+                }
+                if searching {
+                    ui.spinner();
+                }
+                if ui.add_enabled(!self.found.is_empty(), Button::new(format!("Relink {} file(s)", self.found.len()))).clicked() {
+                    relinked = Some(self.found.clone());
+                    self.missing.retain(|reference| !self.found.contains_key(&reference.path));
+                    self.found.clear();
+                }
+            });
+        });
+        self.open = open && !self.missing.is_empty();
+        relinked
+    }
+
+    /// Search the folders for the missing files on another thread, sending where they were found.
+    fn search(&self) -> Receiver<HashMap<PathBuf, PathBuf>> {
+        let folders = self.folders.clone();
+        let fingerprints = self.fingerprints;
+        let missing = self
+            .missing
+            .iter()
+            .map(|reference| {
+                let fingerprint = reference.audio.as_ref().filter(|_| fingerprints).map(|(samples, channels)| Fingerprint::new(samples, *channels));
+                (reference.path.clone(), fingerprint)
+            })
+            .collect_vec();
+        tasks::spawn(Job::new(Kind::Relink, "Searching for the files of the project"), move || {
+            let files = folders.iter().flat_map(|folder| files_in(folder)).unique().collect_vec();
+            let audio = if fingerprints {
+                files.iter().filter_map(|file| Some((file, Fingerprint::read(file)?))).collect_vec()
+            } else {
+                Vec::new()
+            };
+            missing
+                .into_iter()
+                .filter_map(|(path, fingerprint)| {
+                    let name = path.file_name()?;
+                    let named = files.iter().filter(|file| file.file_name().is_some_and(|other| other.eq_ignore_ascii_case(name))).collect_vec();
+                    let Some(fingerprint) = fingerprint else {
+                        return named.first().map(|file| (path.clone(), (*file).clone()));
+                    };
+                    // Files with the same name are preferred, as long as their audio matches.
+                    let closest = audio
+                        .iter()
+                        .filter_map(|(file, other)| Some((*file, fingerprint.distance(other)?)))
+                        .filter(|(_, distance)| *distance <= FINGERPRINT_TOLERANCE)
+                        .min_by(|(a, a_distance), (b, b_distance)| named.contains(b).cmp(&named.contains(a)).then(a_distance.total_cmp(b_distance)));
+                    closest.or_else(|| named.first().map(|file| (*file, 0.))).map(|(file, _)| (path.clone(), file.clone()))
+                })
+                .collect()
+        })
+    }
+}
+
+/// Return every file in `folder` and the folders in it, leaving out hidden ones.
+fn files_in(folder: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = fs::read_dir(&folder) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => folders.push(entry.path()),
+                Ok(kind) if kind.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    files
+}
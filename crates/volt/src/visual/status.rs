@@ -1,10 +1,35 @@
+use std::time::Duration;
+
 use eframe::egui;
-use egui::{include_image, Color32, FontFamily, Image, Label, Margin, RichText, TextureOptions, Ui, Vec2, Widget};
+use egui::{popup_above_or_below_widget, vec2, AboveOrBelow, Align, Button, Color32, FontFamily, Grid, Label, Layout, Margin, PopupCloseBehavior, ProgressBar, RichText, ScrollArea, Sense, Spinner, TextureOptions, Ui, Widget};
+
+use super::{notification::NotificationDrawer, ThemeColors};
+use crate::{config, tasks::{self, Task}, timings};
 
-use super::ThemeColors;
+/// The color of the text of the status bar.
+const TEXT: Color32 = Color32::from_rgb(0x77, 0x74, 0x90);
+/// The audio load past which the CPU meter turns yellow, then red, since the audio is close to dropping out.
+const LOAD_WARNING: f64 = 0.7;
+const LOAD_CRITICAL: f64 = 0.9;
+
+/// What the status bar shows about the project and the audio engine, as of this frame.
+pub struct Status {
+    /// Where playback is, as a bar and beat counted from 1, and in time.
+    pub position: ((u32, u32), Duration),
+    /// The sample rate of the output device and how many frames it asks for at a time once it did, or [`None`] if there is no output device.
+    pub format: Option<(u32, Option<usize>)>,
+    /// How full the audio callbacks filled their buffers' time on average and at worst, if any ran yet, see [`timings::audio_load`].
+    pub load: Option<(f64, f64)>,
+    /// How many decoded files and rendered tracks the engine keeps in memory, and how many bytes they take.
+    pub cache: Option<(usize, usize)>,
+    pub selection: Option<String>,
+    /// The jobs running in the background or waiting to, see [`crate::tasks`].
+    pub tasks: Vec<Task>,
+}
 
-pub fn status(themes: &ThemeColors) -> impl Widget + use<'_> {
-    |ui: &mut Ui| {
+/// Show the status bar with `info` at its left, and the bell of `notifications` and the level meter of the master output in `master` at its right.
+pub fn status<'a>(themes: &'a ThemeColors, info: Status, master: impl Widget + 'a, notifications: &'a mut NotificationDrawer) -> impl Widget + 'a {
+    move |ui: &mut Ui| {
         let navbar_texture_image = super::build_gradient(20, themes.navbar_background_gradient_bottom, themes.navbar_background_gradient_top);
         let navbar_texture = ui.ctx().load_texture("navbar_texture", navbar_texture_image, TextureOptions::default());
 
@@ -14,22 +39,123 @@ pub fn status(themes: &ThemeColors) -> impl Widget + use<'_> {
             egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
             egui::Color32::WHITE,
         );
-        // ui.painter().line(
-        //     vec![ui.available_rect_before_wrap().left_top(), ui.available_rect_before_wrap().right_top()],
-        //     egui::Stroke::new(1.0, Color32::from_hex("#353248").unwrap()),
-        // );
         ui.horizontal(|ui| {
-            egui::Frame::default().show(ui, |ui| {
+            egui::Frame::none().inner_margin(Margin::symmetric(5., 0.)).show(ui, |ui| {
                 ui.horizontal(|ui| {
-                    egui::Frame::none().show(ui, |ui| {
-                        ui.style_mut().spacing.item_spacing = Vec2::ZERO;
-                        egui::Frame::none().inner_margin(Margin::same(5.)).show(ui, |ui| {
-                            ui.add(Label::new(RichText::new("Volt v1.0.0").family(FontFamily::Proportional).color(Color32::from_hex("#777490").unwrap())).selectable(false));
-                        });
-                    });
-                })
-            })
+                    ui.spacing_mut().item_spacing = vec2(12., 0.);
+                    add_info(ui, &info);
+                });
+            });
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                ui.add_space(5.);
+                ui.add(master);
+                ui.add(Label::new(RichText::new("Master").color(TEXT)).selectable(false));
+                ui.add_space(8.);
+                notifications.bell(ui);
+                add_tasks(ui, &info.tasks);
+            });
         })
         .response
     }
 }
+
+fn add_info(ui: &mut Ui, info: &Status) {
+    let text = |text: String| Label::new(RichText::new(text).family(FontFamily::Monospace).color(TEXT)).selectable(false);
+    let ((bar, beat), time) = info.position;
+    ui.add(text(format!("{bar}:{beat}"))).on_hover_text("Bar and beat");
+    ui.add(text(super::format_time(time))).on_hover_text("Time");
+    match info.format {
+        Some((sample_rate, buffer)) => {
+            let rate = format!("{:.1} kHz", f64::from(sample_rate) / 1000.);
+            #[allow(clippy::cast_precision_loss, reason = "buffer sizes are small")]
+            let format = buffer.map_or_else(|| rate.clone(), |frames| format!("{rate}, {frames} samples ({:.1} ms)", frames as f64 * 1000. / f64::from(sample_rate)));
+            ui.add(text(format)).on_hover_text("The sample rate of the output device and how many samples it asks for at a time");
+        }
+        None => {
+            ui.add(text("No audio device".into()));
+        }
+    }
+    add_load(ui, info.load);
+    if let Some((count, bytes)) = info.cache {
+        ui.add(text(format!("Cache {}", timings::format_bytes(u64::try_from(bytes).unwrap_or(u64::MAX)))))
+            .on_hover_text(format!("{count} decoded audio file(s) and rendered track(s) kept in memory"));
+    }
+    if let Some(selection) = &info.selection {
+        ui.add(text(selection.clone()));
+    }
+}
+
+/// Show how much of the time the audio device gives the callbacks they take, as a bar.
+fn add_load(ui: &mut Ui, load: Option<(f64, f64)>) {
+    ui.add(Label::new(RichText::new("CPU").family(FontFamily::Monospace).color(TEXT)).selectable(false));
+    let (rect, response) = ui.allocate_exact_size(vec2(48., 8.), Sense::hover());
+    ui.painter().rect_filled(rect, 2., Color32::from_black_alpha(0x60));
+    let Some((average, worst)) = load else {
+        response.on_hover_text("Nothing was played yet");
+        return;
+    };
+    let color = if worst >= LOAD_CRITICAL {
+        Color32::from_rgb(0xe0, 0x55, 0x55)
+    } else if worst >= LOAD_WARNING {
+        Color32::from_rgb(0xe3, 0xb3, 0x41)
+    } else {
+        Color32::from_rgb(0x5f, 0xb3, 0x6f)
+    };
+    #[allow(clippy::cast_possible_truncation, reason = "only used for drawing")]
+    let width = |load: f64| rect.width() * load.clamp(0., 1.) as f32;
+    ui.painter().rect_filled(rect.with_max_x(rect.left() + width(average)), 2., color);
+    ui.painter().vline(rect.left() + width(worst), rect.y_range(), (1., color));
+    response.on_hover_text(format!(
+        "The audio takes {:.0}% of the time it has on average, and {:.0}% at worst. It drops out past 100%.",
+        average * 100.,
+        worst * 100.
+    ));
+}
+
+/// Show how many jobs are running in the background, which lists them when clicked, with a button to cancel those that can be.
+fn add_tasks(ui: &mut Ui, tasks: &[Task]) {
+    if tasks.is_empty() {
+        return;
+    }
+    let running = tasks.iter().filter(|task| task.running.is_some()).count();
+    let response = ui.add(Button::new(RichText::new(format!("{} task(s)", tasks.len())).family(FontFamily::Monospace).color(TEXT)).frame(false));
+    if !config::reduced_motion() {
+        ui.add(Spinner::new().size(12.).color(TEXT));
+    }
+    let popup_id = response.id.with("tasks");
+    if response.clicked() {
+        ui.memory_mut(|memory| memory.toggle_popup(popup_id));
+    }
+    popup_above_or_below_widget(ui, popup_id, &response, AboveOrBelow::Above, PopupCloseBehavior::CloseOnClickOutside, |ui| {
+        ui.set_min_width(360.);
+        ui.label(format!("{running} running, {} waiting", tasks.len() - running));
+        ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+            Grid::new("tasks").num_columns(3).striped(true).show(ui, |ui| {
+                for task in tasks {
+                    ui.label(&task.description).on_hover_text(format!("{}, {:?} priority", task.kind.name(), task.priority));
+                    match (task.running, task.progress.fraction()) {
+                        (Some(_), Some(fraction)) => {
+                            #[allow(clippy::cast_possible_truncation, reason = "the fraction is between 0 and 1")]
+                            ui.add(ProgressBar::new(fraction as f32).desired_width(100.).show_percentage());
+                        }
+                        (Some(running), None) => {
+                            ui.weak(format!("Running for {} s", running.as_secs()));
+                        }
+                        (None, _) => {
+                            ui.weak("Waiting");
+                        }
+                    }
+                    if task.cancellable && !task.progress.is_cancelled() {
+                        if ui.small_button("Cancel").clicked() {
+                            tasks::cancel(&task.progress);
+                        }
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    });
+    response.on_hover_text("Jobs running in the background");
+}
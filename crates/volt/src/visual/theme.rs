@@ -0,0 +1,112 @@
+//! Themes kept as TOML files in the `themes` folder of the configuration folder, which set the colors of [`ThemeColors`] and are reloaded when they change.
+//!
+//! Colors are written like `"#1e2132"`, or `"#07081580"` with an alpha. Colors left out of a theme are the default ones.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crossbeam_channel::{unbounded, Receiver};
+use itertools::Itertools;
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher as _};
+use tracing::error;
+
+use super::ThemeColors;
+use crate::config;
+
+/// The folder themes are kept in, in the configuration folder.
+const FOLDER: &str = "themes";
+/// The theme written with the default colors when the folder is created, to start new themes from.
+const EXAMPLE: &str = "example";
+
+/// Serializes colors as hex strings.
+pub mod hex {
+    use egui::Color32;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref, reason = "serde passes fields by reference")]
+    pub fn serialize<S: Serializer>(color: &Color32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&color.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color32, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Color32::from_hex(&text).map_err(|_| D::Error::custom(format!("\"{text}\" isn't a color like \"#1e2132\"")))
+    }
+}
+
+/// Return the folder themes are kept in, creating it along with an example theme if it doesn't exist yet.
+pub fn folder() -> PathBuf {
+    let folder = config::path(FOLDER);
+    if !folder.exists() {
+        let example = toml::to_string(&ThemeColors::default()).unwrap_or_default();
+        if let Err(error) = fs::create_dir_all(&folder).and_then(|()| fs::write(path(&folder, EXAMPLE), example)) {
+            error!("Couldn't create the themes folder: {error}");
+        }
+    }
+    folder
+}
+
+fn path(folder: &Path, name: &str) -> PathBuf {
+    folder.join(format!("{name}.toml"))
+}
+
+/// Return the names of the themes there are, in alphabetical order.
+pub fn available() -> Vec<String> {
+    match fs::read_dir(folder()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .sorted()
+            .collect(),
+        Err(error) => {
+            error!("Couldn't read the themes folder: {error}");
+            Vec::new()
+        }
+    }
+}
+
+/// Read the theme called `name`.
+///
+/// # Errors
+///
+/// Returns why the theme couldn't be read, to be shown to the user.
+pub fn load(name: &str) -> Result<ThemeColors, String> {
+    match fs::read_to_string(path(&folder(), name)) {
+        Ok(text) => toml::from_str(&text).map_err(|error| error.message().to_string()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Err("it doesn't exist".into()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Watches the themes folder, to reload the theme in use when it's edited.
+pub struct Watcher {
+    /// Kept for as long as the folder is watched, or [`None`] if it can't be.
+    _handle: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<Event>>,
+    folder: PathBuf,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        let (tx, events) = unbounded();
+        let folder = folder();
+        let watcher = recommended_watcher(tx)
+            .and_then(|mut watcher| watcher.watch(&folder, RecursiveMode::NonRecursive).map(|()| watcher))
+            .inspect_err(|error| error!("Couldn't watch the themes folder: {error}"))
+            .ok();
+        Self { _handle: watcher, events, folder }
+    }
+
+    /// Return whether the theme called `name` changed since the last call, which is never the case if there is no name.
+    pub fn changed(&self, name: Option<&str>) -> bool {
+        let path = path(&self.folder, name.unwrap_or_default());
+        // Every event is taken, so that they don't pile up while another theme is used. Paths are compared by name, as the system may give them another way.
+        let events = self.events.try_iter().flatten().collect_vec();
+        name.is_some() && events.iter().filter(|event| !event.kind.is_access()).any(|event| event.paths.iter().any(|other| other.file_name() == path.file_name()))
+    }
+}